@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use rabbitmq_stream_client::{
+    test_broker::TestBroker,
+    types::{Compression, Message},
+    Environment, Producer,
+};
+use tokio::{runtime::Runtime, sync::mpsc};
+
+const MESSAGES: usize = 1000;
+const BODY_SIZE: usize = 256;
+
+fn messages() -> Vec<Message> {
+    (0..MESSAGES)
+        .map(|_| Message::builder().body(vec![b'x'; BODY_SIZE]).build())
+        .collect()
+}
+
+/// Producer on a fresh in-memory broker, dropped with the messages it stored
+async fn producer(sub_entry_size: usize) -> (Environment, Producer) {
+    let environment = Environment::builder()
+        .test_broker(TestBroker::new())
+        .build()
+        .await
+        .unwrap();
+    environment.stream_creator().create("bench").await.unwrap();
+    let mut builder = environment.producer().batch_size(100);
+    if sub_entry_size > 1 {
+        builder = builder
+            .sub_entry_size(sub_entry_size)
+            .compression(Compression::Gzip);
+    }
+    let producer = builder.build("bench").await.unwrap();
+    (environment, producer)
+}
+
+/// Time publishing the messages with `send` until they are all confirmed
+async fn send(producer: &Producer, messages: Vec<Message>) -> Duration {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    for message in messages {
+        let tx = tx.clone();
+        producer
+            .send(message, move |status| async move {
+                let _ = tx.send(status.is_ok());
+            })
+            .await
+            .unwrap();
+    }
+    producer.flush().await.unwrap();
+    for _ in 0..MESSAGES {
+        assert_eq!(Some(true), rx.recv().await);
+    }
+    start.elapsed()
+}
+
+/// Time publishing the messages with `batch_send` until they are all confirmed
+async fn batch_send(producer: &Producer, messages: Vec<Message>) -> Duration {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let start = Instant::now();
+    producer
+        .batch_send(messages, move |status| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(status.is_ok());
+            }
+        })
+        .await
+        .unwrap();
+    for _ in 0..MESSAGES {
+        assert_eq!(Some(true), rx.recv().await);
+    }
+    start.elapsed()
+}
+
+fn producer_benchmarks(c: &mut Criterion) {
+    let runtime = &Runtime::new().unwrap();
+    let mut group = c.benchmark_group("producer");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+
+    // a fresh broker for every iteration, it keeps every message published
+    let bench = |sub_entry_size: usize, batched: bool| {
+        move |iters: u64| {
+            runtime.block_on(async {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let (environment, producer) = producer(sub_entry_size).await;
+                    let messages = messages();
+                    elapsed += if batched {
+                        batch_send(&producer, messages).await
+                    } else {
+                        send(&producer, messages).await
+                    };
+                    producer.close().await.unwrap();
+                    drop(environment);
+                }
+                elapsed
+            })
+        }
+    };
+    group.bench_function("send", |b| b.iter_custom(bench(1, false)));
+    group.bench_function("batch_send", |b| b.iter_custom(bench(1, true)));
+    group.bench_function("send_gzip_sub_entries", |b| b.iter_custom(bench(10, false)));
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = producer_benchmarks
+}
+criterion_main!(benches);
@@ -34,7 +34,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     for i in 0..message_count {
         producer
-            .send(Message::builder().body(format!("message{}", i)).build())
+            .send(
+                Message::builder().body(format!("message{}", i)).build(),
+                |confirmation| async move {
+                    info!("Got confirmation : {:?}", confirmation);
+                },
+            )
             .await?;
     }
 
@@ -0,0 +1,395 @@
+//! Throughput and latency test of a RabbitMQ stream setup, in the spirit of the Java
+//! stream PerfTest
+//!
+//! ```text
+//! cargo run --release --example stream-perf -- --producers 2 --consumers 2 --size 1000
+//! cargo run --release --example stream-perf -- --rate 10000 --filter-values eu,us --time 60
+//! ```
+//!
+//! The producers write the time they publish a message in its first 8 bytes, the
+//! consumers compute the latency from it: run the tool on a single host or with
+//! synchronized clocks.
+
+mod common;
+
+use std::{
+    convert::TryInto,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::{Parser, ValueEnum};
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    error::StreamCreateError,
+    types::{Compression, Message, OffsetSpecification, ResponseCode, Value},
+    Environment,
+};
+use tokio::{sync::watch, time::Instant};
+
+const FILTER_PROPERTY: &str = "filter";
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "stream-perf",
+    about = "Throughput and latency test of RabbitMQ streams"
+)]
+struct Args {
+    /// URI of the node, `rabbitmq-stream+tls://` for TLS
+    #[arg(long, default_value = common::DEFAULT_URI)]
+    uri: String,
+    /// Streams to publish to and consume from, created when missing
+    #[arg(long, value_delimiter = ',', default_value = "stream-perf")]
+    streams: Vec<String>,
+    /// Number of producers, spread over the streams
+    #[arg(long, default_value_t = 1)]
+    producers: usize,
+    /// Number of consumers, spread over the streams
+    #[arg(long, default_value_t = 1)]
+    consumers: usize,
+    /// Size of the message bodies in bytes, at least 8 for the timestamp
+    #[arg(long, default_value_t = 100)]
+    size: usize,
+    /// Maximum messages per second of each producer, no limit with 0
+    #[arg(long, default_value_t = 0)]
+    rate: u64,
+    /// Messages accumulated before a publish frame is sent
+    #[arg(long, default_value_t = 100)]
+    batch_size: usize,
+    /// Messages packed in a sub-entry, no sub-entries with 1
+    #[arg(long, default_value_t = 1)]
+    sub_entry_size: usize,
+    /// Compression of the sub-entries
+    #[arg(long, value_enum, default_value = "none")]
+    compression: CompressionArg,
+    /// Messages waiting for a confirmation before the producers block
+    #[arg(long, default_value_t = 10_000)]
+    max_in_flight: usize,
+    /// Filter values set in turn on the messages, the consumers subscribe to all of them
+    #[arg(long, value_delimiter = ',')]
+    filter_values: Vec<String>,
+    /// Where the consumers start in the streams
+    #[arg(long, value_enum, default_value = "next")]
+    offset: OffsetArg,
+    /// Duration of the test in seconds, until interrupted with 0
+    #[arg(long, default_value_t = 0)]
+    time: u64,
+    /// Delete the streams at the end of the test
+    #[arg(long)]
+    delete_streams: bool,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum CompressionArg {
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl From<CompressionArg> for Compression {
+    fn from(compression: CompressionArg) -> Self {
+        match compression {
+            CompressionArg::None => Compression::None,
+            CompressionArg::Gzip => Compression::Gzip,
+            CompressionArg::Snappy => Compression::Snappy,
+            CompressionArg::Lz4 => Compression::Lz4,
+            CompressionArg::Zstd => Compression::Zstd,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OffsetArg {
+    First,
+    Last,
+    Next,
+}
+
+impl From<OffsetArg> for OffsetSpecification {
+    fn from(offset: OffsetArg) -> Self {
+        match offset {
+            OffsetArg::First => OffsetSpecification::First,
+            OffsetArg::Last => OffsetSpecification::Last,
+            OffsetArg::Next => OffsetSpecification::Next,
+        }
+    }
+}
+
+/// Counters of the producers and consumers, the interval ones are reset at each printout
+#[derive(Default)]
+struct Stats {
+    published: AtomicU64,
+    confirmed: AtomicU64,
+    errored: AtomicU64,
+    consumed: AtomicU64,
+    latency_sum: AtomicU64,
+    latency_count: AtomicU64,
+    latency_max: AtomicU64,
+}
+
+impl Stats {
+    fn record_latency(&self, micros: u64) {
+        self.latency_sum.fetch_add(micros, Ordering::Relaxed);
+        self.latency_count.fetch_add(1, Ordering::Relaxed);
+        self.latency_max.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn totals(&self) -> [u64; 4] {
+        [
+            self.published.load(Ordering::Relaxed),
+            self.confirmed.load(Ordering::Relaxed),
+            self.errored.load(Ordering::Relaxed),
+            self.consumed.load(Ordering::Relaxed),
+        ]
+    }
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if args.size < 8 {
+        return Err("the size must be at least 8 bytes".into());
+    }
+    let environment = common::environment_builder(&args.uri)?.build().await?;
+    for stream in &args.streams {
+        match environment.stream_creator().create(stream).await {
+            Err(StreamCreateError::Create {
+                status: ResponseCode::StreamAlreadyExists,
+                ..
+            }) => {}
+            result => result?,
+        }
+    }
+
+    let stats = Arc::new(Stats::default());
+    let (stop, stopped) = watch::channel(false);
+    let mut tasks = Vec::new();
+    for n in 0..args.consumers {
+        let stream = args.streams[n % args.streams.len()].clone();
+        tasks.push(tokio::spawn(consume(
+            environment.clone(),
+            stream,
+            args.offset.into(),
+            args.filter_values.clone(),
+            stats.clone(),
+            stopped.clone(),
+        )));
+    }
+    for n in 0..args.producers {
+        let stream = args.streams[n % args.streams.len()].clone();
+        tasks.push(tokio::spawn(publish(
+            environment.clone(),
+            stream,
+            ProducerSettings {
+                size: args.size,
+                rate: args.rate,
+                batch_size: args.batch_size,
+                sub_entry_size: args.sub_entry_size,
+                compression: args.compression.into(),
+                max_in_flight: args.max_in_flight,
+                filter_values: args.filter_values.clone(),
+            },
+            stats.clone(),
+            stopped.clone(),
+        )));
+    }
+
+    let started = Instant::now();
+    let deadline = async {
+        match args.time {
+            0 => futures::future::pending().await,
+            time => tokio::time::sleep(Duration::from_secs(time)).await,
+        }
+    };
+    tokio::select! {
+        _ = print_stats(&stats, started) => {}
+        _ = deadline => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    let _ = stop.send(true);
+    for task in tasks {
+        if let Err(err) = task.await? {
+            eprintln!("{}", err);
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    let [published, confirmed, errored, consumed] = stats.totals();
+    println!(
+        "Summary: published {} ({:.0} msg/s), confirmed {} ({:.0} msg/s), errored {}, consumed {} ({:.0} msg/s)",
+        published,
+        published as f64 / elapsed,
+        confirmed,
+        confirmed as f64 / elapsed,
+        errored,
+        consumed,
+        consumed as f64 / elapsed,
+    );
+
+    if args.delete_streams {
+        for stream in &args.streams {
+            environment.delete_stream(stream).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the rates of the last second, every second
+async fn print_stats(stats: &Stats, started: Instant) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    interval.tick().await;
+    let mut previous = stats.totals();
+    let mut last = Instant::now();
+    loop {
+        interval.tick().await;
+        let totals = stats.totals();
+        let elapsed = last.elapsed().as_secs_f64();
+        last = Instant::now();
+        let rate = |n: usize| (totals[n] - previous[n]) as f64 / elapsed;
+        let count = stats.latency_count.swap(0, Ordering::Relaxed);
+        let sum = stats.latency_sum.swap(0, Ordering::Relaxed);
+        let max = stats.latency_max.swap(0, Ordering::Relaxed);
+        let latency = match count {
+            0 => "-".to_owned(),
+            count => format!(
+                "{:.3} ms avg, {:.3} ms max",
+                sum as f64 / count as f64 / 1000.0,
+                max as f64 / 1000.0
+            ),
+        };
+        println!(
+            "{}s, published {:.0} msg/s, confirmed {:.0} msg/s, consumed {:.0} msg/s, latency {}",
+            started.elapsed().as_secs(),
+            rate(0),
+            rate(1),
+            rate(3),
+            latency
+        );
+        previous = totals;
+    }
+}
+
+struct ProducerSettings {
+    size: usize,
+    rate: u64,
+    batch_size: usize,
+    sub_entry_size: usize,
+    compression: Compression,
+    max_in_flight: usize,
+    filter_values: Vec<String>,
+}
+
+async fn publish(
+    environment: Environment,
+    stream: String,
+    settings: ProducerSettings,
+    stats: Arc<Stats>,
+    mut stopped: watch::Receiver<bool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut builder = environment
+        .producer()
+        .batch_size(settings.batch_size)
+        .max_in_flight(settings.max_in_flight);
+    if settings.sub_entry_size > 1 {
+        builder = builder
+            .sub_entry_size(settings.sub_entry_size)
+            .compression(settings.compression);
+    }
+    if !settings.filter_values.is_empty() {
+        builder = builder.filter_value_extractor(|message| {
+            match message.application_property(FILTER_PROPERTY) {
+                Some(Value::String(value)) => value,
+                _ => String::new(),
+            }
+        });
+    }
+    let producer = builder.build(&stream).await?;
+
+    let started = Instant::now();
+    let mut sent: u64 = 0;
+    while !*stopped.borrow() {
+        if settings.rate > 0 {
+            let due = started + Duration::from_secs_f64(sent as f64 / settings.rate as f64);
+            tokio::select! {
+                _ = tokio::time::sleep_until(due) => {}
+                _ = stopped.changed() => break,
+            }
+        }
+        let mut body = vec![0; settings.size];
+        body[..8].copy_from_slice(&now_nanos().to_be_bytes());
+        let mut message = Message::builder().body(body);
+        if !settings.filter_values.is_empty() {
+            let value = &settings.filter_values[sent as usize % settings.filter_values.len()];
+            message = message.application_property(FILTER_PROPERTY, value.as_str());
+        }
+        let confirmations = stats.clone();
+        producer
+            .send(message.build(), move |status| async move {
+                match status {
+                    Ok(status) if status.confirmed() => &confirmations.confirmed,
+                    _ => &confirmations.errored,
+                }
+                .fetch_add(1, Ordering::Relaxed);
+            })
+            .await?;
+        stats.published.fetch_add(1, Ordering::Relaxed);
+        sent += 1;
+    }
+    producer.close().await?;
+    Ok(())
+}
+
+async fn consume(
+    environment: Environment,
+    stream: String,
+    offset: OffsetSpecification,
+    filter_values: Vec<String>,
+    stats: Arc<Stats>,
+    mut stopped: watch::Receiver<bool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut builder = environment.consumer().offset(offset);
+    if !filter_values.is_empty() {
+        let values = filter_values.clone();
+        builder = builder.filter_values(filter_values, move |message| {
+            matches!(
+                message.application_property(FILTER_PROPERTY),
+                Some(Value::String(value)) if values.contains(&value)
+            )
+        });
+    }
+    let mut consumer = builder.build(&stream).await?;
+    loop {
+        let delivery = tokio::select! {
+            delivery = consumer.next() => delivery,
+            _ = stopped.changed() => break,
+        };
+        let delivery = match delivery {
+            Some(delivery) => delivery?,
+            None => break,
+        };
+        stats.consumed.fetch_add(1, Ordering::Relaxed);
+        if let Some(timestamp) = delivery
+            .message
+            .data()
+            .and_then(|body| body.get(..8))
+            .and_then(|timestamp| timestamp.try_into().ok())
+        {
+            let latency = now_nanos().saturating_sub(u64::from_be_bytes(timestamp));
+            stats.record_latency(latency / 1000);
+        }
+    }
+    consumer.handle().close().await?;
+    Ok(())
+}
@@ -0,0 +1,15 @@
+//! Deliver frames decoded as raw chunks, then their entries and compressed sub-entries
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use rabbitmq_stream_protocol::{Response, ResponseKind};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, response)) = Response::decode_bytes(&Bytes::copy_from_slice(data), true) {
+        if let ResponseKind::RawDeliver(chunk) = response.kind_ref() {
+            let _ = chunk.messages();
+            let _ = chunk.decode_messages();
+        }
+    }
+});
@@ -0,0 +1,20 @@
+//! AMQP 1.0 messages, the bodies of the entries of a chunk, decoded and encoded again
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rabbitmq_stream_protocol::{
+    codec::{Decoder, Encoder},
+    message::Message,
+};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((_, message)) = Message::decode(data) {
+        let _ = message.value();
+        let _ = message.sequences();
+        let _ = message.properties();
+        let _ = message.application_properties();
+        let _ = message.message_annotations();
+        let mut encoded = Vec::new();
+        let _ = message.encode(&mut encoded);
+    }
+});
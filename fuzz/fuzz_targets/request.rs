@@ -0,0 +1,9 @@
+//! Frames written by the clients, as decoded by the test broker and the capture tools
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rabbitmq_stream_protocol::{codec::Decoder, Request};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Request::decode(data);
+});
@@ -0,0 +1,11 @@
+//! Frames read from the broker, as decoded by the client
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use rabbitmq_stream_protocol::{codec::Decoder, Response};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Response::decode(data);
+    let _ = Response::decode_bytes(&Bytes::copy_from_slice(data), false);
+});
@@ -0,0 +1,161 @@
+use bytes::Bytes;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use rabbitmq_stream_protocol::{
+    codec::{Decoder, Encoder},
+    commands::{credit::CreditCommand, deliver::chunk_crc, publish::PublishCommand},
+    compression::Compression,
+    message::Message,
+    types::PublishedMessage,
+    Request, Response, ResponseKind,
+};
+
+const MESSAGES: usize = 100;
+const BODY_SIZE: usize = 256;
+
+fn message(n: usize) -> Message {
+    Message::builder()
+        .body(vec![b'x'; BODY_SIZE])
+        .message_id(n as u64)
+        .content_type("application/octet-stream")
+        .application_property("region", "eu-west-1")
+        .build()
+}
+
+fn messages() -> Vec<Message> {
+    (0..MESSAGES).map(message).collect()
+}
+
+fn encode(command: &impl Encoder) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(command.encoded_size() as usize);
+    command.encode(&mut buffer).unwrap();
+    buffer
+}
+
+fn publish_request(entries: Vec<PublishedMessage>) -> Request {
+    PublishCommand::new(1, entries).into()
+}
+
+/// Deliver frame of a chunk holding `entries`, as read from the socket
+fn deliver_frame(entries: &[PublishedMessage]) -> Bytes {
+    let mut data = Vec::new();
+    for entry in entries {
+        entry.encode_entry(&mut data).unwrap();
+    }
+    let records: u32 = entries.iter().map(|entry| entry.records() as u32).sum();
+
+    let mut body = Vec::new();
+    8u16.encode(&mut body).unwrap(); // key
+    1u16.encode(&mut body).unwrap(); // version
+    1u8.encode(&mut body).unwrap(); // subscription id
+    0x50i8.encode(&mut body).unwrap(); // magic version
+    0u8.encode(&mut body).unwrap(); // chunk type
+    (entries.len() as u16).encode(&mut body).unwrap();
+    records.encode(&mut body).unwrap();
+    0u64.encode(&mut body).unwrap(); // timestamp
+    0u64.encode(&mut body).unwrap(); // epoch
+    0u64.encode(&mut body).unwrap(); // chunk first offset
+    0i32.encode(&mut body).unwrap(); // crc
+    (data.len() as u32).encode(&mut body).unwrap();
+    0u32.encode(&mut body).unwrap(); // trailer length
+    0u32.encode(&mut body).unwrap(); // reserved
+    body.extend_from_slice(&data);
+
+    let mut frame = Vec::with_capacity(4 + body.len());
+    (body.len() as u32).encode(&mut frame).unwrap();
+    frame.extend_from_slice(&body);
+    frame.into()
+}
+
+fn simple_entries() -> Vec<PublishedMessage> {
+    messages()
+        .into_iter()
+        .enumerate()
+        .map(|(n, message)| PublishedMessage::new(n as u64, message))
+        .collect()
+}
+
+fn sub_entries(compression: Compression) -> Vec<PublishedMessage> {
+    messages()
+        .chunks(10)
+        .enumerate()
+        .map(|(n, messages)| PublishedMessage::sub_entry(n as u64, compression, messages).unwrap())
+        .collect()
+}
+
+fn command_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("command");
+
+    let credit: Request = CreditCommand::new(1, 10).into();
+    group.bench_function("credit_encode", |b| b.iter(|| encode(black_box(&credit))));
+    let credit = encode(&credit);
+    group.bench_function("credit_decode", |b| {
+        b.iter(|| Request::decode(black_box(&credit)).unwrap())
+    });
+
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+    group.bench_function("publish_encode", |b| {
+        b.iter_batched(
+            || publish_request(simple_entries()),
+            |request| encode(&request),
+            BatchSize::SmallInput,
+        )
+    });
+    let publish = encode(&publish_request(simple_entries()));
+    group.bench_function("publish_decode", |b| {
+        b.iter(|| Request::decode(black_box(&publish)).unwrap())
+    });
+    group.finish();
+}
+
+fn chunk_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk");
+    group.throughput(Throughput::Elements(MESSAGES as u64));
+
+    let simple = deliver_frame(&simple_entries());
+    group.bench_function("deliver_decode", |b| {
+        b.iter(|| Response::decode_bytes(black_box(&simple), false).unwrap())
+    });
+    group.bench_function("chunk_crc", |b| b.iter(|| chunk_crc(black_box(&simple))));
+    group.bench_function("raw_deliver_messages", |b| {
+        b.iter(|| {
+            let (_, response) = Response::decode_bytes(black_box(&simple), true).unwrap();
+            match response.kind() {
+                ResponseKind::RawDeliver(chunk) => chunk.messages().unwrap(),
+                _ => unreachable!(),
+            }
+        })
+    });
+
+    let sub_entries = deliver_frame(&sub_entries(Compression::Gzip));
+    group.bench_function("deliver_decode_gzip_sub_entries", |b| {
+        b.iter(|| Response::decode_bytes(black_box(&sub_entries), false).unwrap())
+    });
+    group.finish();
+}
+
+fn message_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("message");
+
+    let message = message(0);
+    let encoded = encode(&message);
+    group.throughput(Throughput::Bytes(encoded.len() as u64));
+    group.bench_function("encode", |b| b.iter(|| encode(black_box(&message))));
+    group.bench_function("decode", |b| {
+        b.iter(|| Message::decode(black_box(&encoded)).unwrap())
+    });
+    group.bench_function("round_trip", |b| {
+        b.iter(|| {
+            let (_, decoded) = Message::decode(&encode(black_box(&message))).unwrap();
+            decoded
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    command_benchmarks,
+    chunk_benchmarks,
+    message_benchmarks
+);
+criterion_main!(benches);
@@ -0,0 +1,134 @@
+//! Scratch buffers reused across the frames encoded by a thread
+//!
+//! Encoding a message goes through intermediate buffers, for the AMQP sections and the
+//! records of sub-entries. Taking them from a pool keeps the allocation rate flat instead of
+//! growing with the number of messages published.
+
+use std::{cell::RefCell, thread::LocalKey};
+
+use ntex_bytes::BytesMut;
+
+/// Most buffers kept per thread, nested encodings take one each
+const MAX_POOLED_BUFFERS: usize = 8;
+/// Buffers grown beyond this are released, a single large message must not pin its memory
+const MAX_POOLED_CAPACITY: usize = 1024 * 1024;
+
+thread_local! {
+    static AMQP_BUFFERS: RefCell<Vec<BytesMut>> = const { RefCell::new(Vec::new()) };
+    static RECORD_BUFFERS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+trait Scratch {
+    fn with_capacity(capacity: usize) -> Self;
+    fn reserve(&mut self, additional: usize);
+    fn capacity(&self) -> usize;
+    fn clear(&mut self);
+}
+
+impl Scratch for BytesMut {
+    fn with_capacity(capacity: usize) -> Self {
+        BytesMut::with_capacity(capacity)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        BytesMut::reserve(self, additional)
+    }
+
+    fn capacity(&self) -> usize {
+        BytesMut::capacity(self)
+    }
+
+    fn clear(&mut self) {
+        BytesMut::clear(self)
+    }
+}
+
+impl Scratch for Vec<u8> {
+    fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity(capacity)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional)
+    }
+
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+}
+
+fn with_pooled<T: Scratch, R>(
+    pool: &'static LocalKey<RefCell<Vec<T>>>,
+    capacity: usize,
+    f: impl FnOnce(&mut T) -> R,
+) -> R {
+    let mut buffer = match pool.with(|pool| pool.borrow_mut().pop()) {
+        Some(mut buffer) => {
+            buffer.reserve(capacity);
+            buffer
+        }
+        None => T::with_capacity(capacity),
+    };
+    let result = f(&mut buffer);
+    if buffer.capacity() <= MAX_POOLED_CAPACITY {
+        buffer.clear();
+        pool.with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < MAX_POOLED_BUFFERS {
+                pool.push(buffer);
+            }
+        });
+    }
+    result
+}
+
+/// Run `f` with an empty buffer of at least `capacity` bytes for the AMQP codec
+pub(crate) fn with_amqp_buffer<R>(capacity: usize, f: impl FnOnce(&mut BytesMut) -> R) -> R {
+    with_pooled(&AMQP_BUFFERS, capacity, f)
+}
+
+/// Run `f` with an empty buffer of at least `capacity` bytes for the records of a sub-entry
+pub(crate) fn with_record_buffer<R>(capacity: usize, f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    with_pooled(&RECORD_BUFFERS, capacity, f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_amqp_buffer, with_record_buffer, MAX_POOLED_CAPACITY};
+
+    #[test]
+    fn buffer_reused_test() {
+        let address = with_record_buffer(64, |buffer| {
+            buffer.extend_from_slice(b"records");
+            buffer.as_ptr() as usize
+        });
+        with_record_buffer(16, |buffer| {
+            assert!(buffer.is_empty());
+            assert_eq!(address, buffer.as_ptr() as usize);
+        });
+    }
+
+    #[test]
+    fn nested_buffers_test() {
+        with_amqp_buffer(8, |outer| {
+            outer.extend_from_slice(b"outer");
+            with_amqp_buffer(8, |inner| {
+                assert!(inner.is_empty());
+                inner.extend_from_slice(b"inner");
+            });
+            assert_eq!(&b"outer"[..], &outer[..]);
+        });
+    }
+
+    #[test]
+    fn large_buffer_released_test() {
+        with_record_buffer(MAX_POOLED_CAPACITY + 1, |_| {});
+        with_record_buffer(16, |buffer| {
+            assert!(buffer.capacity() <= MAX_POOLED_CAPACITY);
+        });
+    }
+}
@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 
 use byteorder::ByteOrder;
+use bytes::Bytes;
 
+use crate::compression::Compression;
 use crate::message::Message;
-use crate::types::PublishedMessage;
 use crate::types::PublishingError;
+use crate::types::{PublishedEntry, PublishedMessage, SubEntry};
 use crate::ResponseCode;
-use crate::{error::DecodeError, types::Header};
+use crate::{
+    error::DecodeError,
+    types::{Header, PeerProperties},
+};
 
 use super::Decoder;
 
@@ -53,15 +58,28 @@ impl Decoder for i64 {
 
 impl Decoder for Vec<u8> {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
-        let (input, len) = read_i32(input)?;
-        let len = len as usize;
-        Ok((&input[len..], input[..len].to_vec()))
+        let (input, data) = read_slice(input)?;
+        Ok((input, data.to_vec()))
     }
 }
 
+/// Read a size or a count encoded as an `i32`, rejecting the negative ones
+pub(crate) fn read_len(input: &[u8]) -> Result<(&[u8], usize), DecodeError> {
+    let (input, len) = read_i32(input)?;
+    if len < 0 {
+        return Err(DecodeError::InvalidLength(len));
+    }
+    Ok((input, len as usize))
+}
+
+/// Capacity to reserve for `len` items of at least `item_size` bytes, bounded by the
+/// remaining input so a corrupted count cannot allocate more than the frame holds
+pub(crate) fn bounded_capacity(len: usize, input: &[u8], item_size: usize) -> usize {
+    len.min(input.len() / item_size)
+}
+
 pub fn read_vec<T: Decoder>(input: &[u8]) -> Result<(&[u8], Vec<T>), DecodeError> {
-    let (mut input, len) = read_i32(input)?;
-    let len = len as usize;
+    let (mut input, len) = read_len(input)?;
     let mut result: Vec<T> = Vec::new();
     for _ in 0..len {
         let (input1, value) = T::decode(input)?;
@@ -103,9 +121,106 @@ impl Decoder for Header {
 impl Decoder for PublishedMessage {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
         let (input, publishing_id) = u64::decode(input)?;
-        let (input, body) = read_vec::<u8>(input)?;
-        let (_, message) = Message::decode(&body)?;
-        Ok((input, PublishedMessage::new(publishing_id, message)))
+        let (input, entry) = PublishedEntry::decode(input)?;
+        Ok((
+            input,
+            PublishedMessage {
+                publishing_id,
+                filter_value: None,
+                entry,
+            },
+        ))
+    }
+}
+
+impl Decoder for PublishedEntry {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
+        if is_sub_entry(input)? {
+            let (input, sub_entry) = read_sub_entry(input)?;
+            Ok((input, PublishedEntry::SubEntry(sub_entry.into())))
+        } else {
+            let (input, body) = read_slice(input)?;
+            let (_, message) = Message::decode(body)?;
+            Ok((input, PublishedEntry::Simple(message)))
+        }
+    }
+}
+
+/// Read bytes prefixed by their size without copying them
+pub(crate) fn read_slice(input: &[u8]) -> Result<(&[u8], &[u8]), DecodeError> {
+    let (input, len) = read_len(input)?;
+    check_len(input, len)?;
+    let (data, input) = input.split_at(len);
+    Ok((input, data))
+}
+
+/// Sub-entries start with a byte with the high bit set, simple entries with their size
+pub(crate) fn is_sub_entry(input: &[u8]) -> Result<bool, DecodeError> {
+    check_len(input, 1)?;
+    Ok(input[0] & 0x80 != 0)
+}
+
+/// Sub-entry of a frame, the compressed records borrowed from the frame
+pub(crate) struct SubEntrySlice<'a> {
+    pub(crate) compression: Compression,
+    pub(crate) records: u16,
+    pub(crate) uncompressed_len: u32,
+    pub(crate) data: &'a [u8],
+}
+
+pub(crate) fn read_sub_entry(input: &[u8]) -> Result<(&[u8], SubEntrySlice<'_>), DecodeError> {
+    let (input, entry_type) = read_u8(input)?;
+    let (input, records) = read_u16(input)?;
+    let (input, uncompressed_len) = read_u32(input)?;
+    let (input, data) = read_slice(input)?;
+
+    Ok((
+        input,
+        SubEntrySlice {
+            compression: Compression::from_code((entry_type & 0x70) >> 4)?,
+            records,
+            uncompressed_len,
+            data,
+        },
+    ))
+}
+
+impl From<SubEntrySlice<'_>> for SubEntry {
+    fn from(sub_entry: SubEntrySlice<'_>) -> Self {
+        SubEntry {
+            compression: sub_entry.compression,
+            records: sub_entry.records,
+            uncompressed_len: sub_entry.uncompressed_len,
+            data: sub_entry.data.to_vec(),
+        }
+    }
+}
+
+impl SubEntrySlice<'_> {
+    /// Records of the sub-entry, a slice of `buffer` when they are not compressed and
+    /// `data` is a slice of it
+    pub(crate) fn decompress(&self, buffer: Option<&Bytes>) -> Result<Bytes, DecodeError> {
+        Ok(match (self.compression, buffer) {
+            (Compression::None, Some(buffer)) => buffer.slice_ref(self.data),
+            (compression, _) => {
+                Bytes::from(compression.decompress(self.data, self.uncompressed_len as usize)?)
+            }
+        })
+    }
+
+    /// Decompress and decode the messages of the sub-entry, their bodies share the
+    /// decompressed records, or `buffer` for uncompressed records when `data` is a slice of it
+    pub(crate) fn messages(&self, buffer: Option<&Bytes>) -> Result<Vec<Message>, DecodeError> {
+        let records = self.decompress(buffer)?;
+        let mut input = &records[..];
+        let mut messages = Vec::with_capacity(bounded_capacity(self.records as usize, input, 4));
+        for _ in 0..self.records {
+            let (input1, body) = read_slice(input)?;
+            let (_, message) = Message::decode_in(body, Some(&records))?;
+            messages.push(message);
+            input = input1;
+        }
+        Ok(messages)
     }
 }
 
@@ -130,9 +245,14 @@ impl Decoder for Option<String> {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (input, len) = read_i16(input)?;
 
-        if len == 0 {
+        // -1 is the null string
+        if len == 0 || len == -1 {
             return Ok((input, None));
         }
+        if len < 0 {
+            return Err(DecodeError::InvalidLength(len as i32));
+        }
+        check_len(input, len as usize)?;
         let (bytes, input) = input.split_at(len as usize);
         let string = String::from_utf8(bytes.to_vec())?;
         Ok((input, Some(string)))
@@ -143,7 +263,7 @@ impl Decoder for HashMap<String, String> {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (mut input, num_properties) = read_u32(input)?;
 
-        let mut map = HashMap::with_capacity(num_properties as usize);
+        let mut map = HashMap::with_capacity(bounded_capacity(num_properties as usize, input, 4));
         for _ in 0..num_properties {
             let (input1, key) = Option::<String>::decode(input)?;
             let (input2, value) = Option::<String>::decode(input1)?;
@@ -158,6 +278,44 @@ impl Decoder for HashMap<String, String> {
     }
 }
 
+impl Decoder for PeerProperties {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (mut input, num_properties) = read_u32(input)?;
+
+        let mut properties = PeerProperties::new();
+        for _ in 0..num_properties {
+            let (input1, key) = read_str(input)?;
+            let (input2, value) = read_str(input1)?;
+
+            if let (Some(k), Some(v)) = (key, value) {
+                properties.push_decoded(k, v);
+            }
+            input = input2;
+        }
+
+        Ok((input, properties))
+    }
+}
+
+impl Decoder for HashMap<String, i64> {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (mut input, num_properties) = read_u32(input)?;
+
+        let mut map = HashMap::with_capacity(bounded_capacity(num_properties as usize, input, 10));
+        for _ in 0..num_properties {
+            let (input1, key) = Option::<String>::decode(input)?;
+            let (input2, value) = read_i64(input1)?;
+
+            if let Some(k) = key {
+                map.insert(k, value);
+            }
+            input = input2;
+        }
+
+        Ok((input, map))
+    }
+}
+
 impl Decoder for Vec<String> {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (mut input, num_properties) = read_u32(input)?;
@@ -199,6 +357,24 @@ impl Decoder for Vec<PublishingError> {
     }
 }
 
+/// Borrow a string like `Option<String>` decodes it, `None` when null or empty
+fn read_str(input: &[u8]) -> Result<(&[u8], Option<&str>), DecodeError> {
+    let (input, len) = read_i16(input)?;
+
+    if len == 0 || len == -1 {
+        return Ok((input, None));
+    }
+    if len < 0 {
+        return Err(DecodeError::InvalidLength(len as i32));
+    }
+    check_len(input, len as usize)?;
+    let (bytes, input) = input.split_at(len as usize);
+    match std::str::from_utf8(bytes) {
+        Ok(string) => Ok((input, Some(string))),
+        Err(_) => Err(String::from_utf8(bytes.to_vec()).unwrap_err().into()),
+    }
+}
+
 pub fn check_len(input: &[u8], size: usize) -> Result<(), DecodeError> {
     if input.len() < size {
         return Err(DecodeError::Incomplete(size));
@@ -233,3 +409,60 @@ reader!(read_u32, 4, u32);
 reader!(read_i32, 4, i32);
 reader!(read_u64, 8, u64);
 reader!(read_i64, 8, i64);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{codec::Decoder, error::DecodeError, Response};
+
+    #[test]
+    fn decode_negative_length_test() {
+        assert!(matches!(
+            Vec::<u8>::decode(&[0xff, 0xff, 0xff, 0xfe, 1, 2]),
+            Err(DecodeError::InvalidLength(-2))
+        ));
+        assert!(matches!(
+            Vec::<u32>::decode(&[0x80, 0, 0, 0]),
+            Err(DecodeError::InvalidLength(i32::MIN))
+        ));
+        assert!(matches!(
+            Option::<String>::decode(&[0xff, 0x00, b'a']),
+            Err(DecodeError::InvalidLength(-256))
+        ));
+        assert_eq!(None, Option::<String>::decode(&[0xff, 0xff]).unwrap().1);
+    }
+
+    #[test]
+    fn decode_truncated_length_test() {
+        assert!(matches!(
+            Vec::<u8>::decode(&[0, 0, 0, 5, 1, 2]),
+            Err(DecodeError::Incomplete(5))
+        ));
+        assert!(matches!(
+            Option::<String>::decode(&[0, 3, b'a']),
+            Err(DecodeError::Incomplete(3))
+        ));
+    }
+
+    #[test]
+    fn decode_oversized_count_test() {
+        // a count of 4 billion properties does not reserve room for them
+        assert!(matches!(
+            HashMap::<String, String>::decode(&[0xff, 0xff, 0xff, 0xff, 0, 1, b'a']),
+            Err(DecodeError::Incomplete(2))
+        ));
+
+        // deliver frame announcing 4 billion records in a one byte chunk
+        let mut frame = vec![0, 0, 0, 0, 0, 8, 0, 1, 1, 80, 0, 0, 0];
+        frame.extend_from_slice(&[0xff; 4]);
+        frame.extend_from_slice(&[0; 40]);
+        frame.push(0);
+        let size = (frame.len() - 4) as u32;
+        frame[..4].copy_from_slice(&size.to_be_bytes());
+        assert!(matches!(
+            Response::decode(&frame),
+            Err(DecodeError::Incomplete(_))
+        ));
+    }
+}
@@ -2,7 +2,14 @@ use std::{collections::HashMap, io::Write};
 
 use byteorder::{BigEndian, WriteBytesExt};
 
-use crate::{error::EncodeError, types::Header, types::PublishedMessage, ResponseCode};
+use crate::{
+    compression::Compression,
+    error::EncodeError,
+    message::Message,
+    types::{Header, PeerProperties},
+    types::{PublishedEntry, PublishedMessage, SubEntry},
+    ResponseCode,
+};
 
 use crate::types::PublishingError;
 
@@ -100,13 +107,71 @@ impl Encoder for Header {
 
 impl Encoder for PublishedMessage {
     fn encoded_size(&self) -> u32 {
-        self.publishing_id.encoded_size() + 4 + self.message.encoded_size()
+        self.publishing_id.encoded_size() + self.entry.encoded_size()
     }
 
     fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
         self.publishing_id.encode(writer)?;
-        self.message.encoded_size().encode(writer)?;
-        self.message.encode(writer)?;
+        self.entry.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Encoder for PublishedEntry {
+    fn encoded_size(&self) -> u32 {
+        match self {
+            PublishedEntry::Simple(message) => 4 + message.encoded_size(),
+            PublishedEntry::SubEntry(sub_entry) => sub_entry.encoded_size(),
+            PublishedEntry::Records(messages) => 1 + 2 + 4 + 4 + records_size(messages),
+        }
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        match self {
+            PublishedEntry::Simple(message) => {
+                message.encoded_size().encode(writer)?;
+                message.encode(writer)?;
+            }
+            PublishedEntry::SubEntry(sub_entry) => sub_entry.encode(writer)?,
+            PublishedEntry::Records(messages) => {
+                let size = records_size(messages);
+                writer.write_u8(0x80 | (Compression::None.code() << 4))?;
+                (messages.len() as u16).encode(writer)?;
+                // uncompressed and stored lengths
+                size.encode(writer)?;
+                size.encode(writer)?;
+                for message in messages {
+                    message.encoded_size().encode(writer)?;
+                    message.encode(writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size of the records of an uncompressed sub-entry, each one prefixed with its size
+fn records_size(messages: &[Message]) -> u32 {
+    messages
+        .iter()
+        .map(|message| 4 + message.encoded_size())
+        .sum()
+}
+
+impl Encoder for SubEntry {
+    fn encoded_size(&self) -> u32 {
+        1 + self.records.encoded_size()
+            + self.uncompressed_len.encoded_size()
+            + 4
+            + self.data.len() as u32
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        writer.write_u8(0x80 | (self.compression.code() << 4))?;
+        self.records.encode(writer)?;
+        self.uncompressed_len.encode(writer)?;
+        writer.write_u32::<BigEndian>(self.data.len() as u32)?;
+        writer.write_all(&self.data)?;
         Ok(())
     }
 }
@@ -155,6 +220,42 @@ impl Encoder for HashMap<String, String> {
     }
 }
 
+impl Encoder for PeerProperties {
+    fn encoded_size(&self) -> u32 {
+        4 + self
+            .iter()
+            .fold(0, |acc, (k, v)| acc + k.encoded_size() + v.encoded_size())
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        writer.write_u32::<BigEndian>(self.len() as u32)?;
+
+        for (k, v) in self.iter() {
+            k.encode(writer)?;
+            v.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Encoder for HashMap<String, i64> {
+    fn encoded_size(&self) -> u32 {
+        4 + self.iter().fold(0, |acc, (k, v)| {
+            acc + k.as_str().encoded_size() + v.encoded_size()
+        })
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        writer.write_u32::<BigEndian>(self.len() as u32)?;
+
+        for (k, v) in self {
+            k.as_str().encode(writer)?;
+            v.encode(writer)?;
+        }
+        Ok(())
+    }
+}
+
 impl Encoder for Option<String> {
     fn encoded_size(&self) -> u32 {
         2 + self.as_ref().map(|string| string.len() as u32).unwrap_or(0)
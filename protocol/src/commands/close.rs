@@ -17,8 +17,9 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
+/// Frame size of a [`CloseResponse`], the [`CloseRequest`] of the other side is longer as
+/// it carries a reason, both share the same key
+pub(crate) const CLOSE_RESPONSE_FRAME_SIZE: u32 = 2 + 2 + 4 + 2;
 
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
@@ -36,6 +37,18 @@ impl CloseRequest {
             closing_reason,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn closing_code(&self) -> &ResponseCode {
+        &self.closing_code
+    }
+
+    pub fn closing_reason(&self) -> &str {
+        &self.closing_reason
+    }
 }
 
 impl Encoder for CloseRequest {
@@ -69,7 +82,7 @@ impl Decoder for CloseRequest {
             CloseRequest {
                 correlation_id,
                 closing_code,
-                closing_reason: closing_reason.unwrap(),
+                closing_reason: closing_reason.unwrap_or_default(),
             },
         ))
     }
@@ -94,6 +107,13 @@ impl CloseResponse {
     }
 }
 
+impl Command for CloseResponse {
+    /// Answers to server requests carry the response flag in their key
+    fn key(&self) -> u16 {
+        COMMAND_CLOSE | 0x8000
+    }
+}
+
 impl Encoder for CloseResponse {
     fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
         self.correlation_id.encode(writer)?;
@@ -0,0 +1,182 @@
+use std::io::Write;
+
+use crate::{
+    codec::{Decoder, Encoder},
+    error::{DecodeError, EncodeError},
+    protocol::commands::COMMAND_CONSUMER_UPDATE,
+    FromResponse, ResponseCode,
+};
+
+use super::{subscribe::OffsetSpecification, Command};
+
+/// Sent by the server when a single active consumer is activated or deactivated
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct ConsumerUpdateCommand {
+    pub(crate) correlation_id: u32,
+    subscription_id: u8,
+    active: u8,
+}
+
+impl ConsumerUpdateCommand {
+    pub fn new(correlation_id: u32, subscription_id: u8, active: u8) -> Self {
+        Self {
+            correlation_id,
+            subscription_id,
+            active,
+        }
+    }
+
+    /// Correlation id the [`ConsumerUpdateResponse`] must answer with
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn subscription_id(&self) -> u8 {
+        self.subscription_id
+    }
+
+    /// Whether the consumer became the active one
+    pub fn is_active(&self) -> bool {
+        self.active == 1
+    }
+}
+
+impl Encoder for ConsumerUpdateCommand {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size()
+            + self.subscription_id.encoded_size()
+            + self.active.encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.subscription_id.encode(writer)?;
+        self.active.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for ConsumerUpdateCommand {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, subscription_id) = u8::decode(input)?;
+        let (input, active) = u8::decode(input)?;
+
+        Ok((
+            input,
+            ConsumerUpdateCommand {
+                correlation_id,
+                subscription_id,
+                active,
+            },
+        ))
+    }
+}
+
+impl Command for ConsumerUpdateCommand {
+    fn key(&self) -> u16 {
+        COMMAND_CONSUMER_UPDATE
+    }
+}
+
+impl FromResponse for ConsumerUpdateCommand {
+    fn from_response(response: crate::Response) -> Option<Self> {
+        match response.kind {
+            crate::ResponseKind::ConsumerUpdate(consumer_update) => Some(consumer_update),
+            _ => None,
+        }
+    }
+}
+
+/// Answer of the client to a [`ConsumerUpdateCommand`], with the offset to resume from
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct ConsumerUpdateResponse {
+    pub(crate) correlation_id: u32,
+    response_code: ResponseCode,
+    offset_specification: OffsetSpecification,
+}
+
+impl ConsumerUpdateResponse {
+    pub fn new(
+        correlation_id: u32,
+        response_code: ResponseCode,
+        offset_specification: OffsetSpecification,
+    ) -> Self {
+        Self {
+            correlation_id,
+            response_code,
+            offset_specification,
+        }
+    }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn response_code(&self) -> &ResponseCode {
+        &self.response_code
+    }
+
+    /// Offset the activated consumer resumes from
+    pub fn offset_specification(&self) -> &OffsetSpecification {
+        &self.offset_specification
+    }
+}
+
+impl Encoder for ConsumerUpdateResponse {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size()
+            + self.response_code.encoded_size()
+            + self.offset_specification.encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.response_code.encode(writer)?;
+        self.offset_specification.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for ConsumerUpdateResponse {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, response_code) = ResponseCode::decode(input)?;
+        let (input, offset_specification) = OffsetSpecification::decode(input)?;
+
+        Ok((
+            input,
+            ConsumerUpdateResponse {
+                correlation_id,
+                response_code,
+                offset_specification,
+            },
+        ))
+    }
+}
+
+impl Command for ConsumerUpdateResponse {
+    /// Answers to server requests carry the response flag in their key
+    fn key(&self) -> u16 {
+        COMMAND_CONSUMER_UPDATE | 0x8000
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::tests::command_encode_decode_test;
+
+    use super::{ConsumerUpdateCommand, ConsumerUpdateResponse};
+
+    #[test]
+    fn consumer_update_request_test() {
+        command_encode_decode_test::<ConsumerUpdateCommand>();
+    }
+
+    #[test]
+    fn consumer_update_response_test() {
+        command_encode_decode_test::<ConsumerUpdateResponse>();
+    }
+}
@@ -9,9 +9,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct CreateStreamCommand {
@@ -28,6 +25,18 @@ impl CreateStreamCommand {
             args,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+
+    pub fn args(&self) -> &HashMap<String, String> {
+        &self.args
+    }
 }
 
 impl Encoder for CreateStreamCommand {
@@ -61,7 +70,7 @@ impl Decoder for CreateStreamCommand {
             input,
             CreateStreamCommand {
                 correlation_id,
-                stream_name: stream_name.unwrap(),
+                stream_name: stream_name.unwrap_or_default(),
                 args,
             },
         ))
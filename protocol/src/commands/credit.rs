@@ -1,8 +1,5 @@
 use std::io::Write;
 
-#[cfg(test)]
-use fake::Fake;
-
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
@@ -26,6 +23,14 @@ impl CreditCommand {
             credit,
         }
     }
+
+    pub fn subscription_id(&self) -> u8 {
+        self.subscription_id
+    }
+
+    pub fn credit(&self) -> u16 {
+        self.credit
+    }
 }
 
 impl Encoder for CreditCommand {
@@ -8,9 +8,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct DeclarePublisherCommand {
@@ -34,6 +31,22 @@ impl DeclarePublisherCommand {
             stream_name,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn publisher_id(&self) -> u8 {
+        self.publisher_id
+    }
+
+    pub fn publisher_reference(&self) -> Option<&str> {
+        self.publisher_reference.as_deref()
+    }
+
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
 }
 
 impl Encoder for DeclarePublisherCommand {
@@ -72,7 +85,7 @@ impl Decoder for DeclarePublisherCommand {
                 correlation_id,
                 publisher_id,
                 publisher_reference,
-                stream_name: stream_name.unwrap(),
+                stream_name: stream_name.unwrap_or_default(),
             },
         ))
     }
@@ -8,9 +8,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct Delete {
@@ -25,6 +22,14 @@ impl Delete {
             stream,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
 }
 
 impl Encoder for Delete {
@@ -53,7 +58,7 @@ impl Decoder for Delete {
             input,
             Delete {
                 correlation_id,
-                stream: stream.unwrap(),
+                stream: stream.unwrap_or_default(),
             },
         ))
     }
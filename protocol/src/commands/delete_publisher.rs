@@ -8,9 +8,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct DeletePublisherCommand {
@@ -25,6 +22,14 @@ impl DeletePublisherCommand {
             publisher_id,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn publisher_id(&self) -> u8 {
+        self.publisher_id
+    }
 }
 
 impl Encoder for DeletePublisherCommand {
@@ -1,7 +1,12 @@
 use std::io::Write;
 
+use bytes::Bytes;
+
 use super::Command;
-use crate::codec::decoder::read_vec;
+use crate::codec::decoder::{
+    bounded_capacity, check_len, is_sub_entry, read_slice, read_sub_entry, SubEntrySlice,
+};
+use crate::compression::Compression;
 use crate::message::Message;
 use crate::{
     codec::{Decoder, Encoder},
@@ -9,9 +14,6 @@ use crate::{
     protocol::commands::COMMAND_DELIVER,
 };
 use byteorder::{BigEndian, WriteBytesExt};
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug, Clone)]
 pub struct DeliverCommand {
@@ -59,6 +61,28 @@ impl DeliverCommand {
     }
 }
 
+impl DeliverCommand {
+    /// Offset of the first message of the chunk, the next ones follow in order
+    pub fn chunk_first_offset(&self) -> u64 {
+        self.chunk_first_offset
+    }
+
+    /// Creation time of the chunk in milliseconds since the epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Epoch of the stream leader which wrote the chunk
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Number of entries, a sub-entry counts as one entry
+    pub fn num_entries(&self) -> u16 {
+        self.num_entries
+    }
+}
+
 impl Encoder for DeliverCommand {
     fn encoded_size(&self) -> u32 {
         self.subscription_id.encoded_size()
@@ -75,7 +99,7 @@ impl Encoder for DeliverCommand {
             + 4 // vec of messages
             + self.messages.iter().fold(0, |acc, message| {
                 acc + 1 +  message.encoded_size()
-            }) as u32
+            })
     }
 
     fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
@@ -94,7 +118,7 @@ impl Encoder for DeliverCommand {
             .iter()
             .fold(0, |acc, message| acc + 1 + message.encoded_size());
 
-        writer.write_u32::<BigEndian>(size as u32)?;
+        writer.write_u32::<BigEndian>(size)?;
         self.trailer_length.encode(writer)?;
         self.reserved.encode(writer)?;
 
@@ -108,6 +132,16 @@ impl Encoder for DeliverCommand {
 
 impl Decoder for DeliverCommand {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        DeliverCommand::decode_in(input, None)
+    }
+}
+
+impl DeliverCommand {
+    /// Decode the frame, the message bodies share `buffer` when `input` is a slice of it
+    pub(crate) fn decode_in<'a>(
+        input: &'a [u8],
+        buffer: Option<&Bytes>,
+    ) -> Result<(&'a [u8], Self), DecodeError> {
         let (input, subscription_id) = u8::decode(input)?;
         let (input, magic_version) = i8::decode(input)?;
         let (input, chunk_type) = u8::decode(input)?;
@@ -119,15 +153,9 @@ impl Decoder for DeliverCommand {
         let (input, chunk_crc) = i32::decode(input)?;
         let (input, _data_length) = u32::decode(input)?;
         let (input, trailer_length) = u32::decode(input)?;
-        let (mut input, reserved) = u32::decode(input)?;
+        let (input, reserved) = u32::decode(input)?;
 
-        let mut messages = Vec::new();
-        for _ in 0..num_records {
-            let (input1, result) = read_vec(input)?;
-            let (_, message) = Message::decode(&result)?;
-            messages.push(message);
-            input = input1;
-        }
+        let (input, messages) = read_messages(input, num_records, buffer)?;
 
         Ok((
             input,
@@ -154,6 +182,486 @@ impl Command for DeliverCommand {
     }
 }
 
+/// Read `num_records` messages, a sub-entry packs several records so entries are read
+/// until all records are decoded
+fn read_messages<'a>(
+    mut input: &'a [u8],
+    num_records: u32,
+    buffer: Option<&Bytes>,
+) -> Result<(&'a [u8], Vec<Message>), DecodeError> {
+    let mut messages = Vec::with_capacity(bounded_capacity(num_records as usize, input, 4));
+    while messages.len() < num_records as usize {
+        if is_sub_entry(input)? {
+            let (input1, sub_entry) = read_sub_entry(input)?;
+            messages.extend(sub_entry.messages(buffer)?);
+            input = input1;
+        } else {
+            let (input1, data) = read_slice(input)?;
+            let (_, message) = Message::decode_in(data, buffer)?;
+            messages.push(message);
+            input = input1;
+        }
+    }
+    Ok((input, messages))
+}
+
+/// Message of a chunk which failed to decode
+#[derive(Debug)]
+pub struct UndecodedMessage {
+    /// Bytes of the entry, the whole sub-entry for a message of a sub-entry
+    pub data: Vec<u8>,
+    pub error: DecodeError,
+}
+
+/// Read `num_records` messages one by one, a message failing to decode is returned
+/// with its bytes, every record of a sub-entry failing to decode with the sub-entry
+fn read_messages_lenient(
+    mut input: &[u8],
+    num_records: u32,
+    buffer: &Bytes,
+) -> Result<Vec<Result<Message, UndecodedMessage>>, DecodeError> {
+    let mut messages = Vec::with_capacity(bounded_capacity(num_records as usize, input, 4));
+    while messages.len() < num_records as usize {
+        let entry = input;
+        if is_sub_entry(input)? {
+            let (input1, sub_entry) = read_sub_entry(input)?;
+            input = input1;
+            match sub_entry.messages(Some(buffer)) {
+                Ok(sub_entry_messages) => messages.extend(sub_entry_messages.into_iter().map(Ok)),
+                Err(error) => {
+                    let data = &entry[..entry.len() - input.len()];
+                    messages.push(Err(UndecodedMessage {
+                        data: data.to_vec(),
+                        error,
+                    }));
+                    for _ in 1..sub_entry.records {
+                        messages.push(Err(UndecodedMessage {
+                            data: data.to_vec(),
+                            error: DecodeError::MessageParse("Invalid sub-entry".to_owned()),
+                        }));
+                    }
+                }
+            }
+        } else {
+            let (input1, data) = read_slice(input)?;
+            input = input1;
+            messages.push(match Message::decode_in(data, Some(buffer)) {
+                Ok((_, message)) => Ok(message),
+                Err(error) => Err(UndecodedMessage {
+                    data: data.to_vec(),
+                    error,
+                }),
+            });
+        }
+    }
+    Ok(messages)
+}
+
+/// Deliver frame with the entries of the chunk left undecoded
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug, Clone)]
+pub struct RawDeliverCommand {
+    pub subscription_id: u8,
+    magic_version: i8,
+    chunk_type: u8,
+    num_entries: u16,
+    num_records: u32,
+    timestamp: u64,
+    epoch: u64,
+    chunk_first_offset: u64,
+    chunk_crc: i32,
+    reserved: u32,
+    /// Entries of the chunk as stored by the broker
+    #[cfg_attr(
+        test,
+        dummy(expr = "Bytes::from(::fake::Fake::fake::<Vec<u8>>(&::fake::Faker))")
+    )]
+    pub data: Bytes,
+}
+
+impl RawDeliverCommand {
+    /// Type of the chunk, 0 for user messages
+    pub fn chunk_type(&self) -> u8 {
+        self.chunk_type
+    }
+
+    /// Number of entries, a sub-entry counts as one entry
+    pub fn num_entries(&self) -> u16 {
+        self.num_entries
+    }
+
+    /// Number of messages in the chunk
+    pub fn num_records(&self) -> u32 {
+        self.num_records
+    }
+
+    /// Creation time of the chunk in milliseconds since the epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Epoch of the stream leader which wrote the chunk
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Offset of the first message of the chunk, the next ones follow in order
+    pub fn chunk_first_offset(&self) -> u64 {
+        self.chunk_first_offset
+    }
+
+    /// CRC32 of the entries, as computed by the broker
+    pub fn chunk_crc(&self) -> i32 {
+        self.chunk_crc
+    }
+
+    /// Check the CRC32 of the entries against the one computed by the broker
+    pub fn check_crc(&self) -> Result<(), DecodeError> {
+        verify_crc(self.chunk_first_offset, self.chunk_crc, &self.data)
+    }
+
+    /// Decode the messages of the chunk, their bodies share the chunk
+    pub fn messages(&self) -> Result<Vec<Message>, DecodeError> {
+        read_messages(&self.data, self.num_records, Some(&self.data)).map(|(_, messages)| messages)
+    }
+
+    /// Decode the messages of the chunk one by one, the ones failing to decode are
+    /// returned with their bytes instead of failing the whole chunk
+    pub fn decode_messages(&self) -> Result<Vec<Result<Message, UndecodedMessage>>, DecodeError> {
+        read_messages_lenient(&self.data, self.num_records, &self.data)
+    }
+
+    /// Encoded messages of the chunk, in order, without decoding them
+    ///
+    /// The records are slices of the chunk, or of the decompressed records of their
+    /// sub-entry, so iterating allocates nothing for uncompressed chunks. Decode the ones
+    /// needed with [`Message::decode_shared`]. The iteration ends after an error.
+    pub fn records(&self) -> ChunkRecords<'_> {
+        ChunkRecords {
+            chunk: &self.data,
+            input: &self.data,
+            remaining: self.num_records,
+            sub_entry: None,
+        }
+    }
+
+    /// Entries of the chunk, in order, decoded as they are iterated
+    ///
+    /// Unlike [`RawDeliverCommand::records`], a sub-entry is yielded as is, its records
+    /// are only decompressed and decoded by [`ChunkEntry::messages`]: the entries not needed
+    /// are skipped for the cost of reading their size. The iteration ends after an error.
+    pub fn entries(&self) -> ChunkEntries<'_> {
+        ChunkEntries {
+            chunk: &self.data,
+            input: &self.data,
+            remaining: self.num_records,
+        }
+    }
+}
+
+/// Entry of a chunk, a message or a sub-entry packing several, see
+/// [`RawDeliverCommand::entries`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkEntry {
+    compression: Option<Compression>,
+    records: u16,
+    uncompressed_len: u32,
+    data: Bytes,
+}
+
+impl ChunkEntry {
+    /// Check if the entry is a sub-entry
+    pub fn is_sub_entry(&self) -> bool {
+        self.compression.is_some()
+    }
+
+    /// Compression of the records of a sub-entry, `None` for a single message
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Number of messages of the entry
+    pub fn records(&self) -> u16 {
+        self.records
+    }
+
+    /// The encoded message, or the records of a sub-entry as stored, sharing the chunk
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Decode the messages of the entry, decompressing the records of a sub-entry
+    pub fn messages(&self) -> Result<Vec<Message>, DecodeError> {
+        match self.sub_entry() {
+            Some(sub_entry) => sub_entry.messages(Some(&self.data)),
+            None => Ok(vec![Message::decode_shared(&self.data)?]),
+        }
+    }
+
+    fn sub_entry(&self) -> Option<SubEntrySlice<'_>> {
+        self.compression.map(|compression| SubEntrySlice {
+            compression,
+            records: self.records,
+            uncompressed_len: self.uncompressed_len,
+            data: &self.data,
+        })
+    }
+}
+
+/// Read the entry starting `input`, a slice of `chunk`
+fn read_entry<'a>(chunk: &Bytes, input: &'a [u8]) -> Result<(&'a [u8], ChunkEntry), DecodeError> {
+    if is_sub_entry(input)? {
+        let (input, sub_entry) = read_sub_entry(input)?;
+        Ok((
+            input,
+            ChunkEntry {
+                compression: Some(sub_entry.compression),
+                records: sub_entry.records,
+                uncompressed_len: sub_entry.uncompressed_len,
+                data: chunk.slice_ref(sub_entry.data),
+            },
+        ))
+    } else {
+        let (input, record) = read_slice(input)?;
+        Ok((
+            input,
+            ChunkEntry {
+                compression: None,
+                records: 1,
+                uncompressed_len: record.len() as u32,
+                data: chunk.slice_ref(record),
+            },
+        ))
+    }
+}
+
+/// Iterator over the entries of a chunk, see [`RawDeliverCommand::entries`]
+pub struct ChunkEntries<'a> {
+    chunk: &'a Bytes,
+    input: &'a [u8],
+    /// Records of the chunk left, the iteration ends once they are all read
+    remaining: u32,
+}
+
+impl Iterator for ChunkEntries<'_> {
+    type Item = Result<ChunkEntry, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match read_entry(self.chunk, self.input) {
+            Ok((input, entry)) => {
+                self.input = input;
+                self.remaining = self.remaining.saturating_sub(entry.records as u32);
+                Some(Ok(entry))
+            }
+            Err(error) => {
+                self.remaining = 0;
+                Some(Err(error))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+/// Iterator over the encoded messages of a chunk, see [`RawDeliverCommand::records`]
+pub struct ChunkRecords<'a> {
+    chunk: &'a Bytes,
+    input: &'a [u8],
+    remaining: u32,
+    sub_entry: Option<SubEntryRecords>,
+}
+
+/// Decompressed records of the sub-entry being iterated
+struct SubEntryRecords {
+    records: Bytes,
+    position: usize,
+    remaining: u16,
+}
+
+impl ChunkRecords<'_> {
+    fn read(&mut self) -> Result<Bytes, DecodeError> {
+        loop {
+            if let Some(sub_entry) = &mut self.sub_entry {
+                if sub_entry.remaining > 0 {
+                    let (input, record) = read_slice(&sub_entry.records[sub_entry.position..])?;
+                    let position = sub_entry.records.len() - input.len();
+                    let record = sub_entry.records.slice_ref(record);
+                    sub_entry.position = position;
+                    sub_entry.remaining -= 1;
+                    return Ok(record);
+                }
+                self.sub_entry = None;
+            }
+
+            let (input, entry) = read_entry(self.chunk, self.input)?;
+            self.input = input;
+            match entry.sub_entry() {
+                Some(sub_entry) => {
+                    self.sub_entry = Some(SubEntryRecords {
+                        records: sub_entry.decompress(Some(&entry.data))?,
+                        position: 0,
+                        remaining: entry.records,
+                    })
+                }
+                None => return Ok(entry.data),
+            }
+        }
+    }
+}
+
+impl Iterator for ChunkRecords<'_> {
+    type Item = Result<Bytes, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let record = self.read();
+        match record {
+            Ok(_) => self.remaining -= 1,
+            Err(_) => self.remaining = 0,
+        }
+        Some(record)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+impl Encoder for RawDeliverCommand {
+    fn encoded_size(&self) -> u32 {
+        self.subscription_id.encoded_size()
+            + self.magic_version.encoded_size()
+            + self.chunk_type.encoded_size()
+            + self.num_entries.encoded_size()
+            + self.num_records.encoded_size()
+            + self.timestamp.encoded_size()
+            + self.epoch.encoded_size()
+            + self.chunk_first_offset.encoded_size()
+            + self.chunk_crc.encoded_size()
+            + 4 // data length
+            + 4 // trailer length
+            + self.reserved.encoded_size()
+            + self.data.len() as u32
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.subscription_id.encode(writer)?;
+        self.magic_version.encode(writer)?;
+        self.chunk_type.encode(writer)?;
+        self.num_entries.encode(writer)?;
+        self.num_records.encode(writer)?;
+        self.timestamp.encode(writer)?;
+        self.epoch.encode(writer)?;
+        self.chunk_first_offset.encode(writer)?;
+        self.chunk_crc.encode(writer)?;
+        writer.write_u32::<BigEndian>(self.data.len() as u32)?;
+        writer.write_u32::<BigEndian>(0)?;
+        self.reserved.encode(writer)?;
+        writer.write_all(&self.data)?;
+        Ok(())
+    }
+}
+
+impl Decoder for RawDeliverCommand {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        RawDeliverCommand::decode_in(input, None)
+    }
+}
+
+impl RawDeliverCommand {
+    /// Decode the frame, the chunk shares `buffer` when `input` is a slice of it
+    pub(crate) fn decode_in<'a>(
+        input: &'a [u8],
+        buffer: Option<&Bytes>,
+    ) -> Result<(&'a [u8], Self), DecodeError> {
+        let (input, subscription_id) = u8::decode(input)?;
+        let (input, magic_version) = i8::decode(input)?;
+        let (input, chunk_type) = u8::decode(input)?;
+        let (input, num_entries) = u16::decode(input)?;
+        let (input, num_records) = u32::decode(input)?;
+        let (input, timestamp) = u64::decode(input)?;
+        let (input, epoch) = u64::decode(input)?;
+        let (input, chunk_first_offset) = u64::decode(input)?;
+        let (input, chunk_crc) = i32::decode(input)?;
+        let (input, data_length) = u32::decode(input)?;
+        let (input, trailer_length) = u32::decode(input)?;
+        let (input, reserved) = u32::decode(input)?;
+
+        check_len(input, data_length as usize + trailer_length as usize)?;
+        let (data, input) = input.split_at(data_length as usize);
+        let input = &input[trailer_length as usize..];
+
+        Ok((
+            input,
+            RawDeliverCommand {
+                subscription_id,
+                magic_version,
+                chunk_type,
+                num_entries,
+                num_records,
+                timestamp,
+                epoch,
+                chunk_first_offset,
+                chunk_crc,
+                reserved,
+                data: match buffer {
+                    Some(buffer) => buffer.slice_ref(data),
+                    None => Bytes::copy_from_slice(data),
+                },
+            },
+        ))
+    }
+}
+
+/// CRC32 of the entries of a chunk, as computed by the broker for the chunk header
+///
+/// The hardware instructions of the CPU are used when available, detected at runtime.
+pub fn chunk_crc(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+fn verify_crc(first_offset: u64, expected: i32, data: &[u8]) -> Result<(), DecodeError> {
+    let computed = chunk_crc(data);
+    if computed == expected as u32 {
+        Ok(())
+    } else {
+        Err(DecodeError::ChunkCrcMismatch {
+            first_offset,
+            expected: expected as u32,
+            computed,
+        })
+    }
+}
+
+/// Check the CRC of the chunk of a deliver frame, `input` starting after the frame header
+pub(crate) fn check_crc(input: &[u8]) -> Result<(), DecodeError> {
+    // subscription id, magic version, chunk type, entries, records, timestamp and epoch
+    check_len(input, 25)?;
+    let (input, chunk_first_offset) = u64::decode(&input[25..])?;
+    let (input, chunk_crc) = i32::decode(input)?;
+    let (input, data_length) = u32::decode(input)?;
+    // trailer length and reserved
+    check_len(input, 8 + data_length as usize)?;
+    verify_crc(
+        chunk_first_offset,
+        chunk_crc,
+        &input[8..8 + data_length as usize],
+    )
+}
+
+impl Command for RawDeliverCommand {
+    fn key(&self) -> u16 {
+        COMMAND_DELIVER
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use fake::{Dummy, Faker};
@@ -161,12 +669,24 @@ mod tests {
     use crate::commands::tests::command_encode_decode_test;
     use ntex_amqp_codec::Message as AmpqMessage;
 
-    use super::{DeliverCommand, Message};
+    use super::{chunk_crc, Bytes, DeliverCommand, Message, RawDeliverCommand};
+    use crate::{
+        codec::{Decoder, Encoder},
+        compression::Compression,
+        error::DecodeError,
+        protocol::commands::COMMAND_DELIVER,
+        types::{PublishedEntry, PublishedMessage},
+        Response,
+    };
     impl Dummy<Faker> for Message {
         fn dummy_with_rng<R: rand::Rng + ?Sized>(_config: &Faker, _rng: &mut R) -> Self {
             Message {
                 message: AmpqMessage::default(),
                 publishing_id: None,
+                body: None,
+                sequences: Vec::new(),
+                encoded_sections: None,
+                encoded: None,
             }
         }
     }
@@ -175,4 +695,284 @@ mod tests {
     fn deliver_request_test() {
         command_encode_decode_test::<DeliverCommand>();
     }
+
+    #[test]
+    fn raw_deliver_request_test() {
+        command_encode_decode_test::<RawDeliverCommand>();
+    }
+
+    #[test]
+    fn raw_deliver_messages_test() {
+        let mut data = vec![];
+        for message in [
+            Message::builder().body("message0").build(),
+            Message::builder().body("message1").build(),
+        ] {
+            message.encoded_size().encode(&mut data).unwrap();
+            message.encode(&mut data).unwrap();
+        }
+
+        let mut buffer = vec![];
+        1u8.encode(&mut buffer).unwrap(); // subscription id
+        0i8.encode(&mut buffer).unwrap(); // magic version
+        0u8.encode(&mut buffer).unwrap(); // chunk type
+        2u16.encode(&mut buffer).unwrap(); // entries
+        2u32.encode(&mut buffer).unwrap(); // records
+        0u64.encode(&mut buffer).unwrap(); // timestamp
+        0u64.encode(&mut buffer).unwrap(); // epoch
+        0u64.encode(&mut buffer).unwrap(); // chunk first offset
+        0i32.encode(&mut buffer).unwrap(); // crc
+        (data.len() as u32).encode(&mut buffer).unwrap();
+        0u32.encode(&mut buffer).unwrap(); // trailer length
+        0u32.encode(&mut buffer).unwrap(); // reserved
+        buffer.extend_from_slice(&data);
+
+        let (remaining, raw) = RawDeliverCommand::decode(&buffer).unwrap();
+
+        assert!(remaining.is_empty());
+        assert_eq!(data, raw.data);
+        let messages = raw.messages().unwrap();
+        let bodies: Vec<_> = messages.iter().map(Message::data).collect();
+        assert_eq!(vec![Some(&b"message0"[..]), Some(&b"message1"[..])], bodies);
+    }
+
+    #[test]
+    fn raw_deliver_records_and_entries_test() {
+        let message = |body: &str| Message::builder().body(body.to_owned()).build();
+        let sub_entry = match PublishedMessage::sub_entry(
+            0,
+            Compression::Gzip,
+            &[message("message1"), message("message2")],
+        )
+        .unwrap()
+        .entry
+        {
+            PublishedEntry::SubEntry(sub_entry) => sub_entry,
+            _ => unreachable!(),
+        };
+        let mut data = vec![];
+        message("message0")
+            .encoded_size()
+            .encode(&mut data)
+            .unwrap();
+        message("message0").encode(&mut data).unwrap();
+        sub_entry.encode(&mut data).unwrap();
+        message("message3")
+            .encoded_size()
+            .encode(&mut data)
+            .unwrap();
+        message("message3").encode(&mut data).unwrap();
+
+        let mut buffer = vec![];
+        1u8.encode(&mut buffer).unwrap(); // subscription id
+        0i8.encode(&mut buffer).unwrap(); // magic version
+        0u8.encode(&mut buffer).unwrap(); // chunk type
+        3u16.encode(&mut buffer).unwrap(); // entries
+        5u32.encode(&mut buffer).unwrap(); // records, one more than the chunk holds
+        0u64.encode(&mut buffer).unwrap(); // timestamp
+        0u64.encode(&mut buffer).unwrap(); // epoch
+        0u64.encode(&mut buffer).unwrap(); // chunk first offset
+        0i32.encode(&mut buffer).unwrap(); // crc
+        (data.len() as u32).encode(&mut buffer).unwrap();
+        0u32.encode(&mut buffer).unwrap(); // trailer length
+        0u32.encode(&mut buffer).unwrap(); // reserved
+        buffer.extend_from_slice(&data);
+        let (_, raw) = RawDeliverCommand::decode(&buffer).unwrap();
+
+        let mut records = raw.records();
+        let mut bodies = vec![];
+        for record in records.by_ref().take(4) {
+            let record = record.unwrap();
+            bodies.push(Message::decode_shared(&record).unwrap().body().unwrap());
+        }
+        assert_eq!(
+            vec!["message0", "message1", "message2", "message3"],
+            bodies
+                .iter()
+                .map(|body| std::str::from_utf8(body).unwrap())
+                .collect::<Vec<_>>()
+        );
+        // simple entries are slices of the chunk
+        assert!(raw.data.as_ptr_range().contains(&bodies[0].as_ptr()));
+        assert!(raw.data.as_ptr_range().contains(&bodies[3].as_ptr()));
+
+        assert!(records.next().unwrap().is_err());
+        assert!(records.next().is_none());
+
+        let data = |message: &Message| message.data().map(<[u8]>::to_vec);
+        let mut entries = raw.entries();
+        let entry = entries.next().unwrap().unwrap();
+        assert!(!entry.is_sub_entry());
+        assert_eq!(1, entry.records());
+        assert_eq!(bodies[0], entry.messages().unwrap()[0].body().unwrap());
+        let entry = entries.next().unwrap().unwrap();
+        assert_eq!(Some(Compression::Gzip), entry.compression());
+        assert_eq!(2, entry.records());
+        assert_eq!(
+            vec![Some(b"message1".to_vec()), Some(b"message2".to_vec())],
+            entry
+                .messages()
+                .unwrap()
+                .iter()
+                .map(data)
+                .collect::<Vec<_>>()
+        );
+        let entry = entries.next().unwrap().unwrap();
+        assert_eq!(
+            Some(b"message3".to_vec()),
+            data(&entry.messages().unwrap()[0])
+        );
+        assert!(entries.next().unwrap().is_err());
+        assert!(entries.next().is_none());
+    }
+
+    #[test]
+    fn raw_deliver_decode_messages_test() {
+        let message = Message::builder().body("message0").build();
+        let mut data = vec![];
+        message.encoded_size().encode(&mut data).unwrap();
+        message.encode(&mut data).unwrap();
+        3u32.encode(&mut data).unwrap();
+        data.extend_from_slice(&[0xff, 0xff, 0xff]);
+
+        let mut buffer = vec![];
+        1u8.encode(&mut buffer).unwrap(); // subscription id
+        0i8.encode(&mut buffer).unwrap(); // magic version
+        0u8.encode(&mut buffer).unwrap(); // chunk type
+        2u16.encode(&mut buffer).unwrap(); // entries
+        2u32.encode(&mut buffer).unwrap(); // records
+        0u64.encode(&mut buffer).unwrap(); // timestamp
+        0u64.encode(&mut buffer).unwrap(); // epoch
+        0u64.encode(&mut buffer).unwrap(); // chunk first offset
+        0i32.encode(&mut buffer).unwrap(); // crc
+        (data.len() as u32).encode(&mut buffer).unwrap();
+        0u32.encode(&mut buffer).unwrap(); // trailer length
+        0u32.encode(&mut buffer).unwrap(); // reserved
+        buffer.extend_from_slice(&data);
+
+        let (_, raw) = RawDeliverCommand::decode(&buffer).unwrap();
+
+        assert!(raw.messages().is_err());
+        let mut messages = raw.decode_messages().unwrap().into_iter();
+        assert_eq!(
+            Some(&b"message0"[..]),
+            messages.next().unwrap().unwrap().data()
+        );
+        assert_eq!(
+            vec![0xff, 0xff, 0xff],
+            messages.next().unwrap().unwrap_err().data
+        );
+        assert!(messages.next().is_none());
+    }
+
+    #[test]
+    fn deliver_shared_body_test() {
+        let message = Message::builder().body("message0").build();
+        let mut buffer = vec![];
+        1u8.encode(&mut buffer).unwrap(); // subscription id
+        0i8.encode(&mut buffer).unwrap(); // magic version
+        0u8.encode(&mut buffer).unwrap(); // chunk type
+        1u16.encode(&mut buffer).unwrap(); // entries
+        1u32.encode(&mut buffer).unwrap(); // records
+        0u64.encode(&mut buffer).unwrap(); // timestamp
+        0u64.encode(&mut buffer).unwrap(); // epoch
+        0u64.encode(&mut buffer).unwrap(); // chunk first offset
+        0i32.encode(&mut buffer).unwrap(); // crc
+        (message.encoded_size() + 4).encode(&mut buffer).unwrap();
+        0u32.encode(&mut buffer).unwrap(); // trailer length
+        0u32.encode(&mut buffer).unwrap(); // reserved
+        message.encoded_size().encode(&mut buffer).unwrap();
+        message.encode(&mut buffer).unwrap();
+        let buffer = Bytes::from(buffer);
+
+        let (_, deliver) = DeliverCommand::decode_in(&buffer, Some(&buffer)).unwrap();
+
+        let body = deliver.messages[0].body().unwrap();
+        assert_eq!(&b"message0"[..], &body[..]);
+        assert!(buffer.as_ptr_range().contains(&body.as_ptr()));
+    }
+
+    /// Deliver frame of a chunk with one sub-entry of two messages
+    fn sub_entry_chunk(compression: Compression) -> Bytes {
+        let messages = vec![
+            Message::builder().body("message0").build(),
+            Message::builder().body("message1").build(),
+        ];
+        let sub_entry = PublishedMessage::sub_entry(0, compression, messages)
+            .unwrap()
+            .entry;
+
+        let mut buffer = vec![];
+        1u8.encode(&mut buffer).unwrap(); // subscription id
+        0i8.encode(&mut buffer).unwrap(); // magic version
+        0u8.encode(&mut buffer).unwrap(); // chunk type
+        1u16.encode(&mut buffer).unwrap(); // entries
+        2u32.encode(&mut buffer).unwrap(); // records
+        0u64.encode(&mut buffer).unwrap(); // timestamp
+        0u64.encode(&mut buffer).unwrap(); // epoch
+        0u64.encode(&mut buffer).unwrap(); // chunk first offset
+        0i32.encode(&mut buffer).unwrap(); // crc
+        sub_entry.encoded_size().encode(&mut buffer).unwrap();
+        0u32.encode(&mut buffer).unwrap(); // trailer length
+        0u32.encode(&mut buffer).unwrap(); // reserved
+        sub_entry.encode(&mut buffer).unwrap();
+        buffer.into()
+    }
+
+    #[test]
+    fn chunk_crc_test() {
+        // the check value of CRC-32/ISO-HDLC, computed by the broker with erlang:crc32/1
+        assert_eq!(0xcbf4_3926, chunk_crc(b"123456789"));
+
+        let mut buffer = sub_entry_chunk(Compression::None).to_vec();
+        let crc = chunk_crc(&buffer[49..]);
+        buffer[33..37].copy_from_slice(&crc.to_be_bytes());
+        let mut frame = vec![];
+        (4 + buffer.len() as u32).encode(&mut frame).unwrap();
+        COMMAND_DELIVER.encode(&mut frame).unwrap();
+        1u16.encode(&mut frame).unwrap();
+        frame.extend_from_slice(&buffer);
+
+        let (_, raw) = RawDeliverCommand::decode(&buffer).unwrap();
+        assert!(raw.check_crc().is_ok());
+        assert!(Response::check_chunk_crc(&frame).is_ok());
+
+        let last = frame.len() - 1;
+        frame[last] ^= 0xff;
+        let (_, raw) = RawDeliverCommand::decode(&frame[8..]).unwrap();
+        for result in [raw.check_crc(), Response::check_chunk_crc(&frame)] {
+            match result {
+                Err(DecodeError::ChunkCrcMismatch {
+                    first_offset,
+                    expected,
+                    computed,
+                }) => {
+                    assert_eq!(0, first_offset);
+                    assert_eq!(crc, expected);
+                    assert_eq!(chunk_crc(&frame[57..]), computed);
+                }
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn deliver_sub_entry_test() {
+        let buffer = sub_entry_chunk(Compression::Gzip);
+        let (remaining, deliver) = DeliverCommand::decode(&buffer).unwrap();
+
+        assert!(remaining.is_empty());
+        let bodies: Vec<_> = deliver.messages.iter().map(Message::data).collect();
+        assert_eq!(vec![Some(&b"message0"[..]), Some(&b"message1"[..])], bodies);
+    }
+
+    #[test]
+    fn deliver_uncompressed_sub_entry_shared_body_test() {
+        let buffer = sub_entry_chunk(Compression::None);
+        let (_, deliver) = DeliverCommand::decode_in(&buffer, Some(&buffer)).unwrap();
+
+        let body = deliver.messages[1].body().unwrap();
+        assert_eq!(&b"message1"[..], &body[..]);
+        assert!(buffer.as_ptr_range().contains(&body.as_ptr()));
+    }
 }
@@ -9,15 +9,9 @@ use crate::{
 use super::Command;
 
 #[cfg_attr(test, derive(fake::Dummy))]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Default)]
 pub struct HeartBeatCommand {}
 
-impl HeartBeatCommand {
-    pub fn default() -> Self {
-        Self {}
-    }
-}
-
 impl Encoder for HeartBeatCommand {
     fn encoded_size(&self) -> u32 {
         0
@@ -10,9 +10,6 @@ use crate::{
 use super::Command;
 
 use byteorder::{BigEndian, WriteBytesExt};
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct MetadataCommand {
@@ -27,6 +24,14 @@ impl MetadataCommand {
             streams,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn streams(&self) -> &[String] {
+        &self.streams
+    }
 }
 
 impl Encoder for MetadataCommand {
@@ -96,7 +101,7 @@ impl Decoder for Broker {
             input,
             Broker {
                 reference,
-                host: host.unwrap(),
+                host: host.unwrap_or_default(),
                 port,
             },
         ))
@@ -112,7 +117,7 @@ impl Decoder for StreamMetadata {
         Ok((
             input,
             StreamMetadata {
-                stream_name: stream_name.unwrap(),
+                stream_name: stream_name.unwrap_or_default(),
                 code,
                 leader_reference,
                 replicas_references,
@@ -5,9 +5,6 @@ use crate::{
 use super::Command;
 
 use crate::codec::Encoder;
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct MetadataUpdateCommand {
@@ -15,6 +12,21 @@ pub struct MetadataUpdateCommand {
     stream: String,
 }
 
+impl MetadataUpdateCommand {
+    pub fn new(code: ResponseCode, stream: String) -> Self {
+        Self { code, stream }
+    }
+
+    /// Stream whose topology changed
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+}
+
 impl Decoder for MetadataUpdateCommand {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (input, code) = ResponseCode::decode(input)?;
@@ -24,7 +36,7 @@ impl Decoder for MetadataUpdateCommand {
             input,
             MetadataUpdateCommand {
                 code,
-                stream: stream.unwrap(),
+                stream: stream.unwrap_or_default(),
             },
         ))
     }
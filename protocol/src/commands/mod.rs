@@ -1,30 +1,43 @@
 pub mod close;
+pub mod consumer_update;
 pub mod create_stream;
+pub mod create_super_stream;
 pub mod credit;
 pub mod declare_publisher;
 pub mod delete;
 pub mod delete_publisher;
+pub mod delete_super_stream;
 pub mod deliver;
 pub mod generic;
 pub mod heart_beat;
 pub mod metadata;
 pub mod metadata_update;
 pub mod open;
+pub mod partitions;
 pub mod peer_properties;
 pub mod publish;
 pub mod publish_confirm;
 pub mod publish_error;
 pub mod query_offset;
 pub mod query_publisher_sequence;
+pub mod route;
 pub mod sasl_authenticate;
 pub mod sasl_handshake;
 pub mod store_offset;
+pub mod stream_stats;
 pub mod subscribe;
 pub mod tune;
 pub mod unsubscribe;
 
+use crate::protocol::version::PROTOCOL_VERSION;
+
 pub trait Command {
     fn key(&self) -> u16;
+
+    /// Version of the command, sent in the request header
+    fn version(&self) -> u16 {
+        PROTOCOL_VERSION
+    }
 }
 
 #[cfg(test)]
@@ -1,18 +1,16 @@
-use std::{collections::HashMap, io::Write};
+use std::io::Write;
 
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
     protocol::commands::COMMAND_OPEN,
     response::ResponseCode,
+    types::PeerProperties,
     FromResponse,
 };
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct OpenCommand {
@@ -27,6 +25,14 @@ impl OpenCommand {
             virtual_host,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn virtual_host(&self) -> &str {
+        &self.virtual_host
+    }
 }
 
 impl Encoder for OpenCommand {
@@ -52,12 +58,16 @@ impl Command for OpenCommand {
 pub struct OpenResponse {
     pub(crate) correlation_id: u32,
     pub(crate) code: ResponseCode,
-    pub connection_properties: HashMap<String, String>,
+    pub connection_properties: PeerProperties,
 }
 
 impl OpenResponse {
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+
     /// Get a reference to the open response's connection properties.
-    pub fn connection_properties(&self) -> &HashMap<String, String> {
+    pub fn connection_properties(&self) -> &PeerProperties {
         &self.connection_properties
     }
 }
@@ -75,7 +85,7 @@ impl Decoder for OpenResponse {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (input, correlation_id) = u32::decode(input)?;
         let (input, response_code) = ResponseCode::decode(input)?;
-        let (input, connection_properties) = HashMap::decode(input)?;
+        let (input, connection_properties) = PeerProperties::decode(input)?;
 
         Ok((
             input,
@@ -97,7 +107,7 @@ impl Decoder for OpenCommand {
             input,
             OpenCommand {
                 correlation_id,
-                virtual_host: virtual_host.unwrap(),
+                virtual_host: virtual_host.unwrap_or_default(),
             },
         ))
     }
@@ -0,0 +1,134 @@
+use std::io::Write;
+
+use crate::{
+    codec::{Decoder, Encoder},
+    error::{DecodeError, EncodeError},
+    protocol::commands::COMMAND_PARTITIONS,
+    FromResponse, ResponseCode,
+};
+
+use super::Command;
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct PartitionsCommand {
+    correlation_id: u32,
+    super_stream: String,
+}
+
+impl PartitionsCommand {
+    pub fn new(correlation_id: u32, super_stream: String) -> Self {
+        Self {
+            correlation_id,
+            super_stream,
+        }
+    }
+}
+
+impl Encoder for PartitionsCommand {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size() + self.super_stream.as_str().encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.super_stream.as_str().encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for PartitionsCommand {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, super_stream) = Option::decode(input)?;
+
+        Ok((
+            input,
+            PartitionsCommand {
+                correlation_id,
+                super_stream: super_stream.unwrap_or_default(),
+            },
+        ))
+    }
+}
+
+impl Command for PartitionsCommand {
+    fn key(&self) -> u16 {
+        COMMAND_PARTITIONS
+    }
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq)]
+pub struct PartitionsResponse {
+    pub(crate) correlation_id: u32,
+    code: ResponseCode,
+    pub streams: Vec<String>,
+}
+
+impl PartitionsResponse {
+    /// Get a reference to the partitions response's code.
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code == ResponseCode::Ok
+    }
+}
+
+impl Encoder for PartitionsResponse {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size() + self.code.encoded_size() + self.streams.encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.code.encode(writer)?;
+        self.streams.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for PartitionsResponse {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, code) = ResponseCode::decode(input)?;
+        let (input, streams) = Vec::decode(input)?;
+
+        Ok((
+            input,
+            PartitionsResponse {
+                correlation_id,
+                code,
+                streams,
+            },
+        ))
+    }
+}
+
+impl FromResponse for PartitionsResponse {
+    fn from_response(response: crate::Response) -> Option<Self> {
+        match response.kind {
+            crate::ResponseKind::Partitions(partitions) => Some(partitions),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::tests::command_encode_decode_test;
+
+    use super::{PartitionsCommand, PartitionsResponse};
+
+    #[test]
+    fn partitions_request_test() {
+        command_encode_decode_test::<PartitionsCommand>();
+    }
+
+    #[test]
+    fn partitions_response_test() {
+        command_encode_decode_test::<PartitionsResponse>();
+    }
+}
@@ -1,30 +1,41 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 use std::io::Write;
 
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
     protocol::commands::COMMAND_PEER_PROPERTIES,
+    types::PeerProperties,
     FromResponse, ResponseCode,
 };
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct PeerPropertiesCommand {
     correlation_id: u32,
-    client_properties: HashMap<String, String>,
+    /// Encoded already, the static properties of a client are encoded once
+    #[cfg_attr(
+        test,
+        dummy(expr = "Cow::Owned(encode_properties(&::fake::Fake::fake(&::fake::Faker)))")
+    )]
+    client_properties: Cow<'static, [u8]>,
 }
 
 impl PeerPropertiesCommand {
-    pub fn new(correlation_id: u32, client_properties: HashMap<String, String>) -> Self {
+    pub fn new(correlation_id: u32, client_properties: &PeerProperties) -> Self {
+        Self::with_encoded(correlation_id, encode_properties(client_properties))
+    }
+
+    /// Command sending properties encoded by [`encode_properties`]
+    pub fn with_encoded(
+        correlation_id: u32,
+        client_properties: impl Into<Cow<'static, [u8]>>,
+    ) -> Self {
         Self {
             correlation_id,
-            client_properties,
+            client_properties: client_properties.into(),
         }
     }
 
@@ -32,16 +43,28 @@ impl PeerPropertiesCommand {
     pub fn correlation_id(&self) -> u32 {
         self.correlation_id
     }
+
+    pub fn client_properties(&self) -> Result<PeerProperties, DecodeError> {
+        PeerProperties::decode(&self.client_properties).map(|(_, properties)| properties)
+    }
+}
+
+/// Encode `properties` as the client properties of a [`PeerPropertiesCommand`]
+pub fn encode_properties(properties: &PeerProperties) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(properties.encoded_size() as usize);
+    // writing to a vector does not fail
+    let _ = properties.encode(&mut encoded);
+    encoded
 }
 
 impl Encoder for PeerPropertiesCommand {
     fn encoded_size(&self) -> u32 {
-        self.correlation_id.encoded_size() + self.client_properties.encoded_size()
+        self.correlation_id.encoded_size() + self.client_properties.len() as u32
     }
 
     fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
         self.correlation_id.encode(writer)?;
-        self.client_properties.encode(writer)?;
+        writer.write_all(&self.client_properties)?;
         Ok(())
     }
 }
@@ -49,14 +72,12 @@ impl Encoder for PeerPropertiesCommand {
 impl Decoder for PeerPropertiesCommand {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (input, correlation_id) = u32::decode(input)?;
-        let (input, client_properties) = HashMap::decode(input)?;
+        let (rest, _) = PeerProperties::decode(input)?;
+        let client_properties = input[..input.len() - rest.len()].to_vec();
 
         Ok((
-            input,
-            PeerPropertiesCommand {
-                correlation_id,
-                client_properties,
-            },
+            rest,
+            PeerPropertiesCommand::with_encoded(correlation_id, client_properties),
         ))
     }
 }
@@ -72,15 +93,11 @@ impl Command for PeerPropertiesCommand {
 pub struct PeerPropertiesResponse {
     pub(crate) correlation_id: u32,
     pub(crate) code: ResponseCode,
-    pub server_properties: HashMap<String, String>,
+    pub server_properties: PeerProperties,
 }
 
 impl PeerPropertiesResponse {
-    pub fn new(
-        correlation_id: u32,
-        code: ResponseCode,
-        server_properties: HashMap<String, String>,
-    ) -> Self {
+    pub fn new(correlation_id: u32, code: ResponseCode, server_properties: PeerProperties) -> Self {
         Self {
             correlation_id,
             code,
@@ -88,7 +105,11 @@ impl PeerPropertiesResponse {
         }
     }
 
-    pub fn server_properties(&self) -> &HashMap<String, String> {
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+
+    pub fn server_properties(&self) -> &PeerProperties {
         &self.server_properties
     }
 }
@@ -106,7 +127,7 @@ impl Decoder for PeerPropertiesResponse {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
         let (input, correlation_id) = u32::decode(input)?;
         let (input, code) = ResponseCode::decode(input)?;
-        let (input, server_properties) = HashMap::decode(input)?;
+        let (input, server_properties) = PeerProperties::decode(input)?;
 
         Ok((
             input,
@@ -126,13 +147,24 @@ mod tests {
     use crate::commands::peer_properties::PeerPropertiesResponse;
     use crate::commands::tests::command_encode_decode_test;
 
-    use super::PeerPropertiesCommand;
+    use crate::types::PeerProperties;
+
+    use super::{encode_properties, PeerPropertiesCommand};
 
     #[test]
     fn peer_properties_request_test() {
         command_encode_decode_test::<PeerPropertiesCommand>()
     }
 
+    #[test]
+    fn pre_encoded_properties_test() {
+        let properties = PeerProperties::from_static(&[("product", "RabbitMQ Stream")]);
+        let encoded: &'static [u8] = Box::leak(encode_properties(&properties).into_boxed_slice());
+        let command = PeerPropertiesCommand::with_encoded(1, encoded);
+        assert_eq!(PeerPropertiesCommand::new(1, &properties), command);
+        assert_eq!(properties, command.client_properties().unwrap());
+    }
+
     impl Encoder for PeerPropertiesResponse {
         fn encoded_size(&self) -> u32 {
             0
@@ -1,16 +1,23 @@
 use std::io::Write;
 
+use byteorder::{BigEndian, WriteBytesExt};
+
 use crate::{
-    codec::{Decoder, Encoder},
+    codec::{
+        decoder::{bounded_capacity, check_len, read_i16},
+        Decoder, Encoder,
+    },
     error::{DecodeError, EncodeError},
-    protocol::commands::COMMAND_PUBLISH,
+    protocol::{commands::COMMAND_PUBLISH, version::PROTOCOL_VERSION},
+    types::PublishedEntry,
 };
 
 use super::Command;
 
 use crate::types::PublishedMessage;
-#[cfg(test)]
-use fake::Fake;
+
+/// Version of the publish command carrying a filter value per message
+pub const PUBLISH_FILTER_VERSION: u16 = 2;
 
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
@@ -26,16 +33,91 @@ impl PublishCommand {
             published_messages,
         }
     }
+
+    fn has_filter_values(&self) -> bool {
+        self.published_messages
+            .iter()
+            .any(|message| message.filter_value.is_some())
+    }
+
+    /// Decode the command encoded with `version`
+    pub fn decode_version(input: &[u8], version: u16) -> Result<(&[u8], Self), DecodeError> {
+        if version < PUBLISH_FILTER_VERSION {
+            return Self::decode(input);
+        }
+
+        let (input, publisher_id) = u8::decode(input)?;
+        let (mut input, len) = u32::decode(input)?;
+        let mut published_messages = Vec::with_capacity(bounded_capacity(len as usize, input, 12));
+        for _ in 0..len {
+            let (input1, publishing_id) = u64::decode(input)?;
+            let (input1, filter_value) = read_filter_value(input1)?;
+            let (input1, entry) = PublishedEntry::decode(input1)?;
+            published_messages.push(PublishedMessage {
+                publishing_id,
+                filter_value,
+                entry,
+            });
+            input = input1;
+        }
+
+        Ok((
+            input,
+            PublishCommand {
+                publisher_id,
+                published_messages,
+            },
+        ))
+    }
+
+    pub fn publisher_id(&self) -> u8 {
+        self.publisher_id
+    }
+
+    pub fn published_messages(&self) -> &[PublishedMessage] {
+        &self.published_messages
+    }
+}
+
+/// A missing filter value is encoded as a null string
+fn read_filter_value(input: &[u8]) -> Result<(&[u8], Option<String>), DecodeError> {
+    let (input, len) = read_i16(input)?;
+    if len < 0 {
+        return Ok((input, None));
+    }
+    check_len(input, len as usize)?;
+    let (bytes, input) = input.split_at(len as usize);
+    Ok((input, Some(String::from_utf8(bytes.to_vec())?)))
 }
 
 impl Encoder for PublishCommand {
     fn encoded_size(&self) -> u32 {
-        self.publisher_id.encoded_size() + self.published_messages.encoded_size()
+        self.publisher_id.encoded_size()
+            + if self.has_filter_values() {
+                4 + self
+                    .published_messages
+                    .iter()
+                    .fold(0, |acc, message| acc + message.encoded_size_v2())
+            } else {
+                self.published_messages.encoded_size()
+            }
     }
 
     fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
         self.publisher_id.encode(writer)?;
-        self.published_messages.encode(writer)?;
+        if !self.has_filter_values() {
+            return self.published_messages.encode(writer);
+        }
+
+        writer.write_u32::<BigEndian>(self.published_messages.len() as u32)?;
+        for message in &self.published_messages {
+            message.publishing_id.encode(writer)?;
+            match &message.filter_value {
+                Some(filter_value) => filter_value.as_str().encode(writer)?,
+                None => writer.write_i16::<BigEndian>(-1)?,
+            }
+            message.entry.encode(writer)?;
+        }
         Ok(())
     }
 }
@@ -44,6 +126,15 @@ impl Command for PublishCommand {
     fn key(&self) -> u16 {
         COMMAND_PUBLISH
     }
+
+    /// Version 2 when a message carries a filter value
+    fn version(&self) -> u16 {
+        if self.has_filter_values() {
+            PUBLISH_FILTER_VERSION
+        } else {
+            PROTOCOL_VERSION
+        }
+    }
 }
 
 impl Decoder for PublishCommand {
@@ -64,12 +155,96 @@ impl Decoder for PublishCommand {
 #[cfg(test)]
 mod tests {
 
-    use crate::commands::tests::command_encode_decode_test;
+    use fake::{Fake, Faker};
 
-    use super::PublishCommand;
+    use crate::{
+        codec::{Decoder, Encoder},
+        commands::{tests::command_encode_decode_test, Command},
+        compression::Compression,
+        message::Message,
+        types::{PublishedEntry, PublishedMessage, SubEntry},
+    };
+
+    use super::{PublishCommand, PUBLISH_FILTER_VERSION};
 
     #[test]
     fn publish_request_test() {
         command_encode_decode_test::<PublishCommand>();
     }
+
+    #[test]
+    fn publish_request_filter_value_test() {
+        let message = |publishing_id| {
+            PublishedMessage::new(
+                publishing_id,
+                Message::builder().body(&b"message"[..]).build(),
+            )
+        };
+        let command = PublishCommand::new(
+            Faker.fake(),
+            vec![message(1).filter_value(Some("emea".to_owned())), message(2)],
+        );
+        assert_eq!(PUBLISH_FILTER_VERSION, command.version());
+
+        let mut buffer = vec![];
+        command.encode(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), command.encoded_size() as usize);
+
+        let (remaining, decoded) =
+            PublishCommand::decode_version(&buffer, command.version()).unwrap();
+
+        let mut decoded_buffer = vec![];
+        decoded.encode(&mut decoded_buffer).unwrap();
+
+        assert_eq!(buffer, decoded_buffer);
+        assert_eq!(
+            Some("emea"),
+            decoded.published_messages[0].filter_value.as_deref()
+        );
+        assert_eq!(None, decoded.published_messages[1].filter_value);
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn publish_request_uncompressed_sub_entry_test() {
+        let messages = vec![
+            Message::builder().body(&b"message0"[..]).build(),
+            Message::builder().body(&b"message1"[..]).build(),
+        ];
+        let command = PublishCommand::new(
+            1,
+            vec![PublishedMessage::sub_entry(5, Compression::None, messages.clone()).unwrap()],
+        );
+        let mut buffer = vec![];
+        command.encode(&mut buffer).unwrap();
+        assert_eq!(command.encoded_size() as usize, buffer.len());
+
+        // the records are written as a sub-entry holding them as is
+        let mut records = vec![];
+        for message in &messages {
+            message.encoded_size().encode(&mut records).unwrap();
+            message.encode(&mut records).unwrap();
+        }
+        let expected = PublishCommand::new(
+            1,
+            vec![PublishedMessage {
+                publishing_id: 5,
+                filter_value: None,
+                entry: PublishedEntry::SubEntry(SubEntry {
+                    compression: Compression::None,
+                    records: 2,
+                    uncompressed_len: records.len() as u32,
+                    data: records,
+                }),
+            }],
+        );
+        let mut expected_buffer = vec![];
+        expected.encode(&mut expected_buffer).unwrap();
+        assert_eq!(expected_buffer, buffer);
+
+        let (remaining, decoded) = PublishCommand::decode(&buffer).unwrap();
+        assert_eq!(expected, decoded);
+        assert_eq!(2, decoded.published_messages[0].records());
+        assert!(remaining.is_empty());
+    }
 }
@@ -4,29 +4,50 @@ use crate::{
     protocol::commands::COMMAND_QUERY_OFFSET,
     FromResponse, ResponseCode,
 };
-use std::io::Write;
+use std::{io::Write, sync::Arc};
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct QueryOffsetRequest {
     correlation_id: u32,
-    reference: String,
-    stream: String,
+    #[cfg_attr(
+        test,
+        dummy(expr = "::fake::Fake::fake::<String>(&::fake::Faker).into()")
+    )]
+    reference: Arc<str>,
+    #[cfg_attr(
+        test,
+        dummy(expr = "::fake::Fake::fake::<String>(&::fake::Faker).into()")
+    )]
+    stream: Arc<str>,
 }
 
 impl QueryOffsetRequest {
-    pub fn new(correlation_id: u32, reference: String, stream: String) -> Self {
+    pub fn new(
+        correlation_id: u32,
+        reference: impl Into<Arc<str>>,
+        stream: impl Into<Arc<str>>,
+    ) -> Self {
         Self {
             correlation_id,
-            reference,
-            stream,
+            reference: reference.into(),
+            stream: stream.into(),
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
 }
 
 impl Encoder for QueryOffsetRequest {
@@ -37,15 +58,15 @@ impl Encoder for QueryOffsetRequest {
         }
 
         self.correlation_id.encode(writer)?;
-        self.reference.as_str().encode(writer)?;
-        self.stream.as_str().encode(writer)?;
+        self.reference.as_ref().encode(writer)?;
+        self.stream.as_ref().encode(writer)?;
         Ok(())
     }
 
     fn encoded_size(&self) -> u32 {
         self.correlation_id.encoded_size()
-            + self.stream.as_str().encoded_size()
-            + self.reference.as_str().encoded_size()
+            + self.stream.as_ref().encoded_size()
+            + self.reference.as_ref().encoded_size()
     }
 }
 
@@ -69,8 +90,8 @@ impl Decoder for QueryOffsetRequest {
                         input,
                         QueryOffsetRequest {
                             correlation_id,
-                            reference,
-                            stream: opt_stream.unwrap(),
+                            reference: reference.into(),
+                            stream: opt_stream.unwrap_or_default().into(),
                         },
                     ));
                 }
@@ -102,6 +123,19 @@ impl QueryOffsetResponse {
     pub fn from_response(&self) -> u64 {
         self.offset
     }
+
+    /// Stored offset, meaningful only when the response is ok
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn code(&self) -> &ResponseCode {
+        &self.response_code
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.response_code == ResponseCode::Ok
+    }
 }
 
 impl Encoder for QueryOffsetResponse {
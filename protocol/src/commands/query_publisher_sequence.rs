@@ -4,29 +4,50 @@ use crate::{
     protocol::commands::COMMAND_QUERY_PUBLISHER_SEQUENCE,
     FromResponse, ResponseCode,
 };
-use std::io::Write;
+use std::{io::Write, sync::Arc};
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct QueryPublisherRequest {
     correlation_id: u32,
-    publisher_reference: String,
-    stream: String,
+    #[cfg_attr(
+        test,
+        dummy(expr = "::fake::Fake::fake::<String>(&::fake::Faker).into()")
+    )]
+    publisher_reference: Arc<str>,
+    #[cfg_attr(
+        test,
+        dummy(expr = "::fake::Fake::fake::<String>(&::fake::Faker).into()")
+    )]
+    stream: Arc<str>,
 }
 
 impl QueryPublisherRequest {
-    pub fn new(correlation_id: u32, publisher_reference: String, stream: String) -> Self {
+    pub fn new(
+        correlation_id: u32,
+        publisher_reference: impl Into<Arc<str>>,
+        stream: impl Into<Arc<str>>,
+    ) -> Self {
         Self {
             correlation_id,
-            publisher_reference,
-            stream,
+            publisher_reference: publisher_reference.into(),
+            stream: stream.into(),
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn publisher_reference(&self) -> &str {
+        &self.publisher_reference
+    }
+
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
 }
 
 impl Encoder for QueryPublisherRequest {
@@ -37,15 +58,15 @@ impl Encoder for QueryPublisherRequest {
         }
 
         self.correlation_id.encode(writer)?;
-        self.publisher_reference.as_str().encode(writer)?;
-        self.stream.as_str().encode(writer)?;
+        self.publisher_reference.as_ref().encode(writer)?;
+        self.stream.as_ref().encode(writer)?;
         Ok(())
     }
 
     fn encoded_size(&self) -> u32 {
         self.correlation_id.encoded_size()
-            + self.stream.as_str().encoded_size()
-            + self.publisher_reference.as_str().encoded_size()
+            + self.stream.as_ref().encoded_size()
+            + self.publisher_reference.as_ref().encoded_size()
     }
 }
 
@@ -69,8 +90,8 @@ impl Decoder for QueryPublisherRequest {
                         input,
                         QueryPublisherRequest {
                             correlation_id,
-                            publisher_reference,
-                            stream: opt_stream.unwrap(),
+                            publisher_reference: publisher_reference.into(),
+                            stream: opt_stream.unwrap_or_default().into(),
                         },
                     ));
                 }
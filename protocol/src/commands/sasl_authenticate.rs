@@ -1,4 +1,4 @@
-use std::io::Write;
+use std::{fmt, io::Write};
 
 use crate::{
     codec::{Decoder, Encoder},
@@ -8,11 +8,8 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq)]
 pub struct SaslAuthenticateCommand {
     correlation_id: u32,
     mechanism: String,
@@ -27,6 +24,29 @@ impl SaslAuthenticateCommand {
             sasl_data,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn mechanism(&self) -> &str {
+        &self.mechanism
+    }
+
+    pub fn sasl_data(&self) -> &[u8] {
+        &self.sasl_data
+    }
+}
+
+/// The SASL data holds the credentials, it is left out
+impl fmt::Debug for SaslAuthenticateCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SaslAuthenticateCommand")
+            .field("correlation_id", &self.correlation_id)
+            .field("mechanism", &self.mechanism)
+            .field("sasl_data", &"***")
+            .finish()
+    }
 }
 
 impl Encoder for SaslAuthenticateCommand {
@@ -54,7 +74,7 @@ impl Decoder for SaslAuthenticateCommand {
             input,
             SaslAuthenticateCommand {
                 correlation_id,
-                mechanism: mechanism.unwrap(),
+                mechanism: mechanism.unwrap_or_default(),
                 sasl_data,
             },
         ))
@@ -78,4 +98,13 @@ mod tests {
     fn sasl_authenticate_request_test() {
         command_encode_decode_test::<SaslAuthenticateCommand>()
     }
+
+    #[test]
+    fn sasl_data_redacted_test() {
+        let command =
+            SaslAuthenticateCommand::new(1, "PLAIN".to_owned(), b"\0guest\0s3cr3t".to_vec());
+        let debug = format!("{:?}", command);
+        assert!(!debug.contains("s3cr3t"));
+        assert!(debug.contains(r#"sasl_data: "***""#));
+    }
 }
@@ -10,9 +10,6 @@ use crate::{
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct SaslHandshakeCommand {
@@ -23,6 +20,10 @@ impl SaslHandshakeCommand {
     pub fn new(correlation_id: u32) -> Self {
         Self { correlation_id }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
 }
 
 impl Encoder for SaslHandshakeCommand {
@@ -59,6 +60,10 @@ pub struct SaslHandshakeResponse {
 }
 
 impl SaslHandshakeResponse {
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+
     pub fn mechanisms(&self) -> &Vec<String> {
         &self.mechanisms
     }
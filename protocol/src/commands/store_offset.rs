@@ -3,29 +3,46 @@ use crate::{
     error::{DecodeError, EncodeError},
     protocol::commands::COMMAND_STORE_OFFSET,
 };
-use std::io::Write;
+use std::{io::Write, sync::Arc};
 
 use super::Command;
 
-#[cfg(test)]
-use fake::Fake;
-
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(PartialEq, Debug)]
 pub struct StoreOffset {
-    reference: String,
-    stream: String,
+    #[cfg_attr(
+        test,
+        dummy(expr = "::fake::Fake::fake::<String>(&::fake::Faker).into()")
+    )]
+    reference: Arc<str>,
+    #[cfg_attr(
+        test,
+        dummy(expr = "::fake::Fake::fake::<String>(&::fake::Faker).into()")
+    )]
+    stream: Arc<str>,
     offset: u64,
 }
 
 impl StoreOffset {
-    pub fn new(reference: String, stream: String, offset: u64) -> Self {
+    pub fn new(reference: impl Into<Arc<str>>, stream: impl Into<Arc<str>>, offset: u64) -> Self {
         Self {
-            reference,
-            stream,
+            reference: reference.into(),
+            stream: stream.into(),
             offset,
         }
     }
+
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
 }
 
 impl Encoder for StoreOffset {
@@ -35,15 +52,15 @@ impl Encoder for StoreOffset {
             return Err(EncodeError::MaxSizeError(size));
         }
 
-        self.reference.as_str().encode(writer)?;
-        self.stream.as_str().encode(writer)?;
+        self.reference.as_ref().encode(writer)?;
+        self.stream.as_ref().encode(writer)?;
         self.offset.encode(writer)?;
         Ok(())
     }
 
     fn encoded_size(&self) -> u32 {
-        self.reference.as_str().encoded_size()
-            + self.stream.as_str().encoded_size()
+        self.reference.as_ref().encoded_size()
+            + self.stream.as_ref().encoded_size()
             + self.offset.encoded_size()
     }
 }
@@ -65,8 +82,8 @@ impl Decoder for StoreOffset {
                     return Ok((
                         input,
                         StoreOffset {
-                            reference,
-                            stream,
+                            reference: reference.into(),
+                            stream: stream.into(),
                             offset,
                         },
                     ));
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::{
+    codec::{Decoder, Encoder},
+    error::{DecodeError, EncodeError},
+    protocol::commands::COMMAND_STREAM_STATS,
+    FromResponse, ResponseCode,
+};
+
+use super::Command;
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(PartialEq, Debug)]
+pub struct StreamStatsCommand {
+    correlation_id: u32,
+    stream: String,
+}
+
+impl StreamStatsCommand {
+    pub fn new(correlation_id: u32, stream: String) -> Self {
+        Self {
+            correlation_id,
+            stream,
+        }
+    }
+}
+
+impl Encoder for StreamStatsCommand {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size() + self.stream.as_str().encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.stream.as_str().encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for StreamStatsCommand {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, stream) = Option::decode(input)?;
+
+        Ok((
+            input,
+            StreamStatsCommand {
+                correlation_id,
+                stream: stream.unwrap_or_default(),
+            },
+        ))
+    }
+}
+
+impl Command for StreamStatsCommand {
+    fn key(&self) -> u16 {
+        COMMAND_STREAM_STATS
+    }
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq)]
+pub struct StreamStatsResponse {
+    pub(crate) correlation_id: u32,
+    code: ResponseCode,
+    pub stats: HashMap<String, i64>,
+}
+
+impl StreamStatsResponse {
+    /// Get a reference to the stream stats response's code.
+    pub fn code(&self) -> &ResponseCode {
+        &self.code
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code == ResponseCode::Ok
+    }
+}
+
+impl Encoder for StreamStatsResponse {
+    fn encoded_size(&self) -> u32 {
+        self.correlation_id.encoded_size() + self.code.encoded_size() + self.stats.encoded_size()
+    }
+
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        self.correlation_id.encode(writer)?;
+        self.code.encode(writer)?;
+        self.stats.encode(writer)?;
+        Ok(())
+    }
+}
+
+impl Decoder for StreamStatsResponse {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        let (input, correlation_id) = u32::decode(input)?;
+        let (input, code) = ResponseCode::decode(input)?;
+        let (input, stats) = HashMap::decode(input)?;
+
+        Ok((
+            input,
+            StreamStatsResponse {
+                correlation_id,
+                code,
+                stats,
+            },
+        ))
+    }
+}
+
+impl FromResponse for StreamStatsResponse {
+    fn from_response(response: crate::Response) -> Option<Self> {
+        match response.kind {
+            crate::ResponseKind::StreamStats(stats) => Some(stats),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::tests::command_encode_decode_test;
+
+    use super::{StreamStatsCommand, StreamStatsResponse};
+
+    #[test]
+    fn stream_stats_request_test() {
+        command_encode_decode_test::<StreamStatsCommand>();
+    }
+
+    #[test]
+    fn stream_stats_response_test() {
+        command_encode_decode_test::<StreamStatsResponse>();
+    }
+}
@@ -1,9 +1,6 @@
 use std::collections::HashMap;
 use std::io::Write;
 
-#[cfg(test)]
-use fake::Fake;
-
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
@@ -41,6 +38,31 @@ impl SubscribeCommand {
             properties,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn subscription_id(&self) -> u8 {
+        self.subscription_id
+    }
+
+    pub fn stream_name(&self) -> &str {
+        &self.stream_name
+    }
+
+    pub fn offset_specification(&self) -> &OffsetSpecification {
+        &self.offset_specification
+    }
+
+    /// Credits granted with the subscription
+    pub fn credit(&self) -> u16 {
+        self.credit
+    }
+
+    pub fn properties(&self) -> &HashMap<String, String> {
+        &self.properties
+    }
 }
 
 impl Encoder for SubscribeCommand {
@@ -78,7 +100,7 @@ impl Decoder for SubscribeCommand {
             SubscribeCommand {
                 correlation_id,
                 subscription_id,
-                stream_name: stream_name.unwrap(),
+                stream_name: stream_name.unwrap_or_default(),
                 offset_specification,
                 credit,
                 properties,
@@ -94,12 +116,18 @@ impl Command for SubscribeCommand {
 }
 
 #[cfg_attr(test, derive(fake::Dummy))]
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Default)]
 pub enum OffsetSpecification {
+    /// Start from the first message available in the stream
     First,
+    /// Start from the last chunk written to the stream
     Last,
+    /// Start with the next message written to the stream
+    #[default]
     Next,
+    /// Start from the given offset
     Offset(u64),
+    /// Start from the first chunk written at or after the given timestamp, in milliseconds
     Timestamp(i64),
 }
 
@@ -151,7 +179,7 @@ impl Decoder for OffsetSpecification {
                 let (input, timestamp) = i64::decode(input)?;
                 Ok((input, OffsetSpecification::Timestamp(timestamp)))
             }
-            _ => panic!("Offset type not supported"),
+            offset_type => Err(DecodeError::UnsupportedOffsetType(offset_type)),
         }
     }
 }
@@ -160,10 +188,19 @@ impl Decoder for OffsetSpecification {
 mod tests {
     use crate::commands::tests::command_encode_decode_test;
 
-    use super::SubscribeCommand;
+    use super::{OffsetSpecification, SubscribeCommand};
+    use crate::{codec::Decoder, error::DecodeError};
 
     #[test]
     fn subscribe_request_test() {
         command_encode_decode_test::<SubscribeCommand>();
     }
+
+    #[test]
+    fn offset_specification_unsupported_type_test() {
+        assert!(matches!(
+            OffsetSpecification::decode(&[0, 6]),
+            Err(DecodeError::UnsupportedOffsetType(6))
+        ));
+    }
 }
@@ -1,8 +1,5 @@
 use std::io::Write;
 
-#[cfg(test)]
-use fake::Fake;
-
 use crate::{
     codec::{Decoder, Encoder},
     error::{DecodeError, EncodeError},
@@ -25,6 +22,14 @@ impl UnSubscribeCommand {
             subscription_id,
         }
     }
+
+    pub fn correlation_id(&self) -> u32 {
+        self.correlation_id
+    }
+
+    pub fn subscription_id(&self) -> u8 {
+        self.subscription_id
+    }
 }
 
 impl Encoder for UnSubscribeCommand {
@@ -0,0 +1,361 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::error::{DecodeError, EncodeError};
+
+/// Most bytes reserved ahead for the decompressed records, the declared size is not trusted
+const MAX_PREALLOCATION: usize = 1024 * 1024;
+/// Start of the dictionaries trained by zstd, followed by their id
+const ZSTD_DICTIONARY_MAGIC: u32 = 0xEC30_A437;
+
+/// Compression codec applied to a sub-entry of a publish or deliver frame
+///
+/// Gzip is always available, the other codecs require the crate feature with the same name
+/// (`snappy`, `lz4`, `zstd`).
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+impl Compression {
+    pub(crate) fn code(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Snappy => 2,
+            Compression::Lz4 => 3,
+            Compression::Zstd => 4,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Result<Self, DecodeError> {
+        match code {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Gzip),
+            2 => Ok(Compression::Snappy),
+            3 => Ok(Compression::Lz4),
+            4 => Ok(Compression::Zstd),
+            code => Err(DecodeError::UnsupportedCompression(code)),
+        }
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => {
+                let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+                encoder.write_all(data)?;
+                encoder
+                    .into_inner()
+                    .map_err(|err| EncodeError::Io(err.into_error()))
+            }
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(data)?;
+                encoder
+                    .finish()
+                    .map_err(|err| EncodeError::Io(std::io::Error::other(err)))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::stream::encode_all(data, 0)?),
+            #[allow(unreachable_patterns)]
+            compression => Err(EncodeError::UnsupportedCompression(compression.code())),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8], uncompressed_len: usize) -> Result<Vec<u8>, DecodeError> {
+        let mut output = Vec::with_capacity(uncompressed_len.min(MAX_PREALLOCATION));
+        // one byte more than declared tells a corrupted size from the exact one
+        let limit = uncompressed_len as u64 + 1;
+        let result = match self {
+            Compression::None => {
+                output.extend_from_slice(data);
+                return Ok(output);
+            }
+            Compression::Gzip => flate2::read::GzDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output),
+            #[cfg(feature = "snappy")]
+            Compression::Snappy => snap::read::FrameDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => lz4_flex::frame::FrameDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut output),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => match zstd::zstd_safe::get_dict_id_from_frame(data) {
+                None => zstd::stream::read::Decoder::new(data)
+                    .and_then(|decoder| decoder.take(limit).read_to_end(&mut output)),
+                Some(id) => match ZstdDictionary::registered(id.get()) {
+                    Some(dictionary) => zstd::stream::read::Decoder::with_prepared_dictionary(
+                        std::io::BufReader::new(data),
+                        &dictionary.0.decoder,
+                    )
+                    .and_then(|decoder| decoder.take(limit).read_to_end(&mut output)),
+                    None => Err(std::io::Error::other(format!(
+                        "Unknown zstd dictionary {}",
+                        id
+                    ))),
+                },
+            },
+            #[allow(unreachable_patterns)]
+            compression => return Err(DecodeError::UnsupportedCompression(compression.code())),
+        };
+
+        result.map_err(DecodeError::Decompression)?;
+        if output.len() > uncompressed_len {
+            return Err(DecodeError::MismatchSize(output.len()));
+        }
+        Ok(output)
+    }
+}
+
+/// Dictionary trained by zstd on samples of the messages, for sub-entries of small similar
+/// messages
+///
+/// The producers compress with it, the consumers find the dictionary of a sub-entry by its
+/// id among the ones registered in the process with [`ZstdDictionary::register`]. Requires
+/// the `zstd` feature to compress and decompress.
+#[derive(Clone)]
+pub struct ZstdDictionary(Arc<ZstdDictionaryInner>);
+
+struct ZstdDictionaryInner {
+    id: u32,
+    data: Vec<u8>,
+    #[cfg(feature = "zstd")]
+    encoder: zstd::dict::EncoderDictionary<'static>,
+    #[cfg(feature = "zstd")]
+    decoder: zstd::dict::DecoderDictionary<'static>,
+}
+
+/// Dictionaries of the consumers, by id
+fn zstd_dictionaries() -> &'static RwLock<HashMap<u32, ZstdDictionary>> {
+    static DICTIONARIES: OnceLock<RwLock<HashMap<u32, ZstdDictionary>>> = OnceLock::new();
+    DICTIONARIES.get_or_init(Default::default)
+}
+
+impl ZstdDictionary {
+    /// Dictionary trained by zstd, e.g. with `zstd --train`
+    ///
+    /// Fails when `dictionary` is not a trained dictionary, raw content has no id to find it
+    /// with when decompressing.
+    pub fn new(dictionary: impl Into<Vec<u8>>) -> std::io::Result<Self> {
+        let data = dictionary.into();
+        let id = match data.get(..8) {
+            Some(header) if header[..4] == ZSTD_DICTIONARY_MAGIC.to_le_bytes() => {
+                u32::from_le_bytes([header[4], header[5], header[6], header[7]])
+            }
+            _ => 0,
+        };
+        if id == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Not a trained zstd dictionary",
+            ));
+        }
+        // preparing a corrupted dictionary panics, loading it fails
+        #[cfg(feature = "zstd")]
+        {
+            zstd::bulk::Compressor::with_dictionary(0, &data)?;
+            zstd::bulk::Decompressor::with_dictionary(&data)?;
+        }
+        Ok(ZstdDictionary(Arc::new(ZstdDictionaryInner {
+            id,
+            #[cfg(feature = "zstd")]
+            encoder: zstd::dict::EncoderDictionary::copy(&data, 0),
+            #[cfg(feature = "zstd")]
+            decoder: zstd::dict::DecoderDictionary::copy(&data),
+            data,
+        })))
+    }
+
+    /// Train a dictionary of at most `max_size` bytes on `samples`, e.g. encoded messages
+    #[cfg(feature = "zstd")]
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> std::io::Result<Self> {
+        Self::new(zstd::dict::from_samples(samples, max_size)?)
+    }
+
+    /// Id of the dictionary, written in the frames compressed with it
+    pub fn id(&self) -> u32 {
+        self.0.id
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0.data
+    }
+
+    /// Make the dictionary available to decompress the sub-entries of the process
+    pub fn register(&self) {
+        zstd_dictionaries()
+            .write()
+            .unwrap()
+            .insert(self.id(), self.clone());
+    }
+
+    #[cfg(feature = "zstd")]
+    pub(crate) fn registered(id: u32) -> Option<ZstdDictionary> {
+        zstd_dictionaries().read().unwrap().get(&id).cloned()
+    }
+
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>, EncodeError> {
+        #[cfg(feature = "zstd")]
+        {
+            let mut encoder = zstd::stream::write::Encoder::with_prepared_dictionary(
+                Vec::new(),
+                &self.0.encoder,
+            )?;
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        #[cfg(not(feature = "zstd"))]
+        {
+            let _ = data;
+            Err(EncodeError::UnsupportedCompression(
+                Compression::Zstd.code(),
+            ))
+        }
+    }
+}
+
+impl std::fmt::Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZstdDictionary")
+            .field("id", &self.0.id)
+            .field("size", &self.0.data.len())
+            .finish()
+    }
+}
+
+impl PartialEq for ZstdDictionary {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id && self.0.data == other.0.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, ZstdDictionary};
+    use crate::error::DecodeError;
+
+    fn compression_roundtrip_test(compression: Compression) {
+        let data = b"message message message message".repeat(10);
+
+        let compressed = compression.compress(&data).unwrap();
+        let decompressed = compression.decompress(&compressed, data.len()).unwrap();
+
+        assert_eq!(data, decompressed);
+    }
+
+    #[test]
+    fn decompress_oversized_test() {
+        let data = b"message message message message".repeat(10);
+        let compressed = Compression::Gzip.compress(&data).unwrap();
+
+        assert!(matches!(
+            Compression::Gzip.decompress(&compressed, 10),
+            Err(DecodeError::MismatchSize(11))
+        ));
+        // the declared size is only a hint for the allocation
+        assert_eq!(
+            data,
+            Compression::Gzip
+                .decompress(&compressed, u32::MAX as usize)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn none_compression_test() {
+        compression_roundtrip_test(Compression::None);
+    }
+
+    #[test]
+    fn gzip_compression_test() {
+        compression_roundtrip_test(Compression::Gzip);
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn snappy_compression_test() {
+        compression_roundtrip_test(Compression::Snappy);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_compression_test() {
+        compression_roundtrip_test(Compression::Lz4);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_compression_test() {
+        compression_roundtrip_test(Compression::Zstd);
+    }
+
+    #[test]
+    fn untrained_dictionary_test() {
+        assert!(ZstdDictionary::new(b"raw content".to_vec()).is_err());
+        let mut corrupted = 0xEC30_A437u32.to_le_bytes().to_vec();
+        corrupted.extend_from_slice(&42u32.to_le_bytes());
+        corrupted.extend_from_slice(&[0; 8]);
+        #[cfg(feature = "zstd")]
+        assert!(ZstdDictionary::new(corrupted).is_err());
+        #[cfg(not(feature = "zstd"))]
+        assert_eq!(42, ZstdDictionary::new(corrupted).unwrap().id());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_dictionary_test() {
+        let samples: Vec<Vec<u8>> = (0..1000)
+            .map(|n| {
+                format!(
+                    "{{\"order\":{},\"status\":\"shipped\",\"country\":\"IT\"}}",
+                    n
+                )
+                .into_bytes()
+            })
+            .collect();
+        let dictionary = ZstdDictionary::train(&samples, 1024).unwrap();
+        let data = &samples[7];
+
+        let compressed = dictionary.compress(data).unwrap();
+        assert!(compressed.len() < Compression::Zstd.compress(data).unwrap().len());
+        // unknown until registered
+        assert!(matches!(
+            Compression::Zstd.decompress(&compressed, data.len()),
+            Err(DecodeError::Decompression(_))
+        ));
+        dictionary.register();
+        assert_eq!(
+            data,
+            &Compression::Zstd
+                .decompress(&compressed, data.len())
+                .unwrap()
+        );
+        // frames without dictionary are still decompressed
+        let plain = Compression::Zstd.compress(data).unwrap();
+        assert_eq!(
+            data,
+            &Compression::Zstd.decompress(&plain, data.len()).unwrap()
+        );
+    }
+}
@@ -7,7 +7,18 @@ pub enum DecodeError {
     UnknownResponseCode(u16),
     UnsupportedResponseType(u16),
     MismatchSize(usize),
+    /// Negative size or count
+    InvalidLength(i32),
     MessageParse(String),
+    UnsupportedCompression(u8),
+    UnsupportedOffsetType(u16),
+    Decompression(std::io::Error),
+    /// The CRC32 of the entries of a chunk differs from the one of its header
+    ChunkCrcMismatch {
+        first_offset: u64,
+        expected: u32,
+        computed: u32,
+    },
     Empty,
 }
 
@@ -15,6 +26,32 @@ pub enum DecodeError {
 pub enum EncodeError {
     Io(std::io::Error),
     MaxSizeError(usize),
+    UnsupportedCompression(u8),
+}
+
+/// Failure to convert a message to its body
+#[derive(Debug)]
+pub enum BodyError {
+    /// The body is not a data section
+    NotData,
+    Utf8Error(FromUtf8Error),
+}
+
+impl std::fmt::Display for BodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BodyError::NotData => write!(f, "Message body is not a data section"),
+            BodyError::Utf8Error(err) => write!(f, "Message body is not UTF-8: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for BodyError {}
+
+impl From<FromUtf8Error> for BodyError {
+    fn from(err: FromUtf8Error) -> Self {
+        BodyError::Utf8Error(err)
+    }
 }
 
 impl From<std::io::Error> for EncodeError {
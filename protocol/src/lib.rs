@@ -1,5 +1,7 @@
+mod buffer_pool;
 pub mod codec;
 pub mod commands;
+pub mod compression;
 pub mod error;
 pub mod message;
 mod protocol;
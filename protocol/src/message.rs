@@ -1,15 +1,51 @@
-use ntex_amqp_codec::{Encode, Message as AmpqMessage};
-use ntex_bytes::BytesMut;
+use std::{convert::TryFrom, io::Write, sync::Arc, time::SystemTime};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use bytes::Bytes;
+use ntex_amqp_codec::{
+    protocol::Section, types::List, Decode, Encode, Message as AmpqMessage, MessageBody,
+};
 
 use crate::{
-    codec::{Decoder, Encoder},
-    error::DecodeError,
+    buffer_pool::with_amqp_buffer,
+    codec::{
+        decoder::{check_len, read_u32, read_u8},
+        Decoder, Encoder,
+    },
+    error::{BodyError, DecodeError, EncodeError},
 };
 
-#[derive(Debug, PartialEq, Clone)]
+mod amqp;
+pub mod amqp091;
+mod batch;
+mod validate;
+
+pub use amqp::{MessageId, Properties, Value};
+pub use batch::{MessageBatch, MessageBatchBuilder};
+
+#[derive(Debug, Clone)]
 pub struct Message {
     pub(crate) publishing_id: Option<u64>,
     pub(crate) message: AmpqMessage,
+    /// Data section of the body, kept out of `message` to share the bytes it is
+    /// built or decoded from
+    pub(crate) body: Option<Bytes>,
+    /// AMQP sequence sections of the body, kept out of `message` as the AMQP codec
+    /// writes lists as arrays
+    pub(crate) sequences: Vec<List>,
+    /// Encoding of the sections of `message`, shared by the messages of a batch
+    pub(crate) encoded_sections: Option<Arc<[u8]>>,
+    /// AMQP encoding shared by the clones, written as is when present
+    pub(crate) encoded: Option<Arc<[u8]>>,
+}
+
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        self.publishing_id == other.publishing_id
+            && self.message == other.message
+            && self.body == other.body
+            && self.sequences == other.sequences
+    }
 }
 
 unsafe impl Send for Message {}
@@ -17,30 +53,383 @@ unsafe impl Sync for Message {}
 
 impl Encoder for Message {
     fn encoded_size(&self) -> u32 {
-        self.message.encoded_size() as u32
+        match &self.encoded {
+            Some(encoded) => encoded.len() as u32,
+            None => {
+                let body = self.body.as_ref().map_or(0, |body| data_section_size(body));
+                let sequences: usize = self.sequences.iter().map(sequence_section_size).sum();
+                let sections = match &self.encoded_sections {
+                    Some(sections) => sections.len(),
+                    None => self.message.encoded_size(),
+                };
+                (sections + body + sequences) as u32
+            }
+        }
     }
 
-    fn encode(&self, writer: &mut impl std::io::Write) -> Result<(), crate::error::EncodeError> {
-        let mut buf = BytesMut::with_capacity(self.encoded_size() as usize);
+    fn encode(&self, writer: &mut impl Write) -> Result<(), EncodeError> {
+        if let Some(encoded) = &self.encoded {
+            writer.write_all(encoded)?;
+            return Ok(());
+        }
 
-        ntex_amqp_codec::Encode::encode(&self.message, &mut buf);
-
-        writer.write_all(&buf)?;
+        match &self.encoded_sections {
+            Some(sections) => writer.write_all(sections)?,
+            None => {
+                let sections = self.message.encoded_size();
+                if sections > 0 {
+                    with_amqp_buffer(sections, |buf| {
+                        ntex_amqp_codec::Encode::encode(&self.message, buf);
+                        writer.write_all(buf)
+                    })?;
+                }
+            }
+        }
+        // written last, `body` and `sequences` are only set when `message` has no footer
+        if let Some(body) = &self.body {
+            write_data_section(writer, body)?;
+        }
+        for sequence in &self.sequences {
+            write_sequence_section(writer, sequence)?;
+        }
 
         Ok(())
     }
 }
 
+const FORMAT_DESCRIBED: u8 = 0x00;
+const FORMAT_SMALL_ULONG: u8 = 0x53;
+const FORMAT_ULONG: u8 = 0x80;
+const FORMAT_BINARY8: u8 = 0xa0;
+const FORMAT_BINARY32: u8 = 0xb0;
+const FORMAT_LIST8: u8 = 0xc0;
+const FORMAT_LIST32: u8 = 0xd0;
+const SECTION_DATA: u8 = 0x75;
+const SECTION_SEQUENCE: u8 = 0x76;
+
+fn data_section_size(data: &[u8]) -> usize {
+    match data.len() > u8::MAX as usize {
+        true => 3 + 5 + data.len(),
+        false => 3 + 2 + data.len(),
+    }
+}
+
+fn write_data_section(writer: &mut impl Write, data: &[u8]) -> Result<(), EncodeError> {
+    writer.write_all(&[FORMAT_DESCRIBED, FORMAT_SMALL_ULONG, SECTION_DATA])?;
+    if data.len() > u8::MAX as usize {
+        writer.write_u8(FORMAT_BINARY32)?;
+        writer.write_u32::<BigEndian>(data.len() as u32)?;
+    } else {
+        writer.write_u8(FORMAT_BINARY8)?;
+        writer.write_u8(data.len() as u8)?;
+    }
+    writer.write_all(data)?;
+    Ok(())
+}
+
+/// Encoded size of the items of a list, `true` when it needs the 32 bits format
+fn list_size(list: &List) -> (usize, bool) {
+    let items: usize = list.0.iter().map(|item| item.encoded_size()).sum();
+    (
+        items,
+        items + 1 > u8::MAX as usize || list.0.len() > u8::MAX as usize,
+    )
+}
+
+fn sequence_section_size(sequence: &List) -> usize {
+    match list_size(sequence) {
+        (items, true) => 3 + 9 + items,
+        (items, false) => 3 + 3 + items,
+    }
+}
+
+fn write_sequence_section(writer: &mut impl Write, sequence: &List) -> Result<(), EncodeError> {
+    writer.write_all(&[FORMAT_DESCRIBED, FORMAT_SMALL_ULONG, SECTION_SEQUENCE])?;
+    let (items, large) = list_size(sequence);
+    if large {
+        writer.write_u8(FORMAT_LIST32)?;
+        writer.write_u32::<BigEndian>((items + 4) as u32)?;
+        writer.write_u32::<BigEndian>(sequence.0.len() as u32)?;
+    } else {
+        writer.write_u8(FORMAT_LIST8)?;
+        writer.write_u8((items + 1) as u8)?;
+        writer.write_u8(sequence.0.len() as u8)?;
+    }
+    with_amqp_buffer(items, |buf| {
+        for item in sequence.0.iter() {
+            item.encode(buf);
+        }
+        writer.write_all(buf)
+    })?;
+    Ok(())
+}
+
+/// Remaining input and bytes of a data section
+type DataSection<'a> = (&'a [u8], &'a [u8]);
+
+/// Read a data section, `None` when `input` starts with another section
+fn read_data_section(input: &[u8]) -> Result<Option<DataSection<'_>>, DecodeError> {
+    let input = match input {
+        [FORMAT_DESCRIBED, FORMAT_SMALL_ULONG, SECTION_DATA, input @ ..] => input,
+        [FORMAT_DESCRIBED, FORMAT_ULONG, 0, 0, 0, 0, 0, 0, 0, SECTION_DATA, input @ ..] => input,
+        _ => return Ok(None),
+    };
+    let (input, format) = read_u8(input)?;
+    let (input, len) = match format {
+        FORMAT_BINARY8 => read_u8(input).map(|(input, len)| (input, len as usize))?,
+        FORMAT_BINARY32 => read_u32(input).map(|(input, len)| (input, len as usize))?,
+        _ => return Ok(None),
+    };
+    check_len(input, len)?;
+    let (data, input) = input.split_at(len);
+    Ok(Some((input, data)))
+}
+
+/// Message without its body, the bytes of its data section and its sequence sections
+type Sections<'a> = (AmpqMessage, Option<&'a [u8]>, Vec<List>);
+
+/// Decode the sections of a message with at most one data section, the body is
+/// returned apart. `None` when the message has sections only the AMQP codec handles
+fn decode_sections(mut input: &[u8]) -> Result<Option<Sections<'_>>, DecodeError> {
+    let mut message = AmpqMessage::default();
+    let mut body = None;
+    let mut sequences = Vec::new();
+    while !input.is_empty() {
+        if let Some((remaining, data)) = read_data_section(input)? {
+            if body.replace(data).is_some() {
+                return Ok(None);
+            }
+            input = remaining;
+            continue;
+        }
+        let (remaining, section) =
+            Section::decode(input).map_err(|err| DecodeError::MessageParse(err.to_string()))?;
+        match section {
+            Section::Header(header) => {
+                message.set_header(header);
+            }
+            Section::MessageAnnotations(annotations) => {
+                for (key, value) in annotations.0 {
+                    message.add_message_annotation(key, value);
+                }
+            }
+            Section::Properties(properties) => *message.properties_mut() = properties,
+            Section::ApplicationProperties(properties) => {
+                for (key, value) in properties.0 {
+                    message.set_app_property(key, value);
+                }
+            }
+            Section::AmqpSequence(sequence) => sequences.push(sequence),
+            _ => return Ok(None),
+        }
+        input = remaining;
+    }
+    Ok(Some((message, body, sequences)))
+}
+
 impl Message {
     pub fn builder() -> MessageBuilder {
         MessageBuilder(Message {
             message: AmpqMessage::default(),
             publishing_id: None,
+            body: None,
+            sequences: Vec::new(),
+            encoded_sections: None,
+            encoded: None,
+        })
+    }
+
+    /// Start a builder from the sections of the message, to publish a modified copy
+    pub fn into_builder(self) -> MessageBuilder {
+        MessageBuilder(Message {
+            encoded_sections: None,
+            encoded: None,
+            ..self
         })
     }
 
+    /// Start a message with `value` serialized to JSON as body and `application/json`
+    /// as content type
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(
+        value: &T,
+    ) -> Result<MessageBuilder, serde_json::Error> {
+        Ok(Message::builder()
+            .body(serde_json::to_vec(value)?)
+            .content_type("application/json"))
+    }
+
+    /// Start a message with `value` encoded to protobuf as body and
+    /// `application/x-protobuf` as content type
+    #[cfg(feature = "prost")]
+    pub fn protobuf<M: prost::Message>(value: &M) -> MessageBuilder {
+        Message::builder()
+            .body(value.encode_to_vec())
+            .content_type("application/x-protobuf")
+    }
+
+    /// Build a message from its AMQP encoding, kept to publish it without encoding it again
+    pub fn from_encoded(encoded: Arc<[u8]>) -> Result<Self, DecodeError> {
+        let (_, mut message) = Message::decode(&encoded)?;
+        message.encoded = Some(encoded);
+        Ok(message)
+    }
+
+    /// Decode a message encoded in `encoded`, its body sharing the bytes
+    pub fn decode_shared(encoded: &Bytes) -> Result<Self, DecodeError> {
+        Message::decode_in(encoded, Some(encoded)).map(|(_, message)| message)
+    }
+
+    /// Encode the message once and share the bytes with its clones
+    ///
+    /// Useful to publish the same message to many streams, the body is no longer
+    /// encoded by every producer.
+    pub fn cache_encoded(&mut self) -> Arc<[u8]> {
+        if let Some(encoded) = &self.encoded {
+            return encoded.clone();
+        }
+        let mut buf = Vec::with_capacity(self.encoded_size() as usize);
+        self.encode(&mut buf)
+            .expect("Encoding to a vector does not fail");
+
+        let encoded: Arc<[u8]> = Arc::from(buf);
+        self.encoded = Some(encoded.clone());
+        encoded
+    }
+
     pub fn data(&self) -> Option<&[u8]> {
-        self.message.body().data().map(|data| data.as_ref())
+        self.body
+            .as_deref()
+            .or_else(|| self.message.body().data().map(|data| data.as_ref()))
+    }
+
+    /// Deserialize the JSON body of the message, the content type is not checked
+    #[cfg(feature = "json")]
+    pub fn body_as_json<T: serde::de::DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(self.data().unwrap_or_default())
+    }
+
+    /// Decode the protobuf body of the message, the content type is not checked
+    #[cfg(feature = "prost")]
+    pub fn body_as_protobuf<M: prost::Message + Default>(&self) -> Result<M, prost::DecodeError> {
+        M::decode(self.data().unwrap_or_default())
+    }
+
+    /// AMQP value body of the message, `None` for the other bodies and the list, map
+    /// and described values
+    pub fn value(&self) -> Option<Value> {
+        self.message.body().value().and_then(Value::from_variant)
+    }
+
+    /// AMQP sequence sections of the body, list, map and described items are skipped
+    pub fn sequences(&self) -> Vec<Vec<Value>> {
+        self.sequences
+            .iter()
+            .map(|sequence| sequence.0.iter().filter_map(Value::from_variant).collect())
+            .collect()
+    }
+
+    /// Body of the message, sharing the bytes of the frame it was decoded from
+    ///
+    /// Holding on to the body keeps the whole frame in memory, copy it to keep a few
+    /// small bodies of a large chunk.
+    pub fn body(&self) -> Option<Bytes> {
+        self.body.clone().or_else(|| {
+            self.message
+                .body()
+                .data()
+                .map(|data| Bytes::copy_from_slice(data))
+        })
+    }
+
+    /// Properties section of the message
+    pub fn properties(&self) -> Option<Properties> {
+        self.message.properties().map(Properties::from)
+    }
+
+    /// Id of the message, set by the application
+    pub fn message_id(&self) -> Option<MessageId> {
+        self.message
+            .properties()
+            .and_then(|properties| properties.message_id.as_ref())
+            .map(MessageId::from)
+    }
+
+    /// Id of the message this message relates to, the request of a reply
+    pub fn correlation_id(&self) -> Option<MessageId> {
+        self.message
+            .properties()
+            .and_then(|properties| properties.correlation_id.as_ref())
+            .map(MessageId::from)
+    }
+
+    /// Address to send the replies to
+    pub fn reply_to(&self) -> Option<&str> {
+        self.message
+            .properties()
+            .and_then(|properties| properties.reply_to.as_deref())
+    }
+
+    /// MIME type of the body
+    pub fn content_type(&self) -> Option<&str> {
+        self.message
+            .properties()
+            .and_then(|properties| properties.content_type.as_ref())
+            .map(|content_type| content_type.as_str())
+    }
+
+    /// Time the message was created at, in milliseconds precision
+    pub fn creation_time(&self) -> Option<SystemTime> {
+        self.message
+            .properties()
+            .and_then(|properties| properties.creation_time.as_ref())
+            .map(amqp::system_time)
+    }
+
+    /// Value of the `key` application property
+    pub fn application_property(&self, key: &str) -> Option<Value> {
+        self.message.app_property(key).and_then(Value::from_variant)
+    }
+
+    /// Application properties of the message, in their encoding order
+    ///
+    /// Properties with list, map or described values are skipped.
+    pub fn application_properties(&self) -> Vec<(String, Value)> {
+        self.message
+            .app_properties()
+            .map(|properties| {
+                properties
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        Value::from_variant(value).map(|value| (key.as_str().to_owned(), value))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Value of the `key` message annotation
+    pub fn message_annotation(&self, key: &str) -> Option<Value> {
+        self.message
+            .message_annotation(key)
+            .and_then(Value::from_variant)
+    }
+
+    /// Message annotations of the message, in their encoding order
+    ///
+    /// Annotations with list, map or described values are skipped.
+    pub fn message_annotations(&self) -> Vec<(String, Value)> {
+        self.message
+            .message_annotations()
+            .map(|annotations| {
+                annotations
+                    .iter()
+                    .filter_map(|(key, value)| {
+                        Value::from_variant(value).map(|value| (key.as_str().to_owned(), value))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// Set the message's publishing id.
@@ -54,36 +443,166 @@ impl Message {
     }
 }
 
-impl Decoder for Message {
-    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
+impl Message {
+    /// Decode a message, its body shares `buffer` when `input` is a slice of it and is
+    /// copied otherwise
+    pub(crate) fn decode_in<'a>(
+        input: &'a [u8],
+        buffer: Option<&Bytes>,
+    ) -> Result<(&'a [u8], Self), DecodeError> {
+        validate::check_encoding(input)?;
+        if let Some((message, body, sequences)) = decode_sections(input)? {
+            let body = body.map(|body| match buffer {
+                Some(buffer) => buffer.slice_ref(body),
+                None => Bytes::copy_from_slice(body),
+            });
+            return Ok((
+                &[],
+                Message {
+                    publishing_id: None,
+                    message,
+                    body,
+                    sequences,
+                    encoded_sections: None,
+                    encoded: None,
+                },
+            ));
+        }
+
         ntex_amqp_codec::Decode::decode(input)
             .map_err(|err| DecodeError::MessageParse(err.to_string()))
-            .map(|message| {
+            .map(|(input, mut message): (_, AmpqMessage)| {
+                let mut sequences = Vec::new();
+                message.set_body(|body| sequences = std::mem::take(&mut body.sequence));
                 (
-                    message.0,
+                    input,
                     Message {
                         publishing_id: None,
-                        message: message.1,
+                        message,
+                        body: None,
+                        sequences,
+                        encoded_sections: None,
+                        encoded: None,
                     },
                 )
             })
     }
 }
 
+impl Decoder for Message {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
+        Message::decode_in(input, None)
+    }
+}
+
 pub struct MessageBuilder(Message);
 
 impl MessageBuilder {
-    pub fn body(mut self, data: impl Into<Vec<u8>>) -> Self {
+    /// Set the body, a [`Bytes`] body is published without being copied
+    pub fn body(mut self, data: impl Into<Bytes>) -> Self {
+        self.0
+            .message
+            .set_body(|body| *body = MessageBody::default());
+        self.0.body = Some(data.into());
+        self.0.sequences.clear();
+        self
+    }
+
+    /// Set an AMQP value as body, replacing the body set before
+    pub fn value(mut self, value: impl Into<Value>) -> Self {
+        let value = value.into().into_variant();
+        self.0.body = None;
+        self.0.sequences.clear();
+        self.0.message.set_body(|body| {
+            *body = MessageBody::default();
+            body.value = Some(value);
+        });
+        self
+    }
+
+    /// Append an AMQP sequence section to the body, replacing a data or value body set
+    /// before
+    pub fn sequence<I, V>(mut self, values: I) -> Self
+    where
+        I: IntoIterator<Item = V>,
+        V: Into<Value>,
+    {
+        let sequence = List(
+            values
+                .into_iter()
+                .map(|value| value.into().into_variant())
+                .collect(),
+        );
+        self.0.body = None;
+        self.0
+            .message
+            .set_body(|body| *body = MessageBody::default());
+        self.0.sequences.push(sequence);
+        self
+    }
+
+    /// Set the properties section, replacing the properties set before
+    pub fn properties(mut self, properties: Properties) -> Self {
+        *self.0.message.properties_mut() = properties.into();
+        self
+    }
+
+    /// Set the id of the message
+    pub fn message_id(mut self, message_id: impl Into<MessageId>) -> Self {
+        self.0.message.properties_mut().message_id = Some(message_id.into().into());
+        self
+    }
+
+    /// Set the id of the message this message relates to, the request of a reply
+    pub fn correlation_id(mut self, correlation_id: impl Into<MessageId>) -> Self {
+        self.0.message.properties_mut().correlation_id = Some(correlation_id.into().into());
+        self
+    }
+
+    /// Set the address to send the replies to
+    pub fn reply_to(mut self, reply_to: impl Into<String>) -> Self {
+        self.0.message.properties_mut().reply_to = Some(reply_to.into().into());
+        self
+    }
+
+    /// Set the MIME type of the body
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.0.message.properties_mut().content_type = Some(content_type.into().into());
+        self
+    }
+
+    /// Set the time the message was created at, truncated to milliseconds
+    pub fn creation_time(mut self, creation_time: SystemTime) -> Self {
+        self.0.message.properties_mut().creation_time = Some(amqp::from_system_time(creation_time));
+        self
+    }
+
+    /// Add an application property, usable by the broker to filter messages
+    pub fn application_property(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.0
+            .message
+            .set_app_property(key.into(), value.into().into_variant());
+        self
+    }
+
+    /// Add a message annotation
+    pub fn message_annotation(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
         self.0
             .message
-            .set_body(|body| body.set_data(data.into().into()));
+            .add_message_annotation(key.into(), value.into().into_variant());
         self
     }
 
-    pub fn publising_id(mut self, publishing_id: u64) -> Self {
+    /// Publishing id used by the broker to deduplicate messages of a named producer
+    pub fn publishing_id(mut self, publishing_id: u64) -> Self {
         self.0.publishing_id = Some(publishing_id);
         self
     }
+
+    #[deprecated(note = "use `publishing_id` instead")]
+    pub fn publising_id(self, publishing_id: u64) -> Self {
+        self.publishing_id(publishing_id)
+    }
     pub fn build(self) -> Message {
         self.0
     }
@@ -94,3 +613,370 @@ impl From<Message> for Vec<Message> {
         vec![message]
     }
 }
+
+macro_rules! message_from {
+    ($($ty:ty),*) => {
+        $(impl From<$ty> for Message {
+            /// Message with `body` as data section
+            fn from(body: $ty) -> Self {
+                Message::builder().body(body).build()
+            }
+        })*
+    };
+}
+
+message_from!(String, Vec<u8>, Bytes);
+
+impl From<&str> for Message {
+    /// Message with a copy of `body` as data section
+    fn from(body: &str) -> Self {
+        Message::builder().body(body.to_owned()).build()
+    }
+}
+
+impl TryFrom<Message> for Vec<u8> {
+    type Error = BodyError;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        message.body().map(Vec::from).ok_or(BodyError::NotData)
+    }
+}
+
+impl TryFrom<Message> for String {
+    type Error = BodyError;
+
+    fn try_from(message: Message) -> Result<Self, Self::Error> {
+        let body = Vec::<u8>::try_from(message)?;
+        Ok(String::from_utf8(body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::TryFrom,
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use ntex_amqp_codec::{Encode, Message as AmpqMessage};
+    use ntex_bytes::BytesMut;
+
+    use crate::{
+        codec::{Decoder, Encoder},
+        error::BodyError,
+    };
+
+    use super::{Message, MessageBatch, MessageId, Properties, Value};
+
+    #[test]
+    fn message_conversions_test() {
+        let message = Message::from("hello");
+        assert_eq!(Some(&b"hello"[..]), message.data());
+        assert_eq!("hello", String::try_from(message).unwrap());
+
+        let message: Message = vec![0xff, 0xfe].into();
+        assert!(String::try_from(message.clone()).is_err());
+        assert_eq!(vec![0xff, 0xfe], Vec::<u8>::try_from(message).unwrap());
+
+        let message = Message::builder().value("value").build();
+        assert!(matches!(
+            Vec::<u8>::try_from(message),
+            Err(BodyError::NotData)
+        ));
+    }
+
+    #[test]
+    fn message_amqp091_headers_test() {
+        let message = Message::builder()
+            .amqp091_headers(vec![
+                ("x-stream-filter-value", Value::from("emea")),
+                ("x-custom", Value::from(1i32)),
+                ("region", Value::from("emea")),
+            ])
+            .message_annotation("internal", "skipped")
+            .build();
+
+        assert_eq!(
+            vec![("region".to_owned(), Value::String("emea".to_owned()))],
+            message.application_properties()
+        );
+        assert_eq!(Some("emea".to_owned()), message.amqp091_filter_value());
+        assert_eq!(
+            vec![
+                (
+                    "x-stream-filter-value".to_owned(),
+                    Value::String("emea".to_owned())
+                ),
+                ("x-custom".to_owned(), Value::Int(1)),
+                ("region".to_owned(), Value::String("emea".to_owned())),
+            ],
+            message.amqp091_headers()
+        );
+    }
+
+    #[test]
+    fn message_batch_test() {
+        let batch = MessageBatch::builder()
+            .content_type("text/plain")
+            .application_property("region", "emea")
+            .body("message0")
+            .bodies(vec!["message1", "message2"])
+            .build();
+        assert_eq!(3, batch.len());
+
+        for (i, message) in batch.into_iter().enumerate() {
+            let expected = Message::builder()
+                .content_type("text/plain")
+                .application_property("region", "emea")
+                .body(format!("message{}", i))
+                .build();
+            let mut buffer = vec![];
+            message.encode(&mut buffer).unwrap();
+            let mut expected_buffer = vec![];
+            expected.encode(&mut expected_buffer).unwrap();
+            assert_eq!(expected_buffer, buffer);
+            assert_eq!(message.encoded_size() as usize, buffer.len());
+
+            assert_eq!(Some("text/plain"), message.content_type());
+            let (_, decoded) = Message::decode(&buffer).unwrap();
+            assert_eq!(
+                Some(Value::String("emea".to_owned())),
+                decoded.application_property("region")
+            );
+            assert_eq!(Some(format!("message{}", i).as_bytes()), decoded.data());
+        }
+
+        let message = MessageBatch::builder().body("data").build().into_messages();
+        let mut buffer = vec![];
+        message[0].encode(&mut buffer).unwrap();
+        assert_eq!(message[0].encoded_size() as usize, buffer.len());
+    }
+
+    #[test]
+    fn message_property_accessors_test() {
+        let creation_time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let message = Message::builder()
+            .body("request")
+            .message_id(1)
+            .correlation_id("request-1")
+            .reply_to("replies")
+            .content_type("application/json")
+            .creation_time(creation_time)
+            .build();
+
+        let mut buffer = vec![];
+        message.encode(&mut buffer).unwrap();
+        let (_, decoded) = Message::decode(&buffer).unwrap();
+
+        assert_eq!(Some(MessageId::Ulong(1)), decoded.message_id());
+        assert_eq!(
+            Some(MessageId::String("request-1".to_owned())),
+            decoded.correlation_id()
+        );
+        assert_eq!(Some("replies"), decoded.reply_to());
+        assert_eq!(Some("application/json"), decoded.content_type());
+        assert_eq!(Some(creation_time), decoded.creation_time());
+
+        let message = Message::builder().body("message").build();
+        assert_eq!(None, message.message_id());
+        assert_eq!(None, message.creation_time());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn message_json_test() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Order {
+            id: u32,
+        }
+
+        let message = Message::json(&Order { id: 1 }).unwrap().build();
+
+        assert_eq!(Some("application/json"), message.content_type());
+        assert_eq!(Some(&br#"{"id":1}"#[..]), message.data());
+        assert_eq!(Order { id: 1 }, message.body_as_json().unwrap());
+        assert!(Message::builder()
+            .body("not json")
+            .build()
+            .body_as_json::<Order>()
+            .is_err());
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn message_protobuf_test() {
+        #[derive(Clone, PartialEq, prost::Message)]
+        struct Order {
+            #[prost(uint32, tag = "1")]
+            id: u32,
+        }
+
+        let message = Message::protobuf(&Order { id: 1 }).build();
+
+        assert_eq!(Some("application/x-protobuf"), message.content_type());
+        assert_eq!(Some(&[0x08, 0x01][..]), message.data());
+        assert_eq!(Order { id: 1 }, message.body_as_protobuf().unwrap());
+        assert!(Message::builder()
+            .body(vec![0xff])
+            .build()
+            .body_as_protobuf::<Order>()
+            .is_err());
+    }
+
+    #[test]
+    fn message_value_and_sequence_test() {
+        let message = Message::builder()
+            .body("replaced")
+            .value(Value::Symbol("value".to_owned()))
+            .build();
+        let mut buffer = vec![];
+        message.encode(&mut buffer).unwrap();
+        let (_, decoded) = Message::decode(&buffer).unwrap();
+        assert_eq!(None, decoded.data());
+        assert_eq!(Some(Value::Symbol("value".to_owned())), decoded.value());
+        assert!(decoded.sequences().is_empty());
+
+        let message = Message::builder()
+            .sequence(vec![1i32, 2])
+            .sequence(vec!["three"])
+            .build();
+        let mut buffer = vec![];
+        message.encode(&mut buffer).unwrap();
+        assert_eq!(message.encoded_size() as usize, buffer.len());
+        assert_eq!(&[0x00, 0x53, 0x76, 0xc0], &buffer[..4]);
+        let (_, decoded) = Message::decode(&buffer).unwrap();
+        assert_eq!(None, decoded.value());
+        assert_eq!(
+            vec![
+                vec![Value::Int(1), Value::Int(2)],
+                vec![Value::String("three".to_owned())]
+            ],
+            decoded.sequences()
+        );
+
+        let message = Message::builder().sequence(0..300u32).build();
+        let mut buffer = vec![];
+        message.encode(&mut buffer).unwrap();
+        assert_eq!(message.encoded_size() as usize, buffer.len());
+        let (_, decoded) = Message::decode(&buffer).unwrap();
+        assert_eq!(300, decoded.sequences()[0].len());
+
+        let message = Message::builder().value(1u64).body("data").build();
+        assert_eq!(None, message.value());
+        assert_eq!(Some(&b"data"[..]), message.data());
+    }
+
+    #[test]
+    fn message_body_encoding_test() {
+        for body in [vec![1u8; 7], vec![2u8; 300]] {
+            let message = Message::builder()
+                .body(body.clone())
+                .application_property("key", "value")
+                .build();
+            let mut buffer = vec![];
+            message.encode(&mut buffer).unwrap();
+            assert_eq!(buffer.len() as u32, message.encoded_size());
+
+            let mut amqp_message = AmpqMessage::default();
+            amqp_message.set_app_property("key", "value");
+            amqp_message.set_body(|amqp_body| amqp_body.set_data(body.clone().into()));
+            let mut expected = BytesMut::new();
+            amqp_message.encode(&mut expected);
+            assert_eq!(&expected[..], &buffer[..]);
+
+            let (_, decoded) = Message::decode(&buffer).unwrap();
+            assert_eq!(Some(&body[..]), decoded.body().as_deref());
+            assert_eq!(
+                Some(Value::String("value".to_owned())),
+                decoded.application_property("key")
+            );
+        }
+    }
+
+    #[test]
+    fn message_value_body_test() {
+        let mut amqp_message = AmpqMessage::default();
+        amqp_message.set_value("value");
+        let mut expected = BytesMut::new();
+        amqp_message.encode(&mut expected);
+
+        let (_, message) = Message::decode(&expected).unwrap();
+        assert_eq!(None, message.data());
+
+        let mut buffer = vec![];
+        message.encode(&mut buffer).unwrap();
+        assert_eq!(&expected[..], &buffer[..]);
+    }
+
+    #[test]
+    fn cached_message_encoding_test() {
+        let mut message = Message::builder().body("message").build();
+        let mut expected = vec![];
+        message.encode(&mut expected).unwrap();
+
+        let encoded = message.cache_encoded();
+        let mut copy = message.clone();
+        assert!(Arc::ptr_eq(&encoded, &copy.cache_encoded()));
+
+        let mut buffer = vec![];
+        copy.encode(&mut buffer).unwrap();
+        assert_eq!(expected, buffer);
+        assert_eq!(expected.len() as u32, copy.encoded_size());
+
+        let (_, decoded) = Message::decode(&buffer).unwrap();
+        assert_eq!(copy.data(), decoded.data());
+    }
+
+    #[test]
+    fn message_from_encoded_test() {
+        let encoded = Message::builder().body("message").build().cache_encoded();
+
+        let message = Message::from_encoded(encoded.clone()).unwrap();
+
+        assert_eq!(Some(&b"message"[..]), message.data());
+        assert_eq!(encoded.len() as u32, message.encoded_size());
+    }
+
+    #[test]
+    fn message_sections_test() {
+        let properties = Properties {
+            message_id: Some(MessageId::Ulong(42)),
+            reply_to: Some("replies".to_owned()),
+            content_type: Some("application/json".to_owned()),
+            creation_time: Some(1_700_000_000_000),
+            ..Default::default()
+        };
+        let message = Message::builder()
+            .body("message")
+            .properties(properties.clone())
+            .application_property("region", "emea")
+            .application_property("priority", 5i32)
+            .message_annotation("x-opt-source", Value::Symbol("billing".to_owned()))
+            .build();
+
+        let mut buffer = vec![];
+        message.encode(&mut buffer).unwrap();
+        let (remaining, decoded) = Message::decode(&buffer).unwrap();
+        assert!(remaining.is_empty());
+
+        assert_eq!(Some(&b"message"[..]), decoded.data());
+        assert_eq!(Some(properties), decoded.properties());
+        assert_eq!(
+            Some(Value::String("emea".to_owned())),
+            decoded.application_property("region")
+        );
+        assert_eq!(
+            vec![
+                ("region".to_owned(), Value::String("emea".to_owned())),
+                ("priority".to_owned(), Value::Int(5)),
+            ],
+            decoded.application_properties()
+        );
+        assert_eq!(
+            Some(Value::Symbol("billing".to_owned())),
+            decoded.message_annotation("x-opt-source")
+        );
+        assert_eq!(None, decoded.message_annotation("missing"));
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, TimeZone, Utc};
+use ntex_amqp_codec::{
+    protocol::{MessageId as AmqpMessageId, Properties as AmqpProperties},
+    types::Variant,
+};
+use ntex_bytes::{ByteString, Bytes};
+use ordered_float::OrderedFloat;
+use uuid::Uuid;
+
+/// Value of an application property or a message annotation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Boolean(bool),
+    Ubyte(u8),
+    Ushort(u16),
+    Uint(u32),
+    Ulong(u64),
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Char(char),
+    /// Milliseconds since the Unix epoch
+    Timestamp(i64),
+    Uuid([u8; 16]),
+    Binary(Vec<u8>),
+    String(String),
+    Symbol(String),
+}
+
+macro_rules! value_from {
+    ($($ty:ty => $variant:ident),*) => {
+        $(impl From<$ty> for Value {
+            fn from(value: $ty) -> Self {
+                Value::$variant(value.into())
+            }
+        })*
+    };
+}
+
+value_from!(
+    bool => Boolean,
+    u8 => Ubyte,
+    u16 => Ushort,
+    u32 => Uint,
+    u64 => Ulong,
+    i8 => Byte,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+    char => Char,
+    Vec<u8> => Binary,
+    &[u8] => Binary,
+    String => String,
+    &str => String
+);
+
+impl Value {
+    /// Convert a decoded value, lists, maps and described values are not supported
+    pub(crate) fn from_variant(variant: &Variant) -> Option<Self> {
+        Some(match variant {
+            Variant::Null => Value::Null,
+            Variant::Boolean(value) => Value::Boolean(*value),
+            Variant::Ubyte(value) => Value::Ubyte(*value),
+            Variant::Ushort(value) => Value::Ushort(*value),
+            Variant::Uint(value) => Value::Uint(*value),
+            Variant::Ulong(value) => Value::Ulong(*value),
+            Variant::Byte(value) => Value::Byte(*value),
+            Variant::Short(value) => Value::Short(*value),
+            Variant::Int(value) => Value::Int(*value),
+            Variant::Long(value) => Value::Long(*value),
+            Variant::Float(value) => Value::Float(value.into_inner()),
+            Variant::Double(value) => Value::Double(value.into_inner()),
+            Variant::Char(value) => Value::Char(*value),
+            Variant::Timestamp(value) => Value::Timestamp(value.timestamp_millis()),
+            Variant::Uuid(value) => Value::Uuid(*value.as_bytes()),
+            Variant::Binary(value) => Value::Binary(value.to_vec()),
+            Variant::String(value) => Value::String(value.as_str().to_owned()),
+            Variant::Symbol(value) => Value::Symbol(value.as_str().to_owned()),
+            Variant::StaticSymbol(value) => Value::Symbol(value.0.to_owned()),
+            Variant::List(_) | Variant::Map(_) | Variant::Described(_) => return None,
+        })
+    }
+
+    pub(crate) fn into_variant(self) -> Variant {
+        match self {
+            Value::Null => Variant::Null,
+            Value::Boolean(value) => Variant::Boolean(value),
+            Value::Ubyte(value) => Variant::Ubyte(value),
+            Value::Ushort(value) => Variant::Ushort(value),
+            Value::Uint(value) => Variant::Uint(value),
+            Value::Ulong(value) => Variant::Ulong(value),
+            Value::Byte(value) => Variant::Byte(value),
+            Value::Short(value) => Variant::Short(value),
+            Value::Int(value) => Variant::Int(value),
+            Value::Long(value) => Variant::Long(value),
+            Value::Float(value) => Variant::Float(OrderedFloat(value)),
+            Value::Double(value) => Variant::Double(OrderedFloat(value)),
+            Value::Char(value) => Variant::Char(value),
+            Value::Timestamp(value) => Variant::Timestamp(timestamp(value)),
+            Value::Uuid(value) => Variant::Uuid(Uuid::from_bytes(value)),
+            Value::Binary(value) => Variant::Binary(Bytes::from(value)),
+            Value::String(value) => Variant::from(value),
+            Value::Symbol(value) => Variant::Symbol(value.into()),
+        }
+    }
+}
+
+/// Identifier of a message, used for the message and correlation ids of [`Properties`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MessageId {
+    Ulong(u64),
+    Uuid([u8; 16]),
+    Binary(Vec<u8>),
+    String(String),
+}
+
+impl From<u64> for MessageId {
+    fn from(id: u64) -> Self {
+        MessageId::Ulong(id)
+    }
+}
+
+impl From<String> for MessageId {
+    fn from(id: String) -> Self {
+        MessageId::String(id)
+    }
+}
+
+impl From<&str> for MessageId {
+    fn from(id: &str) -> Self {
+        MessageId::String(id.to_owned())
+    }
+}
+
+impl From<&AmqpMessageId> for MessageId {
+    fn from(id: &AmqpMessageId) -> Self {
+        match id {
+            AmqpMessageId::Ulong(id) => MessageId::Ulong(*id),
+            AmqpMessageId::Uuid(id) => MessageId::Uuid(*id.as_bytes()),
+            AmqpMessageId::Binary(id) => MessageId::Binary(id.to_vec()),
+            AmqpMessageId::String(id) => MessageId::String(id.to_string()),
+        }
+    }
+}
+
+impl From<MessageId> for AmqpMessageId {
+    fn from(id: MessageId) -> Self {
+        match id {
+            MessageId::Ulong(id) => AmqpMessageId::Ulong(id),
+            MessageId::Uuid(id) => AmqpMessageId::Uuid(Uuid::from_bytes(id)),
+            MessageId::Binary(id) => AmqpMessageId::Binary(Bytes::from(id)),
+            MessageId::String(id) => AmqpMessageId::String(ByteString::from(id)),
+        }
+    }
+}
+
+/// Properties section of a message, times are milliseconds since the Unix epoch
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Properties {
+    pub message_id: Option<MessageId>,
+    pub user_id: Option<Vec<u8>>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    pub reply_to: Option<String>,
+    pub correlation_id: Option<MessageId>,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub absolute_expiry_time: Option<i64>,
+    pub creation_time: Option<i64>,
+    pub group_id: Option<String>,
+    pub group_sequence: Option<u32>,
+    pub reply_to_group_id: Option<String>,
+}
+
+impl From<&AmqpProperties> for Properties {
+    fn from(properties: &AmqpProperties) -> Self {
+        let string = |value: &Option<ByteString>| value.as_ref().map(|value| value.to_string());
+        Properties {
+            message_id: properties.message_id.as_ref().map(MessageId::from),
+            user_id: properties.user_id.as_ref().map(|user_id| user_id.to_vec()),
+            to: string(&properties.to),
+            subject: string(&properties.subject),
+            reply_to: string(&properties.reply_to),
+            correlation_id: properties.correlation_id.as_ref().map(MessageId::from),
+            content_type: properties
+                .content_type
+                .as_ref()
+                .map(|content_type| content_type.as_str().to_owned()),
+            content_encoding: properties
+                .content_encoding
+                .as_ref()
+                .map(|content_encoding| content_encoding.as_str().to_owned()),
+            absolute_expiry_time: properties
+                .absolute_expiry_time
+                .map(|time| time.timestamp_millis()),
+            creation_time: properties.creation_time.map(|time| time.timestamp_millis()),
+            group_id: string(&properties.group_id),
+            group_sequence: properties.group_sequence,
+            reply_to_group_id: string(&properties.reply_to_group_id),
+        }
+    }
+}
+
+impl From<Properties> for AmqpProperties {
+    fn from(properties: Properties) -> Self {
+        AmqpProperties {
+            message_id: properties.message_id.map(Into::into),
+            user_id: properties.user_id.map(Bytes::from),
+            to: properties.to.map(ByteString::from),
+            subject: properties.subject.map(ByteString::from),
+            reply_to: properties.reply_to.map(ByteString::from),
+            correlation_id: properties.correlation_id.map(Into::into),
+            content_type: properties.content_type.map(Into::into),
+            content_encoding: properties.content_encoding.map(Into::into),
+            absolute_expiry_time: properties.absolute_expiry_time.map(timestamp),
+            creation_time: properties.creation_time.map(timestamp),
+            group_id: properties.group_id.map(ByteString::from),
+            group_sequence: properties.group_sequence,
+            reply_to_group_id: properties.reply_to_group_id.map(ByteString::from),
+        }
+    }
+}
+
+pub(crate) fn system_time(time: &DateTime<Utc>) -> SystemTime {
+    let millis = time.timestamp_millis();
+    match millis >= 0 {
+        true => UNIX_EPOCH + Duration::from_millis(millis as u64),
+        false => UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs()),
+    }
+}
+
+pub(crate) fn from_system_time(time: SystemTime) -> DateTime<Utc> {
+    let millis = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i64,
+        Err(error) => -(error.duration().as_millis() as i64),
+    };
+    timestamp(millis)
+}
+
+fn timestamp(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .unwrap_or_else(|| Utc.timestamp_millis_opt(0).unwrap())
+}
@@ -0,0 +1,68 @@
+//! Mapping between the sections of a message and the headers of AMQP 0.9.1
+//!
+//! Streams can also be published to and consumed from over AMQP 0.9.1, the broker then
+//! converts the messages:
+//!
+//! - the application properties of a message become headers of the same name
+//! - the message annotations starting with `x-` become headers of the same name
+//! - the headers starting with `x-` of an AMQP 0.9.1 message become message annotations,
+//!   the others application properties
+//!
+//! The broker also adds headers of its own, like [`STREAM_OFFSET_HEADER`] on the messages
+//! delivered to AMQP 0.9.1 consumers.
+
+use super::{Message, MessageBuilder, Value};
+
+/// Header of the offset of a message delivered to an AMQP 0.9.1 consumer
+pub const STREAM_OFFSET_HEADER: &str = "x-stream-offset";
+
+/// Header of the filter value of a message published over AMQP 0.9.1, kept as a message
+/// annotation of the same name
+pub const STREAM_FILTER_VALUE_HEADER: &str = "x-stream-filter-value";
+
+/// Prefix of the headers mapped to message annotations
+const ANNOTATION_PREFIX: &str = "x-";
+
+impl Message {
+    /// Headers an AMQP 0.9.1 consumer receives the message with
+    ///
+    /// The `x-` annotations come first, then the application properties. Values the
+    /// [`Value`] type does not represent are skipped.
+    pub fn amqp091_headers(&self) -> Vec<(String, Value)> {
+        self.message_annotations()
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(ANNOTATION_PREFIX))
+            .chain(self.application_properties())
+            .collect()
+    }
+
+    /// Filter value of a message published over AMQP 0.9.1, see [`STREAM_FILTER_VALUE_HEADER`]
+    pub fn amqp091_filter_value(&self) -> Option<String> {
+        match self.message_annotation(STREAM_FILTER_VALUE_HEADER) {
+            Some(Value::String(value)) | Some(Value::Symbol(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl MessageBuilder {
+    /// Add AMQP 0.9.1 headers the way the broker converts them
+    ///
+    /// Headers starting with `x-` are added as message annotations, the others as
+    /// application properties.
+    pub fn amqp091_headers<I, K, V>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        for (key, value) in headers {
+            let key = key.into();
+            self = match key.starts_with(ANNOTATION_PREFIX) {
+                true => self.message_annotation(key, value),
+                false => self.application_property(key, value),
+            };
+        }
+        self
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use ntex_amqp_codec::Encode;
+use ntex_bytes::BytesMut;
+
+use super::{Message, MessageBuilder, Properties, Value};
+
+/// Messages sharing their properties, application properties and annotations
+///
+/// Built with [`MessageBatch::builder`], to publish with a producer batch send.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageBatch(Vec<Message>);
+
+impl MessageBatch {
+    /// Start a batch, the sections set on the builder are encoded once for all its messages
+    pub fn builder() -> MessageBatchBuilder {
+        MessageBatchBuilder {
+            sections: Message::builder(),
+            bodies: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Message> {
+        self.0.iter()
+    }
+
+    pub fn into_messages(self) -> Vec<Message> {
+        self.0
+    }
+}
+
+impl From<MessageBatch> for Vec<Message> {
+    fn from(batch: MessageBatch) -> Self {
+        batch.0
+    }
+}
+
+impl IntoIterator for MessageBatch {
+    type Item = Message;
+    type IntoIter = std::vec::IntoIter<Message>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+pub struct MessageBatchBuilder {
+    /// Shared sections, kept in a message without body
+    sections: MessageBuilder,
+    bodies: Vec<Bytes>,
+}
+
+impl MessageBatchBuilder {
+    /// Set the properties section of every message
+    pub fn properties(mut self, properties: Properties) -> Self {
+        self.sections = self.sections.properties(properties);
+        self
+    }
+
+    /// Set the MIME type of every body
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.sections = self.sections.content_type(content_type);
+        self
+    }
+
+    /// Add an application property to every message
+    pub fn application_property(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.sections = self.sections.application_property(key, value);
+        self
+    }
+
+    /// Add a message annotation to every message
+    pub fn message_annotation(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.sections = self.sections.message_annotation(key, value);
+        self
+    }
+
+    /// Add a message with `body`
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.bodies.push(body.into());
+        self
+    }
+
+    /// Add a message per body
+    pub fn bodies<I, B>(mut self, bodies: I) -> Self
+    where
+        I: IntoIterator<Item = B>,
+        B: Into<Bytes>,
+    {
+        self.bodies.extend(bodies.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn build(self) -> MessageBatch {
+        let sections = self.sections.build().message;
+        let size = sections.encoded_size();
+        let encoded_sections: Option<Arc<[u8]>> = (size > 0).then(|| {
+            let mut buf = BytesMut::with_capacity(size);
+            sections.encode(&mut buf);
+            Arc::from(&buf[..])
+        });
+
+        MessageBatch(
+            self.bodies
+                .into_iter()
+                .map(|body| Message {
+                    publishing_id: None,
+                    message: sections.clone(),
+                    body: Some(body),
+                    sequences: Vec::new(),
+                    encoded_sections: encoded_sections.clone(),
+                    encoded: None,
+                })
+                .collect(),
+        )
+    }
+}
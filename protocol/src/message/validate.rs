@@ -0,0 +1,218 @@
+use chrono::{TimeZone, Utc};
+
+use crate::{
+    codec::decoder::{check_len, read_i64, read_u32, read_u8},
+    error::DecodeError,
+};
+
+/// Most lists, maps, arrays and described values nested in each other
+const MAX_DEPTH: usize = 64;
+
+const FORMAT_TIMESTAMP: u8 = 0x83;
+
+/// Check the sizes and counts of the AMQP values of `input` before they are handed to the
+/// AMQP codec, which trusts them: a corrupted message yields an error instead of a panic,
+/// a deep recursion or an allocation for billions of items
+pub(crate) fn check_encoding(mut input: &[u8]) -> Result<(), DecodeError> {
+    let nesting = Nesting {
+        depth: 0,
+        in_key: false,
+    };
+    while !input.is_empty() {
+        input = check_value(input, nesting)?;
+    }
+    Ok(())
+}
+
+fn invalid(reason: &str) -> DecodeError {
+    DecodeError::MessageParse(format!("Invalid AMQP encoding: {}", reason))
+}
+
+/// Position of a value in the values enclosing it
+#[derive(Clone, Copy)]
+struct Nesting {
+    depth: usize,
+    /// In the key of a map, the AMQP codec panics hashing the maps
+    in_key: bool,
+}
+
+impl Nesting {
+    fn inner(self) -> Result<Self, DecodeError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(invalid("too deeply nested"));
+        }
+        Ok(Nesting {
+            depth: self.depth + 1,
+            ..self
+        })
+    }
+}
+
+/// Check a value with its constructor, returns the input after it
+fn check_value(input: &[u8], nesting: Nesting) -> Result<&[u8], DecodeError> {
+    let (input, constructor) = read_constructor(input, nesting)?;
+    check_element(input, constructor, nesting)
+}
+
+/// Read a constructor, the descriptor of a described constructor is checked and skipped
+fn read_constructor(input: &[u8], nesting: Nesting) -> Result<(&[u8], u8), DecodeError> {
+    let (input, format) = read_u8(input)?;
+    if format != 0x00 {
+        return Ok((input, format));
+    }
+    let nesting = nesting.inner()?;
+    let input = check_value(input, nesting)?;
+    read_constructor(input, nesting)
+}
+
+/// Bytes of the values of `format` written without size, `None` for the other formats
+fn fixed_width(format: u8) -> Option<usize> {
+    match format {
+        0x40..=0x45 => Some(0),
+        0x50..=0x56 => Some(1),
+        0x60 | 0x61 => Some(2),
+        0x70..=0x74 => Some(4),
+        0x80..=0x84 => Some(8),
+        0x94 | 0x98 => Some(16),
+        _ => None,
+    }
+}
+
+/// Check the encoding of a value of `format` without its constructor
+fn check_element(input: &[u8], format: u8, nesting: Nesting) -> Result<&[u8], DecodeError> {
+    if format == FORMAT_TIMESTAMP {
+        let (input, millis) = read_i64(input)?;
+        if !valid_timestamp(millis) {
+            return Err(invalid("timestamp out of range"));
+        }
+        return Ok(input);
+    }
+    if let Some(width) = fixed_width(format) {
+        check_len(input, width)?;
+        return Ok(&input[width..]);
+    }
+    let (input, size) = match format {
+        0xa0 | 0xa1 | 0xa3 | 0xc0 | 0xc1 | 0xe0 => {
+            read_u8(input).map(|(input, size)| (input, size as usize))?
+        }
+        0xb0 | 0xb1 | 0xb3 | 0xd0 | 0xd1 | 0xf0 => {
+            read_u32(input).map(|(input, size)| (input, size as usize))?
+        }
+        _ => return Err(invalid("unknown format code")),
+    };
+    check_len(input, size)?;
+    let (body, input) = input.split_at(size);
+    match format {
+        0xc1 | 0xd1 if nesting.in_key => return Err(invalid("map in the key of a map")),
+        0xc0 => check_compound(read_u8(body)?, false, nesting)?,
+        0xc1 => check_compound(read_u8(body)?, true, nesting)?,
+        0xd0 => check_compound(read_u32(body)?, false, nesting)?,
+        0xd1 => check_compound(read_u32(body)?, true, nesting)?,
+        0xe0 => check_array(read_u8(body)?, nesting)?,
+        0xf0 => check_array(read_u32(body)?, nesting)?,
+        _ => {}
+    }
+    Ok(input)
+}
+
+/// Whether the AMQP codec converts the timestamp without panicking, it splits the
+/// milliseconds before 1970 in the second before and a leap nanosecond count
+fn valid_timestamp(millis: i64) -> bool {
+    let seconds = millis / 1000;
+    let (seconds, nanoseconds) = if seconds < 0 {
+        (seconds - 1, (1000 + millis - seconds * 1000) * 1_000_000)
+    } else {
+        (seconds, (millis - seconds * 1000) * 1_000_000)
+    };
+    Utc.timestamp_opt(seconds, nanoseconds.unsigned_abs() as u32)
+        .single()
+        .is_some()
+}
+
+/// Check the items of a list or a map, each one has at least its constructor byte and
+/// they fill its size
+fn check_compound<C: Into<u64>>(
+    (mut items, count): (&[u8], C),
+    map: bool,
+    nesting: Nesting,
+) -> Result<(), DecodeError> {
+    let count = count.into();
+    let nesting = nesting.inner()?;
+    if count > items.len() as u64 {
+        return Err(invalid("more items than bytes"));
+    }
+    for item in 0..count {
+        let in_key = nesting.in_key || (map && item.is_multiple_of(2));
+        items = check_value(items, Nesting { in_key, ..nesting })?;
+    }
+    // the AMQP codec reads the items of a list past its size
+    if !items.is_empty() {
+        return Err(invalid("size larger than the items"));
+    }
+    Ok(())
+}
+
+/// Check the elements of an array, they share the constructor written before them
+fn check_array<C: Into<u64>>(
+    (body, count): (&[u8], C),
+    nesting: Nesting,
+) -> Result<(), DecodeError> {
+    let count = count.into();
+    let nesting = nesting.inner()?;
+    let (mut elements, format) = read_constructor(body, nesting)?;
+    // elements without bytes only fit the count of a small array
+    let bound = match fixed_width(format) {
+        Some(0) => u8::MAX as u64,
+        _ => elements.len() as u64,
+    };
+    if count > bound {
+        return Err(invalid("more elements than bytes"));
+    }
+    for _ in 0..count {
+        elements = check_element(elements, format, nesting)?;
+    }
+    if !elements.is_empty() {
+        return Err(invalid("size larger than the elements"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_encoding;
+    use crate::{codec::Decoder, error::DecodeError, message::Message};
+
+    fn assert_invalid(input: &[u8]) {
+        assert!(matches!(
+            check_encoding(input),
+            Err(DecodeError::MessageParse(_) | DecodeError::Incomplete(_))
+        ));
+        assert!(Message::decode(input).is_err());
+    }
+
+    #[test]
+    fn check_encoding_test() {
+        // application properties: described map with a string key and an uint value
+        check_encoding(&[
+            0x00, 0x53, 0x74, 0xc1, 0x06, 0x02, 0xa1, 0x01, b'k', 0x52, 0x01,
+        ])
+        .unwrap();
+
+        // list32 of 4 billion items in 5 bytes
+        assert_invalid(&[0xd0, 0x00, 0x00, 0x00, 0x05, 0xff, 0xff, 0xff, 0xff, 0x40]);
+        // list8 reading its item past its size
+        assert_invalid(&[0xc0, 0x01, 0x01, 0x40]);
+        // map as the key of a map
+        assert_invalid(&[0xc1, 0x05, 0x02, 0xc1, 0x01, 0x00, 0x40]);
+        // timestamp out of the range of the dates
+        assert_invalid(&[0x83, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        // lists nested in each other
+        let nested = (0..80).fold(vec![0x45], |inner, _| {
+            [vec![0xc0, inner.len() as u8 + 1, 0x01], inner].concat()
+        });
+        assert!(matches!(
+            check_encoding(&nested),
+            Err(DecodeError::MessageParse(error)) if error.contains("nested")
+        ));
+    }
+}
@@ -3,14 +3,30 @@ use std::io::Write;
 use crate::{
     codec::{decoder::read_u32, Decoder, Encoder},
     commands::{
-        close::CloseRequest, create_stream::CreateStreamCommand, credit::CreditCommand,
-        declare_publisher::DeclarePublisherCommand, delete::Delete,
-        delete_publisher::DeletePublisherCommand, heart_beat::HeartBeatCommand,
-        metadata::MetadataCommand, open::OpenCommand, peer_properties::PeerPropertiesCommand,
-        publish::PublishCommand, query_offset::QueryOffsetRequest,
+        close::{CloseRequest, CloseResponse, CLOSE_RESPONSE_FRAME_SIZE},
+        consumer_update::ConsumerUpdateResponse,
+        create_stream::CreateStreamCommand,
+        create_super_stream::CreateSuperStreamCommand,
+        credit::CreditCommand,
+        declare_publisher::DeclarePublisherCommand,
+        delete::Delete,
+        delete_publisher::DeletePublisherCommand,
+        delete_super_stream::DeleteSuperStreamCommand,
+        heart_beat::HeartBeatCommand,
+        metadata::MetadataCommand,
+        open::OpenCommand,
+        partitions::PartitionsCommand,
+        peer_properties::PeerPropertiesCommand,
+        publish::PublishCommand,
+        query_offset::QueryOffsetRequest,
         query_publisher_sequence::QueryPublisherRequest,
-        sasl_authenticate::SaslAuthenticateCommand, sasl_handshake::SaslHandshakeCommand,
-        store_offset::StoreOffset, subscribe::SubscribeCommand, tune::TunesCommand,
+        route::RouteCommand,
+        sasl_authenticate::SaslAuthenticateCommand,
+        sasl_handshake::SaslHandshakeCommand,
+        store_offset::StoreOffset,
+        stream_stats::StreamStatsCommand,
+        subscribe::SubscribeCommand,
+        tune::TunesCommand,
         unsubscribe::UnSubscribeCommand,
     },
     error::{DecodeError, EncodeError},
@@ -45,6 +61,8 @@ pub enum RequestKind {
     Tunes(TunesCommand),
     Open(OpenCommand),
     Close(CloseRequest),
+    /// Answer to a close initiated by the broker
+    CloseResponse(CloseResponse),
     Delete(Delete),
     CreateStream(CreateStreamCommand),
     Subscribe(SubscribeCommand),
@@ -58,6 +76,12 @@ pub enum RequestKind {
     QueryPublisherSequence(QueryPublisherRequest),
     StoreOffset(StoreOffset),
     Unsubscribe(UnSubscribeCommand),
+    StreamStats(StreamStatsCommand),
+    Route(RouteCommand),
+    Partitions(PartitionsCommand),
+    CreateSuperStream(CreateSuperStreamCommand),
+    DeleteSuperStream(DeleteSuperStreamCommand),
+    ConsumerUpdate(ConsumerUpdateResponse),
 }
 
 impl Encoder for RequestKind {
@@ -74,6 +98,7 @@ impl Encoder for RequestKind {
             RequestKind::Credit(credit) => credit.encoded_size(),
             RequestKind::Metadata(metadata) => metadata.encoded_size(),
             RequestKind::Close(close) => close.encoded_size(),
+            RequestKind::CloseResponse(close) => close.encoded_size(),
             RequestKind::DeclarePublisher(declare_publisher) => declare_publisher.encoded_size(),
             RequestKind::DeletePublisher(delete_publisher) => delete_publisher.encoded_size(),
             RequestKind::Heartbeat(heartbeat) => heartbeat.encoded_size(),
@@ -82,6 +107,16 @@ impl Encoder for RequestKind {
             RequestKind::QueryPublisherSequence(query_publisher) => query_publisher.encoded_size(),
             RequestKind::StoreOffset(store_offset) => store_offset.encoded_size(),
             RequestKind::Unsubscribe(unsubscribe) => unsubscribe.encoded_size(),
+            RequestKind::StreamStats(stream_stats) => stream_stats.encoded_size(),
+            RequestKind::Route(route) => route.encoded_size(),
+            RequestKind::Partitions(partitions) => partitions.encoded_size(),
+            RequestKind::CreateSuperStream(create_super_stream) => {
+                create_super_stream.encoded_size()
+            }
+            RequestKind::DeleteSuperStream(delete_super_stream) => {
+                delete_super_stream.encoded_size()
+            }
+            RequestKind::ConsumerUpdate(consumer_update) => consumer_update.encoded_size(),
         }
     }
 
@@ -98,6 +133,7 @@ impl Encoder for RequestKind {
             RequestKind::Credit(credit) => credit.encode(writer),
             RequestKind::Metadata(metadata) => metadata.encode(writer),
             RequestKind::Close(close) => close.encode(writer),
+            RequestKind::CloseResponse(close) => close.encode(writer),
             RequestKind::DeclarePublisher(declare_publisher) => declare_publisher.encode(writer),
             RequestKind::DeletePublisher(delete_publisher) => delete_publisher.encode(writer),
             RequestKind::Heartbeat(heartbeat) => heartbeat.encode(writer),
@@ -106,6 +142,16 @@ impl Encoder for RequestKind {
             RequestKind::QueryPublisherSequence(query_publisher) => query_publisher.encode(writer),
             RequestKind::StoreOffset(store_offset) => store_offset.encode(writer),
             RequestKind::Unsubscribe(unsubcribe) => unsubcribe.encode(writer),
+            RequestKind::StreamStats(stream_stats) => stream_stats.encode(writer),
+            RequestKind::Route(route) => route.encode(writer),
+            RequestKind::Partitions(partitions) => partitions.encode(writer),
+            RequestKind::CreateSuperStream(create_super_stream) => {
+                create_super_stream.encode(writer)
+            }
+            RequestKind::DeleteSuperStream(delete_super_stream) => {
+                delete_super_stream.encode(writer)
+            }
+            RequestKind::ConsumerUpdate(consumer_update) => consumer_update.encode(writer),
         }
     }
 }
@@ -124,7 +170,7 @@ impl Encoder for Request {
 
 impl Decoder for Request {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
-        let (input, _) = read_u32(input)?;
+        let (input, size) = read_u32(input)?;
         let (input, header) = Header::decode(input)?;
 
         let (input, cmd) = match header.key() {
@@ -144,6 +190,9 @@ impl Decoder for Request {
                 CreateStreamCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
             }
             COMMAND_METADATA => MetadataCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
+            COMMAND_CLOSE if size == CLOSE_RESPONSE_FRAME_SIZE => {
+                CloseResponse::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
             COMMAND_CLOSE => CloseRequest::decode(input).map(|(i, kind)| (i, kind.into()))?,
             COMMAND_CREDIT => CreditCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
             COMMAND_DECLARE_PUBLISHER => {
@@ -156,7 +205,8 @@ impl Decoder for Request {
             COMMAND_HEARTBEAT => {
                 HeartBeatCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
             }
-            COMMAND_PUBLISH => PublishCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
+            COMMAND_PUBLISH => PublishCommand::decode_version(input, header.version())
+                .map(|(i, kind)| (i, kind.into()))?,
             COMMAND_QUERY_OFFSET => {
                 QueryOffsetRequest::decode(input).map(|(i, kind)| (i, kind.into()))?
             }
@@ -171,6 +221,22 @@ impl Decoder for Request {
             COMMAND_UNSUBSCRIBE => {
                 UnSubscribeCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
             }
+            COMMAND_STREAM_STATS => {
+                StreamStatsCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_ROUTE => RouteCommand::decode(input).map(|(i, kind)| (i, kind.into()))?,
+            COMMAND_PARTITIONS => {
+                PartitionsCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_CREATE_SUPER_STREAM => {
+                CreateSuperStreamCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_DELETE_SUPER_STREAM => {
+                DeleteSuperStreamCommand::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
+            COMMAND_CONSUMER_UPDATE => {
+                ConsumerUpdateResponse::decode(input).map(|(i, kind)| (i, kind.into()))?
+            }
             n => return Err(DecodeError::UnsupportedResponseType(n)),
         };
         Ok((input, Request { header, kind: cmd }))
@@ -183,14 +249,17 @@ mod tests {
     use crate::{
         codec::{Decoder, Encoder},
         commands::{
-            close::CloseRequest, create_stream::CreateStreamCommand, credit::CreditCommand,
+            close::CloseRequest, create_stream::CreateStreamCommand,
+            create_super_stream::CreateSuperStreamCommand, credit::CreditCommand,
             declare_publisher::DeclarePublisherCommand, delete::Delete,
-            delete_publisher::DeletePublisherCommand, heart_beat::HeartBeatCommand,
-            metadata::MetadataCommand, open::OpenCommand, peer_properties::PeerPropertiesCommand,
-            publish::PublishCommand, query_offset::QueryOffsetRequest,
-            query_publisher_sequence::QueryPublisherRequest,
-            sasl_authenticate::SaslAuthenticateCommand, sasl_handshake::SaslHandshakeCommand,
-            store_offset::StoreOffset, subscribe::SubscribeCommand, tune::TunesCommand,
+            delete_publisher::DeletePublisherCommand,
+            delete_super_stream::DeleteSuperStreamCommand, heart_beat::HeartBeatCommand,
+            metadata::MetadataCommand, open::OpenCommand, partitions::PartitionsCommand,
+            peer_properties::PeerPropertiesCommand, publish::PublishCommand,
+            query_offset::QueryOffsetRequest, query_publisher_sequence::QueryPublisherRequest,
+            route::RouteCommand, sasl_authenticate::SaslAuthenticateCommand,
+            sasl_handshake::SaslHandshakeCommand, store_offset::StoreOffset,
+            stream_stats::StreamStatsCommand, subscribe::SubscribeCommand, tune::TunesCommand,
             unsubscribe::UnSubscribeCommand, Command,
         },
     };
@@ -288,6 +357,26 @@ mod tests {
     fn request_unsubscribe_test() {
         request_encode_decode_test::<UnSubscribeCommand>()
     }
+    #[test]
+    fn request_partitions_test() {
+        request_encode_decode_test::<PartitionsCommand>()
+    }
+    #[test]
+    fn request_route_test() {
+        request_encode_decode_test::<RouteCommand>()
+    }
+    #[test]
+    fn request_stream_stats_test() {
+        request_encode_decode_test::<StreamStatsCommand>()
+    }
+    #[test]
+    fn request_create_super_stream_test() {
+        request_encode_decode_test::<CreateSuperStreamCommand>()
+    }
+    #[test]
+    fn request_delete_super_stream_test() {
+        request_encode_decode_test::<DeleteSuperStreamCommand>()
+    }
     fn request_encode_decode_test<T>()
     where
         T: Dummy<Faker> + Encoder + Decoder + Debug + PartialEq + Command + Into<Request>,
@@ -297,7 +386,7 @@ mod tests {
         let request: Request = command.into();
 
         let mut buffer = vec![];
-        let _ = request.encode(&mut buffer).unwrap();
+        request.encode(&mut buffer).unwrap();
 
         let (remaining, decoded) = Request::decode(&buffer).unwrap();
 
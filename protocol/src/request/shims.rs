@@ -1,16 +1,32 @@
 use crate::{
     commands::{
-        close::CloseRequest, create_stream::CreateStreamCommand, credit::CreditCommand,
-        declare_publisher::DeclarePublisherCommand, delete::Delete,
-        delete_publisher::DeletePublisherCommand, heart_beat::HeartBeatCommand,
-        metadata::MetadataCommand, open::OpenCommand, peer_properties::PeerPropertiesCommand,
-        publish::PublishCommand, query_offset::QueryOffsetRequest,
+        close::{CloseRequest, CloseResponse},
+        consumer_update::ConsumerUpdateResponse,
+        create_stream::CreateStreamCommand,
+        create_super_stream::CreateSuperStreamCommand,
+        credit::CreditCommand,
+        declare_publisher::DeclarePublisherCommand,
+        delete::Delete,
+        delete_publisher::DeletePublisherCommand,
+        delete_super_stream::DeleteSuperStreamCommand,
+        heart_beat::HeartBeatCommand,
+        metadata::MetadataCommand,
+        open::OpenCommand,
+        partitions::PartitionsCommand,
+        peer_properties::PeerPropertiesCommand,
+        publish::PublishCommand,
+        query_offset::QueryOffsetRequest,
         query_publisher_sequence::QueryPublisherRequest,
-        sasl_authenticate::SaslAuthenticateCommand, sasl_handshake::SaslHandshakeCommand,
-        store_offset::StoreOffset, subscribe::SubscribeCommand, tune::TunesCommand,
-        unsubscribe::UnSubscribeCommand, Command,
+        route::RouteCommand,
+        sasl_authenticate::SaslAuthenticateCommand,
+        sasl_handshake::SaslHandshakeCommand,
+        store_offset::StoreOffset,
+        stream_stats::StreamStatsCommand,
+        subscribe::SubscribeCommand,
+        tune::TunesCommand,
+        unsubscribe::UnSubscribeCommand,
+        Command,
     },
-    protocol::version::PROTOCOL_VERSION,
     types::Header,
     Request, RequestKind,
 };
@@ -20,7 +36,7 @@ where
 {
     fn from(cmd: T) -> Self {
         Request {
-            header: Header::new(cmd.key(), PROTOCOL_VERSION),
+            header: Header::new(cmd.key(), cmd.version()),
             kind: cmd.into(),
         }
     }
@@ -130,3 +146,39 @@ impl From<UnSubscribeCommand> for RequestKind {
         RequestKind::Unsubscribe(cmd)
     }
 }
+impl From<PartitionsCommand> for RequestKind {
+    fn from(cmd: PartitionsCommand) -> Self {
+        RequestKind::Partitions(cmd)
+    }
+}
+impl From<RouteCommand> for RequestKind {
+    fn from(cmd: RouteCommand) -> Self {
+        RequestKind::Route(cmd)
+    }
+}
+impl From<StreamStatsCommand> for RequestKind {
+    fn from(cmd: StreamStatsCommand) -> Self {
+        RequestKind::StreamStats(cmd)
+    }
+}
+impl From<CreateSuperStreamCommand> for RequestKind {
+    fn from(cmd: CreateSuperStreamCommand) -> Self {
+        RequestKind::CreateSuperStream(cmd)
+    }
+}
+impl From<DeleteSuperStreamCommand> for RequestKind {
+    fn from(cmd: DeleteSuperStreamCommand) -> Self {
+        RequestKind::DeleteSuperStream(cmd)
+    }
+}
+impl From<CloseResponse> for RequestKind {
+    fn from(cmd: CloseResponse) -> Self {
+        RequestKind::CloseResponse(cmd)
+    }
+}
+
+impl From<ConsumerUpdateResponse> for RequestKind {
+    fn from(cmd: ConsumerUpdateResponse) -> Self {
+        RequestKind::ConsumerUpdate(cmd)
+    }
+}
@@ -1,17 +1,31 @@
 use std::convert::TryInto;
 
+use bytes::Bytes;
+
 use crate::{
     codec::{
         decoder::{read_u16, read_u32},
         Decoder,
     },
     commands::{
-        close::CloseResponse, credit::CreditResponse, deliver::DeliverCommand,
-        generic::GenericResponse, heart_beat::HeartbeatResponse, metadata::MetadataResponse,
-        metadata_update::MetadataUpdateCommand, open::OpenResponse,
-        peer_properties::PeerPropertiesResponse, publish_confirm::PublishConfirm,
-        publish_error::PublishErrorResponse, query_offset::QueryOffsetResponse,
-        query_publisher_sequence::QueryPublisherResponse, sasl_handshake::SaslHandshakeResponse,
+        close::{CloseRequest, CloseResponse, CLOSE_RESPONSE_FRAME_SIZE},
+        consumer_update::ConsumerUpdateCommand,
+        credit::CreditResponse,
+        deliver::{self, DeliverCommand, RawDeliverCommand},
+        generic::GenericResponse,
+        heart_beat::HeartbeatResponse,
+        metadata::MetadataResponse,
+        metadata_update::MetadataUpdateCommand,
+        open::OpenResponse,
+        partitions::PartitionsResponse,
+        peer_properties::PeerPropertiesResponse,
+        publish_confirm::PublishConfirm,
+        publish_error::PublishErrorResponse,
+        query_offset::QueryOffsetResponse,
+        query_publisher_sequence::QueryPublisherResponse,
+        route::RouteResponse,
+        sasl_handshake::SaslHandshakeResponse,
+        stream_stats::StreamStatsResponse,
         tune::TunesCommand,
     },
     error::DecodeError,
@@ -41,6 +55,7 @@ pub enum ResponseCode {
     AccessRefused,
     PrecoditionFailed,
     PublisherDoesNotExist,
+    NoOffset,
 }
 #[derive(Debug, PartialEq)]
 pub struct Response {
@@ -52,11 +67,15 @@ pub struct Response {
 pub enum ResponseKind {
     Open(OpenResponse),
     Close(CloseResponse),
+    /// Close initiated by the broker, to be answered with a [`CloseResponse`]
+    CloseRequest(CloseRequest),
     PeerProperties(PeerPropertiesResponse),
     SaslHandshake(SaslHandshakeResponse),
     Generic(GenericResponse),
     Tunes(TunesCommand),
     Deliver(DeliverCommand),
+    /// Deliver frame of a connection decoding raw chunks, see [`Response::decode_raw_chunks`]
+    RawDeliver(RawDeliverCommand),
     Heartbeat(HeartbeatResponse),
     Metadata(MetadataResponse),
     MetadataUpdate(MetadataUpdateCommand),
@@ -65,6 +84,10 @@ pub enum ResponseKind {
     QueryOffset(QueryOffsetResponse),
     QueryPublisherSequence(QueryPublisherResponse),
     Credit(CreditResponse),
+    StreamStats(StreamStatsResponse),
+    Partitions(PartitionsResponse),
+    Route(RouteResponse),
+    ConsumerUpdate(ConsumerUpdateCommand),
 }
 
 impl Response {
@@ -84,13 +107,20 @@ impl Response {
             ResponseKind::QueryPublisherSequence(query_publisher) => {
                 Some(query_publisher.correlation_id)
             }
+            ResponseKind::StreamStats(stream_stats) => Some(stream_stats.correlation_id),
+            ResponseKind::Partitions(partitions) => Some(partitions.correlation_id),
+            ResponseKind::Route(route) => Some(route.correlation_id),
             ResponseKind::MetadataUpdate(_) => None,
             ResponseKind::PublishConfirm(_) => None,
             ResponseKind::PublishError(_) => None,
             ResponseKind::Tunes(_) => None,
             ResponseKind::Heartbeat(_) => None,
             ResponseKind::Deliver(_) => None,
+            ResponseKind::RawDeliver(_) => None,
             ResponseKind::Credit(_) => None,
+            // a request of the server, answered by the client with the same correlation id
+            ResponseKind::ConsumerUpdate(_) => None,
+            ResponseKind::CloseRequest(_) => None,
         }
     }
 
@@ -109,9 +139,34 @@ impl Response {
     }
 }
 
-impl Decoder for Response {
-    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
-        let (input, _) = read_u32(input)?;
+impl Response {
+    /// Decode a response keeping the entries of Deliver frames undecoded
+    pub fn decode_raw_chunks(input: &[u8]) -> Result<(&[u8], Self), DecodeError> {
+        Self::decode_frame(input, true, None)
+    }
+
+    /// Decode a response read in `buffer`, the message bodies and the raw chunks of
+    /// Deliver frames share its bytes instead of copying them
+    pub fn decode_bytes(buffer: &Bytes, raw_chunks: bool) -> Result<(&[u8], Self), DecodeError> {
+        Self::decode_frame(buffer, raw_chunks, Some(buffer))
+    }
+
+    /// Check the CRC32 of the chunk of a Deliver frame, other frames are not checked
+    pub fn check_chunk_crc(frame: &[u8]) -> Result<(), DecodeError> {
+        let (input, _) = read_u32(frame)?;
+        let (input, header) = Header::decode(input)?;
+        match header.key() {
+            COMMAND_DELIVER => deliver::check_crc(input),
+            _ => Ok(()),
+        }
+    }
+
+    fn decode_frame<'a>(
+        input: &'a [u8],
+        raw_chunks: bool,
+        buffer: Option<&Bytes>,
+    ) -> Result<(&'a [u8], Self), DecodeError> {
+        let (input, size) = read_u32(input)?;
 
         let (input, header) = Header::decode(input)?;
 
@@ -120,6 +175,8 @@ impl Decoder for Response {
                 OpenResponse::decode(input).map(|(i, kind)| (i, ResponseKind::Open(kind)))?
             }
 
+            COMMAND_CLOSE if size > CLOSE_RESPONSE_FRAME_SIZE => CloseRequest::decode(input)
+                .map(|(i, kind)| (i, ResponseKind::CloseRequest(kind)))?,
             COMMAND_CLOSE => {
                 CloseResponse::decode(input).map(|(i, kind)| (i, ResponseKind::Close(kind)))?
             }
@@ -134,13 +191,17 @@ impl Decoder for Response {
             | COMMAND_SUBSCRIBE
             | COMMAND_UNSUBSCRIBE
             | COMMAND_CREATE_STREAM
-            | COMMAND_DELETE_STREAM => {
+            | COMMAND_DELETE_STREAM
+            | COMMAND_CREATE_SUPER_STREAM
+            | COMMAND_DELETE_SUPER_STREAM => {
                 GenericResponse::decode(input).map(|(i, kind)| (i, ResponseKind::Generic(kind)))?
             }
             COMMAND_TUNE => {
                 TunesCommand::decode(input).map(|(i, kind)| (i, ResponseKind::Tunes(kind)))?
             }
-            COMMAND_DELIVER => DeliverCommand::decode(input)
+            COMMAND_DELIVER if raw_chunks => RawDeliverCommand::decode_in(input, buffer)
+                .map(|(remaining, kind)| (remaining, ResponseKind::RawDeliver(kind)))?,
+            COMMAND_DELIVER => DeliverCommand::decode_in(input, buffer)
                 .map(|(remaining, kind)| (remaining, ResponseKind::Deliver(kind)))?,
 
             COMMAND_HEARTBEAT => HeartbeatResponse::decode(input)
@@ -164,12 +225,27 @@ impl Decoder for Response {
             COMMAND_QUERY_PUBLISHER_SEQUENCE => QueryPublisherResponse::decode(input)
                 .map(|(remaining, kind)| (remaining, ResponseKind::QueryPublisherSequence(kind)))?,
 
+            COMMAND_STREAM_STATS => StreamStatsResponse::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::StreamStats(kind)))?,
+            COMMAND_PARTITIONS => PartitionsResponse::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::Partitions(kind)))?,
+            COMMAND_ROUTE => RouteResponse::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::Route(kind)))?,
+            COMMAND_CONSUMER_UPDATE => ConsumerUpdateCommand::decode(input)
+                .map(|(remaining, kind)| (remaining, ResponseKind::ConsumerUpdate(kind)))?,
+
             n => return Err(DecodeError::UnsupportedResponseType(n)),
         };
         Ok((input, Response { header, kind }))
     }
 }
 
+impl Decoder for Response {
+    fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
+        Self::decode_frame(input, false, None)
+    }
+}
+
 impl Decoder for ResponseCode {
     fn decode(input: &[u8]) -> Result<(&[u8], Self), crate::error::DecodeError> {
         let (input, code) = read_u16(input)?;
@@ -193,21 +269,33 @@ mod tests {
     use crate::{
         codec::{Decoder, Encoder},
         commands::{
-            close::CloseResponse, deliver::DeliverCommand, generic::GenericResponse,
-            heart_beat::HeartbeatResponse, metadata::MetadataResponse,
-            metadata_update::MetadataUpdateCommand, open::OpenResponse,
-            peer_properties::PeerPropertiesResponse, publish_confirm::PublishConfirm,
-            publish_error::PublishErrorResponse, query_offset::QueryOffsetResponse,
+            close::{CloseRequest, CloseResponse},
+            consumer_update::ConsumerUpdateCommand,
+            deliver::DeliverCommand,
+            generic::GenericResponse,
+            heart_beat::HeartbeatResponse,
+            metadata::MetadataResponse,
+            metadata_update::MetadataUpdateCommand,
+            open::OpenResponse,
+            partitions::PartitionsResponse,
+            peer_properties::PeerPropertiesResponse,
+            publish_confirm::PublishConfirm,
+            publish_error::PublishErrorResponse,
+            query_offset::QueryOffsetResponse,
             query_publisher_sequence::QueryPublisherResponse,
-            sasl_handshake::SaslHandshakeResponse, tune::TunesCommand,
+            route::RouteResponse,
+            sasl_handshake::SaslHandshakeResponse,
+            stream_stats::StreamStatsResponse,
+            tune::TunesCommand,
         },
         protocol::{
             commands::{
-                COMMAND_CLOSE, COMMAND_DELIVER, COMMAND_HEARTBEAT, COMMAND_METADATA,
-                COMMAND_METADATA_UPDATE, COMMAND_OPEN, COMMAND_PEER_PROPERTIES,
-                COMMAND_PUBLISH_CONFIRM, COMMAND_PUBLISH_ERROR, COMMAND_QUERY_OFFSET,
-                COMMAND_QUERY_PUBLISHER_SEQUENCE, COMMAND_SASL_AUTHENTICATE,
-                COMMAND_SASL_HANDSHAKE, COMMAND_TUNE,
+                COMMAND_CLOSE, COMMAND_CONSUMER_UPDATE, COMMAND_DELIVER, COMMAND_HEARTBEAT,
+                COMMAND_METADATA, COMMAND_METADATA_UPDATE, COMMAND_OPEN, COMMAND_PARTITIONS,
+                COMMAND_PEER_PROPERTIES, COMMAND_PUBLISH_CONFIRM, COMMAND_PUBLISH_ERROR,
+                COMMAND_QUERY_OFFSET, COMMAND_QUERY_PUBLISHER_SEQUENCE, COMMAND_ROUTE,
+                COMMAND_SASL_AUTHENTICATE, COMMAND_SASL_HANDSHAKE, COMMAND_STREAM_STATS,
+                COMMAND_TUNE,
             },
             version::PROTOCOL_VERSION,
         },
@@ -220,12 +308,14 @@ mod tests {
             match self {
                 ResponseKind::Open(open) => open.encoded_size(),
                 ResponseKind::Close(close) => close.encoded_size(),
+                ResponseKind::CloseRequest(close) => close.encoded_size(),
                 ResponseKind::PeerProperties(peer_properties) => peer_properties.encoded_size(),
                 ResponseKind::SaslHandshake(handshake) => handshake.encoded_size(),
                 ResponseKind::Generic(generic) => generic.encoded_size(),
                 ResponseKind::Tunes(tune) => tune.encoded_size(),
                 ResponseKind::Heartbeat(heartbeat) => heartbeat.encoded_size(),
                 ResponseKind::Deliver(deliver) => deliver.encoded_size(),
+                ResponseKind::RawDeliver(deliver) => deliver.encoded_size(),
                 ResponseKind::Metadata(metadata) => metadata.encoded_size(),
                 ResponseKind::MetadataUpdate(metadata) => metadata.encoded_size(),
                 ResponseKind::PublishConfirm(publish_confirm) => publish_confirm.encoded_size(),
@@ -235,6 +325,10 @@ mod tests {
                     query_publisher.encoded_size()
                 }
                 ResponseKind::Credit(credit) => credit.encoded_size(),
+                ResponseKind::StreamStats(stream_stats) => stream_stats.encoded_size(),
+                ResponseKind::Partitions(partitions) => partitions.encoded_size(),
+                ResponseKind::Route(route) => route.encoded_size(),
+                ResponseKind::ConsumerUpdate(consumer_update) => consumer_update.encoded_size(),
             }
         }
 
@@ -245,12 +339,14 @@ mod tests {
             match self {
                 ResponseKind::Open(open) => open.encode(writer),
                 ResponseKind::Close(close) => close.encode(writer),
+                ResponseKind::CloseRequest(close) => close.encode(writer),
                 ResponseKind::PeerProperties(peer_properties) => peer_properties.encode(writer),
                 ResponseKind::SaslHandshake(handshake) => handshake.encode(writer),
                 ResponseKind::Generic(generic) => generic.encode(writer),
                 ResponseKind::Tunes(tune) => tune.encode(writer),
                 ResponseKind::Heartbeat(heartbeat) => heartbeat.encode(writer),
                 ResponseKind::Deliver(deliver) => deliver.encode(writer),
+                ResponseKind::RawDeliver(deliver) => deliver.encode(writer),
                 ResponseKind::Metadata(metadata) => metadata.encode(writer),
                 ResponseKind::MetadataUpdate(metadata) => metadata.encode(writer),
                 ResponseKind::PublishConfirm(publish_confirm) => publish_confirm.encode(writer),
@@ -260,13 +356,17 @@ mod tests {
                     query_publisher.encode(writer)
                 }
                 ResponseKind::Credit(credit) => credit.encode(writer),
+                ResponseKind::StreamStats(stream_stats) => stream_stats.encode(writer),
+                ResponseKind::Partitions(partitions) => partitions.encode(writer),
+                ResponseKind::Route(route) => route.encode(writer),
+                ResponseKind::ConsumerUpdate(consumer_update) => consumer_update.encode(writer),
             }
         }
     }
 
     impl Encoder for Response {
         fn encoded_size(&self) -> u32 {
-            self.header.encoded_size() + 2 + self.kind.encoded_size()
+            self.header.encoded_size() + self.kind.encoded_size()
         }
 
         fn encode(
@@ -347,10 +447,30 @@ mod tests {
         response_test!(CloseResponse, ResponseKind::Close, COMMAND_CLOSE);
     }
     #[test]
+    fn close_request_response_test() {
+        response_test!(CloseRequest, ResponseKind::CloseRequest, COMMAND_CLOSE);
+    }
+    #[test]
     fn deliver_response_test() {
         response_test!(DeliverCommand, ResponseKind::Deliver, COMMAND_DELIVER);
     }
     #[test]
+    fn raw_deliver_response_test() {
+        use fake::{Fake, Faker};
+        let response = Response {
+            header: Header::new(COMMAND_DELIVER, PROTOCOL_VERSION),
+            kind: ResponseKind::RawDeliver(Faker.fake()),
+        };
+
+        let mut buffer = vec![];
+        response.encode(&mut buffer).unwrap();
+
+        let (remaining, decoded) = Response::decode_raw_chunks(&buffer).unwrap();
+
+        assert_eq!(response, decoded);
+        assert!(remaining.is_empty());
+    }
+    #[test]
     fn metadata_update_response_test() {
         response_test!(
             MetadataUpdateCommand,
@@ -398,4 +518,32 @@ mod tests {
             COMMAND_HEARTBEAT
         );
     }
+    #[test]
+    fn stream_stats_response_test() {
+        response_test!(
+            StreamStatsResponse,
+            ResponseKind::StreamStats,
+            COMMAND_STREAM_STATS
+        );
+    }
+    #[test]
+    fn partitions_response_test() {
+        response_test!(
+            PartitionsResponse,
+            ResponseKind::Partitions,
+            COMMAND_PARTITIONS
+        );
+    }
+    #[test]
+    fn route_response_test() {
+        response_test!(RouteResponse, ResponseKind::Route, COMMAND_ROUTE);
+    }
+    #[test]
+    fn consumer_update_response_test() {
+        response_test!(
+            ConsumerUpdateCommand,
+            ResponseKind::ConsumerUpdate,
+            COMMAND_CONSUMER_UPDATE
+        );
+    }
 }
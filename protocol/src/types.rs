@@ -20,25 +20,161 @@ impl Header {
     }
 }
 
-#[cfg(test)]
-use fake::Fake;
+use std::{borrow::Cow, collections::HashMap, iter::FromIterator};
+
+use smallvec::SmallVec;
 
-use crate::{message::Message, ResponseCode};
+use crate::{
+    buffer_pool::with_record_buffer,
+    codec::Encoder,
+    compression::{Compression, ZstdDictionary},
+    error::EncodeError,
+    message::Message,
+    ResponseCode,
+};
 
 #[cfg_attr(test, derive(fake::Dummy))]
 #[derive(Debug, PartialEq)]
 pub struct PublishedMessage {
     pub(crate) publishing_id: u64,
-    pub(crate) message: Message,
+    #[cfg_attr(test, dummy(default))]
+    pub(crate) filter_value: Option<String>,
+    pub(crate) entry: PublishedEntry,
+}
+
+/// Payload of a published message, a single message or a batch of messages
+/// sharing one publishing id
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq)]
+pub(crate) enum PublishedEntry {
+    Simple(Message),
+    SubEntry(SubEntry),
+    /// Uncompressed sub-entry, the records are encoded straight into the frame
+    #[cfg_attr(test, dummy(skip))]
+    Records(Vec<Message>),
+}
+
+#[cfg_attr(test, derive(fake::Dummy))]
+#[derive(Debug, PartialEq)]
+pub(crate) struct SubEntry {
+    pub(crate) compression: Compression,
+    pub(crate) records: u16,
+    pub(crate) uncompressed_len: u32,
+    pub(crate) data: Vec<u8>,
 }
 
 impl PublishedMessage {
     pub fn new(publishing_id: u64, message: Message) -> Self {
         Self {
             publishing_id,
-            message,
+            filter_value: None,
+            entry: PublishedEntry::Simple(message),
         }
     }
+
+    /// Attach a filter value, published with version 2 of the publish command
+    ///
+    /// The broker indexes the filter values of a chunk so consumers subscribing with filter
+    /// values can skip the chunks they are not interested in.
+    pub fn filter_value(mut self, filter_value: Option<String>) -> Self {
+        self.filter_value = filter_value;
+        self
+    }
+
+    /// Size of the message in a publish frame of version 2, which carries the filter value
+    pub fn encoded_size_v2(&self) -> u32 {
+        self.encoded_size()
+            + 2
+            + self
+                .filter_value
+                .as_ref()
+                .map(|value| value.len() as u32)
+                .unwrap_or(0)
+    }
+
+    /// Pack `messages` in a single sub-entry compressed with `compression`
+    ///
+    /// Without compression the messages are kept and encoded with the frame, a compressed
+    /// sub-entry is encoded and compressed now as its size is part of the frame.
+    pub fn sub_entry(
+        publishing_id: u64,
+        compression: Compression,
+        messages: impl Into<Vec<Message>>,
+    ) -> Result<Self, EncodeError> {
+        let messages = messages.into();
+        if compression == Compression::None {
+            return Ok(Self {
+                publishing_id,
+                filter_value: None,
+                entry: PublishedEntry::Records(messages),
+            });
+        }
+        Self::compressed_sub_entry(publishing_id, compression, messages, |records| {
+            compression.compress(records)
+        })
+    }
+
+    /// Pack `messages` in a single sub-entry compressed by zstd with `dictionary`
+    pub fn zstd_sub_entry(
+        publishing_id: u64,
+        dictionary: &ZstdDictionary,
+        messages: impl Into<Vec<Message>>,
+    ) -> Result<Self, EncodeError> {
+        Self::compressed_sub_entry(
+            publishing_id,
+            Compression::Zstd,
+            messages.into(),
+            |records| dictionary.compress(records),
+        )
+    }
+
+    fn compressed_sub_entry(
+        publishing_id: u64,
+        compression: Compression,
+        messages: Vec<Message>,
+        compress: impl FnOnce(&[u8]) -> Result<Vec<u8>, EncodeError>,
+    ) -> Result<Self, EncodeError> {
+        let size = messages
+            .iter()
+            .map(|message| 4 + message.encoded_size() as usize)
+            .sum();
+        let (uncompressed_len, data) = with_record_buffer(size, |records| {
+            for message in &messages {
+                message.encoded_size().encode(records)?;
+                message.encode(records)?;
+            }
+            Ok::<_, EncodeError>((records.len() as u32, compress(records)?))
+        })?;
+
+        Ok(Self {
+            publishing_id,
+            filter_value: None,
+            entry: PublishedEntry::SubEntry(SubEntry {
+                compression,
+                records: messages.len() as u16,
+                uncompressed_len,
+                data,
+            }),
+        })
+    }
+
+    pub fn publishing_id(&self) -> u64 {
+        self.publishing_id
+    }
+
+    /// Number of messages of the entry, more than one for a sub-entry
+    pub fn records(&self) -> u16 {
+        match &self.entry {
+            PublishedEntry::Simple(_) => 1,
+            PublishedEntry::SubEntry(sub_entry) => sub_entry.records,
+            PublishedEntry::Records(messages) => messages.len() as u16,
+        }
+    }
+
+    /// Encode the entry as the broker stores it in a chunk
+    pub fn encode_entry(&self, writer: &mut impl std::io::Write) -> Result<(), EncodeError> {
+        self.entry.encode(writer)
+    }
 }
 
 #[cfg_attr(test, derive(fake::Dummy))]
@@ -56,3 +192,158 @@ impl PublishingError {
         }
     }
 }
+
+/// Keys of the handshake properties kept without allocating when decoded
+const KNOWN_PROPERTY_KEYS: &[&str] = &[
+    "product",
+    "version",
+    "platform",
+    "copyright",
+    "information",
+    "cluster_name",
+    "connection_name",
+    "advertised_host",
+    "advertised_port",
+];
+
+/// Properties exchanged on the handshake, e.g. the server and connection properties
+///
+/// A handful of pairs kept in received order, inline up to 8 of them. The usual keys are
+/// not allocated, the handshakes of reconnecting clients do not churn small maps.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerProperties(SmallVec<[(Cow<'static, str>, Cow<'static, str>); 8]>);
+
+impl PeerProperties {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Properties with static keys and values, built without allocating
+    pub fn from_static(properties: &[(&'static str, &'static str)]) -> Self {
+        PeerProperties(
+            properties
+                .iter()
+                .map(|(key, value)| (Cow::Borrowed(*key), Cow::Borrowed(*value)))
+                .collect(),
+        )
+    }
+
+    /// Set `key` to `value`, replacing the previous value
+    pub fn insert(
+        &mut self,
+        key: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) {
+        let key = key.into();
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_ref())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(key, value)| (key.as_ref(), value.as_ref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Append a decoded pair, the usual keys are taken from the static ones
+    pub(crate) fn push_decoded(&mut self, key: &str, value: &str) {
+        let key = KNOWN_PROPERTY_KEYS
+            .iter()
+            .find(|known| **known == key)
+            .map(|known| Cow::Borrowed(*known))
+            .unwrap_or_else(|| Cow::Owned(key.to_owned()));
+        self.insert(key, value.to_owned());
+    }
+}
+
+impl From<HashMap<String, String>> for PeerProperties {
+    fn from(properties: HashMap<String, String>) -> Self {
+        properties.into_iter().collect()
+    }
+}
+
+impl From<PeerProperties> for HashMap<String, String> {
+    fn from(properties: PeerProperties) -> Self {
+        properties
+            .0
+            .into_iter()
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for PeerProperties
+where
+    K: Into<Cow<'static, str>>,
+    V: Into<Cow<'static, str>>,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut properties = PeerProperties::new();
+        for (key, value) in iter {
+            properties.insert(key, value);
+        }
+        properties
+    }
+}
+
+#[cfg(test)]
+impl fake::Dummy<fake::Faker> for PeerProperties {
+    fn dummy_with_rng<R: rand::Rng + ?Sized>(config: &fake::Faker, rng: &mut R) -> Self {
+        let properties: HashMap<String, String> = fake::Dummy::dummy_with_rng(config, rng);
+        properties.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::HashMap};
+
+    use super::PeerProperties;
+    use crate::codec::{Decoder, Encoder};
+
+    #[test]
+    fn peer_properties_codec_test() {
+        let mut properties = PeerProperties::from_static(&[("product", "RabbitMQ")]);
+        properties.insert("version", "3.13.0".to_owned());
+        properties.insert("custom", "value");
+        properties.insert("product", "RabbitMQ Stream");
+
+        let mut encoded = Vec::new();
+        properties.encode(&mut encoded).unwrap();
+        assert_eq!(properties.encoded_size() as usize, encoded.len());
+        // the wire format of a map of strings
+        let map: HashMap<String, String> = properties.clone().into();
+        assert_eq!(map, HashMap::decode(&encoded).unwrap().1);
+
+        let (rest, decoded) = PeerProperties::decode(&encoded).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(properties, decoded);
+        assert_eq!(Some("RabbitMQ Stream"), decoded.get("product"));
+        assert_eq!(None, decoded.get("platform"));
+        assert_eq!(
+            vec!["product", "version", "custom"],
+            decoded.iter().map(|(key, _)| key).collect::<Vec<_>>()
+        );
+        // the known keys are not allocated
+        assert!(matches!(&decoded.0[1].0, Cow::Borrowed(_)));
+        assert!(matches!(&decoded.0[2].0, Cow::Owned(_)));
+    }
+}
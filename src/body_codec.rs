@@ -0,0 +1,221 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt};
+use rabbitmq_stream_protocol::message::{Message, MessageBuilder};
+
+use crate::{
+    consumer::Delivery,
+    error::{BodyCodecError, ConsumerDeliveryError},
+    Consumer, ConsumerHandle,
+};
+
+pub type CodecError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Encodes values to the bodies of one content type and back, see [`BodyCodecs`]
+pub trait BodyCodec<T>: Send + Sync {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError>;
+}
+
+/// Registry of [`BodyCodec`] keyed by content type
+///
+/// Producers encode values with the codec of the content type they choose, consumers
+/// decode every message with the codec of its content type.
+pub struct BodyCodecs<T> {
+    codecs: HashMap<String, Arc<dyn BodyCodec<T>>>,
+    default_content_type: Option<String>,
+}
+
+impl<T> Clone for BodyCodecs<T> {
+    fn clone(&self) -> Self {
+        BodyCodecs {
+            codecs: self.codecs.clone(),
+            default_content_type: self.default_content_type.clone(),
+        }
+    }
+}
+
+impl<T> Default for BodyCodecs<T> {
+    fn default() -> Self {
+        BodyCodecs {
+            codecs: HashMap::new(),
+            default_content_type: None,
+        }
+    }
+}
+
+impl<T> BodyCodecs<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the codec of `content_type`, replacing the one registered before
+    pub fn register(mut self, content_type: &str, codec: impl BodyCodec<T> + 'static) -> Self {
+        self.codecs.insert(content_type.to_owned(), Arc::new(codec));
+        self
+    }
+
+    /// Content type of the messages published without one
+    pub fn default_content_type(mut self, content_type: &str) -> Self {
+        self.default_content_type = Some(content_type.to_owned());
+        self
+    }
+
+    /// Start a message with `value` encoded as body and `content_type` as content type
+    pub fn encode(&self, content_type: &str, value: &T) -> Result<MessageBuilder, BodyCodecError> {
+        let data = self
+            .codec(Some(content_type))?
+            .encode(value)
+            .map_err(|error| BodyCodecError::Codec {
+                content_type: content_type.to_owned(),
+                error,
+            })?;
+        Ok(Message::builder().body(data).content_type(content_type))
+    }
+
+    /// Decode the body of `message` with the codec of its content type
+    pub fn decode(&self, message: &Message) -> Result<T, BodyCodecError> {
+        let content_type = message
+            .content_type()
+            .or(self.default_content_type.as_deref());
+        self.codec(content_type)?
+            .decode(message.data().unwrap_or_default())
+            .map_err(|error| BodyCodecError::Codec {
+                content_type: content_type.unwrap_or_default().to_owned(),
+                error,
+            })
+    }
+
+    fn codec(&self, content_type: Option<&str>) -> Result<&dyn BodyCodec<T>, BodyCodecError> {
+        content_type
+            .and_then(|content_type| self.codecs.get(content_type))
+            .map(|codec| codec.as_ref())
+            .ok_or_else(|| BodyCodecError::UnknownContentType(content_type.map(str::to_owned)))
+    }
+}
+
+/// [`Consumer`] yielding the bodies of the messages decoded with [`BodyCodecs`]
+pub struct DecodedConsumer<T> {
+    consumer: Consumer,
+    codecs: BodyCodecs<T>,
+}
+
+impl<T> DecodedConsumer<T> {
+    pub(crate) fn new(consumer: Consumer, codecs: BodyCodecs<T>) -> Self {
+        DecodedConsumer { consumer, codecs }
+    }
+
+    /// Return an handle for the underlying [`Consumer`]
+    pub fn handle(&self) -> ConsumerHandle {
+        self.consumer.handle()
+    }
+
+    /// Check if the consumer is closed
+    pub fn is_closed(&self) -> bool {
+        self.consumer.is_closed()
+    }
+
+    /// Return the underlying [`Consumer`]
+    pub fn into_inner(self) -> Consumer {
+        self.consumer
+    }
+
+    fn decode(&self, delivery: Delivery) -> Result<Delivery<T>, ConsumerDeliveryError> {
+        let offset = delivery.offset;
+        let message = self
+            .codecs
+            .decode(&delivery.message)
+            .map_err(|error| ConsumerDeliveryError::Decode { offset, error })?;
+        Ok(Delivery {
+            stream: delivery.stream,
+            subscription_id: delivery.subscription_id,
+            offset,
+            message,
+            chunk: delivery.chunk,
+        })
+    }
+}
+
+impl<T> Stream for DecodedConsumer<T> {
+    type Item = Result<Delivery<T>, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.consumer.poll_next_unpin(cx).map(|delivery| {
+            delivery.map(|delivery| delivery.and_then(|delivery| self.decode(delivery)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::message::Message;
+
+    use super::{BodyCodec, BodyCodecs, CodecError};
+    use crate::error::BodyCodecError;
+
+    struct Text;
+
+    impl BodyCodec<String> for Text {
+        fn encode(&self, value: &String) -> Result<Vec<u8>, CodecError> {
+            Ok(value.as_bytes().to_vec())
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<String, CodecError> {
+            Ok(String::from_utf8(data.to_vec())?)
+        }
+    }
+
+    struct Reversed;
+
+    impl BodyCodec<String> for Reversed {
+        fn encode(&self, value: &String) -> Result<Vec<u8>, CodecError> {
+            Ok(value.chars().rev().collect::<String>().into_bytes())
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<String, CodecError> {
+            Ok(String::from_utf8(data.to_vec())?.chars().rev().collect())
+        }
+    }
+
+    #[test]
+    fn body_codecs_test() {
+        let codecs = BodyCodecs::new()
+            .register("text/plain", Text)
+            .register("text/reversed", Reversed)
+            .default_content_type("text/plain");
+
+        let message = codecs
+            .encode("text/reversed", &"message".to_owned())
+            .unwrap()
+            .build();
+        assert_eq!(Some(&b"egassem"[..]), message.data());
+        assert_eq!("message", codecs.decode(&message).unwrap());
+
+        let message = Message::builder().body("message").build();
+        assert_eq!("message", codecs.decode(&message).unwrap());
+
+        let message = Message::builder()
+            .body("message")
+            .content_type("application/json")
+            .build();
+        assert!(matches!(
+            codecs.decode(&message),
+            Err(BodyCodecError::UnknownContentType(Some(content_type))) if content_type == "application/json"
+        ));
+
+        let message = Message::builder()
+            .body(vec![0xff])
+            .content_type("text/plain")
+            .build();
+        assert!(matches!(
+            codecs.decode(&message),
+            Err(BodyCodecError::Codec { .. })
+        ));
+    }
+}
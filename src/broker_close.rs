@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use rabbitmq_stream_protocol::ResponseCode;
+
+use crate::{error::ClientError, Client};
+
+/// Close of a connection initiated by the broker, e.g. when its node is drained
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrokerClose {
+    /// Node of the connection, as `host:port`
+    pub node: String,
+    pub code: ResponseCode,
+    pub reason: String,
+}
+
+/// Decision of a [`BrokerClosePolicy::Decide`] callback
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrokerCloseAction {
+    Recover,
+    Fail,
+}
+
+/// What the producers and consumers do once the broker closed their connection, see
+/// [`EnvironmentBuilder::on_broker_close`](crate::EnvironmentBuilder::on_broker_close)
+///
+/// ```
+/// use rabbitmq_stream_client::types::{BrokerCloseAction, BrokerClosePolicy, ResponseCode};
+///
+/// // recover from a drained node, fail when the permissions changed
+/// let policy = BrokerClosePolicy::decide(|close| match close.code {
+///     ResponseCode::AccessRefused => BrokerCloseAction::Fail,
+///     _ => BrokerCloseAction::Recover,
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub enum BrokerClosePolicy {
+    /// Recover on a new connection, as after a network failure
+    #[default]
+    Recover,
+    /// Close the producer or consumer, its pending operations fail with
+    /// [`ClientError::ClosedByBroker`]
+    Fail,
+    /// Let the callback decide, it is called by every producer and consumer of the connection
+    Decide(Arc<dyn Fn(&BrokerClose) -> BrokerCloseAction + Send + Sync>),
+}
+
+impl BrokerClosePolicy {
+    /// [`BrokerClosePolicy::Decide`] with `callback`
+    pub fn decide(
+        callback: impl Fn(&BrokerClose) -> BrokerCloseAction + Send + Sync + 'static,
+    ) -> Self {
+        BrokerClosePolicy::Decide(Arc::new(callback))
+    }
+
+    /// Error to fail with once the connection of `client` closed, `None` to recover or when
+    /// the broker did not close it
+    pub(crate) async fn failure(&self, client: &Client) -> Option<ClientError> {
+        let (code, reason) = client.close_reason().await?;
+        let close = BrokerClose {
+            node: client.node(),
+            code,
+            reason,
+        };
+        let action = match self {
+            BrokerClosePolicy::Recover => BrokerCloseAction::Recover,
+            BrokerClosePolicy::Fail => BrokerCloseAction::Fail,
+            BrokerClosePolicy::Decide(callback) => callback(&close),
+        };
+        (action == BrokerCloseAction::Fail).then_some(ClientError::ClosedByBroker {
+            code: close.code,
+            reason: close.reason,
+        })
+    }
+}
+
+impl std::fmt::Debug for BrokerClosePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrokerClosePolicy::Recover => f.write_str("Recover"),
+            BrokerClosePolicy::Fail => f.write_str("Fail"),
+            BrokerClosePolicy::Decide(_) => f.write_str("Decide"),
+        }
+    }
+}
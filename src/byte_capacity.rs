@@ -1,8 +1,16 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::RetentionParseError;
+
 pub const KILOBYTE: u64 = 1000;
 pub const MEGABYTE: u64 = 1000 * KILOBYTE;
 pub const GIGABYTE: u64 = 1000 * MEGABYTE;
 pub const TERABYTE: u64 = 1000 * GIGABYTE;
 
+/// Size in bytes, with the decimal units of the broker, e.g. for `max-length-bytes`
+///
+/// It parses from and displays as a number followed by an optional unit, like `500MB`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ByteCapacity {
     B(u64),
     KB(u64),
@@ -15,10 +23,93 @@ impl ByteCapacity {
     pub fn bytes(&self) -> u64 {
         match self {
             ByteCapacity::B(x) => *x,
-            ByteCapacity::KB(x) => x * KILOBYTE,
-            ByteCapacity::MB(x) => x * MEGABYTE,
-            ByteCapacity::GB(x) => x * GIGABYTE,
-            ByteCapacity::TB(x) => x * TERABYTE,
+            ByteCapacity::KB(x) => x.saturating_mul(KILOBYTE),
+            ByteCapacity::MB(x) => x.saturating_mul(MEGABYTE),
+            ByteCapacity::GB(x) => x.saturating_mul(GIGABYTE),
+            ByteCapacity::TB(x) => x.saturating_mul(TERABYTE),
+        }
+    }
+
+    /// The value of the stream arguments, a number of bytes
+    pub(crate) fn to_argument(self) -> String {
+        self.bytes().to_string()
+    }
+}
+
+impl fmt::Display for ByteCapacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteCapacity::B(x) => write!(f, "{}B", x),
+            ByteCapacity::KB(x) => write!(f, "{}KB", x),
+            ByteCapacity::MB(x) => write!(f, "{}MB", x),
+            ByteCapacity::GB(x) => write!(f, "{}GB", x),
+            ByteCapacity::TB(x) => write!(f, "{}TB", x),
+        }
+    }
+}
+
+impl FromStr for ByteCapacity {
+    type Err = RetentionParseError;
+
+    /// Parse a number of bytes followed by `B`, `KB`, `MB`, `GB` or `TB`, in any case
+    fn from_str(capacity: &str) -> Result<Self, Self::Err> {
+        let upper = capacity.trim().to_ascii_uppercase();
+        let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let value: u64 = digits
+            .parse()
+            .map_err(|_| RetentionParseError::InvalidValue(capacity.to_owned()))?;
+        let (parsed, unit) = match &upper[digits.len()..] {
+            "" | "B" => (ByteCapacity::B(value), 1),
+            "KB" => (ByteCapacity::KB(value), KILOBYTE),
+            "MB" => (ByteCapacity::MB(value), MEGABYTE),
+            "GB" => (ByteCapacity::GB(value), GIGABYTE),
+            "TB" => (ByteCapacity::TB(value), TERABYTE),
+            unit => {
+                return Err(RetentionParseError::UnknownUnit {
+                    value: capacity.to_owned(),
+                    unit: unit.to_owned(),
+                    expected: "B, KB, MB, GB or TB",
+                })
+            }
+        };
+        // the broker takes the size in bytes
+        match value.checked_mul(unit) {
+            Some(_) => Ok(parsed),
+            None => Err(RetentionParseError::InvalidValue(capacity.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ByteCapacity;
+    use crate::error::RetentionParseError;
+
+    #[test]
+    fn byte_capacity_parse_test() {
+        assert_eq!(Ok(ByteCapacity::GB(10)), "10GB".parse());
+        assert_eq!(Ok(ByteCapacity::MB(500)), "500mb".parse());
+        assert_eq!(Ok(ByteCapacity::B(42)), "42".parse());
+        assert_eq!(
+            Err(RetentionParseError::InvalidValue("GB".to_owned())),
+            "GB".parse::<ByteCapacity>()
+        );
+        assert_eq!(
+            Err(RetentionParseError::InvalidValue("20000000TB".to_owned())),
+            "20000000TB".parse::<ByteCapacity>()
+        );
+        assert_eq!(u64::MAX, ByteCapacity::TB(u64::MAX).bytes());
+        assert!(matches!(
+            "10PB".parse::<ByteCapacity>(),
+            Err(RetentionParseError::UnknownUnit { .. })
+        ));
+    }
+
+    #[test]
+    fn byte_capacity_display_test() {
+        for capacity in &[ByteCapacity::B(1), ByteCapacity::KB(2), ByteCapacity::TB(3)] {
+            assert_eq!(Ok(*capacity), capacity.to_string().parse());
         }
+        assert_eq!("10000000000", ByteCapacity::GB(10).to_argument());
     }
 }
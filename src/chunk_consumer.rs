@@ -0,0 +1,464 @@
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering::{Relaxed, SeqCst},
+        },
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use bytes::Bytes;
+use futures::{task::AtomicWaker, Stream};
+use rabbitmq_stream_protocol::{
+    commands::deliver::{self, RawDeliverCommand},
+    compression::Compression,
+    message::Message,
+    ResponseKind,
+};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tracing::trace;
+
+use crate::{
+    client::{MessageHandler, MessageResult},
+    consumer::{ChunkMetadata, ConsumerBuilder},
+    error::{
+        ClientError, ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError,
+        ConsumerQueryOffsetError, ConsumerStoreOffsetError,
+    },
+    metrics::{MetricsCollector, MetricsContext},
+    offset_tracking::{BrokerOffsetStore, OffsetStore},
+    Client,
+};
+
+/// API for consuming the chunks of a stream with their entries left undecoded
+///
+/// Meant for tools copying or archiving streams, which do not need the AMQP messages:
+/// the entries are yielded as stored by the broker, [`Chunk::messages`] decodes them on demand.
+pub struct ChunkConsumer {
+    receiver: Receiver<Result<Chunk, ConsumerDeliveryError>>,
+    internal: Arc<ChunkConsumerInternal>,
+}
+
+struct ChunkConsumerInternal {
+    client: Client,
+    stream: Arc<str>,
+    name: Option<String>,
+    subscription_id: u8,
+    sender: Sender<Result<Chunk, ConsumerDeliveryError>>,
+    closed: AtomicBool,
+    waker: AtomicWaker,
+    offset_store: Arc<dyn OffsetStore>,
+}
+
+impl ChunkConsumerInternal {
+    fn record(&self, event: impl Fn(&dyn MetricsCollector, &MetricsContext<'_>)) {
+        if let Some(metrics) = self.client.metrics() {
+            metrics.record(Some(&self.stream), event);
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Relaxed)
+    }
+
+    async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        let name = self
+            .name
+            .as_ref()
+            .ok_or(ConsumerStoreOffsetError::NameMissing)?;
+        self.offset_store
+            .store(name, &self.stream, offset)
+            .await
+            .map_err(ConsumerStoreOffsetError::from)
+    }
+
+    async fn query_offset(&self) -> Result<u64, ConsumerQueryOffsetError> {
+        let name = self
+            .name
+            .as_ref()
+            .ok_or(ConsumerQueryOffsetError::NameMissing)?;
+        self.offset_store
+            .load(name, &self.stream)
+            .await?
+            .ok_or_else(|| ConsumerQueryOffsetError::OffsetNotFound {
+                stream: self.stream.to_string(),
+                name: name.clone(),
+            })
+    }
+}
+
+/// Builder for [`ChunkConsumer`], see [`ConsumerBuilder::raw_chunks`]
+pub struct ChunkConsumerBuilder {
+    pub(crate) builder: ConsumerBuilder,
+}
+
+impl ChunkConsumerBuilder {
+    /// Subscribe to the stream
+    ///
+    /// A credit is granted to the broker for every chunk received.
+    pub async fn build(self, stream: &str) -> Result<ChunkConsumer, ConsumerCreateError> {
+        let builder = self.builder;
+        let client = builder.environment.create_raw_chunk_client().await?;
+
+        let subscription_id = 1;
+        let mut properties = HashMap::new();
+        if let Some(name) = &builder.name {
+            properties.insert("name".to_owned(), name.clone());
+        }
+
+        let (tx, rx) = channel(builder.initial_credits.max(1) as usize);
+        let consumer = Arc::new(ChunkConsumerInternal {
+            client: client.clone(),
+            stream: stream.into(),
+            name: builder.name,
+            subscription_id,
+            sender: tx,
+            closed: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+            offset_store: builder
+                .offset_store
+                .unwrap_or_else(|| Arc::new(BrokerOffsetStore::new(client.clone()))),
+        });
+        client
+            .set_handler(ChunkConsumerMessageHandler(consumer.clone()))
+            .await;
+
+        let response = client
+            .subscribe(
+                subscription_id,
+                stream,
+                builder.offset_specification,
+                builder.initial_credits,
+                properties,
+            )
+            .await?;
+
+        if response.is_ok() {
+            let credits = builder.initial_credits as u64;
+            consumer.record(|collector, context| collector.credit(context, credits));
+            Ok(ChunkConsumer {
+                receiver: rx,
+                internal: consumer,
+            })
+        } else {
+            Err(ConsumerCreateError::Create {
+                stream: stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
+}
+
+impl ChunkConsumer {
+    /// Return an handle for current [`ChunkConsumer`]
+    pub fn handle(&self) -> ChunkConsumerHandle {
+        ChunkConsumerHandle(self.internal.clone())
+    }
+
+    /// Check if the consumer is closed
+    pub fn is_closed(&self) -> bool {
+        self.internal.is_closed()
+    }
+}
+
+impl Stream for ChunkConsumer {
+    type Item = Result<Chunk, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.internal.waker.register(cx.waker());
+        let poll = Pin::new(&mut self.receiver).poll_recv(cx);
+        match (self.is_closed(), poll.is_ready()) {
+            (true, false) => Poll::Ready(None),
+            _ => poll,
+        }
+    }
+}
+
+/// Handler API for [`ChunkConsumer`]
+#[derive(Clone)]
+pub struct ChunkConsumerHandle(Arc<ChunkConsumerInternal>);
+
+impl ChunkConsumerHandle {
+    /// Close the [`ChunkConsumer`] associated to this handle
+    pub async fn close(self) -> Result<(), ConsumerCloseError> {
+        match self.0.closed.compare_exchange(false, true, SeqCst, SeqCst) {
+            Ok(false) => {
+                let response = self.0.client.unsubscribe(self.0.subscription_id).await?;
+                if response.is_ok() {
+                    self.0.waker.wake();
+                    Ok(())
+                } else {
+                    Err(ConsumerCloseError::Close {
+                        stream: self.0.stream.to_string(),
+                        status: response.code().clone(),
+                    })
+                }
+            }
+            _ => Err(ConsumerCloseError::AlreadyClosed),
+        }
+    }
+
+    /// Check if the consumer is closed
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    /// Store `offset` under the name of the consumer, e.g. the last offset of a copied chunk
+    pub async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        self.0.store_offset(offset).await
+    }
+
+    /// Query the offset last stored under the name of the consumer
+    pub async fn query_offset(&self) -> Result<u64, ConsumerQueryOffsetError> {
+        self.0.query_offset().await
+    }
+}
+
+struct ChunkConsumerMessageHandler(Arc<ChunkConsumerInternal>);
+
+#[async_trait::async_trait]
+impl MessageHandler for ChunkConsumerMessageHandler {
+    async fn handle_message(&self, item: MessageResult) -> crate::RabbitMQStreamResult<()> {
+        match item {
+            Some(Ok(response)) => {
+                if let ResponseKind::RawDeliver(deliver) = response.kind() {
+                    let records = deliver.num_records() as u64;
+                    let bytes = deliver.data.len() as u64;
+                    self.0.record(|collector, context| {
+                        collector.chunk(context, records);
+                        collector.delivered_bytes(context, bytes);
+                    });
+                    let _ = self
+                        .0
+                        .sender
+                        .send(Ok(Chunk {
+                            stream: self.0.stream.clone(),
+                            subscription_id: self.0.subscription_id,
+                            deliver,
+                        }))
+                        .await;
+
+                    // TODO handle credit fail
+                    if self
+                        .0
+                        .client
+                        .credit(self.0.subscription_id, 1)
+                        .await
+                        .is_ok()
+                    {
+                        self.0
+                            .record(|collector, context| collector.credit(context, 1));
+                    }
+                }
+            }
+            Some(Err(err)) => {
+                let _ = self.0.sender.send(Err(err.into())).await;
+            }
+            None => {
+                trace!("Closing chunk consumer");
+                self.0.closed.store(true, Relaxed);
+                self.0.waker.wake();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Chunk received by a [`ChunkConsumer`]
+#[derive(Debug)]
+pub struct Chunk {
+    stream: Arc<str>,
+    /// Subscription the chunk was delivered to
+    pub subscription_id: u8,
+    deliver: RawDeliverCommand,
+}
+
+impl Chunk {
+    /// Stream the chunk was read from
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// Metadata of the chunk, as attached to the deliveries of a [`crate::Consumer`]
+    pub fn metadata(&self) -> ChunkMetadata {
+        ChunkMetadata {
+            first_offset: self.first_offset(),
+            timestamp: self.timestamp(),
+            epoch: self.epoch(),
+            num_entries: self.num_entries(),
+            num_records: self.num_records(),
+        }
+    }
+
+    /// Offset of the first message of the chunk
+    pub fn first_offset(&self) -> u64 {
+        self.deliver.chunk_first_offset()
+    }
+
+    /// Offset of the last message of the chunk
+    pub fn last_offset(&self) -> u64 {
+        self.first_offset() + (self.num_records() as u64).saturating_sub(1)
+    }
+
+    /// Creation time of the chunk in milliseconds since the epoch
+    pub fn timestamp(&self) -> u64 {
+        self.deliver.timestamp()
+    }
+
+    /// Epoch of the stream leader which wrote the chunk
+    pub fn epoch(&self) -> u64 {
+        self.deliver.epoch()
+    }
+
+    /// Type of the chunk, 0 for user messages
+    pub fn chunk_type(&self) -> u8 {
+        self.deliver.chunk_type()
+    }
+
+    /// Number of entries, a sub-entry counts as one entry
+    pub fn num_entries(&self) -> u16 {
+        self.deliver.num_entries()
+    }
+
+    /// Number of messages in the chunk
+    pub fn num_records(&self) -> u32 {
+        self.deliver.num_records()
+    }
+
+    /// CRC32 of the entries, as computed by the broker
+    pub fn crc(&self) -> i32 {
+        self.deliver.chunk_crc()
+    }
+
+    /// Check the CRC32 of the entries against [`Chunk::crc`]
+    ///
+    /// The chunks are checked on delivery with
+    /// [`EnvironmentBuilder::check_chunk_crc`](crate::EnvironmentBuilder::check_chunk_crc).
+    pub fn check_crc(&self) -> Result<(), ClientError> {
+        Ok(self.deliver.check_crc()?)
+    }
+
+    /// Entries of the chunk as stored by the broker
+    pub fn data(&self) -> &[u8] {
+        &self.deliver.data
+    }
+
+    /// Take the entries of the chunk, sharing the bytes of the frame
+    pub fn into_data(self) -> Bytes {
+        self.deliver.data
+    }
+
+    /// Decode the messages of the chunk
+    pub fn messages(&self) -> Result<Vec<Message>, ClientError> {
+        Ok(self.deliver.messages()?)
+    }
+
+    /// Messages of the chunk left encoded, sharing the bytes of the chunk
+    ///
+    /// Unlike [`Chunk::messages`], nothing is decoded or allocated per message for an
+    /// uncompressed chunk, [`ChunkRecord::message`] decodes the messages needed. The
+    /// iteration ends after an error.
+    pub fn records(&self) -> impl Iterator<Item = Result<ChunkRecord, ClientError>> + '_ {
+        (self.first_offset()..)
+            .zip(self.deliver.records())
+            .map(|(offset, record)| Ok(ChunkRecord::new(offset, record?)))
+    }
+
+    /// Entries of the chunk, a sub-entry packing several messages is yielded as one entry
+    ///
+    /// Skipping an entry only costs reading its size, the records of a sub-entry are
+    /// decompressed and decoded by [`ChunkEntry::messages`]. The iteration ends after an error.
+    pub fn entries(&self) -> impl Iterator<Item = Result<ChunkEntry, ClientError>> + '_ {
+        let mut offset = self.first_offset();
+        self.deliver.entries().map(move |entry| {
+            let entry = entry?;
+            let first_offset = offset;
+            offset += entry.records() as u64;
+            Ok(ChunkEntry {
+                offset: first_offset,
+                entry,
+            })
+        })
+    }
+}
+
+/// Entry of a [`Chunk`], a message or a sub-entry, see [`Chunk::entries`]
+#[derive(Clone, Debug)]
+pub struct ChunkEntry {
+    offset: u64,
+    entry: deliver::ChunkEntry,
+}
+
+impl ChunkEntry {
+    /// Offset of the first message of the entry
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Offset of the last message of the entry
+    pub fn last_offset(&self) -> u64 {
+        self.offset + (self.num_records() as u64).saturating_sub(1)
+    }
+
+    /// Number of messages of the entry, more than one for a sub-entry
+    pub fn num_records(&self) -> u16 {
+        self.entry.records()
+    }
+
+    /// Check if the entry is a sub-entry
+    pub fn is_sub_entry(&self) -> bool {
+        self.entry.is_sub_entry()
+    }
+
+    /// Compression of the records of a sub-entry, `None` for a single message
+    pub fn compression(&self) -> Option<Compression> {
+        self.entry.compression()
+    }
+
+    /// The encoded message, or the records of a sub-entry as stored by the broker
+    pub fn data(&self) -> &[u8] {
+        self.entry.data()
+    }
+
+    /// Decode the messages of the entry, decompressing the records of a sub-entry
+    pub fn messages(&self) -> Result<Vec<Message>, ClientError> {
+        Ok(self.entry.messages()?)
+    }
+}
+
+/// Encoded message of a [`Chunk`], see [`Chunk::records`]
+#[derive(Clone, Debug)]
+pub struct ChunkRecord {
+    offset: u64,
+    data: Bytes,
+}
+
+impl ChunkRecord {
+    fn new(offset: u64, data: Bytes) -> Self {
+        ChunkRecord { offset, data }
+    }
+
+    /// Offset of the message in the stream
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// AMQP encoding of the message
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Take the encoded message, sharing the bytes of the chunk
+    pub fn into_data(self) -> Bytes {
+        self.data
+    }
+
+    /// Decode the message, its body sharing the bytes of the chunk
+    pub fn message(&self) -> Result<Message, ClientError> {
+        Ok(Message::decode_shared(&self.data)?)
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+/// Skip the nodes whose connections keep failing, see
+/// [`EnvironmentBuilder::circuit_breaker`](crate::EnvironmentBuilder::circuit_breaker)
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rabbitmq_stream_client::types::CircuitBreaker;
+///
+/// let breaker = CircuitBreaker::new()
+///     .failure_threshold(5)
+///     .cool_down(Duration::from_secs(10));
+/// ```
+///
+/// After `failure_threshold` consecutive failures to connect to a node, or of requests
+/// sent to it, the circuit of the node opens: the connections skip it for `cool_down`. The
+/// first connection after the cool-down is a trial, its success closes the circuit and its
+/// failure opens it again. The clones share the circuits.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cool_down: Duration,
+    nodes: Arc<Mutex<HashMap<String, NodeCircuit>>>,
+}
+
+#[derive(Debug, Default)]
+struct NodeCircuit {
+    /// Consecutive failures
+    failures: u32,
+    /// Skipped until then, once the failures reached the threshold
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// Open a circuit after 3 failures, for 30 seconds
+    pub fn new() -> Self {
+        CircuitBreaker {
+            failure_threshold: 3,
+            cool_down: Duration::from_secs(30),
+            nodes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Consecutive failures opening the circuit of a node
+    pub fn failure_threshold(mut self, failures: u32) -> Self {
+        self.failure_threshold = failures.max(1);
+        self
+    }
+
+    /// Time a node is skipped once its circuit opened
+    pub fn cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Nodes skipped now, as `host:port`
+    pub fn open_nodes(&self) -> Vec<String> {
+        let now = Instant::now();
+        let nodes = self.nodes.lock().unwrap();
+        let mut open: Vec<String> = nodes
+            .iter()
+            .filter(|(_, circuit)| circuit.open_until.is_some_and(|until| until > now))
+            .map(|(node, _)| node.clone())
+            .collect();
+        open.sort();
+        open
+    }
+
+    /// Whether a connection may go to `node`, the trial after the cool-down holds the
+    /// circuit open for the others
+    pub(crate) fn allow(&self, node: &str) -> bool {
+        let now = Instant::now();
+        let mut nodes = self.nodes.lock().unwrap();
+        match nodes
+            .get_mut(node)
+            .and_then(|circuit| circuit.open_until.as_mut())
+        {
+            Some(until) if *until > now => false,
+            Some(until) => {
+                *until = now + self.cool_down;
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub(crate) fn record_success(&self, node: &str) {
+        self.nodes.lock().unwrap().remove(node);
+    }
+
+    pub(crate) fn record_failure(&self, node: &str) {
+        let mut nodes = self.nodes.lock().unwrap();
+        let circuit = nodes.entry(node.to_owned()).or_default();
+        circuit.failures += 1;
+        if circuit.failures >= self.failure_threshold {
+            circuit.open_until = Some(Instant::now() + self.cool_down);
+        }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::CircuitBreaker;
+
+    #[tokio::test(start_paused = true)]
+    async fn circuit_breaker_test() {
+        let breaker = CircuitBreaker::new()
+            .failure_threshold(2)
+            .cool_down(Duration::from_secs(10));
+        breaker.record_failure("node-1:5552");
+        assert!(breaker.allow("node-1:5552"));
+        breaker.record_failure("node-1:5552");
+        assert!(!breaker.allow("node-1:5552"));
+        assert!(breaker.allow("node-2:5552"));
+        assert_eq!(vec!["node-1:5552"], breaker.open_nodes());
+
+        // a single trial once cooled down, it fails and the circuit opens again
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(breaker.allow("node-1:5552"));
+        assert!(!breaker.allow("node-1:5552"));
+        breaker.record_failure("node-1:5552");
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(!breaker.allow("node-1:5552"));
+
+        // the successful trial closes it
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(breaker.allow("node-1:5552"));
+        breaker.record_success("node-1:5552");
+        assert!(breaker.allow("node-1:5552"));
+        assert!(breaker.open_nodes().is_empty());
+    }
+
+    #[test]
+    fn success_resets_failures_test() {
+        let breaker = CircuitBreaker::new().failure_threshold(2);
+        breaker.record_failure("node-1:5552");
+        breaker.record_success("node-1:5552");
+        breaker.record_failure("node-1:5552");
+        assert!(breaker.allow("node-1:5552"));
+    }
+}
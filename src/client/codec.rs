@@ -1,28 +1,68 @@
-use bytes::{Buf, BufMut, BytesMut};
-use rabbitmq_stream_protocol::{error::DecodeError, Request, Response};
+use bytes::{BufMut, BytesMut};
+use rabbitmq_stream_protocol::{Request, Response};
 use tokio_util::codec::{Decoder as TokioDecoder, Encoder as TokioEncoder};
 
-use rabbitmq_stream_protocol::codec::{Decoder, Encoder};
+use rabbitmq_stream_protocol::codec::Encoder;
 
-use crate::error::ClientError;
+use std::sync::Arc;
 
-#[derive(Debug)]
-pub(crate) struct RabbitMqStreamCodec {}
+use super::heartbeat::HeartbeatRtt;
+use crate::{
+    error::ClientError,
+    metrics::Metrics,
+    wire_capture::{ConnectionCapture, FrameDirection},
+};
+
+/// Most bytes reserved ahead for a partially read frame
+const MAX_RESERVE: usize = 1024 * 1024;
+
+pub(crate) struct RabbitMqStreamCodec {
+    /// Decode Deliver frames as raw chunks
+    pub(crate) raw_chunks: bool,
+    /// Check the CRC of Deliver frames before decoding them
+    pub(crate) check_crc: bool,
+    pub(crate) metrics: Option<Metrics>,
+    pub(crate) capture: Option<ConnectionCapture>,
+    pub(crate) heartbeat_rtt: Arc<HeartbeatRtt>,
+}
 
 impl TokioDecoder for RabbitMqStreamCodec {
     type Item = Response;
     type Error = ClientError;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Response>, ClientError> {
-        match Response::decode(buf) {
-            Ok((remaining, response)) => {
-                let len = remaining.len();
-                buf.advance(buf.len() - len);
-                Ok(Some(response))
+        // frames are decoded once fully read, split from the buffer so the messages
+        // of Deliver frames share its bytes
+        if buf.len() < 4 {
+            return Ok(None);
+        }
+        let len = 4 + u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        if buf.len() < len {
+            // the size is read from the network, the buffer grows as the frame arrives
+            buf.reserve((len - buf.len()).min(MAX_RESERVE));
+            return Ok(None);
+        }
+        let frame = buf.split_to(len).freeze();
+        if let Some(rtt) = self.heartbeat_rtt.received() {
+            if let Some(metrics) = &self.metrics {
+                metrics.record(None, |collector, context| {
+                    collector.heartbeat_rtt(context, rtt)
+                });
             }
-            Err(DecodeError::Incomplete(_)) => Ok(None),
-            Err(e) => Err(e.into()),
         }
+        if let Some(capture) = &self.capture {
+            capture.record(FrameDirection::Inbound, &frame);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record(None, |collector, context| {
+                collector.read_bytes(context, len as u64)
+            });
+        }
+        if self.check_crc {
+            Response::check_chunk_crc(&frame)?;
+        }
+        let (_, response) = Response::decode_bytes(&frame, self.raw_chunks)?;
+        Ok(Some(response))
     }
 }
 
@@ -31,9 +71,19 @@ impl TokioEncoder<Request> for RabbitMqStreamCodec {
 
     fn encode(&mut self, req: Request, buf: &mut BytesMut) -> Result<(), ClientError> {
         let len = req.encoded_size();
-        buf.reserve(len as usize);
+        let start = buf.len();
+        // the frame is encoded in place, its size prefix included
+        buf.reserve(4 + len as usize);
         let mut writer = buf.writer();
         req.encode(&mut writer)?;
+        if let Some(capture) = &self.capture {
+            capture.record(FrameDirection::Outbound, &writer.get_ref()[start..]);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record(None, |collector, context| {
+                collector.written_bytes(context, len as u64)
+            });
+        }
 
         Ok(())
     }
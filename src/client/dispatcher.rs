@@ -70,6 +70,18 @@ where
         (correlation_id, rx)
     }
 
+    /// Stop waiting for the response of `correlation_id`
+    pub async fn forget(&self, correlation_id: u32) {
+        self.0.requests.lock().await.remove(&correlation_id);
+    }
+
+    /// Correlation ids of the requests waiting for a response
+    pub async fn pending_correlation_ids(&self) -> Vec<u32> {
+        let mut correlation_ids: Vec<u32> = self.0.requests.lock().await.keys().copied().collect();
+        correlation_ids.sort_unstable();
+        correlation_ids
+    }
+
     #[cfg(test)]
     async fn requests_count(&self) -> usize {
         self.0.requests.lock().await.len()
@@ -145,6 +157,8 @@ where
             // TODO handle reconnection if the connection is broken
             trace!("Stream closed");
         }
+        // the requests waiting for a response fail
+        state.requests.lock().await.clear();
         state.close(None).await;
     });
 }
@@ -152,10 +166,7 @@ where
 #[cfg(test)]
 mod tests {
 
-    use std::{
-        collections::HashMap,
-        sync::{Arc, Mutex},
-    };
+    use std::sync::{Arc, Mutex};
 
     use rabbitmq_stream_protocol::{
         commands::{
@@ -163,7 +174,7 @@ mod tests {
             peer_properties::{PeerPropertiesCommand, PeerPropertiesResponse},
             Command,
         },
-        types::Header,
+        types::{Header, PeerProperties},
         Request, RequestKind, Response, ResponseCode, ResponseKind,
     };
     use tokio::sync::mpsc::channel as tokio_channel;
@@ -209,7 +220,7 @@ mod tests {
                             let peer_response = PeerPropertiesResponse::new(
                                 peer.correlation_id(),
                                 ResponseCode::Ok,
-                                HashMap::new(),
+                                PeerProperties::new(),
                             );
                             let response = Response::new(
                                 Header::new(peer.key(), 1),
@@ -275,7 +286,8 @@ mod tests {
 
         let (correlation_id, mut rx) = dispatcher.response_channel().await;
 
-        let req: Request = PeerPropertiesCommand::new(correlation_id, HashMap::new()).into();
+        let req: Request =
+            PeerPropertiesCommand::new(correlation_id, &PeerProperties::new()).into();
 
         assert_eq!(1, dispatcher.requests_count().await);
         tx.send(req).await.unwrap();
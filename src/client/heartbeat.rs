@@ -0,0 +1,110 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::Sink;
+use rabbitmq_stream_protocol::{commands::heart_beat::HeartBeatCommand, Request};
+use tracing::trace;
+
+use super::channel::ChannelSender;
+
+/// No heartbeat waiting for a frame, or no round trip measured yet
+const NONE: u64 = u64::MAX;
+
+/// Round trip between the heartbeats sent to the broker and the next frame read
+pub(crate) struct HeartbeatRtt {
+    origin: Instant,
+    /// Microseconds since `origin` the last heartbeat was sent at
+    sent_at: AtomicU64,
+    last_rtt: AtomicU64,
+}
+
+impl HeartbeatRtt {
+    pub(crate) fn new() -> Self {
+        HeartbeatRtt {
+            origin: Instant::now(),
+            sent_at: AtomicU64::new(NONE),
+            last_rtt: AtomicU64::new(NONE),
+        }
+    }
+
+    fn sent(&self) {
+        let now = self.origin.elapsed().as_micros() as u64;
+        // a heartbeat still waiting keeps its time, the round trip includes the wait
+        let _ = self
+            .sent_at
+            .compare_exchange(NONE, now, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    /// A frame was read, the round trip if a heartbeat was waiting for it
+    pub(crate) fn received(&self) -> Option<Duration> {
+        let sent_at = self.sent_at.swap(NONE, Ordering::Relaxed);
+        if sent_at == NONE {
+            return None;
+        }
+        let now = self.origin.elapsed().as_micros() as u64;
+        let rtt = now.saturating_sub(sent_at);
+        self.last_rtt.store(rtt, Ordering::Relaxed);
+        Some(Duration::from_micros(rtt))
+    }
+
+    pub(crate) fn last_rtt(&self) -> Option<Duration> {
+        match self.last_rtt.load(Ordering::Relaxed) {
+            NONE => None,
+            rtt => Some(Duration::from_micros(rtt)),
+        }
+    }
+}
+
+/// Send a heartbeat every `interval` until the connection is closed or dropped
+pub(crate) async fn send_heartbeats<T>(
+    channel: Weak<ChannelSender<T>>,
+    rtt: Arc<HeartbeatRtt>,
+    interval: Duration,
+) where
+    T: Sink<Request> + Unpin,
+{
+    let mut ticker = tokio::time::interval(interval);
+    // the first tick completes immediately
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let channel = match channel.upgrade() {
+            Some(channel) if !channel.is_closed() => channel,
+            _ => break,
+        };
+        rtt.sent();
+        if channel
+            .send(HeartBeatCommand::default().into())
+            .await
+            .is_err()
+        {
+            trace!("Failed to send a heartbeat");
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeartbeatRtt;
+
+    #[test]
+    fn heartbeat_rtt_test() {
+        let rtt = HeartbeatRtt::new();
+        assert_eq!(None, rtt.received());
+        assert_eq!(None, rtt.last_rtt());
+
+        rtt.sent();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let measured = rtt.received().unwrap();
+        assert!(measured.as_millis() >= 2);
+        assert_eq!(Some(measured), rtt.last_rtt());
+        // only the first frame after a heartbeat counts
+        assert_eq!(None, rtt.received());
+    }
+}
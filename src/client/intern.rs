@@ -0,0 +1,46 @@
+//! Stream and reference names shared by the commands instead of allocated by each of them
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+/// Names kept at most, the ones beyond are allocated on every call
+const MAX_NAMES: usize = 4096;
+
+fn names() -> &'static RwLock<HashSet<Arc<str>>> {
+    static NAMES: OnceLock<RwLock<HashSet<Arc<str>>>> = OnceLock::new();
+    NAMES.get_or_init(Default::default)
+}
+
+/// Shared copy of `name`, allocated the first time only
+pub(crate) fn intern(name: &str) -> Arc<str> {
+    if let Some(interned) = names().read().unwrap().get(name) {
+        return interned.clone();
+    }
+    let mut names = names().write().unwrap();
+    if let Some(interned) = names.get(name) {
+        return interned.clone();
+    }
+    let interned: Arc<str> = name.into();
+    if names.len() < MAX_NAMES {
+        names.insert(interned.clone());
+    }
+    interned
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::intern;
+
+    #[test]
+    fn intern_test() {
+        let first = intern("intern-test-stream");
+        let second = intern(&String::from("intern-test-stream"));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!("intern-test-stream", &*second);
+        assert!(!Arc::ptr_eq(&first, &intern("intern-test-other")));
+    }
+}
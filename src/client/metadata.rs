@@ -8,7 +8,7 @@ pub struct Broker {
     pub port: u32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct StreamMetadata {
     pub stream: String,
     pub response_code: ResponseCode,
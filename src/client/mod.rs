@@ -2,69 +2,130 @@ mod channel;
 mod codec;
 mod dispatcher;
 mod handler;
+mod heartbeat;
+mod intern;
 mod metadata;
 mod options;
+mod stream;
+mod tls;
 
-use crate::{error::ClientError, RabbitMQStreamResult};
+use crate::{
+    debug_state::{ClientDebugState, PublisherDebugState, SubscriptionDebugState},
+    error::ClientError,
+    events::{self, StreamEvent},
+    metrics::{Metrics, MetricsCollector, MetricsContext},
+    names::{validate_stream, validate_super_stream, validate_vhost},
+    timeouts::with_timeout,
+    RabbitMQStreamResult,
+};
 use futures::{
     stream::{SplitSink, SplitStream},
     Stream, StreamExt, TryFutureExt,
 };
 pub use metadata::{Broker, StreamMetadata};
-pub use options::ClientOptions;
+pub use options::{ClientOptions, TlsConfiguration};
 use rabbitmq_stream_protocol::{
+    codec::Encoder,
     commands::{
         close::{CloseRequest, CloseResponse},
+        consumer_update::ConsumerUpdateResponse,
         create_stream::CreateStreamCommand,
+        create_super_stream::CreateSuperStreamCommand,
         credit::CreditCommand,
         declare_publisher::DeclarePublisherCommand,
         delete::Delete,
         delete_publisher::DeletePublisherCommand,
+        delete_super_stream::DeleteSuperStreamCommand,
         generic::GenericResponse,
         metadata::MetadataCommand,
         open::{OpenCommand, OpenResponse},
-        peer_properties::{PeerPropertiesCommand, PeerPropertiesResponse},
+        partitions::{PartitionsCommand, PartitionsResponse},
+        peer_properties::{encode_properties, PeerPropertiesCommand, PeerPropertiesResponse},
         publish::PublishCommand,
         query_offset::{QueryOffsetRequest, QueryOffsetResponse},
         query_publisher_sequence::{QueryPublisherRequest, QueryPublisherResponse},
+        route::{RouteCommand, RouteResponse},
         sasl_authenticate::SaslAuthenticateCommand,
         sasl_handshake::{SaslHandshakeCommand, SaslHandshakeResponse},
         store_offset::StoreOffset,
+        stream_stats::{StreamStatsCommand, StreamStatsResponse},
         subscribe::{OffsetSpecification, SubscribeCommand},
         tune::TunesCommand,
         unsubscribe::UnSubscribeCommand,
     },
+    compression::{Compression, ZstdDictionary},
+    error::EncodeError,
     message::Message,
-    types::PublishedMessage,
+    types::{PeerProperties, PublishedMessage},
     FromResponse, Request, Response, ResponseCode, ResponseKind,
 };
-use tracing::trace;
+pub use tls::CertificateStore;
+use tracing::{debug_span, field, trace, warn, Instrument};
 
 pub use self::handler::{MessageHandler, MessageResult};
+pub(crate) use self::intern::intern;
 use self::{
     channel::{channel, ChannelReceiver, ChannelSender},
     codec::RabbitMqStreamCodec,
     dispatcher::Dispatcher,
+    heartbeat::{send_heartbeats, HeartbeatRtt},
+    stream::GenericTcpStream,
 };
 
 use std::future::Future;
 use std::{
     collections::HashMap,
-    sync::{atomic::AtomicU64, Arc},
+    sync::{atomic::AtomicU64, Arc, OnceLock},
+    time::{Duration, Instant},
 };
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, RwLock};
 use tokio::{net::TcpStream, sync::Notify};
 use tokio_util::codec::Framed;
 
-type SinkConnection = SplitSink<Framed<TcpStream, RabbitMqStreamCodec>, Request>;
-type StreamConnection = SplitStream<Framed<TcpStream, RabbitMqStreamCodec>>;
+/// Bytes of a publish frame besides the messages: size, key, version, publisher id and count
+pub(crate) const PUBLISH_FRAME_OVERHEAD: usize = 4 + 2 + 2 + 1 + 4;
+
+/// Properties the client introduces itself with, encoded on the first connection only
+fn client_properties() -> &'static [u8] {
+    static CLIENT_PROPERTIES: OnceLock<Vec<u8>> = OnceLock::new();
+    CLIENT_PROPERTIES.get_or_init(|| {
+        encode_properties(&PeerProperties::from_static(&[
+            ("product", "RabbitMQ Stream"),
+            ("version", env!("CARGO_PKG_VERSION")),
+            ("platform", "Rust"),
+            (
+                "information",
+                "Licensed under the Apache 2.0 and MPL 2.0 licenses",
+            ),
+        ]))
+    })
+}
+
+/// Fail the handshake if the broker answered `step` with an error
+fn handshake_step(step: &'static str, code: &ResponseCode) -> Result<(), ClientError> {
+    match code {
+        ResponseCode::Ok => Ok(()),
+        code => Err(ClientError::Handshake {
+            step,
+            code: code.clone(),
+        }),
+    }
+}
+
+type SinkConnection = SplitSink<Framed<GenericTcpStream, RabbitMqStreamCodec>, Request>;
+type StreamConnection = SplitStream<Framed<GenericTcpStream, RabbitMqStreamCodec>>;
 
 pub struct ClientState {
-    server_properties: HashMap<String, String>,
-    connection_properties: HashMap<String, String>,
-    handler: Option<Arc<dyn MessageHandler>>,
+    server_properties: PeerProperties,
+    connection_properties: PeerProperties,
+    /// Queue of the task handing the messages to the handler, in the order they arrived
+    handler: Option<mpsc::UnboundedSender<MessageResult>>,
     heartbeat: u32,
     max_frame_size: u32,
+    subscriptions: HashMap<u8, SubscriptionDebugState>,
+    publishers: HashMap<u8, PublisherDebugState>,
+    /// Code and reason of a close initiated by the broker
+    close_reason: Option<(ResponseCode, String)>,
 }
 
 #[async_trait::async_trait]
@@ -73,27 +134,34 @@ impl MessageHandler for Client {
         match &item {
             Some(Ok(response)) => match response.kind_ref() {
                 ResponseKind::Tunes(tune) => self.handle_tune_command(tune).await,
-                _ => {
+                ResponseKind::CloseRequest(close) => self.handle_close_request(close).await,
+                kind => {
+                    let subscription_id = match kind {
+                        ResponseKind::Deliver(delivery) => Some(delivery.subscription_id),
+                        ResponseKind::RawDeliver(delivery) => Some(delivery.subscription_id),
+                        _ => None,
+                    };
+                    if let Some(subscription_id) = subscription_id {
+                        self.update_subscription(subscription_id, |subscription| {
+                            subscription.credits = subscription.credits.saturating_sub(1)
+                        })
+                        .await;
+                    }
                     if let Some(handler) = self.state.read().await.handler.as_ref() {
-                        let handler = handler.clone();
-
-                        tokio::task::spawn(async move { handler.handle_message(item).await });
+                        let _ = handler.send(item);
                     }
                 }
             },
             Some(Err(err)) => {
                 trace!(?err);
                 if let Some(handler) = self.state.read().await.handler.as_ref() {
-                    let handler = handler.clone();
-
-                    tokio::task::spawn(async move { handler.handle_message(item).await });
+                    let _ = handler.send(item);
                 }
             }
             None => {
                 trace!("Closing client");
                 if let Some(handler) = self.state.read().await.handler.as_ref() {
-                    let handler = handler.clone();
-                    tokio::task::spawn(async move { handler.handle_message(None).await });
+                    let _ = handler.send(None);
                 }
             }
         }
@@ -112,22 +180,56 @@ pub struct Client {
     opts: ClientOptions,
     tune_notifier: Arc<Notify>,
     publish_sequence: Arc<AtomicU64>,
+    metrics: Option<Metrics>,
+    heartbeat_rtt: Arc<HeartbeatRtt>,
 }
 
 impl Client {
     pub async fn connect(opts: impl Into<ClientOptions>) -> Result<Client, ClientError> {
         let broker = opts.into();
+        validate_vhost(&broker.v_host)?;
+        let span = debug_span!(
+            "rabbitmq_stream.connect",
+            host = %broker.host,
+            port = broker.port,
+            vhost = %broker.v_host,
+            elapsed_us = field::Empty,
+        );
 
-        let (sender, receiver) = Client::create_connection(&broker).await?;
+        let started = Instant::now();
+        let client = Client::open_connection(broker)
+            .instrument(span.clone())
+            .await;
+        span.record("elapsed_us", started.elapsed().as_micros() as u64);
+        match &client {
+            Ok(_) => trace!(parent: &span, "Connection opened"),
+            Err(error) => trace!(parent: &span, ?error, "Failed to open the connection"),
+        }
+        client
+    }
+
+    async fn open_connection(broker: ClientOptions) -> Result<Client, ClientError> {
+        let started = Instant::now();
+        let metrics = broker.metrics();
+        let heartbeat_rtt = Arc::new(HeartbeatRtt::new());
+        let (sender, receiver) = with_timeout(
+            "connect",
+            broker.timeouts.connect,
+            Client::create_connection(&broker, metrics.clone(), heartbeat_rtt.clone()),
+        )
+        .await?;
 
         let dispatcher = Dispatcher::new();
 
         let state = ClientState {
-            server_properties: HashMap::new(),
-            connection_properties: HashMap::new(),
+            server_properties: PeerProperties::new(),
+            connection_properties: PeerProperties::new(),
             handler: None,
             heartbeat: broker.heartbeat,
             max_frame_size: broker.max_frame_size,
+            subscriptions: HashMap::new(),
+            publishers: HashMap::new(),
+            close_reason: None,
         };
         let mut client = Client {
             dispatcher,
@@ -136,38 +238,152 @@ impl Client {
             state: Arc::new(RwLock::new(state)),
             tune_notifier: Arc::new(Notify::new()),
             publish_sequence: Arc::new(AtomicU64::new(1)),
+            metrics,
+            heartbeat_rtt,
         };
 
-        client.initialize(receiver).await?;
+        let handshake_timeout = client.opts.timeouts.handshake;
+        with_timeout("handshake", handshake_timeout, client.initialize(receiver)).await?;
+        if let Some(metrics) = &client.metrics {
+            let handshake = started.elapsed();
+            metrics.record(None, |collector, context| {
+                collector.open_connection(context);
+                collector.connection_handshake(context, handshake);
+            });
+        }
 
         Ok(client)
     }
 
+    /// Host and port of the node the client is connected to
+    pub(crate) fn node(&self) -> String {
+        format!("{}:{}", self.opts.host, self.opts.port)
+    }
+
     /// Get client's server properties.
-    pub async fn server_properties(&self) -> HashMap<String, String> {
+    pub async fn server_properties(&self) -> PeerProperties {
         self.state.read().await.server_properties.clone()
     }
 
     /// Get client's connection properties.
-    pub async fn connection_properties(&self) -> HashMap<String, String> {
+    pub async fn connection_properties(&self) -> PeerProperties {
         self.state.read().await.connection_properties.clone()
     }
 
+    pub(crate) fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    fn record(
+        &self,
+        stream: Option<&str>,
+        event: impl Fn(&dyn MetricsCollector, &MetricsContext<'_>),
+    ) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(stream, event);
+        }
+    }
+
+    /// Time between the last heartbeat sent to the broker and the next frame read from
+    /// it, `None` until a heartbeat was answered
+    pub fn last_rtt(&self) -> Option<Duration> {
+        self.heartbeat_rtt.last_rtt()
+    }
+
+    /// Frame max negotiated with the broker, 0 when frames are not limited
+    pub async fn max_frame_size(&self) -> u32 {
+        self.state.read().await.max_frame_size
+    }
+
+    /// Snapshot of the subscriptions, publishers and pending requests of the connection
+    pub async fn debug_state(&self) -> ClientDebugState {
+        let state = self.state.read().await;
+        let mut subscriptions: Vec<SubscriptionDebugState> =
+            state.subscriptions.values().cloned().collect();
+        subscriptions.sort_by_key(|subscription| subscription.subscription_id);
+        let mut publishers: Vec<PublisherDebugState> = state.publishers.values().cloned().collect();
+        publishers.sort_by_key(|publisher| publisher.publisher_id);
+        drop(state);
+
+        ClientDebugState {
+            host: self.opts.host.clone(),
+            port: self.opts.port,
+            subscriptions,
+            publishers,
+            pending_correlation_ids: self.dispatcher.pending_correlation_ids().await,
+        }
+    }
+
+    async fn update_subscription(
+        &self,
+        subscription_id: u8,
+        update: impl FnOnce(&mut SubscriptionDebugState),
+    ) {
+        if let Some(subscription) = self
+            .state
+            .write()
+            .await
+            .subscriptions
+            .get_mut(&subscription_id)
+        {
+            update(subscription);
+        }
+    }
+
+    /// Hand the messages to `handler` one at a time, in the order they were received
+    ///
+    /// The messages queued for a replaced handler still go to it.
     pub async fn set_handler<H: MessageHandler>(&self, handler: H) {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        tokio::task::spawn(async move {
+            while let Some(item) = receiver.recv().await {
+                let _ = handler.handle_message(item).await;
+            }
+        });
         let mut state = self.state.write().await;
 
-        state.handler = Some(Arc::new(handler));
+        state.handler = Some(sender);
+    }
+
+    /// Code and reason sent by the broker if it closed the connection
+    pub async fn close_reason(&self) -> Option<(ResponseCode, String)> {
+        self.state.read().await.close_reason.clone()
     }
 
     pub async fn close(&self) -> RabbitMQStreamResult<()> {
+        if let Some(error) = self.closed_by_broker().await {
+            return Err(error);
+        }
         if self.channel.is_closed() {
             return Err(ClientError::AlreadyClosed);
         }
-        let _: CloseResponse = self
+        let response: RabbitMQStreamResult<CloseResponse> = self
             .send_and_receive(|correlation_id| {
                 CloseRequest::new(correlation_id, ResponseCode::Ok, "Ok".to_owned())
             })
-            .await?;
+            .await;
+        if let Err(error) = response {
+            // the connection is shut even when the broker does not answer
+            let _ = self.channel.close().await;
+            return Err(error);
+        }
+        if let Some(metrics) = &self.metrics {
+            let mut state = self.state.write().await;
+            for (_, publisher) in state.publishers.drain() {
+                metrics.record(Some(&publisher.stream), |collector, context| {
+                    collector.close_producer(context)
+                });
+            }
+            for (_, subscription) in state.subscriptions.drain() {
+                metrics.record(Some(&subscription.stream), |collector, context| {
+                    collector.close_consumer(context)
+                });
+            }
+            drop(state);
+            metrics.record(None, |collector, context| {
+                collector.close_connection(context)
+            });
+        }
         self.channel.close().await
     }
     pub async fn subscribe(
@@ -178,24 +394,53 @@ impl Client {
         credit: u16,
         properties: HashMap<String, String>,
     ) -> RabbitMQStreamResult<GenericResponse> {
-        self.send_and_receive(|correlation_id| {
-            SubscribeCommand::new(
-                correlation_id,
+        validate_stream(stream)?;
+        let response: GenericResponse = self
+            .send_and_receive(|correlation_id| {
+                SubscribeCommand::new(
+                    correlation_id,
+                    subscription_id,
+                    stream.to_owned(),
+                    offset_specification,
+                    credit,
+                    properties,
+                )
+            })
+            .await?;
+        if response.is_ok() {
+            self.record(Some(stream), |collector, context| {
+                collector.open_consumer(context)
+            });
+            self.state.write().await.subscriptions.insert(
                 subscription_id,
-                stream.to_owned(),
-                offset_specification,
-                credit,
-                properties,
-            )
-        })
-        .await
+                SubscriptionDebugState {
+                    subscription_id,
+                    stream: stream.to_owned(),
+                    credits: credit as u32,
+                },
+            );
+        }
+        Ok(response)
     }
 
     pub async fn unsubscribe(&self, subscription_id: u8) -> RabbitMQStreamResult<GenericResponse> {
-        self.send_and_receive(|correlation_id| {
-            UnSubscribeCommand::new(correlation_id, subscription_id)
-        })
-        .await
+        let response: GenericResponse = self
+            .send_and_receive(|correlation_id| {
+                UnSubscribeCommand::new(correlation_id, subscription_id)
+            })
+            .await?;
+        let removed = self
+            .state
+            .write()
+            .await
+            .subscriptions
+            .remove(&subscription_id);
+        if let Some(subscription) = removed {
+            self.record(Some(&subscription.stream), |collector, context| {
+                collector.close_consumer(context)
+            });
+        }
+        Ok(response)
     }
 
     pub async fn create_stream(
@@ -203,6 +448,7 @@ impl Client {
         stream: &str,
         options: HashMap<String, String>,
     ) -> RabbitMQStreamResult<GenericResponse> {
+        validate_stream(stream)?;
         self.send_and_receive(|correlation_id| {
             CreateStreamCommand::new(correlation_id, stream.to_owned(), options)
         })
@@ -210,43 +456,134 @@ impl Client {
     }
 
     pub async fn delete_stream(&self, stream: &str) -> RabbitMQStreamResult<GenericResponse> {
+        validate_stream(stream)?;
         self.send_and_receive(|correlation_id| Delete::new(correlation_id, stream.to_owned()))
             .await
     }
 
+    pub async fn create_super_stream(
+        &self,
+        super_stream: &str,
+        partitions: Vec<String>,
+        binding_keys: Vec<String>,
+        options: HashMap<String, String>,
+    ) -> RabbitMQStreamResult<GenericResponse> {
+        validate_super_stream(super_stream)?;
+        for partition in &partitions {
+            validate_stream(partition)?;
+        }
+        self.send_and_receive(|correlation_id| {
+            CreateSuperStreamCommand::new(
+                correlation_id,
+                super_stream.to_owned(),
+                partitions,
+                binding_keys,
+                options,
+            )
+        })
+        .await
+    }
+
+    pub async fn delete_super_stream(
+        &self,
+        super_stream: &str,
+    ) -> RabbitMQStreamResult<GenericResponse> {
+        validate_super_stream(super_stream)?;
+        self.send_and_receive(|correlation_id| {
+            DeleteSuperStreamCommand::new(correlation_id, super_stream.to_owned())
+        })
+        .await
+    }
+
     pub async fn credit(&self, subscription_id: u8, credit: u16) -> RabbitMQStreamResult<()> {
-        self.send(CreditCommand::new(subscription_id, credit)).await
+        self.send(CreditCommand::new(subscription_id, credit))
+            .await?;
+        self.update_subscription(subscription_id, |subscription| {
+            subscription.credits += credit as u32
+        })
+        .await;
+        Ok(())
     }
 
     pub async fn metadata(
         &self,
         streams: Vec<String>,
     ) -> RabbitMQStreamResult<HashMap<String, StreamMetadata>> {
+        for stream in &streams {
+            validate_stream(stream)?;
+        }
         self.send_and_receive(|correlation_id| MetadataCommand::new(correlation_id, streams))
             .await
             .map(metadata::from_response)
     }
 
+    pub async fn stream_stats(&self, stream: &str) -> RabbitMQStreamResult<StreamStatsResponse> {
+        validate_stream(stream)?;
+        self.send_and_receive(|correlation_id| {
+            StreamStatsCommand::new(correlation_id, stream.to_owned())
+        })
+        .await
+    }
+
+    pub async fn partitions(&self, super_stream: &str) -> RabbitMQStreamResult<PartitionsResponse> {
+        validate_super_stream(super_stream)?;
+        self.send_and_receive(|correlation_id| {
+            PartitionsCommand::new(correlation_id, super_stream.to_owned())
+        })
+        .await
+    }
+
+    pub async fn route(
+        &self,
+        routing_key: &str,
+        super_stream: &str,
+    ) -> RabbitMQStreamResult<RouteResponse> {
+        validate_super_stream(super_stream)?;
+        self.send_and_receive(|correlation_id| {
+            RouteCommand::new(
+                correlation_id,
+                routing_key.to_owned(),
+                super_stream.to_owned(),
+            )
+        })
+        .await
+    }
+
     pub async fn store_offset(
         &self,
         reference: &str,
         stream: &str,
         offset: u64,
     ) -> RabbitMQStreamResult<()> {
-        self.send(StoreOffset::new(
-            reference.to_owned(),
-            stream.to_owned(),
-            offset,
+        validate_stream(stream)?;
+        self.send(StoreOffset::new(intern(reference), intern(stream), offset))
+            .await
+    }
+
+    /// Answer a ConsumerUpdate request of the server with the offset to resume from
+    pub async fn consumer_update(
+        &self,
+        correlation_id: u32,
+        offset_specification: OffsetSpecification,
+    ) -> RabbitMQStreamResult<()> {
+        self.send(ConsumerUpdateResponse::new(
+            correlation_id,
+            ResponseCode::Ok,
+            offset_specification,
         ))
         .await
     }
 
-    pub async fn query_offset(&self, reference: String, stream: &str) -> Result<u64, ClientError> {
+    pub async fn query_offset(
+        &self,
+        reference: String,
+        stream: &str,
+    ) -> RabbitMQStreamResult<QueryOffsetResponse> {
+        validate_stream(stream)?;
         self.send_and_receive::<QueryOffsetResponse, _, _>(|correlation_id| {
-            QueryOffsetRequest::new(correlation_id, reference, stream.to_owned())
+            QueryOffsetRequest::new(correlation_id, reference, intern(stream))
         })
         .await
-        .map(|query_offset| query_offset.from_response())
     }
 
     pub async fn declare_publisher(
@@ -255,25 +592,49 @@ impl Client {
         publisher_reference: Option<String>,
         stream: &str,
     ) -> RabbitMQStreamResult<GenericResponse> {
-        self.send_and_receive(|correlation_id| {
-            DeclarePublisherCommand::new(
-                correlation_id,
+        validate_stream(stream)?;
+        let response: GenericResponse = self
+            .send_and_receive(|correlation_id| {
+                DeclarePublisherCommand::new(
+                    correlation_id,
+                    publisher_id,
+                    publisher_reference.clone(),
+                    stream.to_owned(),
+                )
+            })
+            .await?;
+        if response.is_ok() {
+            self.record(Some(stream), |collector, context| {
+                collector.open_producer(context)
+            });
+            self.state.write().await.publishers.insert(
                 publisher_id,
-                publisher_reference,
-                stream.to_owned(),
-            )
-        })
-        .await
+                PublisherDebugState {
+                    publisher_id,
+                    stream: stream.to_owned(),
+                    reference: publisher_reference,
+                },
+            );
+        }
+        Ok(response)
     }
 
     pub async fn delete_publisher(
         &self,
         publisher_id: u8,
     ) -> RabbitMQStreamResult<GenericResponse> {
-        self.send_and_receive(|correlation_id| {
-            DeletePublisherCommand::new(correlation_id, publisher_id)
-        })
-        .await
+        let response: GenericResponse = self
+            .send_and_receive(|correlation_id| {
+                DeletePublisherCommand::new(correlation_id, publisher_id)
+            })
+            .await?;
+        let removed = self.state.write().await.publishers.remove(&publisher_id);
+        if let Some(publisher) = removed {
+            self.record(Some(&publisher.stream), |collector, context| {
+                collector.close_producer(context)
+            });
+        }
+        Ok(response)
     }
 
     pub async fn publish(
@@ -281,34 +642,175 @@ impl Client {
         publisher_id: u8,
         messages: impl Into<Vec<Message>>,
     ) -> RabbitMQStreamResult<Vec<u64>> {
-        let messages = messages.into();
-        let mut messages_to_publish = Vec::with_capacity(messages.len());
+        self.publish_filtered(
+            publisher_id,
+            messages.into(),
+            None::<fn(&Message) -> String>,
+        )
+        .await
+    }
+
+    /// Publish messages with the filter value `filter_value_extractor` returns for each of them
+    ///
+    /// The messages are sent with version 2 of the publish command, supported by RabbitMQ 3.13+.
+    pub async fn publish_with_filter_value(
+        &self,
+        publisher_id: u8,
+        messages: impl Into<Vec<Message>>,
+        filter_value_extractor: impl Fn(&Message) -> String,
+    ) -> RabbitMQStreamResult<Vec<u64>> {
+        self.publish_filtered(publisher_id, messages.into(), Some(filter_value_extractor))
+            .await
+    }
+
+    async fn publish_filtered(
+        &self,
+        publisher_id: u8,
+        messages: Vec<Message>,
+        filter_value_extractor: Option<impl Fn(&Message) -> String>,
+    ) -> RabbitMQStreamResult<Vec<u64>> {
         let mut sequences = Vec::with_capacity(messages.len());
+        let mut entries = Vec::with_capacity(messages.len());
 
-        // TODO batch publish with max frame size check
         for message in messages {
-            let publishing_id = match message.publishing_id() {
-                Some(publishing_id) => *publishing_id,
-                None => self
-                    .publish_sequence
-                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed),
-            };
+            let publishing_id = self.publishing_id(&message);
+            let filter_value = filter_value_extractor
+                .as_ref()
+                .map(|extractor| extractor(&message));
             sequences.push(publishing_id);
-            messages_to_publish.push(PublishedMessage::new(publishing_id, message));
+            entries.push(PublishedMessage::new(publishing_id, message).filter_value(filter_value));
         }
-        self.send(PublishCommand::new(publisher_id, messages_to_publish))
+        self.publish_entries(publisher_id, entries, filter_value_extractor.is_some())
             .await?;
 
         Ok(sequences)
     }
 
+    /// Publish messages packed in sub-entries of at most `sub_entry_size` messages
+    ///
+    /// Each sub-entry is compressed with `compression` and carries the publishing id
+    /// of its last message, which is the only one confirmed by the broker.
+    pub async fn publish_sub_entries(
+        &self,
+        publisher_id: u8,
+        messages: impl Into<Vec<Message>>,
+        sub_entry_size: usize,
+        compression: Compression,
+    ) -> RabbitMQStreamResult<Vec<u64>> {
+        self.publish_grouped(
+            publisher_id,
+            messages,
+            sub_entry_size,
+            |publishing_id, group| PublishedMessage::sub_entry(publishing_id, compression, group),
+        )
+        .await
+    }
+
+    /// Publish messages packed in sub-entries compressed by zstd with `dictionary`, see
+    /// [`Client::publish_sub_entries`]
+    pub async fn publish_zstd_sub_entries(
+        &self,
+        publisher_id: u8,
+        messages: impl Into<Vec<Message>>,
+        sub_entry_size: usize,
+        dictionary: &ZstdDictionary,
+    ) -> RabbitMQStreamResult<Vec<u64>> {
+        self.publish_grouped(
+            publisher_id,
+            messages,
+            sub_entry_size,
+            |publishing_id, group| {
+                PublishedMessage::zstd_sub_entry(publishing_id, dictionary, group)
+            },
+        )
+        .await
+    }
+
+    async fn publish_grouped(
+        &self,
+        publisher_id: u8,
+        messages: impl Into<Vec<Message>>,
+        sub_entry_size: usize,
+        sub_entry: impl Fn(u64, Vec<Message>) -> Result<PublishedMessage, EncodeError>,
+    ) -> RabbitMQStreamResult<Vec<u64>> {
+        let messages: Vec<Message> = messages.into();
+        let mut sequences = Vec::with_capacity(messages.len());
+        let mut entries = Vec::new();
+
+        // the groups own their messages, uncompressed sub-entries are encoded with the frame
+        let mut messages = messages.into_iter().peekable();
+        while messages.peek().is_some() {
+            let group: Vec<Message> = messages
+                .by_ref()
+                .take(sub_entry_size.clamp(1, u16::MAX as usize))
+                .collect();
+            for message in &group {
+                sequences.push(self.publishing_id(message));
+            }
+            entries.push(sub_entry(sequences[sequences.len() - 1], group)?);
+        }
+        self.publish_entries(publisher_id, entries, false).await?;
+
+        Ok(sequences)
+    }
+
+    fn publishing_id(&self, message: &Message) -> u64 {
+        match message.publishing_id() {
+            Some(publishing_id) => *publishing_id,
+            None => self
+                .publish_sequence
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+        }
+    }
+
+    /// Send the entries in as few publish frames as the negotiated frame max allows
+    async fn publish_entries(
+        &self,
+        publisher_id: u8,
+        entries: Vec<PublishedMessage>,
+        filtered: bool,
+    ) -> RabbitMQStreamResult<()> {
+        let max_frame_size = self.state.read().await.max_frame_size as usize;
+        let mut entries_to_publish = Vec::with_capacity(entries.len());
+        let mut frame_size = PUBLISH_FRAME_OVERHEAD;
+
+        for entry in entries {
+            let entry_size = match filtered {
+                true => entry.encoded_size_v2(),
+                false => entry.encoded_size(),
+            } as usize;
+
+            // an entry bigger than the frame max is still sent on its own
+            if !entries_to_publish.is_empty()
+                && max_frame_size > 0
+                && frame_size + entry_size > max_frame_size
+            {
+                self.send(PublishCommand::new(
+                    publisher_id,
+                    std::mem::take(&mut entries_to_publish),
+                ))
+                .await?;
+                frame_size = PUBLISH_FRAME_OVERHEAD;
+            }
+            frame_size += entry_size;
+            entries_to_publish.push(entry);
+        }
+        if !entries_to_publish.is_empty() {
+            self.send(PublishCommand::new(publisher_id, entries_to_publish))
+                .await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn query_publisher_sequence(
         &self,
         reference: &str,
         stream: &str,
     ) -> Result<u64, ClientError> {
+        validate_stream(stream)?;
         self.send_and_receive::<QueryPublisherResponse, _, _>(|correlation_id| {
-            QueryPublisherRequest::new(correlation_id, reference.to_owned(), stream.to_owned())
+            QueryPublisherRequest::new(correlation_id, intern(reference), intern(stream))
         })
         .await
         .map(|sequence| sequence.from_response())
@@ -316,6 +818,8 @@ impl Client {
 
     async fn create_connection(
         broker: &ClientOptions,
+        metrics: Option<Metrics>,
+        heartbeat_rtt: Arc<HeartbeatRtt>,
     ) -> Result<
         (
             ChannelSender<SinkConnection>,
@@ -323,14 +827,49 @@ impl Client {
         ),
         ClientError,
     > {
-        let stream = TcpStream::connect((broker.host.as_str(), broker.port)).await?;
-        let stream = Framed::new(stream, RabbitMqStreamCodec {});
+        let stream = match (
+            Client::connect_stream(broker).await?,
+            &broker.fault_injector,
+        ) {
+            (stream, Some(injector)) => GenericTcpStream::Faulty(Box::new(injector.wrap(stream))),
+            (stream, None) => stream,
+        };
+        let stream = Framed::new(
+            stream,
+            RabbitMqStreamCodec {
+                raw_chunks: broker.raw_chunks,
+                check_crc: broker.check_crc,
+                metrics,
+                heartbeat_rtt,
+                capture: broker
+                    .wire_capture
+                    .as_ref()
+                    .map(|capture| capture.connection()),
+            },
+        );
 
         let (sink, stream) = stream.split();
         let (tx, rx) = channel(sink, stream);
 
         Ok((tx, rx))
     }
+
+    async fn connect_stream(broker: &ClientOptions) -> Result<GenericTcpStream, ClientError> {
+        if let Some(replay) = &broker.wire_replay {
+            return Ok(GenericTcpStream::Replay(replay.connect()?));
+        }
+        #[cfg(feature = "test-broker")]
+        if let Some(test_broker) = &broker.test_broker {
+            return Ok(GenericTcpStream::Memory(test_broker.connect()?));
+        }
+        let stream = TcpStream::connect((broker.host.as_str(), broker.port)).await?;
+        if broker.tls.enabled() {
+            tls::connect(&broker.host, stream, &broker.tls).await
+        } else {
+            Ok(GenericTcpStream::Tcp(stream))
+        }
+    }
+
     async fn initialize<T>(&mut self, receiver: ChannelReceiver<T>) -> Result<(), ClientError>
     where
         T: Stream<Item = Result<Response, ClientError>> + Unpin + Send,
@@ -372,7 +911,7 @@ impl Client {
     fn max_value(&self, client: u32, server: u32) -> u32 {
         match (client, server) {
             (client, server) if client == 0 || server == 0 => client.max(server),
-            (client, server) => client.max(server),
+            (client, server) => client.min(server),
         }
     }
 
@@ -390,15 +929,16 @@ impl Client {
     async fn handle_authentication(&self, _mechanism: Vec<String>) -> Result<(), ClientError> {
         let auth_data = format!("\u{0000}{}\u{0000}{}", self.opts.user, self.opts.password);
 
-        self.send_and_receive::<GenericResponse, _, _>(|correlation_id| {
-            SaslAuthenticateCommand::new(
-                correlation_id,
-                "PLAIN".to_owned(),
-                auth_data.as_bytes().to_vec(),
-            )
-        })
-        .await
-        .map(|_| ())
+        let response = self
+            .send_and_receive::<GenericResponse, _, _>(|correlation_id| {
+                SaslAuthenticateCommand::new(
+                    correlation_id,
+                    "PLAIN".to_owned(),
+                    auth_data.as_bytes().to_vec(),
+                )
+            })
+            .await?;
+        handshake_step("authentication", response.code())
     }
 
     async fn sasl_mechanism(&self) -> Result<Vec<String>, ClientError> {
@@ -406,7 +946,10 @@ impl Client {
             SaslHandshakeCommand::new(correlation_id)
         })
         .await
-        .map(|handshake| handshake.mechanisms)
+        .and_then(|handshake| {
+            handshake_step("SASL handshake", handshake.code())?;
+            Ok(handshake.mechanisms)
+        })
     }
 
     async fn send_and_receive<T, R, M>(&self, msg_factory: M) -> Result<T, ClientError>
@@ -416,22 +959,66 @@ impl Client {
         M: FnOnce(u32) -> R,
     {
         let (correlation_id, mut receiver) = self.dispatcher.response_channel().await;
+        let request: Request = msg_factory(correlation_id).into();
+        let span = debug_span!(
+            "rabbitmq_stream.rpc",
+            correlation_id,
+            command_key = request.header().key(),
+            elapsed_us = field::Empty,
+        );
 
-        self.channel
-            .send(msg_factory(correlation_id).into())
-            .await?;
-
-        let response = receiver.recv().await.expect("It should contain a response");
+        let started = Instant::now();
+        let response = async {
+            if let Err(error) = self.channel.send(request).await {
+                return Err(self.closed_by_broker().await.unwrap_or(error));
+            }
+            match tokio::time::timeout(self.opts.timeouts.rpc, receiver.recv()).await {
+                Ok(Some(response)) => Ok(response),
+                // the connection closed before the response came
+                Ok(None) => Err(self
+                    .closed_by_broker()
+                    .await
+                    .unwrap_or(ClientError::AlreadyClosed)),
+                Err(_) => {
+                    self.dispatcher.forget(correlation_id).await;
+                    Err(ClientError::Timeout {
+                        operation: "request",
+                        timeout: self.opts.timeouts.rpc,
+                    })
+                }
+            }
+        }
+        .instrument(span.clone())
+        .await;
+        span.record("elapsed_us", started.elapsed().as_micros() as u64);
 
-        self.handle_response::<T>(response).await
+        match response {
+            Ok(response) => {
+                trace!(parent: &span, "Response received");
+                self.handle_response::<T>(response).await
+            }
+            Err(error) => {
+                trace!(parent: &span, ?error, "Request failed");
+                Err(error)
+            }
+        }
     }
 
     async fn send<R>(&self, msg: R) -> Result<(), ClientError>
     where
         R: Into<Request>,
     {
-        self.channel.send(msg.into()).await?;
-        Ok(())
+        match self.channel.send(msg.into()).await {
+            Ok(()) => Ok(()),
+            Err(error) => Err(self.closed_by_broker().await.unwrap_or(error)),
+        }
+    }
+
+    /// [`ClientError::ClosedByBroker`] once the broker closed the connection, its code and
+    /// reason explain why the frames cannot be sent anymore
+    async fn closed_by_broker(&self) -> Option<ClientError> {
+        let (code, reason) = self.close_reason().await?;
+        Some(ClientError::ClosedByBroker { code, reason })
     }
 
     async fn handle_response<T: FromResponse>(&self, response: Response) -> Result<T, ClientError> {
@@ -443,20 +1030,53 @@ impl Client {
         })
     }
 
-    async fn open(&self) -> Result<HashMap<String, String>, ClientError> {
+    async fn open(&self) -> Result<PeerProperties, ClientError> {
         self.send_and_receive::<OpenResponse, _, _>(|correlation_id| {
             OpenCommand::new(correlation_id, self.opts.v_host.clone())
         })
         .await
-        .map(|open| open.connection_properties)
+        .and_then(|open| {
+            handshake_step("open", open.code())?;
+            Ok(open.connection_properties)
+        })
     }
 
-    async fn peer_properties(&self) -> Result<HashMap<String, String>, ClientError> {
+    async fn peer_properties(&self) -> Result<PeerProperties, ClientError> {
         self.send_and_receive::<PeerPropertiesResponse, _, _>(|correlation_id| {
-            PeerPropertiesCommand::new(correlation_id, HashMap::new())
+            PeerPropertiesCommand::with_encoded(correlation_id, client_properties())
         })
         .await
-        .map(|peer_properties| peer_properties.server_properties)
+        .and_then(|peer_properties| {
+            handshake_step("peer properties", peer_properties.code())?;
+            Ok(peer_properties.server_properties)
+        })
+    }
+
+    async fn handle_close_request(&self, close: &CloseRequest) {
+        let code = close.closing_code().clone();
+        let reason = close.closing_reason().to_owned();
+        warn!(
+            host = %self.opts.host,
+            port = self.opts.port,
+            ?code,
+            reason = reason.as_str(),
+            "Connection closed by the broker"
+        );
+        self.state.write().await.close_reason = Some((code.clone(), reason.clone()));
+        if let Some(events) = &self.opts.events {
+            events::emit(
+                events,
+                StreamEvent::ConnectionClosed {
+                    node: format!("{}:{}", self.opts.host, self.opts.port),
+                    code,
+                    reason,
+                },
+            );
+        }
+        let _ = self
+            .send(CloseResponse::new(close.correlation_id(), ResponseCode::Ok))
+            .await;
+        let _ = self.channel.close().await;
     }
 
     async fn handle_tune_command(&self, tunes: &TunesCommand) {
@@ -472,7 +1092,30 @@ impl Client {
             .channel
             .send(TunesCommand::new(max_frame_size, heart_beat).into())
             .await;
+        if heart_beat > 0 {
+            tokio::task::spawn(send_heartbeats(
+                Arc::downgrade(&self.channel),
+                self.heartbeat_rtt.clone(),
+                Duration::from_secs(heart_beat as u64),
+            ));
+        }
 
         self.tune_notifier.notify_one();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::{codec::Decoder, types::PeerProperties};
+
+    use super::client_properties;
+
+    #[test]
+    fn client_properties_test() {
+        // encoded once and shared by the handshakes
+        assert_eq!(client_properties().as_ptr(), client_properties().as_ptr());
+        let (_, properties) = PeerProperties::decode(client_properties()).unwrap();
+        assert_eq!(Some("RabbitMQ Stream"), properties.get("product"));
+        assert_eq!(Some(env!("CARGO_PKG_VERSION")), properties.get("version"));
+    }
+}
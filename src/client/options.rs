@@ -1,4 +1,21 @@
-#[derive(Clone, Debug)]
+use std::{fmt, sync::Arc};
+
+use tokio_rustls::rustls::crypto::CryptoProvider;
+
+use super::tls::CertificateStore;
+#[cfg(feature = "test-broker")]
+use crate::test_broker::TestBroker;
+use crate::{
+    events::EventSender,
+    fault_injection::FaultInjector,
+    metrics::{Metrics, MetricsCollector, StreamCounters},
+    timeouts::Timeouts,
+    wire_capture::WireCapture,
+    wire_replay::WireReplay,
+};
+
+/// The `Debug` output masks the password
+#[derive(Clone)]
 pub struct ClientOptions {
     pub(crate) host: String,
     pub(crate) port: u16,
@@ -7,6 +24,64 @@ pub struct ClientOptions {
     pub(crate) v_host: String,
     pub(crate) heartbeat: u32,
     pub(crate) max_frame_size: u32,
+    pub(crate) tls: TlsConfiguration,
+    pub(crate) timeouts: Timeouts,
+    /// Keep the entries of delivered chunks undecoded
+    pub(crate) raw_chunks: bool,
+    /// Check the CRC of delivered chunks before decoding them
+    pub(crate) check_crc: bool,
+    pub(crate) metrics_collector: Option<Arc<dyn MetricsCollector>>,
+    pub(crate) stream_counters: Option<Arc<StreamCounters>>,
+    pub(crate) wire_capture: Option<WireCapture>,
+    /// Play the recorded connections instead of connecting to `host` and `port`
+    pub(crate) wire_replay: Option<WireReplay>,
+    pub(crate) fault_injector: Option<FaultInjector>,
+    /// Receives the connections closed by the broker
+    pub(crate) events: Option<EventSender>,
+    /// Connect to this broker instead of `host` and `port`
+    #[cfg(feature = "test-broker")]
+    pub(crate) test_broker: Option<TestBroker>,
+}
+
+impl ClientOptions {
+    pub(crate) fn metrics(&self) -> Option<Metrics> {
+        if self.metrics_collector.is_none() && self.stream_counters.is_none() {
+            return None;
+        }
+        Some(Metrics::new(
+            self.metrics_collector.clone(),
+            self.stream_counters.clone(),
+            &self.v_host,
+            &format!("{}:{}", self.host, self.port),
+        ))
+    }
+}
+
+impl fmt::Debug for ClientOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ClientOptions");
+        debug
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("user", &self.user)
+            .field("password", &"***")
+            .field("v_host", &self.v_host)
+            .field("heartbeat", &self.heartbeat)
+            .field("max_frame_size", &self.max_frame_size)
+            .field("tls", &self.tls)
+            .field("timeouts", &self.timeouts)
+            .field("raw_chunks", &self.raw_chunks)
+            .field("check_crc", &self.check_crc)
+            .field("metrics_collector", &self.metrics_collector)
+            .field("stream_counters", &self.stream_counters)
+            .field("wire_capture", &self.wire_capture)
+            .field("wire_replay", &self.wire_replay)
+            .field("fault_injector", &self.fault_injector)
+            .field("events", &self.events);
+        #[cfg(feature = "test-broker")]
+        debug.field("test_broker", &self.test_broker);
+        debug.finish()
+    }
 }
 
 impl Default for ClientOptions {
@@ -19,6 +94,111 @@ impl Default for ClientOptions {
             v_host: "/".to_owned(),
             heartbeat: 60,
             max_frame_size: 1048576,
+            tls: TlsConfiguration::default(),
+            timeouts: Timeouts::default(),
+            raw_chunks: false,
+            check_crc: false,
+            metrics_collector: None,
+            stream_counters: None,
+            wire_capture: None,
+            wire_replay: None,
+            fault_injector: None,
+            events: None,
+            #[cfg(feature = "test-broker")]
+            test_broker: None,
         }
     }
 }
+
+/// TLS settings used when connecting to the broker
+///
+/// TLS is disabled by default. When enabled without root certificates
+/// the Mozilla root store bundled with `webpki-roots` is used.
+///
+/// The cryptography comes from `ring`, or from the FIPS validated module of `aws-lc-rs`
+/// with the `fips` feature. Another provider, e.g. an OpenSSL FIPS one, is set with
+/// [`TlsConfiguration::crypto_provider`].
+#[derive(Clone, Debug, Default)]
+pub struct TlsConfiguration {
+    pub(crate) enabled: bool,
+    pub(crate) trust_certificates: bool,
+    pub(crate) root_certificates_path: Option<String>,
+    pub(crate) client_certificates_path: Option<String>,
+    pub(crate) client_keys_path: Option<String>,
+    pub(crate) crypto_provider: Option<Arc<CryptoProvider>>,
+    pub(crate) require_fips: bool,
+    pub(crate) certificate_store: Option<CertificateStore>,
+}
+
+impl TlsConfiguration {
+    /// Enable or disable TLS
+    pub fn enable(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Accept any server certificate without verification.
+    ///
+    /// Only meant for development setups with self signed certificates.
+    pub fn trust_certificates(mut self, trust_certificates: bool) -> Self {
+        self.trust_certificates = trust_certificates;
+        self
+    }
+
+    /// PEM file with the CA certificates used to verify the broker
+    pub fn add_root_certificates(mut self, path: &str) -> Self {
+        self.root_certificates_path = Some(path.to_owned());
+        self
+    }
+
+    /// PEM files with the client certificate chain and private key for mutual TLS
+    pub fn add_client_certificates_keys(mut self, certificate_path: &str, key_path: &str) -> Self {
+        self.client_certificates_path = Some(certificate_path.to_owned());
+        self.client_keys_path = Some(key_path.to_owned());
+        self
+    }
+
+    /// Certificates and key taking precedence over the files, rotated without rebuilding
+    /// the environment
+    pub fn certificate_store(mut self, store: CertificateStore) -> Self {
+        self.certificate_store = Some(store);
+        self
+    }
+
+    /// Cryptography used instead of the default one of the build
+    pub fn crypto_provider(mut self, provider: Arc<CryptoProvider>) -> Self {
+        self.crypto_provider = Some(provider);
+        self
+    }
+
+    /// Refuse to connect unless the cipher suites, key exchanges and TLS settings are all
+    /// FIPS approved
+    ///
+    /// The connection fails with [`ClientError::TlsNotFips`](crate::error::ClientError::TlsNotFips)
+    /// otherwise, e.g. when the default `ring` provider is used.
+    pub fn require_fips(mut self, require_fips: bool) -> Self {
+        self.require_fips = require_fips;
+        self
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ClientOptions;
+
+    #[test]
+    fn password_redacted_test() {
+        let options = ClientOptions {
+            password: "s3cr3t-password".to_owned(),
+            ..Default::default()
+        };
+        let debug = format!("{:?}", options);
+        assert!(!debug.contains("s3cr3t-password"));
+        assert!(debug.contains(r#"password: "***""#));
+        assert!(debug.contains(r#"user: "guest""#));
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+#[cfg(feature = "test-broker")]
+use tokio::io::DuplexStream;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::client::TlsStream;
+
+use crate::{fault_injection::FaultyStream, wire_replay::ReplayStream};
+
+/// Plain or TLS transport to the broker
+pub(crate) enum GenericTcpStream {
+    Tcp(TcpStream),
+    SecureTcp(Box<TlsStream<TcpStream>>),
+    /// Transport with the faults of a [`crate::types::FaultInjector`]
+    Faulty(Box<FaultyStream<GenericTcpStream>>),
+    /// Connection played by a [`crate::types::WireReplay`]
+    Replay(ReplayStream),
+    /// Connection to a [`crate::test_broker::TestBroker`]
+    #[cfg(feature = "test-broker")]
+    Memory(DuplexStream),
+}
+
+impl AsyncRead for GenericTcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            GenericTcpStream::SecureTcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            GenericTcpStream::Faulty(stream) => Pin::new(stream).poll_read(cx, buf),
+            GenericTcpStream::Replay(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "test-broker")]
+            GenericTcpStream::Memory(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for GenericTcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            GenericTcpStream::SecureTcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            GenericTcpStream::Faulty(stream) => Pin::new(stream).poll_write(cx, buf),
+            GenericTcpStream::Replay(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "test-broker")]
+            GenericTcpStream::Memory(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            GenericTcpStream::SecureTcp(stream) => Pin::new(stream).poll_flush(cx),
+            GenericTcpStream::Faulty(stream) => Pin::new(stream).poll_flush(cx),
+            GenericTcpStream::Replay(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "test-broker")]
+            GenericTcpStream::Memory(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            GenericTcpStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            GenericTcpStream::SecureTcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            GenericTcpStream::Faulty(stream) => Pin::new(stream).poll_shutdown(cx),
+            GenericTcpStream::Replay(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "test-broker")]
+            GenericTcpStream::Memory(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
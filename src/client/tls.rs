@@ -0,0 +1,318 @@
+use std::{
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{
+        self,
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{self, CryptoProvider},
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+        ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+
+use crate::error::ClientError;
+
+use super::{options::TlsConfiguration, stream::GenericTcpStream};
+
+pub(crate) async fn connect(
+    host: &str,
+    stream: TcpStream,
+    configuration: &TlsConfiguration,
+) -> Result<GenericTcpStream, ClientError> {
+    let config = client_config(configuration)?;
+    let domain = ServerName::try_from(host.to_owned())
+        .map_err(|err| ClientError::GenericError(Box::new(err)))?;
+
+    let stream = TlsConnector::from(Arc::new(config))
+        .connect(domain, stream)
+        .await?;
+
+    Ok(GenericTcpStream::SecureTcp(Box::new(stream)))
+}
+
+/// Certificates and key replacing the files of a [`TlsConfiguration`], rotated at runtime
+///
+/// The clones share the certificates: once replaced, the connections opened next, e.g. by
+/// the recovery of the producers and consumers, use the new ones. The connections open
+/// already keep the previous ones until they are closed, see
+/// [`Environment::cycle_connections`](crate::Environment::cycle_connections).
+///
+/// The files of the configuration are read again by each connection, rotating them on
+/// disk needs no store.
+#[derive(Clone, Default)]
+pub struct CertificateStore(Arc<RwLock<StoredCertificates>>);
+
+#[derive(Default)]
+struct StoredCertificates {
+    roots: Option<Vec<CertificateDer<'static>>>,
+    client: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+}
+
+impl CertificateStore {
+    /// Store without certificates, the files of the configuration are used until set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Verify the broker with the CA certificates of the PEM `certificates`
+    pub fn set_root_certificates(&self, certificates: &[u8]) -> Result<(), ClientError> {
+        let certificates = parse_certificates(certificates)?;
+        self.0.write().unwrap().roots = Some(certificates);
+        Ok(())
+    }
+
+    /// Authenticate with the PEM certificate chain and private key for mutual TLS
+    pub fn set_client_certificates(
+        &self,
+        certificates: &[u8],
+        key: &[u8],
+    ) -> Result<(), ClientError> {
+        let certificates = parse_certificates(certificates)?;
+        let key = PrivateKeyDer::from_pem_slice(key)
+            .map_err(|err| ClientError::GenericError(Box::new(err)))?;
+        self.0.write().unwrap().client = Some((certificates, key));
+        Ok(())
+    }
+
+    fn roots(&self) -> Option<Vec<CertificateDer<'static>>> {
+        self.0.read().unwrap().roots.clone()
+    }
+
+    fn client(&self) -> Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+        let stored = self.0.read().unwrap();
+        let (certificates, key) = stored.client.as_ref()?;
+        Some((certificates.clone(), key.clone_key()))
+    }
+}
+
+impl fmt::Debug for CertificateStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stored = self.0.read().unwrap();
+        f.debug_struct("CertificateStore")
+            .field("roots", &stored.roots.as_ref().map(Vec::len))
+            .field(
+                "client",
+                &stored
+                    .client
+                    .as_ref()
+                    .map(|(certificates, _)| certificates.len()),
+            )
+            .field("key", &stored.client.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+fn client_config(configuration: &TlsConfiguration) -> Result<ClientConfig, ClientError> {
+    let provider = configuration
+        .crypto_provider
+        .clone()
+        .unwrap_or_else(|| Arc::new(default_provider()));
+    let builder = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let builder = if configuration.trust_certificates {
+        builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(TrustAllCertificates(provider)))
+    } else {
+        builder.with_root_certificates(root_store(configuration)?)
+    };
+
+    let stored = configuration
+        .certificate_store
+        .as_ref()
+        .and_then(CertificateStore::client);
+    let config = match (
+        stored,
+        &configuration.client_certificates_path,
+        &configuration.client_keys_path,
+    ) {
+        (Some((certificates, key)), _, _) => builder.with_client_auth_cert(certificates, key)?,
+        (None, Some(certificates), Some(key)) => {
+            builder.with_client_auth_cert(load_certificates(certificates)?, load_key(key)?)?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    if configuration.require_fips && !config.fips() {
+        return Err(ClientError::TlsNotFips);
+    }
+    Ok(config)
+}
+
+#[cfg(feature = "fips")]
+fn default_provider() -> CryptoProvider {
+    // the cipher suites and key exchanges of the FIPS module only
+    crypto::aws_lc_rs::default_provider()
+}
+
+#[cfg(not(feature = "fips"))]
+fn default_provider() -> CryptoProvider {
+    crypto::ring::default_provider()
+}
+
+fn root_store(configuration: &TlsConfiguration) -> Result<RootCertStore, ClientError> {
+    let mut roots = RootCertStore::empty();
+    let stored = configuration
+        .certificate_store
+        .as_ref()
+        .and_then(CertificateStore::roots);
+    let certificates = match (stored, &configuration.root_certificates_path) {
+        (Some(certificates), _) => certificates,
+        (None, Some(path)) => load_certificates(path)?,
+        (None, None) => {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            return Ok(roots);
+        }
+    };
+    for certificate in certificates {
+        roots.add(certificate)?;
+    }
+    Ok(roots)
+}
+
+fn load_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, ClientError> {
+    CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect())
+        .map_err(|err| ClientError::GenericError(Box::new(err)))
+}
+
+fn parse_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, ClientError> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ClientError::GenericError(Box::new(err)))?;
+    if certificates.is_empty() {
+        return Err(ClientError::GenericError("no PEM certificate found".into()));
+    }
+    Ok(certificates)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, ClientError> {
+    PrivateKeyDer::from_pem_file(path).map_err(|err| ClientError::GenericError(Box::new(err)))
+}
+
+/// Verifier used when [`TlsConfiguration::trust_certificates`] is set
+#[derive(Debug)]
+struct TrustAllCertificates(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for TrustAllCertificates {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio_rustls::rustls::crypto;
+
+    use super::{client_config, CertificateStore};
+    use crate::{client::TlsConfiguration, error::ClientError};
+
+    const CERTIFICATE: &[u8] = include_bytes!("../../tests/certs/client.pem");
+    const KEY: &[u8] = include_bytes!("../../tests/certs/client.key");
+
+    #[test]
+    fn certificate_store_test() {
+        let store = CertificateStore::new();
+        let configuration = TlsConfiguration::default().certificate_store(store.clone());
+        assert!(!client_config(&configuration)
+            .unwrap()
+            .client_auth_cert_resolver
+            .has_certs());
+
+        // picked up by the next configuration, without rebuilding the environment
+        store.set_client_certificates(CERTIFICATE, KEY).unwrap();
+        store.set_root_certificates(CERTIFICATE).unwrap();
+        assert!(client_config(&configuration)
+            .unwrap()
+            .client_auth_cert_resolver
+            .has_certs());
+
+        let debug = format!("{:?}", store);
+        assert!(debug.contains(r#"key: Some("***")"#));
+        assert!(!debug.contains("PRIVATE KEY"));
+    }
+
+    #[test]
+    fn invalid_certificates_test() {
+        let store = CertificateStore::new();
+        assert!(store.set_root_certificates(b"not a certificate").is_err());
+        assert!(store
+            .set_client_certificates(CERTIFICATE, b"not a key")
+            .is_err());
+        // the previous certificates are kept
+        assert!(store.roots().is_none());
+        assert!(store.client().is_none());
+    }
+
+    #[test]
+    fn crypto_provider_test() {
+        let mut provider = crypto::ring::default_provider();
+        provider.cipher_suites.retain(|suite| {
+            suite.suite() == crypto::ring::cipher_suite::TLS13_AES_128_GCM_SHA256.suite()
+        });
+        let configuration = TlsConfiguration::default().crypto_provider(Arc::new(provider));
+
+        let config = client_config(&configuration).unwrap();
+        assert_eq!(1, config.crypto_provider().cipher_suites.len());
+    }
+
+    #[cfg(not(feature = "fips"))]
+    #[test]
+    fn require_fips_test() {
+        // ring is not a FIPS validated module
+        let configuration = TlsConfiguration::default().require_fips(true);
+        assert!(matches!(
+            client_config(&configuration),
+            Err(ClientError::TlsNotFips)
+        ));
+        assert!(client_config(&configuration.require_fips(false)).is_ok());
+    }
+}
@@ -0,0 +1,375 @@
+//! Connections shared by the producers, or by the consumers, of an environment
+//!
+//! A connection hosts up to the configured number of publishers, or subscriptions, each
+//! one leasing an id on it. The leases for a node only share the connections opened for
+//! it, once they are full another one is opened. The
+//! frames received on a connection are routed to the handler of the publisher or
+//! subscription they carry the id of, the others go to every handler.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use rabbitmq_stream_protocol::{
+    commands::{metadata_update::MetadataUpdateCommand, Command},
+    types::Header,
+    Response, ResponseKind,
+};
+
+use crate::{
+    client::{Client, MessageHandler, MessageResult},
+    error::ClientError,
+    RabbitMQStreamResult,
+};
+
+/// Publishers or subscriptions on a connection at most, the ids are bytes starting at 1
+pub(crate) const MAX_PER_CONNECTION: usize = u8::MAX as usize;
+
+pub(crate) struct ConnectionPool {
+    /// Publishers or subscriptions per connection
+    capacity: usize,
+    connections: Mutex<Vec<PooledConnection>>,
+    /// Held while opening a connection, the leases that do not fit wait for it instead of
+    /// opening their own, e.g. when recovering together
+    opening: tokio::sync::Mutex<()>,
+}
+
+struct PooledConnection {
+    client: Client,
+    /// Node the connection was opened for, `None` for the endpoints of the environment
+    node: Option<String>,
+    /// Connection delivering the chunks undecoded
+    raw_chunks: bool,
+    handlers: SharedHandlers,
+}
+
+impl ConnectionPool {
+    pub(crate) fn new(capacity: usize) -> Self {
+        ConnectionPool {
+            capacity: capacity.clamp(1, MAX_PER_CONNECTION),
+            connections: Mutex::new(Vec::new()),
+            opening: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Lease an id on a connection to `node` with room left, or on a new one opened with
+    /// `connect`
+    ///
+    /// With `id` the lease takes this id, a recovered publisher or subscription keeps its
+    /// own.
+    pub(crate) async fn acquire<F, Fut>(
+        self: &Arc<Self>,
+        node: Option<String>,
+        raw_chunks: bool,
+        id: Option<u8>,
+        connect: F,
+    ) -> RabbitMQStreamResult<ConnectionLease>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = RabbitMQStreamResult<Client>>,
+    {
+        if let Some(lease) = self.lease(&node, raw_chunks, id) {
+            return Ok(lease);
+        }
+        let _opening = self.opening.lock().await;
+        if let Some(lease) = self.lease(&node, raw_chunks, id) {
+            return Ok(lease);
+        }
+
+        let client = connect().await?;
+        let handlers = SharedHandlers::default();
+        client.set_handler(handlers.clone()).await;
+        let id = id.unwrap_or(1);
+        handlers.reserve(id);
+        self.connections.lock().unwrap().push(PooledConnection {
+            client: client.clone(),
+            node,
+            raw_chunks,
+            handlers: handlers.clone(),
+        });
+        Ok(ConnectionLease {
+            pool: self.clone(),
+            client,
+            handlers,
+            id,
+            released: AtomicBool::new(false),
+        })
+    }
+
+    fn lease(
+        self: &Arc<Self>,
+        node: &Option<String>,
+        raw_chunks: bool,
+        id: Option<u8>,
+    ) -> Option<ConnectionLease> {
+        let connections = self.connections.lock().unwrap();
+        connections
+            .iter()
+            .filter(|connection| connection.node == *node && connection.raw_chunks == raw_chunks)
+            .find_map(|connection| {
+                let id = connection.handlers.try_reserve(self.capacity, id)?;
+                Some(ConnectionLease {
+                    pool: self.clone(),
+                    client: connection.client.clone(),
+                    handlers: connection.handlers.clone(),
+                    id,
+                    released: AtomicBool::new(false),
+                })
+            })
+    }
+
+    /// Stop leasing ids on the connections open now and return them, the leases after the
+    /// call open new connections
+    pub(crate) fn retire(&self) -> Vec<Client> {
+        let connections = self.connections.lock().unwrap();
+        connections
+            .iter()
+            .filter(|connection| connection.handlers.retire())
+            .map(|connection| connection.client.clone())
+            .collect()
+    }
+
+    /// Free `id`, returns the connection to close when it was the last one
+    fn release(&self, handlers: &SharedHandlers, id: u8) -> Option<Client> {
+        let mut connections = self.connections.lock().unwrap();
+        if handlers.release(id) > 0 {
+            return None;
+        }
+        let position = connections
+            .iter()
+            .position(|connection| connection.handlers.same(handlers))?;
+        Some(connections.remove(position).client)
+    }
+}
+
+/// Id of a publisher or subscription on a pooled connection, freed on drop
+pub(crate) struct ConnectionLease {
+    pool: Arc<ConnectionPool>,
+    client: Client,
+    handlers: SharedHandlers,
+    id: u8,
+    released: AtomicBool,
+}
+
+impl ConnectionLease {
+    /// Publisher or subscription id to use on the connection
+    pub(crate) fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Hand the frames carrying the id of the lease, and the ones without id, to `handler`
+    pub(crate) fn set_handler(&self, handler: impl MessageHandler) {
+        self.handlers.set(self.id, Arc::new(handler));
+    }
+
+    /// Free the id and drop the handler, closing the connection if no other lease is left
+    pub(crate) async fn release(&self) {
+        if let Some(client) = self.detach() {
+            let _ = client.close().await;
+        }
+    }
+
+    /// Free the id and drop the handler, returns the connection to close if no other lease
+    /// is left
+    pub(crate) fn detach(&self) -> Option<Client> {
+        match self.released.swap(true, Ordering::SeqCst) {
+            true => None,
+            false => self.pool.release(&self.handlers, self.id),
+        }
+    }
+}
+
+impl Deref for ConnectionLease {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Drop for ConnectionLease {
+    fn drop(&mut self) {
+        if let Some(client) = self.detach() {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                runtime.spawn(async move {
+                    let _ = client.close().await;
+                });
+            }
+        }
+    }
+}
+
+/// Handlers of the publishers or subscriptions of a connection, by id
+#[derive(Clone, Default)]
+struct SharedHandlers(Arc<Mutex<HandlersState>>);
+
+#[derive(Default)]
+struct HandlersState {
+    /// `None` until the lease sets its handler
+    handlers: HashMap<u8, Option<Arc<dyn MessageHandler>>>,
+    /// The connection is closed, no new lease is taken on it
+    closed: bool,
+}
+
+impl SharedHandlers {
+    fn same(&self, other: &SharedHandlers) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Close the connection to new leases, false when it was already closed
+    fn retire(&self) -> bool {
+        !std::mem::replace(&mut self.0.lock().unwrap().closed, true)
+    }
+
+    fn reserve(&self, id: u8) {
+        self.0.lock().unwrap().handlers.insert(id, None);
+    }
+
+    /// Reserve `id`, or the lowest free one, unless the connection is full or closed
+    fn try_reserve(&self, capacity: usize, id: Option<u8>) -> Option<u8> {
+        let mut state = self.0.lock().unwrap();
+        if state.closed || state.handlers.len() >= capacity {
+            return None;
+        }
+        let id = match id {
+            Some(id) if state.handlers.contains_key(&id) => return None,
+            Some(id) => id,
+            None => (1..=u8::MAX).find(|id| !state.handlers.contains_key(id))?,
+        };
+        state.handlers.insert(id, None);
+        Some(id)
+    }
+
+    fn set(&self, id: u8, handler: Arc<dyn MessageHandler>) {
+        self.0.lock().unwrap().handlers.insert(id, Some(handler));
+    }
+
+    /// Free `id` and return the number of ids left
+    fn release(&self, id: u8) -> usize {
+        let mut state = self.0.lock().unwrap();
+        state.handlers.remove(&id);
+        state.handlers.len()
+    }
+
+    fn handler(&self, id: u8) -> Option<Arc<dyn MessageHandler>> {
+        self.0.lock().unwrap().handlers.get(&id).cloned().flatten()
+    }
+
+    fn all(&self) -> Vec<Arc<dyn MessageHandler>> {
+        let state = self.0.lock().unwrap();
+        state.handlers.values().flatten().cloned().collect()
+    }
+}
+
+/// Publisher or subscription a frame is for, `None` for the frames of the connection
+fn frame_id(response: &Response) -> Option<u8> {
+    match response.kind_ref() {
+        ResponseKind::PublishConfirm(confirm) => Some(confirm.publisher_id),
+        ResponseKind::PublishError(error) => Some(error.publisher_id),
+        ResponseKind::Deliver(deliver) => Some(deliver.subscription_id),
+        ResponseKind::RawDeliver(deliver) => Some(deliver.subscription_id),
+        ResponseKind::ConsumerUpdate(update) => Some(update.subscription_id()),
+        _ => None,
+    }
+}
+
+/// Copy of a frame of the connection for every handler, the ones they ignore are dropped
+fn copy_frame(item: &MessageResult) -> Option<MessageResult> {
+    match item {
+        Some(Ok(response)) => match response.kind_ref() {
+            ResponseKind::MetadataUpdate(update) => {
+                let update =
+                    MetadataUpdateCommand::new(update.code().clone(), update.stream().to_owned());
+                Some(Some(Ok(Response::new(
+                    Header::new(update.key(), 1),
+                    ResponseKind::MetadataUpdate(update),
+                ))))
+            }
+            _ => None,
+        },
+        Some(Err(error)) => Some(Some(Err(ClientError::GenericError(
+            error.to_string().into(),
+        )))),
+        None => Some(None),
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageHandler for SharedHandlers {
+    async fn handle_message(&self, item: MessageResult) -> RabbitMQStreamResult<()> {
+        if item.is_none() {
+            self.0.lock().unwrap().closed = true;
+        }
+        if let Some(Ok(response)) = &item {
+            if let Some(id) = frame_id(response) {
+                if let Some(handler) = self.handler(id) {
+                    handler.handle_message(item).await?;
+                }
+                return Ok(());
+            }
+        }
+
+        let mut handlers = self.all().into_iter();
+        let last = handlers.next_back();
+        for handler in handlers {
+            if let Some(copy) = copy_frame(&item) {
+                let _ = handler.handle_message(copy).await;
+            }
+        }
+        if let Some(handler) = last {
+            handler.handle_message(item).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SharedHandlers, MAX_PER_CONNECTION};
+
+    #[test]
+    fn reserve_ids_test() {
+        let handlers = SharedHandlers::default();
+        assert_eq!(Some(1), handlers.try_reserve(3, None));
+        assert_eq!(Some(5), handlers.try_reserve(3, Some(5)));
+        // an id taken already
+        assert_eq!(None, handlers.try_reserve(3, Some(5)));
+        assert_eq!(Some(2), handlers.try_reserve(3, None));
+        // full
+        assert_eq!(None, handlers.try_reserve(3, None));
+
+        assert_eq!(2, handlers.release(1));
+        assert_eq!(Some(1), handlers.try_reserve(3, None));
+    }
+
+    #[test]
+    fn all_ids_test() {
+        let handlers = SharedHandlers::default();
+        for id in 1..=u8::MAX {
+            assert_eq!(Some(id), handlers.try_reserve(MAX_PER_CONNECTION, None));
+        }
+        assert_eq!(None, handlers.try_reserve(MAX_PER_CONNECTION, None));
+    }
+
+    #[test]
+    fn closed_connection_test() {
+        let handlers = SharedHandlers::default();
+        handlers.0.lock().unwrap().closed = true;
+        assert_eq!(None, handlers.try_reserve(3, None));
+    }
+
+    #[test]
+    fn retired_connection_test() {
+        let handlers = SharedHandlers::default();
+        assert_eq!(Some(1), handlers.try_reserve(3, None));
+        assert!(handlers.retire());
+        assert!(!handlers.retire());
+        assert_eq!(None, handlers.try_reserve(3, None));
+    }
+}
@@ -1,88 +1,810 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     pin::Pin,
     sync::{
         atomic::{
-            AtomicBool,
+            AtomicBool, AtomicU32, AtomicU64, AtomicUsize,
             Ordering::{Relaxed, SeqCst},
         },
-        Arc,
+        Arc, Weak,
     },
     task::{Context, Poll},
+    time::Duration,
 };
 
 use rabbitmq_stream_protocol::{
-    commands::subscribe::OffsetSpecification, message::Message, ResponseKind,
+    codec::Encoder,
+    commands::{
+        consumer_update::ConsumerUpdateCommand,
+        deliver::{DeliverCommand, RawDeliverCommand},
+        subscribe::OffsetSpecification,
+    },
+    compression::ZstdDictionary,
+    message::Message,
+    ResponseCode, ResponseKind,
+};
+use tokio::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Notify, Semaphore,
 };
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-use tracing::trace;
+use tracing::{debug_span, trace, warn, Instrument};
 
+#[cfg(feature = "serde")]
+use crate::typed_consumer::{Json, PayloadFormat, TypedConsumer};
 use crate::{
-    client::{MessageHandler, MessageResult},
-    error::{ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError},
+    body_codec::{BodyCodecs, DecodedConsumer},
+    byte_capacity::ByteCapacity,
+    chunk_consumer::ChunkConsumerBuilder,
+    client::{MessageHandler, MessageResult, StreamMetadata},
+    connection_pool::ConnectionLease,
+    debug_state::ConsumerDebugState,
+    error::{
+        ClientError, ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError,
+        ConsumerQueryOffsetError, ConsumerStoreOffsetError, StreamCreateError, StreamStatsError,
+    },
+    events::StreamEvent,
+    interceptor::ConsumerInterceptor,
+    metrics::{Metrics, MetricsCollector, MetricsContext},
+    offset_tracking::{
+        AutoOffsetTracking, BrokerOffsetStore, OffsetStore, OffsetTracker, OrderedCompletions,
+    },
+    poison_message::{PoisonMessage, PoisonMessageHandling, PoisonMessageRouter, PoisonReason},
+    retry_policy::{next_delay, RetryPolicy},
+    slow_consumer::{SlowConsumerDetector, SlowConsumerReason, SlowConsumerThresholds},
+    stream_creator::StreamCreator,
+    stream_stats::StreamStats,
     Client, Environment,
 };
-use futures::{task::AtomicWaker, Stream};
+use futures::{future::BoxFuture, task::AtomicWaker, FutureExt, Stream, StreamExt};
+use std::future::Future;
 
 /// API for consuming RabbitMQ stream messages
 pub struct Consumer {
     receiver: Receiver<Result<Delivery, ConsumerDeliveryError>>,
     internal: Arc<ConsumerInternal>,
+    /// Offset of the last delivery returned, processed once the next one is requested
+    last_offset: Option<u64>,
 }
 
 struct ConsumerInternal {
-    client: Client,
-    stream: String,
+    environment: Environment,
+    /// Replaced when the subscription is recovered
+    client: std::sync::RwLock<ConnectionLease>,
+    /// Incremented with every recovery, events of the previous connections are ignored
+    generation: AtomicU64,
+    stream: Arc<str>,
+    name: Option<String>,
     subscription_id: u8,
     sender: Sender<Result<Delivery, ConsumerDeliveryError>>,
     closed: Arc<AtomicBool>,
     waker: AtomicWaker,
+    /// Messages before this offset are skipped, chunks are delivered from their start
+    start_offset: AtomicU64,
+    tracker: Option<OffsetTracker>,
+    /// `None` to store offsets in the broker with the current client
+    offset_store: Option<Arc<dyn OffsetStore>>,
+    initial_credits: u16,
+    properties: HashMap<String, String>,
+    credit_strategy: CreditStrategy,
+    /// Credits granted to the broker and not consumed by a chunk yet
+    outstanding_credits: AtomicU32,
+    offset_specification: OffsetSpecification,
+    consumer_update_listener: Option<ConsumerUpdateListener>,
+    filter: Option<ConsumerFilter>,
+    /// Always true unless single active consumer is enabled
+    active: AtomicBool,
+    /// Last offset of the chunks not processed yet, with [`CreditStrategy::OnProcessed`]
+    unprocessed_chunks: std::sync::Mutex<VecDeque<u64>>,
+    /// Offset following the last message returned to the application, 0 if none was
+    next_offset: AtomicU64,
+    /// Credits withheld from the broker while the consumer is paused, `None` when running
+    paused: std::sync::Mutex<Option<u32>>,
+    /// Set by [`ConsumerHandle::close_gracefully`] once the consumer unsubscribed
+    draining: AtomicBool,
+    /// The application processed the last message returned and waits for the next one
+    idle: AtomicBool,
+    /// Notified when the application polls or completes a message, or drops the consumer
+    progressed: Notify,
+    /// Offset following the last message sent to the application or filtered out, 0 if none
+    /// was, the subscription resumes from it after a recovery
+    dispatched_offset: AtomicU64,
+    recovering: AtomicBool,
+    recovery_listener: Option<RecoveryListener>,
+    stream_deleted: StreamDeletedPolicy,
+    /// Set once the deleted stream was created again, the subscription starts from its first offset
+    stream_recreated: AtomicBool,
+    /// Set to decode the messages one by one, on a raw chunk connection
+    poison: Option<Arc<PoisonMessageRouter>>,
+    /// Set when messages are processed concurrently, offsets are then tracked and chunks
+    /// processed as the messages complete in order instead of as they are returned
+    completions: Option<std::sync::Mutex<OrderedCompletions>>,
+    interceptors: Vec<Arc<dyn ConsumerInterceptor>>,
+    slow_consumer: Option<SlowConsumerDetector>,
+    buffered_bytes: Option<BufferedBytes>,
+    metrics: Option<Metrics>,
+}
+
+/// Encoded size of the deliveries waiting to be returned, bounded by `max`
+struct BufferedBytes {
+    max: usize,
+    used: std::sync::Mutex<usize>,
+    released: Notify,
+}
+
+impl BufferedBytes {
+    fn new(max: usize) -> Self {
+        BufferedBytes {
+            max,
+            used: std::sync::Mutex::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Wait for room for `bytes`, a message larger than the limit waits for an empty buffer
+    async fn reserve(&self, bytes: usize) {
+        loop {
+            let released = self.released.notified();
+            {
+                let mut used = self.used.lock().unwrap();
+                if *used == 0 || *used + bytes <= self.max {
+                    *used += bytes;
+                    return;
+                }
+            }
+            released.await;
+        }
+    }
+
+    fn release(&self, bytes: usize) {
+        let mut used = self.used.lock().unwrap();
+        *used = used.saturating_sub(bytes);
+        drop(used);
+        self.released.notify_waiters();
+    }
+
+    fn used(&self) -> usize {
+        *self.used.lock().unwrap()
+    }
 }
 
 impl ConsumerInternal {
     fn is_closed(&self) -> bool {
         self.closed.load(Relaxed)
     }
+
+    fn record(&self, event: impl Fn(&dyn MetricsCollector, &MetricsContext<'_>)) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(Some(&self.stream), event);
+        }
+    }
+
+    fn client(&self) -> Client {
+        self.client.read().unwrap().clone()
+    }
+
+    fn offset_store(&self) -> Arc<dyn OffsetStore> {
+        match &self.offset_store {
+            Some(offset_store) => offset_store.clone(),
+            None => Arc::new(BrokerOffsetStore::new(self.client())),
+        }
+    }
+
+    /// Release the subscription id, the handler holding the consumer is dropped with it
+    async fn release_connection(&self) {
+        let client = self.client.read().unwrap().detach();
+        if let Some(client) = client {
+            let _ = client.close().await;
+        }
+    }
+
+    /// Close the consumer once its recovery gave up, the deliveries end with `error`
+    async fn give_up(&self, error: ConsumerDeliveryError) {
+        let _ = self.sender.send(Err(error)).await;
+        self.closed.store(true, Relaxed);
+        self.release_connection().await;
+        self.waker.wake();
+    }
+
+    fn report_slow(&self, reason: SlowConsumerReason) {
+        warn!(stream = %self.stream, name = ?self.name, ?reason, "Slow consumer");
+        self.record(|collector, context| collector.slow_consumer(context));
+        self.environment.emit(StreamEvent::SlowConsumer {
+            stream: self.stream.to_string(),
+            name: self.name.clone(),
+            reason,
+        });
+    }
+
+    fn notify_recovery(&self, event: ConsumerRecoveryEvent) {
+        trace!(?event, "Consumer recovery");
+        self.environment
+            .emit(StreamEvent::ConsumerRecovery(event.clone()));
+        if let Some(listener) = &self.recovery_listener {
+            listener(event);
+        }
+    }
+
+    /// Subscribe again on a new connection, after the last message sent to the application
+    async fn resubscribe(self: &Arc<Self>) -> Result<OffsetSpecification, ConsumerCreateError> {
+        let connection = stream_connection(
+            &self.environment,
+            &self.stream,
+            None,
+            self.poison.is_some(),
+            Some(self.subscription_id),
+        )
+        .await?;
+        let generation = self.generation.fetch_add(1, SeqCst) + 1;
+        connection.set_handler(ConsumerMessageHandler(self.clone(), generation));
+
+        let offset_specification = match self.dispatched_offset.load(SeqCst) {
+            _ if self.stream_recreated.load(SeqCst) => OffsetSpecification::First,
+            0 => self.offset_specification.clone(),
+            offset => OffsetSpecification::Offset(offset),
+        };
+        if let OffsetSpecification::Offset(offset) = offset_specification {
+            self.start_offset.store(offset, SeqCst);
+        }
+        self.outstanding_credits
+            .store(self.initial_credits as u32, SeqCst);
+        self.unprocessed_chunks.lock().unwrap().clear();
+        self.active.store(
+            !self.properties.contains_key("single-active-consumer"),
+            SeqCst,
+        );
+
+        // the previous connection is closed once the other consumers left it
+        drop(std::mem::replace(
+            &mut *self.client.write().unwrap(),
+            connection,
+        ));
+        let client = self.client();
+
+        let response = client
+            .subscribe(
+                self.subscription_id,
+                &self.stream,
+                offset_specification.clone(),
+                self.initial_credits,
+                self.properties.clone(),
+            )
+            .await?;
+        if response.is_ok() {
+            self.stream_recreated.store(false, SeqCst);
+            let credits = self.initial_credits as u64;
+            self.record(|collector, context| collector.credit(context, credits));
+            Ok(offset_specification)
+        } else {
+            Err(ConsumerCreateError::Create {
+                stream: self.stream.to_string(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
+    /// Create the deleted stream again with the [`StreamDeletedPolicy`], `None` when the
+    /// consumer closes instead
+    async fn recreate_stream(&self) -> Option<Result<(), StreamCreateError>> {
+        let creator = match &self.stream_deleted {
+            StreamDeletedPolicy::Close => return None,
+            StreamDeletedPolicy::Recreate(creator) => StreamCreator::clone(creator),
+        };
+        match creator.create(&self.stream).await {
+            Ok(())
+            | Err(StreamCreateError::Create {
+                status: ResponseCode::StreamAlreadyExists,
+                ..
+            }) => {
+                // the offsets of the new stream start again at 0
+                self.dispatched_offset.store(0, SeqCst);
+                self.start_offset.store(0, SeqCst);
+                self.stream_recreated.store(true, SeqCst);
+                Some(Ok(()))
+            }
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// Take the `count` messages of a chunk for dispatch, returns the first offset not
+    /// dispatched already
+    ///
+    /// The handler of a lost connection finishes dispatching the chunk it received while the
+    /// recovered subscription starts after it, each message is dispatched by one of them.
+    fn claim(&self, first_offset: u64, count: u64) -> u64 {
+        if count == 0 {
+            return first_offset;
+        }
+        let dispatched = self
+            .dispatched_offset
+            .fetch_max(first_offset + count, SeqCst);
+        dispatched.max(first_offset)
+    }
+
+    /// Number of credits to grant after a chunk was received, `delivered` if it had messages
+    /// for the application
+    fn credits_after_chunk(&self, delivered: bool) -> u16 {
+        let outstanding = self
+            .outstanding_credits
+            .fetch_update(SeqCst, SeqCst, |outstanding| {
+                Some(outstanding.saturating_sub(1))
+            })
+            .unwrap_or_default()
+            .saturating_sub(1);
+
+        let credits = match self.credit_strategy {
+            CreditStrategy::PerChunk => 1,
+            CreditStrategy::Refill { threshold, credits } if outstanding < threshold as u32 => {
+                credits
+            }
+            CreditStrategy::Refill { .. } => 0,
+            // chunks with no message for the application are processed already
+            CreditStrategy::OnProcessed if !delivered => 1,
+            CreditStrategy::OnProcessed => 0,
+        };
+        self.outstanding_credits.fetch_add(credits as u32, SeqCst);
+        credits
+    }
+
+    /// Track the activation of a single active consumer and answer with the offset to resume from
+    async fn consumer_update(self: &Arc<Self>, update: ConsumerUpdateCommand) {
+        trace!(
+            stream = %self.stream,
+            active = update.is_active(),
+            "Consumer update"
+        );
+        self.active.store(update.is_active(), SeqCst);
+        self.environment.emit(StreamEvent::ConsumerUpdate {
+            stream: self.stream.to_string(),
+            name: self.name.clone(),
+            active: update.is_active(),
+        });
+
+        // the next active consumer resumes from the offset stored by this one
+        if !update.is_active() {
+            if let Err(error) = self.flush_offset().await {
+                trace!(?error, "Failed to store consumer offset on deactivation");
+            }
+        }
+
+        let offset_specification = match &self.consumer_update_listener {
+            Some(listener) => {
+                listener(ConsumerUpdateContext {
+                    consumer: self.clone(),
+                    active: update.is_active(),
+                })
+                .await
+            }
+            None => self.offset_specification.clone(),
+        };
+        if update.is_active() {
+            let start_offset = match offset_specification {
+                OffsetSpecification::Offset(offset) => offset,
+                _ => 0,
+            };
+            self.start_offset.store(start_offset, SeqCst);
+            // the activated consumer may go back, e.g. to the offset stored by the previous one
+            self.dispatched_offset.store(start_offset, SeqCst);
+        }
+
+        if let Err(error) = self
+            .client()
+            .consumer_update(update.correlation_id(), offset_specification)
+            .await
+        {
+            trace!(?error, "Failed to answer consumer update");
+        }
+    }
+
+    /// Grant a credit for every chunk fully processed once `offset` was processed
+    fn processed(self: &Arc<Self>, offset: u64) {
+        let mut unprocessed_chunks = self.unprocessed_chunks.lock().unwrap();
+        let mut credits = 0;
+        while unprocessed_chunks
+            .front()
+            .is_some_and(|last_offset| *last_offset <= offset)
+        {
+            unprocessed_chunks.pop_front();
+            credits += 1;
+        }
+        drop(unprocessed_chunks);
+
+        if credits > 0 {
+            self.outstanding_credits.fetch_add(credits as u32, SeqCst);
+            let consumer = self.clone();
+            tokio::task::spawn(async move { consumer.grant_credits(credits).await });
+        }
+    }
+
+    /// Send `credits` to the broker, or keep them until the consumer is resumed
+    async fn grant_credits(&self, credits: u16) {
+        if let Some(withheld) = self.paused.lock().unwrap().as_mut() {
+            *withheld += credits as u32;
+            return;
+        }
+        // TODO handle credit fail
+        if self
+            .client()
+            .credit(self.subscription_id, credits)
+            .await
+            .is_ok()
+        {
+            self.record(|collector, context| collector.credit(context, credits as u64));
+        }
+    }
+
+    fn pause(&self) {
+        self.paused.lock().unwrap().get_or_insert(0);
+    }
+
+    async fn resume(&self) -> Result<(), ConsumerDeliveryError> {
+        let withheld = self.paused.lock().unwrap().take().unwrap_or_default();
+        if withheld > 0 {
+            let credits = withheld.min(u16::MAX as u32) as u16;
+            self.client()
+                .credit(self.subscription_id, credits)
+                .await
+                .map_err(ConsumerDeliveryError::from)?;
+            self.record(|collector, context| collector.credit(context, credits as u64));
+        }
+        Ok(())
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused.lock().unwrap().is_some()
+    }
+
+    /// Wait until every message received was returned and processed, or the consumer dropped
+    async fn drained(&self) {
+        loop {
+            let progressed = self.progressed.notified();
+            if self.sender.is_closed()
+                || (self.sender.capacity() == self.sender.max_capacity()
+                    && self.idle.load(SeqCst)
+                    && self
+                        .completions
+                        .as_ref()
+                        .is_none_or(|completions| completions.lock().unwrap().is_empty()))
+            {
+                return;
+            }
+            progressed.await;
+        }
+    }
+
+    /// Run a delivered message through the interceptors
+    fn intercept(&self, offset: u64, message: Message) -> Result<Message, ConsumerDeliveryError> {
+        self.interceptors
+            .iter()
+            .try_fold(message, |message, interceptor| {
+                interceptor.on_delivery(&self.stream, offset, message)
+            })
+            .map_err(|error| ConsumerDeliveryError::Interceptor { offset, error })
+    }
+
+    /// Record a message completed by the concurrent handler, tracking the offsets in order
+    fn complete(self: &Arc<Self>, offset: u64) {
+        let mut completions = match &self.completions {
+            Some(completions) => completions.lock().unwrap(),
+            None => return,
+        };
+        // tracked under the lock, the consumer is not drained before its last offset is
+        if let Some(committable) = completions.complete(offset) {
+            self.track(committable);
+            if self.credit_strategy == CreditStrategy::OnProcessed {
+                self.processed(committable);
+            }
+        }
+        drop(completions);
+        self.progressed.notify_waiters();
+    }
+
+    /// Record a consumed offset and store it when the tracking strategy asks for it
+    fn track(self: &Arc<Self>, offset: u64) {
+        if self
+            .tracker
+            .as_ref()
+            .is_some_and(|tracker| tracker.track(offset))
+        {
+            let consumer = self.clone();
+            tokio::task::spawn(async move {
+                if let Err(error) = consumer.flush_offset().await {
+                    trace!(?error, "Failed to store consumer offset");
+                }
+            });
+        }
+    }
+
+    /// Store the last consumed offset if it was not stored yet
+    async fn flush_offset(&self) -> Result<(), ConsumerStoreOffsetError> {
+        match &self.tracker {
+            Some(tracker) => {
+                tracker
+                    .store_pending(|offset| self.store_offset(offset))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        let name = self
+            .name
+            .as_ref()
+            .ok_or(ConsumerStoreOffsetError::NameMissing)?;
+        self.offset_store()
+            .store(name, &self.stream, offset)
+            .await
+            .map_err(ConsumerStoreOffsetError::from)
+    }
+
+    async fn debug_state(&self) -> ConsumerDebugState {
+        ConsumerDebugState {
+            stream: self.stream.to_string(),
+            subscription_id: self.subscription_id,
+            outstanding_credits: self.outstanding_credits.load(SeqCst),
+            pending_deliveries: self.sender.max_capacity() - self.sender.capacity(),
+            pending_bytes: self.buffered_bytes.as_ref().map(BufferedBytes::used),
+            active: self.active.load(SeqCst),
+            paused: self.is_paused(),
+            recovering: self.recovering.load(SeqCst),
+            closed: self.is_closed(),
+            connection: self.client().debug_state().await,
+        }
+    }
+
+    /// Number of committed messages after the last one returned to the application
+    async fn lag(&self) -> Result<u64, StreamStatsError> {
+        let response = self.client().stream_stats(&self.stream).await?;
+        if !response.is_ok() {
+            return Err(StreamStatsError::Stats {
+                stream: self.stream.to_string(),
+                status: response.code().clone(),
+            });
+        }
+        let stats = StreamStats::new(response.stats);
+
+        let committed = match stats.committed_offset().or(stats.committed_chunk_id()) {
+            Some(committed) => committed + 1,
+            None => return Ok(0),
+        };
+        let next_offset = match self.next_offset.load(SeqCst) {
+            0 => match self.offset_specification {
+                OffsetSpecification::Offset(offset) => offset,
+                OffsetSpecification::First | OffsetSpecification::Timestamp(_) => {
+                    stats.first_offset().unwrap_or(committed)
+                }
+                OffsetSpecification::Next | OffsetSpecification::Last => committed,
+            },
+            next_offset => next_offset,
+        };
+        let lag = committed.saturating_sub(next_offset);
+        self.record(|collector, context| collector.lag(context, lag));
+        Ok(lag)
+    }
+
+    async fn query_offset(&self) -> Result<u64, ConsumerQueryOffsetError> {
+        let name = self
+            .name
+            .as_ref()
+            .ok_or(ConsumerQueryOffsetError::NameMissing)?;
+        self.offset_store()
+            .load(name, &self.stream)
+            .await?
+            .ok_or_else(|| ConsumerQueryOffsetError::OffsetNotFound {
+                stream: self.stream.to_string(),
+                name: name.clone(),
+            })
+    }
+}
+
+/// How a [`Consumer`] grants credits, the number of chunks the broker may send, after the initial ones
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum CreditStrategy {
+    /// Grant one credit after every chunk, keeping the initial credits outstanding
+    #[default]
+    PerChunk,
+    /// Grant `credits` credits once fewer than `threshold` credits are outstanding
+    ///
+    /// A low threshold bounds the memory used by chunks buffered in the consumer,
+    /// a high one keeps the broker busy and lowers the latency.
+    Refill { threshold: u16, credits: u16 },
+    /// Grant one credit once every message of a chunk was processed
+    ///
+    /// A message counts as processed when the next one is requested from the [`Consumer`],
+    /// or when the handler returned for consumers started with a message handler.
+    /// At most the initial credits worth of chunks are buffered, at the cost of throughput.
+    OnProcessed,
+}
+
+/// Consumer defaults carried by the [`Environment`]
+#[derive(Clone, Debug)]
+pub(crate) struct ConsumerOptions {
+    pub(crate) initial_credits: u16,
+    pub(crate) offset_specification: OffsetSpecification,
+    pub(crate) buffer_size: usize,
+    pub(crate) max_buffered_bytes: Option<usize>,
+    pub(crate) consumers_per_connection: usize,
+}
+
+impl Default for ConsumerOptions {
+    fn default() -> Self {
+        ConsumerOptions {
+            initial_credits: 10,
+            offset_specification: OffsetSpecification::default(),
+            buffer_size: 10000,
+            max_buffered_bytes: None,
+            consumers_per_connection: 1,
+        }
+    }
 }
 
 /// Builder for [`Consumer`]
+#[derive(Clone)]
 pub struct ConsumerBuilder {
     pub(crate) environment: Environment,
+    pub(crate) name: Option<String>,
     pub(crate) offset_specification: OffsetSpecification,
+    pub(crate) initial_credits: u16,
+    /// Capacity of the channel between the connection and the consumer, in messages
+    pub(crate) buffer_size: usize,
+    /// Capacity of the channel in bytes, unbounded when `None`
+    pub(crate) max_buffered_bytes: Option<usize>,
+    pub(crate) credit_strategy: CreditStrategy,
+    pub(crate) auto_offset_tracking: Option<AutoOffsetTracking>,
+    pub(crate) single_active_consumer: bool,
+    /// Super stream of the partition consumed, for single active consumer groups spanning partitions
+    pub(crate) super_stream: Option<String>,
+    pub(crate) consumer_update_listener: Option<ConsumerUpdateListener>,
+    pub(crate) filter: Option<ConsumerFilter>,
+    pub(crate) match_unfiltered: bool,
+    pub(crate) offset_store: Option<Arc<dyn OffsetStore>>,
+    pub(crate) recovery_listener: Option<RecoveryListener>,
+    pub(crate) stream_deleted: StreamDeletedPolicy,
+    pub(crate) poison_messages: Option<PoisonMessageHandling>,
+    /// Set by [`HandlerConsumerBuilder::concurrency`], offsets are tracked as messages complete
+    pub(crate) ordered_completions: bool,
+    pub(crate) interceptors: Vec<Arc<dyn ConsumerInterceptor>>,
+    /// Start after the stored offset, from this specification when none is stored
+    pub(crate) stored_offset_fallback: Option<OffsetSpecification>,
+    pub(crate) slow_consumer: SlowConsumerThresholds,
+    /// Metadata of the stream queried beforehand, it is located when building otherwise
+    pub(crate) located: Option<StreamMetadata>,
 }
 
 impl ConsumerBuilder {
-    pub async fn build(self, stream: &str) -> Result<Consumer, ConsumerCreateError> {
-        let client = self.environment.create_client().await?;
+    pub async fn build(mut self, stream: &str) -> Result<Consumer, ConsumerCreateError> {
+        if (self.auto_offset_tracking.is_some()
+            || self.single_active_consumer
+            || self.stored_offset_fallback.is_some())
+            && self.name.is_none()
+        {
+            return Err(ConsumerCreateError::NameMissing);
+        }
+
+        let connection = stream_connection(
+            &self.environment,
+            stream,
+            self.located.take(),
+            self.poison_messages.is_some(),
+            None,
+        )
+        .await?;
+        let client = Client::clone(&connection);
+
+        if let (Some(fallback), Some(name)) = (self.stored_offset_fallback.take(), &self.name) {
+            let offset_store = match &self.offset_store {
+                Some(offset_store) => offset_store.clone(),
+                None => Arc::new(BrokerOffsetStore::new(client.clone())),
+            };
+            // the stored offset is the last one processed
+            self.offset_specification = match offset_store.load(name, stream).await? {
+                Some(stored) => OffsetSpecification::Offset(stored + 1),
+                None => fallback,
+            };
+        }
+
+        let subscription_id = connection.id();
+        let start_offset = match self.offset_specification {
+            OffsetSpecification::Offset(offset) => offset,
+            _ => 0,
+        };
+        let mut properties = HashMap::new();
+        if let Some(name) = &self.name {
+            properties.insert("name".to_owned(), name.clone());
+        }
+        if let Some(filter) = &self.filter {
+            for (index, value) in filter.values.iter().enumerate() {
+                properties.insert(format!("filter.{}", index), value.clone());
+            }
+            properties.insert(
+                "match-unfiltered".to_owned(),
+                self.match_unfiltered.to_string(),
+            );
+        }
+        if self.single_active_consumer {
+            properties.insert("single-active-consumer".to_owned(), "true".to_owned());
+            if let Some(super_stream) = &self.super_stream {
+                properties.insert("super-stream".to_owned(), super_stream.clone());
+            }
+        }
+
+        // the handler must be set before subscribing, a ConsumerUpdate can follow the subscription
+        let (tx, rx) = channel(self.buffer_size.max(1));
+        let environment = self.environment.clone();
+        let poison = self
+            .poison_messages
+            .map(|handling| Arc::new(PoisonMessageRouter::new(handling, environment)));
+        let consumer = Arc::new(ConsumerInternal {
+            environment: self.environment,
+            subscription_id,
+            stream: stream.into(),
+            name: self.name,
+            client: std::sync::RwLock::new(connection),
+            generation: AtomicU64::new(0),
+            sender: tx,
+            closed: Arc::new(AtomicBool::new(false)),
+            waker: AtomicWaker::new(),
+            start_offset: AtomicU64::new(start_offset),
+            tracker: self.auto_offset_tracking.map(OffsetTracker::new),
+            offset_store: self.offset_store,
+            initial_credits: self.initial_credits,
+            properties: properties.clone(),
+            credit_strategy: self.credit_strategy,
+            outstanding_credits: AtomicU32::new(self.initial_credits as u32),
+            offset_specification: self.offset_specification.clone(),
+            consumer_update_listener: self.consumer_update_listener,
+            filter: self.filter.clone(),
+            active: AtomicBool::new(!self.single_active_consumer),
+            unprocessed_chunks: std::sync::Mutex::new(VecDeque::new()),
+            next_offset: AtomicU64::new(0),
+            paused: std::sync::Mutex::new(None),
+            draining: AtomicBool::new(false),
+            idle: AtomicBool::new(true),
+            progressed: Notify::new(),
+            dispatched_offset: AtomicU64::new(0),
+            recovering: AtomicBool::new(false),
+            recovery_listener: self.recovery_listener,
+            stream_deleted: self.stream_deleted,
+            stream_recreated: AtomicBool::new(false),
+            poison,
+            completions: self
+                .ordered_completions
+                .then(|| std::sync::Mutex::new(OrderedCompletions::default())),
+            interceptors: self.interceptors,
+            slow_consumer: SlowConsumerDetector::new(self.slow_consumer),
+            buffered_bytes: self.max_buffered_bytes.map(BufferedBytes::new),
+            metrics: client.metrics().cloned(),
+        });
+        consumer
+            .client
+            .read()
+            .unwrap()
+            .set_handler(ConsumerMessageHandler(consumer.clone(), 0));
 
-        let subscription_id = 1;
         let response = client
             .subscribe(
                 subscription_id,
                 stream,
                 self.offset_specification,
-                1,
-                HashMap::new(),
+                self.initial_credits,
+                properties,
             )
             .await?;
 
         if response.is_ok() {
-            let (tx, rx) = channel(10000);
-            let consumer = Arc::new(ConsumerInternal {
-                subscription_id,
-                stream: stream.to_string(),
-                client: client.clone(),
-                sender: tx,
-                closed: Arc::new(AtomicBool::new(false)),
-                waker: AtomicWaker::new(),
-            });
-
-            let msg_handler = ConsumerMessageHandler(consumer.clone());
-            client.set_handler(msg_handler).await;
+            let credits = self.initial_credits as u64;
+            consumer.record(|collector, context| collector.credit(context, credits));
+            if let Some(tracker) = &consumer.tracker {
+                tokio::task::spawn(flush_offset_periodically(
+                    Arc::downgrade(&consumer),
+                    tracker.strategy.flush_interval,
+                ));
+            }
 
             Ok(Consumer {
                 receiver: rx,
                 internal: consumer,
+                last_offset: None,
             })
         } else {
             Err(ConsumerCreateError::Create {
@@ -92,13 +814,551 @@ impl ConsumerBuilder {
         }
     }
 
+    /// Name of the consumer, the reference its offsets are stored under in the broker
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Store the offset in the broker automatically, requires a [`ConsumerBuilder::name`]
+    pub fn auto_offset_tracking(mut self, auto_offset_tracking: AutoOffsetTracking) -> Self {
+        self.auto_offset_tracking = Some(auto_offset_tracking);
+        self
+    }
+
+    /// Share the stream with the other consumers of the same name, only one of them receives
+    /// messages at a time, requires a [`ConsumerBuilder::name`]
+    pub fn enable_single_active_consumer(mut self, single_active_consumer: bool) -> Self {
+        self.single_active_consumer = single_active_consumer;
+        self
+    }
+
+    /// Choose the offset to resume from when the single active consumer is activated
+    ///
+    /// The listener is called on every activation change, its result is ignored on
+    /// deactivation. Without a listener the consumer resumes from [`ConsumerBuilder::offset`].
+    pub fn consumer_update_listener<F, Fut>(mut self, listener: F) -> Self
+    where
+        F: Fn(ConsumerUpdateContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OffsetSpecification> + Send + 'static,
+    {
+        self.consumer_update_listener = Some(Arc::new(move |context| listener(context).boxed()));
+        self
+    }
+
+    /// Only receive the messages published with one of the filter `values`
+    ///
+    /// The broker filters whole chunks with a bloom filter, so chunks can still contain
+    /// other messages: `post_filter` is called on every message and only the ones it accepts
+    /// are delivered. Requires RabbitMQ 3.13 or later.
+    pub fn filter_values(
+        mut self,
+        values: Vec<String>,
+        post_filter: impl Fn(&Message) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(ConsumerFilter {
+            values,
+            post_filter: Arc::new(post_filter),
+        });
+        self
+    }
+
+    /// Also receive the messages published without filter value, false by default
+    ///
+    /// Only used along with [`ConsumerBuilder::filter_values`].
+    pub fn match_unfiltered(mut self, match_unfiltered: bool) -> Self {
+        self.match_unfiltered = match_unfiltered;
+        self
+    }
+
+    /// Listener notified when the consumer recovers its subscription
+    ///
+    /// The consumer subscribes again on a new connection when the broker reports a change of
+    /// the stream topology or the connection closes, resuming after the last message it
+    /// received. The listener runs on the connection tasks and should not block.
+    pub fn on_recovery(
+        mut self,
+        listener: impl Fn(ConsumerRecoveryEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.recovery_listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// What the consumer does once the stream it consumes is deleted,
+    /// [`StreamDeletedPolicy::Close`] by default
+    pub fn on_stream_deleted(mut self, policy: StreamDeletedPolicy) -> Self {
+        self.stream_deleted = policy;
+        self
+    }
+
+    /// Persist offsets with `offset_store` instead of the broker
+    pub fn offset_store(mut self, offset_store: impl OffsetStore + 'static) -> Self {
+        self.offset_store = Some(Arc::new(offset_store));
+        self
+    }
+
+    /// Where to start consuming the stream, [`OffsetSpecification::Next`] by default
     pub fn offset(mut self, offset_specification: OffsetSpecification) -> Self {
         self.offset_specification = offset_specification;
+        self.stored_offset_fallback = None;
+        self
+    }
+
+    /// Start after the offset stored under the name of the consumer, from `fallback` when
+    /// none is stored yet, requires a name
+    pub fn start_from_stored_offset_or(mut self, fallback: OffsetSpecification) -> Self {
+        self.stored_offset_fallback = Some(fallback);
+        self
+    }
+
+    /// Number of chunks the broker may deliver before waiting for more credit
+    pub fn initial_credits(mut self, initial_credits: u16) -> Self {
+        self.initial_credits = initial_credits;
+        self
+    }
+
+    /// Maximum number of delivered messages waiting to be returned by the consumer, 10000 by
+    /// default
+    ///
+    /// Once the buffer is full the messages of the next chunks wait in the connection. A small
+    /// buffer bounds the memory held for large messages, a large one absorbs the bursts of
+    /// small messages.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Maximum encoded size of the delivered messages waiting to be returned by the
+    /// consumer, unbounded by default
+    ///
+    /// Applies with [`ConsumerBuilder::buffer_size`], the next chunks wait in the connection
+    /// until the messages returned make room. A message larger than the limit waits for
+    /// the buffer to be empty.
+    pub fn max_buffered_bytes(mut self, max_buffered_bytes: ByteCapacity) -> Self {
+        self.max_buffered_bytes = Some(max_buffered_bytes.bytes() as usize);
+        self
+    }
+
+    /// Decompress the sub-entries compressed by zstd with `dictionary`
+    ///
+    /// A sub-entry names the dictionary it was compressed with, the dictionary is registered
+    /// for every consumer of the process. Requires the `zstd` feature.
+    pub fn zstd_dictionary(self, dictionary: ZstdDictionary) -> Self {
+        dictionary.register();
+        self
+    }
+
+    /// How credits are granted after the initial ones, [`CreditStrategy::PerChunk`] by default
+    pub fn credit_strategy(mut self, credit_strategy: CreditStrategy) -> Self {
+        self.credit_strategy = credit_strategy;
+        self
+    }
+
+    /// Give up on the messages failing to decode or to be handled instead of failing again
+    ///
+    /// Messages are then decoded one by one, a message failing to decode is routed as set by
+    /// `poison_messages` and the consumer moves on to the next one.
+    pub fn poison_messages(mut self, poison_messages: PoisonMessageHandling) -> Self {
+        self.poison_messages = Some(poison_messages);
+        self
+    }
+
+    /// Append `interceptor` to the ones the messages go through before the application
+    pub fn interceptor(mut self, interceptor: impl ConsumerInterceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Report the consumer slow after `chunks` chunks in a row left it without credits
+    /// while the application was still processing a message
+    ///
+    /// The consumer is reported with a warning, a [`crate::types::StreamEvent::SlowConsumer`]
+    /// event and the metrics collector, once per streak.
+    pub fn slow_consumer_credit_exhaustions(mut self, chunks: u32) -> Self {
+        self.slow_consumer.credit_exhaustions = Some(chunks.max(1));
+        self
+    }
+
+    /// Report the consumer slow when the messages received and not returned to the
+    /// application yet reach `bytes`, each time they go over it
+    pub fn slow_consumer_pending_bytes(mut self, bytes: ByteCapacity) -> Self {
+        self.slow_consumer.pending_bytes = Some(bytes.bytes());
+        self
+    }
+
+    /// Process the messages with `handler` on a task spawned by the consumer
+    ///
+    /// Messages are handled one at a time, in order, credits are granted to the broker as
+    /// chunks are delivered. Use [`HandlerConsumerBuilder::start`] to subscribe.
+    pub fn message_handler<F, Fut>(self, handler: F) -> HandlerConsumerBuilder
+    where
+        F: Fn(MessageContext, Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        HandlerConsumerBuilder {
+            builder: self,
+            handler: Arc::new(move |context, message| handler(context, message).map(Ok).boxed()),
+            concurrency: 1,
+            retry_policy: None,
+        }
+    }
+
+    /// Process the messages with a fallible `handler` on a task spawned by the consumer
+    ///
+    /// A failed message is handled again up to [`PoisonMessageHandling::max_attempts`] times
+    /// before it is routed as a poison message, once without [`ConsumerBuilder::poison_messages`].
+    pub fn try_message_handler<F, Fut, E>(self, handler: F) -> HandlerConsumerBuilder
+    where
+        F: Fn(MessageContext, Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        HandlerConsumerBuilder {
+            builder: self,
+            handler: Arc::new(move |context, message| {
+                handler(context, message)
+                    .map(|result| result.map_err(|error| error.to_string()))
+                    .boxed()
+            }),
+            concurrency: 1,
+            retry_policy: None,
+        }
+    }
+
+    /// Consume whole chunks with their entries left undecoded instead of messages
+    ///
+    /// Only the name, offset, initial credits and offset store of the builder apply.
+    /// Use [`ChunkConsumerBuilder::build`] to subscribe.
+    pub fn raw_chunks(self) -> ChunkConsumerBuilder {
+        ChunkConsumerBuilder { builder: self }
+    }
+}
+
+type RecoveryListener = Arc<dyn Fn(ConsumerRecoveryEvent) + Send + Sync>;
+
+/// Progress of the recovery of a consumer subscription, see [`ConsumerBuilder::on_recovery`]
+#[derive(Clone, Debug)]
+pub enum ConsumerRecoveryEvent {
+    /// The subscription was lost, the consumer subscribes again
+    Started { stream: String },
+    /// The consumer subscribed again and resumes from `offset`
+    Recovered {
+        stream: String,
+        offset: OffsetSpecification,
+    },
+    /// An attempt failed, the next one starts after a delay
+    Failed { stream: String, error: String },
+    /// The [recovery policy](crate::EnvironmentBuilder::recovery_policy) gave up after
+    /// `error`, the consumer is closed
+    GaveUp { stream: String, error: String },
+    /// The stream does not exist anymore, the consumer closes or creates it again depending
+    /// on its [`StreamDeletedPolicy`]
+    StreamDeleted { stream: String },
+}
+
+/// What a consumer does once its stream is deleted, see [`ConsumerBuilder::on_stream_deleted`]
+#[derive(Clone, Default)]
+pub enum StreamDeletedPolicy {
+    /// Close the consumer, the deliveries end with [`ConsumerDeliveryError::StreamDeleted`]
+    #[default]
+    Close,
+    /// Create the stream again with the creator and consume it from its first offset, for
+    /// the ephemeral streams deleted and created by the applications
+    Recreate(Box<StreamCreator>),
+}
+
+impl StreamDeletedPolicy {
+    /// [`StreamDeletedPolicy::Recreate`] with `creator`
+    pub fn recreate(creator: StreamCreator) -> Self {
+        StreamDeletedPolicy::Recreate(Box::new(creator))
+    }
+}
+
+/// Filter values sent to the broker and the client-side post filter
+#[derive(Clone)]
+pub(crate) struct ConsumerFilter {
+    values: Vec<String>,
+    post_filter: Arc<dyn Fn(&Message) -> bool + Send + Sync>,
+}
+
+type ConsumerUpdateListener =
+    Arc<dyn Fn(ConsumerUpdateContext) -> BoxFuture<'static, OffsetSpecification> + Send + Sync>;
+
+/// State of a single active consumer passed to its [`ConsumerBuilder::consumer_update_listener`]
+pub struct ConsumerUpdateContext {
+    consumer: Arc<ConsumerInternal>,
+    active: bool,
+}
+
+impl ConsumerUpdateContext {
+    /// Whether the consumer is being activated
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Stream of the consumer
+    pub fn stream(&self) -> &str {
+        &self.consumer.stream
+    }
+
+    /// Name of the consumer, shared by the consumers of the group
+    pub fn name(&self) -> Option<&str> {
+        self.consumer.name.as_deref()
+    }
+
+    /// Query the offset stored for the group, e.g. to resume after it
+    pub async fn query_offset(&self) -> Result<u64, ConsumerQueryOffsetError> {
+        self.consumer.query_offset().await
+    }
+}
+
+type DeliveryHandler =
+    Arc<dyn Fn(MessageContext, Message) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+/// Builder for a [`Consumer`] driven by a message handler, see [`ConsumerBuilder::message_handler`]
+pub struct HandlerConsumerBuilder {
+    builder: ConsumerBuilder,
+    handler: DeliveryHandler,
+    concurrency: usize,
+    /// Set for at-least-once processing, the failed messages are retried with it
+    retry_policy: Option<Arc<dyn RetryPolicy>>,
+}
+
+impl HandlerConsumerBuilder {
+    /// Handle up to `concurrency` messages at the same time, defaults to 1
+    ///
+    /// Messages may then complete out of order: the offset tracked for automatic offset
+    /// tracking, and the chunks processed for [`CreditStrategy::OnProcessed`], only advance
+    /// up to the last message before which every message completed.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Store the offset of a message only once the handler succeeded on it, or once it was
+    /// given up on and parked
+    ///
+    /// ```no_run
+    /// # async fn example(env: rabbitmq_stream_client::Environment) -> Result<(), Box<dyn std::error::Error>> {
+    /// use rabbitmq_stream_client::types::{
+    ///     OffsetSpecification, PoisonMessageHandling, RecoveryBackoff,
+    /// };
+    ///
+    /// let handle = env
+    ///     .consumer()
+    ///     .name("billing")
+    ///     .start_from_stored_offset_or(OffsetSpecification::First)
+    ///     .poison_messages(PoisonMessageHandling::parking_stream("orders-parked"))
+    ///     .try_message_handler(|_context, _message| async { Ok::<_, std::io::Error>(()) })
+    ///     .at_least_once(RecoveryBackoff::new().max_attempts(5))
+    ///     .start("orders")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// A failed message is handled again following `retry_policy`, in place of
+    /// [`PoisonMessageHandling::max_attempts`]. Once the policy gives up the message goes to
+    /// the [poison message handling](ConsumerBuilder::poison_messages) and the offset moves
+    /// past it. Without poison message handling, or when parking fails, the consumer closes
+    /// with the offset stored before the message: the next consumer started from the stored
+    /// offset receives it again.
+    ///
+    /// The consumer needs a name, automatic offset tracking is enabled with its defaults
+    /// unless [`ConsumerBuilder::auto_offset_tracking`] is set.
+    pub fn at_least_once(mut self, retry_policy: impl RetryPolicy + 'static) -> Self {
+        self.retry_policy = Some(Arc::new(retry_policy));
         self
     }
+
+    /// Subscribe to the stream and start handling messages
+    ///
+    /// The returned handle closes the consumer, which stops the processing task.
+    pub async fn start(self, stream: &str) -> Result<ConsumerHandle, ConsumerCreateError> {
+        let mut builder = self.builder;
+        builder.ordered_completions = self.concurrency > 1 || self.retry_policy.is_some();
+        if self.retry_policy.is_some() && builder.auto_offset_tracking.is_none() {
+            builder.auto_offset_tracking = Some(AutoOffsetTracking::default());
+        }
+        let mut consumer = builder.build(stream).await?;
+        let handle = consumer.handle();
+        let handler = self.handler;
+        let retry_policy = self.retry_policy;
+        let concurrency = Arc::new(Semaphore::new(self.concurrency));
+
+        tokio::task::spawn(async move {
+            while let Some(delivery) = consumer.next().await {
+                match delivery {
+                    Ok(delivery) if consumer.internal.completions.is_some() => {
+                        let permit = concurrency.clone().acquire_owned().await.unwrap();
+                        let internal = consumer.internal.clone();
+                        let handler = handler.clone();
+                        let retry_policy = retry_policy.clone();
+                        tokio::task::spawn(async move {
+                            let offset = delivery.offset;
+                            if handle_with_attempts(
+                                &internal,
+                                &handler,
+                                retry_policy.as_deref(),
+                                delivery,
+                            )
+                            .await
+                            {
+                                internal.complete(offset);
+                            } else {
+                                warn!(
+                                    stream = %internal.stream,
+                                    offset,
+                                    "Message given up on and not parked, closing the consumer"
+                                );
+                                let _ = ConsumerHandle(internal).close().await;
+                            }
+                            drop(permit);
+                        });
+                    }
+                    Ok(delivery) => {
+                        handle_with_attempts(&consumer.internal, &handler, None, delivery).await;
+                    }
+                    Err(error) => trace!(?error, "Consumer delivery error"),
+                }
+            }
+            trace!("Message handler stopped");
+        });
+
+        Ok(handle)
+    }
+}
+
+/// Run the handler until it succeeds, giving up on the message after the last attempt
+///
+/// With a retry policy the attempts follow it, and false is returned when the message was
+/// given up on without being parked.
+async fn handle_with_attempts(
+    consumer: &Arc<ConsumerInternal>,
+    handler: &DeliveryHandler,
+    retry_policy: Option<&dyn RetryPolicy>,
+    delivery: Delivery,
+) -> bool {
+    let max_attempts = consumer
+        .poison
+        .as_ref()
+        .map_or(1, |poison| poison.handling.max_attempts);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let context = MessageContext {
+            stream: consumer.stream.clone(),
+            subscription_id: delivery.subscription_id,
+            offset: delivery.offset,
+            chunk: delivery.chunk,
+            consumer: consumer.clone(),
+        };
+        let error = match handler(context, delivery.message.clone()).await {
+            Ok(()) => return true,
+            Err(error) => error,
+        };
+        trace!(
+            offset = delivery.offset,
+            attempts,
+            error,
+            "Message handler failed"
+        );
+
+        let give_up = match retry_policy {
+            Some(policy) => {
+                let failure: Box<dyn std::error::Error + Send + Sync> = error.clone().into();
+                match next_delay(policy, attempts, &*failure) {
+                    Some(delay) => {
+                        tokio::time::sleep(delay).await;
+                        false
+                    }
+                    None => true,
+                }
+            }
+            None => attempts >= max_attempts,
+        };
+        if give_up {
+            return match &consumer.poison {
+                Some(poison) => {
+                    let mut message = delivery.message;
+                    let parked = poison
+                        .route(PoisonMessage {
+                            stream: delivery.stream,
+                            offset: delivery.offset,
+                            data: message.cache_encoded(),
+                            message: Some(message),
+                            reason: PoisonReason::Handler { attempts, error },
+                        })
+                        .await;
+                    parked || retry_policy.is_none()
+                }
+                None => retry_policy.is_none(),
+            };
+        }
+    }
+}
+
+/// Where a message handled by a [`HandlerConsumerBuilder`] consumer comes from
+pub struct MessageContext {
+    stream: Arc<str>,
+    subscription_id: u8,
+    offset: u64,
+    chunk: ChunkMetadata,
+    consumer: Arc<ConsumerInternal>,
+}
+
+impl MessageContext {
+    /// Stream of the message
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// Subscription the message was delivered to
+    pub fn subscription_id(&self) -> u8 {
+        self.subscription_id
+    }
+
+    /// Position of the message in the stream
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Chunk the message was stored in
+    pub fn chunk(&self) -> &ChunkMetadata {
+        &self.chunk
+    }
+
+    /// Handle of the consumer, to close it from the handler
+    pub fn handle(&self) -> ConsumerHandle {
+        ConsumerHandle(self.consumer.clone())
+    }
 }
 
 impl Consumer {
+    /// Deserialize the body of the messages from JSON
+    ///
+    /// A message failing to deserialize is returned as [`ConsumerDeliveryError::Deserialize`].
+    #[cfg(feature = "serde")]
+    pub fn typed<T: serde::de::DeserializeOwned>(self) -> TypedConsumer<T> {
+        TypedConsumer::new(self, Json)
+    }
+
+    /// Deserialize the body of the messages with `format`
+    #[cfg(feature = "serde")]
+    pub fn typed_with<T: serde::de::DeserializeOwned, F: PayloadFormat>(
+        self,
+        format: F,
+    ) -> TypedConsumer<T, F> {
+        TypedConsumer::new(self, format)
+    }
+
+    /// Decode the body of the messages with the codec of their content type
+    ///
+    /// A message failing to decode is returned as [`ConsumerDeliveryError::Decode`].
+    pub fn decoded<T>(self, codecs: BodyCodecs<T>) -> DecodedConsumer<T> {
+        DecodedConsumer::new(self, codecs)
+    }
+
     /// Return an handle for current [`Consumer`]
     pub fn handle(&self) -> ConsumerHandle {
         ConsumerHandle(self.internal.clone())
@@ -108,6 +1368,59 @@ impl Consumer {
     pub fn is_closed(&self) -> bool {
         self.internal.is_closed()
     }
+
+    /// Check if the consumer is the active one of its single active consumer group
+    ///
+    /// Always true when single active consumer is not enabled.
+    pub fn is_active(&self) -> bool {
+        self.internal.active.load(SeqCst)
+    }
+
+    /// Stream consumed
+    pub fn stream(&self) -> &str {
+        &self.internal.stream
+    }
+
+    /// Store `offset` in the broker under the name of the consumer
+    pub async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        self.internal.store_offset(offset).await
+    }
+
+    /// Query the offset last stored in the broker under the name of the consumer
+    pub async fn query_offset(&self) -> Result<u64, ConsumerQueryOffsetError> {
+        self.internal.query_offset().await
+    }
+
+    /// Stop granting credits, the broker stops delivering once the outstanding ones are used
+    ///
+    /// The subscription and the consumer state are kept, messages already received are still
+    /// returned. Credits earned while paused are granted by [`Consumer::resume`].
+    pub fn pause(&self) {
+        self.internal.pause()
+    }
+
+    /// Grant the credits withheld since [`Consumer::pause`] and resume the deliveries
+    pub async fn resume(&self) -> Result<(), ConsumerDeliveryError> {
+        self.internal.resume().await
+    }
+
+    /// Check if the consumer is paused
+    pub fn is_paused(&self) -> bool {
+        self.internal.is_paused()
+    }
+
+    /// Number of committed messages the consumer did not return yet, from the stream statistics
+    ///
+    /// Before RabbitMQ 3.13 the broker only reports the committed chunk, so the messages of
+    /// the last committed chunk are not counted. Requires RabbitMQ 3.11 or later.
+    pub async fn lag(&self) -> Result<u64, StreamStatsError> {
+        self.internal.lag().await
+    }
+
+    /// Snapshot of the internal state of the consumer and its connection
+    pub async fn debug_state(&self) -> ConsumerDebugState {
+        self.internal.debug_state().await
+    }
 }
 
 impl Stream for Consumer {
@@ -115,7 +1428,33 @@ impl Stream for Consumer {
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         self.internal.waker.register(cx.waker());
+        if let Some(offset) = self.last_offset.take() {
+            self.internal.processed(offset);
+        }
         let poll = Pin::new(&mut self.receiver).poll_recv(cx);
+        self.internal.idle.store(poll.is_pending(), SeqCst);
+        if let Poll::Ready(Some(Ok(delivery))) = &poll {
+            if let Some(detector) = &self.internal.slow_consumer {
+                if detector.tracks_bytes() {
+                    detector.remove_pending(delivery.message.encoded_size() as u64);
+                }
+            }
+            if let Some(buffered) = &self.internal.buffered_bytes {
+                buffered.release(delivery.message.encoded_size() as usize);
+            }
+            self.internal.next_offset.store(delivery.offset + 1, SeqCst);
+            if let Some(completions) = &self.internal.completions {
+                completions.lock().unwrap().start(delivery.offset);
+            } else {
+                self.internal.track(delivery.offset);
+            }
+            if self.internal.credit_strategy == CreditStrategy::OnProcessed
+                && self.internal.completions.is_none()
+            {
+                self.last_offset = Some(delivery.offset);
+            }
+        }
+        self.internal.progressed.notify_waiters();
         match (self.is_closed(), poll.is_ready()) {
             (true, false) => Poll::Ready(None),
             _ => poll,
@@ -123,7 +1462,16 @@ impl Stream for Consumer {
     }
 }
 
+impl Drop for Consumer {
+    fn drop(&mut self) {
+        // the handle waiting for the consumer to drain sees the channel closed
+        self.receiver.close();
+        self.internal.progressed.notify_waiters();
+    }
+}
+
 /// Handler API for [`Consumer`]
+#[derive(Clone)]
 pub struct ConsumerHandle(Arc<ConsumerInternal>);
 
 impl ConsumerHandle {
@@ -131,13 +1479,23 @@ impl ConsumerHandle {
     pub async fn close(self) -> Result<(), ConsumerCloseError> {
         match self.0.closed.compare_exchange(false, true, SeqCst, SeqCst) {
             Ok(false) => {
-                let response = self.0.client.unsubscribe(self.0.subscription_id).await?;
+                if let Err(error) = self.0.flush_offset().await {
+                    trace!(?error, "Failed to store consumer offset on close");
+                }
+                // a graceful close in progress already unsubscribed
+                if self.0.draining.load(SeqCst) {
+                    self.0.waker.wake();
+                    return Ok(());
+                }
+                let response = self.0.client().unsubscribe(self.0.subscription_id).await;
+                self.0.release_connection().await;
+                let response = response?;
                 if response.is_ok() {
                     self.0.waker.wake();
                     Ok(())
                 } else {
                     Err(ConsumerCloseError::Close {
-                        stream: self.0.stream.clone(),
+                        stream: self.0.stream.to_string(),
                         status: response.code().clone(),
                     })
                 }
@@ -145,37 +1503,432 @@ impl ConsumerHandle {
             _ => Err(ConsumerCloseError::AlreadyClosed),
         }
     }
+
+    /// Close the [`Consumer`] once the messages it already received are processed
+    ///
+    /// Credits stop being granted and the consumer unsubscribes, the messages received are
+    /// still returned until none is left and the last one was processed, for at most
+    /// `timeout`. The offset of the last message is then stored if automatic tracking is
+    /// enabled and the consumer ends.
+    pub async fn close_gracefully(self, timeout: Duration) -> Result<(), ConsumerCloseError> {
+        if self.0.is_closed() || self.0.draining.swap(true, SeqCst) {
+            return Err(ConsumerCloseError::AlreadyClosed);
+        }
+        self.0.pause();
+
+        let response = self.0.client().unsubscribe(self.0.subscription_id).await?;
+        if !response.is_ok() {
+            return Err(ConsumerCloseError::Close {
+                stream: self.0.stream.to_string(),
+                status: response.code().clone(),
+            });
+        }
+
+        if tokio::time::timeout(timeout, self.0.drained())
+            .await
+            .is_err()
+        {
+            trace!(stream = %self.0.stream, "Consumer not drained before the close timeout");
+        }
+        if self.0.closed.swap(true, SeqCst) {
+            self.0.release_connection().await;
+            return Ok(());
+        }
+        if let Err(error) = self.0.flush_offset().await {
+            trace!(?error, "Failed to store consumer offset on close");
+        }
+        self.0.release_connection().await;
+        self.0.waker.wake();
+        Ok(())
+    }
+
     /// Check if the consumer is closed
     pub async fn is_closed(&self) -> bool {
         self.0.is_closed()
     }
+
+    /// Check if the consumer is the active one of its single active consumer group
+    pub fn is_active(&self) -> bool {
+        self.0.active.load(SeqCst)
+    }
+
+    /// Store `offset` in the broker under the name of the consumer
+    pub async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        self.0.store_offset(offset).await
+    }
+
+    /// Query the offset last stored in the broker under the name of the consumer
+    pub async fn query_offset(&self) -> Result<u64, ConsumerQueryOffsetError> {
+        self.0.query_offset().await
+    }
+
+    /// See [`Consumer::pause`]
+    pub fn pause(&self) {
+        self.0.pause()
+    }
+
+    /// See [`Consumer::resume`]
+    pub async fn resume(&self) -> Result<(), ConsumerDeliveryError> {
+        self.0.resume().await
+    }
+
+    /// Check if the consumer is paused
+    pub fn is_paused(&self) -> bool {
+        self.0.is_paused()
+    }
+
+    /// See [`Consumer::lag`]
+    pub async fn lag(&self) -> Result<u64, StreamStatsError> {
+        self.0.lag().await
+    }
+
+    /// See [`Consumer::debug_state`]
+    pub async fn debug_state(&self) -> ConsumerDebugState {
+        self.0.debug_state().await
+    }
+}
+
+/// Replica the next consumer connects to, the consumers of a stream are spread over them
+static NEXT_REPLICA: AtomicUsize = AtomicUsize::new(0);
+
+/// Lease a subscription id on a connection to a node hosting `stream`, one of its replicas
+/// or the leader when it has none, `stream` is located unless its metadata is given
+async fn stream_connection(
+    environment: &Environment,
+    stream: &str,
+    metadata: Option<StreamMetadata>,
+    raw_chunks: bool,
+    id: Option<u8>,
+) -> Result<ConnectionLease, ConsumerCreateError> {
+    let located = match metadata {
+        Some(metadata) if metadata.response_code == ResponseCode::Ok => Ok(metadata),
+        Some(metadata) => Err(metadata.response_code),
+        None => environment.locate(stream).await?,
+    };
+    let metadata = match located {
+        Ok(metadata) => metadata,
+        Err(status) => {
+            return Err(ConsumerCreateError::Create {
+                stream: stream.to_owned(),
+                status,
+            })
+        }
+    };
+    let node = match metadata.replicas.len() {
+        0 => &metadata.leader,
+        replicas => &metadata.replicas[NEXT_REPLICA.fetch_add(1, Relaxed) % replicas],
+    };
+    Ok(environment
+        .consumer_connection(Some(node), raw_chunks, id)
+        .await?)
+}
+
+async fn flush_offset_periodically(consumer: Weak<ConsumerInternal>, flush_interval: Duration) {
+    let mut interval = tokio::time::interval(flush_interval);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let consumer = match consumer.upgrade() {
+            Some(consumer) if !consumer.is_closed() => consumer,
+            _ => break,
+        };
+        if let Err(error) = consumer.flush_offset().await {
+            trace!(?error, "Failed to store consumer offset");
+        }
+    }
 }
 
-struct ConsumerMessageHandler(Arc<ConsumerInternal>);
+/// Subscribe again until it succeeds or the consumer is closed
+async fn recover_subscription(consumer: Weak<ConsumerInternal>) {
+    let stream = match consumer.upgrade() {
+        Some(consumer) if !consumer.recovering.swap(true, SeqCst) => {
+            consumer.notify_recovery(ConsumerRecoveryEvent::Started {
+                stream: consumer.stream.to_string(),
+            });
+            consumer.stream.clone()
+        }
+        _ => return,
+    };
+
+    for attempt in 1u32.. {
+        let consumer = match consumer.upgrade() {
+            Some(consumer) if !consumer.is_closed() => consumer,
+            _ => return,
+        };
+
+        let span = debug_span!(
+            "rabbitmq_stream.consumer_recovery",
+            stream = %stream,
+            attempt,
+        );
+        match consumer.resubscribe().instrument(span).await {
+            // closed while subscribing again
+            Ok(_) if consumer.is_closed() => {
+                let _ = consumer
+                    .client()
+                    .unsubscribe(consumer.subscription_id)
+                    .await;
+                consumer.release_connection().await;
+                return;
+            }
+            Ok(offset) => {
+                consumer.recovering.store(false, SeqCst);
+                consumer.record(|collector, context| collector.reconnect(context));
+                consumer.notify_recovery(ConsumerRecoveryEvent::Recovered {
+                    stream: stream.to_string(),
+                    offset,
+                });
+                return;
+            }
+            Err(ConsumerCreateError::Create {
+                status: ResponseCode::StreamDoesNotExist,
+                ..
+            }) if attempt_stream_deleted(&consumer, &stream).await => {}
+            Err(error) => {
+                let policy = &*consumer.environment.options.recovery_policy;
+                let delay = match next_delay(policy, attempt, &error) {
+                    Some(delay) => delay,
+                    None => {
+                        let reason = error.to_string();
+                        // boxed as is, the error keeps its code and retryability
+                        let client_error = ClientError::GenericError(Box::new(error));
+                        consumer.give_up(client_error.into()).await;
+                        consumer.notify_recovery(ConsumerRecoveryEvent::GaveUp {
+                            stream: stream.to_string(),
+                            error: reason,
+                        });
+                        return;
+                    }
+                };
+                consumer.notify_recovery(ConsumerRecoveryEvent::Failed {
+                    stream: stream.to_string(),
+                    error: error.to_string(),
+                });
+                drop(consumer);
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Apply the [`StreamDeletedPolicy`] once the stream was deleted, true if the recovery
+/// attempts again right away or is over, false to go on with the recovery policy
+async fn attempt_stream_deleted(consumer: &ConsumerInternal, stream: &str) -> bool {
+    consumer.notify_recovery(ConsumerRecoveryEvent::StreamDeleted {
+        stream: stream.to_owned(),
+    });
+    match consumer.recreate_stream().await {
+        None => {
+            consumer
+                .give_up(ConsumerDeliveryError::StreamDeleted {
+                    stream: stream.to_owned(),
+                })
+                .await;
+            true
+        }
+        Some(Ok(())) => true,
+        Some(Err(error)) => {
+            warn!(%stream, %error, "Failed to create the deleted stream again");
+            false
+        }
+    }
+}
+
+/// Handler of the connection of the given generation
+struct ConsumerMessageHandler(Arc<ConsumerInternal>, u64);
+
+impl ConsumerMessageHandler {
+    async fn handle_delivery(&self, delivery: DeliverCommand) {
+        let chunk = ChunkMetadata {
+            first_offset: delivery.chunk_first_offset(),
+            timestamp: delivery.timestamp(),
+            epoch: delivery.epoch(),
+            num_entries: delivery.num_entries(),
+            num_records: delivery.messages.len() as u32,
+        };
+        let from = self.first_to_dispatch(&chunk, delivery.messages.len());
+        let messages = (chunk.first_offset..).zip(delivery.messages).collect();
+        self.handle_messages(chunk, from, messages).await
+    }
+
+    /// Decode the messages one by one, giving up on the ones failing to decode
+    async fn handle_raw_delivery(&self, delivery: RawDeliverCommand, poison: &PoisonMessageRouter) {
+        let chunk = ChunkMetadata {
+            first_offset: delivery.chunk_first_offset(),
+            timestamp: delivery.timestamp(),
+            epoch: delivery.epoch(),
+            num_entries: delivery.num_entries(),
+            num_records: delivery.num_records(),
+        };
+        let entries = match delivery.decode_messages() {
+            Ok(entries) => entries,
+            Err(error) => {
+                let _ = self
+                    .0
+                    .sender
+                    .send(Err(ClientError::from(error).into()))
+                    .await;
+                return;
+            }
+        };
+
+        let from = self.first_to_dispatch(&chunk, entries.len());
+        let mut messages = Vec::with_capacity(entries.len());
+        for (offset, entry) in (chunk.first_offset..).zip(entries) {
+            match entry {
+                Ok(message) => messages.push((offset, message)),
+                Err(undecoded) if offset >= from => {
+                    poison
+                        .route(PoisonMessage {
+                            stream: self.0.stream.clone(),
+                            offset,
+                            data: undecoded.data.into(),
+                            message: None,
+                            reason: PoisonReason::Decode(format!("{:?}", undecoded.error)),
+                        })
+                        .await;
+                }
+                Err(_) => {}
+            }
+        }
+        self.handle_messages(chunk, from, messages).await
+    }
+
+    /// Claim the `count` messages of `chunk`, returns the offset of the first one for the
+    /// application
+    fn first_to_dispatch(&self, chunk: &ChunkMetadata, count: usize) -> u64 {
+        let claimed = self.0.claim(chunk.first_offset, count as u64);
+        claimed.max(self.0.start_offset.load(SeqCst))
+    }
+
+    /// Send the messages from offset `from` to the application
+    async fn handle_messages(
+        &self,
+        chunk: ChunkMetadata,
+        from: u64,
+        messages: Vec<(u64, Message)>,
+    ) {
+        self.0.record(|collector, context| {
+            collector.chunk(context, chunk.num_records as u64);
+            let bytes = messages
+                .iter()
+                .map(|(_, message)| message.encoded_size() as u64)
+                .sum();
+            collector.delivered_bytes(context, bytes);
+        });
+        let messages: Vec<(u64, Message)> = messages
+            .into_iter()
+            .filter(|(offset, message)| {
+                *offset >= from
+                    && self
+                        .0
+                        .filter
+                        .as_ref()
+                        .is_none_or(|filter| (filter.post_filter)(message))
+            })
+            .collect();
+
+        if self.0.credit_strategy == CreditStrategy::OnProcessed {
+            if let Some((last_offset, _)) = messages.last() {
+                self.0
+                    .unprocessed_chunks
+                    .lock()
+                    .unwrap()
+                    .push_back(*last_offset);
+            }
+        }
+        let delivered = !messages.is_empty();
+        let consumed = messages.len() as u64;
+        if delivered {
+            self.0
+                .record(|collector, context| collector.consume(context, consumed));
+        }
+
+        for (offset, message) in messages {
+            let delivery = self.0.intercept(offset, message).map(|message| Delivery {
+                stream: self.0.stream.clone(),
+                subscription_id: self.0.subscription_id,
+                offset,
+                message,
+                chunk,
+            });
+            if let (Some(detector), Ok(delivery)) = (&self.0.slow_consumer, &delivery) {
+                if detector.tracks_bytes() {
+                    let bytes = delivery.message.encoded_size() as u64;
+                    if let Some(reason) = detector.add_pending(bytes) {
+                        self.0.report_slow(reason);
+                    }
+                }
+            }
+            if let (Some(buffered), Ok(delivery)) = (&self.0.buffered_bytes, &delivery) {
+                tokio::select! {
+                    _ = buffered.reserve(delivery.message.encoded_size() as usize) => {}
+                    // the consumer was dropped, nothing makes room anymore
+                    _ = self.0.sender.closed() => return,
+                }
+            }
+            let _ = self.0.sender.send(delivery).await;
+        }
+
+        let credits = self.0.credits_after_chunk(delivered);
+        if credits > 0 {
+            self.0.grant_credits(credits).await;
+        }
+        if let Some(detector) = &self.0.slow_consumer {
+            let exhausted = self.0.outstanding_credits.load(SeqCst) == 0
+                && !self.0.idle.load(SeqCst)
+                && !self.0.is_paused();
+            if let Some(reason) = detector.chunk(exhausted) {
+                self.0.report_slow(reason);
+            }
+        }
+    }
+}
 
 #[async_trait::async_trait]
 impl MessageHandler for ConsumerMessageHandler {
     async fn handle_message(&self, item: MessageResult) -> crate::RabbitMQStreamResult<()> {
+        if self.1 != self.0.generation.load(SeqCst) {
+            return Ok(());
+        }
         match item {
-            Some(Ok(response)) => {
-                if let ResponseKind::Deliver(delivery) = response.kind() {
-                    for message in delivery.messages {
-                        let _ = self
-                            .0
-                            .sender
-                            .send(Ok(Delivery {
-                                subscription_id: self.0.subscription_id,
-                                message,
-                            }))
-                            .await;
+            Some(Ok(response)) => match response.kind() {
+                ResponseKind::Deliver(delivery) => self.handle_delivery(delivery).await,
+                ResponseKind::RawDeliver(delivery) => {
+                    if let Some(poison) = &self.0.poison {
+                        self.handle_raw_delivery(delivery, poison).await
                     }
                 }
-                // TODO handle credit fail
-                let _ = self.0.client.credit(self.0.subscription_id, 1).await;
-            }
+                ResponseKind::ConsumerUpdate(update) => self.0.consumer_update(update).await,
+                // the broker cancelled the subscription
+                ResponseKind::MetadataUpdate(update) if update.stream() == &*self.0.stream => {
+                    self.0.environment.emit(StreamEvent::MetadataUpdate {
+                        stream: update.stream().to_owned(),
+                        code: update.code().clone(),
+                    });
+                    tokio::task::spawn(recover_subscription(Arc::downgrade(&self.0)));
+                }
+                _ => {}
+            },
             Some(Err(err)) => {
                 let _ = self.0.sender.send(Err(err.into())).await;
             }
+            None if !self.0.is_closed() && !self.0.draining.load(SeqCst) => {
+                trace!("Consumer connection closed");
+                let policy = &self.0.environment.options.broker_close;
+                match policy.failure(&self.0.client()).await {
+                    Some(error) => {
+                        self.0.notify_recovery(ConsumerRecoveryEvent::GaveUp {
+                            stream: self.0.stream.to_string(),
+                            error: error.to_string(),
+                        });
+                        self.0.give_up(error.into()).await;
+                    }
+                    None => {
+                        tokio::task::spawn(recover_subscription(Arc::downgrade(&self.0)));
+                    }
+                }
+            }
             None => {
                 trace!("Closing consumer");
                 self.0.closed.store(true, Relaxed);
@@ -185,8 +1938,68 @@ impl MessageHandler for ConsumerMessageHandler {
         Ok(())
     }
 }
+/// Message received by a [`Consumer`], deserialized to `T` by a typed consumer
 #[derive(Debug)]
-pub struct Delivery {
+pub struct Delivery<T = Message> {
+    pub(crate) stream: Arc<str>,
+    /// Subscription the message was delivered to
     pub subscription_id: u8,
-    pub message: Message,
+    /// Position of the message in the stream
+    pub offset: u64,
+    pub message: T,
+    pub(crate) chunk: ChunkMetadata,
+}
+
+impl<T> Delivery<T> {
+    /// Stream the message was read from, the partition for a super stream consumer
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// Chunk the message was stored in
+    pub fn chunk(&self) -> &ChunkMetadata {
+        &self.chunk
+    }
+}
+
+/// Metadata of the chunk a message was stored in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkMetadata {
+    pub(crate) first_offset: u64,
+    pub(crate) timestamp: u64,
+    pub(crate) epoch: u64,
+    pub(crate) num_entries: u16,
+    pub(crate) num_records: u32,
+}
+
+impl ChunkMetadata {
+    /// Offset of the first message of the chunk, which identifies the chunk
+    pub fn first_offset(&self) -> u64 {
+        self.first_offset
+    }
+
+    /// Offset of the last message of the chunk
+    pub fn last_offset(&self) -> u64 {
+        self.first_offset + (self.num_records as u64).saturating_sub(1)
+    }
+
+    /// Creation time of the chunk in milliseconds since the epoch
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Epoch of the stream leader which wrote the chunk
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Number of entries, a sub-entry counts as one entry
+    pub fn num_entries(&self) -> u16 {
+        self.num_entries
+    }
+
+    /// Number of messages in the chunk, including the ones skipped or filtered out
+    pub fn num_records(&self) -> u32 {
+        self.num_records
+    }
 }
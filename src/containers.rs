@@ -0,0 +1,154 @@
+//! RabbitMQ node in a container for the integration tests of the applications, enabled with
+//! the `testcontainers` feature
+//!
+//! [`RabbitMqStreamContainer`] starts the `rabbitmq` image with the stream plugin through
+//! [testcontainers](https://docs.rs/testcontainers), waits until the node accepts stream
+//! connections and builds environments connected to it. The container is removed when it
+//! is dropped.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use rabbitmq_stream_client::{containers::RabbitMqStreamContainer, types::Message};
+//!
+//! let rabbitmq = RabbitMqStreamContainer::start().await?;
+//! let environment = rabbitmq.environment().await?;
+//! environment.stream_creator().create("orders").await?;
+//!
+//! let producer = environment.producer().build("orders").await?;
+//! producer
+//!     .send_with_confirm(Message::builder().body("hello").build())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use testcontainers::{
+    core::{IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+    ContainerAsync, GenericImage, ImageExt,
+};
+use tokio::time::Instant;
+
+use crate::{error::ContainerError, Environment, EnvironmentBuilder, RabbitMQStreamResult};
+
+const IMAGE: &str = "rabbitmq";
+const DEFAULT_TAG: &str = "4.1-management";
+
+const STREAM_PORT: u16 = 5552;
+const MANAGEMENT_PORT: u16 = 15672;
+
+const ENABLED_PLUGINS: &[u8] =
+    b"[rabbitmq_management,rabbitmq_stream,rabbitmq_stream_management].\n";
+
+/// Delay between two connection attempts while waiting for the node
+const READINESS_POLL: Duration = Duration::from_millis(500);
+
+/// Running RabbitMQ node with the stream plugin, see the [module](self) documentation
+pub struct RabbitMqStreamContainer {
+    container: ContainerAsync<GenericImage>,
+    host: String,
+    port: u16,
+    management_port: u16,
+}
+
+impl RabbitMqStreamContainer {
+    pub fn builder() -> RabbitMqStreamContainerBuilder {
+        RabbitMqStreamContainerBuilder {
+            tag: DEFAULT_TAG.to_owned(),
+            startup_timeout: Duration::from_secs(120),
+        }
+    }
+
+    /// Start a container with the default options of [`RabbitMqStreamContainer::builder`]
+    pub async fn start() -> Result<Self, ContainerError> {
+        Self::builder().start().await
+    }
+
+    /// Host the ports of the container are mapped on
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Host port mapped to the stream port of the node
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Host port mapped to the management port of the node
+    pub fn management_port(&self) -> u16 {
+        self.management_port
+    }
+
+    /// Builder of an environment connected to the node, to set more options
+    pub fn environment_builder(&self) -> EnvironmentBuilder {
+        let builder = Environment::builder().host(&self.host).port(self.port);
+        #[cfg(feature = "management")]
+        let builder = builder.management_port(self.management_port);
+        builder
+    }
+
+    /// Environment connected to the node with the default `guest` user
+    pub async fn environment(&self) -> RabbitMQStreamResult<Environment> {
+        self.environment_builder().build().await
+    }
+
+    /// The container, to stop, pause or inspect the node
+    pub fn container(&self) -> &ContainerAsync<GenericImage> {
+        &self.container
+    }
+}
+
+/// Builder for a [`RabbitMqStreamContainer`]
+pub struct RabbitMqStreamContainerBuilder {
+    tag: String,
+    startup_timeout: Duration,
+}
+
+impl RabbitMqStreamContainerBuilder {
+    /// Tag of the `rabbitmq` image, `4.1-management` by default
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = tag.to_owned();
+        self
+    }
+
+    /// Maximum time for the node to start and accept connections, 2 minutes by default
+    pub fn startup_timeout(mut self, startup_timeout: Duration) -> Self {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    /// Start the container and wait until the node accepts stream connections
+    pub async fn start(self) -> Result<RabbitMqStreamContainer, ContainerError> {
+        let deadline = Instant::now() + self.startup_timeout;
+        let container = GenericImage::new(IMAGE, self.tag.as_str())
+            .with_exposed_port(STREAM_PORT.tcp())
+            .with_exposed_port(MANAGEMENT_PORT.tcp())
+            .with_wait_for(WaitFor::message_on_stdout("Server startup complete"))
+            .with_copy_to("/etc/rabbitmq/enabled_plugins", ENABLED_PLUGINS.to_vec())
+            .with_startup_timeout(self.startup_timeout)
+            .start()
+            .await?;
+        let rabbitmq = RabbitMqStreamContainer {
+            host: container.get_host().await?.to_string(),
+            port: container.get_host_port_ipv4(STREAM_PORT).await?,
+            management_port: container.get_host_port_ipv4(MANAGEMENT_PORT).await?,
+            container,
+        };
+
+        // the stream listener can start after the startup message
+        loop {
+            match rabbitmq.environment().await {
+                Ok(_) => return Ok(rabbitmq),
+                Err(error) if Instant::now() + READINESS_POLL >= deadline => {
+                    return Err(ContainerError::NotReady {
+                        timeout: self.startup_timeout,
+                        error,
+                    })
+                }
+                Err(_) => tokio::time::sleep(READINESS_POLL).await,
+            }
+        }
+    }
+}
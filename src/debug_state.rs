@@ -0,0 +1,71 @@
+/// State of a connection, see [`crate::Client::debug_state`]
+///
+/// Like the other debug snapshots it implements `Serialize` with the `serde` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ClientDebugState {
+    pub host: String,
+    pub port: u16,
+    pub subscriptions: Vec<SubscriptionDebugState>,
+    pub publishers: Vec<PublisherDebugState>,
+    /// Correlation ids of the requests waiting for a response
+    pub pending_correlation_ids: Vec<u32>,
+}
+
+/// Subscription of a connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SubscriptionDebugState {
+    pub subscription_id: u8,
+    pub stream: String,
+    /// Credits granted to the broker and not consumed by a chunk yet
+    pub credits: u32,
+}
+
+/// Publisher declared on a connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PublisherDebugState {
+    pub publisher_id: u8,
+    pub stream: String,
+    pub reference: Option<String>,
+}
+
+/// State of a producer, see [`crate::Producer::debug_state`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProducerDebugState {
+    pub stream: String,
+    pub publisher_id: u8,
+    /// Publishing id of the next message without one
+    pub next_publishing_id: u64,
+    /// Messages waiting in the accumulator for the next publish frame
+    pub accumulated: usize,
+    /// Messages published and waiting for their confirm
+    pub unconfirmed: usize,
+    /// Encoded size of the messages waiting for their confirm
+    pub unconfirmed_bytes: usize,
+    pub recovering: bool,
+    pub closed: bool,
+    pub connection: ClientDebugState,
+}
+
+/// State of a consumer, see [`crate::Consumer::debug_state`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ConsumerDebugState {
+    pub stream: String,
+    pub subscription_id: u8,
+    /// Credits granted to the broker and not consumed by a chunk yet
+    pub outstanding_credits: u32,
+    /// Deliveries received and not returned to the application yet
+    pub pending_deliveries: usize,
+    /// Encoded size of the pending deliveries, tracked with
+    /// [`max_buffered_bytes`](crate::ConsumerBuilder::max_buffered_bytes) only
+    pub pending_bytes: Option<usize>,
+    pub active: bool,
+    pub paused: bool,
+    pub recovering: bool,
+    pub closed: bool,
+    pub connection: ClientDebugState,
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use rabbitmq_stream_protocol::message::Message;
+use tokio::sync::Mutex;
+
+use crate::{
+    error::{ClientError, ProducerCloseError, ProducerCreateError, ProducerPublishError},
+    producer::{confirm_sender, wait_for_confirm, ConfirmationStatus},
+    Producer, ProducerBuilder,
+};
+
+type SequenceSource = Arc<dyn Fn(&Message) -> u64 + Send + Sync>;
+
+/// Builder for a [`DedupProducer`], see [`ProducerBuilder::deduplicate`]
+pub struct DedupProducerBuilder {
+    pub(crate) builder: ProducerBuilder,
+    pub(crate) sequence: SequenceSource,
+}
+
+impl DedupProducerBuilder {
+    /// Declare the named publisher and resume after the last sequence stored by the broker
+    pub async fn build(self, stream: &str) -> Result<DedupProducer, ProducerCreateError> {
+        if self.builder.name.is_none() {
+            return Err(ProducerCreateError::NameMissing);
+        }
+        let producer = self.builder.build(stream).await?;
+        let last_sequence = stored_sequence(&producer).await?;
+        Ok(DedupProducer {
+            producer,
+            sequence: self.sequence,
+            last_sequence: Arc::new(Mutex::new(last_sequence)),
+        })
+    }
+}
+
+/// Outcome of a message sent with a [`DedupProducer`]
+#[derive(Debug)]
+pub enum DedupStatus {
+    /// The message was published
+    Published(ConfirmationStatus),
+    /// The sequence of the message is not above the last one published, it is skipped
+    Duplicate { sequence: u64 },
+}
+
+impl DedupStatus {
+    /// Check if the message was skipped as a duplicate
+    pub fn is_duplicate(&self) -> bool {
+        matches!(self, DedupStatus::Duplicate { .. })
+    }
+}
+
+/// Producer publishing each sequence of its input once, even when the input is replayed
+///
+/// ```no_run
+/// # async fn example(env: rabbitmq_stream_client::Environment) -> Result<(), Box<dyn std::error::Error>> {
+/// use rabbitmq_stream_client::types::{Message, Value};
+///
+/// // the sequence is the line number of the imported file
+/// let producer = env
+///     .producer()
+///     .name("file-import")
+///     .deduplicate(|message| match message.application_property("line") {
+///         Some(Value::Ulong(line)) => line,
+///         _ => 0,
+///     })
+///     .build("lines")
+///     .await?;
+/// let file = std::fs::read_to_string("import.txt")?;
+/// for (line, text) in file.lines().enumerate() {
+///     let message = Message::builder()
+///         .body(text.to_owned())
+///         .application_property("line", line as u64 + 1)
+///         .build();
+///     producer.send_with_confirm(message).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The sequence source maps a message to its position in the input, the same message
+/// always to the same sequence and the later messages to greater ones, e.g. the line of a
+/// file or the offset of a source stream. The sequence is the publishing id of the
+/// message: the broker discards the ones not greater than the last id stored for the
+/// name of the producer, across restarts and connection recoveries. The messages of a
+/// sequence not greater than the last one published are skipped without being sent, the
+/// sequences start at 1.
+pub struct DedupProducer {
+    producer: Producer,
+    sequence: SequenceSource,
+    /// Sequence of the last message sent, held while the messages are handed to the
+    /// producer so that the sequences are published in order
+    last_sequence: Arc<Mutex<Option<u64>>>,
+}
+
+impl DedupProducer {
+    /// Publish a message unless its sequence was published already, and wait for the
+    /// broker to confirm or reject it
+    pub async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<DedupStatus, ProducerPublishError> {
+        let mut statuses = self.batch_send_with_confirm(vec![message]).await?;
+        Ok(statuses.remove(0))
+    }
+
+    /// Publish the messages whose sequence was not published yet, and wait for the
+    /// outcome of each of them
+    ///
+    /// Statuses are returned in the same order as the messages.
+    pub async fn batch_send_with_confirm(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<DedupStatus>, ProducerPublishError> {
+        let mut last_sequence = self.last_sequence.lock().await;
+        let mut next_sequence = *last_sequence;
+        let mut duplicates = Vec::with_capacity(messages.len());
+        let mut receivers = Vec::new();
+        let mut to_publish = Vec::new();
+        for mut message in messages {
+            let sequence = (self.sequence)(&message);
+            if next_sequence.is_some_and(|last| sequence <= last) {
+                duplicates.push(Some(sequence));
+                continue;
+            }
+            next_sequence = Some(sequence);
+            duplicates.push(None);
+            message.set_publishing_id(sequence);
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            receivers.push(rx);
+            to_publish.push((message, confirm_sender(tx)));
+        }
+        if !to_publish.is_empty() {
+            self.producer.internal_send(to_publish, true).await?;
+            *last_sequence = next_sequence;
+        }
+        drop(last_sequence);
+
+        let mut receivers = receivers.into_iter();
+        let mut statuses = Vec::with_capacity(duplicates.len());
+        let mut failed = false;
+        for duplicate in duplicates {
+            statuses.push(match duplicate {
+                Some(sequence) => DedupStatus::Duplicate { sequence },
+                None => match wait_for_confirm(receivers.next().unwrap()).await {
+                    Ok(status) => {
+                        failed |= !status.confirmed();
+                        DedupStatus::Published(status)
+                    }
+                    Err(error) => {
+                        self.resync().await?;
+                        return Err(error);
+                    }
+                },
+            });
+        }
+        if failed {
+            self.resync().await?;
+        }
+        Ok(statuses)
+    }
+
+    /// Go back to the last sequence stored by the broker once a message failed, so that
+    /// it is published again instead of being skipped when the input is replayed
+    async fn resync(&self) -> Result<(), ProducerPublishError> {
+        let mut last_sequence = self.last_sequence.lock().await;
+        *last_sequence = stored_sequence(&self.producer).await?;
+        Ok(())
+    }
+
+    /// Sequence of the last message published, `None` before the first one
+    pub async fn last_sequence(&self) -> Option<u64> {
+        *self.last_sequence.lock().await
+    }
+
+    /// Producer publishing the messages
+    pub fn producer(&self) -> &Producer {
+        &self.producer
+    }
+
+    /// Close the producer once the pending messages are confirmed, see [`Producer::close`]
+    pub async fn close(self) -> Result<(), ProducerCloseError> {
+        self.producer.close().await
+    }
+}
+
+/// Last sequence stored by the broker for the name of the producer
+async fn stored_sequence(producer: &Producer) -> Result<Option<u64>, ClientError> {
+    // the broker answers 0 for a reference without messages
+    match producer.last_publishing_id().await? {
+        Some(0) | None => Ok(None),
+        last_sequence => Ok(last_sequence),
+    }
+}
@@ -1,17 +1,51 @@
-use crate::types::OffsetSpecification;
+use std::{collections::HashMap, convert::TryFrom, future::Future, sync::Arc, time::Duration};
+
+use crate::types::{
+    Broker, ByteCapacity, Compression, Message, OffsetSpecification, ResponseCode, StreamMetadata,
+    StreamStats,
+};
+
+use tokio::sync::broadcast;
+use tracing::trace;
+
+#[cfg(feature = "test-broker")]
+use crate::test_broker::TestBroker;
 
 use crate::{
-    client::{Client, ClientOptions},
-    consumer::ConsumerBuilder,
-    error::StreamDeleteError,
-    producer::ProducerBuilder,
+    broker_close::BrokerClosePolicy,
+    circuit_breaker::CircuitBreaker,
+    client::{Client, ClientOptions, TlsConfiguration},
+    connection_pool::{ConnectionLease, ConnectionPool},
+    consumer::{ConsumerBuilder, ConsumerOptions, StreamDeletedPolicy},
+    error::{
+        ClientError, OffsetStoreError, StreamDeleteError, StreamStatsError, SuperStreamQueryError,
+    },
+    events::{self, EventSender, StreamEvent, EVENTS_CAPACITY},
+    fault_injection::FaultInjector,
+    metrics::{MetricsCollector, StreamCounters},
+    offset_tracking::{BrokerOffsetStore, OffsetStore},
+    producer::{OverflowStrategy, ProducerBuilder, ProducerOptions},
+    recovery_backoff::RecoveryBackoff,
+    replay::ReplayBuilder,
+    replicator::ReplicatorBuilder,
+    retry_policy::{retry, NoRetry, RetryPolicy},
     stream_creator::StreamCreator,
+    super_stream_consumer::SuperStreamConsumerBuilder,
+    super_stream_creator::SuperStreamCreator,
+    super_stream_producer::SuperStreamProducerBuilder,
+    timeouts::Timeouts,
+    wire_capture::WireCapture,
+    wire_replay::WireReplay,
     RabbitMQStreamResult,
 };
 /// Main access point to a node
 #[derive(Clone)]
 pub struct Environment {
     pub(crate) options: EnvironmentOptions,
+    stream_counters: Arc<StreamCounters>,
+    pub(crate) events: EventSender,
+    producer_connections: Arc<ConnectionPool>,
+    consumer_connections: Arc<ConnectionPool>,
 }
 
 impl Environment {
@@ -19,11 +53,54 @@ impl Environment {
         EnvironmentBuilder(EnvironmentOptions::default())
     }
 
-    async fn boostrap(options: EnvironmentOptions) -> RabbitMQStreamResult<Self> {
+    async fn boostrap(mut options: EnvironmentOptions) -> RabbitMQStreamResult<Self> {
+        let stream_counters = Arc::new(StreamCounters::default());
+        options.client_options.stream_counters = Some(stream_counters.clone());
+        let events = broadcast::channel(EVENTS_CAPACITY).0;
+        options.client_options.events = Some(events.clone());
+        #[cfg(feature = "management")]
+        if options.management_discovery {
+            options.endpoints = crate::management::discover_endpoints(&options).await?;
+        }
+
         // check connection
-        let client = Client::connect(options.client_options.clone()).await?;
+        let client = connect(&options, options.client_options.clone()).await?;
         client.close().await?;
-        Ok(Environment { options })
+        Ok(Environment {
+            producer_connections: Arc::new(ConnectionPool::new(
+                options.producer_options.producers_per_connection,
+            )),
+            consumer_connections: Arc::new(ConnectionPool::new(
+                options.consumer_options.consumers_per_connection,
+            )),
+            options,
+            stream_counters,
+            events,
+        })
+    }
+
+    /// Receive the recovery, activation and topology events of the producers and consumers,
+    /// and the connections closed by the broker
+    ///
+    /// Only the events sent after the call are received, a receiver lagging behind loses
+    /// the oldest ones.
+    pub fn events(&self) -> broadcast::Receiver<StreamEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) fn emit(&self, event: StreamEvent) {
+        events::emit(&self.events, event);
+    }
+
+    /// Stream endpoints found by the [management discovery](EnvironmentBuilder::management_discovery)
+    #[cfg(feature = "management")]
+    pub fn endpoints(&self) -> &[(String, u16)] {
+        &self.options.endpoints
+    }
+
+    /// Throughput counters of the streams published to and consumed from
+    pub fn stream_counters(&self) -> &StreamCounters {
+        &self.stream_counters
     }
 
     /// Returns a builder for creating a stream with a specific configuration
@@ -31,30 +108,346 @@ impl Environment {
         StreamCreator::new(self.clone())
     }
 
+    /// Returns a builder for creating a super stream with a specific configuration
+    pub fn super_stream_creator(&self) -> SuperStreamCreator {
+        SuperStreamCreator::new(self.clone())
+    }
+
     /// Returns a builder for creating a producer
     pub fn producer(&self) -> ProducerBuilder {
         ProducerBuilder {
             environment: self.clone(),
             name: None,
+            batch_size: self.options.producer_options.batch_size,
+            batch_publishing_delay: self.options.producer_options.batch_publishing_delay,
+            confirm_timeout: self.options.client_options.timeouts.confirm,
+            close_timeout: self.options.client_options.timeouts.close,
+            sub_entry_size: 1,
+            compression: Compression::None,
+            zstd_dictionary: None,
+            max_in_flight: 10_000,
+            max_in_flight_bytes: self.options.producer_options.max_in_flight_bytes,
+            max_message_size: self.options.producer_options.max_message_size,
+            overflow_strategy: OverflowStrategy::Wait,
+            publish_error_handler: None,
+            metadata_update_handler: None,
+            filter_value_extractor: None,
+            recovery_listener: None,
+            interceptors: Vec::new(),
+            time_stamping: Default::default(),
+            message_id_generator: None,
+            rate_limiter: None,
         }
     }
 
+    /// Returns a builder for creating a producer publishing to the partitions of a super stream
+    ///
+    /// `routing_key_extractor` returns the routing key of a message, which picks its partition.
+    pub fn super_stream_producer(
+        &self,
+        routing_key_extractor: impl Fn(&Message) -> String + Send + Sync + 'static,
+    ) -> SuperStreamProducerBuilder {
+        SuperStreamProducerBuilder::new(self, routing_key_extractor)
+    }
+
+    /// Returns a builder for creating a consumer of every partition of a super stream
+    pub fn super_stream_consumer(&self) -> SuperStreamConsumerBuilder {
+        SuperStreamConsumerBuilder::new(self)
+    }
+
     /// Returns a builder for creating a consumer
     pub fn consumer(&self) -> ConsumerBuilder {
         ConsumerBuilder {
             environment: self.clone(),
-            offset_specification: OffsetSpecification::Next,
+            name: None,
+            offset_specification: self.options.consumer_options.offset_specification.clone(),
+            initial_credits: self.options.consumer_options.initial_credits,
+            buffer_size: self.options.consumer_options.buffer_size,
+            max_buffered_bytes: self.options.consumer_options.max_buffered_bytes,
+            credit_strategy: Default::default(),
+            auto_offset_tracking: None,
+            single_active_consumer: false,
+            super_stream: None,
+            filter: None,
+            match_unfiltered: false,
+            consumer_update_listener: None,
+            offset_store: None,
+            recovery_listener: None,
+            poison_messages: None,
+            ordered_completions: false,
+            interceptors: Vec::new(),
+            stored_offset_fallback: None,
+            stream_deleted: StreamDeletedPolicy::default(),
+            slow_consumer: Default::default(),
+            located: None,
         }
     }
     pub(crate) async fn create_client(&self) -> RabbitMQStreamResult<Client> {
-        Client::connect(self.options.client_options.clone()).await
+        connect(&self.options, self.options.client_options.clone()).await
+    }
+
+    /// Send `request` on a connection opened for it, attempted again as the
+    /// [retry policy](EnvironmentBuilder::retry_policy) allows
+    pub(crate) async fn with_client<T, F, Fut>(&self, request: F) -> RabbitMQStreamResult<T>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = RabbitMQStreamResult<T>>,
+    {
+        let request = &request;
+        retry(&*self.options.retry_policy, move || async move {
+            let client = self.create_client().await?;
+            let response = request(client.clone()).await;
+            // the request is not sent again when only the close failed
+            if let Err(error) = client.close().await {
+                trace!(?error, "Failed to close the request connection");
+            }
+            match response {
+                // the node is not at fault for a name refused before sending it
+                Err(error @ ClientError::InvalidName { .. }) => Err(error),
+                Err(error) => {
+                    if let Some(breaker) = &self.options.circuit_breaker {
+                        breaker.record_failure(&client.node());
+                    }
+                    Err(error)
+                }
+                response => response,
+            }
+        })
+        .await
+    }
+
+    /// Connect a client receiving the chunks delivered to its subscriptions undecoded
+    pub(crate) async fn create_raw_chunk_client(&self) -> RabbitMQStreamResult<Client> {
+        self.connect_node(None, true).await
+    }
+
+    /// Connect to `node`, or to the endpoints of the environment when it cannot be reached,
+    /// e.g. behind a load balancer when the broker advertises host names of its own network
+    async fn connect_node(
+        &self,
+        node: Option<&Broker>,
+        raw_chunks: bool,
+    ) -> RabbitMQStreamResult<Client> {
+        let mut options = self.options.client_options.clone();
+        options.raw_chunks = raw_chunks;
+        if let Some(Broker { host, port }) = node {
+            if let Ok(port) = u16::try_from(*port) {
+                let nodes = vec![(host.clone(), port)];
+                match connect_to(&self.options, options.clone(), nodes).await {
+                    Ok(client) => return Ok(client),
+                    Err(error) => trace!(?error, host, port, "Connecting to the endpoints"),
+                }
+            }
+        }
+        connect(&self.options, options).await
+    }
+
+    /// Leader and replicas of `stream`, `Err` with the code of the broker when it is not
+    /// available
+    pub(crate) async fn locate(
+        &self,
+        stream: &str,
+    ) -> RabbitMQStreamResult<Result<StreamMetadata, ResponseCode>> {
+        let mut metadata = self.stream_metadata(vec![stream.to_owned()]).await?;
+        Ok(match metadata.remove(stream) {
+            Some(metadata) if metadata.response_code == ResponseCode::Ok => Ok(metadata),
+            Some(metadata) => Err(metadata.response_code),
+            None => Err(ResponseCode::StreamDoesNotExist),
+        })
+    }
+
+    /// Lease a publisher id, `id` when given, on a connection to `node` shared by the
+    /// producers, on one to the endpoints of the environment without `node`
+    pub(crate) async fn producer_connection(
+        &self,
+        node: Option<&Broker>,
+        id: Option<u8>,
+    ) -> RabbitMQStreamResult<ConnectionLease> {
+        self.producer_connections
+            .acquire(node.map(node_name), false, id, || {
+                self.connect_node(node, false)
+            })
+            .await
+    }
+
+    /// Lease a subscription id, `id` when given, on a connection to `node` shared by the
+    /// consumers, on one to the endpoints of the environment without `node`
+    pub(crate) async fn consumer_connection(
+        &self,
+        node: Option<&Broker>,
+        raw_chunks: bool,
+        id: Option<u8>,
+    ) -> RabbitMQStreamResult<ConnectionLease> {
+        self.consumer_connections
+            .acquire(node.map(node_name), raw_chunks, id, || {
+                self.connect_node(node, raw_chunks)
+            })
+            .await
+    }
+
+    /// Close the connections of the producers and consumers one at a time, `interval`
+    /// apart, returns the number of connections cycled
+    ///
+    /// The producers and consumers recover on new connections, opened with the current TLS
+    /// certificates, e.g. once rotated in a [`CertificateStore`](crate::CertificateStore).
+    /// Spreading the closes keeps the connections from all reconnecting at once.
+    pub async fn cycle_connections(&self, interval: Duration) -> usize {
+        let mut connections = self.producer_connections.retire();
+        connections.extend(self.consumer_connections.retire());
+
+        for (index, client) in connections.iter().enumerate() {
+            if index > 0 {
+                tokio::time::sleep(interval).await;
+            }
+            // the connection is gone as well when the close fails
+            let _ = client.close().await;
+        }
+        connections.len()
+    }
+
+    /// Check if a stream exists
+    pub async fn stream_exists(&self, stream: &str) -> RabbitMQStreamResult<bool> {
+        let metadata = self.stream_metadata(vec![stream.to_owned()]).await?;
+
+        Ok(metadata
+            .get(stream)
+            .map(|metadata| metadata.response_code == ResponseCode::Ok)
+            .unwrap_or(false))
+    }
+
+    /// Query leader and replicas of the given streams
+    ///
+    /// Streams that do not exist are not part of the result.
+    pub async fn stream_metadata(
+        &self,
+        streams: Vec<String>,
+    ) -> RabbitMQStreamResult<HashMap<String, StreamMetadata>> {
+        let streams = &streams;
+        self.with_client(|client| async move { client.metadata(streams.clone()).await })
+            .await
+    }
+
+    /// Returns a builder for replaying the messages of `stream` written in a time range
+    ///
+    /// Unlike a [`crate::Consumer`], the replay ends instead of waiting for new messages.
+    pub fn replay(&self, stream: &str) -> ReplayBuilder {
+        ReplayBuilder::new(self.clone(), stream)
+    }
+
+    /// Returns a builder for copying the messages of `stream` to another stream
+    pub fn replicator(&self, stream: &str) -> ReplicatorBuilder {
+        ReplicatorBuilder::new(self.clone(), stream)
+    }
+
+    /// Client of the management HTTP API of the node, on the port set with
+    /// [`EnvironmentBuilder::management_port`] and with the credentials of the environment
+    #[cfg(feature = "management")]
+    pub fn management(&self) -> Result<crate::ManagementClient, crate::error::ManagementError> {
+        crate::ManagementClient::new(&self.options)
+    }
+
+    /// Query first and committed offsets of a stream
+    ///
+    /// Requires RabbitMQ 3.11 or later.
+    pub async fn stream_stats(&self, stream: &str) -> Result<StreamStats, StreamStatsError> {
+        let client = self.create_client().await?;
+        let response = client.stream_stats(stream).await?;
+        client.close().await?;
+
+        if response.is_ok() {
+            Ok(StreamStats::new(response.stats))
+        } else {
+            Err(StreamStatsError::Stats {
+                stream: stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
+    /// Query the offset stored by the consumer `reference` on `stream`, `None` if it stored none
+    pub async fn query_offset(
+        &self,
+        stream: &str,
+        reference: &str,
+    ) -> Result<Option<u64>, OffsetStoreError> {
+        self.with_client(|client| async move {
+            match BrokerOffsetStore::new(client).load(reference, stream).await {
+                Err(OffsetStoreError::Client(error)) => Err(error),
+                offset => Ok(offset),
+            }
+        })
+        .await?
+    }
+
+    /// Store `offset` as the last one processed by the consumer `reference` on `stream`
+    ///
+    /// A consumer resumes after it, a running consumer overwrites it with its own offsets. The
+    /// broker does not answer: a missing stream is only reported by [`Environment::query_offset`].
+    pub async fn store_offset(
+        &self,
+        stream: &str,
+        reference: &str,
+        offset: u64,
+    ) -> Result<(), OffsetStoreError> {
+        let client = self.create_client().await?;
+        BrokerOffsetStore::new(client.clone())
+            .store(reference, stream, offset)
+            .await?;
+        client.close().await?;
+        Ok(())
+    }
+
+    /// Query the partitions of a super stream, in binding order
+    pub async fn query_partitions(
+        &self,
+        super_stream: &str,
+    ) -> Result<Vec<String>, SuperStreamQueryError> {
+        let response = self
+            .with_client(|client| async move { client.partitions(super_stream).await })
+            .await?;
+
+        if response.is_ok() {
+            Ok(response.streams)
+        } else {
+            Err(SuperStreamQueryError::Query {
+                super_stream: super_stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
+    /// Query the partitions a routing key is bound to in a super stream
+    ///
+    /// An empty list means no partition matches the routing key.
+    pub async fn query_route(
+        &self,
+        super_stream: &str,
+        routing_key: &str,
+    ) -> Result<Vec<String>, SuperStreamQueryError> {
+        let response = self
+            .with_client(|client| async move { client.route(routing_key, super_stream).await })
+            .await?;
+
+        if response.is_ok() {
+            Ok(response.streams)
+        } else {
+            Err(SuperStreamQueryError::Query {
+                super_stream: super_stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
     }
 
     /// Delete a stream
     pub async fn delete_stream(&self, stream: &str) -> Result<(), StreamDeleteError> {
-        let response = self.create_client().await?.delete_stream(stream).await?;
+        let client = self.create_client().await?;
+        let response = client.delete_stream(stream).await?;
+        client.close().await?;
 
         if response.is_ok() {
+            self.emit(StreamEvent::StreamDeleted {
+                stream: stream.to_owned(),
+            });
             Ok(())
         } else {
             Err(StreamDeleteError::Delete {
@@ -63,6 +456,31 @@ impl Environment {
             })
         }
     }
+
+    /// Delete a super stream and all its partitions
+    pub async fn delete_super_stream(&self, super_stream: &str) -> Result<(), StreamDeleteError> {
+        let client = self.create_client().await?;
+
+        #[cfg(feature = "management")]
+        if crate::management::is_required(&client.server_properties().await) {
+            client.close().await?;
+            return crate::management::delete_super_stream(&self.options, super_stream)
+                .await
+                .map_err(StreamDeleteError::from);
+        }
+
+        let response = client.delete_super_stream(super_stream).await?;
+        client.close().await?;
+
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(StreamDeleteError::Delete {
+                stream: super_stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
 }
 
 /// Builder for [`Environment`]
@@ -96,16 +514,309 @@ impl EnvironmentBuilder {
         self.0.client_options.port = port;
         self
     }
+
+    /// Timeouts of the connections, requests and producers, see [`Timeouts`]
+    ///
+    /// The producers override the confirm and close timeouts with
+    /// [`ProducerBuilder::confirm_timeout`] and [`ProducerBuilder::close_timeout`].
+    pub fn timeouts(mut self, timeouts: Timeouts) -> EnvironmentBuilder {
+        self.0.client_options.timeouts = timeouts;
+        self
+    }
+
+    /// Connect to the broker using TLS, see [`TlsConfiguration`]
+    pub fn tls(mut self, tls_configuration: TlsConfiguration) -> EnvironmentBuilder {
+        self.0.client_options.tls = tls_configuration;
+        self
+    }
+
+    /// Report the events of the connections, producers and consumers to `collector`
+    pub fn metrics_collector(
+        mut self,
+        collector: impl MetricsCollector + 'static,
+    ) -> EnvironmentBuilder {
+        self.0.client_options.metrics_collector = Some(Arc::new(collector));
+        self
+    }
+
+    /// Record the frames of every connection with `capture`, to replay them offline with
+    /// [`crate::types::CaptureReader`] when reporting a protocol issue
+    pub fn wire_capture(mut self, capture: WireCapture) -> EnvironmentBuilder {
+        self.0.client_options.wire_capture = Some(capture);
+        self
+    }
+
+    /// Play the connections recorded by a [`WireCapture`] instead of connecting to a
+    /// RabbitMQ node, to pin a protocol issue in a regression test
+    pub fn wire_replay(mut self, replay: WireReplay) -> EnvironmentBuilder {
+        self.0.client_options.wire_replay = Some(replay);
+        self
+    }
+
+    /// Connect to the in-memory `broker` instead of a RabbitMQ node, for the tests
+    #[cfg(feature = "test-broker")]
+    pub fn test_broker(mut self, broker: TestBroker) -> EnvironmentBuilder {
+        self.0.client_options.test_broker = Some(broker);
+        self
+    }
+
+    /// Apply the faults of `injector` to the frames of every connection, to test how the
+    /// application copes with lost, late or broken frames and connections
+    pub fn fault_injector(mut self, injector: FaultInjector) -> EnvironmentBuilder {
+        self.0.client_options.fault_injector = Some(injector);
+        self
+    }
+
+    /// Check the CRC32 of every chunk delivered to the consumers before decoding it, a
+    /// chunk corrupted on the way failing with [`DecodeError::ChunkCrcMismatch`]
+    ///
+    /// Disabled by default, TCP already detects most transmission errors.
+    ///
+    /// [`DecodeError::ChunkCrcMismatch`]: rabbitmq_stream_protocol::error::DecodeError::ChunkCrcMismatch
+    pub fn check_chunk_crc(mut self, enabled: bool) -> EnvironmentBuilder {
+        self.0.client_options.check_crc = enabled;
+        self
+    }
+
+    /// Delay between the attempts to recover the producers and consumers
+    pub fn recovery_backoff(mut self, backoff: RecoveryBackoff) -> EnvironmentBuilder {
+        self.0.recovery_policy = Arc::new(backoff);
+        self
+    }
+
+    /// Attempts to recover the producers and consumers, a [`RecoveryBackoff`] by default
+    ///
+    /// A producer or consumer whose recovery gives up is closed, its pending messages fail.
+    pub fn recovery_policy(mut self, policy: impl RetryPolicy + 'static) -> EnvironmentBuilder {
+        self.0.recovery_policy = Arc::new(policy);
+        self
+    }
+
+    /// Skip for a while the nodes whose connections or requests keep failing, every node is
+    /// attempted by default
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> EnvironmentBuilder {
+        self.0.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// What the producers and consumers do once the broker closed their connection, e.g.
+    /// when its node is drained, [`BrokerClosePolicy::Recover`] by default
+    pub fn on_broker_close(mut self, policy: BrokerClosePolicy) -> EnvironmentBuilder {
+        self.0.broker_close = policy;
+        self
+    }
+
+    /// Attempts of the metadata lookups, offset queries and stream creations of the
+    /// environment, [`NoRetry`] by default
+    ///
+    /// Only the client errors, e.g. a failed connection, are attempted again, not the
+    /// errors answered by the broker like a stream that exists already.
+    pub fn retry_policy(mut self, policy: impl RetryPolicy + 'static) -> EnvironmentBuilder {
+        self.0.retry_policy = Arc::new(policy);
+        self
+    }
+
+    /// Default maximum number of messages per publish frame for every producer
+    pub fn producer_batch_size(mut self, batch_size: usize) -> EnvironmentBuilder {
+        self.0.producer_options.batch_size = batch_size;
+        self
+    }
+
+    /// Default maximum time a message waits in the accumulator of every producer
+    pub fn producer_batch_publishing_delay(
+        mut self,
+        batch_publishing_delay: Duration,
+    ) -> EnvironmentBuilder {
+        self.0.producer_options.batch_publishing_delay = batch_publishing_delay;
+        self
+    }
+
+    /// Default confirmation timeout for every producer, see [`Timeouts::confirm`]
+    pub fn producer_confirm_timeout(mut self, confirm_timeout: Duration) -> EnvironmentBuilder {
+        self.0.client_options.timeouts.confirm = Some(confirm_timeout);
+        self
+    }
+
+    /// Default maximum encoded size of the messages of every producer, see
+    /// [`ProducerBuilder::max_message_size`]
+    pub fn producer_max_message_size(
+        mut self,
+        max_message_size: ByteCapacity,
+    ) -> EnvironmentBuilder {
+        self.0.producer_options.max_message_size = Some(max_message_size.bytes() as usize);
+        self
+    }
+
+    /// Most producers sharing a connection, another connection is opened once they are
+    /// all full
+    ///
+    /// Defaults to 1, a connection per producer, and is at most 255.
+    pub fn producers_per_connection(mut self, producers: usize) -> EnvironmentBuilder {
+        self.0.producer_options.producers_per_connection = producers;
+        self
+    }
+
+    /// Most consumers sharing a connection, another connection is opened once they are
+    /// all full
+    ///
+    /// Defaults to 1, a connection per consumer, and is at most 255. A consumer slow to
+    /// take its messages delays the other consumers of its connection.
+    pub fn consumers_per_connection(mut self, consumers: usize) -> EnvironmentBuilder {
+        self.0.consumer_options.consumers_per_connection = consumers;
+        self
+    }
+
+    /// Default maximum encoded size of the unconfirmed messages of every producer, see
+    /// [`ProducerBuilder::max_in_flight_bytes`]
+    pub fn producer_max_in_flight_bytes(
+        mut self,
+        max_in_flight_bytes: ByteCapacity,
+    ) -> EnvironmentBuilder {
+        self.0.producer_options.max_in_flight_bytes = Some(max_in_flight_bytes.bytes() as usize);
+        self
+    }
+
+    /// Default initial credits for every consumer
+    pub fn consumer_initial_credits(mut self, initial_credits: u16) -> EnvironmentBuilder {
+        self.0.consumer_options.initial_credits = initial_credits;
+        self
+    }
+
+    /// Default size of the buffer of every consumer, see [`ConsumerBuilder::buffer_size`]
+    pub fn consumer_buffer_size(mut self, buffer_size: usize) -> EnvironmentBuilder {
+        self.0.consumer_options.buffer_size = buffer_size;
+        self
+    }
+
+    /// Default maximum encoded size of the deliveries buffered by every consumer, see
+    /// [`ConsumerBuilder::max_buffered_bytes`]
+    pub fn consumer_max_buffered_bytes(
+        mut self,
+        max_buffered_bytes: ByteCapacity,
+    ) -> EnvironmentBuilder {
+        self.0.consumer_options.max_buffered_bytes = Some(max_buffered_bytes.bytes() as usize);
+        self
+    }
+
+    /// Default offset specification for every consumer
+    pub fn consumer_offset(
+        mut self,
+        offset_specification: OffsetSpecification,
+    ) -> EnvironmentBuilder {
+        self.0.consumer_options.offset_specification = offset_specification;
+        self
+    }
+
+    /// Port of the management plugin, used by [`Environment::management`] and for super
+    /// streams on brokers older than 3.13
+    ///
+    /// Defaults to 15672.
+    #[cfg(feature = "management")]
+    pub fn management_port(mut self, management_port: u16) -> EnvironmentBuilder {
+        self.0.management_port = Some(management_port);
+        self
+    }
+
+    /// Discover the stream listeners of the cluster through the management API when
+    /// building the environment, and connect to them instead of the stream port of `host`
+    ///
+    /// Only the management API of `host` needs to be reachable beforehand, like when the
+    /// platform exposes the HTTP endpoint of the cluster alone. The connections go to the
+    /// first endpoint accepting them, in the order of the nodes.
+    #[cfg(feature = "management")]
+    pub fn management_discovery(mut self, enabled: bool) -> EnvironmentBuilder {
+        self.0.management_discovery = enabled;
+        self
+    }
 }
 #[derive(Clone)]
 pub struct EnvironmentOptions {
     pub(crate) client_options: ClientOptions,
+    pub(crate) producer_options: ProducerOptions,
+    pub(crate) consumer_options: ConsumerOptions,
+    /// Reconnections of the producers and consumers
+    pub(crate) recovery_policy: Arc<dyn RetryPolicy>,
+    /// Requests of the environment
+    pub(crate) retry_policy: Arc<dyn RetryPolicy>,
+    #[cfg(feature = "management")]
+    pub(crate) management_port: Option<u16>,
+    #[cfg(feature = "management")]
+    pub(crate) management_discovery: bool,
+    /// Stream endpoints to connect to in place of the host and port of the client options
+    pub(crate) endpoints: Vec<(String, u16)>,
+    pub(crate) circuit_breaker: Option<CircuitBreaker>,
+    pub(crate) broker_close: BrokerClosePolicy,
 }
 
 impl Default for EnvironmentOptions {
     fn default() -> Self {
-        Self {
-            client_options: ClientOptions::default(),
+        EnvironmentOptions {
+            client_options: Default::default(),
+            producer_options: Default::default(),
+            consumer_options: Default::default(),
+            recovery_policy: Arc::new(RecoveryBackoff::new()),
+            retry_policy: Arc::new(NoRetry),
+            #[cfg(feature = "management")]
+            management_port: None,
+            #[cfg(feature = "management")]
+            management_discovery: false,
+            endpoints: Vec::new(),
+            circuit_breaker: None,
+            broker_close: BrokerClosePolicy::default(),
         }
     }
 }
+
+/// Connect to the first endpoint accepting the connection, or to the host of the options
+///
+/// The nodes whose circuit is open are skipped.
+async fn connect(
+    options: &EnvironmentOptions,
+    client_options: ClientOptions,
+) -> RabbitMQStreamResult<Client> {
+    let nodes = match options.endpoints.is_empty() {
+        true => vec![(client_options.host.clone(), client_options.port)],
+        false => options.endpoints.clone(),
+    };
+    connect_to(options, client_options, nodes).await
+}
+
+/// Connect to the first of `nodes` reachable and allowed by the circuit breaker
+async fn connect_to(
+    options: &EnvironmentOptions,
+    client_options: ClientOptions,
+    nodes: Vec<(String, u16)>,
+) -> RabbitMQStreamResult<Client> {
+    let breaker = options.circuit_breaker.as_ref();
+    let mut last_error = None;
+    for (host, port) in nodes {
+        let node = format!("{}:{}", host, port);
+        if breaker.is_some_and(|breaker| !breaker.allow(&node)) {
+            last_error.get_or_insert(ClientError::CircuitOpen { node });
+            continue;
+        }
+        let mut client_options = client_options.clone();
+        client_options.host = host;
+        client_options.port = port;
+        match Client::connect(client_options).await {
+            Ok(client) => {
+                if let Some(breaker) = breaker {
+                    breaker.record_success(&node);
+                }
+                return Ok(client);
+            }
+            Err(error) => {
+                if let Some(breaker) = breaker {
+                    breaker.record_failure(&node);
+                }
+                last_error = Some(error);
+            }
+        }
+    }
+    Err(last_error.expect("one node at least"))
+}
+
+/// Key of the pooled connections to `node`
+fn node_name(node: &Broker) -> String {
+    format!("{}:{}", node.host, node.port)
+}
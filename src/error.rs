@@ -4,6 +4,10 @@ use rabbitmq_stream_protocol::{
 };
 use thiserror::Error;
 
+use crate::{body_codec::CodecError, interceptor::InterceptorError};
+
+pub use rabbitmq_stream_protocol::error::BodyError;
+
 #[derive(Error, Debug)]
 pub enum ClientError {
     #[error(transparent)]
@@ -17,6 +21,36 @@ pub enum ClientError {
     GenericError(#[from] Box<dyn std::error::Error + Send + Sync>),
     #[error("Client already closed")]
     AlreadyClosed,
+    /// The broker answered a step of the connection handshake with an error
+    #[error("Connection handshake failed at {step}: {code:?}")]
+    Handshake {
+        step: &'static str,
+        code: ResponseCode,
+    },
+    #[error("Connection closed by the broker {code:?}: {reason}")]
+    ClosedByBroker { code: ResponseCode, reason: String },
+    #[error(transparent)]
+    Tls(#[from] tokio_rustls::rustls::Error),
+    /// The circuits of the nodes are open, see [`crate::types::CircuitBreaker`]
+    #[error("Circuit of node {node} is open")]
+    CircuitOpen { node: String },
+    /// FIPS is required and the TLS configuration is not FIPS compliant
+    #[error("TLS configuration is not FIPS compliant")]
+    TlsNotFips,
+    /// The broker did not answer in time, see [`crate::types::Timeouts`]
+    #[error("{operation} timed out after {timeout:?}")]
+    Timeout {
+        operation: &'static str,
+        timeout: std::time::Duration,
+    },
+    /// A stream, super stream or virtual host name the broker would refuse, checked before
+    /// sending it
+    #[error("Invalid {kind} name {name:?}: {reason}")]
+    InvalidName {
+        kind: &'static str,
+        name: String,
+        reason: &'static str,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -27,6 +61,90 @@ pub enum ProtocolError {
     Decode(DecodeError),
 }
 
+impl ProtocolError {
+    /// Frames failing to encode or decode fail the same way again, never retryable
+    pub fn is_retryable(&self) -> bool {
+        false
+    }
+}
+
+impl ClientError {
+    /// Whether the operation may succeed when attempted again, e.g. on a new connection
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Io(_)
+            | ClientError::AlreadyClosed
+            | ClientError::ClosedByBroker { .. }
+            | ClientError::CircuitOpen { .. }
+            | ClientError::Timeout { .. } => true,
+            ClientError::Handshake { code, .. } => is_retryable_code(code),
+            ClientError::GenericError(error) => is_retryable_source(&**error),
+            ClientError::Protocol(_)
+            | ClientError::CastError(_)
+            | ClientError::Tls(_)
+            | ClientError::TlsNotFips
+            | ClientError::InvalidName { .. } => false,
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ClientError::Handshake { code, .. } | ClientError::ClosedByBroker { code, .. } => {
+                Some(code)
+            }
+            ClientError::GenericError(error) => source_response_code(&**error),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an error code of the broker may go away, when the stream has no leader or after
+/// an internal error
+fn is_retryable_code(code: &ResponseCode) -> bool {
+    matches!(
+        code,
+        ResponseCode::StreamNotAvailable | ResponseCode::InternalError
+    )
+}
+
+/// Classify the error wrapped in a [`ClientError::GenericError`], the unknown ones are not
+/// retryable
+fn is_retryable_source(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(error) = error.downcast_ref::<ClientError>() {
+        error.is_retryable()
+    } else if let Some(error) = error.downcast_ref::<ProducerCreateError>() {
+        error.is_retryable()
+    } else if let Some(error) = error.downcast_ref::<ProducerPublishError>() {
+        error.is_retryable()
+    } else if let Some(error) = error.downcast_ref::<ProducerCloseError>() {
+        error.is_retryable()
+    } else if let Some(error) = error.downcast_ref::<ConsumerCreateError>() {
+        error.is_retryable()
+    } else {
+        error.downcast_ref::<std::io::Error>().is_some()
+    }
+}
+
+/// Code of the error wrapped in a [`ClientError::GenericError`]
+fn source_response_code<'a>(
+    error: &'a (dyn std::error::Error + 'static),
+) -> Option<&'a ResponseCode> {
+    if let Some(error) = error.downcast_ref::<ClientError>() {
+        error.response_code()
+    } else if let Some(error) = error.downcast_ref::<ProducerCreateError>() {
+        error.response_code()
+    } else if let Some(error) = error.downcast_ref::<ProducerPublishError>() {
+        error.response_code()
+    } else if let Some(error) = error.downcast_ref::<ProducerCloseError>() {
+        error.response_code()
+    } else {
+        error
+            .downcast_ref::<ConsumerCreateError>()
+            .and_then(ConsumerCreateError::response_code)
+    }
+}
+
 impl From<EncodeError> for ClientError {
     fn from(err: EncodeError) -> Self {
         ClientError::Protocol(ProtocolError::Encode(err))
@@ -46,10 +164,37 @@ pub enum StreamCreateError {
         stream: String,
         status: ResponseCode,
     },
+    #[error("Invalid {argument} {value} for stream {stream}, expected {expected}")]
+    InvalidArgument {
+        stream: String,
+        argument: &'static str,
+        value: String,
+        expected: &'static str,
+    },
     #[error(transparent)]
     Client(#[from] ClientError),
 }
 
+impl StreamCreateError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            StreamCreateError::Create { status, .. } => is_retryable_code(status),
+            StreamCreateError::InvalidArgument { .. } => false,
+            StreamCreateError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            StreamCreateError::Create { status, .. } => Some(status),
+            StreamCreateError::InvalidArgument { .. } => None,
+            StreamCreateError::Client(error) => error.response_code(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum StreamDeleteError {
     #[error("Failed to delete stream {stream} status: {status:?}")]
@@ -61,6 +206,82 @@ pub enum StreamDeleteError {
     Client(#[from] ClientError),
 }
 
+impl StreamDeleteError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            StreamDeleteError::Delete { status, .. } => is_retryable_code(status),
+            StreamDeleteError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            StreamDeleteError::Delete { status, .. } => Some(status),
+            StreamDeleteError::Client(error) => error.response_code(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StreamStatsError {
+    #[error("Failed to get stats for stream {stream} status: {status:?}")]
+    Stats {
+        stream: String,
+        status: ResponseCode,
+    },
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+impl StreamStatsError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            StreamStatsError::Stats { status, .. } => is_retryable_code(status),
+            StreamStatsError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            StreamStatsError::Stats { status, .. } => Some(status),
+            StreamStatsError::Client(error) => error.response_code(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SuperStreamQueryError {
+    #[error("Failed to query super stream {super_stream} status: {status:?}")]
+    Query {
+        super_stream: String,
+        status: ResponseCode,
+    },
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+impl SuperStreamQueryError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SuperStreamQueryError::Query { status, .. } => is_retryable_code(status),
+            SuperStreamQueryError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            SuperStreamQueryError::Query { status, .. } => Some(status),
+            SuperStreamQueryError::Client(error) => error.response_code(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ProducerCreateError {
     #[error("Failed to create producer for stream {stream} status {status:?}")]
@@ -68,10 +289,32 @@ pub enum ProducerCreateError {
         stream: String,
         status: ResponseCode,
     },
+    #[error("A deduplicating producer needs a name")]
+    NameMissing,
     #[error(transparent)]
     Client(#[from] ClientError),
 }
 
+impl ProducerCreateError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProducerCreateError::Create { status, .. } => is_retryable_code(status),
+            ProducerCreateError::NameMissing => false,
+            ProducerCreateError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ProducerCreateError::Create { status, .. } => Some(status),
+            ProducerCreateError::NameMissing => None,
+            ProducerCreateError::Client(error) => error.response_code(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ProducerPublishError {
     #[error("Failed to publish message for stream {stream} status {status:?}")]
@@ -82,9 +325,124 @@ pub enum ProducerPublishError {
     },
     #[error("Failed to publish message, the producer is closed")]
     Closed,
+    #[error("Failed to publish message {publishing_id} for stream {stream}: {reason}")]
+    Publish {
+        stream: String,
+        publishing_id: u64,
+        reason: String,
+    },
+    #[error("Too many unconfirmed messages for stream {stream}")]
+    QueueFull { stream: String },
+    #[error("Message {publishing_id} for stream {stream} dropped by the overflow strategy")]
+    Dropped { stream: String, publishing_id: u64 },
+    #[error("No partition of super stream {super_stream} for routing key {routing_key}")]
+    NoRoute {
+        super_stream: String,
+        routing_key: String,
+    },
+    #[error("Timed out waiting confirmation of message {publishing_id} for stream {stream}")]
+    Timeout { stream: String, publishing_id: u64 },
+    #[error("Message of {size} bytes does not fit in a frame of at most {max} bytes")]
+    MessageTooLarge { size: usize, max: usize },
+    #[error("Message of {size} bytes exceeds the maximum message size of {limit} bytes")]
+    MessageSizeLimit { size: usize, limit: usize },
+    #[error("Message for stream {stream} rejected by an interceptor: {error}")]
+    Interceptor {
+        stream: String,
+        error: InterceptorError,
+    },
     #[error(transparent)]
     Client(#[from] ClientError),
 }
+
+impl ProducerPublishError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProducerPublishError::Create { status, .. } => is_retryable_code(status),
+            // the producer has room again once messages are confirmed
+            ProducerPublishError::QueueFull { .. }
+            | ProducerPublishError::Dropped { .. }
+            | ProducerPublishError::Timeout { .. } => true,
+            ProducerPublishError::Closed
+            | ProducerPublishError::Publish { .. }
+            | ProducerPublishError::NoRoute { .. }
+            | ProducerPublishError::MessageTooLarge { .. }
+            | ProducerPublishError::MessageSizeLimit { .. }
+            | ProducerPublishError::Interceptor { .. } => false,
+            ProducerPublishError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ProducerPublishError::Create { status, .. } => Some(status),
+            ProducerPublishError::Client(error) => error.response_code(),
+            _ => None,
+        }
+    }
+}
+/// Reason sent by the broker in a publish error frame
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum StreamPublishError {
+    #[error("Stream does not exist")]
+    StreamDoesNotExist,
+    #[error("Stream not available")]
+    StreamNotAvailable,
+    #[error("Publisher does not exist")]
+    PublisherDoesNotExist,
+    #[error("Access refused")]
+    AccessRefused,
+    #[error("Frame too large")]
+    FrameTooLarge,
+    #[error("Precondition failed")]
+    PreconditionFailed,
+    #[error("Internal error")]
+    InternalError,
+    #[error("Publish error status {0:?}")]
+    Other(ResponseCode),
+}
+
+impl StreamPublishError {
+    /// Whether publishing the message again may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            StreamPublishError::StreamNotAvailable | StreamPublishError::InternalError
+        )
+    }
+
+    /// Code sent by the broker
+    pub fn response_code(&self) -> ResponseCode {
+        match self {
+            StreamPublishError::StreamDoesNotExist => ResponseCode::StreamDoesNotExist,
+            StreamPublishError::StreamNotAvailable => ResponseCode::StreamNotAvailable,
+            StreamPublishError::PublisherDoesNotExist => ResponseCode::PublisherDoesNotExist,
+            StreamPublishError::AccessRefused => ResponseCode::AccessRefused,
+            StreamPublishError::FrameTooLarge => ResponseCode::FrameTooLarge,
+            StreamPublishError::PreconditionFailed => ResponseCode::PrecoditionFailed,
+            StreamPublishError::InternalError => ResponseCode::InternalError,
+            StreamPublishError::Other(code) => code.clone(),
+        }
+    }
+}
+
+impl From<ResponseCode> for StreamPublishError {
+    fn from(code: ResponseCode) -> Self {
+        match code {
+            ResponseCode::StreamDoesNotExist => StreamPublishError::StreamDoesNotExist,
+            ResponseCode::StreamNotAvailable => StreamPublishError::StreamNotAvailable,
+            ResponseCode::PublisherDoesNotExist => StreamPublishError::PublisherDoesNotExist,
+            ResponseCode::AccessRefused => StreamPublishError::AccessRefused,
+            ResponseCode::FrameTooLarge => StreamPublishError::FrameTooLarge,
+            ResponseCode::PrecoditionFailed => StreamPublishError::PreconditionFailed,
+            ResponseCode::InternalError => StreamPublishError::InternalError,
+            code => StreamPublishError::Other(code),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ProducerCloseError {
     #[error("Failed to close producer for stream {stream} status {status:?}")]
@@ -94,10 +452,63 @@ pub enum ProducerCloseError {
     },
     #[error("Producer already closed")]
     AlreadyClosed,
+    #[error("Closed producer for stream {stream} with {unconfirmed} unconfirmed messages")]
+    Timeout { stream: String, unconfirmed: usize },
     #[error(transparent)]
     Client(#[from] ClientError),
 }
 
+impl ProducerCloseError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProducerCloseError::Close { status, .. } => is_retryable_code(status),
+            ProducerCloseError::AlreadyClosed | ProducerCloseError::Timeout { .. } => false,
+            ProducerCloseError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ProducerCloseError::Close { status, .. } => Some(status),
+            ProducerCloseError::AlreadyClosed | ProducerCloseError::Timeout { .. } => None,
+            ProducerCloseError::Client(error) => error.response_code(),
+        }
+    }
+}
+
+/// Any error of a [`Producer`](crate::Producer), to handle them in one place
+#[derive(Error, Debug)]
+pub enum ProducerError {
+    #[error(transparent)]
+    Create(#[from] ProducerCreateError),
+    #[error(transparent)]
+    Publish(#[from] ProducerPublishError),
+    #[error(transparent)]
+    Close(#[from] ProducerCloseError),
+}
+
+impl ProducerError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProducerError::Create(error) => error.is_retryable(),
+            ProducerError::Publish(error) => error.is_retryable(),
+            ProducerError::Close(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ProducerError::Create(error) => error.response_code(),
+            ProducerError::Publish(error) => error.response_code(),
+            ProducerError::Close(error) => error.response_code(),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ConsumerCreateError {
     #[error("Failed to create consumer for stream {stream} status {status:?}")]
@@ -105,8 +516,34 @@ pub enum ConsumerCreateError {
         stream: String,
         status: ResponseCode,
     },
+    #[error("Offset tracking, single active consumer and stored offsets require a consumer name")]
+    NameMissing,
     #[error(transparent)]
     Client(#[from] ClientError),
+    #[error(transparent)]
+    OffsetStore(#[from] OffsetStoreError),
+}
+
+impl ConsumerCreateError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConsumerCreateError::Create { status, .. } => is_retryable_code(status),
+            ConsumerCreateError::NameMissing => false,
+            ConsumerCreateError::Client(error) => error.is_retryable(),
+            ConsumerCreateError::OffsetStore(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ConsumerCreateError::Create { status, .. } => Some(status),
+            ConsumerCreateError::NameMissing => None,
+            ConsumerCreateError::Client(error) => error.response_code(),
+            ConsumerCreateError::OffsetStore(error) => error.response_code(),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -118,7 +555,152 @@ pub enum ConsumerDeliveryError {
     },
     #[error(transparent)]
     Client(#[from] ClientError),
+    #[error("Message at offset {offset} rejected by an interceptor: {error}")]
+    Interceptor {
+        offset: u64,
+        error: InterceptorError,
+    },
+    #[error("Failed to decode the message at offset {offset}: {error}")]
+    Decode { offset: u64, error: BodyCodecError },
+    #[error("Stream {stream} was deleted")]
+    StreamDeleted { stream: String },
+    #[cfg(feature = "serde")]
+    #[error("Failed to deserialize the message at offset {offset}: {error}")]
+    Deserialize {
+        offset: u64,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
+
+impl ConsumerDeliveryError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConsumerDeliveryError::Credit { status, .. } => is_retryable_code(status),
+            ConsumerDeliveryError::Client(error) => error.is_retryable(),
+            // the message fails the same way when delivered again
+            _ => false,
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ConsumerDeliveryError::Credit { status, .. } => Some(status),
+            ConsumerDeliveryError::Client(error) => error.response_code(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BodyCodecError {
+    #[error("No codec registered for content type {0:?}")]
+    UnknownContentType(Option<String>),
+    #[error("Failed to encode or decode a {content_type} body: {error}")]
+    Codec {
+        content_type: String,
+        error: CodecError,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum ConsumerStoreOffsetError {
+    #[error("Cannot store the offset of a consumer without a name")]
+    NameMissing,
+    #[error("Partition {0} is not consumed by this super stream consumer")]
+    PartitionNotFound(String),
+    #[error(transparent)]
+    Store(#[from] OffsetStoreError),
+}
+
+impl ConsumerStoreOffsetError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConsumerStoreOffsetError::Store(error) => error.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ConsumerStoreOffsetError::Store(error) => error.response_code(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConsumerQueryOffsetError {
+    #[error("Cannot query the offset of a consumer without a name")]
+    NameMissing,
+    #[error("Partition {0} is not consumed by this super stream consumer")]
+    PartitionNotFound(String),
+    #[error("No offset stored for consumer {name} on stream {stream}")]
+    OffsetNotFound { stream: String, name: String },
+    #[error(transparent)]
+    Store(#[from] OffsetStoreError),
+}
+
+impl ConsumerQueryOffsetError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConsumerQueryOffsetError::Store(error) => error.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ConsumerQueryOffsetError::Store(error) => error.response_code(),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned by an [`OffsetStore`](crate::types::OffsetStore)
+#[derive(Error, Debug)]
+pub enum OffsetStoreError {
+    #[error("Failed to query offset of {reference} on stream {stream} status {status:?}")]
+    QueryOffset {
+        stream: String,
+        reference: String,
+        status: ResponseCode,
+    },
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    /// The file of a [`FileOffsetStore`](crate::types::FileOffsetStore) failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl OffsetStoreError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            OffsetStoreError::QueryOffset { status, .. } => is_retryable_code(status),
+            OffsetStoreError::Client(error) => error.is_retryable(),
+            OffsetStoreError::Io(_) => false,
+            OffsetStoreError::Other(error) => is_retryable_source(&**error),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            OffsetStoreError::QueryOffset { status, .. } => Some(status),
+            OffsetStoreError::Client(error) => error.response_code(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ConsumerCloseError {
     #[error("Failed to close consumer for stream {stream} status {status:?}")]
@@ -131,3 +713,281 @@ pub enum ConsumerCloseError {
     #[error(transparent)]
     Client(#[from] ClientError),
 }
+
+impl ConsumerCloseError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConsumerCloseError::Close { status, .. } => is_retryable_code(status),
+            ConsumerCloseError::AlreadyClosed => false,
+            ConsumerCloseError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ConsumerCloseError::Close { status, .. } => Some(status),
+            ConsumerCloseError::AlreadyClosed => None,
+            ConsumerCloseError::Client(error) => error.response_code(),
+        }
+    }
+}
+
+/// Any error of a [`Consumer`](crate::Consumer), to handle them in one place
+#[derive(Error, Debug)]
+pub enum ConsumerError {
+    #[error(transparent)]
+    Create(#[from] ConsumerCreateError),
+    #[error(transparent)]
+    Delivery(#[from] ConsumerDeliveryError),
+    #[error(transparent)]
+    Close(#[from] ConsumerCloseError),
+    #[error(transparent)]
+    StoreOffset(#[from] ConsumerStoreOffsetError),
+    #[error(transparent)]
+    QueryOffset(#[from] ConsumerQueryOffsetError),
+}
+
+impl ConsumerError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ConsumerError::Create(error) => error.is_retryable(),
+            ConsumerError::Delivery(error) => error.is_retryable(),
+            ConsumerError::Close(error) => error.is_retryable(),
+            ConsumerError::StoreOffset(error) => error.is_retryable(),
+            ConsumerError::QueryOffset(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ConsumerError::Create(error) => error.response_code(),
+            ConsumerError::Delivery(error) => error.response_code(),
+            ConsumerError::Close(error) => error.response_code(),
+            ConsumerError::StoreOffset(error) => error.response_code(),
+            ConsumerError::QueryOffset(error) => error.response_code(),
+        }
+    }
+}
+
+/// Error of a [`Replicator`](crate::Replicator)
+#[derive(Error, Debug)]
+pub enum ReplicatorError {
+    #[error("Stream {0} cannot be replicated to itself")]
+    SameStream(String),
+    #[error("Message at offset {offset} of {stream} was not confirmed by the target: {status:?}")]
+    Unconfirmed {
+        stream: String,
+        offset: u64,
+        status: ResponseCode,
+    },
+    #[error(transparent)]
+    ConsumerCreate(#[from] ConsumerCreateError),
+    #[error(transparent)]
+    ConsumerDelivery(#[from] ConsumerDeliveryError),
+    #[error(transparent)]
+    ProducerCreate(#[from] ProducerCreateError),
+    #[error(transparent)]
+    ProducerPublish(#[from] ProducerPublishError),
+    #[error(transparent)]
+    ProducerClose(#[from] ProducerCloseError),
+    #[error(transparent)]
+    Client(#[from] ClientError),
+}
+
+impl ReplicatorError {
+    /// Whether the operation may succeed when attempted again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ReplicatorError::SameStream(_) => false,
+            ReplicatorError::Unconfirmed { status, .. } => is_retryable_code(status),
+            ReplicatorError::ConsumerCreate(error) => error.is_retryable(),
+            ReplicatorError::ConsumerDelivery(error) => error.is_retryable(),
+            ReplicatorError::ProducerCreate(error) => error.is_retryable(),
+            ReplicatorError::ProducerPublish(error) => error.is_retryable(),
+            ReplicatorError::ProducerClose(error) => error.is_retryable(),
+            ReplicatorError::Client(error) => error.is_retryable(),
+        }
+    }
+
+    /// Code the broker answered with, if the error comes from the broker
+    pub fn response_code(&self) -> Option<&ResponseCode> {
+        match self {
+            ReplicatorError::SameStream(_) => None,
+            ReplicatorError::Unconfirmed { status, .. } => Some(status),
+            ReplicatorError::ConsumerCreate(error) => error.response_code(),
+            ReplicatorError::ConsumerDelivery(error) => error.response_code(),
+            ReplicatorError::ProducerCreate(error) => error.response_code(),
+            ReplicatorError::ProducerPublish(error) => error.response_code(),
+            ReplicatorError::ProducerClose(error) => error.response_code(),
+            ReplicatorError::Client(error) => error.response_code(),
+        }
+    }
+}
+
+/// Difference between the traffic recorded for a
+/// [`WireReplay`](crate::types::WireReplay) and the frames the client sent
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// `expected` is empty in place of the authentication frame, it is not recorded
+    #[error("Connection {connection} sent {actual:?} instead of the recorded {expected:?}")]
+    UnexpectedFrame {
+        connection: u32,
+        expected: bytes::Bytes,
+        actual: bytes::Bytes,
+    },
+    #[error("Connection {connection} sent {actual:?} after the recorded frames")]
+    ExtraFrame {
+        connection: u32,
+        actual: bytes::Bytes,
+    },
+    #[error("Connection {connection} did not send {missing} recorded frames")]
+    MissingFrames { connection: u32, missing: usize },
+    #[error("Connection {0} was opened after the recorded ones")]
+    ExtraConnection(u32),
+    #[error("{0} recorded connections were not opened")]
+    MissingConnections(usize),
+}
+
+/// Error parsing a [`ByteCapacity`](crate::types::ByteCapacity) or a
+/// [`MaxAge`](crate::types::MaxAge)
+#[derive(Error, Debug, PartialEq)]
+pub enum RetentionParseError {
+    #[error("Invalid retention value {0}")]
+    InvalidValue(String),
+    #[error("Unknown unit {unit} in {value}, expected {expected}")]
+    UnknownUnit {
+        value: String,
+        unit: String,
+        expected: &'static str,
+    },
+}
+
+/// Error of a request to the management HTTP API, see [`ManagementClient`](crate::ManagementClient)
+#[cfg(feature = "management")]
+#[derive(Error, Debug)]
+pub enum ManagementError {
+    #[error("Invalid management address {0}")]
+    Address(String),
+    #[error("Failed to reach the management API: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("The management API answered {status} to {path}: {body}")]
+    Status {
+        path: String,
+        status: u16,
+        body: String,
+    },
+    #[error("Unexpected answer of the management API: {0}")]
+    InvalidResponse(String),
+}
+
+/// Error starting a [`RabbitMqStreamContainer`](crate::containers::RabbitMqStreamContainer)
+#[cfg(feature = "testcontainers")]
+#[derive(Error, Debug)]
+pub enum ContainerError {
+    #[error("Failed to start the container: {0}")]
+    Start(#[from] testcontainers::TestcontainersError),
+    #[error("The broker did not accept connections within {timeout:?}: {error}")]
+    NotReady {
+        timeout: std::time::Duration,
+        error: ClientError,
+    },
+}
+
+/// Error of a request to a toxiproxy server, see [`Toxiproxy`](crate::toxiproxy::Toxiproxy)
+#[cfg(feature = "toxiproxy")]
+#[derive(Error, Debug)]
+pub enum ToxiproxyError {
+    #[error("Invalid toxiproxy address {0}")]
+    Address(String),
+    #[error("Failed to reach toxiproxy: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Toxiproxy answered {status} to {url}: {body}")]
+    Status {
+        url: String,
+        status: u16,
+        body: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rabbitmq_stream_protocol::ResponseCode;
+
+    use super::{
+        ClientError, ConsumerCreateError, ConsumerError, ProducerError, ProducerPublishError,
+        StreamCreateError, StreamPublishError,
+    };
+
+    #[test]
+    fn broker_codes_test() {
+        let error = StreamCreateError::Create {
+            stream: "orders".to_owned(),
+            status: ResponseCode::StreamAlreadyExists,
+        };
+        assert!(!error.is_retryable());
+        assert_eq!(
+            Some(&ResponseCode::StreamAlreadyExists),
+            error.response_code()
+        );
+
+        let error = ProducerError::from(ProducerPublishError::Create {
+            stream: "orders".to_owned(),
+            publisher_id: 1,
+            status: ResponseCode::StreamNotAvailable,
+        });
+        assert!(error.is_retryable());
+        assert_eq!(
+            Some(&ResponseCode::StreamNotAvailable),
+            error.response_code()
+        );
+
+        let error = StreamPublishError::from(ResponseCode::PrecoditionFailed);
+        assert!(!error.is_retryable());
+        assert_eq!(ResponseCode::PrecoditionFailed, error.response_code());
+    }
+
+    #[test]
+    fn client_errors_test() {
+        let timeout = ClientError::Timeout {
+            operation: "request",
+            timeout: Duration::from_secs(1),
+        };
+        assert!(timeout.is_retryable());
+        assert_eq!(None, timeout.response_code());
+
+        let handshake = ClientError::Handshake {
+            step: "authentication",
+            code: ResponseCode::AuthenticationFailure,
+        };
+        assert!(!handshake.is_retryable());
+        let error = ConsumerError::from(ConsumerCreateError::from(handshake));
+        assert!(!error.is_retryable());
+        assert_eq!(
+            Some(&ResponseCode::AuthenticationFailure),
+            error.response_code()
+        );
+    }
+
+    #[test]
+    fn wrapped_errors_test() {
+        let wrapped = ClientError::GenericError(Box::new(ConsumerCreateError::Create {
+            stream: "orders".to_owned(),
+            status: ResponseCode::StreamNotAvailable,
+        }));
+        assert!(wrapped.is_retryable());
+        assert_eq!(
+            Some(&ResponseCode::StreamNotAvailable),
+            wrapped.response_code()
+        );
+
+        let unknown = ClientError::GenericError("unknown".into());
+        assert!(!unknown.is_retryable());
+        assert_eq!(None, unknown.response_code());
+    }
+}
@@ -0,0 +1,51 @@
+use rabbitmq_stream_protocol::ResponseCode;
+use tokio::sync::broadcast;
+
+use crate::{
+    consumer::ConsumerRecoveryEvent, producer::ProducerRecoveryEvent,
+    slow_consumer::SlowConsumerReason,
+};
+
+/// Events kept for the receivers lagging behind, older ones are dropped
+pub(crate) const EVENTS_CAPACITY: usize = 1024;
+
+/// Event of the producers and consumers of an environment, see [`crate::Environment::events`]
+///
+/// The events of a stream are sent by each of its producers and consumers.
+#[derive(Clone, Debug)]
+pub enum StreamEvent {
+    /// A producer lost its connection or recovered it
+    ProducerRecovery(ProducerRecoveryEvent),
+    /// A consumer lost its subscription or recovered it
+    ConsumerRecovery(ConsumerRecoveryEvent),
+    /// A single active consumer was activated or deactivated
+    ConsumerUpdate {
+        stream: String,
+        name: Option<String>,
+        active: bool,
+    },
+    /// A consumer does not keep up with the deliveries
+    SlowConsumer {
+        stream: String,
+        name: Option<String>,
+        reason: SlowConsumerReason,
+    },
+    /// The broker reported a change of the topology of a stream, its leader moved or it
+    /// was deleted
+    MetadataUpdate { stream: String, code: ResponseCode },
+    /// A stream was deleted with [`crate::Environment::delete_stream`]
+    StreamDeleted { stream: String },
+    /// The broker closed a connection, `node` is its host and port
+    ConnectionClosed {
+        node: String,
+        code: ResponseCode,
+        reason: String,
+    },
+}
+
+pub(crate) type EventSender = broadcast::Sender<StreamEvent>;
+
+/// Send `event` if anyone is listening
+pub(crate) fn emit(sender: &EventSender, event: StreamEvent) {
+    let _ = sender.send(event);
+}
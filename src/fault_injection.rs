@@ -0,0 +1,451 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use futures::ready;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep},
+};
+
+use crate::wire_capture::FrameDirection;
+
+/// Bytes read at once from the wrapped transport
+const READ_SIZE: usize = 8 * 1024;
+
+/// Fault applied to a frame by a [`FaultInjector`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Lose the frame
+    Drop,
+    /// Hold the frame, and the frames following it, for the duration
+    Delay(Duration),
+    /// Pass the frame twice
+    Duplicate,
+    /// Keep the first bytes of the frame after its size, the size is updated so the
+    /// following frames are read correctly
+    Truncate(usize),
+}
+
+/// Frames a [`Fault`] is applied to, see [`FaultInjector::inject`]
+#[derive(Clone, Debug)]
+pub struct FaultRule {
+    direction: FrameDirection,
+    fault: Fault,
+    key: Option<u16>,
+    skip: usize,
+    times: usize,
+}
+
+impl FaultRule {
+    /// Apply `fault` to the next frame going in `direction`
+    pub fn new(direction: FrameDirection, fault: Fault) -> Self {
+        FaultRule {
+            direction,
+            fault,
+            key: None,
+            skip: 0,
+            times: 1,
+        }
+    }
+
+    /// Only the frames with `key`, with the `0x8000` flag for the responses to the
+    /// requests of the client
+    pub fn key(mut self, key: u16) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Let the first `skip` frames through
+    pub fn skip(mut self, skip: usize) -> Self {
+        self.skip = skip;
+        self
+    }
+
+    /// Apply the fault to `times` frames, `usize::MAX` for every frame
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = times;
+        self
+    }
+
+    fn matches(&self, direction: FrameDirection, key: u16) -> bool {
+        self.direction == direction && self.key.is_none_or(|rule_key| rule_key == key)
+    }
+}
+
+/// Handle injecting faults in the connections of an environment, see
+/// [`crate::EnvironmentBuilder::fault_injector`]
+///
+/// ```
+/// use rabbitmq_stream_client::types::{Fault, FaultInjector, FaultRule, FrameDirection};
+///
+/// let faults = FaultInjector::new();
+/// // lose the second publish confirm
+/// faults.inject(
+///     FaultRule::new(FrameDirection::Inbound, Fault::Drop)
+///         .key(3)
+///         .skip(1),
+/// );
+/// ```
+///
+/// The rules are checked in the order they were added, the first one matching a frame
+/// applies and is removed once it applied the number of times it was given.
+#[derive(Clone, Default)]
+pub struct FaultInjector(Arc<Mutex<Faults>>);
+
+#[derive(Default)]
+struct Faults {
+    rules: Vec<FaultRule>,
+    /// Incremented by [`FaultInjector::sever`], the connections of a previous generation
+    /// are broken
+    generation: u64,
+    /// Readers waiting for a frame, woken up when the connections are severed
+    readers: Vec<Waker>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        FaultInjector::default()
+    }
+
+    /// Add `rule` after the rules left
+    pub fn inject(&self, rule: FaultRule) {
+        self.0.lock().unwrap().rules.push(rule);
+    }
+
+    /// Remove the rules left
+    pub fn clear(&self) {
+        self.0.lock().unwrap().rules.clear();
+    }
+
+    /// Break the open connections, their reads and writes fail from now on while the
+    /// connections opened afterwards work
+    pub fn sever(&self) {
+        let mut faults = self.0.lock().unwrap();
+        faults.generation += 1;
+        for reader in faults.readers.drain(..) {
+            reader.wake();
+        }
+    }
+
+    /// Wrap the transport of a new connection
+    pub(crate) fn wrap<S>(&self, inner: S) -> FaultyStream<S> {
+        FaultyStream {
+            inner,
+            injector: self.clone(),
+            generation: self.0.lock().unwrap().generation,
+            inbound: FrameQueue::new(FrameDirection::Inbound),
+            outbound: FrameQueue::new(FrameDirection::Outbound),
+        }
+    }
+
+    /// Fault of the frame with `key` going in `direction`
+    fn fault(&self, direction: FrameDirection, key: u16) -> Option<Fault> {
+        let mut faults = self.0.lock().unwrap();
+        let index = faults
+            .rules
+            .iter()
+            .position(|rule| rule.matches(direction, key))?;
+        let rule = &mut faults.rules[index];
+        if rule.skip > 0 {
+            rule.skip -= 1;
+            return None;
+        }
+        let fault = rule.fault;
+        if rule.times != usize::MAX {
+            rule.times -= 1;
+        }
+        if rule.times == 0 {
+            faults.rules.remove(index);
+        }
+        Some(fault)
+    }
+
+    /// Whether the connections of `generation` were severed, `reader` is woken up when
+    /// they are
+    fn severed(&self, generation: u64, reader: Option<&Waker>) -> bool {
+        let mut faults = self.0.lock().unwrap();
+        if faults.generation != generation {
+            return true;
+        }
+        if let Some(reader) = reader {
+            if !faults.readers.iter().any(|waker| waker.will_wake(reader)) {
+                faults.readers.push(reader.clone());
+            }
+        }
+        false
+    }
+}
+
+impl fmt::Debug for FaultInjector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FaultInjector")
+            .field("rules", &self.0.lock().unwrap().rules)
+            .finish()
+    }
+}
+
+/// Frames going in a direction of a [`FaultyStream`]
+struct FrameQueue {
+    direction: FrameDirection,
+    /// Start of the next frame
+    partial: BytesMut,
+    /// Frames to pass, with the time a delayed frame is held until
+    frames: VecDeque<(Option<Instant>, Bytes)>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl FrameQueue {
+    fn new(direction: FrameDirection) -> Self {
+        FrameQueue {
+            direction,
+            partial: BytesMut::new(),
+            frames: VecDeque::new(),
+            delay: None,
+        }
+    }
+
+    /// Split `bytes` in frames and apply the faults
+    fn push(&mut self, injector: &FaultInjector, bytes: &[u8]) {
+        self.partial.extend_from_slice(bytes);
+        while self.partial.len() >= 4 {
+            let size = u32::from_be_bytes([
+                self.partial[0],
+                self.partial[1],
+                self.partial[2],
+                self.partial[3],
+            ]) as usize;
+            if self.partial.len() < 4 + size {
+                break;
+            }
+            let frame = self.partial.split_to(4 + size).freeze();
+            let fault = match frame.get(4..6) {
+                Some(key) => injector.fault(self.direction, u16::from_be_bytes([key[0], key[1]])),
+                None => None,
+            };
+            match fault {
+                None => self.frames.push_back((None, frame)),
+                Some(Fault::Drop) => {}
+                Some(Fault::Delay(delay)) => {
+                    self.frames.push_back((Some(Instant::now() + delay), frame))
+                }
+                Some(Fault::Duplicate) => {
+                    self.frames.push_back((None, frame.clone()));
+                    self.frames.push_back((None, frame));
+                }
+                Some(Fault::Truncate(len)) => {
+                    let len = len.min(size);
+                    let mut truncated = BytesMut::with_capacity(4 + len);
+                    truncated.put_u32(len as u32);
+                    truncated.extend_from_slice(&frame[4..4 + len]);
+                    self.frames.push_back((None, truncated.freeze()));
+                }
+            }
+        }
+    }
+
+    /// Whether a frame can be passed, pending while a delayed frame is held
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        let deadline = match self.frames.front_mut() {
+            Some((deadline, _)) => deadline,
+            None => return Poll::Ready(false),
+        };
+        if let Some(until) = *deadline {
+            let delay = self
+                .delay
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(until)));
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+            *deadline = None;
+        }
+        Poll::Ready(true)
+    }
+
+    fn front(&self) -> &[u8] {
+        self.frames.front().map_or(&[], |(_, frame)| frame)
+    }
+
+    fn consume(&mut self, len: usize) {
+        if let Some((_, frame)) = self.frames.front_mut() {
+            frame.advance(len);
+            if frame.is_empty() {
+                self.frames.pop_front();
+            }
+        }
+    }
+}
+
+fn severed_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionReset,
+        "connection severed by the fault injector",
+    )
+}
+
+/// Transport applying the faults of a [`FaultInjector`] to the frames going through it
+pub(crate) struct FaultyStream<S> {
+    inner: S,
+    injector: FaultInjector,
+    generation: u64,
+    inbound: FrameQueue,
+    outbound: FrameQueue,
+}
+
+impl<S: AsyncWrite + Unpin> FaultyStream<S> {
+    /// Write the outbound frames ready
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        loop {
+            if !ready!(self.outbound.poll_ready(cx)) {
+                return Poll::Ready(Ok(()));
+            }
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, self.outbound.front()))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.outbound.consume(written);
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for FaultyStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.injector.severed(this.generation, Some(cx.waker())) {
+                return Poll::Ready(Err(severed_error()));
+            }
+            if ready!(this.inbound.poll_ready(cx)) {
+                let front = this.inbound.front();
+                let len = front.len().min(buf.remaining());
+                buf.put_slice(&front[..len]);
+                this.inbound.consume(len);
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut bytes = [0; READ_SIZE];
+            let mut read = ReadBuf::new(&mut bytes);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read))?;
+            if read.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            this.inbound.push(&this.injector, read.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FaultyStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.injector.severed(this.generation, None) {
+            return Poll::Ready(Err(severed_error()));
+        }
+        ready!(this.poll_drain(cx))?;
+        this.outbound.push(&this.injector, buf);
+        // what is left is written by the next write or flush
+        if let Poll::Ready(Err(error)) = this.poll_drain(cx) {
+            return Poll::Ready(Err(error));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.injector.severed(this.generation, None) {
+            return Poll::Ready(Err(severed_error()));
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{Fault, FaultInjector, FaultRule};
+    use crate::wire_capture::FrameDirection;
+
+    fn frame(key: u16, body: &[u8]) -> Vec<u8> {
+        let mut frame = ((body.len() + 4) as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&key.to_be_bytes());
+        frame.extend_from_slice(&1u16.to_be_bytes());
+        frame.extend_from_slice(body);
+        frame
+    }
+
+    #[tokio::test]
+    async fn fault_injector_test() {
+        let faults = FaultInjector::new();
+        faults.inject(
+            FaultRule::new(FrameDirection::Inbound, Fault::Drop)
+                .key(3)
+                .skip(1),
+        );
+        faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Duplicate).key(8));
+        faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Truncate(5)).key(9));
+        faults.inject(
+            FaultRule::new(
+                FrameDirection::Inbound,
+                Fault::Delay(Duration::from_millis(20)),
+            )
+            .key(10),
+        );
+
+        let (client, mut server) = tokio::io::duplex(1024);
+        let mut client = faults.wrap(client);
+        let mut sent = Vec::new();
+        for (key, body) in [(3, "a"), (3, "b"), (8, "c"), (9, "de"), (10, "f")] {
+            sent.extend(frame(key, body.as_bytes()));
+        }
+        server.write_all(&sent).await.unwrap();
+
+        let mut expected = Vec::new();
+        for (key, body) in [(3, "a"), (8, "c"), (8, "c"), (9, "d"), (10, "f")] {
+            expected.extend(frame(key, body.as_bytes()));
+        }
+        let started = std::time::Instant::now();
+        let mut received = vec![0; expected.len()];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(expected, received);
+        assert!(started.elapsed() >= Duration::from_millis(20));
+
+        // the next frames pass
+        server.write_all(&frame(3, b"g")).await.unwrap();
+        let mut received = vec![0; 9];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(frame(3, b"g"), received);
+
+        faults.sever();
+        assert!(client.read_u8().await.is_err());
+        assert!(client.write_all(&frame(3, b"h")).await.is_err());
+
+        let (opened, mut server) = tokio::io::duplex(1024);
+        let mut opened = faults.wrap(opened);
+        opened.write_all(&frame(3, b"i")).await.unwrap();
+        let mut received = vec![0; 9];
+        server.read_exact(&mut received).await.unwrap();
+        assert_eq!(frame(3, b"i"), received);
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{error::OffsetStoreError, offset_tracking::OffsetStore};
+
+/// First bytes of the file, followed by the records
+const MAGIC: &[u8; 8] = b"RMQSOFF1";
+/// Records appended before the file is rewritten with the last offsets only
+const COMPACT_AFTER: usize = 1024;
+
+/// [`OffsetStore`] appending the offsets to a local file, for the consumers which cannot
+/// keep them in the broker
+///
+/// ```no_run
+/// # async fn example(env: rabbitmq_stream_client::Environment) -> Result<(), Box<dyn std::error::Error>> {
+/// use rabbitmq_stream_client::types::{FileOffsetStore, OffsetSpecification};
+///
+/// let offsets = FileOffsetStore::open("/var/lib/billing/offsets")?;
+/// let consumer = env
+///     .consumer()
+///     .name("billing")
+///     .offset_store(offsets.clone())
+///     .start_from_stored_offset_or(OffsetSpecification::First)
+///     .build("orders")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Each stored offset is appended as a checksummed record and synced to disk before
+/// `store` returns. A record torn by a crash is dropped when the file is opened again, the
+/// previous offset of the consumer is then loaded. Once the records outnumber the offsets
+/// the file is rewritten to a temporary file renamed over it. The clones share the file,
+/// a file is opened by one store at a time.
+#[derive(Clone)]
+pub struct FileOffsetStore(Arc<Mutex<StoreFile>>);
+
+struct StoreFile {
+    path: PathBuf,
+    file: File,
+    offsets: HashMap<(String, String), u64>,
+    /// Records in the file, compacted when they reach `compact_after`
+    records: usize,
+    compact_after: usize,
+}
+
+impl FileOffsetStore {
+    /// Open the file at `path`, created when missing, and load its offsets
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OffsetStoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+
+        if content.is_empty() {
+            file.write_all(MAGIC)?;
+            file.sync_all()?;
+            content.extend_from_slice(MAGIC);
+        }
+        if !content.starts_with(MAGIC) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not an offset store", path.display()),
+            )
+            .into());
+        }
+
+        let mut offsets = HashMap::new();
+        let mut records = 0;
+        let mut position = MAGIC.len();
+        while let Some((length, reference, stream, offset)) = decode_record(&content[position..]) {
+            offsets.insert((reference, stream), offset);
+            records += 1;
+            position += length;
+        }
+        // the tail was torn by a crash while appending
+        if position < content.len() {
+            file.set_len(position as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(FileOffsetStore(Arc::new(Mutex::new(StoreFile {
+            path,
+            file,
+            offsets,
+            records,
+            compact_after: COMPACT_AFTER,
+        }))))
+    }
+}
+
+impl StoreFile {
+    fn store(&mut self, reference: &str, stream: &str, offset: u64) -> io::Result<()> {
+        let key = (reference.to_owned(), stream.to_owned());
+        if self.offsets.get(&key) == Some(&offset) {
+            return Ok(());
+        }
+        let length = self.file.metadata()?.len();
+        let appended = self
+            .file
+            .write_all(&encode_record(reference, stream, offset))
+            .and_then(|()| self.file.sync_data());
+        if let Err(error) = appended {
+            // a partial record would be followed by the next ones, and lose them on open
+            let _ = self.file.set_len(length);
+            return Err(error);
+        }
+        self.offsets.insert(key, offset);
+        self.records += 1;
+
+        if self.records >= self.compact_after.max(2 * self.offsets.len()) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the file with the last offset of each consumer
+    fn compact(&mut self) -> io::Result<()> {
+        let compacted = self.path.with_extension("compacting");
+        let mut file = File::create(&compacted)?;
+        let mut content = MAGIC.to_vec();
+        for ((reference, stream), offset) in &self.offsets {
+            content.extend(encode_record(reference, stream, *offset));
+        }
+        file.write_all(&content)?;
+        file.sync_all()?;
+        std::fs::rename(&compacted, &self.path)?;
+        if let Some(directory) = self.path.parent() {
+            // the rename is durable once the directory is synced, not supported everywhere
+            let _ = File::open(directory).and_then(|directory| directory.sync_all());
+        }
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.records = self.offsets.len();
+        Ok(())
+    }
+}
+
+/// Length, checksum, then the reference, the stream and the offset
+fn encode_record(reference: &str, stream: &str, offset: u64) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(12 + reference.len() + stream.len());
+    for value in [reference, stream] {
+        payload.extend((value.len() as u16).to_be_bytes());
+        payload.extend_from_slice(value.as_bytes());
+    }
+    payload.extend(offset.to_be_bytes());
+
+    let mut record = Vec::with_capacity(8 + payload.len());
+    record.extend((payload.len() as u32).to_be_bytes());
+    record.extend(crc32fast::hash(&payload).to_be_bytes());
+    record.extend(payload);
+    record
+}
+
+/// Record at the start of `input` with its length, `None` when it is incomplete or corrupt
+fn decode_record(input: &[u8]) -> Option<(usize, String, String, u64)> {
+    let length = u32::from_be_bytes(input.get(0..4)?.try_into().ok()?) as usize;
+    let checksum = u32::from_be_bytes(input.get(4..8)?.try_into().ok()?);
+    let payload = input.get(8..8 + length)?;
+    if crc32fast::hash(payload) != checksum {
+        return None;
+    }
+
+    let mut fields = payload;
+    let mut string = || {
+        let size = u16::from_be_bytes(fields.get(0..2)?.try_into().ok()?) as usize;
+        let value = String::from_utf8(fields.get(2..2 + size)?.to_vec()).ok()?;
+        fields = &fields[2 + size..];
+        Some(value)
+    };
+    let reference = string()?;
+    let stream = string()?;
+    let offset = u64::from_be_bytes(fields.try_into().ok()?);
+    Some((8 + length, reference, stream, offset))
+}
+
+#[async_trait::async_trait]
+impl OffsetStore for FileOffsetStore {
+    async fn load(&self, reference: &str, stream: &str) -> Result<Option<u64>, OffsetStoreError> {
+        let file = self.0.lock().unwrap();
+        Ok(file
+            .offsets
+            .get(&(reference.to_owned(), stream.to_owned()))
+            .copied())
+    }
+
+    async fn store(
+        &self,
+        reference: &str,
+        stream: &str,
+        offset: u64,
+    ) -> Result<(), OffsetStoreError> {
+        let file = self.0.clone();
+        let (reference, stream) = (reference.to_owned(), stream.to_owned());
+        tokio::task::spawn_blocking(move || {
+            file.lock().unwrap().store(&reference, &stream, offset)
+        })
+        .await
+        .map_err(|error| OffsetStoreError::Other(Box::new(error)))??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, path::PathBuf};
+
+    use super::{encode_record, FileOffsetStore};
+    use crate::offset_tracking::OffsetStore;
+
+    fn path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("offset-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn reopen_test() {
+        let path = path("reopen");
+        let store = FileOffsetStore::open(&path).unwrap();
+        assert_eq!(None, store.load("billing", "orders").await.unwrap());
+        store.store("billing", "orders", 41).await.unwrap();
+        store.store("billing", "orders", 42).await.unwrap();
+        store.store("audit", "orders", 7).await.unwrap();
+        drop(store);
+
+        let store = FileOffsetStore::open(&path).unwrap();
+        assert_eq!(Some(42), store.load("billing", "orders").await.unwrap());
+        assert_eq!(Some(7), store.load("audit", "orders").await.unwrap());
+        assert_eq!(None, store.load("billing", "invoices").await.unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn torn_record_test() {
+        let path = path("torn");
+        let store = FileOffsetStore::open(&path).unwrap();
+        store.store("billing", "orders", 41).await.unwrap();
+        drop(store);
+        // a crash in the middle of the next record
+        let record = encode_record("billing", "orders", 42);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&record[..record.len() - 3]).unwrap();
+        let length = file.metadata().unwrap().len();
+        drop(file);
+
+        let store = FileOffsetStore::open(&path).unwrap();
+        assert_eq!(Some(41), store.load("billing", "orders").await.unwrap());
+        assert!(std::fs::metadata(&path).unwrap().len() < length);
+        // the next records follow the last complete one
+        store.store("billing", "orders", 43).await.unwrap();
+        drop(store);
+        let store = FileOffsetStore::open(&path).unwrap();
+        assert_eq!(Some(43), store.load("billing", "orders").await.unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn compaction_test() {
+        let path = path("compaction");
+        let store = FileOffsetStore::open(&path).unwrap();
+        store.0.lock().unwrap().compact_after = 4;
+        for offset in 0..10 {
+            store.store("billing", "orders", offset).await.unwrap();
+        }
+        store.store("audit", "orders", 3).await.unwrap();
+        assert!(store.0.lock().unwrap().records < 4);
+        drop(store);
+
+        let store = FileOffsetStore::open(&path).unwrap();
+        assert_eq!(Some(9), store.load("billing", "orders").await.unwrap());
+        assert_eq!(Some(3), store.load("audit", "orders").await.unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn not_an_offset_store_test() {
+        let path = path("invalid");
+        std::fs::write(&path, b"something else").unwrap();
+        assert!(FileOffsetStore::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
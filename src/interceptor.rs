@@ -0,0 +1,26 @@
+use rabbitmq_stream_protocol::message::Message;
+
+pub type InterceptorError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Observes or transforms the messages of a producer before they are published
+///
+/// Interceptors run in the order they were added to the builder, each one receiving the
+/// message returned by the previous one. An error rejects the send with
+/// [`crate::error::ProducerPublishError::Interceptor`].
+pub trait ProducerInterceptor: Send + Sync {
+    fn on_send(&self, stream: &str, message: Message) -> Result<Message, InterceptorError>;
+}
+
+/// Observes or transforms the messages delivered to a consumer before the application
+///
+/// Interceptors run in the order they were added to the builder, each one receiving the
+/// message returned by the previous one. An error is returned in place of the message as
+/// [`crate::error::ConsumerDeliveryError::Interceptor`].
+pub trait ConsumerInterceptor: Send + Sync {
+    fn on_delivery(
+        &self,
+        stream: &str,
+        offset: u64,
+        message: Message,
+    ) -> Result<Message, InterceptorError>;
+}
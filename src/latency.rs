@@ -0,0 +1,179 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Buckets per power of two, the percentiles are within 1/8 of the latency
+const SUB_BUCKET_BITS: u32 = 3;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+
+/// Latencies are counted up to 2^32 microseconds, a bit more than an hour
+const MAX_MICROS: u64 = (1 << 32) - 1;
+const BUCKETS: usize = (32 - SUB_BUCKET_BITS as usize + 1) * SUB_BUCKETS;
+
+/// Distribution of the confirm latencies of a producer
+pub(crate) struct LatencyHistogram {
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl LatencyHistogram {
+    pub(crate) fn new() -> Self {
+        LatencyHistogram {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, latency: Duration) {
+        let micros = (latency.as_micros() as u64).min(MAX_MICROS);
+        self.buckets[bucket(micros)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+        self.min.fetch_min(micros, Ordering::Relaxed);
+        self.max.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LatencySnapshot {
+        let buckets: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        LatencySnapshot {
+            count: buckets.iter().sum(),
+            sum: self.sum.load(Ordering::Relaxed),
+            min: self.min.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+            buckets,
+        }
+    }
+}
+
+fn bucket(micros: u64) -> usize {
+    if micros < SUB_BUCKETS as u64 {
+        return micros as usize;
+    }
+    let magnitude = 63 - micros.leading_zeros();
+    let sub_bucket = (micros >> (magnitude - SUB_BUCKET_BITS)) as usize & (SUB_BUCKETS - 1);
+    (magnitude - SUB_BUCKET_BITS + 1) as usize * SUB_BUCKETS + sub_bucket
+}
+
+/// Smallest latency of the bucket, in microseconds
+fn bucket_start(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let magnitude = (bucket / SUB_BUCKETS) as u32 + SUB_BUCKET_BITS - 1;
+    ((SUB_BUCKETS + bucket % SUB_BUCKETS) as u64) << (magnitude - SUB_BUCKET_BITS)
+}
+
+/// Confirm latencies of a producer since its creation, see
+/// [`crate::Producer::latency_snapshot`]
+///
+/// A latency is the time between the write of the publish frame and the reception of
+/// its confirm, latencies above an hour are counted as an hour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+    buckets: Vec<u64>,
+}
+
+impl LatencySnapshot {
+    /// Number of confirmed messages
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            _ => Duration::from_micros(self.min),
+        }
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max)
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => Duration::from_micros(self.sum / count),
+        }
+    }
+
+    /// Latency `quantile` of the confirms are below, `quantile` being between 0 and 1
+    ///
+    /// The result is an upper bound, within 1/8 of the actual latency.
+    pub fn percentile(&self, quantile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let end = bucket_start(bucket + 1).saturating_sub(1);
+                return Duration::from_micros(end.min(self.max));
+            }
+        }
+        self.max()
+    }
+
+    pub fn p50(&self) -> Duration {
+        self.percentile(0.5)
+    }
+
+    pub fn p99(&self) -> Duration {
+        self.percentile(0.99)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{bucket, bucket_start, LatencyHistogram, BUCKETS, MAX_MICROS};
+
+    #[test]
+    fn latency_buckets_test() {
+        for micros in [0, 7, 8, 15, 16, 17, 1000, 123_456, MAX_MICROS] {
+            let index = bucket(micros);
+            assert!(bucket_start(index) <= micros, "{}", micros);
+            assert!(bucket_start(index + 1) > micros, "{}", micros);
+        }
+        assert_eq!(BUCKETS - 1, bucket(MAX_MICROS));
+    }
+
+    #[test]
+    fn latency_snapshot_test() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(Duration::ZERO, histogram.snapshot().p99());
+
+        for millis in 1..=100 {
+            histogram.record(Duration::from_millis(millis));
+        }
+        let snapshot = histogram.snapshot();
+
+        assert_eq!(100, snapshot.count());
+        assert_eq!(Duration::from_millis(1), snapshot.min());
+        assert_eq!(Duration::from_millis(100), snapshot.max());
+        assert_eq!(Duration::from_micros(50_500), snapshot.mean());
+        let p50 = snapshot.p50();
+        assert!(p50 >= Duration::from_millis(50) && p50 < Duration::from_millis(57));
+        let p99 = snapshot.p99();
+        assert!(p99 >= Duration::from_millis(99) && p99 <= Duration::from_millis(100));
+        assert_eq!(Duration::from_millis(100), snapshot.percentile(1.0));
+    }
+}
@@ -30,7 +30,12 @@
 //!
 //! for i in 0..10 {
 //!     producer
-//!         .send(Message::builder().body(format!("message{}", i)).build())
+//!         .send(
+//!             Message::builder().body(format!("message{}", i)).build(),
+//!             |confirmation| async move {
+//!                 println!("Got confirmation {:?}", confirmation);
+//!             },
+//!         )
 //!         .await?;
 //! }
 //!
@@ -70,30 +75,156 @@
 //! # }
 //! ```
 //! For more consumer options check [`ConsumerBuilder`]
+//!
+//! ### Tracing
+//!
+//! The client emits [`tracing`](https://docs.rs/tracing) spans at the debug level:
+//!
+//! - `rabbitmq_stream.connect` around the connection setup, with the `host`, `port`,
+//!   `vhost` and `elapsed_us` fields
+//! - `rabbitmq_stream.rpc` around every request waiting for a response, with the
+//!   `correlation_id`, `command_key` and `elapsed_us` fields
+//! - `rabbitmq_stream.producer_recovery` and `rabbitmq_stream.consumer_recovery` around
+//!   every attempt to recover a connection, with the `stream` and `attempt` fields
 
+mod body_codec;
+mod broker_close;
 mod byte_capacity;
+mod chunk_consumer;
+mod circuit_breaker;
 mod client;
+mod connection_pool;
 mod consumer;
+#[cfg(feature = "testcontainers")]
+pub mod containers;
+mod debug_state;
+mod dedup_producer;
 mod environment;
 pub mod error;
+mod events;
+mod fault_injection;
+mod file_offset_store;
+mod interceptor;
+mod latency;
+#[cfg(feature = "management")]
+mod management;
+mod messaging;
+mod metrics;
+mod names;
 mod offset_specification;
+mod offset_tracking;
+#[cfg(feature = "otel")]
+pub mod otel;
+mod payload_logging;
+mod poison_message;
 mod producer;
+mod rate_limiter;
+mod recovery_backoff;
+mod replay;
+mod replicator;
+mod retention;
+mod retry_policy;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+mod slow_consumer;
 mod stream_creator;
+mod stream_stats;
+mod super_stream_consumer;
+mod super_stream_creator;
+mod super_stream_producer;
+#[cfg(feature = "test-broker")]
+pub mod test_broker;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod timeouts;
+#[cfg(feature = "toxiproxy")]
+pub mod toxiproxy;
+#[cfg(feature = "serde")]
+mod typed_consumer;
+mod wire_capture;
+mod wire_replay;
 
 pub type RabbitMQStreamResult<T> = Result<T, error::ClientError>;
 
-pub use crate::client::{Client, ClientOptions};
+pub use crate::body_codec::DecodedConsumer;
+pub use crate::chunk_consumer::{ChunkConsumer, ChunkConsumerBuilder, ChunkConsumerHandle};
+pub use crate::client::{CertificateStore, Client, ClientOptions, TlsConfiguration};
 
-pub use crate::consumer::{Consumer, ConsumerBuilder, ConsumerHandle};
+pub use crate::consumer::{
+    Consumer, ConsumerBuilder, ConsumerHandle, ConsumerRecoveryEvent, ConsumerUpdateContext,
+    HandlerConsumerBuilder, MessageContext,
+};
+pub use crate::dedup_producer::{DedupProducer, DedupProducerBuilder};
 pub use crate::environment::{Environment, EnvironmentBuilder};
-pub use crate::producer::{Producer, ProducerBuilder};
+#[cfg(feature = "management")]
+pub use crate::management::ManagementClient;
+pub use crate::messaging::{MessageConsumer, MessageProducer};
+pub use crate::producer::{Producer, ProducerBuilder, ProducerRecoveryEvent};
+pub use crate::replay::{Replay, ReplayBuilder};
+pub use crate::replicator::{Replicator, ReplicatorBuilder};
+pub use crate::super_stream_consumer::{
+    SuperStreamConsumer, SuperStreamConsumerBuilder, SuperStreamConsumerHandle, SuperStreamWorkers,
+};
+pub use crate::super_stream_producer::{SuperStreamProducer, SuperStreamProducerBuilder};
+#[cfg(feature = "serde")]
+pub use crate::typed_consumer::TypedConsumer;
 pub mod types {
 
+    pub use crate::body_codec::{BodyCodec, BodyCodecs, CodecError};
+    pub use crate::broker_close::{BrokerClose, BrokerCloseAction, BrokerClosePolicy};
     pub use crate::byte_capacity::ByteCapacity;
+    pub use crate::chunk_consumer::{Chunk, ChunkEntry, ChunkRecord};
+    pub use crate::circuit_breaker::CircuitBreaker;
     pub use crate::client::{Broker, MessageResult, StreamMetadata};
-    pub use crate::consumer::Delivery;
+    pub use crate::consumer::{ChunkMetadata, CreditStrategy, Delivery, StreamDeletedPolicy};
+    pub use crate::debug_state::{
+        ClientDebugState, ConsumerDebugState, ProducerDebugState, PublisherDebugState,
+        SubscriptionDebugState,
+    };
+    pub use crate::dedup_producer::DedupStatus;
+    pub use crate::events::StreamEvent;
+    pub use crate::fault_injection::{Fault, FaultInjector, FaultRule};
+    pub use crate::file_offset_store::FileOffsetStore;
+    pub use crate::interceptor::{ConsumerInterceptor, InterceptorError, ProducerInterceptor};
+    pub use crate::latency::LatencySnapshot;
+    #[cfg(feature = "management")]
+    pub use crate::management::{
+        SingleActiveConsumerGroup, StreamConnectionInfo, StreamConsumerInfo, StreamEndpoint,
+        StreamInfo, StreamPolicy,
+    };
+    #[cfg(feature = "metrics-facade")]
+    pub use crate::metrics::MetricsFacadeCollector;
+    #[cfg(feature = "prometheus")]
+    pub use crate::metrics::PrometheusMetricsCollector;
+    pub use crate::metrics::{
+        MetricsCollector, MetricsContext, StreamCounters, StreamCountersSnapshot,
+    };
     pub use crate::offset_specification::OffsetSpecification;
-    pub use crate::stream_creator::StreamCreator;
-    pub use rabbitmq_stream_protocol::message::Message;
+    pub use crate::offset_tracking::{AutoOffsetTracking, BrokerOffsetStore, OffsetStore};
+    #[cfg(feature = "otel")]
+    pub use crate::otel::{OtelConsumerInterceptor, OtelProducerInterceptor};
+    pub use crate::payload_logging::{PayloadLogger, PayloadSample, PAYLOAD_LOG_TARGET};
+    pub use crate::poison_message::{PoisonMessage, PoisonMessageHandling, PoisonReason};
+    pub use crate::producer::{ConfirmationStatus, OverflowStrategy};
+    pub use crate::rate_limiter::RateLimiter;
+    pub use crate::recovery_backoff::RecoveryBackoff;
+    pub use crate::retention::MaxAge;
+    pub use crate::retry_policy::{NoRetry, RetryPolicy};
+    pub use crate::slow_consumer::SlowConsumerReason;
+    pub use crate::stream_creator::{LeaderLocator, StreamCreator};
+    pub use crate::stream_stats::StreamStats;
+    pub use crate::super_stream_creator::SuperStreamCreator;
+    pub use crate::super_stream_producer::{HashRoutingStrategy, RoutingStrategy};
+    pub use crate::timeouts::Timeouts;
+    #[cfg(feature = "serde")]
+    pub use crate::typed_consumer::{Json, PayloadFormat};
+    pub use crate::wire_capture::{CaptureReader, CapturedFrame, FrameDirection, WireCapture};
+    pub use crate::wire_replay::WireReplay;
+    pub use rabbitmq_stream_protocol::compression::{Compression, ZstdDictionary};
+    pub use rabbitmq_stream_protocol::message::amqp091;
+    pub use rabbitmq_stream_protocol::message::{
+        Message, MessageBatch, MessageBatchBuilder, MessageBuilder, MessageId, Properties, Value,
+    };
+    pub use rabbitmq_stream_protocol::types::PeerProperties;
     pub use rabbitmq_stream_protocol::{Response, ResponseCode, ResponseKind};
 }
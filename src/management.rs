@@ -0,0 +1,892 @@
+//! Client of the management HTTP API, to inspect the topology of the cluster, and super
+//! stream topology over it for brokers older than 3.13 which do not support the
+//! CreateSuperStream and DeleteSuperStream commands.
+
+use std::{collections::HashMap, convert::TryFrom, fmt};
+
+use rabbitmq_stream_protocol::types::PeerProperties;
+use reqwest::{Client as HttpClient, Method, RequestBuilder, Response, Url};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    byte_capacity::ByteCapacity,
+    environment::EnvironmentOptions,
+    error::{ClientError, ManagementError},
+    retention::MaxAge,
+    stream_creator::LeaderLocator,
+};
+
+const DEFAULT_MANAGEMENT_PORT: u16 = 15672;
+
+/// Check if the broker is too old for the super stream commands
+pub(crate) fn is_required(server_properties: &PeerProperties) -> bool {
+    let version = match server_properties.get("version") {
+        Some(version) => version,
+        None => return false,
+    };
+    let mut parts = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse::<u32>().unwrap_or(0));
+
+    let major = parts.next().unwrap_or(0);
+    let minor = parts.next().unwrap_or(0);
+
+    (major, minor) < (3, 13)
+}
+
+pub(crate) async fn create_super_stream(
+    options: &EnvironmentOptions,
+    super_stream: &str,
+    partitions: &[String],
+    binding_keys: &[String],
+    args: &HashMap<String, String>,
+) -> Result<(), ClientError> {
+    let management = ManagementClient::new(options).map_err(generic)?;
+
+    management
+        .request(Method::PUT, &["exchanges", &management.vhost, super_stream])
+        .json(&json!({
+            "type": "direct",
+            "durable": true,
+            "auto_delete": false,
+            "arguments": { "x-super-stream": true },
+        }))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(generic)?;
+
+    let mut arguments = queue_arguments(args);
+    arguments.insert("x-queue-type".to_owned(), json!("stream"));
+
+    for (order, (partition, binding_key)) in partitions.iter().zip(binding_keys).enumerate() {
+        management
+            .request(Method::PUT, &["queues", &management.vhost, partition])
+            .json(&json!({
+                "durable": true,
+                "auto_delete": false,
+                "arguments": arguments,
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(generic)?;
+
+        management
+            .request(
+                Method::POST,
+                &[
+                    "bindings",
+                    &management.vhost,
+                    "e",
+                    super_stream,
+                    "q",
+                    partition,
+                ],
+            )
+            .json(&json!({
+                "routing_key": binding_key,
+                "arguments": { "x-stream-partition-order": order },
+            }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(generic)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn delete_super_stream(
+    options: &EnvironmentOptions,
+    super_stream: &str,
+) -> Result<(), ClientError> {
+    let management = ManagementClient::new(options).map_err(generic)?;
+
+    let bindings: Vec<Value> = management
+        .request(
+            Method::GET,
+            &[
+                "exchanges",
+                &management.vhost,
+                super_stream,
+                "bindings",
+                "source",
+            ],
+        )
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(generic)?
+        .json()
+        .await
+        .map_err(generic)?;
+
+    for partition in bindings
+        .iter()
+        .filter_map(|binding| binding.get("destination").and_then(Value::as_str))
+    {
+        management
+            .request(Method::DELETE, &["queues", &management.vhost, partition])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(generic)?;
+    }
+
+    management
+        .request(
+            Method::DELETE,
+            &["exchanges", &management.vhost, super_stream],
+        )
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(generic)?;
+
+    Ok(())
+}
+
+/// Client of the management HTTP API of the node of an environment, see
+/// [`Environment::management`](crate::Environment::management)
+///
+/// It shows the topology the stream protocol does not expose, like the members of the
+/// streams and the stream connections of the whole cluster. The requests go over plain
+/// HTTP with the credentials of the environment and are scoped to its virtual host.
+#[derive(Clone)]
+pub struct ManagementClient {
+    http: HttpClient,
+    base: Url,
+    vhost: String,
+    user: String,
+    password: String,
+}
+
+impl fmt::Debug for ManagementClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManagementClient")
+            .field("base", &self.base.as_str())
+            .field("vhost", &self.vhost)
+            .field("user", &self.user)
+            .field("password", &"***")
+            .finish_non_exhaustive()
+    }
+}
+
+impl ManagementClient {
+    pub(crate) fn new(options: &EnvironmentOptions) -> Result<Self, ManagementError> {
+        let address = format!(
+            "http://{}:{}/api",
+            options.client_options.host,
+            options.management_port.unwrap_or(DEFAULT_MANAGEMENT_PORT)
+        );
+        let base = Url::parse(&address)
+            .map_err(|error| ManagementError::Address(format!("{}: {}", address, error)))?;
+
+        Ok(Self {
+            http: HttpClient::new(),
+            base,
+            vhost: options.client_options.v_host.clone(),
+            user: options.client_options.user.clone(),
+            password: options.client_options.password.clone(),
+        })
+    }
+
+    /// Streams of the virtual host
+    pub async fn streams(&self) -> Result<Vec<StreamInfo>, ManagementError> {
+        let queues = self.get(&["queues", &self.vhost]).await?;
+        queues
+            .as_array()
+            .ok_or_else(|| {
+                ManagementError::InvalidResponse("expected a list of queues".to_owned())
+            })?
+            .iter()
+            .filter(|queue| is_stream(queue))
+            .map(StreamInfo::from_json)
+            .collect()
+    }
+
+    /// Details of `stream`, `None` when there is no stream with this name
+    pub async fn stream(&self, stream: &str) -> Result<Option<StreamInfo>, ManagementError> {
+        match self.get(&["queues", &self.vhost, stream]).await {
+            Ok(queue) if is_stream(&queue) => StreamInfo::from_json(&queue).map(Some),
+            Ok(_) | Err(ManagementError::Status { status: 404, .. }) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Stream protocol connections to the virtual host, on every node of the cluster
+    ///
+    /// Requires the `rabbitmq_stream_management` plugin.
+    pub async fn stream_connections(&self) -> Result<Vec<StreamConnectionInfo>, ManagementError> {
+        let connections = self.get(&["stream", "connections", &self.vhost]).await?;
+        connections
+            .as_array()
+            .ok_or_else(|| {
+                ManagementError::InvalidResponse("expected a list of connections".to_owned())
+            })?
+            .iter()
+            .map(StreamConnectionInfo::from_json)
+            .collect()
+    }
+
+    /// Consumers of the streams of the virtual host, on every node of the cluster
+    ///
+    /// Requires the `rabbitmq_stream_management` plugin.
+    pub async fn stream_consumers(&self) -> Result<Vec<StreamConsumerInfo>, ManagementError> {
+        let consumers = self.get(&["stream", "consumers", &self.vhost]).await?;
+        consumers
+            .as_array()
+            .ok_or_else(|| {
+                ManagementError::InvalidResponse("expected a list of consumers".to_owned())
+            })?
+            .iter()
+            .map(StreamConsumerInfo::from_json)
+            .collect()
+    }
+
+    /// Consumers named `name` of `stream` with single active consumer enabled, to check
+    /// which one gets the messages
+    ///
+    /// For a super stream consumer, `stream` is one of the partitions, each partition having
+    /// its own group.
+    pub async fn single_active_consumer_group(
+        &self,
+        stream: &str,
+        name: &str,
+    ) -> Result<SingleActiveConsumerGroup, ManagementError> {
+        let (active, waiting) = self
+            .stream_consumers()
+            .await?
+            .into_iter()
+            .filter(|consumer| {
+                consumer.single_active_consumer
+                    && consumer.stream == stream
+                    && consumer.name.as_deref() == Some(name)
+            })
+            .partition::<Vec<_>, _>(|consumer| consumer.active);
+        Ok(SingleActiveConsumerGroup {
+            stream: stream.to_owned(),
+            name: name.to_owned(),
+            active: active.into_iter().next(),
+            waiting,
+        })
+    }
+
+    /// Stream listeners of the running nodes of the cluster
+    ///
+    /// Listeners bound to every interface are reached through the host name of their node.
+    pub async fn stream_endpoints(&self) -> Result<Vec<StreamEndpoint>, ManagementError> {
+        let nodes = self.get(&["nodes"]).await?;
+        let running: Vec<&str> = nodes
+            .as_array()
+            .ok_or_else(|| ManagementError::InvalidResponse("expected a list of nodes".to_owned()))?
+            .iter()
+            .filter(|node| node.get("running").and_then(Value::as_bool).unwrap_or(true))
+            .filter_map(|node| node.get("name").and_then(Value::as_str))
+            .collect();
+
+        let overview = self.get(&["overview"]).await?;
+        let listeners = overview
+            .get("listeners")
+            .and_then(Value::as_array)
+            .ok_or_else(|| ManagementError::InvalidResponse("missing listeners".to_owned()))?;
+        Ok(listeners
+            .iter()
+            .filter_map(StreamEndpoint::from_listener)
+            .filter(|endpoint| running.contains(&endpoint.node.as_str()))
+            .collect())
+    }
+
+    /// Create `stream` with the `arguments` of a [`StreamCreator`](crate::StreamCreator)
+    ///
+    /// See [`StreamCreator::create_with_management`](crate::StreamCreator::create_with_management).
+    /// Creating an existing stream with the same arguments succeeds.
+    pub async fn create_stream(
+        &self,
+        stream: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<(), ManagementError> {
+        let mut arguments = queue_arguments(arguments);
+        arguments.insert("x-queue-type".to_owned(), json!("stream"));
+        let request = self
+            .request(Method::PUT, &["queues", &self.vhost, stream])
+            .json(&json!({
+                "durable": true,
+                "auto_delete": false,
+                "arguments": arguments,
+            }));
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Delete `stream` and its messages
+    pub async fn delete_stream(&self, stream: &str) -> Result<(), ManagementError> {
+        self.send(self.request(Method::DELETE, &["queues", &self.vhost, stream]))
+            .await?;
+        Ok(())
+    }
+
+    /// Create or replace the policy `name`, applying its settings to the matching streams
+    ///
+    /// The settings of a policy override the arguments the streams were created with and
+    /// change the existing streams, unlike arguments.
+    pub async fn set_policy(
+        &self,
+        name: &str,
+        policy: &StreamPolicy,
+    ) -> Result<(), ManagementError> {
+        let request = self
+            .request(Method::PUT, &["policies", &self.vhost, name])
+            .json(&policy.to_json());
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Delete the policy `name`, the streams get back the settings they were created with
+    pub async fn delete_policy(&self, name: &str) -> Result<(), ManagementError> {
+        self.send(self.request(Method::DELETE, &["policies", &self.vhost, name]))
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, path: &[&str]) -> Result<Value, ManagementError> {
+        let response = self.send(self.request(Method::GET, path)).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ManagementError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let path = response.url().path().to_owned();
+        let body = response.text().await.unwrap_or_default();
+        Err(ManagementError::Status {
+            path,
+            status: status.as_u16(),
+            body,
+        })
+    }
+
+    fn request(&self, method: Method, path: &[&str]) -> RequestBuilder {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("an http URL has a path")
+            .extend(path);
+
+        self.http
+            .request(method, url)
+            .basic_auth(&self.user, Some(&self.password))
+    }
+}
+
+/// Settings of the streams matching a pattern, see [`ManagementClient::set_policy`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamPolicy {
+    pattern: String,
+    priority: i32,
+    apply_to: &'static str,
+    definition: Map<String, Value>,
+}
+
+impl StreamPolicy {
+    /// Policy of the streams whose name matches the regular expression `pattern`
+    pub fn new(pattern: &str) -> Self {
+        StreamPolicy {
+            pattern: pattern.to_owned(),
+            priority: 0,
+            apply_to: "streams",
+            definition: Map::new(),
+        }
+    }
+
+    /// Priority over the other policies matching the same streams, 0 by default
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Apply to queues of every type, for brokers older than 3.12 which cannot restrict
+    /// a policy to streams
+    pub fn all_queues(mut self) -> Self {
+        self.apply_to = "queues";
+        self
+    }
+
+    /// Discard segments older than `max_age`, a [`MaxAge`] or a [`Duration`](std::time::Duration)
+    pub fn max_age(mut self, max_age: impl Into<MaxAge>) -> Self {
+        self.definition
+            .insert("max-age".to_owned(), json!(max_age.into().to_argument()));
+        self
+    }
+
+    /// Maximum size of the streams before old segments are truncated
+    pub fn max_length(mut self, byte_capacity: ByteCapacity) -> Self {
+        self.definition
+            .insert("max-length-bytes".to_owned(), json!(byte_capacity.bytes()));
+        self
+    }
+
+    /// Maximum size of a single segment file
+    pub fn max_segment_size(mut self, byte_capacity: ByteCapacity) -> Self {
+        self.definition.insert(
+            "stream-max-segment-size-bytes".to_owned(),
+            json!(byte_capacity.bytes()),
+        );
+        self
+    }
+
+    /// Strategy used by the broker to place the leaders of new streams
+    pub fn leader_locator(mut self, leader_locator: LeaderLocator) -> Self {
+        self.definition.insert(
+            "queue-leader-locator".to_owned(),
+            json!(leader_locator.as_ref()),
+        );
+        self
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "pattern": self.pattern,
+            "priority": self.priority,
+            "apply-to": self.apply_to,
+            "definition": self.definition,
+        })
+    }
+}
+
+/// Stream as described by the management API, see [`ManagementClient::streams`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamInfo {
+    pub name: String,
+    pub vhost: String,
+    /// Node of the leader, which the messages are published to
+    pub leader: Option<String>,
+    /// Nodes with a replica of the stream, the leader included
+    pub members: Vec<String>,
+    /// Members currently running
+    pub online: Vec<String>,
+    /// Number of messages, as estimated by the broker
+    pub messages: Option<u64>,
+    /// Name of the policy applied to the stream
+    pub policy: Option<String>,
+}
+
+impl StreamInfo {
+    fn from_json(queue: &Value) -> Result<Self, ManagementError> {
+        Ok(StreamInfo {
+            name: required_str(queue, "name")?,
+            vhost: required_str(queue, "vhost")?,
+            leader: optional_str(queue, "leader").or_else(|| optional_str(queue, "node")),
+            members: strings(queue, "members"),
+            online: strings(queue, "online"),
+            messages: queue.get("messages").and_then(Value::as_u64),
+            policy: optional_str(queue, "policy"),
+        })
+    }
+}
+
+/// Stream protocol connection as described by the management API, see
+/// [`ManagementClient::stream_connections`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamConnectionInfo {
+    /// Name of the connection, with the addresses of both ends
+    pub name: String,
+    pub vhost: String,
+    pub user: Option<String>,
+    /// Node the client is connected to
+    pub node: Option<String>,
+    /// Address of the client
+    pub peer_host: Option<String>,
+    pub peer_port: Option<u16>,
+    /// Time of the connection in milliseconds since the Unix epoch
+    pub connected_at: Option<u64>,
+    /// Properties sent by the client when connecting, the ones with a string value
+    pub client_properties: HashMap<String, String>,
+}
+
+impl StreamConnectionInfo {
+    fn from_json(connection: &Value) -> Result<Self, ManagementError> {
+        let client_properties = connection
+            .get("client_properties")
+            .and_then(Value::as_object)
+            .map(|properties| {
+                properties
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_owned())))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(StreamConnectionInfo {
+            name: required_str(connection, "name")?,
+            vhost: required_str(connection, "vhost")?,
+            user: optional_str(connection, "user"),
+            node: optional_str(connection, "node"),
+            peer_host: optional_str(connection, "peer_host"),
+            peer_port: connection
+                .get("peer_port")
+                .and_then(Value::as_u64)
+                .and_then(|port| u16::try_from(port).ok()),
+            connected_at: connection.get("connected_at").and_then(Value::as_u64),
+            client_properties,
+        })
+    }
+}
+
+/// Consumer of a stream as described by the management API, see
+/// [`ManagementClient::stream_consumers`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamConsumerInfo {
+    pub stream: String,
+    pub vhost: String,
+    /// Name the consumer stores its offsets under
+    pub name: Option<String>,
+    pub subscription_id: u8,
+    /// Name of the connection of the consumer, see [`StreamConnectionInfo::name`]
+    pub connection: String,
+    /// Node the consumer is connected to
+    pub node: Option<String>,
+    pub single_active_consumer: bool,
+    /// Whether the consumer gets the messages, always the case without single active consumer
+    pub active: bool,
+    /// Offset of the last delivered chunk
+    pub offset: Option<u64>,
+    /// Number of messages between the offset and the end of the stream
+    pub offset_lag: Option<u64>,
+}
+
+impl StreamConsumerInfo {
+    fn from_json(consumer: &Value) -> Result<Self, ManagementError> {
+        let queue = consumer
+            .get("queue")
+            .ok_or_else(|| ManagementError::InvalidResponse("missing queue".to_owned()))?;
+        let connection = consumer.get("connection_details").ok_or_else(|| {
+            ManagementError::InvalidResponse("missing connection_details".to_owned())
+        })?;
+        let properties = consumer.get("properties").unwrap_or(&Value::Null);
+        let single_active_consumer =
+            optional_str(properties, "single-active-consumer").as_deref() == Some("true");
+        Ok(StreamConsumerInfo {
+            stream: required_str(queue, "name")?,
+            vhost: required_str(queue, "vhost")?,
+            name: optional_str(properties, "name"),
+            subscription_id: consumer
+                .get("subscription_id")
+                .and_then(Value::as_u64)
+                .and_then(|id| u8::try_from(id).ok())
+                .ok_or_else(|| {
+                    ManagementError::InvalidResponse("missing subscription_id".to_owned())
+                })?,
+            connection: required_str(connection, "name")?,
+            node: optional_str(connection, "node"),
+            single_active_consumer,
+            active: consumer
+                .get("active")
+                .and_then(Value::as_bool)
+                .unwrap_or(!single_active_consumer),
+            offset: consumer.get("offset").and_then(Value::as_u64),
+            offset_lag: consumer.get("offset_lag").and_then(Value::as_u64),
+        })
+    }
+}
+
+/// Consumers of a single active consumer group, see
+/// [`ManagementClient::single_active_consumer_group`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SingleActiveConsumerGroup {
+    pub stream: String,
+    pub name: String,
+    /// Consumer getting the messages, `None` when the group is empty or during a failover
+    pub active: Option<StreamConsumerInfo>,
+    /// Consumers taking over when the active one goes away
+    pub waiting: Vec<StreamConsumerInfo>,
+}
+
+/// Stream listener of a node, see [`ManagementClient::stream_endpoints`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamEndpoint {
+    pub node: String,
+    pub host: String,
+    pub port: u16,
+    /// Whether the listener expects TLS connections
+    pub tls: bool,
+}
+
+impl StreamEndpoint {
+    fn from_listener(listener: &Value) -> Option<Self> {
+        let tls = match listener.get("protocol").and_then(Value::as_str)? {
+            "stream" => false,
+            "stream/ssl" => true,
+            _ => return None,
+        };
+        let node = optional_str(listener, "node")?;
+        let host = match listener.get("ip_address").and_then(Value::as_str) {
+            Some(ip_address) if !matches!(ip_address, "::" | "0.0.0.0") => ip_address.to_owned(),
+            _ => node
+                .split_once('@')
+                .map_or(&node[..], |(_, host)| host)
+                .to_owned(),
+        };
+        let port = listener
+            .get("port")
+            .and_then(Value::as_u64)
+            .and_then(|port| u16::try_from(port).ok())?;
+        Some(StreamEndpoint {
+            node,
+            host,
+            port,
+            tls,
+        })
+    }
+}
+
+/// Endpoints of the stream listeners of the cluster the environment connects to, the TLS
+/// ones when the environment uses TLS
+pub(crate) async fn discover_endpoints(
+    options: &EnvironmentOptions,
+) -> Result<Vec<(String, u16)>, ClientError> {
+    let tls = options.client_options.tls.enabled();
+    let endpoints: Vec<(String, u16)> = ManagementClient::new(options)
+        .map_err(generic)?
+        .stream_endpoints()
+        .await
+        .map_err(generic)?
+        .into_iter()
+        .filter(|endpoint| endpoint.tls == tls)
+        .map(|endpoint| (endpoint.host, endpoint.port))
+        .collect();
+    if endpoints.is_empty() {
+        return Err(generic(ManagementError::InvalidResponse(
+            "no running node with a stream listener".to_owned(),
+        )));
+    }
+    Ok(endpoints)
+}
+
+fn is_stream(queue: &Value) -> bool {
+    queue.get("type").and_then(Value::as_str) == Some("stream")
+}
+
+fn required_str(value: &Value, key: &str) -> Result<String, ManagementError> {
+    optional_str(value, key)
+        .ok_or_else(|| ManagementError::InvalidResponse(format!("missing {}", key)))
+}
+
+fn optional_str(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_owned)
+}
+
+fn strings(value: &Value, key: &str) -> Vec<String> {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|values| {
+            values
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Translate stream creation options to queue arguments, numeric values are sent as numbers
+fn queue_arguments(args: &HashMap<String, String>) -> Map<String, Value> {
+    args.iter()
+        .map(|(key, value)| {
+            let value = value
+                .parse::<u64>()
+                .map(Value::from)
+                .unwrap_or_else(|_| Value::from(value.as_str()));
+            (format!("x-{}", key), value)
+        })
+        .collect()
+}
+
+fn generic<E: std::error::Error + Send + Sync + 'static>(err: E) -> ClientError {
+    ClientError::GenericError(Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::{
+        ManagementClient, StreamConnectionInfo, StreamConsumerInfo, StreamEndpoint, StreamInfo,
+        StreamPolicy,
+    };
+    use crate::{
+        byte_capacity::ByteCapacity, client::ClientOptions, environment::EnvironmentOptions,
+        stream_creator::LeaderLocator,
+    };
+
+    #[test]
+    fn password_redacted_test() {
+        let options = EnvironmentOptions {
+            client_options: ClientOptions {
+                password: "s3cr3t-password".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let debug = format!("{:?}", ManagementClient::new(&options).unwrap());
+        assert!(!debug.contains("s3cr3t-password"));
+        assert!(debug.contains(r#"password: "***""#));
+    }
+
+    #[test]
+    fn stream_info_test() {
+        let queue = json!({
+            "name": "orders",
+            "vhost": "/",
+            "type": "stream",
+            "leader": "rabbit@node-1",
+            "members": ["rabbit@node-1", "rabbit@node-2", "rabbit@node-3"],
+            "online": ["rabbit@node-1", "rabbit@node-2"],
+            "messages": 1200,
+            "policy": "retention",
+        });
+        assert_eq!(
+            StreamInfo {
+                name: "orders".to_owned(),
+                vhost: "/".to_owned(),
+                leader: Some("rabbit@node-1".to_owned()),
+                members: vec![
+                    "rabbit@node-1".to_owned(),
+                    "rabbit@node-2".to_owned(),
+                    "rabbit@node-3".to_owned()
+                ],
+                online: vec!["rabbit@node-1".to_owned(), "rabbit@node-2".to_owned()],
+                messages: Some(1200),
+                policy: Some("retention".to_owned()),
+            },
+            StreamInfo::from_json(&queue).unwrap()
+        );
+        assert!(StreamInfo::from_json(&json!({ "vhost": "/" })).is_err());
+    }
+
+    #[test]
+    fn stream_policy_test() {
+        let policy = StreamPolicy::new("^orders-")
+            .priority(2)
+            .max_age(Duration::from_secs(3600))
+            .max_length(ByteCapacity::GB(10))
+            .max_segment_size(ByteCapacity::MB(100))
+            .leader_locator(LeaderLocator::ClientLocal);
+        assert_eq!(
+            json!({
+                "pattern": "^orders-",
+                "priority": 2,
+                "apply-to": "streams",
+                "definition": {
+                    "max-age": "1h",
+                    "max-length-bytes": 10_000_000_000u64,
+                    "stream-max-segment-size-bytes": 100_000_000,
+                    "queue-leader-locator": "client-local",
+                },
+            }),
+            policy.to_json()
+        );
+        assert_eq!(
+            json!("queues"),
+            StreamPolicy::new(".*").all_queues().to_json()["apply-to"]
+        );
+    }
+
+    #[test]
+    fn stream_connection_info_test() {
+        let connection = json!({
+            "name": "127.0.0.1:52730 -> 127.0.0.1:5552",
+            "vhost": "/",
+            "user": "guest",
+            "node": "rabbit@node-1",
+            "peer_host": "127.0.0.1",
+            "peer_port": 52730,
+            "connected_at": 1700000000000u64,
+            "client_properties": { "product": "RabbitMQ Stream", "version": 1 },
+        });
+        let info = StreamConnectionInfo::from_json(&connection).unwrap();
+        assert_eq!(Some(52730), info.peer_port);
+        assert_eq!(Some(1_700_000_000_000), info.connected_at);
+        assert_eq!(Some("rabbit@node-1"), info.node.as_deref());
+        assert_eq!(1, info.client_properties.len());
+        assert_eq!("RabbitMQ Stream", info.client_properties["product"]);
+    }
+
+    #[test]
+    fn stream_endpoint_test() {
+        let endpoint = |listener| StreamEndpoint::from_listener(&listener);
+        assert_eq!(
+            Some(StreamEndpoint {
+                node: "rabbit@node-1".to_owned(),
+                host: "node-1".to_owned(),
+                port: 5552,
+                tls: false,
+            }),
+            endpoint(json!({
+                "node": "rabbit@node-1",
+                "protocol": "stream",
+                "ip_address": "::",
+                "port": 5552,
+            }))
+        );
+        let tls = endpoint(json!({
+            "node": "rabbit@node-2",
+            "protocol": "stream/ssl",
+            "ip_address": "10.0.0.2",
+            "port": 5551,
+        }))
+        .unwrap();
+        assert!(tls.tls);
+        assert_eq!("10.0.0.2", tls.host);
+        assert_eq!(
+            None,
+            endpoint(json!({
+                "node": "rabbit@node-1",
+                "protocol": "amqp",
+                "ip_address": "::",
+                "port": 5672,
+            }))
+        );
+    }
+
+    #[test]
+    fn stream_consumer_info_test() {
+        let consumer = json!({
+            "queue": { "name": "orders-0", "vhost": "/" },
+            "connection_details": {
+                "name": "127.0.0.1:52730 -> 127.0.0.1:5552",
+                "node": "rabbit@node-1",
+            },
+            "subscription_id": 2,
+            "properties": { "name": "billing", "single-active-consumer": "true" },
+            "active": false,
+            "offset": 120,
+            "offset_lag": 3,
+        });
+        let info = StreamConsumerInfo::from_json(&consumer).unwrap();
+        assert_eq!("orders-0", info.stream);
+        assert_eq!(Some("billing"), info.name.as_deref());
+        assert_eq!(2, info.subscription_id);
+        assert_eq!(Some("rabbit@node-1"), info.node.as_deref());
+        assert!(info.single_active_consumer);
+        assert!(!info.active);
+        assert_eq!(Some(3), info.offset_lag);
+
+        // brokers not reporting the activity only have active consumers without the feature
+        let plain = StreamConsumerInfo::from_json(&json!({
+            "queue": { "name": "orders", "vhost": "/" },
+            "connection_details": { "name": "connection" },
+            "subscription_id": 0,
+            "properties": {},
+        }))
+        .unwrap();
+        assert!(!plain.single_active_consumer);
+        assert!(plain.active);
+        assert_eq!(None, plain.name);
+    }
+}
@@ -0,0 +1,67 @@
+use futures::StreamExt;
+use rabbitmq_stream_protocol::message::Message;
+
+use crate::{
+    error::{ConsumerDeliveryError, ConsumerStoreOffsetError, ProducerPublishError},
+    producer::ConfirmationStatus,
+    types::Delivery,
+    Consumer, Producer,
+};
+
+/// Publishing side of a [`Producer`], for the application code to be tested without a broker
+///
+/// The `test-util` feature provides an in-memory implementation, `test_util::FakeProducer`.
+#[async_trait::async_trait]
+pub trait MessageProducer: Send + Sync {
+    /// Publish a message and wait for the broker to confirm or reject it
+    async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<ConfirmationStatus, ProducerPublishError>;
+
+    /// Publish a batch of messages and wait for the outcome of each of them, in order
+    async fn batch_send_with_confirm(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError>;
+}
+
+/// Consuming side of a [`Consumer`], for the application code to be tested without a broker
+///
+/// The `test-util` feature provides an in-memory implementation, `test_util::FakeConsumer`.
+#[async_trait::async_trait]
+pub trait MessageConsumer: Send {
+    /// Next message of the stream, `None` once the consumer is closed
+    async fn next_delivery(&mut self) -> Option<Result<Delivery, ConsumerDeliveryError>>;
+
+    /// Store `offset` under the name of the consumer
+    async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError>;
+}
+
+#[async_trait::async_trait]
+impl MessageProducer for Producer {
+    async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<ConfirmationStatus, ProducerPublishError> {
+        Producer::send_with_confirm(self, message).await
+    }
+
+    async fn batch_send_with_confirm(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError> {
+        Producer::batch_send_with_confirm(self, messages).await
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageConsumer for Consumer {
+    async fn next_delivery(&mut self) -> Option<Result<Delivery, ConsumerDeliveryError>> {
+        self.next().await
+    }
+
+    async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        Consumer::store_offset(self, offset).await
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use super::{MetricsCollector, MetricsContext};
+
+/// Throughput counters of the streams used by an environment, see
+/// [`crate::Environment::stream_counters`]
+///
+/// The counters only grow, rates are the difference between two snapshots divided by
+/// the time between them.
+#[derive(Debug, Default)]
+pub struct StreamCounters {
+    streams: RwLock<HashMap<String, Arc<Counters>>>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    published: AtomicU64,
+    published_bytes: AtomicU64,
+    confirmed: AtomicU64,
+    errored: AtomicU64,
+    chunks: AtomicU64,
+    delivered: AtomicU64,
+    delivered_bytes: AtomicU64,
+    consumed: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> StreamCountersSnapshot {
+        StreamCountersSnapshot {
+            published: self.published.load(Ordering::Relaxed),
+            published_bytes: self.published_bytes.load(Ordering::Relaxed),
+            confirmed: self.confirmed.load(Ordering::Relaxed),
+            errored: self.errored.load(Ordering::Relaxed),
+            chunks: self.chunks.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            delivered_bytes: self.delivered_bytes.load(Ordering::Relaxed),
+            consumed: self.consumed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters of a stream at the time of [`StreamCounters::snapshot`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamCountersSnapshot {
+    /// Messages published
+    pub published: u64,
+    /// Bytes of the published messages
+    pub published_bytes: u64,
+    /// Messages confirmed by the broker
+    pub confirmed: u64,
+    /// Messages rejected by the broker or failed to be published
+    pub errored: u64,
+    /// Chunks delivered to the consumers
+    pub chunks: u64,
+    /// Messages of the delivered chunks
+    pub delivered: u64,
+    /// Bytes of the messages of the delivered chunks
+    pub delivered_bytes: u64,
+    /// Messages handed to the application
+    pub consumed: u64,
+}
+
+impl StreamCounters {
+    /// Counters of every stream published to or consumed from
+    pub fn snapshot(&self) -> HashMap<String, StreamCountersSnapshot> {
+        self.streams
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(stream, counters)| (stream.clone(), counters.snapshot()))
+            .collect()
+    }
+
+    /// Counters of `stream`, `None` if it was not used
+    pub fn stream(&self, stream: &str) -> Option<StreamCountersSnapshot> {
+        self.streams
+            .read()
+            .unwrap()
+            .get(stream)
+            .map(|counters| counters.snapshot())
+    }
+
+    fn add(&self, context: &MetricsContext<'_>, counter: fn(&Counters) -> &AtomicU64, n: u64) {
+        let stream = match context.stream {
+            Some(stream) => stream,
+            None => return,
+        };
+        let counters = self.streams.read().unwrap().get(stream).cloned();
+        let counters = match counters {
+            Some(counters) => counters,
+            None => self
+                .streams
+                .write()
+                .unwrap()
+                .entry(stream.to_owned())
+                .or_default()
+                .clone(),
+        };
+        counter(&counters).fetch_add(n, Ordering::Relaxed);
+    }
+}
+
+impl MetricsCollector for StreamCounters {
+    fn publish(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.add(context, |counters| &counters.published, messages);
+    }
+
+    fn published_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        self.add(context, |counters| &counters.published_bytes, bytes);
+    }
+
+    fn publish_confirm(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.add(context, |counters| &counters.confirmed, messages);
+    }
+
+    fn publish_error(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.add(context, |counters| &counters.errored, messages);
+    }
+
+    fn chunk(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.add(context, |counters| &counters.chunks, 1);
+        self.add(context, |counters| &counters.delivered, messages);
+    }
+
+    fn delivered_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        self.add(context, |counters| &counters.delivered_bytes, bytes);
+    }
+
+    fn consume(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.add(context, |counters| &counters.consumed, messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StreamCounters;
+    use crate::metrics::{MetricsCollector, MetricsContext};
+
+    #[test]
+    fn stream_counters_test() {
+        let counters = StreamCounters::default();
+        let context = |stream| MetricsContext {
+            vhost: "/",
+            node: "localhost:5552",
+            stream,
+        };
+        counters.publish(&context(Some("orders")), 3);
+        counters.published_bytes(&context(Some("orders")), 300);
+        counters.chunk(&context(Some("orders")), 2);
+        counters.chunk(&context(Some("invoices")), 5);
+        counters.written_bytes(&context(None), 1000);
+
+        let snapshot = counters.snapshot();
+        assert_eq!(2, snapshot.len());
+        let orders = counters.stream("orders").unwrap();
+        assert_eq!(3, orders.published);
+        assert_eq!(300, orders.published_bytes);
+        assert_eq!(1, orders.chunks);
+        assert_eq!(2, orders.delivered);
+        assert_eq!(5, snapshot["invoices"].delivered);
+        assert_eq!(None, counters.stream("payments"));
+    }
+}
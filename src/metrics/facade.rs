@@ -0,0 +1,261 @@
+use std::time::Duration;
+
+use ::metrics::{counter, gauge, histogram, Label};
+
+use super::{MetricsCollector, MetricsContext};
+
+/// [`MetricsCollector`] emitting its metrics through the `metrics` crate facade, to the
+/// recorder installed by the application
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use rabbitmq_stream_client::{types::MetricsFacadeCollector, Environment};
+///
+/// let environment = Environment::builder()
+///     .metrics_collector(MetricsFacadeCollector)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The metrics have the names and labels of the
+/// [`crate::types::PrometheusMetricsCollector`] ones.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsFacadeCollector;
+
+fn connection_labels(context: &MetricsContext<'_>) -> [Label; 2] {
+    [
+        Label::new("vhost", context.vhost.to_owned()),
+        Label::new("node", context.node.to_owned()),
+    ]
+}
+
+fn stream_labels(context: &MetricsContext<'_>) -> [Label; 3] {
+    [
+        Label::new("vhost", context.vhost.to_owned()),
+        Label::new("node", context.node.to_owned()),
+        Label::new("stream", context.stream.unwrap_or_default().to_owned()),
+    ]
+}
+
+impl MetricsCollector for MetricsFacadeCollector {
+    fn open_connection(&self, context: &MetricsContext<'_>) {
+        gauge!(
+            "rabbitmq_stream_connections",
+            connection_labels(context).iter()
+        )
+        .increment(1);
+    }
+
+    fn close_connection(&self, context: &MetricsContext<'_>) {
+        gauge!(
+            "rabbitmq_stream_connections",
+            connection_labels(context).iter()
+        )
+        .decrement(1);
+    }
+
+    fn connection_handshake(&self, context: &MetricsContext<'_>, duration: Duration) {
+        histogram!(
+            "rabbitmq_stream_connection_handshake_seconds",
+            connection_labels(context).iter()
+        )
+        .record(duration);
+    }
+
+    fn reconnect(&self, context: &MetricsContext<'_>) {
+        counter!(
+            "rabbitmq_stream_reconnects_total",
+            stream_labels(context).iter()
+        )
+        .increment(1);
+    }
+
+    fn heartbeat_rtt(&self, context: &MetricsContext<'_>, rtt: Duration) {
+        histogram!(
+            "rabbitmq_stream_heartbeat_rtt_seconds",
+            connection_labels(context).iter()
+        )
+        .record(rtt);
+    }
+
+    fn open_producer(&self, context: &MetricsContext<'_>) {
+        gauge!(
+            "rabbitmq_stream_producers",
+            connection_labels(context).iter()
+        )
+        .increment(1);
+    }
+
+    fn close_producer(&self, context: &MetricsContext<'_>) {
+        gauge!(
+            "rabbitmq_stream_producers",
+            connection_labels(context).iter()
+        )
+        .decrement(1);
+    }
+
+    fn open_consumer(&self, context: &MetricsContext<'_>) {
+        gauge!(
+            "rabbitmq_stream_consumers",
+            connection_labels(context).iter()
+        )
+        .increment(1);
+    }
+
+    fn close_consumer(&self, context: &MetricsContext<'_>) {
+        gauge!(
+            "rabbitmq_stream_consumers",
+            connection_labels(context).iter()
+        )
+        .decrement(1);
+    }
+
+    fn publish(&self, context: &MetricsContext<'_>, messages: u64) {
+        counter!(
+            "rabbitmq_stream_published_total",
+            stream_labels(context).iter()
+        )
+        .increment(messages);
+    }
+
+    fn published_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        counter!(
+            "rabbitmq_stream_published_bytes_total",
+            stream_labels(context).iter()
+        )
+        .increment(bytes);
+    }
+
+    fn publish_confirm(&self, context: &MetricsContext<'_>, messages: u64) {
+        counter!(
+            "rabbitmq_stream_confirmed_total",
+            stream_labels(context).iter()
+        )
+        .increment(messages);
+    }
+
+    fn publish_confirm_latency(&self, context: &MetricsContext<'_>, latency: Duration) {
+        histogram!(
+            "rabbitmq_stream_confirm_latency_seconds",
+            stream_labels(context).iter()
+        )
+        .record(latency);
+    }
+
+    fn publish_error(&self, context: &MetricsContext<'_>, messages: u64) {
+        counter!(
+            "rabbitmq_stream_errored_total",
+            stream_labels(context).iter()
+        )
+        .increment(messages);
+    }
+
+    fn chunk(&self, context: &MetricsContext<'_>, messages: u64) {
+        let labels = stream_labels(context);
+        counter!("rabbitmq_stream_chunks_total", labels.iter()).increment(1);
+        histogram!("rabbitmq_stream_chunk_size", labels.iter()).record(messages as f64);
+    }
+
+    fn delivered_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        counter!(
+            "rabbitmq_stream_delivered_bytes_total",
+            stream_labels(context).iter()
+        )
+        .increment(bytes);
+    }
+
+    fn consume(&self, context: &MetricsContext<'_>, messages: u64) {
+        counter!(
+            "rabbitmq_stream_consumed_total",
+            stream_labels(context).iter()
+        )
+        .increment(messages);
+    }
+
+    fn credit(&self, context: &MetricsContext<'_>, credits: u64) {
+        counter!(
+            "rabbitmq_stream_credits_total",
+            stream_labels(context).iter()
+        )
+        .increment(credits);
+    }
+
+    fn slow_consumer(&self, context: &MetricsContext<'_>) {
+        counter!(
+            "rabbitmq_stream_slow_consumers_total",
+            stream_labels(context).iter()
+        )
+        .increment(1);
+    }
+
+    fn lag(&self, context: &MetricsContext<'_>, messages: u64) {
+        gauge!(
+            "rabbitmq_stream_consumer_lag",
+            stream_labels(context).iter()
+        )
+        .set(messages as f64);
+    }
+
+    fn written_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        counter!(
+            "rabbitmq_stream_written_bytes_total",
+            connection_labels(context).iter()
+        )
+        .increment(bytes);
+    }
+
+    fn read_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        counter!(
+            "rabbitmq_stream_read_bytes_total",
+            connection_labels(context).iter()
+        )
+        .increment(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::MetricsFacadeCollector;
+    use crate::metrics::{MetricsCollector, MetricsContext};
+
+    #[test]
+    fn metrics_facade_collector_test() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let context = MetricsContext {
+            vhost: "/",
+            node: "localhost:5552",
+            stream: Some("orders"),
+        };
+        ::metrics::with_local_recorder(&recorder, || {
+            MetricsFacadeCollector.publish(&context, 3);
+            MetricsFacadeCollector.publish(&context, 2);
+            MetricsFacadeCollector.chunk(&context, 5);
+            MetricsFacadeCollector.lag(&context, 7);
+        });
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let published = metrics
+            .iter()
+            .find(|(key, ..)| key.key().name() == "rabbitmq_stream_published_total")
+            .unwrap();
+        assert_eq!(DebugValue::Counter(5), published.3);
+        assert!(published
+            .0
+            .key()
+            .labels()
+            .any(|label| label.key() == "stream" && label.value() == "orders"));
+        assert!(metrics
+            .iter()
+            .any(|(key, ..)| key.key().name() == "rabbitmq_stream_chunk_size"));
+        let lag = metrics
+            .iter()
+            .find(|(key, ..)| key.key().name() == "rabbitmq_stream_consumer_lag")
+            .unwrap();
+        assert_eq!(DebugValue::Gauge(7.0.into()), lag.3);
+    }
+}
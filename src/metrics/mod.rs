@@ -0,0 +1,239 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+mod counters;
+#[cfg(feature = "metrics-facade")]
+mod facade;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+
+pub use self::counters::{StreamCounters, StreamCountersSnapshot};
+#[cfg(feature = "metrics-facade")]
+pub use self::facade::MetricsFacadeCollector;
+#[cfg(feature = "prometheus")]
+pub use self::prometheus::PrometheusMetricsCollector;
+
+/// Receives the events of the connections, producers and consumers of an environment,
+/// see [`crate::EnvironmentBuilder::metrics_collector`]
+///
+/// The methods are called on the hot paths of the client and should not block, they do
+/// nothing by default.
+pub trait MetricsCollector: Send + Sync {
+    /// A connection was opened
+    fn open_connection(&self, _context: &MetricsContext<'_>) {}
+
+    /// A connection was closed by the client
+    fn close_connection(&self, _context: &MetricsContext<'_>) {}
+
+    /// A connection was opened and authenticated `duration` after connecting to the node
+    fn connection_handshake(&self, _context: &MetricsContext<'_>, _duration: Duration) {}
+
+    /// A producer or consumer recovered its connection
+    fn reconnect(&self, _context: &MetricsContext<'_>) {}
+
+    /// A frame was read `rtt` after a heartbeat was sent to the broker
+    fn heartbeat_rtt(&self, _context: &MetricsContext<'_>, _rtt: Duration) {}
+
+    /// A publisher was declared on a connection
+    fn open_producer(&self, _context: &MetricsContext<'_>) {}
+
+    /// A publisher was deleted, or its connection closed
+    fn close_producer(&self, _context: &MetricsContext<'_>) {}
+
+    /// A subscription was created on a connection
+    fn open_consumer(&self, _context: &MetricsContext<'_>) {}
+
+    /// A subscription was removed, or its connection closed
+    fn close_consumer(&self, _context: &MetricsContext<'_>) {}
+
+    /// `messages` messages were published
+    fn publish(&self, _context: &MetricsContext<'_>, _messages: u64) {}
+
+    /// Messages of `bytes` bytes were published
+    fn published_bytes(&self, _context: &MetricsContext<'_>, _bytes: u64) {}
+
+    /// `messages` messages were confirmed by the broker
+    fn publish_confirm(&self, _context: &MetricsContext<'_>, _messages: u64) {}
+
+    /// A message was confirmed `latency` after its publish frame was written
+    fn publish_confirm_latency(&self, _context: &MetricsContext<'_>, _latency: Duration) {}
+
+    /// `messages` messages were rejected by the broker or failed to be published
+    fn publish_error(&self, _context: &MetricsContext<'_>, _messages: u64) {}
+
+    /// A chunk of `messages` messages was delivered to a consumer
+    fn chunk(&self, _context: &MetricsContext<'_>, _messages: u64) {}
+
+    /// A chunk of messages of `bytes` bytes was delivered to a consumer
+    fn delivered_bytes(&self, _context: &MetricsContext<'_>, _bytes: u64) {}
+
+    /// `messages` messages of a chunk were handed to the application
+    fn consume(&self, _context: &MetricsContext<'_>, _messages: u64) {}
+
+    /// `credits` credits were asked to the broker for a subscription
+    fn credit(&self, _context: &MetricsContext<'_>, _credits: u64) {}
+
+    /// A consumer was reported slow
+    fn slow_consumer(&self, _context: &MetricsContext<'_>) {}
+
+    /// A consumer was `messages` messages behind the committed end of its stream, measured
+    /// by [`Consumer::lag`](crate::Consumer::lag)
+    fn lag(&self, _context: &MetricsContext<'_>, _messages: u64) {}
+
+    /// `bytes` bytes were written to a connection
+    fn written_bytes(&self, _context: &MetricsContext<'_>, _bytes: u64) {}
+
+    /// `bytes` bytes were read from a connection
+    fn read_bytes(&self, _context: &MetricsContext<'_>, _bytes: u64) {}
+}
+
+/// Collector shared with the application, to read the metrics it collects
+impl<T: MetricsCollector + ?Sized> MetricsCollector for Arc<T> {
+    fn open_connection(&self, context: &MetricsContext<'_>) {
+        (**self).open_connection(context)
+    }
+
+    fn close_connection(&self, context: &MetricsContext<'_>) {
+        (**self).close_connection(context)
+    }
+
+    fn connection_handshake(&self, context: &MetricsContext<'_>, duration: Duration) {
+        (**self).connection_handshake(context, duration)
+    }
+
+    fn reconnect(&self, context: &MetricsContext<'_>) {
+        (**self).reconnect(context)
+    }
+
+    fn heartbeat_rtt(&self, context: &MetricsContext<'_>, rtt: Duration) {
+        (**self).heartbeat_rtt(context, rtt)
+    }
+
+    fn open_producer(&self, context: &MetricsContext<'_>) {
+        (**self).open_producer(context)
+    }
+
+    fn close_producer(&self, context: &MetricsContext<'_>) {
+        (**self).close_producer(context)
+    }
+
+    fn open_consumer(&self, context: &MetricsContext<'_>) {
+        (**self).open_consumer(context)
+    }
+
+    fn close_consumer(&self, context: &MetricsContext<'_>) {
+        (**self).close_consumer(context)
+    }
+
+    fn publish(&self, context: &MetricsContext<'_>, messages: u64) {
+        (**self).publish(context, messages)
+    }
+
+    fn published_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        (**self).published_bytes(context, bytes)
+    }
+
+    fn publish_confirm(&self, context: &MetricsContext<'_>, messages: u64) {
+        (**self).publish_confirm(context, messages)
+    }
+
+    fn publish_confirm_latency(&self, context: &MetricsContext<'_>, latency: Duration) {
+        (**self).publish_confirm_latency(context, latency)
+    }
+
+    fn publish_error(&self, context: &MetricsContext<'_>, messages: u64) {
+        (**self).publish_error(context, messages)
+    }
+
+    fn chunk(&self, context: &MetricsContext<'_>, messages: u64) {
+        (**self).chunk(context, messages)
+    }
+
+    fn delivered_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        (**self).delivered_bytes(context, bytes)
+    }
+
+    fn consume(&self, context: &MetricsContext<'_>, messages: u64) {
+        (**self).consume(context, messages)
+    }
+
+    fn credit(&self, context: &MetricsContext<'_>, credits: u64) {
+        (**self).credit(context, credits)
+    }
+
+    fn slow_consumer(&self, context: &MetricsContext<'_>) {
+        (**self).slow_consumer(context)
+    }
+
+    fn lag(&self, context: &MetricsContext<'_>, messages: u64) {
+        (**self).lag(context, messages)
+    }
+
+    fn written_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        (**self).written_bytes(context, bytes)
+    }
+
+    fn read_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        (**self).read_bytes(context, bytes)
+    }
+}
+
+impl fmt::Debug for dyn MetricsCollector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MetricsCollector")
+    }
+}
+
+/// Where an event of a [`MetricsCollector`] happened
+#[derive(Clone, Copy, Debug)]
+pub struct MetricsContext<'a> {
+    pub vhost: &'a str,
+    /// Host and port of the broker node
+    pub node: &'a str,
+    /// `None` for the connection events
+    pub stream: Option<&'a str>,
+}
+
+/// [`MetricsCollector`] and [`StreamCounters`] of an environment with the labels of its
+/// connections
+#[derive(Clone)]
+pub(crate) struct Metrics {
+    collector: Option<Arc<dyn MetricsCollector>>,
+    counters: Option<Arc<StreamCounters>>,
+    vhost: Arc<str>,
+    node: Arc<str>,
+}
+
+impl Metrics {
+    pub(crate) fn new(
+        collector: Option<Arc<dyn MetricsCollector>>,
+        counters: Option<Arc<StreamCounters>>,
+        vhost: &str,
+        node: &str,
+    ) -> Self {
+        Metrics {
+            collector,
+            counters,
+            vhost: vhost.into(),
+            node: node.into(),
+        }
+    }
+
+    /// Report an event of `stream`, or of the connection
+    pub(crate) fn record(
+        &self,
+        stream: Option<&str>,
+        event: impl Fn(&dyn MetricsCollector, &MetricsContext<'_>),
+    ) {
+        let context = MetricsContext {
+            vhost: &self.vhost,
+            node: &self.node,
+            stream,
+        };
+        if let Some(collector) = &self.collector {
+            event(collector.as_ref(), &context);
+        }
+        if let Some(counters) = &self.counters {
+            event(counters.as_ref(), &context);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use std::time::Duration;
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, Result};
+
+use super::{MetricsCollector, MetricsContext};
+
+const CONNECTION_LABELS: &[&str] = &["vhost", "node"];
+const STREAM_LABELS: &[&str] = &["vhost", "node", "stream"];
+
+/// [`MetricsCollector`] registering its metrics in a Prometheus [`Registry`]
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use rabbitmq_stream_client::{types::PrometheusMetricsCollector, Environment};
+///
+/// let registry = prometheus::Registry::new();
+/// let environment = Environment::builder()
+///     .metrics_collector(PrometheusMetricsCollector::new(&registry)?)
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The connection metrics are labeled with the virtual host and the node, the stream
+/// metrics also with the stream.
+#[derive(Clone)]
+pub struct PrometheusMetricsCollector {
+    connections: IntGaugeVec,
+    handshake_duration: HistogramVec,
+    reconnects: IntCounterVec,
+    heartbeat_rtt: HistogramVec,
+    producers: IntGaugeVec,
+    consumers: IntGaugeVec,
+    published: IntCounterVec,
+    published_bytes: IntCounterVec,
+    confirmed: IntCounterVec,
+    confirm_latency: HistogramVec,
+    errored: IntCounterVec,
+    chunks: IntCounterVec,
+    chunk_size: HistogramVec,
+    delivered_bytes: IntCounterVec,
+    consumed: IntCounterVec,
+    credits: IntCounterVec,
+    slow_consumers: IntCounterVec,
+    lag: IntGaugeVec,
+    written_bytes: IntCounterVec,
+    read_bytes: IntCounterVec,
+}
+
+impl PrometheusMetricsCollector {
+    /// Create the metrics and register them in `registry`
+    pub fn new(registry: &Registry) -> Result<Self> {
+        let collector = PrometheusMetricsCollector {
+            connections: IntGaugeVec::new(
+                Opts::new("rabbitmq_stream_connections", "Open connections"),
+                CONNECTION_LABELS,
+            )?,
+            handshake_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "rabbitmq_stream_connection_handshake_seconds",
+                    "Time to connect to a node and authenticate",
+                )
+                .buckets(prometheus::exponential_buckets(0.001, 2.0, 14)?),
+                CONNECTION_LABELS,
+            )?,
+            reconnects: counter(
+                "reconnects_total",
+                "Connections recovered by producers and consumers",
+                STREAM_LABELS,
+            )?,
+            heartbeat_rtt: HistogramVec::new(
+                HistogramOpts::new(
+                    "rabbitmq_stream_heartbeat_rtt_seconds",
+                    "Time between a heartbeat sent to the broker and the next frame read",
+                )
+                .buckets(prometheus::exponential_buckets(0.0005, 2.0, 16)?),
+                CONNECTION_LABELS,
+            )?,
+            producers: IntGaugeVec::new(
+                Opts::new(
+                    "rabbitmq_stream_producers",
+                    "Publishers declared on open connections",
+                ),
+                CONNECTION_LABELS,
+            )?,
+            consumers: IntGaugeVec::new(
+                Opts::new(
+                    "rabbitmq_stream_consumers",
+                    "Subscriptions of open connections",
+                ),
+                CONNECTION_LABELS,
+            )?,
+            published: counter("published_total", "Published messages", STREAM_LABELS)?,
+            published_bytes: counter(
+                "published_bytes_total",
+                "Bytes of the published messages",
+                STREAM_LABELS,
+            )?,
+            confirmed: counter("confirmed_total", "Confirmed messages", STREAM_LABELS)?,
+            confirm_latency: HistogramVec::new(
+                HistogramOpts::new(
+                    "rabbitmq_stream_confirm_latency_seconds",
+                    "Time between the publish of a message and its confirm",
+                )
+                .buckets(prometheus::exponential_buckets(0.0005, 2.0, 16)?),
+                STREAM_LABELS,
+            )?,
+            errored: counter(
+                "errored_total",
+                "Messages rejected or failed to publish",
+                STREAM_LABELS,
+            )?,
+            chunks: counter("chunks_total", "Delivered chunks", STREAM_LABELS)?,
+            chunk_size: HistogramVec::new(
+                HistogramOpts::new("rabbitmq_stream_chunk_size", "Messages per delivered chunk")
+                    .buckets(prometheus::exponential_buckets(1.0, 4.0, 8)?),
+                STREAM_LABELS,
+            )?,
+            delivered_bytes: counter(
+                "delivered_bytes_total",
+                "Bytes of the messages of the delivered chunks",
+                STREAM_LABELS,
+            )?,
+            consumed: counter("consumed_total", "Consumed messages", STREAM_LABELS)?,
+            credits: counter(
+                "credits_total",
+                "Credits asked to the broker",
+                STREAM_LABELS,
+            )?,
+            slow_consumers: counter(
+                "slow_consumers_total",
+                "Times consumers were reported slow",
+                STREAM_LABELS,
+            )?,
+            lag: IntGaugeVec::new(
+                Opts::new(
+                    "rabbitmq_stream_consumer_lag",
+                    "Messages the consumers were behind the end of their stream",
+                ),
+                STREAM_LABELS,
+            )?,
+            written_bytes: counter(
+                "written_bytes_total",
+                "Bytes written to the connections",
+                CONNECTION_LABELS,
+            )?,
+            read_bytes: counter(
+                "read_bytes_total",
+                "Bytes read from the connections",
+                CONNECTION_LABELS,
+            )?,
+        };
+
+        for gauge in [
+            &collector.connections,
+            &collector.producers,
+            &collector.consumers,
+            &collector.lag,
+        ] {
+            registry.register(Box::new(gauge.clone()))?;
+        }
+        for counter in [
+            &collector.reconnects,
+            &collector.published,
+            &collector.published_bytes,
+            &collector.confirmed,
+            &collector.errored,
+            &collector.chunks,
+            &collector.delivered_bytes,
+            &collector.consumed,
+            &collector.credits,
+            &collector.slow_consumers,
+            &collector.written_bytes,
+            &collector.read_bytes,
+        ] {
+            registry.register(Box::new(counter.clone()))?;
+        }
+        registry.register(Box::new(collector.handshake_duration.clone()))?;
+        registry.register(Box::new(collector.heartbeat_rtt.clone()))?;
+        registry.register(Box::new(collector.confirm_latency.clone()))?;
+        registry.register(Box::new(collector.chunk_size.clone()))?;
+
+        Ok(collector)
+    }
+}
+
+fn counter(name: &str, help: &str, labels: &[&str]) -> Result<IntCounterVec> {
+    IntCounterVec::new(Opts::new(format!("rabbitmq_stream_{}", name), help), labels)
+}
+
+fn connection_labels<'a>(context: &MetricsContext<'a>) -> [&'a str; 2] {
+    [context.vhost, context.node]
+}
+
+fn stream_labels<'a>(context: &MetricsContext<'a>) -> [&'a str; 3] {
+    [
+        context.vhost,
+        context.node,
+        context.stream.unwrap_or_default(),
+    ]
+}
+
+impl MetricsCollector for PrometheusMetricsCollector {
+    fn open_connection(&self, context: &MetricsContext<'_>) {
+        self.connections
+            .with_label_values(&connection_labels(context))
+            .inc();
+    }
+
+    fn close_connection(&self, context: &MetricsContext<'_>) {
+        self.connections
+            .with_label_values(&connection_labels(context))
+            .dec();
+    }
+
+    fn connection_handshake(&self, context: &MetricsContext<'_>, duration: Duration) {
+        self.handshake_duration
+            .with_label_values(&connection_labels(context))
+            .observe(duration.as_secs_f64());
+    }
+
+    fn reconnect(&self, context: &MetricsContext<'_>) {
+        self.reconnects
+            .with_label_values(&stream_labels(context))
+            .inc();
+    }
+
+    fn heartbeat_rtt(&self, context: &MetricsContext<'_>, rtt: Duration) {
+        self.heartbeat_rtt
+            .with_label_values(&connection_labels(context))
+            .observe(rtt.as_secs_f64());
+    }
+
+    fn open_producer(&self, context: &MetricsContext<'_>) {
+        self.producers
+            .with_label_values(&connection_labels(context))
+            .inc();
+    }
+
+    fn close_producer(&self, context: &MetricsContext<'_>) {
+        self.producers
+            .with_label_values(&connection_labels(context))
+            .dec();
+    }
+
+    fn open_consumer(&self, context: &MetricsContext<'_>) {
+        self.consumers
+            .with_label_values(&connection_labels(context))
+            .inc();
+    }
+
+    fn close_consumer(&self, context: &MetricsContext<'_>) {
+        self.consumers
+            .with_label_values(&connection_labels(context))
+            .dec();
+    }
+
+    fn publish(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.published
+            .with_label_values(&stream_labels(context))
+            .inc_by(messages);
+    }
+
+    fn published_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        self.published_bytes
+            .with_label_values(&stream_labels(context))
+            .inc_by(bytes);
+    }
+
+    fn publish_confirm(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.confirmed
+            .with_label_values(&stream_labels(context))
+            .inc_by(messages);
+    }
+
+    fn publish_confirm_latency(&self, context: &MetricsContext<'_>, latency: Duration) {
+        self.confirm_latency
+            .with_label_values(&stream_labels(context))
+            .observe(latency.as_secs_f64());
+    }
+
+    fn publish_error(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.errored
+            .with_label_values(&stream_labels(context))
+            .inc_by(messages);
+    }
+
+    fn chunk(&self, context: &MetricsContext<'_>, messages: u64) {
+        let labels = stream_labels(context);
+        self.chunks.with_label_values(&labels).inc();
+        self.chunk_size
+            .with_label_values(&labels)
+            .observe(messages as f64);
+    }
+
+    fn delivered_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        self.delivered_bytes
+            .with_label_values(&stream_labels(context))
+            .inc_by(bytes);
+    }
+
+    fn consume(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.consumed
+            .with_label_values(&stream_labels(context))
+            .inc_by(messages);
+    }
+
+    fn credit(&self, context: &MetricsContext<'_>, credits: u64) {
+        self.credits
+            .with_label_values(&stream_labels(context))
+            .inc_by(credits);
+    }
+
+    fn slow_consumer(&self, context: &MetricsContext<'_>) {
+        self.slow_consumers
+            .with_label_values(&stream_labels(context))
+            .inc();
+    }
+
+    fn lag(&self, context: &MetricsContext<'_>, messages: u64) {
+        self.lag
+            .with_label_values(&stream_labels(context))
+            .set(messages as i64);
+    }
+
+    fn written_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        self.written_bytes
+            .with_label_values(&connection_labels(context))
+            .inc_by(bytes);
+    }
+
+    fn read_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        self.read_bytes
+            .with_label_values(&connection_labels(context))
+            .inc_by(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::Registry;
+
+    use super::PrometheusMetricsCollector;
+    use crate::metrics::{MetricsCollector, MetricsContext};
+
+    #[test]
+    fn prometheus_metrics_collector_test() {
+        let registry = Registry::new();
+        let collector = PrometheusMetricsCollector::new(&registry).unwrap();
+        let context = MetricsContext {
+            vhost: "/",
+            node: "localhost:5552",
+            stream: Some("orders"),
+        };
+        collector.publish(&context, 3);
+        collector.publish(&context, 2);
+        collector.chunk(&context, 5);
+        collector.open_producer(&context);
+        collector.open_producer(&context);
+        collector.close_producer(&context);
+        collector.lag(&context, 7);
+
+        let published = collector
+            .published
+            .with_label_values(&["/", "localhost:5552", "orders"])
+            .get();
+        assert_eq!(5, published);
+        let producers = collector
+            .producers
+            .with_label_values(&["/", "localhost:5552"])
+            .get();
+        assert_eq!(1, producers);
+        let lag = collector
+            .lag
+            .with_label_values(&["/", "localhost:5552", "orders"])
+            .get();
+        assert_eq!(7, lag);
+        assert!(registry
+            .gather()
+            .iter()
+            .any(|family| family.get_name() == "rabbitmq_stream_chunk_size"));
+
+        assert!(PrometheusMetricsCollector::new(&registry).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::error::ClientError;
+
+/// Longest stream, super stream or virtual host name accepted by the broker, in bytes
+const MAX_LENGTH: usize = 255;
+/// Prefix of the names reserved by the broker
+const RESERVED_PREFIX: &str = "amq.";
+
+/// Check a stream name before sending it, the broker would refuse it with a precondition failure
+pub(crate) fn validate_stream(stream: &str) -> Result<(), ClientError> {
+    validate("stream", stream, true)
+}
+
+/// Check a super stream name, its partitions are checked as streams
+pub(crate) fn validate_super_stream(super_stream: &str) -> Result<(), ClientError> {
+    validate("super stream", super_stream, true)
+}
+
+/// Check a virtual host name before opening a connection to it
+pub(crate) fn validate_vhost(vhost: &str) -> Result<(), ClientError> {
+    validate("virtual host", vhost, false)
+}
+
+fn validate(kind: &'static str, name: &str, reserved: bool) -> Result<(), ClientError> {
+    let reason = if name.is_empty() {
+        "the name is empty"
+    } else if name.len() > MAX_LENGTH {
+        "the name is longer than 255 bytes"
+    } else if name.chars().any(char::is_control) {
+        "the name contains control characters"
+    } else if reserved && name.starts_with(RESERVED_PREFIX) {
+        "the amq. prefix is reserved by the broker"
+    } else {
+        return Ok(());
+    };
+    Err(ClientError::InvalidName {
+        kind,
+        name: name.to_owned(),
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_stream, validate_super_stream, validate_vhost};
+    use crate::error::ClientError;
+
+    fn reason(result: Result<(), ClientError>) -> &'static str {
+        match result {
+            Err(ClientError::InvalidName { reason, .. }) => reason,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn valid_names_test() {
+        assert!(validate_stream("orders").is_ok());
+        assert!(validate_stream("commandes-été.v2").is_ok());
+        assert!(validate_stream(&"a".repeat(255)).is_ok());
+        assert!(validate_super_stream("invoices").is_ok());
+        assert!(validate_vhost("/").is_ok());
+        assert!(validate_vhost("amq.vhost").is_ok());
+    }
+
+    #[test]
+    fn invalid_names_test() {
+        assert_eq!("the name is empty", reason(validate_stream("")));
+        assert_eq!(
+            "the name is longer than 255 bytes",
+            reason(validate_stream(&"é".repeat(128)))
+        );
+        assert_eq!(
+            "the name contains control characters",
+            reason(validate_vhost("prod\n"))
+        );
+        assert_eq!(
+            "the amq. prefix is reserved by the broker",
+            reason(validate_super_stream("amq.invoices"))
+        );
+    }
+
+    #[test]
+    fn display_test() {
+        assert_eq!(
+            "Invalid stream name \"\": the name is empty",
+            validate_stream("").unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::BTreeSet, future::Future, sync::Mutex, time::Duration};
+
+use rabbitmq_stream_protocol::ResponseCode;
+
+use crate::{error::OffsetStoreError, Client};
+
+/// Persistence of consumer offsets, by reference (the consumer name) and stream
+///
+/// The default implementation is [`BrokerOffsetStore`], other implementations can checkpoint
+/// offsets in an external system, e.g. in the same transaction as the processing results.
+#[async_trait::async_trait]
+pub trait OffsetStore: Send + Sync {
+    /// Load the stored offset, `None` if nothing was stored yet
+    async fn load(&self, reference: &str, stream: &str) -> Result<Option<u64>, OffsetStoreError>;
+
+    /// Store the offset
+    async fn store(
+        &self,
+        reference: &str,
+        stream: &str,
+        offset: u64,
+    ) -> Result<(), OffsetStoreError>;
+}
+
+/// [`OffsetStore`] keeping offsets in the broker with the StoreOffset and QueryOffset commands
+pub struct BrokerOffsetStore {
+    client: Client,
+}
+
+impl BrokerOffsetStore {
+    pub fn new(client: Client) -> Self {
+        BrokerOffsetStore { client }
+    }
+}
+
+#[async_trait::async_trait]
+impl OffsetStore for BrokerOffsetStore {
+    async fn load(&self, reference: &str, stream: &str) -> Result<Option<u64>, OffsetStoreError> {
+        let response = self
+            .client
+            .query_offset(reference.to_owned(), stream)
+            .await?;
+
+        match response.code() {
+            ResponseCode::Ok => Ok(Some(response.offset())),
+            ResponseCode::NoOffset => Ok(None),
+            status => Err(OffsetStoreError::QueryOffset {
+                stream: stream.to_owned(),
+                reference: reference.to_owned(),
+                status: status.clone(),
+            }),
+        }
+    }
+
+    async fn store(
+        &self,
+        reference: &str,
+        stream: &str,
+        offset: u64,
+    ) -> Result<(), OffsetStoreError> {
+        Ok(self.client.store_offset(reference, stream, offset).await?)
+    }
+}
+
+/// Automatic offset tracking for named consumers
+///
+/// The offset of the last message consumed is stored with the [`OffsetStore`] every
+/// `message_count` messages or every `flush_interval`, whichever comes first,
+/// and once more when the consumer is closed.
+#[derive(Clone, Debug)]
+pub struct AutoOffsetTracking {
+    pub(crate) message_count: u64,
+    pub(crate) flush_interval: Duration,
+}
+
+impl Default for AutoOffsetTracking {
+    fn default() -> Self {
+        AutoOffsetTracking {
+            message_count: 10_000,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl AutoOffsetTracking {
+    /// Number of messages consumed before the offset is stored, defaults to 10000
+    pub fn message_count(mut self, message_count: u64) -> Self {
+        self.message_count = message_count.max(1);
+        self
+    }
+
+    /// Interval at which the offset is stored if it changed, defaults to 5 seconds
+    pub fn flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+}
+
+#[derive(Default)]
+struct TrackerState {
+    last_offset: Option<u64>,
+    stored_offset: Option<u64>,
+    since_store: u64,
+}
+
+/// Keeps the last consumed offset and decides when it must be stored
+pub(crate) struct OffsetTracker {
+    pub(crate) strategy: AutoOffsetTracking,
+    state: Mutex<TrackerState>,
+    /// Held while an offset is stored, so an older offset never overwrites a newer one
+    storing: tokio::sync::Mutex<()>,
+}
+
+impl OffsetTracker {
+    pub(crate) fn new(strategy: AutoOffsetTracking) -> Self {
+        OffsetTracker {
+            strategy,
+            state: Mutex::new(TrackerState::default()),
+            storing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Record a consumed offset, returns true when the message count is reached
+    pub(crate) fn track(&self, offset: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.last_offset = Some(offset);
+        state.since_store += 1;
+
+        let due = state.since_store >= self.strategy.message_count;
+        if due {
+            state.since_store = 0;
+        }
+        due
+    }
+
+    /// Return the last consumed offset if it was not stored yet
+    pub(crate) fn pending(&self) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        state
+            .last_offset
+            .filter(|_| state.last_offset != state.stored_offset)
+    }
+
+    /// Store the last consumed offset with `store` if it was not stored yet, one store at
+    /// a time, the offset is marked stored once `store` succeeded
+    pub(crate) async fn store_pending<F, Fut, E>(&self, store: F) -> Result<(), E>
+    where
+        F: FnOnce(u64) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+    {
+        let _storing = self.storing.lock().await;
+        let offset = match self.pending() {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        store(offset).await?;
+        self.state.lock().unwrap().stored_offset = Some(offset);
+        Ok(())
+    }
+}
+
+/// Offsets of the messages processed concurrently, completed in any order
+#[derive(Default)]
+pub(crate) struct OrderedCompletions {
+    in_progress: BTreeSet<u64>,
+    first_started: Option<u64>,
+    last_started: Option<u64>,
+}
+
+impl OrderedCompletions {
+    pub(crate) fn start(&mut self, offset: u64) {
+        self.in_progress.insert(offset);
+        self.first_started.get_or_insert(offset);
+        self.last_started = Some(offset);
+    }
+
+    /// Mark `offset` completed, returns the highest offset up to which every message started
+    /// is completed, the one which can be committed
+    pub(crate) fn complete(&mut self, offset: u64) -> Option<u64> {
+        self.in_progress.remove(&offset);
+        match self.in_progress.first() {
+            None => self.last_started,
+            Some(first) if self.first_started.is_some_and(|started| started < *first) => {
+                Some(first - 1)
+            }
+            Some(_) => None,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.in_progress.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutoOffsetTracking, OffsetTracker, OrderedCompletions};
+
+    #[tokio::test]
+    async fn offset_tracker_message_count_test() {
+        let tracker = OffsetTracker::new(AutoOffsetTracking::default().message_count(3));
+
+        assert!(!tracker.track(0));
+        assert!(!tracker.track(1));
+        assert!(tracker.track(2));
+        assert_eq!(Some(2), tracker.pending());
+        tracker
+            .store_pending(|_| async { Ok::<_, ()>(()) })
+            .await
+            .unwrap();
+        assert_eq!(None, tracker.pending());
+
+        assert!(!tracker.track(3));
+        assert_eq!(Some(3), tracker.pending());
+    }
+
+    #[tokio::test]
+    async fn offset_tracker_failed_store_test() {
+        let tracker = OffsetTracker::new(AutoOffsetTracking::default());
+        tracker.track(7);
+
+        // the offset stays pending until a store succeeds
+        assert!(tracker.store_pending(|_| async { Err(()) }).await.is_err());
+        assert_eq!(Some(7), tracker.pending());
+        tracker
+            .store_pending(|offset| async move {
+                assert_eq!(7, offset);
+                Ok::<_, ()>(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(None, tracker.pending());
+    }
+
+    #[test]
+    fn ordered_completions_test() {
+        let mut completions = OrderedCompletions::default();
+        for offset in 5..9 {
+            completions.start(offset);
+        }
+
+        assert_eq!(None, completions.complete(6));
+        assert_eq!(None, completions.complete(7));
+        assert_eq!(Some(7), completions.complete(5));
+        assert!(!completions.is_empty());
+
+        completions.start(9);
+        assert_eq!(Some(7), completions.complete(9));
+        assert_eq!(Some(9), completions.complete(8));
+        assert!(completions.is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+//! OpenTelemetry tracing of the published and consumed messages
+//!
+//! The [`OtelProducerInterceptor`] starts a span per published message and injects its
+//! context in the message annotations, the [`OtelConsumerInterceptor`] extracts it back
+//! and starts a span per delivered message as its child, so traces follow the messages
+//! across streams.
+//!
+//! The context is written with the propagator installed with
+//! [`opentelemetry::global::set_text_map_propagator`], a W3C `TraceContextPropagator`
+//! writes the `traceparent` and `tracestate` annotations.
+
+use std::collections::HashMap;
+
+use opentelemetry::{
+    global,
+    trace::{SpanKind, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use rabbitmq_stream_protocol::message::{Message, Value};
+
+use crate::interceptor::{ConsumerInterceptor, InterceptorError, ProducerInterceptor};
+
+/// Name of the tracer the spans are started with
+pub const TRACER_NAME: &str = "rabbitmq-stream-client";
+
+const MESSAGING_SYSTEM: &str = "rabbitmq";
+
+/// Write `context` in the annotations of `message`
+pub fn inject_context(message: Message, context: &Context) -> Message {
+    let mut fields = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(context, &mut fields));
+    if fields.is_empty() {
+        return message;
+    }
+    fields
+        .into_iter()
+        .fold(message.into_builder(), |builder, (key, value)| {
+            builder.message_annotation(key, value)
+        })
+        .build()
+}
+
+/// Read the context written in the annotations of `message` by the producer
+///
+/// Use it as the parent of the spans processing the message.
+pub fn extract_context(message: &Message) -> Context {
+    let fields: HashMap<String, String> = message
+        .message_annotations()
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            Value::String(value) | Value::Symbol(value) => Some((key, value)),
+            _ => None,
+        })
+        .collect();
+    global::get_text_map_propagator(|propagator| propagator.extract(&fields))
+}
+
+/// [`ProducerInterceptor`] starting a `send` span per message, child of the current context
+#[derive(Debug, Clone, Default)]
+pub struct OtelProducerInterceptor;
+
+impl OtelProducerInterceptor {
+    pub fn new() -> Self {
+        OtelProducerInterceptor
+    }
+}
+
+impl ProducerInterceptor for OtelProducerInterceptor {
+    fn on_send(&self, stream: &str, message: Message) -> Result<Message, InterceptorError> {
+        let tracer = global::tracer(TRACER_NAME);
+        let parent = Context::current();
+        let span = tracer
+            .span_builder(format!("send {}", stream))
+            .with_kind(SpanKind::Producer)
+            .with_attributes([
+                KeyValue::new("messaging.system", MESSAGING_SYSTEM),
+                KeyValue::new("messaging.operation.type", "send"),
+                KeyValue::new("messaging.destination.name", stream.to_owned()),
+            ])
+            .start_with_context(&tracer, &parent);
+        let context = parent.with_span(span);
+
+        Ok(inject_context(message, &context))
+    }
+}
+
+/// [`ConsumerInterceptor`] starting a `receive` span per message, child of the context
+/// injected by the producer
+#[derive(Debug, Clone, Default)]
+pub struct OtelConsumerInterceptor;
+
+impl OtelConsumerInterceptor {
+    pub fn new() -> Self {
+        OtelConsumerInterceptor
+    }
+}
+
+impl ConsumerInterceptor for OtelConsumerInterceptor {
+    fn on_delivery(
+        &self,
+        stream: &str,
+        offset: u64,
+        message: Message,
+    ) -> Result<Message, InterceptorError> {
+        let tracer = global::tracer(TRACER_NAME);
+        let parent = extract_context(&message);
+        let _span = tracer
+            .span_builder(format!("receive {}", stream))
+            .with_kind(SpanKind::Consumer)
+            .with_attributes([
+                KeyValue::new("messaging.system", MESSAGING_SYSTEM),
+                KeyValue::new("messaging.operation.type", "receive"),
+                KeyValue::new("messaging.destination.name", stream.to_owned()),
+                KeyValue::new("messaging.rabbitmq.stream.offset", offset as i64),
+            ])
+            .start_with_context(&tracer, &parent);
+
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::{
+        global,
+        trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+        Context,
+    };
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use rabbitmq_stream_protocol::message::{Message, Value};
+
+    use super::{extract_context, OtelConsumerInterceptor, OtelProducerInterceptor};
+    use crate::interceptor::{ConsumerInterceptor, ProducerInterceptor};
+
+    #[test]
+    fn otel_context_propagation_test() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let trace_id = TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap();
+        let span_context = SpanContext::new(
+            trace_id,
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let _guard = Context::new()
+            .with_remote_span_context(span_context)
+            .attach();
+
+        let message = OtelProducerInterceptor::new()
+            .on_send("orders", Message::builder().body("order").build())
+            .unwrap();
+        match message.message_annotation("traceparent") {
+            Some(Value::String(traceparent)) => {
+                assert!(traceparent.contains(&trace_id.to_string()))
+            }
+            other => panic!("unexpected traceparent {:?}", other),
+        }
+
+        let message = OtelConsumerInterceptor::new()
+            .on_delivery("orders", 0, message)
+            .unwrap();
+        let context = extract_context(&message);
+        assert_eq!(trace_id, context.span().span_context().trace_id());
+        assert_eq!(Some(b"order".as_ref()), message.data());
+    }
+}
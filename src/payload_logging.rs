@@ -0,0 +1,181 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use bytes::Bytes;
+use rabbitmq_stream_protocol::message::{Message, Properties, Value};
+use tracing::debug;
+
+use crate::interceptor::{ConsumerInterceptor, InterceptorError, ProducerInterceptor};
+
+/// Target of the events logged by a [`PayloadLogger`], to enable them on their own
+pub const PAYLOAD_LOG_TARGET: &str = "rabbitmq_stream_client::payload";
+
+/// Message sampled by a [`PayloadLogger`], passed to its redaction callback before it is
+/// logged
+#[derive(Clone, Debug, PartialEq)]
+pub struct PayloadSample {
+    pub stream: String,
+    /// `None` for the published messages
+    pub offset: Option<u64>,
+    pub properties: Option<Properties>,
+    pub application_properties: Vec<(String, Value)>,
+    /// Start of the body, see [`PayloadLogger::max_body_size`]
+    pub body: Bytes,
+    /// Size of the whole body
+    pub body_size: usize,
+}
+
+type Redaction = Arc<dyn Fn(&mut PayloadSample) + Send + Sync>;
+
+/// Interceptor logging a sample of the published and consumed messages at debug level
+/// with the [`PAYLOAD_LOG_TARGET`] target
+///
+/// ```
+/// use rabbitmq_stream_client::types::PayloadLogger;
+///
+/// let logger = PayloadLogger::new(|sample| {
+///     sample.application_properties.retain(|(key, _)| key != "email");
+/// })
+/// .sample_every(1000)
+/// .max_body_size(64);
+/// ```
+///
+/// Add it to the producers and consumers to debug with
+/// [`crate::ProducerBuilder::interceptor`] and [`crate::ConsumerBuilder::interceptor`],
+/// its clones share the sampling. The messages go through `redact` before they are
+/// logged, it should remove or mask the personal data of the properties and the body.
+#[derive(Clone)]
+pub struct PayloadLogger {
+    redact: Redaction,
+    every: u64,
+    max_body_size: usize,
+    seen: Arc<AtomicU64>,
+}
+
+impl PayloadLogger {
+    /// Log one message out of 100 with the first 256 bytes of its body
+    pub fn new(redact: impl Fn(&mut PayloadSample) + Send + Sync + 'static) -> Self {
+        PayloadLogger {
+            redact: Arc::new(redact),
+            every: 100,
+            max_body_size: 256,
+            seen: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Log one message out of `every`, every message with 1
+    pub fn sample_every(mut self, every: u64) -> Self {
+        self.every = every.max(1);
+        self
+    }
+
+    /// Bytes of the body logged, the rest is dropped before the redaction
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Redacted sample of `message` if it is one of the sampled messages
+    fn sample(
+        &self,
+        stream: &str,
+        offset: Option<u64>,
+        message: &Message,
+    ) -> Option<PayloadSample> {
+        if !self
+            .seen
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(self.every)
+        {
+            return None;
+        }
+        let body = message.data().unwrap_or_default();
+        let mut sample = PayloadSample {
+            stream: stream.to_owned(),
+            offset,
+            properties: message.properties(),
+            application_properties: message.application_properties(),
+            body: Bytes::copy_from_slice(&body[..body.len().min(self.max_body_size)]),
+            body_size: body.len(),
+        };
+        (self.redact)(&mut sample);
+        Some(sample)
+    }
+
+    fn log(&self, stream: &str, offset: Option<u64>, message: &Message) {
+        if !tracing::enabled!(target: PAYLOAD_LOG_TARGET, tracing::Level::DEBUG) {
+            return;
+        }
+        if let Some(sample) = self.sample(stream, offset, message) {
+            debug!(
+                target: PAYLOAD_LOG_TARGET,
+                stream = sample.stream.as_str(),
+                offset = ?sample.offset,
+                properties = ?sample.properties,
+                application_properties = ?sample.application_properties,
+                body = %String::from_utf8_lossy(&sample.body),
+                body_size = sample.body_size,
+                "{}",
+                if offset.is_some() { "Consumed message" } else { "Published message" },
+            );
+        }
+    }
+}
+
+impl ProducerInterceptor for PayloadLogger {
+    fn on_send(&self, stream: &str, message: Message) -> Result<Message, InterceptorError> {
+        self.log(stream, None, &message);
+        Ok(message)
+    }
+}
+
+impl ConsumerInterceptor for PayloadLogger {
+    fn on_delivery(
+        &self,
+        stream: &str,
+        offset: u64,
+        message: Message,
+    ) -> Result<Message, InterceptorError> {
+        self.log(stream, Some(offset), &message);
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::message::{Message, Value};
+
+    use super::PayloadLogger;
+
+    #[test]
+    fn payload_logger_sample_test() {
+        let logger = PayloadLogger::new(|sample| {
+            sample
+                .application_properties
+                .retain(|(key, _)| key != "email");
+        })
+        .sample_every(2)
+        .max_body_size(5);
+        let message = Message::builder()
+            .body(b"hello world".to_vec())
+            .application_property("email", "user@example.com")
+            .application_property("country", "fr")
+            .build();
+
+        let sample = logger.sample("orders", Some(10), &message).unwrap();
+        assert_eq!("orders", sample.stream);
+        assert_eq!(Some(10), sample.offset);
+        assert_eq!(&b"hello"[..], &sample.body[..]);
+        assert_eq!(11, sample.body_size);
+        assert_eq!(
+            vec![("country".to_owned(), Value::from("fr"))],
+            sample.application_properties
+        );
+
+        // the clones share the sampling
+        assert!(logger.clone().sample("orders", None, &message).is_none());
+        assert!(logger.sample("orders", None, &message).is_some());
+    }
+}
@@ -0,0 +1,169 @@
+use std::{future::Future, sync::Arc};
+
+use futures::{future::BoxFuture, FutureExt};
+use rabbitmq_stream_protocol::message::Message;
+use tokio::sync::Mutex;
+use tracing::trace;
+
+use crate::{Environment, Producer};
+
+type PoisonMessageCallback = Arc<dyn Fn(PoisonMessage) -> BoxFuture<'static, ()> + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) enum PoisonMessageTarget {
+    Callback(PoisonMessageCallback),
+    Stream(String),
+}
+
+/// What a consumer does with the messages it gives up on
+///
+/// A message is given up on when it fails to decode, or when the handler of a
+/// [`crate::ConsumerBuilder::try_message_handler`] consumer fails `max_attempts` times.
+/// The consumer then moves on to the next message instead of failing again.
+#[derive(Clone)]
+pub struct PoisonMessageHandling {
+    pub(crate) target: PoisonMessageTarget,
+    pub(crate) max_attempts: u32,
+}
+
+impl PoisonMessageHandling {
+    /// Hand the poison messages to `callback`, the consumer waits for it to complete
+    pub fn callback<F, Fut>(callback: F) -> Self
+    where
+        F: Fn(PoisonMessage) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        PoisonMessageHandling {
+            target: PoisonMessageTarget::Callback(Arc::new(move |message| {
+                callback(message).boxed()
+            })),
+            max_attempts: 3,
+        }
+    }
+
+    /// Publish the poison messages to the `stream` parking stream, which must exist
+    ///
+    /// A message which failed to decode is published with its bytes as body.
+    pub fn parking_stream(stream: &str) -> Self {
+        PoisonMessageHandling {
+            target: PoisonMessageTarget::Stream(stream.to_owned()),
+            max_attempts: 3,
+        }
+    }
+
+    /// Number of times the handler runs for a message before giving up on it, defaults to 3
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+}
+
+/// Why a message was given up on
+#[derive(Clone, Debug)]
+pub enum PoisonReason {
+    /// The entry is not a valid AMQP message
+    Decode(String),
+    /// The handler failed on every attempt, `error` is the last failure
+    Handler { attempts: u32, error: String },
+}
+
+/// Message a consumer gave up on, see [`PoisonMessageHandling`]
+#[derive(Clone, Debug)]
+pub struct PoisonMessage {
+    pub(crate) stream: Arc<str>,
+    pub(crate) offset: u64,
+    pub(crate) data: Arc<[u8]>,
+    pub(crate) message: Option<Message>,
+    pub(crate) reason: PoisonReason,
+}
+
+impl PoisonMessage {
+    /// Stream the message was read from
+    pub fn stream(&self) -> &str {
+        &self.stream
+    }
+
+    /// Position of the message in the stream
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Encoded message, as stored by the broker
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Decoded message, `None` when decoding failed
+    pub fn message(&self) -> Option<&Message> {
+        self.message.as_ref()
+    }
+
+    pub fn reason(&self) -> &PoisonReason {
+        &self.reason
+    }
+}
+
+/// Routes the poison messages of a consumer to their [`PoisonMessageTarget`]
+pub(crate) struct PoisonMessageRouter {
+    pub(crate) handling: PoisonMessageHandling,
+    environment: Environment,
+    /// Producer of the parking stream, created on the first poison message
+    producer: Mutex<Option<Producer>>,
+}
+
+impl PoisonMessageRouter {
+    pub(crate) fn new(handling: PoisonMessageHandling, environment: Environment) -> Self {
+        PoisonMessageRouter {
+            handling,
+            environment,
+            producer: Mutex::new(None),
+        }
+    }
+
+    /// Returns false when the message could not be parked
+    pub(crate) async fn route(&self, poison: PoisonMessage) -> bool {
+        trace!(
+            stream = poison.stream(),
+            offset = poison.offset,
+            reason = ?poison.reason,
+            "Poison message"
+        );
+        match &self.handling.target {
+            PoisonMessageTarget::Callback(callback) => {
+                callback(poison).await;
+                true
+            }
+            PoisonMessageTarget::Stream(stream) => match self.park(stream, poison).await {
+                Ok(()) => true,
+                Err(error) => {
+                    trace!(?error, "Failed to park poison message");
+                    false
+                }
+            },
+        }
+    }
+
+    async fn park(
+        &self,
+        stream: &str,
+        poison: PoisonMessage,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut producer = self.producer.lock().await;
+        if producer.is_none() {
+            *producer = Some(self.environment.producer().build(stream).await?);
+        }
+        let message = match poison.message {
+            Some(message) => message,
+            None => Message::builder().body(poison.data.to_vec()).build(),
+        };
+        let status = producer
+            .as_ref()
+            .unwrap()
+            .send_with_confirm(message)
+            .await?;
+        match status.confirmed() {
+            true => Ok(()),
+            false => Err(format!("message not confirmed by {}", stream).into()),
+        }
+    }
+}
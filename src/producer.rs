@@ -1,150 +1,1132 @@
 use std::{
     collections::HashMap,
+    pin::Pin,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
-        Arc,
+        Arc, Weak,
     },
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use futures::{future::BoxFuture, FutureExt};
-use rabbitmq_stream_protocol::{message::Message, ResponseCode, ResponseKind};
+use futures::{future::BoxFuture, ready, FutureExt, Sink};
+use rabbitmq_stream_protocol::codec::Encoder;
+use rabbitmq_stream_protocol::{
+    compression::{Compression, ZstdDictionary},
+    message::{Message, MessageId, Value},
+    ResponseCode, ResponseKind,
+};
 use std::future::Future;
-use tokio::sync::{
-    oneshot::{channel, Receiver, Sender},
-    Mutex,
+use tokio::{
+    sync::{mpsc, oneshot, Mutex, Notify, RwLock},
+    time::Instant,
 };
-use tracing::trace;
+use tracing::{debug_span, trace, Instrument};
 
-use crate::{client::MessageHandler, RabbitMQStreamResult};
 use crate::{
-    client::{Client, MessageResult},
+    byte_capacity::ByteCapacity,
+    client::MessageHandler,
+    debug_state::ProducerDebugState,
+    dedup_producer::DedupProducerBuilder,
+    events::{self, EventSender, StreamEvent},
+    latency::{LatencyHistogram, LatencySnapshot},
+    metrics::{Metrics, MetricsCollector, MetricsContext},
+    rate_limiter::RateLimiter,
+    RabbitMQStreamResult,
+};
+use crate::{
+    client::{intern, MessageResult, PUBLISH_FRAME_OVERHEAD},
+    connection_pool::ConnectionLease,
     environment::Environment,
-    error::{ClientError, ProducerCloseError, ProducerCreateError, ProducerPublishError},
+    error::{
+        ClientError, ProducerCloseError, ProducerCreateError, ProducerPublishError,
+        StreamPublishError,
+    },
+    interceptor::ProducerInterceptor,
+    retry_policy::next_delay,
 };
 
 type WaiterMap = Arc<Mutex<HashMap<u64, ProducerMessageWaiter>>>;
 
+/// Publishing ids of the messages packed in a sub-entry, keyed by the id of the sub-entry
+type SubEntryMap = Arc<Mutex<HashMap<u64, Vec<u64>>>>;
+
+pub(crate) type ConfirmCallback = Box<
+    dyn FnOnce(Result<ConfirmationStatus, ProducerPublishError>) -> BoxFuture<'static, ()>
+        + Send
+        + Sync,
+>;
+
+/// Callbacks of a producer waiting to run, in the order of the outcomes
+type CallbackQueue = mpsc::UnboundedSender<BoxFuture<'static, ()>>;
+
+type PublishErrorHandler = Arc<dyn Fn(u64, StreamPublishError) + Send + Sync>;
+
+type MetadataUpdateHandler = Arc<dyn Fn() + Send + Sync>;
+
+type FilterValueExtractor = Arc<dyn Fn(&Message) -> String + Send + Sync>;
+
+type RecoveryListener = Arc<dyn Fn(ProducerRecoveryEvent) + Send + Sync>;
+
+type MessageIdGenerator = Arc<dyn Fn() -> MessageId + Send + Sync>;
+
+/// Progress of the recovery of a producer, see [`ProducerBuilder::on_recovery`]
+#[derive(Clone, Debug)]
+pub enum ProducerRecoveryEvent {
+    /// The connection closed or the stream became unavailable, publishing is paused
+    Started { stream: String },
+    /// The publisher was declared again, `resent` unconfirmed messages were published
+    /// again and publishing resumed after `outage`
+    Recovered {
+        stream: String,
+        outage: Duration,
+        resent: usize,
+    },
+    /// An attempt failed, the next one starts after a delay
+    Failed { stream: String, error: String },
+    /// The [recovery policy](crate::EnvironmentBuilder::recovery_policy) gave up after
+    /// `error`, the producer is closed and its pending messages failed
+    GaveUp { stream: String, error: String },
+}
+
+pub(crate) type ConfirmReceiver =
+    oneshot::Receiver<Result<ConfirmationStatus, ProducerPublishError>>;
+
 pub struct ProducerInternal {
-    client: Client,
-    stream: String,
+    environment: Environment,
+    client: RwLock<ConnectionLease>,
+    confirm_handler: ProducerConfirmHandler,
+    stream: Arc<str>,
+    name: Option<String>,
     producer_id: u8,
     publish_sequence: Arc<AtomicU64>,
     waiting_confirmations: WaiterMap,
+    callbacks: CallbackQueue,
     closed: Arc<AtomicBool>,
+    batch_size: usize,
+    sub_entry_size: usize,
+    compression: Compression,
+    zstd_dictionary: Option<ZstdDictionary>,
+    filter_value_extractor: Option<FilterValueExtractor>,
+    max_message_size: Option<usize>,
+    sub_entries: SubEntryMap,
+    accumulator: Mutex<Vec<Message>>,
+    in_flight: Arc<InFlight>,
+    close_timeout: Duration,
+    /// Publishing is paused until the connection is recovered
+    recovering: Arc<AtomicBool>,
+    recovery_listener: Option<RecoveryListener>,
+    interceptors: Vec<Arc<dyn ProducerInterceptor>>,
+    time_stamping: TimeStamping,
+    message_id_generator: Option<MessageIdGenerator>,
+    rate_limiter: Option<RateLimiter>,
+    metrics: Option<Metrics>,
+}
+
+/// Bytes of a publish entry besides the message: publishing id and size
+const ENTRY_OVERHEAD: usize = 8 + 4;
+
+/// Bytes of a sub-entry holding a single message: publishing id, type, count, sizes and
+/// message size
+const SUB_ENTRY_OVERHEAD: usize = 8 + 1 + 2 + 4 + 4 + 4;
+
+/// Annotation of the time a message was sent, see [`ProducerBuilder::stamp_publish_time`]
+const PUBLISH_TIME_ANNOTATION: &str = "x-opt-publish-time";
+
+/// Times set on the messages sent without them
+#[derive(Clone, Copy, Default)]
+pub(crate) struct TimeStamping {
+    creation_time: bool,
+    publish_time: bool,
+}
+
+impl TimeStamping {
+    fn is_enabled(&self) -> bool {
+        self.creation_time || self.publish_time
+    }
+
+    fn stamp(&self, message: Message, now: SystemTime) -> Message {
+        let creation_time = self.creation_time && message.creation_time().is_none();
+        let publish_time = self.publish_time
+            && message
+                .message_annotation(PUBLISH_TIME_ANNOTATION)
+                .is_none();
+        if !creation_time && !publish_time {
+            return message;
+        }
+
+        let mut builder = message.into_builder();
+        if creation_time {
+            builder = builder.creation_time(now);
+        }
+        if publish_time {
+            let millis = now
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis() as i64)
+                .unwrap_or_default();
+            builder = builder.message_annotation(PUBLISH_TIME_ANNOTATION, Value::Timestamp(millis));
+        }
+        builder.build()
+    }
+}
+
+impl ProducerInternal {
+    /// Reserve room for unconfirmed messages of the given encoded sizes
+    async fn reserve_in_flight(
+        &self,
+        sizes: &[usize],
+    ) -> Result<Vec<InFlightPermit>, ProducerPublishError> {
+        let bytes = sizes.iter().sum();
+        loop {
+            let released = self.in_flight.released.notified();
+            if self.in_flight.try_reserve(sizes.len(), bytes) {
+                return Ok(sizes
+                    .iter()
+                    .map(|bytes| InFlightPermit {
+                        in_flight: self.in_flight.clone(),
+                        bytes: *bytes,
+                    })
+                    .collect());
+            }
+
+            match self.in_flight.overflow_strategy {
+                OverflowStrategy::Wait => released.await,
+                OverflowStrategy::FailFast => {
+                    return Err(ProducerPublishError::QueueFull {
+                        stream: self.stream.to_string(),
+                    })
+                }
+                OverflowStrategy::DropOldest => {
+                    if !self.drop_oldest().await {
+                        released.await
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fail when a message is larger than the maximum message size, before any message of
+    /// the batch is sent
+    fn check_message_size(&self, sizes: &[usize]) -> Result<(), ProducerPublishError> {
+        match (self.max_message_size, sizes.iter().max()) {
+            (Some(limit), Some(&size)) if size > limit => {
+                Err(ProducerPublishError::MessageSizeLimit { size, limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail when a message does not fit in a publish frame on its own, before any
+    /// message of the batch is sent
+    ///
+    /// Compressed sub-entries are not checked, their size is only known once compressed.
+    async fn check_frame_size(
+        &self,
+        messages: &[(Message, ConfirmCallback)],
+        sizes: &[usize],
+    ) -> Result<(), ProducerPublishError> {
+        if self.sub_entry_size > 1 && self.compression != Compression::None {
+            return Ok(());
+        }
+        let max = self.client.read().await.max_frame_size().await as usize;
+        if max == 0 {
+            return Ok(());
+        }
+        let overhead = match self.sub_entry_size > 1 {
+            true => PUBLISH_FRAME_OVERHEAD + SUB_ENTRY_OVERHEAD,
+            false => PUBLISH_FRAME_OVERHEAD + ENTRY_OVERHEAD,
+        };
+        for ((message, _), size) in messages.iter().zip(sizes) {
+            let filter_value = match &self.filter_value_extractor {
+                Some(filter_value_extractor) if self.sub_entry_size <= 1 => {
+                    2 + filter_value_extractor(message).len()
+                }
+                _ => 0,
+            };
+            let size = overhead + filter_value + size;
+            if size > max {
+                return Err(ProducerPublishError::MessageTooLarge { size, max });
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget the oldest unconfirmed message, returns false when there is none
+    ///
+    /// The message is not published when it is still accumulated.
+    async fn drop_oldest(&self) -> bool {
+        let mut accumulator = self.accumulator.lock().await;
+        let mut waiting_confirmations = self.waiting_confirmations.lock().await;
+        let oldest = waiting_confirmations
+            .iter()
+            .min_by_key(|(_, waiter)| waiter.published_at)
+            .map(|(publishing_id, _)| *publishing_id);
+        let waiter = oldest.and_then(|publishing_id| {
+            waiting_confirmations
+                .remove(&publishing_id)
+                .map(|waiter| (publishing_id, waiter))
+        });
+        drop(waiting_confirmations);
+        if let Some((publishing_id, _)) = &waiter {
+            accumulator.retain(|message| message.publishing_id() != Some(publishing_id));
+        }
+        drop(accumulator);
+
+        match waiter {
+            Some((publishing_id, waiter)) => {
+                self.record(|collector, context| collector.publish_error(context, 1));
+                waiter.handle_dropped(publishing_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn flush(&self) -> Result<(), ProducerPublishError> {
+        let mut accumulator = self.accumulator.lock().await;
+        let messages = std::mem::take(&mut *accumulator);
+        self.publish(messages).await
+    }
+
+    /// Number of messages filling a publish frame, `batch_size` entries of `sub_entry_size` messages
+    fn frame_messages(&self) -> usize {
+        self.batch_size.max(1) * self.sub_entry_size.max(1)
+    }
+
+    /// Start the confirm latency of the messages about to be written
+    async fn mark_written(&self, publishing_ids: &[u64]) {
+        let now = Instant::now();
+        let mut waiting_confirmations = self.waiting_confirmations.lock().await;
+        for publishing_id in publishing_ids {
+            if let Some(waiter) = waiting_confirmations.get_mut(publishing_id) {
+                waiter.written_at = Some(now);
+            }
+        }
+    }
+
+    /// Publish in frames of at most `batch_size` entries, the accumulator lock must be held
+    ///
+    /// While recovering nothing is sent, the messages wait for confirmation and are
+    /// published again once the connection is recovered.
+    async fn publish(&self, messages: Vec<Message>) -> Result<(), ProducerPublishError> {
+        if self.recovering.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let client = self.client.read().await.clone();
+        let frame_messages = self.frame_messages();
+        let mut messages = messages.into_iter().peekable();
+        while messages.peek().is_some() {
+            let batch: Vec<Message> = messages.by_ref().take(frame_messages).collect();
+            let publishing_ids: Vec<u64> = batch
+                .iter()
+                .filter_map(|message| message.publishing_id().copied())
+                .collect();
+            let published = batch.len() as u64;
+            let published_bytes: u64 = match &self.metrics {
+                Some(_) => batch
+                    .iter()
+                    .map(|message| message.encoded_size() as u64)
+                    .sum(),
+                None => 0,
+            };
+            self.mark_written(&publishing_ids).await;
+
+            let result = if self.sub_entry_size > 1 {
+                let mut sub_entries = self.sub_entries.lock().await;
+                for group in publishing_ids.chunks(self.sub_entry_size) {
+                    sub_entries.insert(group[group.len() - 1], group.to_vec());
+                }
+                drop(sub_entries);
+
+                match &self.zstd_dictionary {
+                    Some(dictionary) => {
+                        client
+                            .publish_zstd_sub_entries(
+                                self.producer_id,
+                                batch,
+                                self.sub_entry_size,
+                                dictionary,
+                            )
+                            .await
+                    }
+                    None => {
+                        client
+                            .publish_sub_entries(
+                                self.producer_id,
+                                batch,
+                                self.sub_entry_size,
+                                self.compression,
+                            )
+                            .await
+                    }
+                }
+            } else if let Some(filter_value_extractor) = &self.filter_value_extractor {
+                client
+                    .publish_with_filter_value(self.producer_id, batch, |message| {
+                        filter_value_extractor(message)
+                    })
+                    .await
+            } else {
+                client.publish(self.producer_id, batch).await
+            };
+
+            if let Err(err) = result {
+                let publishing_ids = publishing_ids
+                    .into_iter()
+                    .chain(messages.filter_map(|message| message.publishing_id().copied()));
+                self.fail_waiters(publishing_ids, &err).await;
+                return Err(err.into());
+            }
+            self.record(|collector, context| {
+                collector.publish(context, published);
+                collector.published_bytes(context, published_bytes);
+            });
+        }
+        Ok(())
+    }
+
+    /// Stamp the messages, give them an id and run them through the interceptors,
+    /// keeping the publishing ids set on them
+    fn intercept(
+        &self,
+        messages: Vec<(Message, ConfirmCallback)>,
+    ) -> Result<Vec<(Message, ConfirmCallback)>, ProducerPublishError> {
+        if self.interceptors.is_empty()
+            && !self.time_stamping.is_enabled()
+            && self.message_id_generator.is_none()
+        {
+            return Ok(messages);
+        }
+        let now = SystemTime::now();
+        messages
+            .into_iter()
+            .map(|(message, cb)| {
+                let publishing_id = message.publishing_id().copied();
+                let message = self.time_stamping.stamp(message, now);
+                let message = match &self.message_id_generator {
+                    Some(generator) if message.message_id().is_none() => {
+                        message.into_builder().message_id(generator()).build()
+                    }
+                    _ => message,
+                };
+                let mut message = self
+                    .interceptors
+                    .iter()
+                    .try_fold(message, |message, interceptor| {
+                        interceptor.on_send(&self.stream, message)
+                    })
+                    .map_err(|error| ProducerPublishError::Interceptor {
+                        stream: self.stream.to_string(),
+                        error,
+                    })?;
+                if let (Some(publishing_id), None) = (publishing_id, message.publishing_id()) {
+                    message.set_publishing_id(publishing_id);
+                }
+                Ok((message, cb))
+            })
+            .collect()
+    }
+
+    /// Wait until the callback of every published message ran
+    async fn wait_for_confirms(&self) {
+        loop {
+            let released = self.in_flight.released.notified();
+            if self.in_flight.usage.lock().unwrap().messages == 0 {
+                return;
+            }
+            released.await;
+        }
+    }
+
+    /// Replace the broken connection and publish again the unconfirmed messages
+    ///
+    /// Messages keep their publishing id, so the broker deduplicates the ones it
+    /// already stored when the producer has a name.
+    async fn recover(&self) -> Result<usize, ProducerCreateError> {
+        let client =
+            leader_connection(&self.environment, &self.stream, Some(self.producer_id)).await?;
+        client.set_handler(self.confirm_handler.clone());
+
+        let response = client
+            .declare_publisher(self.producer_id, self.name.clone(), &self.stream)
+            .await?;
+        if !response.is_ok() {
+            return Err(ProducerCreateError::Create {
+                stream: self.stream.to_string(),
+                status: response.code().clone(),
+            });
+        }
+        // the previous connection no longer hands its frames to the producer, it is closed
+        // once the other producers left it
+        drop(std::mem::replace(&mut *self.client.write().await, client));
+
+        let mut accumulator = self.accumulator.lock().await;
+        accumulator.clear();
+        self.sub_entries.lock().await.clear();
+
+        let mut unconfirmed: Vec<Message> = self
+            .waiting_confirmations
+            .lock()
+            .await
+            .values()
+            .map(|waiter| waiter.message.clone())
+            .collect();
+        unconfirmed.sort_by_key(|message| message.publishing_id().copied());
+        let resent = unconfirmed.len();
+        trace!(
+            stream = &*self.stream,
+            messages = resent,
+            "Resending unconfirmed messages"
+        );
+
+        self.recovering.store(false, Ordering::SeqCst);
+        if let Err(error) = self.publish(unconfirmed).await {
+            trace!(?error, "Failed to resend unconfirmed messages");
+        }
+        Ok(resent)
+    }
+
+    /// Close the producer once its recovery gave up, the pending messages fail with `error`
+    async fn give_up(&self, error: ClientError) {
+        self.closed.store(true, Ordering::SeqCst);
+        let pending: Vec<u64> = self
+            .waiting_confirmations
+            .lock()
+            .await
+            .keys()
+            .copied()
+            .collect();
+        self.fail_waiters(pending.into_iter(), &error).await;
+        self.client.read().await.release().await;
+    }
+
+    fn notify_recovery(&self, event: ProducerRecoveryEvent) {
+        self.environment
+            .emit(StreamEvent::ProducerRecovery(event.clone()));
+        if let Some(listener) = &self.recovery_listener {
+            listener(event);
+        }
+    }
+
+    async fn fail_waiters(&self, publishing_ids: impl Iterator<Item = u64>, err: &ClientError) {
+        let mut sub_entries = self.sub_entries.lock().await;
+        let mut waiting_confirmations = self.waiting_confirmations.lock().await;
+        let waiters: Vec<(u64, ProducerMessageWaiter)> = publishing_ids
+            .filter_map(|publishing_id| {
+                sub_entries.remove(&publishing_id);
+                waiting_confirmations
+                    .remove(&publishing_id)
+                    .map(|waiter| (publishing_id, waiter))
+            })
+            .collect();
+        drop(waiting_confirmations);
+        drop(sub_entries);
+
+        let failed = waiters.len() as u64;
+        for (publishing_id, waiter) in waiters {
+            waiter.handle_publish_failure(publishing_id, err);
+        }
+        self.record(|collector, context| collector.publish_error(context, failed));
+    }
+
+    fn record(&self, event: impl Fn(&dyn MetricsCollector, &MetricsContext<'_>)) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(Some(&self.stream), event);
+        }
+    }
+}
+
+/// Producer defaults carried by the [`Environment`]
+#[derive(Clone, Debug)]
+pub(crate) struct ProducerOptions {
+    pub(crate) batch_size: usize,
+    pub(crate) batch_publishing_delay: Duration,
+    pub(crate) max_message_size: Option<usize>,
+    pub(crate) max_in_flight_bytes: Option<usize>,
+    pub(crate) producers_per_connection: usize,
+}
+
+impl Default for ProducerOptions {
+    fn default() -> Self {
+        ProducerOptions {
+            batch_size: 100,
+            batch_publishing_delay: Duration::from_millis(100),
+            max_message_size: None,
+            max_in_flight_bytes: None,
+            producers_per_connection: 1,
+        }
+    }
 }
 
 /// API for publising messages to RabbitMQ stream
-#[derive(Clone)]
-pub struct Producer(Arc<ProducerInternal>);
+///
+/// The producer is also a [`Sink`] of messages, sending waits for room when the
+/// in-flight limit is reached and closing the sink closes the producer.
+pub struct Producer(Arc<ProducerInternal>, std::sync::Mutex<SinkState>);
+
+impl Clone for Producer {
+    fn clone(&self) -> Self {
+        Producer(self.0.clone(), Default::default())
+    }
+}
+
+/// Operation in progress of the [`Sink`] implementation
+#[derive(Default)]
+struct SinkState {
+    pending: Option<BoxFuture<'static, Result<(), ProducerPublishError>>>,
+    flushing: bool,
+}
 
 /// Builder for [`Producer`]
+#[derive(Clone)]
 pub struct ProducerBuilder {
     pub(crate) environment: Environment,
     pub(crate) name: Option<String>,
+    pub(crate) batch_size: usize,
+    pub(crate) batch_publishing_delay: Duration,
+    pub(crate) confirm_timeout: Option<Duration>,
+    pub(crate) close_timeout: Duration,
+    pub(crate) sub_entry_size: usize,
+    pub(crate) compression: Compression,
+    pub(crate) zstd_dictionary: Option<ZstdDictionary>,
+    pub(crate) max_in_flight: usize,
+    pub(crate) max_in_flight_bytes: Option<usize>,
+    pub(crate) max_message_size: Option<usize>,
+    pub(crate) overflow_strategy: OverflowStrategy,
+    pub(crate) publish_error_handler: Option<PublishErrorHandler>,
+    pub(crate) metadata_update_handler: Option<MetadataUpdateHandler>,
+    pub(crate) filter_value_extractor: Option<FilterValueExtractor>,
+    pub(crate) recovery_listener: Option<RecoveryListener>,
+    pub(crate) interceptors: Vec<Arc<dyn ProducerInterceptor>>,
+    pub(crate) time_stamping: TimeStamping,
+    pub(crate) message_id_generator: Option<MessageIdGenerator>,
+    pub(crate) rate_limiter: Option<RateLimiter>,
 }
 
 impl ProducerBuilder {
     pub async fn build(self, stream: &str) -> Result<Producer, ProducerCreateError> {
-        let client = self.environment.create_client().await?;
+        let client = leader_connection(&self.environment, stream, None).await?;
 
         let waiting_confirmations: WaiterMap = Arc::new(Mutex::new(HashMap::new()));
+        let sub_entries: SubEntryMap = Arc::new(Mutex::new(HashMap::new()));
+        let connection_closed = Arc::new(Notify::new());
+        let recovering = Arc::new(AtomicBool::new(false));
+        let (callbacks, queued_callbacks) = mpsc::unbounded_channel();
 
         let confirm_handler = ProducerConfirmHandler {
+            stream: stream.to_owned(),
             waiting_confirmations: waiting_confirmations.clone(),
+            sub_entries: sub_entries.clone(),
+            publish_error_handler: self.publish_error_handler,
+            metadata_update_handler: self.metadata_update_handler,
+            events: self.environment.events.clone(),
+            connection_closed: connection_closed.clone(),
+            recovering: recovering.clone(),
+            latencies: Arc::new(LatencyHistogram::new()),
+            metrics: client.metrics().cloned(),
         };
 
-        client.set_handler(confirm_handler).await;
+        client.set_handler(confirm_handler.clone());
 
-        let producer_id = 1;
+        let producer_id = client.id();
         let response = client
             .declare_publisher(producer_id, self.name.clone(), stream)
             .await?;
+        if !response.is_ok() {
+            return Err(ProducerCreateError::Create {
+                stream: stream.to_owned(),
+                status: response.code().clone(),
+            });
+        }
 
-        let publish_sequence = if let Some(name) = self.name {
-            let sequence = client.query_publisher_sequence(&name, stream).await?;
-            Arc::new(AtomicU64::new(sequence))
-        } else {
-            Arc::new(AtomicU64::new(0))
+        // continue after the last publishing id stored by the broker for this reference, the
+        // broker answers 0 as well when none is stored so the ids of a new reference start at 1
+        let publish_sequence = match &self.name {
+            Some(name) => {
+                let last_publishing_id = client.query_publisher_sequence(name, stream).await?;
+                Arc::new(AtomicU64::new(last_publishing_id + 1))
+            }
+            None => Arc::new(AtomicU64::new(0)),
         };
 
-        if response.is_ok() {
-            let producer = ProducerInternal {
-                producer_id,
-                stream: stream.to_string(),
-                client,
-                publish_sequence,
-                waiting_confirmations,
-                closed: Arc::new(AtomicBool::new(false)),
-            };
-            Ok(Producer(Arc::new(producer)))
-        } else {
-            Err(ProducerCreateError::Create {
-                stream: stream.to_owned(),
-                status: response.code().clone(),
-            })
+        let producer = Arc::new(ProducerInternal {
+            producer_id,
+            stream: intern(stream),
+            name: self.name,
+            environment: self.environment,
+            metrics: client.metrics().cloned(),
+            client: RwLock::new(client),
+            confirm_handler,
+            publish_sequence,
+            waiting_confirmations,
+            callbacks,
+            closed: Arc::new(AtomicBool::new(false)),
+            batch_size: self.batch_size,
+            sub_entry_size: self.sub_entry_size,
+            compression: self.compression,
+            zstd_dictionary: self.zstd_dictionary,
+            filter_value_extractor: self.filter_value_extractor,
+            max_message_size: self.max_message_size,
+            sub_entries,
+            accumulator: Mutex::new(Vec::new()),
+            in_flight: Arc::new(InFlight {
+                max_messages: self.max_in_flight,
+                max_bytes: self.max_in_flight_bytes,
+                overflow_strategy: self.overflow_strategy,
+                usage: std::sync::Mutex::new(InFlightUsage::default()),
+                released: Notify::new(),
+            }),
+            close_timeout: self.close_timeout,
+            recovering,
+            recovery_listener: self.recovery_listener,
+            interceptors: self.interceptors,
+            time_stamping: self.time_stamping,
+            message_id_generator: self.message_id_generator,
+            rate_limiter: self.rate_limiter,
+        });
+
+        tokio::task::spawn(run_callbacks(queued_callbacks));
+
+        tokio::task::spawn(flush_accumulator(
+            Arc::downgrade(&producer),
+            self.batch_publishing_delay,
+        ));
+
+        tokio::task::spawn(recover_connection(
+            Arc::downgrade(&producer),
+            connection_closed,
+        ));
+
+        if let Some(confirm_timeout) = self.confirm_timeout {
+            tokio::task::spawn(expire_confirmations(
+                Arc::downgrade(&producer),
+                confirm_timeout,
+            ));
         }
+
+        Ok(Producer(producer, Default::default()))
     }
 
+    /// Publisher reference enabling deduplication on the broker
+    ///
+    /// Messages with a publishing id not greater than the last one stored for
+    /// the reference are discarded by the broker.
     pub fn name(mut self, name: &str) -> Self {
         self.name = Some(name.to_owned());
         self
     }
+
+    /// Publish each sequence returned by `sequence` once, see [`crate::DedupProducer`]
+    ///
+    /// The producer needs a name.
+    pub fn deduplicate(
+        self,
+        sequence: impl Fn(&Message) -> u64 + Send + Sync + 'static,
+    ) -> DedupProducerBuilder {
+        DedupProducerBuilder {
+            builder: self,
+            sequence: Arc::new(sequence),
+        }
+    }
+
+    /// Maximum number of messages published in a single frame
+    ///
+    /// Messages passed to [`Producer::send`] are accumulated until `batch_size`
+    /// of them are pending or the batch publishing delay elapses.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Maximum time a message passed to [`Producer::send`] waits in the accumulator
+    pub fn batch_publishing_delay(mut self, batch_publishing_delay: Duration) -> Self {
+        self.batch_publishing_delay = batch_publishing_delay;
+        self
+    }
+
+    /// Pack up to `sub_entry_size` messages in a single entry of the publish frame
+    ///
+    /// Disabled with the default of 1. The broker confirms a sub-entry as a whole,
+    /// deduplication applies to the publishing id of its last message.
+    pub fn sub_entry_size(mut self, sub_entry_size: usize) -> Self {
+        self.sub_entry_size = sub_entry_size.clamp(1, u16::MAX as usize);
+        self
+    }
+
+    /// Compression of the sub-entries, see [`ProducerBuilder::sub_entry_size`]
+    ///
+    /// Only gzip is available by default, other codecs need the crate feature of the same name.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self.zstd_dictionary = None;
+        self
+    }
+
+    /// Compress the sub-entries by zstd with `dictionary`, see [`ProducerBuilder::sub_entry_size`]
+    ///
+    /// The dictionary is registered for the consumers of the process, the consumers of other
+    /// processes need it too, see [`ConsumerBuilder::zstd_dictionary`](crate::ConsumerBuilder::zstd_dictionary).
+    /// Requires the `zstd` feature.
+    pub fn zstd_dictionary(mut self, dictionary: ZstdDictionary) -> Self {
+        dictionary.register();
+        self.compression = Compression::Zstd;
+        self.zstd_dictionary = Some(dictionary);
+        self
+    }
+
+    /// Maximum number of messages waiting for a confirmation, 10000 by default
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight;
+        self
+    }
+
+    /// Maximum encoded size of the messages waiting for a confirmation, unbounded by default
+    pub fn max_in_flight_bytes(mut self, max_in_flight_bytes: ByteCapacity) -> Self {
+        self.max_in_flight_bytes = Some(max_in_flight_bytes.bytes() as usize);
+        self
+    }
+
+    /// Maximum encoded size of a message, larger messages are rejected with
+    /// [`ProducerPublishError::MessageSizeLimit`]
+    ///
+    /// Independent of the frame max negotiated with the broker, unbounded by default.
+    pub fn max_message_size(mut self, max_message_size: ByteCapacity) -> Self {
+        self.max_message_size = Some(max_message_size.bytes() as usize);
+        self
+    }
+
+    /// What happens to sends when the in-flight limit is reached, see [`OverflowStrategy`]
+    pub fn overflow_strategy(mut self, overflow_strategy: OverflowStrategy) -> Self {
+        self.overflow_strategy = overflow_strategy;
+        self
+    }
+
+    /// Handler invoked with the publishing id and the reason of every message rejected by the broker
+    ///
+    /// Runs on the connection task in addition to the callback of the message and should not block.
+    pub fn on_publish_error(
+        mut self,
+        handler: impl Fn(u64, StreamPublishError) + Send + Sync + 'static,
+    ) -> Self {
+        self.publish_error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Publish every message with the filter value returned by `filter_value_extractor`
+    ///
+    /// Consumers subscribing with filter values then only receive the chunks containing
+    /// messages with one of their values, requires RabbitMQ 3.13+.
+    /// Messages packed in sub-entries are published without filter value.
+    pub fn filter_value_extractor(
+        mut self,
+        filter_value_extractor: impl Fn(&Message) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.filter_value_extractor = Some(Arc::new(filter_value_extractor));
+        self
+    }
+
+    /// Listener notified when the producer recovers from a closed connection or an
+    /// unavailable stream
+    ///
+    /// Publishing pauses during the outage: the messages sent meanwhile wait for
+    /// confirmation and are published once the publisher is declared again.
+    pub fn on_recovery(
+        mut self,
+        listener: impl Fn(ProducerRecoveryEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.recovery_listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Append `interceptor` to the ones the messages go through before they are published
+    pub fn interceptor(mut self, interceptor: impl ProducerInterceptor + 'static) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Set the creation time property of the messages sent without one to the time
+    /// they are sent, before they go through the interceptors
+    pub fn stamp_creation_time(mut self) -> Self {
+        self.time_stamping.creation_time = true;
+        self
+    }
+
+    /// Add an `x-opt-publish-time` timestamp annotation to the messages sent without one,
+    /// before they go through the interceptors
+    pub fn stamp_publish_time(mut self) -> Self {
+        self.time_stamping.publish_time = true;
+        self
+    }
+
+    /// Set the id of the messages sent without one to a random UUID, before they go
+    /// through the interceptors
+    pub fn generate_message_ids(self) -> Self {
+        self.message_id_generator(|| MessageId::Uuid(*uuid::Uuid::new_v4().as_bytes()))
+    }
+
+    /// Set the id of the messages sent without one to an id of `generator`, before they
+    /// go through the interceptors
+    pub fn message_id_generator(
+        mut self,
+        generator: impl Fn() -> MessageId + Send + Sync + 'static,
+    ) -> Self {
+        self.message_id_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Throttle the sends with `rate_limiter`, shared with the producers it is passed to
+    ///
+    /// The sends wait for the limiter before taking their in-flight permits.
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Handler invoked when the broker notifies a change of the stream topology
+    pub(crate) fn on_metadata_update(mut self, handler: impl Fn() + Send + Sync + 'static) -> Self {
+        self.metadata_update_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Maximum time [`Producer::close`] waits for the outstanding confirms, 10 seconds by default
+    pub fn close_timeout(mut self, close_timeout: Duration) -> Self {
+        self.close_timeout = close_timeout;
+        self
+    }
+
+    /// Maximum time to wait for the broker to confirm a message
+    ///
+    /// Waits forever when not set.
+    pub fn confirm_timeout(mut self, confirm_timeout: Duration) -> Self {
+        self.confirm_timeout = Some(confirm_timeout);
+        self
+    }
 }
 
 impl Producer {
-    pub async fn send(&self, message: Message) -> Result<u64, ProducerPublishError> {
-        let (publishing_id, rx) = self.internal_send(message).await?;
-
-        rx.await
-            .map_err(|err| ClientError::GenericError(Box::new(err)))?
-            .map(|_| publishing_id)
+    /// Publish a message, `cb` is invoked once the broker confirms or rejects it
+    ///
+    /// The message is accumulated and published with others once the batch size is reached
+    /// or the batch publishing delay elapses, use [`Producer::flush`] to publish it right away.
+    /// Publishing ids are assigned by the producer unless the message already has one,
+    /// see [`crate::types::MessageBuilder::publishing_id`], the ids assigned afterwards follow it.
+    /// The callbacks of a producer run one at a time, in the order of the outcomes.
+    pub async fn send<Fut>(
+        &self,
+        message: Message,
+        cb: impl FnOnce(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+    ) -> Result<(), ProducerPublishError>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.internal_send(
+            vec![(message, Box::new(move |status| cb(status).boxed()))],
+            false,
+        )
+        .await
     }
 
+    /// Publish a message, `cb` receives its publishing id once the broker confirms it
+    #[deprecated(note = "use `Producer::send`, its callback receives a `ConfirmationStatus`")]
     pub async fn send_with_callback<Fut>(
         &self,
         message: Message,
-        cb: impl Fn(Result<u64, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+        cb: impl FnOnce(Result<u64, ProducerPublishError>) -> Fut + Send + Sync + 'static,
     ) -> Result<(), ProducerPublishError>
     where
-        Fut: Future<Output = ()> + Send + Sync,
+        Fut: Future<Output = ()> + Send + 'static,
     {
-        let (publishing_id, rx) = self.internal_send(message).await?;
-        tokio::task::spawn(async move {
-            let result = rx.await;
-            match result {
-                Ok(confirm_status) => cb(confirm_status.map(|_| publishing_id)).await,
-                Err(err) => cb(Err(ClientError::GenericError(Box::new(err)).into())).await,
-            };
-        });
-        Ok(())
+        let stream = self.0.stream.to_string();
+        let publisher_id = self.0.producer_id;
+        self.send(message, move |status| {
+            cb(status.and_then(|status| {
+                if status.confirmed() {
+                    Ok(status.publishing_id())
+                } else {
+                    Err(ProducerPublishError::Create {
+                        stream,
+                        publisher_id,
+                        status: status.status().clone(),
+                    })
+                }
+            }))
+        })
+        .await
+    }
+
+    /// Publish a batch of messages, `cb` is invoked once per message
+    ///
+    /// Messages are packed in as few publish frames as the negotiated frame max
+    /// and the producer batch size allow.
+    pub async fn batch_send<Fut>(
+        &self,
+        messages: Vec<Message>,
+        cb: impl Fn(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+    ) -> Result<(), ProducerPublishError>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let cb = Arc::new(cb);
+        let messages = messages
+            .into_iter()
+            .map(|message| {
+                let cb = cb.clone();
+                let cb: ConfirmCallback = Box::new(move |status| cb(status).boxed());
+                (message, cb)
+            })
+            .collect();
+
+        self.internal_send(messages, true).await
+    }
+
+    /// Publish a message and wait for the broker to confirm or reject it
+    pub async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<ConfirmationStatus, ProducerPublishError> {
+        let (tx, rx) = oneshot::channel();
+        self.internal_send(vec![(message, confirm_sender(tx))], true)
+            .await?;
+
+        wait_for_confirm(rx).await
     }
 
-    async fn internal_send(
+    /// Publish a batch of messages and wait for the outcome of each of them
+    ///
+    /// Statuses are returned in the same order as the messages.
+    pub async fn batch_send_with_confirm(
         &self,
-        mut message: Message,
-    ) -> Result<(u64, Receiver<Result<(), ProducerPublishError>>), ProducerPublishError> {
+        messages: Vec<Message>,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError> {
+        let mut receivers = Vec::with_capacity(messages.len());
+        let messages = messages
+            .into_iter()
+            .map(|message| {
+                let (tx, rx) = oneshot::channel();
+                receivers.push(rx);
+                (message, confirm_sender(tx))
+            })
+            .collect();
+        self.internal_send(messages, true).await?;
+
+        let mut statuses = Vec::with_capacity(receivers.len());
+        for rx in receivers {
+            statuses.push(wait_for_confirm(rx).await?);
+        }
+        Ok(statuses)
+    }
+
+    /// Publish the accumulated messages without waiting for the batch publishing delay
+    pub async fn flush(&self) -> Result<(), ProducerPublishError> {
+        self.0.flush().await
+    }
+
+    pub(crate) async fn internal_send(
+        &self,
+        messages: Vec<(Message, ConfirmCallback)>,
+        flush: bool,
+    ) -> Result<(), ProducerPublishError> {
         if self.is_closed() {
             return Err(ProducerPublishError::Closed);
         }
-        let publishing_id = match message.publishing_id() {
-            Some(publishing_id) => *publishing_id,
-            None => self.0.publish_sequence.fetch_add(1, Ordering::Relaxed),
-        };
-        message.set_publishing_id(publishing_id);
+        let messages = self.0.intercept(messages)?;
 
-        let (rx, waiter) = ProducerMessageWaiter::waiter(self.0.stream.clone(), self.0.producer_id);
+        let sizes: Vec<usize> = messages
+            .iter()
+            .map(|(message, _)| message.encoded_size() as usize)
+            .collect();
+        self.0.check_message_size(&sizes)?;
+        self.0.check_frame_size(&messages, &sizes).await?;
+        if let Some(rate_limiter) = &self.0.rate_limiter {
+            rate_limiter.acquire(sizes.len(), sizes.iter().sum()).await;
+        }
+        let permits = self.0.reserve_in_flight(&sizes).await?;
+
+        // ids are assigned under the accumulator lock, so the messages are published in id order
+        let mut accumulator = self.0.accumulator.lock().await;
         let mut waiting_confirmation = self.0.waiting_confirmations.lock().await;
-        waiting_confirmation.insert(publishing_id, waiter);
+        for ((mut message, cb), permit) in messages.into_iter().zip(permits) {
+            let publishing_id = match message.publishing_id() {
+                Some(publishing_id) => {
+                    self.0
+                        .publish_sequence
+                        .fetch_max(publishing_id + 1, Ordering::Relaxed);
+                    *publishing_id
+                }
+                None => self.0.publish_sequence.fetch_add(1, Ordering::Relaxed),
+            };
+            message.set_publishing_id(publishing_id);
+
+            let waiter = ProducerMessageWaiter {
+                stream: self.0.stream.clone(),
+                message: message.clone(),
+                published_at: Instant::now(),
+                written_at: None,
+                cb,
+                callbacks: self.0.callbacks.clone(),
+                _permit: permit,
+            };
+            waiting_confirmation.insert(publishing_id, waiter);
+            accumulator.push(message);
+        }
         drop(waiting_confirmation);
-        self.0.client.publish(self.0.producer_id, message).await?;
 
-        Ok((publishing_id, rx))
+        if flush || accumulator.len() >= self.0.frame_messages() {
+            let messages = std::mem::take(&mut *accumulator);
+            self.0.publish(messages).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Query the last publishing id stored by the broker for this producer
+    ///
+    /// Returns `None` when the producer has no name, useful to resume a replay
+    /// with user supplied publishing ids.
+    pub async fn last_publishing_id(&self) -> RabbitMQStreamResult<Option<u64>> {
+        match &self.0.name {
+            Some(name) => self
+                .0
+                .client
+                .read()
+                .await
+                .query_publisher_sequence(name, &self.0.stream)
+                .await
+                .map(Some),
+            None => Ok(None),
+        }
     }
 
     pub fn is_closed(&self) -> bool {
         self.0.closed.load(Ordering::Relaxed)
     }
-    // TODO handle producer state after close
+
+    /// Snapshot of the internal state of the producer and its connection
+    pub async fn debug_state(&self) -> ProducerDebugState {
+        let connection = self.0.client.read().await.clone().debug_state().await;
+        ProducerDebugState {
+            stream: self.0.stream.to_string(),
+            publisher_id: self.0.producer_id,
+            next_publishing_id: self.0.publish_sequence.load(Ordering::Relaxed),
+            accumulated: self.0.accumulator.lock().await.len(),
+            unconfirmed: self.0.waiting_confirmations.lock().await.len(),
+            unconfirmed_bytes: self.0.in_flight.usage.lock().unwrap().bytes,
+            recovering: self.0.recovering.load(Ordering::SeqCst),
+            closed: self.is_closed(),
+            connection,
+        }
+    }
+
+    /// Confirm latencies of the messages published so far
+    pub fn latency_snapshot(&self) -> LatencySnapshot {
+        self.0.confirm_handler.latencies.snapshot()
+    }
+    /// Close the producer once the outstanding messages are confirmed
+    ///
+    /// New sends are refused, the accumulated messages are published and the producer
+    /// waits up to the close timeout for their confirms before deleting the publisher.
+    /// Returns [`ProducerCloseError::Timeout`] when some confirms did not arrive in time,
+    /// the publisher is deleted anyway.
     pub async fn close(self) -> Result<(), ProducerCloseError> {
         match self
             .0
@@ -152,14 +1134,31 @@ impl Producer {
             .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
         {
             Ok(false) => {
-                let response = self.0.client.delete_publisher(self.0.producer_id).await?;
-                if response.is_ok() {
-                    Ok(())
-                } else {
+                self.0.flush().await.map_err(|err| match err {
+                    ProducerPublishError::Client(err) => ProducerCloseError::Client(err),
+                    err => ProducerCloseError::Client(ClientError::GenericError(Box::new(err))),
+                })?;
+                let confirmed =
+                    tokio::time::timeout(self.0.close_timeout, self.0.wait_for_confirms())
+                        .await
+                        .is_ok();
+
+                let client = self.0.client.read().await;
+                let response = client.delete_publisher(self.0.producer_id).await;
+                client.release().await;
+                let response = response?;
+                if !response.is_ok() {
                     Err(ProducerCloseError::Close {
                         status: response.code().clone(),
-                        stream: self.0.stream.clone(),
+                        stream: self.0.stream.to_string(),
                     })
+                } else if !confirmed {
+                    Err(ProducerCloseError::Timeout {
+                        stream: self.0.stream.to_string(),
+                        unconfirmed: self.0.waiting_confirmations.lock().await.len(),
+                    })
+                } else {
+                    Ok(())
                 }
             }
             _ => Err(ProducerCloseError::AlreadyClosed),
@@ -167,20 +1166,394 @@ impl Producer {
     }
 }
 
+impl Producer {
+    /// Drive the pending sink operation, returns true when it was a flush
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<bool, ProducerPublishError>> {
+        let state = self.1.get_mut().unwrap();
+        if let Some(pending) = state.pending.as_mut() {
+            let result = ready!(pending.poll_unpin(cx));
+            state.pending = None;
+            result?;
+        }
+        Poll::Ready(Ok(std::mem::take(&mut state.flushing)))
+    }
+}
+
+impl Sink<Message> for Producer {
+    type Error = ProducerPublishError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_pending(cx).map_ok(|_| ())
+    }
+
+    /// Outcomes are only reported to the handler set with [`ProducerBuilder::on_publish_error`]
+    fn start_send(self: Pin<&mut Self>, message: Message) -> Result<(), Self::Error> {
+        let producer = self.clone();
+        self.get_mut().1.get_mut().unwrap().pending = Some(
+            async move {
+                producer
+                    .internal_send(vec![(message, Box::new(|_| async {}.boxed()))], false)
+                    .await
+            }
+            .boxed(),
+        );
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        loop {
+            if ready!(this.poll_pending(cx))? {
+                return Poll::Ready(Ok(()));
+            }
+            let producer = this.clone();
+            let state = this.1.get_mut().unwrap();
+            state.pending = Some(async move { producer.flush().await }.boxed());
+            state.flushing = true;
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        if this.is_closed() {
+            return Poll::Ready(Ok(()));
+        }
+        let producer = this.clone();
+        this.1.get_mut().unwrap().pending = Some(
+            async move {
+                producer.close().await.map_err(|err| match err {
+                    ProducerCloseError::Client(err) => ProducerPublishError::Client(err),
+                    err => ProducerPublishError::Client(ClientError::GenericError(Box::new(err))),
+                })
+            }
+            .boxed(),
+        );
+        this.poll_pending(cx).map_ok(|_| ())
+    }
+}
+
+/// Behavior of [`Producer`] sends when the in-flight limit is reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverflowStrategy {
+    /// Wait until confirmations free enough room
+    Wait,
+    /// Fail the send with [`ProducerPublishError::QueueFull`]
+    FailFast,
+    /// Drop the oldest unconfirmed messages, their callbacks receive
+    /// [`ProducerPublishError::Dropped`]
+    DropOldest,
+}
+
+/// Bound on the messages waiting for a confirmation
+struct InFlight {
+    max_messages: usize,
+    max_bytes: Option<usize>,
+    overflow_strategy: OverflowStrategy,
+    usage: std::sync::Mutex<InFlightUsage>,
+    released: Notify,
+}
+
+#[derive(Default)]
+struct InFlightUsage {
+    messages: usize,
+    bytes: usize,
+}
+
+impl InFlight {
+    /// A batch bigger than the limit is only accepted when nothing is in flight
+    fn try_reserve(&self, messages: usize, bytes: usize) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        let fits = usage.messages == 0
+            || (usage.messages + messages <= self.max_messages
+                && self
+                    .max_bytes
+                    .is_none_or(|max_bytes| usage.bytes + bytes <= max_bytes));
+        if fits {
+            usage.messages += messages;
+            usage.bytes += bytes;
+        }
+        fits
+    }
+}
+
+/// Room taken by one unconfirmed message, released on drop
+struct InFlightPermit {
+    in_flight: Arc<InFlight>,
+    bytes: usize,
+}
+
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        let mut usage = self.in_flight.usage.lock().unwrap();
+        usage.messages -= 1;
+        usage.bytes -= self.bytes;
+        drop(usage);
+        self.in_flight.released.notify_waiters();
+    }
+}
+
+/// Outcome of a published message
+#[derive(Debug)]
+pub struct ConfirmationStatus {
+    pub(crate) publishing_id: u64,
+    pub(crate) confirmed: bool,
+    pub(crate) status: ResponseCode,
+    pub(crate) message: Message,
+}
+
+impl ConfirmationStatus {
+    /// Publishing id assigned to the message
+    pub fn publishing_id(&self) -> u64 {
+        self.publishing_id
+    }
+
+    /// Check if the broker persisted the message
+    pub fn confirmed(&self) -> bool {
+        self.confirmed
+    }
+
+    /// Code sent by the broker, [`ResponseCode::Ok`] when confirmed
+    pub fn status(&self) -> &ResponseCode {
+        &self.status
+    }
+
+    /// The published message
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+}
+
+pub(crate) fn confirm_sender(
+    tx: oneshot::Sender<Result<ConfirmationStatus, ProducerPublishError>>,
+) -> ConfirmCallback {
+    Box::new(move |status| {
+        async move {
+            let _ = tx.send(status);
+        }
+        .boxed()
+    })
+}
+
+pub(crate) async fn wait_for_confirm(
+    rx: ConfirmReceiver,
+) -> Result<ConfirmationStatus, ProducerPublishError> {
+    rx.await
+        .map_err(|err| ClientError::GenericError(Box::new(err)))?
+}
+
+/// Publish the accumulated messages every `batch_publishing_delay`
+async fn flush_accumulator(producer: Weak<ProducerInternal>, batch_publishing_delay: Duration) {
+    let mut interval = tokio::time::interval(batch_publishing_delay);
+    loop {
+        interval.tick().await;
+
+        let producer = match producer.upgrade() {
+            Some(producer) if !producer.closed.load(Ordering::Relaxed) => producer,
+            _ => break,
+        };
+
+        if let Err(error) = producer.flush().await {
+            trace!(?error, "Failed to flush the accumulated messages");
+        }
+    }
+}
+
+/// Reconnect the producer every time its connection closes or its stream becomes
+/// unavailable, until the producer is closed
+async fn recover_connection(producer: Weak<ProducerInternal>, connection_closed: Arc<Notify>) {
+    loop {
+        connection_closed.notified().await;
+
+        let started = Instant::now();
+        match producer.upgrade() {
+            Some(producer) => {
+                let policy = &producer.environment.options.broker_close;
+                let failure = policy.failure(&*producer.client.read().await).await;
+                if let Some(error) = failure {
+                    producer.notify_recovery(ProducerRecoveryEvent::GaveUp {
+                        stream: producer.stream.to_string(),
+                        error: error.to_string(),
+                    });
+                    producer.give_up(error).await;
+                    return;
+                }
+                producer.notify_recovery(ProducerRecoveryEvent::Started {
+                    stream: producer.stream.to_string(),
+                })
+            }
+            None => return,
+        }
+
+        for attempt in 1u32.. {
+            let producer = match producer.upgrade() {
+                Some(producer) if !producer.closed.load(Ordering::Relaxed) => producer,
+                _ => return,
+            };
+
+            let span = debug_span!(
+                "rabbitmq_stream.producer_recovery",
+                stream = %producer.stream,
+                attempt,
+            );
+            match producer.recover().instrument(span).await {
+                Ok(resent) => {
+                    producer.record(|collector, context| collector.reconnect(context));
+                    producer.notify_recovery(ProducerRecoveryEvent::Recovered {
+                        stream: producer.stream.to_string(),
+                        outage: started.elapsed(),
+                        resent,
+                    });
+                    break;
+                }
+                Err(error) => {
+                    trace!(?error, "Failed to recover the producer connection");
+                    let policy = &*producer.environment.options.recovery_policy;
+                    let delay = match next_delay(policy, attempt, &error) {
+                        Some(delay) => delay,
+                        None => {
+                            let reason = error.to_string();
+                            // boxed as is, the error keeps its code and retryability
+                            producer
+                                .give_up(ClientError::GenericError(Box::new(error)))
+                                .await;
+                            producer.notify_recovery(ProducerRecoveryEvent::GaveUp {
+                                stream: producer.stream.to_string(),
+                                error: reason,
+                            });
+                            return;
+                        }
+                    };
+                    producer.notify_recovery(ProducerRecoveryEvent::Failed {
+                        stream: producer.stream.to_string(),
+                        error: error.to_string(),
+                    });
+                    drop(producer);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+/// Lease a publisher id on a connection to the leader of `stream`, publishing is only
+/// possible there
+///
+/// The stream has no leader while it is unavailable.
+async fn leader_connection(
+    environment: &Environment,
+    stream: &str,
+    id: Option<u8>,
+) -> Result<ConnectionLease, ProducerCreateError> {
+    match environment.locate(stream).await? {
+        Ok(metadata) => Ok(environment
+            .producer_connection(Some(&metadata.leader), id)
+            .await?),
+        Err(status) => Err(ProducerCreateError::Create {
+            stream: stream.to_owned(),
+            status,
+        }),
+    }
+}
+
+/// Run the callbacks of a producer one at a time, off the connection task
+async fn run_callbacks(mut callbacks: mpsc::UnboundedReceiver<BoxFuture<'static, ()>>) {
+    while let Some(callback) = callbacks.recv().await {
+        callback.await;
+    }
+}
+
+/// Fail the confirmations waiting longer than `confirm_timeout`
+async fn expire_confirmations(producer: Weak<ProducerInternal>, confirm_timeout: Duration) {
+    let mut interval = tokio::time::interval(confirm_timeout.min(Duration::from_secs(1)));
+    loop {
+        interval.tick().await;
+
+        let producer = match producer.upgrade() {
+            Some(producer) if !producer.closed.load(Ordering::Relaxed) => producer,
+            _ => break,
+        };
+
+        let mut sub_entries = producer.sub_entries.lock().await;
+        let mut waiting_confirmations = producer.waiting_confirmations.lock().await;
+        let expired: Vec<u64> = waiting_confirmations
+            .iter()
+            .filter(|(_, waiter)| waiter.published_at.elapsed() >= confirm_timeout)
+            .map(|(publishing_id, _)| *publishing_id)
+            .collect();
+        let expired: Vec<(u64, ProducerMessageWaiter)> = expired
+            .into_iter()
+            .filter_map(|publishing_id| {
+                // a late confirm of the entry finds nothing left behind
+                sub_entries.remove(&publishing_id);
+                waiting_confirmations
+                    .remove(&publishing_id)
+                    .map(|waiter| (publishing_id, waiter))
+            })
+            .collect();
+        drop(waiting_confirmations);
+        drop(sub_entries);
+
+        let failed = expired.len() as u64;
+        if failed > 0 {
+            producer.record(|collector, context| collector.publish_error(context, failed));
+        }
+        for (publishing_id, waiter) in expired {
+            waiter.handle_timeout(publishing_id);
+        }
+    }
+}
+
+#[derive(Clone)]
 struct ProducerConfirmHandler {
+    stream: String,
     waiting_confirmations: WaiterMap,
+    sub_entries: SubEntryMap,
+    publish_error_handler: Option<PublishErrorHandler>,
+    metadata_update_handler: Option<MetadataUpdateHandler>,
+    events: EventSender,
+    connection_closed: Arc<Notify>,
+    recovering: Arc<AtomicBool>,
+    latencies: Arc<LatencyHistogram>,
+    metrics: Option<Metrics>,
 }
 
 impl ProducerConfirmHandler {
-    async fn with_waiter(
-        &self,
-        publishing_id: u64,
-        cb: impl FnOnce(ProducerMessageWaiter) -> BoxFuture<'static, ()>,
-    ) {
+    /// Pause publishing and wake up the recovery task, once per outage
+    fn start_recovery(&self) {
+        if !self.recovering.swap(true, Ordering::SeqCst) {
+            self.connection_closed.notify_one();
+        }
+    }
+
+    /// Publishing ids of the messages behind the confirmed or rejected entry
+    async fn entry_publishing_ids(&self, publishing_id: u64) -> Vec<u64> {
+        self.sub_entries
+            .lock()
+            .await
+            .remove(&publishing_id)
+            .unwrap_or_else(|| vec![publishing_id])
+    }
+
+    fn record(&self, event: impl Fn(&dyn MetricsCollector, &MetricsContext<'_>)) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(Some(&self.stream), event);
+        }
+    }
+
+    fn record_latency(&self, waiter: &ProducerMessageWaiter) {
+        let latency = waiter.written_at.unwrap_or(waiter.published_at).elapsed();
+        self.latencies.record(latency);
+        self.record(|collector, context| collector.publish_confirm_latency(context, latency));
+    }
+
+    async fn with_waiter(&self, publishing_id: u64, cb: impl FnOnce(ProducerMessageWaiter)) {
         let mut conf_guard = self.waiting_confirmations.lock().await;
-        match conf_guard.remove(&publishing_id) {
-            Some(confirm_sender) => cb(confirm_sender).await,
-            None => todo!(),
+        let waiter = conf_guard.remove(&publishing_id);
+        drop(conf_guard);
+        match waiter {
+            Some(confirm_sender) => cb(confirm_sender),
+            None => trace!(publishing_id, "No waiter for confirmation"),
         }
     }
 }
@@ -192,26 +1565,62 @@ impl MessageHandler for ProducerConfirmHandler {
             Some(Ok(response)) => {
                 match response.kind() {
                     ResponseKind::PublishConfirm(confirm) => {
-                        for publishing_id in &confirm.publishing_ids {
-                            self.with_waiter(*publishing_id, |waiter| {
-                                async {
-                                    let _ = waiter.handle_confirm().await;
-                                }
-                                .boxed()
-                            })
-                            .await;
+                        let mut confirmed = 0;
+                        for entry_id in &confirm.publishing_ids {
+                            for publishing_id in self.entry_publishing_ids(*entry_id).await {
+                                confirmed += 1;
+                                self.with_waiter(publishing_id, |waiter| {
+                                    self.record_latency(&waiter);
+                                    waiter.handle_confirm(publishing_id)
+                                })
+                                .await;
+                            }
                         }
+                        self.record(|collector, context| {
+                            collector.publish_confirm(context, confirmed)
+                        });
                     }
                     ResponseKind::PublishError(error) => {
                         for err in &error.publishing_errors {
-                            let code = err.error_code.clone();
-                            self.with_waiter(err.publishing_id, move |waiter| {
-                                async {
-                                    let _ = waiter.handle_error(code).await;
+                            // the messages wait for the recovery to publish them again
+                            if matches!(
+                                err.error_code,
+                                ResponseCode::StreamNotAvailable
+                                    | ResponseCode::PublisherDoesNotExist
+                            ) {
+                                self.start_recovery();
+                                continue;
+                            }
+                            for publishing_id in self.entry_publishing_ids(err.publishing_id).await
+                            {
+                                self.record(|collector, context| {
+                                    collector.publish_error(context, 1)
+                                });
+                                if let Some(handler) = &self.publish_error_handler {
+                                    handler(publishing_id, err.error_code.clone().into());
                                 }
-                                .boxed()
-                            })
-                            .await;
+                                let code = err.error_code.clone();
+                                self.with_waiter(publishing_id, move |waiter| {
+                                    waiter.handle_error(publishing_id, code)
+                                })
+                                .await;
+                            }
+                        }
+                    }
+                    ResponseKind::MetadataUpdate(update) => {
+                        trace!(?update, "Metadata update");
+                        events::emit(
+                            &self.events,
+                            StreamEvent::MetadataUpdate {
+                                stream: update.stream().to_owned(),
+                                code: update.code().clone(),
+                            },
+                        );
+                        if update.stream() == self.stream {
+                            self.start_recovery();
+                        }
+                        if let Some(handler) = &self.metadata_update_handler {
+                            handler();
                         }
                     }
                     _ => {}
@@ -219,47 +1628,85 @@ impl MessageHandler for ProducerConfirmHandler {
             }
             Some(Err(error)) => {
                 trace!(?error);
-                // TODO clean all waiting for confirm
             }
-            None => todo!(),
+            None => self.start_recovery(),
         }
         Ok(())
     }
 }
 
 struct ProducerMessageWaiter {
-    stream: String,
-    publisher_id: u8,
-    tx: Sender<Result<(), ProducerPublishError>>,
+    stream: Arc<str>,
+    message: Message,
+    published_at: Instant,
+    /// Last time the message was written in a publish frame
+    written_at: Option<Instant>,
+    cb: ConfirmCallback,
+    callbacks: CallbackQueue,
+    _permit: InFlightPermit,
 }
 
 impl ProducerMessageWaiter {
-    fn waiter(
-        stream: String,
-        publisher_id: u8,
-    ) -> (Receiver<Result<(), ProducerPublishError>>, Self) {
-        let (tx, rx) = channel();
-
-        (
-            rx,
-            Self {
-                stream,
-                publisher_id,
-                tx,
+    /// Queue the callback with the outcome of the message
+    fn complete(
+        self,
+        status: impl FnOnce(Message) -> Result<ConfirmationStatus, ProducerPublishError>,
+    ) {
+        let callback = (self.cb)(status(self.message));
+        let _ = self.callbacks.send(callback);
+    }
+    fn handle_confirm(self, publishing_id: u64) {
+        self.complete(|message| {
+            Ok(ConfirmationStatus {
+                publishing_id,
+                confirmed: true,
+                status: ResponseCode::Ok,
+                message,
+            })
+        })
+    }
+    fn handle_error(self, publishing_id: u64, status: ResponseCode) {
+        self.complete(|message| {
+            Ok(ConfirmationStatus {
+                publishing_id,
+                confirmed: false,
+                status,
+                message,
+            })
+        })
+    }
+    fn handle_publish_failure(self, publishing_id: u64, err: &ClientError) {
+        let error = match err {
+            ClientError::ClosedByBroker { code, reason } => {
+                ProducerPublishError::Client(ClientError::ClosedByBroker {
+                    code: code.clone(),
+                    reason: reason.clone(),
+                })
+            }
+            err => ProducerPublishError::Publish {
+                stream: self.stream.to_string(),
+                publishing_id,
+                reason: err.to_string(),
             },
-        )
+        };
+        self.complete(|_| Err(error))
     }
-
-    async fn handle_confirm(self) -> RabbitMQStreamResult<()> {
-        let _ = self.tx.send(Ok(()));
-        Ok(())
+    fn handle_dropped(self, publishing_id: u64) {
+        let stream = self.stream.to_string();
+        self.complete(|_| {
+            Err(ProducerPublishError::Dropped {
+                stream,
+                publishing_id,
+            })
+        })
     }
-    async fn handle_error(self, status: ResponseCode) -> RabbitMQStreamResult<()> {
-        let _ = self.tx.send(Err(ProducerPublishError::Create {
-            stream: self.stream,
-            publisher_id: self.publisher_id,
-            status,
-        }));
-        Ok(())
+    fn handle_timeout(self, publishing_id: u64) {
+        let stream = self.stream.to_string();
+        self.complete(|_| {
+            Err(ProducerPublishError::Timeout {
+                stream,
+                publishing_id,
+            })
+        })
     }
 }
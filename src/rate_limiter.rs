@@ -0,0 +1,242 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::Instant};
+
+use crate::byte_capacity::ByteCapacity;
+
+/// Token bucket throttling the messages published by the producers sharing it
+///
+/// ```
+/// use rabbitmq_stream_client::types::{ByteCapacity, RateLimiter};
+///
+/// let limiter = RateLimiter::new()
+///     .messages_per_second(10_000)
+///     .bytes_per_second(ByteCapacity::MB(5));
+/// // e.g. once the backfill competes with the live traffic
+/// limiter.set_messages_per_second(Some(1_000));
+/// ```
+///
+/// The buckets hold one second of messages and bytes, a send waits once one of them is
+/// empty. A batch larger than a bucket is sent when the bucket is full and the next sends
+/// wait for the bucket to refill. The clones share the buckets: a limiter passed to
+/// several producers bounds their total rate, the limits can be changed while publishing.
+#[derive(Clone, Debug)]
+pub struct RateLimiter(Arc<RateLimiterInner>);
+
+#[derive(Debug)]
+struct RateLimiterInner {
+    buckets: Mutex<Buckets>,
+    /// Wakes the sends waiting for tokens when the limits change
+    changed: Notify,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    messages: Bucket,
+    bytes: Bucket,
+    refilled_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Bucket {
+    /// Tokens per second, unlimited when `None`
+    rate: Option<f64>,
+    /// Negative after a batch larger than the bucket
+    tokens: f64,
+}
+
+impl Bucket {
+    fn set_rate(&mut self, rate: Option<f64>) {
+        self.tokens = match (self.rate, rate) {
+            (Some(_), Some(rate)) => self.tokens.min(rate),
+            (_, Some(rate)) => rate,
+            (_, None) => 0.0,
+        };
+        self.rate = rate;
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        if let Some(rate) = self.rate {
+            self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        }
+    }
+
+    /// Time until the bucket is no longer in debt
+    fn wait(&self) -> Duration {
+        match self.rate {
+            Some(rate) if self.tokens < 0.0 => Duration::from_secs_f64(-self.tokens / rate),
+            _ => Duration::ZERO,
+        }
+    }
+
+    fn take(&mut self, tokens: usize) {
+        if self.rate.is_some() {
+            self.tokens -= tokens as f64;
+        }
+    }
+}
+
+impl Buckets {
+    /// Take the tokens of the batch, or return how long to wait before trying again
+    fn try_acquire(&mut self, messages: usize, bytes: usize) -> Option<Duration> {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.refilled_at = now;
+        self.messages.refill(elapsed);
+        self.bytes.refill(elapsed);
+
+        let wait = self.messages.wait().max(self.bytes.wait());
+        if wait > Duration::ZERO {
+            return Some(wait);
+        }
+        self.messages.take(messages);
+        self.bytes.take(bytes);
+        None
+    }
+}
+
+impl RateLimiter {
+    /// Limiter without limits, set them with the other methods
+    pub fn new() -> Self {
+        RateLimiter(Arc::new(RateLimiterInner {
+            buckets: Mutex::new(Buckets {
+                messages: Bucket::default(),
+                bytes: Bucket::default(),
+                refilled_at: Instant::now(),
+            }),
+            changed: Notify::new(),
+        }))
+    }
+
+    /// Limit the number of messages sent per second
+    pub fn messages_per_second(self, messages: u32) -> Self {
+        self.set_messages_per_second(Some(messages));
+        self
+    }
+
+    /// Limit the encoded size of the messages sent per second
+    pub fn bytes_per_second(self, bytes: ByteCapacity) -> Self {
+        self.set_bytes_per_second(Some(bytes));
+        self
+    }
+
+    /// Change the limit of messages per second, no limit with `None`
+    pub fn set_messages_per_second(&self, messages: Option<u32>) {
+        self.update(|buckets| {
+            buckets
+                .messages
+                .set_rate(messages.map(|messages| messages.max(1) as f64))
+        });
+    }
+
+    /// Change the limit of bytes per second, no limit with `None`
+    pub fn set_bytes_per_second(&self, bytes: Option<ByteCapacity>) {
+        self.update(|buckets| {
+            buckets
+                .bytes
+                .set_rate(bytes.map(|bytes| bytes.bytes().max(1) as f64))
+        });
+    }
+
+    /// Current limit of messages per second
+    pub fn messages_limit(&self) -> Option<u32> {
+        let buckets = self.0.buckets.lock().unwrap();
+        buckets.messages.rate.map(|rate| rate as u32)
+    }
+
+    /// Current limit of bytes per second
+    pub fn bytes_limit(&self) -> Option<u64> {
+        let buckets = self.0.buckets.lock().unwrap();
+        buckets.bytes.rate.map(|rate| rate as u64)
+    }
+
+    fn update(&self, update: impl FnOnce(&mut Buckets)) {
+        let mut buckets = self.0.buckets.lock().unwrap();
+        // the tokens earned so far are counted with the previous limits
+        buckets.try_acquire(0, 0);
+        update(&mut buckets);
+        drop(buckets);
+        self.0.changed.notify_waiters();
+    }
+
+    /// Wait until `messages` messages of `bytes` bytes in total may be sent
+    pub(crate) async fn acquire(&self, messages: usize, bytes: usize) {
+        loop {
+            let changed = self.0.changed.notified();
+            let wait = self.0.buckets.lock().unwrap().try_acquire(messages, bytes);
+            match wait {
+                None => return,
+                Some(wait) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = changed => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::RateLimiter;
+    use crate::byte_capacity::ByteCapacity;
+
+    #[tokio::test(start_paused = true)]
+    async fn messages_per_second_test() {
+        let limiter = RateLimiter::new().messages_per_second(10);
+        let start = Instant::now();
+        // the bucket starts full, then refills at 10 messages per second
+        limiter.acquire(10, 0).await;
+        assert_eq!(Duration::ZERO, start.elapsed());
+        limiter.acquire(1, 0).await;
+        limiter.acquire(5, 0).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        limiter.acquire(1, 0).await;
+        assert!(start.elapsed() >= Duration::from_millis(600));
+        assert_eq!(Some(10), limiter.messages_limit());
+        assert_eq!(None, limiter.bytes_limit());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bytes_per_second_test() {
+        let limiter = RateLimiter::new().bytes_per_second(ByteCapacity::KB(1));
+        let start = Instant::now();
+        // a batch larger than the bucket goes through, the next one waits for the debt
+        limiter.acquire(1, 3000).await;
+        limiter.acquire(1, 1).await;
+        assert!(start.elapsed() >= Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limit_changed_test() {
+        let limiter = RateLimiter::new().messages_per_second(1);
+        limiter.acquire(2, 0).await;
+
+        let waiting = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire(1, 0).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        // no limit, the waiting send goes through at once
+        let start = Instant::now();
+        limiter.set_messages_per_second(None);
+        waiting.await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(10));
+        assert_eq!(None, limiter.messages_limit());
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::retry_policy::RetryPolicy;
+
+/// Increment of the splitmix64 generator drawing the jitter
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Delay between two attempts to recover a producer or a consumer
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rabbitmq_stream_client::types::RecoveryBackoff;
+///
+/// let backoff = RecoveryBackoff::new()
+///     .delay(Duration::from_secs(2))
+///     .jitter(Duration::from_millis(500))
+///     .seed(42);
+/// ```
+///
+/// Each attempt waits `delay` plus a random jitter up to `jitter`, spreading the
+/// reconnections of the clients of a restarted node. The clones share the generator of
+/// the jitter, a fixed seed replays the same delays. As a [`RetryPolicy`] it attempts
+/// again after every error, without limit unless [`RecoveryBackoff::max_attempts`] is set.
+#[derive(Clone, Debug)]
+pub struct RecoveryBackoff {
+    delay: Duration,
+    jitter: Duration,
+    max_attempts: Option<u32>,
+    state: Arc<AtomicU64>,
+}
+
+impl RecoveryBackoff {
+    /// Wait 5 seconds plus up to 1 second, with a seed taken from the clock
+    pub fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        RecoveryBackoff {
+            delay: Duration::from_secs(5),
+            jitter: Duration::from_secs(1),
+            max_attempts: None,
+            state: Arc::new(AtomicU64::new(seed)),
+        }
+    }
+
+    /// Fixed part of the delay
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Upper bound of the random part of the delay, no jitter with zero
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Give up after `max_attempts` attempts, the first one included
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts.max(1));
+        self
+    }
+
+    /// Seed of the jitter, the sequence of delays is the same for the same seed
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.state = Arc::new(AtomicU64::new(seed));
+        self
+    }
+
+    /// Delay before the next attempt
+    pub(crate) fn next_delay(&self) -> Duration {
+        let mut z = self
+            .state
+            .fetch_add(GOLDEN_GAMMA, Ordering::Relaxed)
+            .wrapping_add(GOLDEN_GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // 53 bits fill the mantissa of a fraction in [0, 1)
+        let fraction = (z >> 11) as f64 / (1u64 << 53) as f64;
+        self.delay + self.jitter.mul_f64(fraction)
+    }
+}
+
+impl RetryPolicy for RecoveryBackoff {
+    fn max_attempts(&self) -> Option<u32> {
+        self.max_attempts
+    }
+
+    fn delay(&self, _attempt: u32) -> Duration {
+        self.next_delay()
+    }
+}
+
+impl Default for RecoveryBackoff {
+    fn default() -> Self {
+        RecoveryBackoff::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RecoveryBackoff;
+    use crate::retry_policy::RetryPolicy;
+
+    #[test]
+    fn recovery_backoff_test() {
+        let backoff = RecoveryBackoff::new()
+            .delay(Duration::from_secs(1))
+            .jitter(Duration::from_millis(100))
+            .seed(7);
+        let delays: Vec<_> = (0..10).map(|_| backoff.next_delay()).collect();
+        assert!(delays
+            .iter()
+            .all(|delay| *delay >= Duration::from_secs(1) && *delay < Duration::from_millis(1100)));
+        assert!(delays.windows(2).any(|pair| pair[0] != pair[1]));
+
+        let replayed = backoff.clone().seed(7);
+        assert_eq!(
+            delays,
+            (0..10).map(|_| replayed.next_delay()).collect::<Vec<_>>()
+        );
+
+        let fixed = RecoveryBackoff::new()
+            .delay(Duration::from_secs(2))
+            .jitter(Duration::ZERO);
+        assert_eq!(Duration::from_secs(2), fixed.next_delay());
+    }
+
+    #[test]
+    fn retry_policy_test() {
+        let backoff = RecoveryBackoff::new().jitter(Duration::ZERO);
+        assert_eq!(None, RetryPolicy::max_attempts(&backoff));
+        assert_eq!(Duration::from_secs(5), RetryPolicy::delay(&backoff, 3));
+        assert_eq!(Some(4), RetryPolicy::max_attempts(&backoff.max_attempts(4)));
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{
+    consumer::Delivery,
+    error::{ConsumerCreateError, ConsumerDeliveryError, StreamStatsError},
+    types::OffsetSpecification,
+    Consumer, Environment,
+};
+
+/// Builder for [`Replay`], see [`Environment::replay`]
+pub struct ReplayBuilder {
+    environment: Environment,
+    stream: String,
+    from: Option<SystemTime>,
+    until: Option<SystemTime>,
+}
+
+impl ReplayBuilder {
+    pub(crate) fn new(environment: Environment, stream: &str) -> Self {
+        ReplayBuilder {
+            environment,
+            stream: stream.to_owned(),
+            from: None,
+            until: None,
+        }
+    }
+
+    /// Start from the first chunk written at or after `from`, the first message by default
+    pub fn from(mut self, from: SystemTime) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Stop with the last chunk written at or before `until`
+    pub fn until(mut self, until: SystemTime) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Subscribe to the stream
+    ///
+    /// The replay also stops with the last message committed when it is built, the
+    /// messages published afterwards are not replayed.
+    pub async fn build(self) -> Result<Replay, ConsumerCreateError> {
+        if let Some(from) = self.from {
+            if from > SystemTime::now() || self.until.is_some_and(|until| until < from) {
+                return Ok(Replay::done());
+            }
+        }
+
+        let stats =
+            self.environment
+                .stream_stats(&self.stream)
+                .await
+                .map_err(|error| match error {
+                    StreamStatsError::Stats { stream, status } => {
+                        ConsumerCreateError::Create { stream, status }
+                    }
+                    StreamStatsError::Client(error) => ConsumerCreateError::Client(error),
+                })?;
+        let last_offset = match stats.committed_offset().or(stats.committed_chunk_id()) {
+            Some(last_offset) => last_offset,
+            None => return Ok(Replay::done()),
+        };
+
+        let offset = match self.from {
+            Some(from) => OffsetSpecification::Timestamp(millis(from) as i64),
+            None => OffsetSpecification::First,
+        };
+        let consumer = self
+            .environment
+            .consumer()
+            .offset(offset)
+            .build(&self.stream)
+            .await?;
+
+        Ok(Replay {
+            consumer: Some(consumer),
+            until: self.until.map(millis),
+            last_offset,
+        })
+    }
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Bounded [`Stream`] of the messages of a stream written in a time range
+///
+/// The consumer is closed once the range is replayed.
+pub struct Replay {
+    consumer: Option<Consumer>,
+    /// Timestamp in milliseconds of the last chunk to replay
+    until: Option<u64>,
+    /// Last committed offset when the replay started
+    last_offset: u64,
+}
+
+impl Replay {
+    fn done() -> Self {
+        Replay {
+            consumer: None,
+            until: None,
+            last_offset: 0,
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(consumer) = self.consumer.take() {
+            if !consumer.is_closed() {
+                tokio::task::spawn(consumer.handle().close());
+            }
+        }
+    }
+}
+
+impl Stream for Replay {
+    type Item = Result<Delivery, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let consumer = match self.consumer.as_mut() {
+            Some(consumer) => consumer,
+            None => return Poll::Ready(None),
+        };
+        let delivery = match consumer.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(delivery))) => delivery,
+            Poll::Ready(None) => {
+                self.consumer = None;
+                return Poll::Ready(None);
+            }
+            poll => return poll,
+        };
+
+        let chunk = *delivery.chunk();
+        if self.until.is_some_and(|until| chunk.timestamp() > until) {
+            self.close();
+            return Poll::Ready(None);
+        }
+        // committed offsets are the last offsets of their chunk
+        if delivery.offset >= self.last_offset && delivery.offset == chunk.last_offset() {
+            self.close();
+        }
+        Poll::Ready(Some(Ok(delivery)))
+    }
+}
@@ -0,0 +1,226 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering::SeqCst},
+    Arc, Mutex,
+};
+
+use futures::StreamExt;
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    error::{ClientError, ReplicatorError},
+    types::OffsetSpecification,
+    Consumer, Environment, Producer,
+};
+
+/// Builder for [`Replicator`], see [`Environment::replicator`]
+pub struct ReplicatorBuilder {
+    source: Environment,
+    stream: String,
+    target: Option<Environment>,
+    target_stream: Option<String>,
+    name: Option<String>,
+    offset: OffsetSpecification,
+}
+
+impl ReplicatorBuilder {
+    pub(crate) fn new(source: Environment, stream: &str) -> Self {
+        ReplicatorBuilder {
+            source,
+            stream: stream.to_owned(),
+            target: None,
+            target_stream: None,
+            name: None,
+            offset: OffsetSpecification::First,
+        }
+    }
+
+    /// Environment of the cluster to replicate to, the source one by default
+    pub fn target(mut self, environment: Environment) -> Self {
+        self.target = Some(environment);
+        self
+    }
+
+    /// Stream to replicate to, named as the source stream by default
+    pub fn target_stream(mut self, stream: &str) -> Self {
+        self.target_stream = Some(stream.to_owned());
+        self
+    }
+
+    /// Name of the producer publishing to the target, `{stream}-replicator` by default
+    ///
+    /// A replicator started again with the same name resumes after the last message
+    /// stored by the target.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Where to start in the source stream when the target has no message of the
+    /// replicator, [`OffsetSpecification::First`] by default
+    pub fn offset(mut self, offset: OffsetSpecification) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Start replicating on a task of its own
+    pub async fn start(self) -> Result<Replicator, ReplicatorError> {
+        let ReplicatorBuilder {
+            source,
+            stream,
+            target,
+            target_stream,
+            name,
+            offset,
+        } = self;
+        let target_stream = target_stream.unwrap_or_else(|| stream.clone());
+        if target.is_none() && target_stream == stream {
+            return Err(ReplicatorError::SameStream(stream));
+        }
+        let target = target.unwrap_or_else(|| source.clone());
+        let name = name.unwrap_or_else(|| format!("{}-replicator", stream));
+
+        let producer = target.producer().name(&name).build(&target_stream).await?;
+        // the publishing ids are the source offsets + 1, 0 when nothing was replicated
+        let offset = match producer.last_publishing_id().await {
+            Ok(Some(0)) | Ok(None) => offset,
+            Ok(Some(next)) => OffsetSpecification::Offset(next),
+            Err(error) => {
+                let _ = producer.close().await;
+                return Err(error.into());
+            }
+        };
+        let consumer = match source.consumer().offset(offset).build(&stream).await {
+            Ok(consumer) => consumer,
+            Err(error) => {
+                let _ = producer.close().await;
+                return Err(error.into());
+            }
+        };
+
+        let progress = Arc::new(Progress::default());
+        let (stop, stopped) = watch::channel(false);
+        let stop = Arc::new(stop);
+        let task = tokio::task::spawn(replicate(
+            consumer,
+            producer,
+            stream,
+            progress.clone(),
+            stop.clone(),
+            stopped,
+        ));
+        Ok(Replicator {
+            progress,
+            stop,
+            task,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Progress {
+    replicated: AtomicU64,
+    /// Highest publishing id confirmed by the target
+    confirmed_id: AtomicU64,
+    failure: Mutex<Option<ReplicatorError>>,
+}
+
+/// Copy of a stream to another stream, of the same or another cluster
+///
+/// Every message is published to the target by a named producer with its offset in the
+/// source stream + 1 as publishing id: when a replicator is started again, it resumes
+/// from the last message stored by the target and the broker drops the messages
+/// published twice.
+pub struct Replicator {
+    progress: Arc<Progress>,
+    stop: Arc<watch::Sender<bool>>,
+    task: JoinHandle<Result<(), ReplicatorError>>,
+}
+
+impl Replicator {
+    /// Number of messages confirmed by the target since the start
+    pub fn replicated(&self) -> u64 {
+        self.progress.replicated.load(SeqCst)
+    }
+
+    /// Offset in the source stream of the last message confirmed by the target
+    pub fn last_offset(&self) -> Option<u64> {
+        self.progress.confirmed_id.load(SeqCst).checked_sub(1)
+    }
+
+    /// Whether the replicator stopped on its own, after a failure or the deletion of the
+    /// source stream
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stop once the messages published to the target are confirmed
+    ///
+    /// Returns the failure which stopped the replicator, if any.
+    pub async fn stop(self) -> Result<(), ReplicatorError> {
+        let _ = self.stop.send(true);
+        self.task
+            .await
+            .map_err(|error| ClientError::GenericError(Box::new(error)))?
+    }
+}
+
+async fn replicate(
+    mut consumer: Consumer,
+    producer: Producer,
+    stream: String,
+    progress: Arc<Progress>,
+    stop: Arc<watch::Sender<bool>>,
+    mut stopped: watch::Receiver<bool>,
+) -> Result<(), ReplicatorError> {
+    let result: Result<(), ReplicatorError> = async {
+        loop {
+            let delivery = tokio::select! {
+                delivery = consumer.next() => delivery,
+                _ = stopped.changed() => return Ok(()),
+            };
+            let delivery = match delivery {
+                Some(delivery) => delivery?,
+                None => return Ok(()),
+            };
+            let mut message = delivery.message;
+            message.set_publishing_id(delivery.offset + 1);
+
+            let (progress, stop, stream) = (progress.clone(), stop.clone(), stream.clone());
+            producer
+                .send(message, move |status| async move {
+                    let failure = match status {
+                        Ok(status) if status.confirmed() => {
+                            progress.replicated.fetch_add(1, SeqCst);
+                            progress
+                                .confirmed_id
+                                .fetch_max(status.publishing_id(), SeqCst);
+                            return;
+                        }
+                        Ok(status) => ReplicatorError::Unconfirmed {
+                            stream,
+                            offset: status.publishing_id() - 1,
+                            status: status.status().clone(),
+                        },
+                        Err(error) => error.into(),
+                    };
+                    // a later message would hide the missing one from the deduplication
+                    progress.failure.lock().unwrap().get_or_insert(failure);
+                    let _ = stop.send(true);
+                })
+                .await?;
+        }
+    }
+    .await;
+
+    // the producer waits for the pending confirmations before closing
+    let closed = producer.close().await;
+    if !consumer.is_closed() {
+        let _ = consumer.handle().close().await;
+    }
+    if let Some(failure) = progress.failure.lock().unwrap().take() {
+        return Err(failure);
+    }
+    result?;
+    closed?;
+    Ok(())
+}
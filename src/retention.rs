@@ -0,0 +1,145 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use crate::error::RetentionParseError;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// Age after which the segments of a stream are discarded, the `max-age` argument
+///
+/// The broker counts in seconds and rejects 0, so the age is rounded up to a whole number
+/// of seconds, at least one. It displays in the format of the broker with the largest unit
+/// that keeps it exact, like `7D` or `90s`, and parses from the same format, `Y` and `M`
+/// standing for 365 and 30 days as for the broker and `d` for days too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MaxAge(u64);
+
+impl MaxAge {
+    pub fn seconds(seconds: u64) -> Self {
+        MaxAge(seconds.max(1))
+    }
+
+    pub fn minutes(minutes: u64) -> Self {
+        Self::seconds(minutes.saturating_mul(MINUTE))
+    }
+
+    pub fn hours(hours: u64) -> Self {
+        Self::seconds(hours.saturating_mul(HOUR))
+    }
+
+    pub fn days(days: u64) -> Self {
+        Self::seconds(days.saturating_mul(DAY))
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs(self.0)
+    }
+
+    /// The value of the stream arguments and policies
+    pub(crate) fn to_argument(self) -> String {
+        self.to_string()
+    }
+}
+
+impl From<Duration> for MaxAge {
+    fn from(duration: Duration) -> Self {
+        let rounding = u64::from(duration.subsec_nanos() > 0);
+        Self::seconds(duration.as_secs() + rounding)
+    }
+}
+
+impl fmt::Display for MaxAge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let units = vec![(DAY, "D"), (HOUR, "h"), (MINUTE, "m")];
+        match units
+            .into_iter()
+            .find(|(unit, _)| self.0.is_multiple_of(*unit))
+        {
+            Some((unit, suffix)) => write!(f, "{}{}", self.0 / unit, suffix),
+            None => write!(f, "{}s", self.0),
+        }
+    }
+}
+
+impl FromStr for MaxAge {
+    type Err = RetentionParseError;
+
+    /// Parse a number followed by `Y`, `M`, `D`, `h`, `m` or `s`, seconds without a unit
+    fn from_str(age: &str) -> Result<Self, Self::Err> {
+        let trimmed = age.trim();
+        let digits = trimmed.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let value: u64 = match digits.parse() {
+            Ok(value) if value > 0 => value,
+            _ => return Err(RetentionParseError::InvalidValue(age.to_owned())),
+        };
+        let unit = match &trimmed[digits.len()..] {
+            "Y" => 365 * DAY,
+            "M" => 30 * DAY,
+            "D" | "d" => DAY,
+            "h" => HOUR,
+            "m" => MINUTE,
+            "" | "s" => 1,
+            unit => {
+                return Err(RetentionParseError::UnknownUnit {
+                    value: age.to_owned(),
+                    unit: unit.to_owned(),
+                    expected: "Y, M, D, h, m or s",
+                })
+            }
+        };
+        match value.checked_mul(unit) {
+            Some(seconds) => Ok(MaxAge::seconds(seconds)),
+            None => Err(RetentionParseError::InvalidValue(age.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::MaxAge;
+    use crate::error::RetentionParseError;
+
+    #[test]
+    fn max_age_display_test() {
+        assert_eq!("7D", MaxAge::days(7).to_string());
+        assert_eq!("36h", MaxAge::hours(36).to_string());
+        assert_eq!("1h", MaxAge::from(Duration::from_secs(3600)).to_string());
+        assert_eq!("90s", MaxAge::seconds(90).to_string());
+        assert_eq!("1s", MaxAge::from(Duration::from_millis(10)).to_string());
+        assert_eq!("2s", MaxAge::from(Duration::from_millis(1500)).to_string());
+        assert_eq!("1s", MaxAge::seconds(0).to_argument());
+    }
+
+    #[test]
+    fn max_age_parse_test() {
+        assert_eq!(Ok(MaxAge::days(365)), "1Y".parse());
+        assert_eq!(Ok(MaxAge::days(60)), "2M".parse());
+        assert_eq!(Ok(MaxAge::minutes(5)), "5m".parse());
+        assert_eq!(Ok(MaxAge::days(7)), "7d".parse());
+        assert_eq!(Ok(MaxAge::seconds(30)), "30".parse());
+        assert_eq!(
+            Err(RetentionParseError::InvalidValue("0s".to_owned())),
+            "0s".parse::<MaxAge>()
+        );
+        assert_eq!(
+            Err(RetentionParseError::InvalidValue(
+                "100000000000000000Y".to_owned()
+            )),
+            "100000000000000000Y".parse::<MaxAge>()
+        );
+        assert_eq!(
+            Duration::from_secs(u64::MAX),
+            MaxAge::days(u64::MAX).duration()
+        );
+        assert!(matches!(
+            "3w".parse::<MaxAge>(),
+            Err(RetentionParseError::UnknownUnit { .. })
+        ));
+        for age in &[MaxAge::days(3), MaxAge::hours(5), MaxAge::seconds(61)] {
+            assert_eq!(Ok(*age), age.to_string().parse());
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::{error::Error, future::Future, time::Duration};
+
+use crate::error::ClientError;
+
+/// When to attempt again an operation that failed
+///
+/// The policy of [`EnvironmentBuilder::retry_policy`](crate::EnvironmentBuilder::retry_policy)
+/// applies to the metadata lookups, offset queries and stream creations of the
+/// environment, the one of [`EnvironmentBuilder::recovery_policy`](crate::EnvironmentBuilder::recovery_policy)
+/// to the reconnections of the producers and consumers.
+///
+/// ```
+/// use std::{error::Error, time::Duration};
+///
+/// use rabbitmq_stream_client::{error::ClientError, types::RetryPolicy};
+///
+/// /// Three attempts, doubling the delay, only when the connection failed
+/// struct ConnectionRetries;
+///
+/// impl RetryPolicy for ConnectionRetries {
+///     fn max_attempts(&self) -> Option<u32> {
+///         Some(3)
+///     }
+///
+///     fn delay(&self, attempt: u32) -> Duration {
+///         Duration::from_millis(100) * 2u32.pow(attempt - 1)
+///     }
+///
+///     fn is_retryable(&self, error: &(dyn Error + 'static)) -> bool {
+///         matches!(error.downcast_ref(), Some(ClientError::Io(_)))
+///     }
+/// }
+/// ```
+pub trait RetryPolicy: Send + Sync {
+    /// Attempts at most, the first one included, no limit with `None`
+    fn max_attempts(&self) -> Option<u32> {
+        None
+    }
+
+    /// Delay after the failed attempt `attempt`, the first one is 1
+    fn delay(&self, attempt: u32) -> Duration;
+
+    /// Whether to attempt again after `error`, every error by default
+    ///
+    /// `error` is the one of the operation, e.g. a
+    /// [`ClientError`](crate::error::ClientError) for the requests of the environment or a
+    /// [`ProducerCreateError`](crate::error::ProducerCreateError) for the recovery of a
+    /// producer.
+    fn is_retryable(&self, error: &(dyn Error + 'static)) -> bool {
+        let _ = error;
+        true
+    }
+}
+
+/// Fail on the first error
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoRetry;
+
+impl RetryPolicy for NoRetry {
+    fn max_attempts(&self) -> Option<u32> {
+        Some(1)
+    }
+
+    fn delay(&self, _attempt: u32) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Delay before the attempt following `attempt`, `None` when the policy gives up
+///
+/// An invalid name fails again on every attempt, it is never retried.
+pub(crate) fn next_delay(
+    policy: &dyn RetryPolicy,
+    attempt: u32,
+    error: &(dyn Error + 'static),
+) -> Option<Duration> {
+    if let Some(ClientError::InvalidName { .. }) = error.downcast_ref() {
+        return None;
+    }
+    let exhausted = policy.max_attempts().is_some_and(|max| attempt >= max);
+    (!exhausted && policy.is_retryable(error)).then(|| policy.delay(attempt))
+}
+
+/// Run `operation` until it succeeds or the policy gives up, returns the last error then
+pub(crate) async fn retry<T, E, F, Fut>(policy: &dyn RetryPolicy, mut operation: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Error + 'static,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        match next_delay(policy, attempt, &error) {
+            Some(delay) => tokio::time::sleep(delay).await,
+            None => return Err(error),
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    use super::{next_delay, retry, NoRetry, RetryPolicy};
+    use crate::error::ClientError;
+
+    struct ThreeAttempts;
+
+    impl RetryPolicy for ThreeAttempts {
+        fn max_attempts(&self) -> Option<u32> {
+            Some(3)
+        }
+
+        fn delay(&self, attempt: u32) -> Duration {
+            Duration::from_secs(attempt as u64)
+        }
+
+        fn is_retryable(&self, error: &(dyn Error + 'static)) -> bool {
+            !matches!(error.downcast_ref(), Some(ClientError::AlreadyClosed))
+        }
+    }
+
+    #[test]
+    fn next_delay_test() {
+        let error = ClientError::CastError("cast".to_owned());
+        assert_eq!(
+            Some(Duration::from_secs(2)),
+            next_delay(&ThreeAttempts, 2, &error)
+        );
+        assert_eq!(None, next_delay(&ThreeAttempts, 3, &error));
+        assert_eq!(
+            None,
+            next_delay(&ThreeAttempts, 1, &ClientError::AlreadyClosed)
+        );
+        assert_eq!(None, next_delay(&NoRetry, 1, &error));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_test() {
+        let attempts = AtomicU32::new(0);
+        let result: Result<u32, ClientError> = retry(&ThreeAttempts, || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(ClientError::CastError("cast".to_owned())),
+                attempt => Ok(attempt),
+            }
+        })
+        .await;
+        assert_eq!(2, result.unwrap());
+
+        attempts.store(0, Ordering::SeqCst);
+        let result: Result<(), ClientError> = retry(&ThreeAttempts, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(ClientError::CastError("cast".to_owned()))
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(3, attempts.load(Ordering::SeqCst));
+    }
+}
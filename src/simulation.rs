@@ -0,0 +1,123 @@
+//! Deterministic simulation of the broker for the tests of the recovery logic, enabled
+//! with the `simulation` feature
+//!
+//! A [`Simulation`] controls everything the recovery of the producers and consumers
+//! depends on, to reproduce reconnect storms, single active consumer handovers and
+//! streams deleted and created again:
+//!
+//! - the time: the tests run on a paused current thread runtime, the timers fire in order
+//!   as soon as every task waits, without waiting for real
+//! - the randomness: the jitter of the [`RecoveryBackoff`] is seeded
+//! - the broker: a [`TestBroker`] with a [`FaultInjector`] on its connections
+//!
+//! The same seed replays the same run.
+//!
+//! ```
+//! # #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::time::Duration;
+//!
+//! use rabbitmq_stream_client::{simulation::Simulation, types::Message};
+//!
+//! let simulation = Simulation::new(42);
+//! let environment = simulation.environment().build().await?;
+//! environment.stream_creator().create("orders").await?;
+//! let producer = environment.producer().name("producer").build("orders").await?;
+//!
+//! // the node is down for a minute, the producer recovers when it is back
+//! simulation.outage(Duration::from_secs(60)).await;
+//! simulation.advance(Duration::from_secs(10)).await;
+//! producer
+//!     .send_with_confirm(Message::builder().body("hello").build())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::{
+    fault_injection::FaultInjector, recovery_backoff::RecoveryBackoff, test_broker::TestBroker,
+    Environment, EnvironmentBuilder,
+};
+
+/// Broker, faults and randomness of a simulated run, see the [module](self) documentation
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    seed: u64,
+    broker: TestBroker,
+    faults: FaultInjector,
+    started: Instant,
+}
+
+impl Simulation {
+    /// Simulation drawing its randomness from `seed`
+    pub fn new(seed: u64) -> Self {
+        Simulation {
+            seed,
+            broker: TestBroker::new(),
+            faults: FaultInjector::new(),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn broker(&self) -> &TestBroker {
+        &self.broker
+    }
+
+    pub fn faults(&self) -> &FaultInjector {
+        &self.faults
+    }
+
+    /// Builder of an environment connected to the broker of the simulation through its
+    /// faults, with a recovery backoff seeded by the simulation
+    ///
+    /// A backoff set on the builder replaces it, keep the runs reproducible with
+    /// [`RecoveryBackoff::seed`].
+    pub fn environment(&self) -> EnvironmentBuilder {
+        Environment::builder()
+            .test_broker(self.broker.clone())
+            .fault_injector(self.faults.clone())
+            .recovery_backoff(RecoveryBackoff::new().seed(self.seed))
+    }
+
+    /// Simulated time since the simulation was created
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Let `duration` of simulated time pass, running the timers due in order
+    ///
+    /// The runtime must be paused, it waits for real otherwise.
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    /// Stop the broker for `duration`: the connections are closed and the new ones refused
+    pub async fn outage(&self, duration: Duration) {
+        self.broker.stop();
+        self.advance(duration).await;
+        self.broker.start();
+    }
+
+    /// Sever every connection `times` times, `interval` apart
+    pub async fn reconnect_storm(&self, times: usize, interval: Duration) {
+        for _ in 0..times {
+            self.faults.sever();
+            self.advance(interval).await;
+        }
+    }
+
+    /// Delete `stream` and create it again after `downtime`, its messages are lost
+    pub async fn recreate_stream(&self, stream: &str, downtime: Duration) {
+        self.broker.delete_stream(stream);
+        self.advance(downtime).await;
+        self.broker.create_stream(stream);
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Why a consumer was reported slow, see [`crate::ConsumerBuilder::slow_consumer_credit_exhaustions`]
+/// and [`crate::ConsumerBuilder::slow_consumer_pending_bytes`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SlowConsumerReason {
+    /// `chunks` chunks in a row left the subscription without credits while the
+    /// application was still processing
+    CreditsExhausted { chunks: u32 },
+    /// The messages received and not returned to the application yet reached `bytes` bytes
+    PendingBytes { bytes: u64 },
+}
+
+/// Thresholds set on the consumer builder, both disabled by default
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct SlowConsumerThresholds {
+    pub(crate) credit_exhaustions: Option<u32>,
+    pub(crate) pending_bytes: Option<u64>,
+}
+
+/// Reports a consumer once per streak of exhausted credits and once each time the pending
+/// bytes go over the threshold
+pub(crate) struct SlowConsumerDetector {
+    thresholds: SlowConsumerThresholds,
+    exhaustions: AtomicU32,
+    pending_bytes: AtomicU64,
+}
+
+impl SlowConsumerDetector {
+    /// `None` when detection is disabled
+    pub(crate) fn new(thresholds: SlowConsumerThresholds) -> Option<Self> {
+        if thresholds.credit_exhaustions.is_none() && thresholds.pending_bytes.is_none() {
+            return None;
+        }
+        Some(SlowConsumerDetector {
+            thresholds,
+            exhaustions: AtomicU32::new(0),
+            pending_bytes: AtomicU64::new(0),
+        })
+    }
+
+    /// A chunk was handled, `exhausted` if no credit is left while the application is busy
+    pub(crate) fn chunk(&self, exhausted: bool) -> Option<SlowConsumerReason> {
+        let threshold = self.thresholds.credit_exhaustions?;
+        if !exhausted {
+            self.exhaustions.store(0, Ordering::Relaxed);
+            return None;
+        }
+        let chunks = self.exhaustions.fetch_add(1, Ordering::Relaxed) + 1;
+        (chunks == threshold).then_some(SlowConsumerReason::CreditsExhausted { chunks })
+    }
+
+    pub(crate) fn tracks_bytes(&self) -> bool {
+        self.thresholds.pending_bytes.is_some()
+    }
+
+    /// A message of `bytes` bytes is waiting for the application
+    pub(crate) fn add_pending(&self, bytes: u64) -> Option<SlowConsumerReason> {
+        let threshold = self.thresholds.pending_bytes?;
+        let previous = self.pending_bytes.fetch_add(bytes, Ordering::Relaxed);
+        let pending = previous + bytes;
+        (previous < threshold && pending >= threshold)
+            .then_some(SlowConsumerReason::PendingBytes { bytes: pending })
+    }
+
+    /// A message of `bytes` bytes was returned to the application
+    pub(crate) fn remove_pending(&self, bytes: u64) {
+        let _ = self
+            .pending_bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pending| {
+                Some(pending.saturating_sub(bytes))
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SlowConsumerDetector, SlowConsumerReason, SlowConsumerThresholds};
+
+    #[test]
+    fn slow_consumer_detector_test() {
+        assert!(SlowConsumerDetector::new(SlowConsumerThresholds::default()).is_none());
+
+        let detector = SlowConsumerDetector::new(SlowConsumerThresholds {
+            credit_exhaustions: Some(2),
+            pending_bytes: Some(100),
+        })
+        .unwrap();
+
+        assert_eq!(None, detector.chunk(true));
+        assert_eq!(
+            Some(SlowConsumerReason::CreditsExhausted { chunks: 2 }),
+            detector.chunk(true)
+        );
+        // reported once per streak
+        assert_eq!(None, detector.chunk(true));
+        assert_eq!(None, detector.chunk(false));
+        assert_eq!(None, detector.chunk(true));
+
+        assert_eq!(None, detector.add_pending(60));
+        assert_eq!(
+            Some(SlowConsumerReason::PendingBytes { bytes: 120 }),
+            detector.add_pending(60)
+        );
+        assert_eq!(None, detector.add_pending(10));
+        detector.remove_pending(100);
+        assert_eq!(
+            Some(SlowConsumerReason::PendingBytes { bytes: 110 }),
+            detector.add_pending(80)
+        );
+    }
+}
@@ -1,8 +1,15 @@
-use std::{collections::HashMap, time::Duration};
+use std::{collections::HashMap, ops::RangeInclusive};
 
-use crate::{byte_capacity::ByteCapacity, environment::Environment, error::StreamCreateError};
+use crate::{
+    byte_capacity::ByteCapacity, environment::Environment, error::StreamCreateError,
+    retention::MaxAge,
+};
+
+const FILTER_SIZE_ARGUMENT: &str = "stream-filter-size-bytes";
+const FILTER_SIZE: RangeInclusive<u8> = 16..=255;
 
 /// Builder for creating a RabbitMQ stream
+#[derive(Clone)]
 pub struct StreamCreator {
     pub(crate) env: Environment,
     pub options: HashMap<String, String>,
@@ -20,9 +27,14 @@ impl StreamCreator {
 
     /// Create a stream with name and options
     pub async fn create(self, stream: &str) -> Result<(), StreamCreateError> {
-        let client = self.env.create_client().await?;
-        let response = client.create_stream(stream, self.options).await?;
-        client.close().await?;
+        check_arguments(stream, &self.options)?;
+        let options = &self.options;
+        let response = self
+            .env
+            .with_client(
+                |client| async move { client.create_stream(stream, options.clone()).await },
+            )
+            .await?;
 
         if response.is_ok() {
             Ok(())
@@ -34,11 +46,27 @@ impl StreamCreator {
         }
     }
 
-    pub fn max_age(mut self, max_age: Duration) -> Self {
+    /// Create a stream with the management HTTP API, see [`Environment::management`]
+    ///
+    /// A fallback for the brokers which do not support an option over the stream protocol.
+    #[cfg(feature = "management")]
+    pub async fn create_with_management(
+        self,
+        stream: &str,
+    ) -> Result<(), crate::error::ManagementError> {
+        self.env
+            .management()?
+            .create_stream(stream, &self.options)
+            .await
+    }
+
+    /// Discard segments older than `max_age`, a [`MaxAge`] or a [`Duration`](std::time::Duration)
+    pub fn max_age(mut self, max_age: impl Into<MaxAge>) -> Self {
         self.options
-            .insert("max-age".to_owned(), format!("{}s", max_age.as_secs()));
+            .insert("max-age".to_owned(), max_age.into().to_argument());
         self
     }
+    /// Strategy used by the broker to place the stream leader
     pub fn leader_locator(mut self, leader_locator: LeaderLocator) -> Self {
         self.options.insert(
             "queue-leader-locator".to_owned(),
@@ -46,24 +74,51 @@ impl StreamCreator {
         );
         self
     }
+    /// Maximum size of the stream before old segments are truncated
     pub fn max_length(mut self, byte_capacity: ByteCapacity) -> Self {
-        self.options.insert(
-            "max-length-bytes".to_owned(),
-            byte_capacity.bytes().to_string(),
-        );
+        self.options
+            .insert("max-length-bytes".to_owned(), byte_capacity.to_argument());
         self
     }
+    /// Maximum size of a single segment file
     pub fn max_segment_size(mut self, byte_capacity: ByteCapacity) -> Self {
         self.options.insert(
             "stream-max-segment-size-bytes".to_owned(),
-            byte_capacity.bytes().to_string(),
+            byte_capacity.to_argument(),
+        );
+        self
+    }
+    /// Size in bytes of the bloom filter of each chunk, from 16 to 255, 16 by default
+    ///
+    /// A larger filter gives fewer false positives to the consumers filtering on many
+    /// values, at the cost of a larger chunk header. Values out of range fail the creation.
+    pub fn filter_size(mut self, filter_size: u8) -> Self {
+        self.options
+            .insert(FILTER_SIZE_ARGUMENT.to_owned(), filter_size.to_string());
+        self
+    }
+
+    /// Number of nodes holding a replica of the stream, the leader included
+    ///
+    /// The broker defaults to every node of the cluster, up to the configured maximum.
+    pub fn initial_cluster_size(mut self, initial_cluster_size: u16) -> Self {
+        self.options.insert(
+            "initial-cluster-size".to_owned(),
+            initial_cluster_size.to_string(),
         );
         self
     }
 }
 
+/// Strategy used by the broker to place the leader of a new stream
+///
+/// `Random` and `LeastLeaders` are the names brokers older than 3.10 use for `Balanced`.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LeaderLocator {
+    /// The node the client creating the stream is connected to
     ClientLocal,
+    /// The node with the fewest leaders, or a random one in large clusters
+    Balanced,
     Random,
     LeastLeaders,
 }
@@ -72,8 +127,53 @@ impl AsRef<str> for LeaderLocator {
     fn as_ref(&self) -> &str {
         match self {
             LeaderLocator::ClientLocal => "client-local",
+            LeaderLocator::Balanced => "balanced",
             LeaderLocator::Random => "random",
             LeaderLocator::LeastLeaders => "least-leaders",
         }
     }
 }
+
+/// Reject the arguments the broker would refuse with a generic precondition failure
+pub(crate) fn check_arguments(
+    stream: &str,
+    options: &HashMap<String, String>,
+) -> Result<(), StreamCreateError> {
+    if let Some(value) = options.get(FILTER_SIZE_ARGUMENT) {
+        if !matches!(value.parse(), Ok(size) if FILTER_SIZE.contains(&size)) {
+            return Err(StreamCreateError::InvalidArgument {
+                stream: stream.to_owned(),
+                argument: FILTER_SIZE_ARGUMENT,
+                value: value.clone(),
+                expected: "a size from 16 to 255 bytes",
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::check_arguments;
+    use crate::error::StreamCreateError;
+
+    #[test]
+    fn check_filter_size_test() {
+        let options = |size: &str| {
+            let mut options = HashMap::new();
+            options.insert("stream-filter-size-bytes".to_owned(), size.to_owned());
+            options
+        };
+        assert!(check_arguments("orders", &HashMap::new()).is_ok());
+        assert!(check_arguments("orders", &options("16")).is_ok());
+        assert!(check_arguments("orders", &options("255")).is_ok());
+        for size in &["15", "256", "large"] {
+            assert!(matches!(
+                check_arguments("orders", &options(size)),
+                Err(StreamCreateError::InvalidArgument { value, .. }) if value == *size
+            ));
+        }
+    }
+}
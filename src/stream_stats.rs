@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+
+/// Statistics of a stream returned by [`crate::Environment::stream_stats`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamStats {
+    stats: HashMap<String, i64>,
+}
+
+impl StreamStats {
+    pub(crate) fn new(stats: HashMap<String, i64>) -> Self {
+        Self { stats }
+    }
+
+    /// Offset of the first message available in the stream, `None` if the stream is empty
+    pub fn first_offset(&self) -> Option<u64> {
+        self.get("first_chunk_id")
+    }
+
+    /// Id (first offset) of the last chunk committed by a quorum of the stream members
+    pub fn committed_chunk_id(&self) -> Option<u64> {
+        self.get("committed_chunk_id")
+    }
+
+    /// Offset of the last committed message, only reported by RabbitMQ 3.13 and later
+    pub fn committed_offset(&self) -> Option<u64> {
+        self.get("committed_offset")
+    }
+
+    /// Raw statistics as sent by the broker
+    pub fn stats(&self) -> &HashMap<String, i64> {
+        &self.stats
+    }
+
+    fn get(&self, key: &str) -> Option<u64> {
+        self.stats
+            .get(key)
+            .filter(|value| **value >= 0)
+            .map(|value| *value as u64)
+    }
+}
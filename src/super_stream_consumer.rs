@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::{future::try_join_all, stream::SelectAll, Stream, StreamExt};
+use rabbitmq_stream_protocol::ResponseCode;
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    byte_capacity::ByteCapacity,
+    consumer::{
+        ConsumerBuilder, ConsumerRecoveryEvent, ConsumerUpdateContext, CreditStrategy, Delivery,
+    },
+    error::{
+        ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError, ConsumerQueryOffsetError,
+        ConsumerStoreOffsetError, StreamStatsError,
+    },
+    interceptor::ConsumerInterceptor,
+    offset_tracking::{AutoOffsetTracking, OffsetStore},
+    poison_message::PoisonMessageHandling,
+    types::{OffsetSpecification, ZstdDictionary},
+    Consumer, ConsumerHandle, Environment,
+};
+
+/// API for consuming the partitions of a super stream as a single [`Stream`]
+///
+/// Every partition has its own subscription, the deliveries of all the partitions are merged.
+/// [`Delivery::stream`] tells the partition of a message and offsets are tracked per partition
+/// under the name of the consumer.
+pub struct SuperStreamConsumer {
+    super_stream: String,
+    consumers: SelectAll<Consumer>,
+    handles: Vec<(String, ConsumerHandle)>,
+}
+
+/// Builder for [`SuperStreamConsumer`]
+pub struct SuperStreamConsumerBuilder {
+    consumer: ConsumerBuilder,
+}
+
+impl SuperStreamConsumerBuilder {
+    pub(crate) fn new(environment: &Environment) -> Self {
+        Self {
+            consumer: environment.consumer(),
+        }
+    }
+
+    /// Subscribe to every partition of the super stream
+    pub async fn build(
+        self,
+        super_stream: &str,
+    ) -> Result<SuperStreamConsumer, ConsumerCreateError> {
+        let client = self.consumer.environment.create_client().await?;
+        let response = client.partitions(super_stream).await;
+        // one query locates every partition, each one is consumed from a node hosting it
+        let metadata = match &response {
+            Ok(response) if response.is_ok() => client.metadata(response.streams.clone()).await,
+            _ => Ok(HashMap::new()),
+        };
+        client.close().await?;
+        let response = response?;
+        let mut metadata = metadata?;
+
+        if !response.is_ok() || response.streams.is_empty() {
+            let status = match response.is_ok() {
+                true => ResponseCode::StreamDoesNotExist,
+                false => response.code().clone(),
+            };
+            return Err(ConsumerCreateError::Create {
+                stream: super_stream.to_owned(),
+                status,
+            });
+        }
+
+        let mut consumer = self.consumer;
+        consumer.super_stream = Some(super_stream.to_owned());
+
+        let mut consumers = SelectAll::new();
+        let mut handles: Vec<(String, ConsumerHandle)> = Vec::with_capacity(response.streams.len());
+        for partition in response.streams {
+            let mut partition_consumer = consumer.clone();
+            partition_consumer.located = metadata.remove(&partition);
+            match partition_consumer.build(&partition).await {
+                Ok(consumer) => {
+                    handles.push((partition, consumer.handle()));
+                    consumers.push(consumer);
+                }
+                Err(err) => {
+                    for (_, handle) in handles {
+                        let _ = handle.close().await;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(SuperStreamConsumer {
+            super_stream: super_stream.to_owned(),
+            consumers,
+            handles,
+        })
+    }
+
+    /// Name of the consumer, the reference the offsets of every partition are stored under
+    pub fn name(mut self, name: &str) -> Self {
+        self.consumer = self.consumer.name(name);
+        self
+    }
+
+    /// Where to start consuming every partition
+    pub fn offset(mut self, offset_specification: OffsetSpecification) -> Self {
+        self.consumer = self.consumer.offset(offset_specification);
+        self
+    }
+
+    /// Start every partition after its stored offset, from `fallback` when none is stored
+    pub fn start_from_stored_offset_or(mut self, fallback: OffsetSpecification) -> Self {
+        self.consumer = self.consumer.start_from_stored_offset_or(fallback);
+        self
+    }
+
+    /// Number of chunks the broker may deliver per partition before waiting for more credit
+    pub fn initial_credits(mut self, initial_credits: u16) -> Self {
+        self.consumer = self.consumer.initial_credits(initial_credits);
+        self
+    }
+
+    /// Maximum number of delivered messages waiting to be returned, per partition
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.consumer = self.consumer.buffer_size(buffer_size);
+        self
+    }
+
+    /// Maximum encoded size of the delivered messages waiting to be returned, per partition
+    pub fn max_buffered_bytes(mut self, max_buffered_bytes: ByteCapacity) -> Self {
+        self.consumer = self.consumer.max_buffered_bytes(max_buffered_bytes);
+        self
+    }
+
+    /// See [`ConsumerBuilder::zstd_dictionary`]
+    pub fn zstd_dictionary(mut self, dictionary: ZstdDictionary) -> Self {
+        self.consumer = self.consumer.zstd_dictionary(dictionary);
+        self
+    }
+
+    /// How credits are granted after the initial ones, for every partition
+    pub fn credit_strategy(mut self, credit_strategy: CreditStrategy) -> Self {
+        self.consumer = self.consumer.credit_strategy(credit_strategy);
+        self
+    }
+
+    /// Store the offset of every partition automatically, requires a name
+    pub fn auto_offset_tracking(mut self, auto_offset_tracking: AutoOffsetTracking) -> Self {
+        self.consumer = self.consumer.auto_offset_tracking(auto_offset_tracking);
+        self
+    }
+
+    /// Share the super stream with the other consumers of the same name
+    ///
+    /// Each partition has a single active consumer across the group, the broker spreads the
+    /// partitions over the consumers and rebalances them when consumers join or leave.
+    pub fn enable_single_active_consumer(mut self, single_active_consumer: bool) -> Self {
+        self.consumer = self
+            .consumer
+            .enable_single_active_consumer(single_active_consumer);
+        self
+    }
+
+    /// Choose the offset to resume a partition from when this consumer becomes its active one
+    ///
+    /// [`ConsumerUpdateContext::stream`] is the partition.
+    pub fn consumer_update_listener<F, Fut>(mut self, listener: F) -> Self
+    where
+        F: Fn(ConsumerUpdateContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = OffsetSpecification> + Send + 'static,
+    {
+        self.consumer = self.consumer.consumer_update_listener(listener);
+        self
+    }
+
+    /// Listener notified when the consumer of a partition recovers its subscription
+    pub fn on_recovery(
+        mut self,
+        listener: impl Fn(ConsumerRecoveryEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.consumer = self.consumer.on_recovery(listener);
+        self
+    }
+
+    /// Give up on the messages of every partition failing to decode, see
+    /// [`ConsumerBuilder::poison_messages`]
+    pub fn poison_messages(mut self, poison_messages: PoisonMessageHandling) -> Self {
+        self.consumer = self.consumer.poison_messages(poison_messages);
+        self
+    }
+
+    /// Persist the offsets of the partitions with `offset_store` instead of the broker
+    pub fn offset_store(mut self, offset_store: impl OffsetStore + 'static) -> Self {
+        self.consumer = self.consumer.offset_store(offset_store);
+        self
+    }
+
+    /// Append `interceptor` to the ones the messages of every partition go through,
+    /// the stream it receives is the partition
+    pub fn interceptor(mut self, interceptor: impl ConsumerInterceptor + 'static) -> Self {
+        self.consumer = self.consumer.interceptor(interceptor);
+        self
+    }
+
+    /// Report the partitions slow after `chunks` chunks in a row left them without credits,
+    /// see [`ConsumerBuilder::slow_consumer_credit_exhaustions`]
+    pub fn slow_consumer_credit_exhaustions(mut self, chunks: u32) -> Self {
+        self.consumer = self.consumer.slow_consumer_credit_exhaustions(chunks);
+        self
+    }
+
+    /// Report the partitions slow when their pending messages reach `bytes`, see
+    /// [`ConsumerBuilder::slow_consumer_pending_bytes`]
+    pub fn slow_consumer_pending_bytes(mut self, bytes: ByteCapacity) -> Self {
+        self.consumer = self.consumer.slow_consumer_pending_bytes(bytes);
+        self
+    }
+}
+
+impl SuperStreamConsumer {
+    /// Return an handle for current [`SuperStreamConsumer`]
+    pub fn handle(&self) -> SuperStreamConsumerHandle {
+        SuperStreamConsumerHandle(self.handles.clone())
+    }
+
+    /// Name of the super stream
+    pub fn super_stream(&self) -> &str {
+        &self.super_stream
+    }
+
+    /// Partitions consumed, in binding order
+    pub fn partitions(&self) -> Vec<String> {
+        self.handles
+            .iter()
+            .map(|(partition, _)| partition.clone())
+            .collect()
+    }
+
+    /// Partitions this consumer is the active one of, all of them without single active consumer
+    pub fn active_partitions(&self) -> Vec<String> {
+        self.handles
+            .iter()
+            .filter(|(_, handle)| handle.is_active())
+            .map(|(partition, _)| partition.clone())
+            .collect()
+    }
+
+    /// Store `offset` for `partition` under the name of the consumer
+    pub async fn store_offset(
+        &self,
+        partition: &str,
+        offset: u64,
+    ) -> Result<(), ConsumerStoreOffsetError> {
+        self.partition_handle(partition)
+            .ok_or_else(|| ConsumerStoreOffsetError::PartitionNotFound(partition.to_owned()))?
+            .store_offset(offset)
+            .await
+    }
+
+    /// Query the offset stored for `partition` under the name of the consumer
+    pub async fn query_offset(&self, partition: &str) -> Result<u64, ConsumerQueryOffsetError> {
+        self.partition_handle(partition)
+            .ok_or_else(|| ConsumerQueryOffsetError::PartitionNotFound(partition.to_owned()))?
+            .query_offset()
+            .await
+    }
+
+    /// Sum of the lag of every partition, see [`Consumer::lag`]
+    pub async fn lag(&self) -> Result<u64, StreamStatsError> {
+        let lags = try_join_all(self.handles.iter().map(|(_, handle)| handle.lag())).await?;
+        Ok(lags.into_iter().sum())
+    }
+
+    /// Hand the deliveries to `handler` from `workers` tasks, each one owning a share of the
+    /// partitions
+    ///
+    /// A partition is always processed by the same worker, in order: the next delivery of a
+    /// worker waits for `handler` to finish with the previous one. The partitions of the
+    /// other workers keep being processed while a handler is slow, instead of stalling the
+    /// merged stream. Partitions are dealt to the workers in binding order.
+    pub fn process<F, Fut>(self, workers: usize, handler: F) -> SuperStreamWorkers
+    where
+        F: Fn(Result<Delivery, ConsumerDeliveryError>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let workers = workers.clamp(1, self.handles.len().max(1));
+        let mut assignments = vec![Vec::new(); workers];
+        for (index, (partition, _)) in self.handles.iter().enumerate() {
+            assignments[index % workers].push(partition.clone());
+        }
+        let mut shares: Vec<_> = (0..workers).map(|_| SelectAll::new()).collect();
+        for consumer in self.consumers {
+            let worker = assignments
+                .iter()
+                .position(|partitions| partitions.iter().any(|p| p == consumer.stream()))
+                .unwrap_or(0);
+            shares[worker].push(consumer);
+        }
+
+        let handler = Arc::new(handler);
+        let (stop, stopped) = watch::channel(false);
+        let tasks = shares
+            .into_iter()
+            .map(|consumers| tokio::task::spawn(work(consumers, handler.clone(), stopped.clone())))
+            .collect();
+        SuperStreamWorkers {
+            handle: SuperStreamConsumerHandle(self.handles),
+            assignments,
+            stop,
+            tasks,
+        }
+    }
+
+    fn partition_handle(&self, partition: &str) -> Option<&ConsumerHandle> {
+        self.handles
+            .iter()
+            .find(|(name, _)| name == partition)
+            .map(|(_, handle)| handle)
+    }
+}
+
+impl Stream for SuperStreamConsumer {
+    type Item = Result<Delivery, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.consumers.poll_next_unpin(cx)
+    }
+}
+
+/// Handler API for [`SuperStreamConsumer`]
+#[derive(Clone)]
+pub struct SuperStreamConsumerHandle(Vec<(String, ConsumerHandle)>);
+
+impl SuperStreamConsumerHandle {
+    /// Close the consumers of every partition, returning the first failure if any
+    pub async fn close(self) -> Result<(), ConsumerCloseError> {
+        let mut result = Ok(());
+        for (_, handle) in self.0 {
+            if let Err(err) = handle.close().await {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Worker tasks processing the partitions of a [`SuperStreamConsumer`], see
+/// [`SuperStreamConsumer::process`]
+pub struct SuperStreamWorkers {
+    handle: SuperStreamConsumerHandle,
+    assignments: Vec<Vec<String>>,
+    stop: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl SuperStreamWorkers {
+    /// Partitions of every worker
+    pub fn assignments(&self) -> &[Vec<String>] {
+        &self.assignments
+    }
+
+    /// Whether every worker stopped on its own, once the consumers of its partitions closed
+    pub fn is_finished(&self) -> bool {
+        self.tasks.iter().all(|task| task.is_finished())
+    }
+
+    /// Stop the workers once their current delivery is handled and close the consumers
+    pub async fn stop(self) -> Result<(), ConsumerCloseError> {
+        let _ = self.stop.send(true);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+        self.handle.close().await
+    }
+}
+
+async fn work<F, Fut>(
+    mut consumers: SelectAll<Consumer>,
+    handler: Arc<F>,
+    mut stopped: watch::Receiver<bool>,
+) where
+    F: Fn(Result<Delivery, ConsumerDeliveryError>) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        let delivery = tokio::select! {
+            delivery = consumers.next() => delivery,
+            _ = stopped.changed() => return,
+        };
+        match delivery {
+            Some(delivery) => handler(delivery).await,
+            None => return,
+        }
+    }
+}
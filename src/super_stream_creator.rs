@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::{
+    byte_capacity::ByteCapacity,
+    environment::Environment,
+    error::StreamCreateError,
+    retention::MaxAge,
+    stream_creator::{check_arguments, LeaderLocator},
+};
+
+/// Builder for creating a RabbitMQ super stream
+///
+/// A super stream is an exchange bound to a set of partition streams.
+/// Partitions are either generated from a count (`{super_stream}-0`, `{super_stream}-1`, ...)
+/// or derived from explicit binding keys (`{super_stream}-{binding_key}`).
+pub struct SuperStreamCreator {
+    pub(crate) env: Environment,
+    pub options: HashMap<String, String>,
+    binding_keys: Option<Vec<String>>,
+    partitions: usize,
+}
+
+impl SuperStreamCreator {
+    pub fn new(env: Environment) -> Self {
+        let creator = Self {
+            env,
+            options: HashMap::new(),
+            binding_keys: None,
+            partitions: 3,
+        };
+
+        creator.leader_locator(LeaderLocator::LeastLeaders)
+    }
+
+    /// Create a super stream with name and options
+    pub async fn create(self, super_stream: &str) -> Result<(), StreamCreateError> {
+        check_arguments(super_stream, &self.options)?;
+        let (partitions, binding_keys) = self.partitions_and_binding_keys(super_stream);
+
+        let client = self.env.create_client().await?;
+
+        #[cfg(feature = "management")]
+        if crate::management::is_required(&client.server_properties().await) {
+            client.close().await?;
+            return crate::management::create_super_stream(
+                &self.env.options,
+                super_stream,
+                &partitions,
+                &binding_keys,
+                &self.options,
+            )
+            .await
+            .map_err(StreamCreateError::from);
+        }
+
+        let response = client
+            .create_super_stream(super_stream, partitions, binding_keys, self.options)
+            .await?;
+        client.close().await?;
+
+        if response.is_ok() {
+            Ok(())
+        } else {
+            Err(StreamCreateError::Create {
+                stream: super_stream.to_owned(),
+                status: response.code().clone(),
+            })
+        }
+    }
+
+    /// Number of partitions, named `{super_stream}-{index}` and bound with the index as key.
+    ///
+    /// Defaults to 3, ignored when [`SuperStreamCreator::binding_keys`] is used.
+    pub fn partitions(mut self, partitions: usize) -> Self {
+        self.partitions = partitions;
+        self
+    }
+
+    /// Create one partition per binding key, named `{super_stream}-{binding_key}`
+    pub fn binding_keys(mut self, binding_keys: Vec<String>) -> Self {
+        self.binding_keys = Some(binding_keys);
+        self
+    }
+
+    /// Discard segments older than `max_age`, a [`MaxAge`] or a
+    /// [`Duration`](std::time::Duration), applied to every partition
+    pub fn max_age(mut self, max_age: impl Into<MaxAge>) -> Self {
+        self.options
+            .insert("max-age".to_owned(), max_age.into().to_argument());
+        self
+    }
+    /// Strategy used by the broker to place the leader of every partition
+    pub fn leader_locator(mut self, leader_locator: LeaderLocator) -> Self {
+        self.options.insert(
+            "queue-leader-locator".to_owned(),
+            leader_locator.as_ref().to_string(),
+        );
+        self
+    }
+    /// Maximum size of each partition before old segments are truncated
+    pub fn max_length(mut self, byte_capacity: ByteCapacity) -> Self {
+        self.options
+            .insert("max-length-bytes".to_owned(), byte_capacity.to_argument());
+        self
+    }
+    /// Maximum size of a single segment file of each partition
+    pub fn max_segment_size(mut self, byte_capacity: ByteCapacity) -> Self {
+        self.options.insert(
+            "stream-max-segment-size-bytes".to_owned(),
+            byte_capacity.to_argument(),
+        );
+        self
+    }
+    /// Size in bytes of the bloom filter of each chunk of the partitions, from 16 to 255
+    pub fn filter_size(mut self, filter_size: u8) -> Self {
+        self.options.insert(
+            "stream-filter-size-bytes".to_owned(),
+            filter_size.to_string(),
+        );
+        self
+    }
+    /// Number of nodes holding a replica of each partition, the leader included
+    pub fn initial_cluster_size(mut self, initial_cluster_size: u16) -> Self {
+        self.options.insert(
+            "initial-cluster-size".to_owned(),
+            initial_cluster_size.to_string(),
+        );
+        self
+    }
+
+    fn partitions_and_binding_keys(&self, super_stream: &str) -> (Vec<String>, Vec<String>) {
+        let binding_keys = match &self.binding_keys {
+            Some(binding_keys) => binding_keys.clone(),
+            None => (0..self.partitions).map(|i| i.to_string()).collect(),
+        };
+        let partitions = binding_keys
+            .iter()
+            .map(|key| format!("{}-{}", super_stream, key))
+            .collect();
+
+        (partitions, binding_keys)
+    }
+}
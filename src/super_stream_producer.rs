@@ -0,0 +1,774 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex as StdMutex, Weak,
+    },
+    time::Duration,
+};
+
+use futures::{
+    future::{try_join_all, BoxFuture},
+    FutureExt,
+};
+use rabbitmq_stream_protocol::{
+    message::{Message, MessageId},
+    ResponseCode,
+};
+use tokio::sync::{Mutex, RwLock, RwLockReadGuard};
+use tracing::trace;
+
+use crate::{
+    client::Client,
+    environment::Environment,
+    error::{ClientError, ProducerCloseError, ProducerCreateError, ProducerPublishError},
+    interceptor::ProducerInterceptor,
+    producer::{ConfirmationStatus, Producer, ProducerBuilder, ProducerRecoveryEvent},
+    types::{ByteCapacity, Compression, RateLimiter, ZstdDictionary},
+};
+
+/// Seed of the murmur3 hash used by the other stream clients to pick a partition
+const MURMUR3_SEED: u32 = 104729;
+
+type RoutingKeyExtractor = Arc<dyn Fn(&Message) -> String + Send + Sync>;
+
+/// Pick the partitions of a message published with a [`SuperStreamProducer`]
+///
+/// `partitions` are the streams of the super stream in binding order, a message
+/// routed to no partition fails with [`ProducerPublishError::NoRoute`].
+pub trait RoutingStrategy: Send + Sync {
+    fn route(&self, message: &Message, partitions: &[String]) -> Vec<String>;
+}
+
+/// Hash the routing key with murmur3 to pick one partition, like the Java client does
+pub struct HashRoutingStrategy {
+    routing_key_extractor: RoutingKeyExtractor,
+}
+
+impl HashRoutingStrategy {
+    pub fn new(routing_key_extractor: impl Fn(&Message) -> String + Send + Sync + 'static) -> Self {
+        Self {
+            routing_key_extractor: Arc::new(routing_key_extractor),
+        }
+    }
+}
+
+impl RoutingStrategy for HashRoutingStrategy {
+    fn route(&self, message: &Message, partitions: &[String]) -> Vec<String> {
+        if partitions.is_empty() {
+            return vec![];
+        }
+        let routing_key = (self.routing_key_extractor)(message);
+        let hash = murmur3_32(routing_key.as_bytes(), MURMUR3_SEED);
+        vec![partitions[(hash as usize) % partitions.len()].clone()]
+    }
+}
+
+/// How a [`SuperStreamProducer`] resolves the partitions of a message
+#[derive(Clone)]
+enum Routing {
+    Strategy(Arc<dyn RoutingStrategy>),
+    /// Partitions bound with the routing key, resolved by the broker
+    Key,
+}
+
+/// API for publishing messages to the partitions of a super stream
+///
+/// Messages are routed with a [`HashRoutingStrategy`] on the extracted routing key unless
+/// another [`RoutingStrategy`] or the key routing is configured.
+///
+/// The partitions and the cached routes are refreshed after the broker notifies a metadata
+/// update on one of the partitions, when a message routes to no known partition and, with
+/// [`SuperStreamProducerBuilder::partitions_refresh_interval`], periodically, so added
+/// partitions receive messages without a restart.
+pub struct SuperStreamProducer {
+    super_stream: String,
+    topology: RwLock<Topology>,
+    producer: ProducerBuilder,
+    routing_key_extractor: RoutingKeyExtractor,
+    routing: Routing,
+    client: Option<Client>,
+    routes: Mutex<HashMap<String, Vec<String>>>,
+    stale: Arc<AtomicBool>,
+    /// Held while the partitions are refreshed
+    refreshing: Mutex<()>,
+}
+
+/// Partitions of the super stream and their producers, in binding order
+struct Topology {
+    partitions: Vec<String>,
+    producers: Vec<Producer>,
+}
+
+impl Topology {
+    fn position(&self, partition: &str) -> Option<usize> {
+        self.partitions.iter().position(|p| p == partition)
+    }
+}
+
+/// Builder for [`SuperStreamProducer`]
+pub struct SuperStreamProducerBuilder {
+    producer: ProducerBuilder,
+    routing_key_extractor: RoutingKeyExtractor,
+    routing: Routing,
+    partitions_refresh_interval: Option<Duration>,
+}
+
+impl SuperStreamProducerBuilder {
+    pub(crate) fn new(
+        environment: &Environment,
+        routing_key_extractor: impl Fn(&Message) -> String + Send + Sync + 'static,
+    ) -> Self {
+        let routing_key_extractor: RoutingKeyExtractor = Arc::new(routing_key_extractor);
+        let extractor = routing_key_extractor.clone();
+        Self {
+            producer: environment.producer(),
+            routing: Routing::Strategy(Arc::new(HashRoutingStrategy::new(move |message| {
+                extractor(message)
+            }))),
+            routing_key_extractor,
+            partitions_refresh_interval: None,
+        }
+    }
+
+    /// Create one producer per partition of the super stream
+    pub async fn build(
+        self,
+        super_stream: &str,
+    ) -> Result<SuperStreamProducer, ProducerCreateError> {
+        let client = self.producer.environment.create_client().await?;
+        let partitions = query_partitions(&client, super_stream).await;
+        let partitions = match partitions {
+            Ok(partitions) => partitions,
+            Err(err) => {
+                client.close().await?;
+                return Err(err);
+            }
+        };
+
+        // the connection resolves the routes of the keys
+        let client = match self.routing {
+            Routing::Key => Some(client),
+            Routing::Strategy(_) => {
+                client.close().await?;
+                None
+            }
+        };
+
+        let stale = Arc::new(AtomicBool::new(false));
+        let notify_stale = stale.clone();
+        let producer = self
+            .producer
+            .on_metadata_update(move || notify_stale.store(true, Ordering::Relaxed));
+
+        let mut producers = Vec::with_capacity(partitions.len());
+        for partition in &partitions {
+            producers.push(producer.clone().build(partition).await?);
+        }
+
+        if let Some(interval) = self.partitions_refresh_interval {
+            tokio::task::spawn(mark_stale(Arc::downgrade(&stale), interval));
+        }
+
+        Ok(SuperStreamProducer {
+            super_stream: super_stream.to_owned(),
+            topology: RwLock::new(Topology {
+                partitions,
+                producers,
+            }),
+            producer,
+            routing_key_extractor: self.routing_key_extractor,
+            routing: self.routing,
+            client,
+            routes: Mutex::new(HashMap::new()),
+            stale,
+            refreshing: Mutex::new(()),
+        })
+    }
+
+    /// Route messages with a custom strategy instead of hashing the routing key
+    pub fn routing_strategy(mut self, routing_strategy: impl RoutingStrategy + 'static) -> Self {
+        self.routing = Routing::Strategy(Arc::new(routing_strategy));
+        self
+    }
+
+    /// Ask the broker for the partitions bound with the routing key, like the super stream
+    /// exchange does, a message can go to several partitions
+    ///
+    /// Routes are cached per routing key.
+    pub fn key_routing(mut self) -> Self {
+        self.routing = Routing::Key;
+        self
+    }
+
+    /// Query the partitions again before the first send after every `interval`, besides
+    /// the metadata updates and the messages routed to no known partition
+    pub fn partitions_refresh_interval(mut self, interval: Duration) -> Self {
+        self.partitions_refresh_interval = Some(interval);
+        self
+    }
+
+    /// Name shared by the producers of every partition, enabling deduplication
+    pub fn name(mut self, name: &str) -> Self {
+        self.producer = self.producer.name(name);
+        self
+    }
+
+    /// See [`ProducerBuilder::batch_size`]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.producer = self.producer.batch_size(batch_size);
+        self
+    }
+
+    /// See [`ProducerBuilder::batch_publishing_delay`]
+    pub fn batch_publishing_delay(mut self, batch_publishing_delay: Duration) -> Self {
+        self.producer = self.producer.batch_publishing_delay(batch_publishing_delay);
+        self
+    }
+
+    /// See [`ProducerBuilder::sub_entry_size`]
+    pub fn sub_entry_size(mut self, sub_entry_size: usize) -> Self {
+        self.producer = self.producer.sub_entry_size(sub_entry_size);
+        self
+    }
+
+    /// See [`ProducerBuilder::compression`]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.producer = self.producer.compression(compression);
+        self
+    }
+
+    /// See [`ProducerBuilder::zstd_dictionary`]
+    pub fn zstd_dictionary(mut self, dictionary: ZstdDictionary) -> Self {
+        self.producer = self.producer.zstd_dictionary(dictionary);
+        self
+    }
+
+    /// See [`ProducerBuilder::confirm_timeout`]
+    pub fn confirm_timeout(mut self, confirm_timeout: Duration) -> Self {
+        self.producer = self.producer.confirm_timeout(confirm_timeout);
+        self
+    }
+
+    /// See [`ProducerBuilder::filter_value_extractor`]
+    pub fn filter_value_extractor(
+        mut self,
+        filter_value_extractor: impl Fn(&Message) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.producer = self.producer.filter_value_extractor(filter_value_extractor);
+        self
+    }
+
+    /// Listener notified when the producer of a partition recovers, see [`ProducerBuilder::on_recovery`]
+    pub fn on_recovery(
+        mut self,
+        listener: impl Fn(ProducerRecoveryEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.producer = self.producer.on_recovery(listener);
+        self
+    }
+
+    /// Append `interceptor` to the ones the messages go through once routed to their
+    /// partition, the stream it receives is the partition
+    pub fn interceptor(mut self, interceptor: impl ProducerInterceptor + 'static) -> Self {
+        self.producer = self.producer.interceptor(interceptor);
+        self
+    }
+
+    /// Set the creation time property of the messages sent without one to the time
+    /// they are sent
+    pub fn stamp_creation_time(mut self) -> Self {
+        self.producer = self.producer.stamp_creation_time();
+        self
+    }
+
+    /// Add an `x-opt-publish-time` timestamp annotation to the messages sent without one
+    pub fn stamp_publish_time(mut self) -> Self {
+        self.producer = self.producer.stamp_publish_time();
+        self
+    }
+
+    /// Set the id of the messages sent without one to a random UUID
+    pub fn generate_message_ids(mut self) -> Self {
+        self.producer = self.producer.generate_message_ids();
+        self
+    }
+
+    /// Set the id of the messages sent without one to an id of `generator`
+    pub fn message_id_generator(
+        mut self,
+        generator: impl Fn() -> MessageId + Send + Sync + 'static,
+    ) -> Self {
+        self.producer = self.producer.message_id_generator(generator);
+        self
+    }
+
+    /// Maximum encoded size of a message, larger messages are rejected with
+    /// [`ProducerPublishError::MessageSizeLimit`]
+    pub fn max_message_size(mut self, max_message_size: ByteCapacity) -> Self {
+        self.producer = self.producer.max_message_size(max_message_size);
+        self
+    }
+
+    /// Throttle the sends to every partition with `rate_limiter`, bounding their total rate
+    pub fn rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.producer = self.producer.rate_limiter(rate_limiter);
+        self
+    }
+}
+
+impl SuperStreamProducer {
+    /// Publish a message to its partitions, `cb` is invoked once the broker confirms or rejects it
+    ///
+    /// With several partitions the callback receives the first failure, if any, including
+    /// the failure to send the message to a partition once another one accepted it.
+    pub async fn send<Fut>(
+        &self,
+        message: Message,
+        cb: impl FnOnce(Result<ConfirmationStatus, ProducerPublishError>) -> Fut + Send + Sync + 'static,
+    ) -> Result<(), ProducerPublishError>
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let (topology, routes) = self.routed(&message).await?;
+        if let [partition] = routes.as_slice() {
+            return topology.producers[*partition].send(message, cb).await;
+        }
+
+        let count = routes.len();
+        let aggregate = Arc::new(StdMutex::new(AggregatedConfirm {
+            remaining: count,
+            outcome: None,
+            cb: Some(Box::new(move |outcome| cb(outcome).boxed())),
+        }));
+        for (sent, partition) in routes.into_iter().enumerate() {
+            let partition_aggregate = aggregate.clone();
+            let result = topology.producers[partition]
+                .send(message.clone(), move |outcome| {
+                    let cb = partition_aggregate.lock().unwrap().record(outcome);
+                    async move {
+                        if let Some((cb, outcome)) = cb {
+                            cb(outcome).await
+                        }
+                    }
+                })
+                .await;
+            match result {
+                Ok(()) => {}
+                Err(error) if sent == 0 => return Err(error),
+                Err(error) => {
+                    let cb = aggregate.lock().unwrap().fail(count - sent, error);
+                    if let Some((cb, outcome)) = cb {
+                        cb(outcome).await
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Publish a message to its partitions and wait for the broker to confirm or reject it
+    ///
+    /// With several partitions the first failure is returned, if any.
+    pub async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<ConfirmationStatus, ProducerPublishError> {
+        let (topology, routes) = self.routed(&message).await?;
+        let statuses = try_join_all(
+            routes
+                .into_iter()
+                .map(|partition| topology.producers[partition].send_with_confirm(message.clone())),
+        )
+        .await?;
+
+        Ok(statuses
+            .into_iter()
+            .reduce(|status, other| match status.confirmed() {
+                true => other,
+                false => status,
+            })
+            .expect("a message is routed to at least one partition"))
+    }
+
+    /// Publish a batch of messages across the partitions and wait for the outcome of each of them
+    ///
+    /// Statuses are returned in the same order as the messages.
+    pub async fn batch_send_with_confirm(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError> {
+        let topology = self.topology().await?;
+        let total = messages.len();
+        let mut batches: Vec<(Vec<usize>, Vec<Message>)> =
+            vec![(Vec::new(), Vec::new()); topology.producers.len()];
+        for (position, message) in messages.into_iter().enumerate() {
+            for partition in self.route(&topology, &message).await? {
+                let batch = &mut batches[partition];
+                batch.0.push(position);
+                batch.1.push(message.clone());
+            }
+        }
+
+        let results = try_join_all(
+            batches
+                .into_iter()
+                .zip(&topology.producers)
+                .filter(|((positions, _), _)| !positions.is_empty())
+                .map(|((positions, messages), producer)| async move {
+                    producer
+                        .batch_send_with_confirm(messages)
+                        .await
+                        .map(|statuses| positions.into_iter().zip(statuses))
+                }),
+        )
+        .await?;
+
+        // a message sent to several partitions reports its first failure
+        let mut statuses: Vec<Option<ConfirmationStatus>> = (0..total).map(|_| None).collect();
+        for (position, status) in results.into_iter().flatten() {
+            let slot = &mut statuses[position];
+            if slot.as_ref().is_none_or(ConfirmationStatus::confirmed) {
+                *slot = Some(status);
+            }
+        }
+        Ok(statuses.into_iter().flatten().collect())
+    }
+
+    /// Publish the accumulated messages of every partition
+    pub async fn flush(&self) -> Result<(), ProducerPublishError> {
+        let topology = self.topology.read().await;
+        try_join_all(topology.producers.iter().map(Producer::flush)).await?;
+        Ok(())
+    }
+
+    /// Partitions the message is published to
+    pub async fn partitions_of(
+        &self,
+        message: &Message,
+    ) -> Result<Vec<String>, ProducerPublishError> {
+        let (topology, routes) = self.routed(message).await?;
+        Ok(routes
+            .into_iter()
+            .map(|partition| topology.partitions[partition].clone())
+            .collect())
+    }
+
+    /// Partitions of the super stream, in binding order
+    pub async fn partitions(&self) -> Result<Vec<String>, ProducerPublishError> {
+        Ok(self.topology().await?.partitions.clone())
+    }
+
+    /// Name of the super stream
+    pub fn super_stream(&self) -> &str {
+        &self.super_stream
+    }
+
+    /// Close the producers of every partition
+    pub async fn close(self) -> Result<(), ProducerCloseError> {
+        let mut result = Ok(());
+        for producer in self.topology.into_inner().producers {
+            if let Err(err) = producer.close().await {
+                result = Err(err);
+            }
+        }
+        if let Some(client) = self.client {
+            client.close().await?;
+        }
+        result
+    }
+
+    /// Current topology, refreshed first when a metadata update was received
+    async fn topology(&self) -> Result<RwLockReadGuard<'_, Topology>, ProducerPublishError> {
+        if self.stale.swap(false, Ordering::Relaxed) {
+            if let Err(err) = self.refresh().await {
+                self.stale.store(true, Ordering::Relaxed);
+                return Err(match err {
+                    ProducerCreateError::Client(err) => ProducerPublishError::Client(err),
+                    err => ProducerPublishError::Client(ClientError::GenericError(Box::new(err))),
+                });
+            }
+        }
+        Ok(self.topology.read().await)
+    }
+
+    /// Query the partitions again, keeping the producers of the partitions still bound
+    ///
+    /// The producers of the added partitions are created before the topology is locked, the
+    /// sends go on meanwhile.
+    async fn refresh(&self) -> Result<(), ProducerCreateError> {
+        let _refreshing = self.refreshing.lock().await;
+        let client = self.producer.environment.create_client().await?;
+        let partitions = query_partitions(&client, &self.super_stream).await;
+        client.close().await?;
+        let partitions = partitions?;
+
+        let known = self.topology.read().await.partitions.clone();
+        let mut added = HashMap::new();
+        for partition in partitions
+            .iter()
+            .filter(|partition| !known.contains(partition))
+        {
+            added.insert(
+                partition.clone(),
+                self.producer.clone().build(partition).await?,
+            );
+        }
+
+        let mut topology = self.topology.write().await;
+        let producers = partitions
+            .iter()
+            .map(|partition| match topology.position(partition) {
+                Some(position) => topology.producers[position].clone(),
+                None => added
+                    .remove(partition)
+                    .expect("the topology only changes while refreshing"),
+            })
+            .collect();
+        let removed = std::mem::replace(
+            &mut *topology,
+            Topology {
+                partitions,
+                producers,
+            },
+        );
+        trace!(
+            super_stream = self.super_stream.as_str(),
+            partitions = ?topology.partitions,
+            "Refreshed super stream partitions"
+        );
+        let removed: Vec<Producer> = removed
+            .partitions
+            .iter()
+            .zip(removed.producers)
+            .filter(|(partition, _)| topology.position(partition).is_none())
+            .map(|(_, producer)| producer)
+            .collect();
+        drop(topology);
+
+        self.routes.lock().await.clear();
+        for producer in removed {
+            let _ = producer.close().await;
+        }
+        Ok(())
+    }
+
+    /// Current topology and the partitions of the message, the partitions are queried again
+    /// once when it routes to none of the known ones
+    async fn routed(
+        &self,
+        message: &Message,
+    ) -> Result<(RwLockReadGuard<'_, Topology>, Vec<usize>), ProducerPublishError> {
+        let topology = self.topology().await?;
+        match self.route(&topology, message).await {
+            Err(ProducerPublishError::NoRoute { .. }) => {
+                drop(topology);
+                let topology = self.topology().await?;
+                let routes = self.route(&topology, message).await?;
+                Ok((topology, routes))
+            }
+            result => result.map(|routes| (topology, routes)),
+        }
+    }
+
+    /// Indexes of the partitions of the message in the topology
+    async fn route(
+        &self,
+        topology: &Topology,
+        message: &Message,
+    ) -> Result<Vec<usize>, ProducerPublishError> {
+        let partitions = match (&self.routing, &self.client) {
+            (Routing::Key, Some(client)) => {
+                let routing_key = (self.routing_key_extractor)(message);
+                let cached = self.routes.lock().await.get(&routing_key).cloned();
+                match cached {
+                    Some(partitions) => partitions,
+                    None => {
+                        let response = client.route(&routing_key, &self.super_stream).await?;
+                        if response.is_ok() {
+                            self.routes
+                                .lock()
+                                .await
+                                .insert(routing_key, response.streams.clone());
+                        }
+                        response.streams
+                    }
+                }
+            }
+            (Routing::Strategy(strategy), _) => strategy.route(message, &topology.partitions),
+            (Routing::Key, None) => unreachable!("key routing keeps a connection"),
+        };
+
+        let partitions: Vec<usize> = partitions
+            .iter()
+            .filter_map(|partition| topology.position(partition))
+            .collect();
+        if partitions.is_empty() {
+            self.stale.store(true, Ordering::Relaxed);
+            return Err(ProducerPublishError::NoRoute {
+                super_stream: self.super_stream.clone(),
+                routing_key: (self.routing_key_extractor)(message),
+            });
+        }
+        Ok(partitions)
+    }
+}
+
+/// Mark the partitions of a super stream producer stale every `interval`, until it is dropped
+async fn mark_stale(stale: Weak<AtomicBool>, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match stale.upgrade() {
+            Some(stale) => stale.store(true, Ordering::Relaxed),
+            None => break,
+        }
+    }
+}
+
+/// Partitions of the super stream in binding order, failing when there is none
+async fn query_partitions(
+    client: &Client,
+    super_stream: &str,
+) -> Result<Vec<String>, ProducerCreateError> {
+    let response = client.partitions(super_stream).await?;
+    if !response.is_ok() || response.streams.is_empty() {
+        let status = match response.is_ok() {
+            true => ResponseCode::StreamDoesNotExist,
+            false => response.code().clone(),
+        };
+        return Err(ProducerCreateError::Create {
+            stream: super_stream.to_owned(),
+            status,
+        });
+    }
+    Ok(response.streams)
+}
+
+type AggregatedCallback = Box<
+    dyn FnOnce(Result<ConfirmationStatus, ProducerPublishError>) -> BoxFuture<'static, ()>
+        + Send
+        + Sync,
+>;
+
+/// Outcome of a message published to several partitions
+struct AggregatedConfirm {
+    remaining: usize,
+    outcome: Option<Result<ConfirmationStatus, ProducerPublishError>>,
+    cb: Option<AggregatedCallback>,
+}
+
+impl AggregatedConfirm {
+    /// Keep the first failure, returns the callback once every partition reported
+    fn record(
+        &mut self,
+        outcome: Result<ConfirmationStatus, ProducerPublishError>,
+    ) -> Option<(
+        AggregatedCallback,
+        Result<ConfirmationStatus, ProducerPublishError>,
+    )> {
+        let failed = |outcome: &Result<ConfirmationStatus, ProducerPublishError>| {
+            outcome.as_ref().map_or(true, |status| !status.confirmed())
+        };
+        if self.outcome.as_ref().is_none_or(|kept| !failed(kept)) {
+            self.outcome = Some(outcome);
+        }
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            return None;
+        }
+        self.cb.take().zip(self.outcome.take())
+    }
+
+    /// Keep `error` for the `unsent` partitions the message could not be sent to
+    fn fail(
+        &mut self,
+        unsent: usize,
+        error: ProducerPublishError,
+    ) -> Option<(
+        AggregatedCallback,
+        Result<ConfirmationStatus, ProducerPublishError>,
+    )> {
+        self.remaining -= unsent - 1;
+        self.record(Err(error))
+    }
+}
+
+/// 32 bits x86 variant of murmur3
+fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mix = |k: u32| k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+
+    let mut hash = seed;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        hash = (hash ^ mix(k))
+            .rotate_left(13)
+            .wrapping_mul(5)
+            .wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let k = tail
+            .iter()
+            .rev()
+            .fold(0u32, |k, byte| (k << 8) | u32::from(*byte));
+        hash ^= mix(k);
+    }
+
+    hash ^= data.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::{murmur3_32, AggregatedConfirm};
+    use crate::error::ProducerPublishError;
+
+    #[test]
+    fn murmur3_32_test() {
+        assert_eq!(0, murmur3_32(b"", 0));
+        assert_eq!(0x248b_fa47, murmur3_32(b"hello", 0));
+        assert_eq!(
+            0x2e4f_f723,
+            murmur3_32(b"The quick brown fox jumps over the lazy dog", 0)
+        );
+    }
+
+    #[test]
+    fn aggregated_confirm_fail_test() {
+        let mut aggregate = AggregatedConfirm {
+            remaining: 3,
+            outcome: None,
+            cb: Some(Box::new(|_| async {}.boxed())),
+        };
+        assert!(aggregate
+            .record(Err(ProducerPublishError::Closed))
+            .is_none());
+
+        // the last two partitions were not sent to, the first failure is reported
+        let (_, outcome) = aggregate
+            .fail(
+                2,
+                ProducerPublishError::QueueFull {
+                    stream: "invoices-1".to_owned(),
+                },
+            )
+            .unwrap();
+        assert!(matches!(outcome, Err(ProducerPublishError::Closed)));
+    }
+}
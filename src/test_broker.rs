@@ -0,0 +1,980 @@
+//! In-memory broker for the tests of the applications, enabled with the `test-broker`
+//! feature
+//!
+//! [`TestBroker`] speaks enough of the stream protocol to run producers and consumers
+//! without a RabbitMQ node: the connection handshake, creating and deleting streams,
+//! declaring publishers, publishing with confirms and deduplication, subscribing with
+//! credits, single active consumers and storing and querying offsets. The connections of
+//! an environment built with [`crate::EnvironmentBuilder::test_broker`] go through an
+//! in-memory duplex stream to it.
+//!
+//! ```
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use rabbitmq_stream_client::{test_broker::TestBroker, types::Message, Environment};
+//!
+//! let broker = TestBroker::new();
+//! let environment = Environment::builder()
+//!     .test_broker(broker.clone())
+//!     .build()
+//!     .await?;
+//! environment.stream_creator().create("orders").await?;
+//!
+//! let producer = environment.producer().build("orders").await?;
+//! producer
+//!     .send_with_confirm(Message::builder().body("hello").build())
+//!     .await?;
+//! producer.close().await?;
+//!
+//! assert_eq!(1, broker.messages("orders").unwrap().len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The tests can also delete and create streams behind the back of the application, and
+//! [`TestBroker::stop`] the broker to test the recovery of the producers and consumers.
+//!
+//! Super streams, stream statistics and filtering are not supported, the broker closes the
+//! connection sending them.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use rabbitmq_stream_protocol::{
+    codec::{Decoder, Encoder},
+    commands::{
+        close::CloseRequest,
+        consumer_update::ConsumerUpdateCommand,
+        deliver::{chunk_crc, DeliverCommand},
+        metadata::{Broker, StreamMetadata},
+        publish::PublishCommand,
+        publish_confirm::PublishConfirm,
+        subscribe::{OffsetSpecification, SubscribeCommand},
+        tune::TunesCommand,
+        Command,
+    },
+    error::EncodeError,
+    message::Message,
+    types::{PeerProperties, PublishingError},
+    Request, RequestKind, ResponseCode,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf},
+    sync::{broadcast, mpsc, oneshot, watch, Semaphore},
+    task::JoinHandle,
+};
+use tracing::trace;
+
+/// Flag of the keys of the frames answering a request
+const RESPONSE_FLAG: u16 = 0x8000;
+
+const PUBLISH_ERROR_KEY: u16 = 4;
+const DELIVER_KEY: u16 = 8;
+const METADATA_UPDATE_KEY: u16 = 16;
+
+const FRAME_VERSION: u16 = 1;
+
+/// Magic `5` and version `0` of the chunks
+const CHUNK_MAGIC_VERSION: i8 = 0x50;
+
+/// Bytes buffered by the in-memory connections in each direction
+const CONNECTION_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Broker keeping its streams in memory, see the [module](self) documentation
+///
+/// Its clones share the streams, keep one to inspect what the application published.
+#[derive(Clone)]
+pub struct TestBroker {
+    user: String,
+    password: String,
+    virtual_hosts: Vec<String>,
+    streams: Arc<Mutex<HashMap<String, Arc<StreamLog>>>>,
+    groups: Arc<Mutex<Groups>>,
+    /// True while the broker is stopped
+    stopped: Arc<watch::Sender<bool>>,
+    /// Code and reason of the close requests sent to the open connections
+    closes: broadcast::Sender<(ResponseCode, String)>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestBroker {
+    /// Broker accepting the `guest` user on the `/` virtual host
+    pub fn new() -> Self {
+        TestBroker {
+            user: "guest".to_owned(),
+            password: "guest".to_owned(),
+            virtual_hosts: vec!["/".to_owned()],
+            streams: Arc::new(Mutex::new(HashMap::new())),
+            groups: Arc::new(Mutex::new(Groups::default())),
+            stopped: Arc::new(watch::channel(false).0),
+            closes: broadcast::channel(1).0,
+            connections: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Credentials the connections must authenticate with
+    pub fn credentials(mut self, user: &str, password: &str) -> Self {
+        self.user = user.to_owned();
+        self.password = password.to_owned();
+        self
+    }
+
+    /// Accept the connections to `virtual_host` too
+    pub fn virtual_host(mut self, virtual_host: &str) -> Self {
+        self.virtual_hosts.push(virtual_host.to_owned());
+        self
+    }
+
+    /// Names of the streams, sorted
+    pub fn streams(&self) -> Vec<String> {
+        let mut streams: Vec<_> = self.streams.lock().unwrap().keys().cloned().collect();
+        streams.sort();
+        streams
+    }
+
+    /// Create `stream`, false if it exists
+    pub fn create_stream(&self, stream: &str) -> bool {
+        match self.streams.lock().unwrap().entry(stream.to_owned()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(Arc::new(StreamLog::new(stream)));
+                true
+            }
+        }
+    }
+
+    /// Delete `stream` with its messages, false if it does not exist
+    ///
+    /// Its publishers and subscriptions receive a metadata update, as when a stream is
+    /// deleted on a RabbitMQ node.
+    pub fn delete_stream(&self, stream: &str) -> bool {
+        let removed = self.streams.lock().unwrap().remove(stream);
+        match removed {
+            Some(stream) => {
+                stream.delete();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close the open connections and refuse the new ones until [`TestBroker::start`]
+    pub fn stop(&self) {
+        self.stopped.send_replace(true);
+    }
+
+    /// Ask the open connections to close with `code` and `reason`, as a node being drained,
+    /// the new connections are accepted
+    pub fn close_connections(&self, code: ResponseCode, reason: &str) {
+        let _ = self.closes.send((code, reason.to_owned()));
+    }
+
+    /// Accept the connections again after [`TestBroker::stop`]
+    pub fn start(&self) {
+        self.stopped.send_replace(false);
+    }
+
+    /// Number of connections opened to the broker, the refused ones included
+    pub fn connection_attempts(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    /// Messages stored in `stream` in offset order, `None` if it does not exist
+    pub fn messages(&self, stream: &str) -> Option<Vec<Message>> {
+        let stream = self.stream(stream)?;
+        let log = stream.log.lock().unwrap();
+        let mut messages = Vec::new();
+        for chunk in &log.chunks {
+            let mut frame = Vec::new();
+            chunk.encode(0, &mut frame).ok()?;
+            let (_, deliver) = DeliverCommand::decode(&frame).ok()?;
+            messages.extend(deliver.messages);
+        }
+        Some(messages)
+    }
+
+    /// Offset stored by the consumer `reference` on `stream`
+    pub fn stored_offset(&self, stream: &str, reference: &str) -> Option<u64> {
+        let stream = self.stream(stream)?;
+        let log = stream.log.lock().unwrap();
+        log.offsets.get(reference).copied()
+    }
+
+    fn stream(&self, stream: &str) -> Option<Arc<StreamLog>> {
+        self.streams.lock().unwrap().get(stream).cloned()
+    }
+
+    /// Stop the delivery of a subscription of `connection` and leave its group
+    fn unsubscribe(&self, connection: usize, subscription_id: u8, subscription: Subscription) {
+        subscription.delivery.abort();
+        if let Some(group) = &subscription.group {
+            self.groups
+                .lock()
+                .unwrap()
+                .leave(group, connection, subscription_id);
+        }
+    }
+
+    /// Open a connection, its other end is served by a task of the broker
+    pub(crate) fn connect(&self) -> std::io::Result<DuplexStream> {
+        let id = self.connections.fetch_add(1, Ordering::Relaxed);
+        if *self.stopped.borrow() {
+            return Err(std::io::ErrorKind::ConnectionRefused.into());
+        }
+        let (client, server) = tokio::io::duplex(CONNECTION_BUFFER_SIZE);
+        tokio::spawn(serve(self.clone(), id, server));
+        Ok(client)
+    }
+}
+
+impl Default for TestBroker {
+    fn default() -> Self {
+        TestBroker::new()
+    }
+}
+
+impl fmt::Debug for TestBroker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestBroker")
+            .field("streams", &self.streams())
+            .finish()
+    }
+}
+
+/// Stream with its chunks, every publish frame is written as a chunk
+struct StreamLog {
+    name: String,
+    log: Mutex<Log>,
+    /// Notified when a chunk is written or the stream deleted
+    appended: watch::Sender<()>,
+    subscribed: watch::Receiver<()>,
+    /// Connections with a publisher on the stream, notified when it is deleted
+    publishers: Mutex<Vec<mpsc::WeakUnboundedSender<Vec<u8>>>>,
+}
+
+#[derive(Default)]
+struct Log {
+    chunks: Vec<Chunk>,
+    next_offset: u64,
+    /// Last publishing id of the named publishers
+    sequences: HashMap<String, u64>,
+    offsets: HashMap<String, u64>,
+    deleted: bool,
+}
+
+#[derive(Clone)]
+struct Chunk {
+    first_offset: u64,
+    timestamp: u64,
+    num_entries: u16,
+    num_records: u32,
+    data: Bytes,
+}
+
+impl StreamLog {
+    fn new(name: &str) -> Self {
+        let (appended, subscribed) = watch::channel(());
+        StreamLog {
+            name: name.to_owned(),
+            log: Mutex::new(Log::default()),
+            appended,
+            subscribed,
+            publishers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Index of the first chunk to deliver to a subscription starting at `offset`
+    fn start(&self, offset: &OffsetSpecification) -> usize {
+        let log = self.log.lock().unwrap();
+        let chunks = &log.chunks;
+        match offset {
+            OffsetSpecification::First => 0,
+            OffsetSpecification::Last => chunks.len().saturating_sub(1),
+            OffsetSpecification::Next => chunks.len(),
+            OffsetSpecification::Offset(offset) => chunks
+                .iter()
+                .position(|chunk| chunk.first_offset + chunk.num_records as u64 > *offset)
+                .unwrap_or(chunks.len()),
+            OffsetSpecification::Timestamp(timestamp) => chunks
+                .iter()
+                .position(|chunk| chunk.timestamp as i64 >= *timestamp)
+                .unwrap_or(chunks.len()),
+        }
+    }
+
+    /// Write the messages of `publish` in a chunk, returning their publishing ids
+    ///
+    /// The messages of `reference` with an id up to its last one are confirmed without
+    /// being written again.
+    fn append(&self, reference: Option<&str>, publish: &PublishCommand) -> Vec<u64> {
+        let mut log = self.log.lock().unwrap();
+        let last = reference.and_then(|reference| log.sequences.get(reference).copied());
+        let mut data = Vec::new();
+        let mut num_entries = 0;
+        let mut num_records = 0;
+        let mut confirmed = Vec::with_capacity(publish.published_messages().len());
+        for message in publish.published_messages() {
+            confirmed.push(message.publishing_id());
+            if last.is_some_and(|last| message.publishing_id() <= last) {
+                continue;
+            }
+            if message.encode_entry(&mut data).is_err() {
+                continue;
+            }
+            num_entries += 1;
+            num_records += message.records() as u32;
+        }
+        if let (Some(reference), Some(id)) = (reference, confirmed.iter().max()) {
+            let sequence = log.sequences.entry(reference.to_owned()).or_default();
+            *sequence = (*sequence).max(*id);
+        }
+        if num_entries > 0 {
+            let first_offset = log.next_offset;
+            log.next_offset += num_records as u64;
+            log.chunks.push(Chunk {
+                first_offset,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64,
+                num_entries,
+                num_records,
+                data: data.into(),
+            });
+            drop(log);
+            let _ = self.appended.send(());
+        }
+        confirmed
+    }
+
+    fn delete(&self) {
+        self.log.lock().unwrap().deleted = true;
+        let _ = self.appended.send(());
+        if let Ok(update) = self.metadata_update() {
+            for publishers in self.publishers.lock().unwrap().iter() {
+                if let Some(publishers) = publishers.upgrade() {
+                    let _ = publishers.send(update.clone());
+                }
+            }
+        }
+    }
+
+    /// Frame telling the clients the stream is not available anymore
+    fn metadata_update(&self) -> Result<Vec<u8>, EncodeError> {
+        frame(METADATA_UPDATE_KEY, |writer| {
+            ResponseCode::StreamNotAvailable.encode(writer)?;
+            self.name.as_str().encode(writer)
+        })
+    }
+
+    /// Notify the connection sending on `frames` when the stream is deleted
+    fn add_publisher(&self, frames: &mpsc::UnboundedSender<Vec<u8>>) {
+        let mut publishers = self.publishers.lock().unwrap();
+        // the connections are closed when their last sender is dropped
+        publishers.retain(|other| other.strong_count() > 0);
+        if !publishers.iter().any(|other| {
+            other
+                .upgrade()
+                .is_some_and(|other| other.same_channel(frames))
+        }) {
+            publishers.push(frames.downgrade());
+        }
+    }
+}
+
+/// Single active consumers of the groups, by stream and name
+#[derive(Default)]
+struct Groups {
+    members: HashMap<(String, String), Vec<Member>>,
+    /// Activations waiting for the answer to their consumer update, by correlation id
+    activations: HashMap<u32, oneshot::Sender<OffsetSpecification>>,
+    correlation_id: u32,
+}
+
+/// Subscription of a single active consumer, the first of its group is the active one
+struct Member {
+    connection: usize,
+    subscription_id: u8,
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    /// Taken when the member is activated
+    activation: Option<oneshot::Sender<OffsetSpecification>>,
+}
+
+impl Groups {
+    /// Send a consumer update to the first member of `group` if it is not active yet
+    fn activate(&mut self, group: &(String, String)) {
+        let member = match self.members.get_mut(group).and_then(|m| m.first_mut()) {
+            Some(member) => member,
+            None => return,
+        };
+        let activation = match member.activation.take() {
+            Some(activation) => activation,
+            None => return,
+        };
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let update = ConsumerUpdateCommand::new(self.correlation_id, member.subscription_id, 1);
+        match frame(update.key(), |writer| update.encode(writer)) {
+            Ok(update) => {
+                let _ = member.frames.send(update);
+            }
+            Err(error) => trace!(?error, "Test broker failed to encode a consumer update"),
+        }
+        self.activations.insert(self.correlation_id, activation);
+    }
+
+    /// Remove a member of `group`, activating the next one if it was the active one
+    fn leave(&mut self, group: &(String, String), connection: usize, subscription_id: u8) {
+        let members = match self.members.get_mut(group) {
+            Some(members) => members,
+            None => return,
+        };
+        let position = members.iter().position(|member| {
+            member.connection == connection && member.subscription_id == subscription_id
+        });
+        if let Some(position) = position {
+            members.remove(position);
+            if members.is_empty() {
+                self.members.remove(group);
+            } else if position == 0 {
+                self.activate(group);
+            }
+        }
+    }
+}
+
+impl Chunk {
+    /// Encode the body of the deliver frame of the chunk
+    fn encode(&self, subscription_id: u8, writer: &mut Vec<u8>) -> Result<(), EncodeError> {
+        subscription_id.encode(writer)?;
+        CHUNK_MAGIC_VERSION.encode(writer)?;
+        // user chunk
+        0u8.encode(writer)?;
+        self.num_entries.encode(writer)?;
+        self.num_records.encode(writer)?;
+        self.timestamp.encode(writer)?;
+        // epoch
+        0u64.encode(writer)?;
+        self.first_offset.encode(writer)?;
+        (chunk_crc(&self.data) as i32).encode(writer)?;
+        (self.data.len() as u32).encode(writer)?;
+        // trailer length and reserved
+        0u32.encode(writer)?;
+        0u32.encode(writer)?;
+        writer.extend_from_slice(&self.data);
+        Ok(())
+    }
+}
+
+/// Frame with `key`, its body written by `body`
+fn frame(
+    key: u16,
+    body: impl FnOnce(&mut Vec<u8>) -> Result<(), EncodeError>,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut frame = vec![0; 4];
+    key.encode(&mut frame)?;
+    FRAME_VERSION.encode(&mut frame)?;
+    body(&mut frame)?;
+    let size = (frame.len() - 4) as u32;
+    frame[..4].copy_from_slice(&size.to_be_bytes());
+    Ok(frame)
+}
+
+async fn read_frame(reader: &mut ReadHalf<DuplexStream>) -> std::io::Result<Vec<u8>> {
+    let size = reader.read_u32().await?;
+    let mut frame = vec![0; 4 + size as usize];
+    frame[..4].copy_from_slice(&size.to_be_bytes());
+    reader.read_exact(&mut frame[4..]).await?;
+    Ok(frame)
+}
+
+async fn serve(broker: TestBroker, id: usize, stream: DuplexStream) {
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (frames, mut outgoing) = mpsc::unbounded_channel::<Vec<u8>>();
+    tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            if writer.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut stopped = broker.stopped.subscribe();
+    let mut closes = broker.closes.subscribe();
+    let mut connection = Connection {
+        broker,
+        id,
+        frames,
+        publishers: HashMap::new(),
+        subscriptions: HashMap::new(),
+    };
+    loop {
+        let frame = tokio::select! {
+            frame = read_frame(&mut reader) => match frame {
+                Ok(frame) => frame,
+                Err(_) => break,
+            },
+            stopped = stopped.wait_for(|stopped| *stopped) => match stopped {
+                Ok(_) => break,
+                Err(_) => continue,
+            },
+            // the client answers and closes the connection
+            Ok((code, reason)) = closes.recv() => {
+                let close = CloseRequest::new(0, code, reason);
+                connection.send(close.key(), |writer| close.encode(writer));
+                continue;
+            }
+        };
+        let request = match Request::decode(&frame) {
+            Ok((_, request)) => request,
+            Err(error) => {
+                trace!(?error, "Test broker failed to decode a frame");
+                break;
+            }
+        };
+        if !connection.handle(request) {
+            break;
+        }
+    }
+}
+
+struct Publisher {
+    stream: String,
+    reference: Option<String>,
+}
+
+struct Subscription {
+    credits: Arc<Semaphore>,
+    delivery: JoinHandle<()>,
+    /// Stream and name of a single active consumer
+    group: Option<(String, String)>,
+}
+
+/// State of a connection to the broker
+struct Connection {
+    broker: TestBroker,
+    id: usize,
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+    publishers: HashMap<u8, Publisher>,
+    subscriptions: HashMap<u8, Subscription>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        for (subscription_id, subscription) in self.subscriptions.drain() {
+            self.broker
+                .unsubscribe(self.id, subscription_id, subscription);
+        }
+    }
+}
+
+impl Connection {
+    fn send(&self, key: u16, body: impl FnOnce(&mut Vec<u8>) -> Result<(), EncodeError>) {
+        match frame(key, body) {
+            Ok(frame) => {
+                let _ = self.frames.send(frame);
+            }
+            Err(error) => trace!(?error, "Test broker failed to encode a frame"),
+        }
+    }
+
+    fn respond(&self, request: &Request, correlation_id: u32, code: ResponseCode) {
+        self.send(request.header().key() | RESPONSE_FLAG, |writer| {
+            correlation_id.encode(writer)?;
+            code.encode(writer)
+        });
+    }
+
+    /// Handle `request`, false when the connection is closed
+    fn handle(&mut self, request: Request) -> bool {
+        let key = request.header().key() | RESPONSE_FLAG;
+        match request.kind() {
+            RequestKind::PeerProperties(peer_properties) => {
+                let properties =
+                    PeerProperties::from_static(&[("product", "RabbitMQ"), ("version", "3.13.0")]);
+                self.send(key, |writer| {
+                    peer_properties.correlation_id().encode(writer)?;
+                    ResponseCode::Ok.encode(writer)?;
+                    properties.encode(writer)
+                });
+            }
+            RequestKind::SaslHandshake(handshake) => self.send(key, |writer| {
+                handshake.correlation_id().encode(writer)?;
+                ResponseCode::Ok.encode(writer)?;
+                vec!["PLAIN".to_owned()].encode(writer)
+            }),
+            RequestKind::SaslAuthenticate(authenticate) => {
+                let expected = format!(
+                    "\u{0000}{}\u{0000}{}",
+                    self.broker.user, self.broker.password
+                );
+                if authenticate.mechanism() != "PLAIN" {
+                    self.respond(
+                        &request,
+                        authenticate.correlation_id(),
+                        ResponseCode::SaslMechanismNotSupported,
+                    );
+                } else if authenticate.sasl_data() != expected.as_bytes() {
+                    self.respond(
+                        &request,
+                        authenticate.correlation_id(),
+                        ResponseCode::AuthenticationFailure,
+                    );
+                } else {
+                    self.respond(&request, authenticate.correlation_id(), ResponseCode::Ok);
+                    let tune = TunesCommand::new(1048576, 60);
+                    self.send(tune.key(), |writer| tune.encode(writer));
+                }
+            }
+            RequestKind::Tunes(_) | RequestKind::Heartbeat(_) => {}
+            RequestKind::Open(open) => {
+                let code = if self
+                    .broker
+                    .virtual_hosts
+                    .iter()
+                    .any(|v| v == open.virtual_host())
+                {
+                    ResponseCode::Ok
+                } else {
+                    ResponseCode::VirtualHostAccessFailure
+                };
+                self.send(key, |writer| {
+                    open.correlation_id().encode(writer)?;
+                    code.encode(writer)?;
+                    PeerProperties::new().encode(writer)
+                });
+            }
+            RequestKind::Close(close) => {
+                self.send(key, |writer| {
+                    close.correlation_id().encode(writer)?;
+                    ResponseCode::Ok.encode(writer)
+                });
+                return false;
+            }
+            RequestKind::CloseResponse(_) => return false,
+            RequestKind::CreateStream(create) => {
+                let code = if self.broker.create_stream(create.stream_name()) {
+                    ResponseCode::Ok
+                } else {
+                    ResponseCode::StreamAlreadyExists
+                };
+                self.respond(&request, create.correlation_id(), code);
+            }
+            RequestKind::Delete(delete) => {
+                let code = if self.broker.delete_stream(delete.stream()) {
+                    ResponseCode::Ok
+                } else {
+                    ResponseCode::StreamDoesNotExist
+                };
+                self.respond(&request, delete.correlation_id(), code);
+            }
+            RequestKind::Metadata(metadata) => {
+                let streams: Vec<_> = metadata
+                    .streams()
+                    .iter()
+                    .map(|stream| StreamMetadata {
+                        stream_name: stream.clone(),
+                        code: match self.broker.stream(stream) {
+                            Some(_) => ResponseCode::Ok,
+                            None => ResponseCode::StreamDoesNotExist,
+                        },
+                        leader_reference: 0,
+                        replicas_references: vec![],
+                    })
+                    .collect();
+                let broker = Broker {
+                    reference: 0,
+                    host: "localhost".to_owned(),
+                    port: 5552,
+                };
+                self.send(key, |writer| {
+                    metadata.correlation_id().encode(writer)?;
+                    1u32.encode(writer)?;
+                    broker.encode(writer)?;
+                    (streams.len() as u32).encode(writer)?;
+                    for stream in &streams {
+                        stream.encode(writer)?;
+                    }
+                    Ok(())
+                });
+            }
+            RequestKind::DeclarePublisher(declare) => {
+                let stream = self.broker.stream(declare.stream_name());
+                let code = match self.publishers.entry(declare.publisher_id()) {
+                    _ if stream.is_none() => ResponseCode::StreamDoesNotExist,
+                    Entry::Occupied(_) => ResponseCode::PrecoditionFailed,
+                    Entry::Vacant(entry) => {
+                        if let Some(stream) = &stream {
+                            stream.add_publisher(&self.frames);
+                        }
+                        entry.insert(Publisher {
+                            stream: declare.stream_name().to_owned(),
+                            reference: declare.publisher_reference().map(str::to_owned),
+                        });
+                        ResponseCode::Ok
+                    }
+                };
+                self.respond(&request, declare.correlation_id(), code);
+            }
+            RequestKind::DeletePublisher(delete) => {
+                let code = match self.publishers.remove(&delete.publisher_id()) {
+                    Some(_) => ResponseCode::Ok,
+                    None => ResponseCode::PublisherDoesNotExist,
+                };
+                self.respond(&request, delete.correlation_id(), code);
+            }
+            RequestKind::Publish(publish) => self.publish(publish),
+            RequestKind::QueryPublisherSequence(query) => {
+                let sequence = self.broker.stream(query.stream()).and_then(|stream| {
+                    let log = stream.log.lock().unwrap();
+                    log.sequences.get(query.publisher_reference()).copied()
+                });
+                self.send(key, |writer| {
+                    query.correlation_id().encode(writer)?;
+                    ResponseCode::Ok.encode(writer)?;
+                    sequence.unwrap_or(0).encode(writer)
+                });
+            }
+            RequestKind::StoreOffset(store) => {
+                if let Some(stream) = self.broker.stream(store.stream()) {
+                    let mut log = stream.log.lock().unwrap();
+                    log.offsets
+                        .insert(store.reference().to_owned(), store.offset());
+                }
+            }
+            RequestKind::QueryOffset(query) => {
+                let (code, offset) = match self.broker.stream(query.stream()) {
+                    Some(stream) => match stream.log.lock().unwrap().offsets.get(query.reference())
+                    {
+                        Some(offset) => (ResponseCode::Ok, *offset),
+                        None => (ResponseCode::NoOffset, 0),
+                    },
+                    None => (ResponseCode::StreamDoesNotExist, 0),
+                };
+                self.send(key, |writer| {
+                    query.correlation_id().encode(writer)?;
+                    code.encode(writer)?;
+                    offset.encode(writer)
+                });
+            }
+            RequestKind::Subscribe(subscribe) => self.subscribe(&request, subscribe),
+            RequestKind::Credit(credit) => {
+                match self.subscriptions.get(&credit.subscription_id()) {
+                    Some(subscription) => {
+                        subscription.credits.add_permits(credit.credit() as usize)
+                    }
+                    None => self.send(key, |writer| {
+                        ResponseCode::SubscriptionIdDoesNotExist.encode(writer)?;
+                        credit.subscription_id().encode(writer)
+                    }),
+                }
+            }
+            RequestKind::Unsubscribe(unsubscribe) => {
+                let code = match self.subscriptions.remove(&unsubscribe.subscription_id()) {
+                    Some(subscription) => {
+                        self.broker.unsubscribe(
+                            self.id,
+                            unsubscribe.subscription_id(),
+                            subscription,
+                        );
+                        ResponseCode::Ok
+                    }
+                    None => ResponseCode::SubscriptionIdDoesNotExist,
+                };
+                self.respond(&request, unsubscribe.correlation_id(), code);
+            }
+            RequestKind::ConsumerUpdate(update) => {
+                let activation = self
+                    .broker
+                    .groups
+                    .lock()
+                    .unwrap()
+                    .activations
+                    .remove(&update.correlation_id());
+                if let Some(activation) = activation {
+                    let _ = activation.send(update.offset_specification().clone());
+                }
+            }
+            RequestKind::StreamStats(_)
+            | RequestKind::Route(_)
+            | RequestKind::Partitions(_)
+            | RequestKind::CreateSuperStream(_)
+            | RequestKind::DeleteSuperStream(_) => {
+                let close = CloseRequest::new(
+                    0,
+                    ResponseCode::UnknownFrame,
+                    format!(
+                        "command {} is not supported by the test broker",
+                        request.header().key()
+                    ),
+                );
+                self.send(close.key(), |writer| close.encode(writer));
+            }
+        }
+        true
+    }
+
+    fn publish(&self, publish: &PublishCommand) {
+        let publisher_id = publish.publisher_id();
+        let stream = self
+            .publishers
+            .get(&publisher_id)
+            .map(|publisher| (self.broker.stream(&publisher.stream), publisher));
+        let code = match stream {
+            Some((Some(stream), publisher)) => {
+                let confirmed = stream.append(publisher.reference.as_deref(), publish);
+                let confirm = PublishConfirm::new(publisher_id, confirmed);
+                self.send(confirm.key(), |writer| confirm.encode(writer));
+                return;
+            }
+            Some((None, _)) => ResponseCode::StreamDoesNotExist,
+            None => ResponseCode::PublisherDoesNotExist,
+        };
+        let errors: Vec<_> = publish
+            .published_messages()
+            .iter()
+            .map(|message| PublishingError::new(message.publishing_id(), code.clone()))
+            .collect();
+        self.send(PUBLISH_ERROR_KEY, |writer| {
+            publisher_id.encode(writer)?;
+            errors.encode(writer)
+        });
+    }
+
+    fn subscribe(&mut self, request: &Request, subscribe: &SubscribeCommand) {
+        let subscription_id = subscribe.subscription_id();
+        let stream = match self.broker.stream(subscribe.stream_name()) {
+            Some(stream) => stream,
+            None => {
+                return self.respond(
+                    request,
+                    subscribe.correlation_id(),
+                    ResponseCode::StreamDoesNotExist,
+                )
+            }
+        };
+        if self.subscriptions.contains_key(&subscription_id) {
+            return self.respond(
+                request,
+                subscribe.correlation_id(),
+                ResponseCode::SubscriptionIdAlreadyExists,
+            );
+        }
+        // the response goes before the first chunk
+        self.respond(request, subscribe.correlation_id(), ResponseCode::Ok);
+
+        let credits = Arc::new(Semaphore::new(subscribe.credit() as usize));
+        let properties = subscribe.properties();
+        let group = match (
+            properties.get("single-active-consumer"),
+            properties.get("name"),
+        ) {
+            (Some(single_active_consumer), Some(name)) if single_active_consumer == "true" => {
+                Some((stream.name.clone(), name.clone()))
+            }
+            _ => None,
+        };
+        let delivery = match &group {
+            // the consumer starts from the offset answered to its activation
+            Some(group) => {
+                let (activation, activated) = oneshot::channel();
+                let mut groups = self.broker.groups.lock().unwrap();
+                groups
+                    .members
+                    .entry(group.clone())
+                    .or_default()
+                    .push(Member {
+                        connection: self.id,
+                        subscription_id,
+                        frames: self.frames.clone(),
+                        activation: Some(activation),
+                    });
+                groups.activate(group);
+                let credits = credits.clone();
+                let frames = self.frames.clone();
+                tokio::spawn(async move {
+                    if let Ok(offset) = activated.await {
+                        let start = stream.start(&offset);
+                        deliver(subscription_id, stream, start, credits, frames).await
+                    }
+                })
+            }
+            None => {
+                let start = stream.start(subscribe.offset_specification());
+                tokio::spawn(deliver(
+                    subscription_id,
+                    stream,
+                    start,
+                    credits.clone(),
+                    self.frames.clone(),
+                ))
+            }
+        };
+        self.subscriptions.insert(
+            subscription_id,
+            Subscription {
+                credits,
+                delivery,
+                group,
+            },
+        );
+    }
+}
+
+/// Deliver the chunks of `stream` from `position`, one chunk per credit
+async fn deliver(
+    subscription_id: u8,
+    stream: Arc<StreamLog>,
+    mut position: usize,
+    credits: Arc<Semaphore>,
+    frames: mpsc::UnboundedSender<Vec<u8>>,
+) {
+    let mut appended = stream.subscribed.clone();
+    loop {
+        appended.borrow_and_update();
+        let chunk = {
+            let log = stream.log.lock().unwrap();
+            if log.deleted {
+                None
+            } else {
+                Some(log.chunks.get(position).cloned())
+            }
+        };
+        let chunk = match chunk {
+            Some(Some(chunk)) => chunk,
+            Some(None) => {
+                if appended.changed().await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            None => {
+                if let Ok(update) = stream.metadata_update() {
+                    let _ = frames.send(update);
+                }
+                return;
+            }
+        };
+
+        match credits.acquire().await {
+            Ok(permit) => permit.forget(),
+            Err(_) => return,
+        }
+        match frame(DELIVER_KEY, |writer| chunk.encode(subscription_id, writer)) {
+            Ok(deliver) => {
+                if frames.send(deliver).is_err() {
+                    return;
+                }
+            }
+            Err(error) => trace!(?error, "Test broker failed to encode a chunk"),
+        }
+        position += 1;
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use rabbitmq_stream_protocol::{
+    codec::Encoder,
+    commands::{
+        deliver::{chunk_crc, DeliverCommand},
+        publish_confirm::PublishConfirm,
+        Command,
+    },
+    message::{Message, MessageId, Properties, Value},
+    types::Header,
+    Response, ResponseKind,
+};
+
+use crate::types::{ChunkMetadata, Delivery};
+
+const FRAME_VERSION: u16 = 1;
+const CHUNK_MAGIC_VERSION: i8 = 0x50;
+
+/// Builder of the deliver frame of a chunk, see [`deliver_chunk`]
+#[derive(Clone, Debug)]
+pub struct DeliverChunkBuilder {
+    subscription_id: u8,
+    first_offset: u64,
+    timestamp: u64,
+    epoch: u64,
+    messages: Vec<Message>,
+}
+
+/// Chunk delivered to `subscription_id`, its messages starting at `first_offset`
+pub fn deliver_chunk(subscription_id: u8, first_offset: u64) -> DeliverChunkBuilder {
+    DeliverChunkBuilder {
+        subscription_id,
+        first_offset,
+        timestamp: now_millis(),
+        epoch: 0,
+        messages: Vec::new(),
+    }
+}
+
+impl DeliverChunkBuilder {
+    /// Append a message to the chunk
+    pub fn message(mut self, message: Message) -> Self {
+        self.messages.push(message);
+        self
+    }
+
+    /// Append messages to the chunk
+    pub fn messages(mut self, messages: impl IntoIterator<Item = Message>) -> Self {
+        self.messages.extend(messages);
+        self
+    }
+
+    /// Creation time of the chunk in milliseconds since the epoch, now by default
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Epoch of the stream leader which wrote the chunk, 0 by default
+    pub fn epoch(mut self, epoch: u64) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// The chunk as the client decodes it, with the CRC of its entries
+    pub fn command(&self) -> DeliverCommand {
+        let mut data = Vec::new();
+        // encoding caches the size in the message, the messages of the chunk stay as built
+        for message in self.messages.clone() {
+            message.encoded_size().encode(&mut data).unwrap();
+            message.encode(&mut data).unwrap();
+        }
+        DeliverCommand::new(
+            self.subscription_id,
+            CHUNK_MAGIC_VERSION,
+            0,
+            self.messages.len() as u16,
+            self.timestamp,
+            self.epoch,
+            self.first_offset,
+            chunk_crc(&data) as i32,
+            0,
+            0,
+            self.messages.clone(),
+        )
+    }
+
+    /// The chunk as handed to the message handler of a [`Client`](crate::Client)
+    pub fn response(&self) -> Response {
+        let command = self.command();
+        Response::new(
+            Header::new(command.key(), FRAME_VERSION),
+            ResponseKind::Deliver(command),
+        )
+    }
+
+    /// The frame as sent by the broker, size included, e.g. for a
+    /// [`WireReplay`](crate::types::WireReplay)
+    pub fn frame(&self) -> Bytes {
+        encode_frame(&self.command())
+    }
+
+    /// The messages of the chunk as a consumer of `stream` returns them
+    pub fn deliveries(&self, stream: &str) -> Vec<Delivery> {
+        let stream: Arc<str> = stream.into();
+        let chunk = ChunkMetadata {
+            first_offset: self.first_offset,
+            timestamp: self.timestamp,
+            epoch: self.epoch,
+            num_entries: self.messages.len() as u16,
+            num_records: self.messages.len() as u32,
+        };
+        self.messages
+            .iter()
+            .zip(self.first_offset..)
+            .map(|(message, offset)| Delivery {
+                stream: stream.clone(),
+                subscription_id: self.subscription_id,
+                offset,
+                message: message.clone(),
+                chunk,
+            })
+            .collect()
+    }
+}
+
+/// Confirmation of `publishing_ids` for the publisher `publisher_id`
+pub fn publish_confirm(publisher_id: u8, publishing_ids: Vec<u64>) -> Response {
+    let confirm = PublishConfirm::new(publisher_id, publishing_ids);
+    Response::new(
+        Header::new(confirm.key(), FRAME_VERSION),
+        ResponseKind::PublishConfirm(confirm),
+    )
+}
+
+/// The frame of [`publish_confirm`] as sent by the broker, size included
+pub fn publish_confirm_frame(publisher_id: u8, publishing_ids: Vec<u64>) -> Bytes {
+    encode_frame(&PublishConfirm::new(publisher_id, publishing_ids))
+}
+
+/// Message delivered to a consumer of `stream` at `offset`, in a chunk of its own
+pub fn delivery(stream: &str, offset: u64, message: Message) -> Delivery {
+    deliver_chunk(0, offset)
+        .message(message)
+        .deliveries(stream)
+        .remove(0)
+}
+
+/// Message with every property, an application property and a message annotation of each
+/// type of value, and a body
+///
+/// Start from [`Message::into_builder`] to change some of them.
+pub fn full_message() -> Message {
+    let values = vec![
+        ("null", Value::Null),
+        ("boolean", Value::Boolean(true)),
+        ("ubyte", Value::Ubyte(u8::MAX)),
+        ("ushort", Value::Ushort(u16::MAX)),
+        ("uint", Value::Uint(u32::MAX)),
+        ("ulong", Value::Ulong(u64::MAX)),
+        ("byte", Value::Byte(i8::MIN)),
+        ("short", Value::Short(i16::MIN)),
+        ("int", Value::Int(i32::MIN)),
+        ("long", Value::Long(i64::MIN)),
+        ("float", Value::Float(1.5)),
+        ("double", Value::Double(-2.25)),
+        ("char", Value::Char('λ')),
+        ("timestamp", Value::Timestamp(1_700_000_000_000)),
+        ("uuid", Value::Uuid([0xab; 16])),
+        ("binary", Value::Binary(vec![0, 1, 2, 255])),
+        ("string", Value::String("value".to_owned())),
+        ("symbol", Value::Symbol("symbol".to_owned())),
+    ];
+
+    let mut builder = Message::builder()
+        .properties(Properties {
+            message_id: Some(MessageId::String("message-id".to_owned())),
+            user_id: Some(b"guest".to_vec()),
+            to: Some("to".to_owned()),
+            subject: Some("subject".to_owned()),
+            reply_to: Some("reply-to".to_owned()),
+            correlation_id: Some(MessageId::Uuid([0xcd; 16])),
+            content_type: Some("application/json".to_owned()),
+            content_encoding: Some("identity".to_owned()),
+            absolute_expiry_time: Some(1_700_000_060_000),
+            creation_time: Some(1_700_000_000_000),
+            group_id: Some("group-id".to_owned()),
+            group_sequence: Some(7),
+            reply_to_group_id: Some("reply-to-group-id".to_owned()),
+        })
+        .body(&br#"{"message":"body"}"#[..]);
+    for (key, value) in values {
+        builder = builder
+            .application_property(key, value.clone())
+            .message_annotation(format!("x-{}", key), value);
+    }
+    builder.build()
+}
+
+fn encode_frame(command: &(impl Command + Encoder)) -> Bytes {
+    let mut frame = Vec::with_capacity(8 + command.encoded_size() as usize);
+    (4 + command.encoded_size()).encode(&mut frame).unwrap();
+    command.key().encode(&mut frame).unwrap();
+    FRAME_VERSION.encode(&mut frame).unwrap();
+    command.encode(&mut frame).unwrap();
+    frame.into()
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
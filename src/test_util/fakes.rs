@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use rabbitmq_stream_protocol::{message::Message, ResponseCode};
+
+use crate::{
+    error::{ConsumerDeliveryError, ConsumerStoreOffsetError, ProducerPublishError},
+    types::{ConfirmationStatus, Delivery},
+    MessageConsumer, MessageProducer,
+};
+
+/// In-memory [`MessageProducer`] keeping the messages it confirms
+///
+/// Clones share the messages, keep one to check what the code under test published.
+#[derive(Clone, Default)]
+pub struct FakeProducer(Arc<Mutex<FakeProducerState>>);
+
+#[derive(Default)]
+struct FakeProducerState {
+    messages: Vec<Message>,
+    next_publishing_id: u64,
+    rejection: Option<ResponseCode>,
+    closed: bool,
+}
+
+impl FakeProducer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages confirmed so far, with their publishing ids
+    pub fn messages(&self) -> Vec<Message> {
+        self.0.lock().unwrap().messages.clone()
+    }
+
+    /// Reject the next messages with `status` until [`FakeProducer::accept`]
+    pub fn reject(&self, status: ResponseCode) {
+        self.0.lock().unwrap().rejection = Some(status);
+    }
+
+    /// Confirm the next messages again
+    pub fn accept(&self) {
+        self.0.lock().unwrap().rejection = None;
+    }
+
+    /// Refuse the next sends with [`ProducerPublishError::Closed`], as a closed producer
+    pub fn close(&self) {
+        self.0.lock().unwrap().closed = true;
+    }
+
+    fn publish(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError> {
+        let mut state = self.0.lock().unwrap();
+        if state.closed {
+            return Err(ProducerPublishError::Closed);
+        }
+        let mut statuses = Vec::with_capacity(messages.len());
+        for mut message in messages {
+            // the ids assigned afterwards follow the ones set on the messages
+            let publishing_id = match message.publishing_id() {
+                Some(publishing_id) => *publishing_id,
+                None => state.next_publishing_id,
+            };
+            state.next_publishing_id = state.next_publishing_id.max(publishing_id + 1);
+            message.set_publishing_id(publishing_id);
+
+            let (confirmed, status) = match &state.rejection {
+                Some(status) => (false, status.clone()),
+                None => {
+                    state.messages.push(message.clone());
+                    (true, ResponseCode::Ok)
+                }
+            };
+            statuses.push(ConfirmationStatus {
+                publishing_id,
+                confirmed,
+                status,
+                message,
+            });
+        }
+        Ok(statuses)
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageProducer for FakeProducer {
+    async fn send_with_confirm(
+        &self,
+        message: Message,
+    ) -> Result<ConfirmationStatus, ProducerPublishError> {
+        let mut statuses = self.publish(vec![message])?;
+        Ok(statuses.remove(0))
+    }
+
+    async fn batch_send_with_confirm(
+        &self,
+        messages: Vec<Message>,
+    ) -> Result<Vec<ConfirmationStatus>, ProducerPublishError> {
+        self.publish(messages)
+    }
+}
+
+/// In-memory [`MessageConsumer`] returning the deliveries queued by the test
+///
+/// The consumer ends, [`MessageConsumer::next_delivery`] returning `None`, once the queued
+/// deliveries are returned. Clones share the deliveries and the stored offset, keep one to
+/// queue messages and check the offsets stored by the code under test.
+#[derive(Clone)]
+pub struct FakeConsumer(Arc<Mutex<FakeConsumerState>>);
+
+struct FakeConsumerState {
+    stream: String,
+    deliveries: VecDeque<Result<Delivery, ConsumerDeliveryError>>,
+    next_offset: u64,
+    stored_offset: Option<u64>,
+}
+
+impl FakeConsumer {
+    /// Consumer of `stream`, the offsets of the messages starting at 0
+    pub fn new(stream: &str) -> Self {
+        FakeConsumer(Arc::new(Mutex::new(FakeConsumerState {
+            stream: stream.to_owned(),
+            deliveries: VecDeque::new(),
+            next_offset: 0,
+            stored_offset: None,
+        })))
+    }
+
+    /// Start the offsets of the next messages at `offset`
+    pub fn set_next_offset(&self, offset: u64) {
+        self.0.lock().unwrap().next_offset = offset;
+    }
+
+    /// Queue `message`, in a chunk of its own, and return its offset
+    pub fn push(&self, message: Message) -> u64 {
+        let mut state = self.0.lock().unwrap();
+        let offset = state.next_offset;
+        state.next_offset += 1;
+        let delivery = super::delivery(&state.stream, offset, message);
+        state.deliveries.push_back(Ok(delivery));
+        offset
+    }
+
+    /// Queue an error, returned in place of a message
+    pub fn push_error(&self, error: ConsumerDeliveryError) {
+        self.0.lock().unwrap().deliveries.push_back(Err(error));
+    }
+
+    /// Number of queued deliveries not returned yet
+    pub fn pending(&self) -> usize {
+        self.0.lock().unwrap().deliveries.len()
+    }
+
+    /// Offset last stored with [`MessageConsumer::store_offset`]
+    pub fn stored_offset(&self) -> Option<u64> {
+        self.0.lock().unwrap().stored_offset
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageConsumer for FakeConsumer {
+    async fn next_delivery(&mut self) -> Option<Result<Delivery, ConsumerDeliveryError>> {
+        self.0.lock().unwrap().deliveries.pop_front()
+    }
+
+    async fn store_offset(&self, offset: u64) -> Result<(), ConsumerStoreOffsetError> {
+        self.0.lock().unwrap().stored_offset = Some(offset);
+        Ok(())
+    }
+}
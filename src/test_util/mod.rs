@@ -0,0 +1,49 @@
+//! Helpers for the unit tests of the applications, enabled with the `test-util` feature
+//!
+//! [`FakeProducer`] and [`FakeConsumer`] implement [`MessageProducer`](crate::MessageProducer)
+//! and [`MessageConsumer`](crate::MessageConsumer) in memory: code written against the
+//! traits runs in the tests without a broker, not even the one of the `test-broker` feature.
+//!
+//! The builders create the values the tests of protocol-level code need and cannot
+//! construct themselves: [`deliver_chunk`] the deliver frames of chunks, as responses, wire
+//! frames or consumer deliveries, [`publish_confirm`] and [`publish_confirm_frame`] the
+//! confirmations of publishers, [`full_message`] a message with every section populated.
+//!
+//! ```
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use rabbitmq_stream_client::{
+//!     test_util::{FakeConsumer, FakeProducer},
+//!     types::Message,
+//!     MessageConsumer, MessageProducer,
+//! };
+//!
+//! /// The code under test, forwarding the messages with an uppercase body
+//! async fn forward(consumer: &mut impl MessageConsumer, producer: &impl MessageProducer) {
+//!     while let Some(Ok(delivery)) = consumer.next_delivery().await {
+//!         let body = delivery.message.data().unwrap_or_default().to_ascii_uppercase();
+//!         producer
+//!             .send_with_confirm(Message::builder().body(body).build())
+//!             .await
+//!             .unwrap();
+//!     }
+//! }
+//!
+//! let mut consumer = FakeConsumer::new("orders");
+//! consumer.push(Message::builder().body("hello").build());
+//! let producer = FakeProducer::new();
+//! forward(&mut consumer, &producer).await;
+//!
+//! assert_eq!(Some(&b"HELLO"[..]), producer.messages()[0].data());
+//! # Ok(())
+//! # }
+//! ```
+
+mod builders;
+mod fakes;
+
+pub use builders::{
+    deliver_chunk, delivery, full_message, publish_confirm, publish_confirm_frame,
+    DeliverChunkBuilder,
+};
+pub use fakes::{FakeConsumer, FakeProducer};
@@ -0,0 +1,131 @@
+use std::{future::Future, time::Duration};
+
+use crate::error::ClientError;
+
+/// Limits of the operations that wait for the broker, see
+/// [`EnvironmentBuilder::timeouts`](crate::EnvironmentBuilder::timeouts)
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use rabbitmq_stream_client::types::Timeouts;
+///
+/// let timeouts = Timeouts::new()
+///     .connect(Duration::from_secs(3))
+///     .rpc(Duration::from_secs(5))
+///     .confirm(Duration::from_secs(30));
+/// ```
+///
+/// The confirm and close timeouts are the defaults of the producers, overridden by
+/// [`ProducerBuilder::confirm_timeout`](crate::ProducerBuilder::confirm_timeout) and
+/// [`ProducerBuilder::close_timeout`](crate::ProducerBuilder::close_timeout).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeouts {
+    pub(crate) connect: Duration,
+    pub(crate) handshake: Duration,
+    pub(crate) rpc: Duration,
+    pub(crate) confirm: Option<Duration>,
+    pub(crate) close: Duration,
+}
+
+impl Timeouts {
+    /// 10 seconds to connect, 10 seconds for the handshake, 30 seconds per request, 10
+    /// seconds to close a producer and no confirm timeout
+    pub fn new() -> Self {
+        Timeouts {
+            connect: Duration::from_secs(10),
+            handshake: Duration::from_secs(10),
+            rpc: Duration::from_secs(30),
+            confirm: None,
+            close: Duration::from_secs(10),
+        }
+    }
+
+    /// Time to open the TCP connection, and the TLS session when enabled
+    pub fn connect(mut self, connect: Duration) -> Self {
+        self.connect = connect;
+        self
+    }
+
+    /// Time from the connection opened to the virtual host opened, authentication included
+    pub fn handshake(mut self, handshake: Duration) -> Self {
+        self.handshake = handshake;
+        self
+    }
+
+    /// Time a request waits for the response of the broker, e.g. to create a stream or a
+    /// publisher
+    pub fn rpc(mut self, rpc: Duration) -> Self {
+        self.rpc = rpc;
+        self
+    }
+
+    /// Time a published message waits for its confirm before failing
+    ///
+    /// Without limit by default: the messages not confirmed when a connection fails are
+    /// published again once the producer recovered.
+    pub fn confirm(mut self, confirm: Duration) -> Self {
+        self.confirm = Some(confirm);
+        self
+    }
+
+    /// Time a producer waits for the confirms of its pending messages when closed
+    pub fn close(mut self, close: Duration) -> Self {
+        self.close = close;
+        self
+    }
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run `future` for `timeout` at most, failing with [`ClientError::Timeout`] of `operation`
+pub(crate) async fn with_timeout<T>(
+    operation: &'static str,
+    timeout: Duration,
+    future: impl Future<Output = Result<T, ClientError>>,
+) -> Result<T, ClientError> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or(Err(ClientError::Timeout { operation, timeout }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{with_timeout, Timeouts};
+    use crate::error::ClientError;
+
+    #[test]
+    fn overrides_test() {
+        let timeouts = Timeouts::new()
+            .rpc(Duration::from_secs(1))
+            .confirm(Duration::from_secs(2));
+        assert_eq!(Duration::from_secs(1), timeouts.rpc);
+        assert_eq!(Some(Duration::from_secs(2)), timeouts.confirm);
+        assert_eq!(Timeouts::new().connect, timeouts.connect);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn with_timeout_test() {
+        let result: Result<(), ClientError> =
+            with_timeout("request", Duration::from_secs(1), std::future::pending()).await;
+        assert!(matches!(
+            result,
+            Err(ClientError::Timeout {
+                operation: "request",
+                timeout,
+            }) if timeout == Duration::from_secs(1)
+        ));
+        assert_eq!(
+            5,
+            with_timeout("request", Duration::from_secs(1), async { Ok(5) })
+                .await
+                .unwrap()
+        );
+    }
+}
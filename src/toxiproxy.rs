@@ -0,0 +1,290 @@
+//! Network failures between the client and the broker for the integration tests, enabled
+//! with the `toxiproxy` feature
+//!
+//! [`Toxiproxy`] drives a [toxiproxy](https://github.com/Shopify/toxiproxy) server over its
+//! HTTP API: a [`Proxy`] forwards the connections of an environment to the broker and
+//! [toxics](Toxic) delay, throttle or reset them, to check how producers and consumers
+//! behave and recover on a degraded network.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::time::Duration;
+//!
+//! use rabbitmq_stream_client::toxiproxy::{Toxic, ToxicDirection, Toxiproxy};
+//!
+//! let toxiproxy = Toxiproxy::new("http://localhost:8474")?;
+//! let proxy = toxiproxy
+//!     .create_proxy("rabbitmq", "0.0.0.0:25552", "localhost:5552")
+//!     .await?;
+//! let environment = proxy.environment_builder().build().await?;
+//!
+//! // every frame of the broker arrives 200ms late
+//! let latency = Toxic::Latency {
+//!     latency: Duration::from_millis(200),
+//!     jitter: Duration::ZERO,
+//! };
+//! proxy
+//!     .add_toxic("latency", ToxicDirection::Downstream, latency)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use reqwest::{Client as HttpClient, Method, RequestBuilder, Response, Url};
+use serde_json::{json, Value};
+
+use crate::{error::ToxiproxyError, Environment, EnvironmentBuilder};
+
+/// Address of the API of a toxiproxy server started with the default options
+pub const DEFAULT_URL: &str = "http://localhost:8474";
+
+/// Client of the HTTP API of a toxiproxy server, see the [module](self) documentation
+#[derive(Clone)]
+pub struct Toxiproxy {
+    http: HttpClient,
+    base: Url,
+}
+
+impl Toxiproxy {
+    /// Client of the server whose API listens on `url`, e.g. [`DEFAULT_URL`]
+    pub fn new(url: &str) -> Result<Self, ToxiproxyError> {
+        let base = Url::parse(url)
+            .map_err(|error| ToxiproxyError::Address(format!("{}: {}", url, error)))?;
+        if base.host_str().is_none() {
+            return Err(ToxiproxyError::Address(url.to_owned()));
+        }
+        Ok(Toxiproxy {
+            http: HttpClient::new(),
+            base,
+        })
+    }
+
+    /// Create a proxy listening on `listen` and forwarding the connections to `upstream`,
+    /// the stream port of the broker as seen by the toxiproxy server
+    ///
+    /// A proxy with the same name, left by a previous run, is replaced.
+    pub async fn create_proxy(
+        &self,
+        name: &str,
+        listen: &str,
+        upstream: &str,
+    ) -> Result<Proxy, ToxiproxyError> {
+        match self
+            .send(self.request(Method::DELETE, &["proxies", name])?)
+            .await
+        {
+            Ok(_) | Err(ToxiproxyError::Status { status: 404, .. }) => {}
+            Err(error) => return Err(error),
+        }
+        let proxy: Value = self
+            .send(self.request(Method::POST, &["proxies"])?.json(&json!({
+                "name": name,
+                "listen": listen,
+                "upstream": upstream,
+                "enabled": true,
+            })))
+            .await?
+            .json()
+            .await?;
+
+        // the server picks the port when the one of `listen` is 0
+        let listen = proxy
+            .get("listen")
+            .and_then(Value::as_str)
+            .unwrap_or(listen);
+        let port = listen
+            .rsplit(':')
+            .next()
+            .and_then(|port| port.parse().ok())
+            .ok_or_else(|| ToxiproxyError::Address(listen.to_owned()))?;
+        Ok(Proxy {
+            toxiproxy: self.clone(),
+            name: name.to_owned(),
+            port,
+        })
+    }
+
+    /// Enable every proxy and remove all their toxics
+    pub async fn reset(&self) -> Result<(), ToxiproxyError> {
+        self.send(self.request(Method::POST, &["reset"])?).await?;
+        Ok(())
+    }
+
+    /// Host the proxies listen on, the one of the API
+    pub fn host(&self) -> &str {
+        self.base.host_str().unwrap_or_default()
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> Result<RequestBuilder, ToxiproxyError> {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .map_err(|_| ToxiproxyError::Address(self.base.to_string()))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(self.http.request(method, url))
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ToxiproxyError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let url = response.url().path().to_owned();
+        let body = response.text().await.unwrap_or_default();
+        Err(ToxiproxyError::Status {
+            url,
+            status: status.as_u16(),
+            body,
+        })
+    }
+}
+
+/// Proxy of a toxiproxy server, created with [`Toxiproxy::create_proxy`]
+pub struct Proxy {
+    toxiproxy: Toxiproxy,
+    name: String,
+    port: u16,
+}
+
+impl Proxy {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Port the proxy listens on, on the host of the toxiproxy server
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Builder of an environment whose connections go through the proxy
+    pub fn environment_builder(&self) -> EnvironmentBuilder {
+        Environment::builder()
+            .host(self.toxiproxy.host())
+            .port(self.port)
+    }
+
+    /// Add `toxic` to the data going in `direction`, `name` being unique for the proxy
+    pub async fn add_toxic(
+        &self,
+        name: &str,
+        direction: ToxicDirection,
+        toxic: Toxic,
+    ) -> Result<(), ToxiproxyError> {
+        self.add_partial_toxic(name, direction, toxic, 1.0).await
+    }
+
+    /// Add `toxic` to a share of the connections, `toxicity` between 0 and 1
+    pub async fn add_partial_toxic(
+        &self,
+        name: &str,
+        direction: ToxicDirection,
+        toxic: Toxic,
+        toxicity: f32,
+    ) -> Result<(), ToxiproxyError> {
+        let (kind, attributes) = toxic.attributes();
+        let request = self
+            .toxiproxy
+            .request(Method::POST, &["proxies", &self.name, "toxics"])?
+            .json(&json!({
+                "name": name,
+                "type": kind,
+                "stream": direction.as_str(),
+                "toxicity": toxicity,
+                "attributes": attributes,
+            }));
+        self.toxiproxy.send(request).await?;
+        Ok(())
+    }
+
+    /// Remove the toxic `name`, the data flows normally again
+    pub async fn remove_toxic(&self, name: &str) -> Result<(), ToxiproxyError> {
+        let request = self
+            .toxiproxy
+            .request(Method::DELETE, &["proxies", &self.name, "toxics", name])?;
+        self.toxiproxy.send(request).await?;
+        Ok(())
+    }
+
+    /// Close the connections and refuse new ones until [`Proxy::enable`]
+    pub async fn disable(&self) -> Result<(), ToxiproxyError> {
+        self.set_enabled(false).await
+    }
+
+    /// Accept connections again after [`Proxy::disable`]
+    pub async fn enable(&self) -> Result<(), ToxiproxyError> {
+        self.set_enabled(true).await
+    }
+
+    /// Delete the proxy from the server, closing its connections
+    pub async fn delete(self) -> Result<(), ToxiproxyError> {
+        let request = self
+            .toxiproxy
+            .request(Method::DELETE, &["proxies", &self.name])?;
+        match self.toxiproxy.send(request).await {
+            Ok(_) | Err(ToxiproxyError::Status { status: 404, .. }) => Ok(()),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn set_enabled(&self, enabled: bool) -> Result<(), ToxiproxyError> {
+        let request = self
+            .toxiproxy
+            .request(Method::POST, &["proxies", &self.name])?
+            .json(&json!({ "enabled": enabled }));
+        self.toxiproxy.send(request).await?;
+        Ok(())
+    }
+}
+
+/// Direction of the data a toxic applies to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToxicDirection {
+    /// From the client to the broker
+    Upstream,
+    /// From the broker to the client
+    Downstream,
+}
+
+impl ToxicDirection {
+    fn as_str(self) -> &'static str {
+        match self {
+            ToxicDirection::Upstream => "upstream",
+            ToxicDirection::Downstream => "downstream",
+        }
+    }
+}
+
+/// Failure applied to the connections of a [`Proxy`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Toxic {
+    /// Delay the data by `latency`, give or take `jitter`
+    Latency { latency: Duration, jitter: Duration },
+    /// Limit the rate of the data, in KB/s
+    Bandwidth { rate: u64 },
+    /// Reset the connections after `timeout`, immediately with zero
+    ResetPeer { timeout: Duration },
+    /// Stop the data and close the connections after `timeout`, never with zero
+    Timeout { timeout: Duration },
+}
+
+impl Toxic {
+    fn attributes(&self) -> (&'static str, Value) {
+        match self {
+            Toxic::Latency { latency, jitter } => (
+                "latency",
+                json!({ "latency": latency.as_millis() as u64, "jitter": jitter.as_millis() as u64 }),
+            ),
+            Toxic::Bandwidth { rate } => ("bandwidth", json!({ "rate": rate })),
+            Toxic::ResetPeer { timeout } => (
+                "reset_peer",
+                json!({ "timeout": timeout.as_millis() as u64 }),
+            ),
+            Toxic::Timeout { timeout } => {
+                ("timeout", json!({ "timeout": timeout.as_millis() as u64 }))
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::{consumer::Delivery, error::ConsumerDeliveryError, Consumer, ConsumerHandle};
+
+/// Serialization format of message bodies, see [`Consumer::typed_with`]
+pub trait PayloadFormat: Send + Unpin {
+    fn deserialize<T: DeserializeOwned>(
+        &self,
+        data: &[u8],
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// JSON bodies, the default [`PayloadFormat`]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Json;
+
+impl PayloadFormat for Json {
+    fn deserialize<T: DeserializeOwned>(
+        &self,
+        data: &[u8],
+    ) -> Result<T, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(serde_json::from_slice(data)?)
+    }
+}
+
+/// [`Consumer`] yielding the bodies of the messages deserialized to `T`
+pub struct TypedConsumer<T, F = Json> {
+    consumer: Consumer,
+    format: F,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T, F> TypedConsumer<T, F> {
+    pub(crate) fn new(consumer: Consumer, format: F) -> Self {
+        TypedConsumer {
+            consumer,
+            format,
+            _type: PhantomData,
+        }
+    }
+
+    /// Return an handle for the underlying [`Consumer`]
+    pub fn handle(&self) -> ConsumerHandle {
+        self.consumer.handle()
+    }
+
+    /// Check if the consumer is closed
+    pub fn is_closed(&self) -> bool {
+        self.consumer.is_closed()
+    }
+
+    /// Return the underlying [`Consumer`]
+    pub fn into_inner(self) -> Consumer {
+        self.consumer
+    }
+}
+
+impl<T: DeserializeOwned, F: PayloadFormat> TypedConsumer<T, F> {
+    fn deserialize(&self, delivery: Delivery) -> Result<Delivery<T>, ConsumerDeliveryError> {
+        let offset = delivery.offset;
+        let message = self
+            .format
+            .deserialize(delivery.message.data().unwrap_or_default())
+            .map_err(|error| ConsumerDeliveryError::Deserialize { offset, error })?;
+        Ok(Delivery {
+            stream: delivery.stream,
+            subscription_id: delivery.subscription_id,
+            offset,
+            message,
+            chunk: delivery.chunk,
+        })
+    }
+}
+
+impl<T: DeserializeOwned, F: PayloadFormat> Stream for TypedConsumer<T, F> {
+    type Item = Result<Delivery<T>, ConsumerDeliveryError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.consumer.poll_next_unpin(cx).map(|delivery| {
+            delivery.map(|delivery| delivery.and_then(|delivery| self.deserialize(delivery)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::{Json, PayloadFormat};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+        item: String,
+    }
+
+    #[test]
+    fn json_format_test() {
+        let order: Order = Json.deserialize(br#"{"id": 1, "item": "book"}"#).unwrap();
+        assert_eq!(
+            Order {
+                id: 1,
+                item: "book".to_owned()
+            },
+            order
+        );
+
+        assert!(Json.deserialize::<Order>(b"not json").is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    convert::TryInto,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use rabbitmq_stream_protocol::{codec::Decoder, error::DecodeError, Request, Response};
+use tracing::trace;
+
+/// First bytes of a capture file
+const MAGIC: &[u8; 8] = b"RMQSCAP1";
+
+/// Key of the frame holding the credentials, never captured
+pub(crate) const SASL_AUTHENTICATE_KEY: u16 = 19;
+
+/// Direction, connection, timestamp and size of a captured frame
+const RECORD_HEADER_SIZE: usize = 1 + 4 + 8 + 4;
+
+/// Direction of a captured frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameDirection {
+    /// Read from the broker
+    Inbound,
+    /// Written to the broker
+    Outbound,
+}
+
+/// Records the frames of the connections of an environment in a file, see
+/// [`crate::EnvironmentBuilder::wire_capture`]
+///
+/// The frames are written as they are, with the time they were read or written and the
+/// connection they belong to, except the authentication frame holding the password.
+/// Read them back with [`CaptureReader`].
+#[derive(Clone)]
+pub struct WireCapture(Arc<Mutex<CaptureWriter>>);
+
+struct CaptureWriter {
+    file: BufWriter<File>,
+    connections: u32,
+}
+
+impl WireCapture {
+    /// Create the capture file, truncating it if it exists
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(MAGIC)?;
+        file.flush()?;
+        Ok(WireCapture(Arc::new(Mutex::new(CaptureWriter {
+            file,
+            connections: 0,
+        }))))
+    }
+
+    /// Capture the frames of a new connection
+    pub(crate) fn connection(&self) -> ConnectionCapture {
+        let mut writer = self.0.lock().unwrap();
+        writer.connections += 1;
+        ConnectionCapture {
+            capture: self.clone(),
+            connection: writer.connections,
+        }
+    }
+}
+
+impl fmt::Debug for WireCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WireCapture")
+    }
+}
+
+/// [`WireCapture`] of a connection
+pub(crate) struct ConnectionCapture {
+    capture: WireCapture,
+    connection: u32,
+}
+
+impl ConnectionCapture {
+    /// Append `frame`, size included, failures are traced and do not affect the connection
+    pub(crate) fn record(&self, direction: FrameDirection, frame: &[u8]) {
+        if direction == FrameDirection::Outbound && frame_key(frame) == Some(SASL_AUTHENTICATE_KEY)
+        {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let mut header = Vec::with_capacity(RECORD_HEADER_SIZE);
+        header.push(match direction {
+            FrameDirection::Inbound => 0,
+            FrameDirection::Outbound => 1,
+        });
+        header.extend_from_slice(&self.connection.to_be_bytes());
+        header.extend_from_slice(&timestamp.to_be_bytes());
+        header.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+
+        let mut writer = self.capture.0.lock().unwrap();
+        let result = writer
+            .file
+            .write_all(&header)
+            .and_then(|_| writer.file.write_all(frame))
+            .and_then(|_| writer.file.flush());
+        if let Err(error) = result {
+            trace!(?error, "Failed to capture a frame");
+        }
+    }
+}
+
+pub(crate) fn frame_key(frame: &[u8]) -> Option<u16> {
+    frame
+        .get(4..6)
+        .map(|key| u16::from_be_bytes([key[0], key[1]]))
+}
+
+/// Frame read from a capture file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapturedFrame {
+    pub direction: FrameDirection,
+    /// Number of the connection, starting at 1 in the order they were opened
+    pub connection: u32,
+    pub timestamp: SystemTime,
+    /// Frame as it went on the wire, size included
+    pub frame: Bytes,
+}
+
+impl CapturedFrame {
+    /// Decode an inbound frame like the client did, `raw_chunks` for the connections of
+    /// the chunk consumers and of the consumers handling poison messages
+    pub fn decode_response(&self, raw_chunks: bool) -> Result<Response, DecodeError> {
+        Response::decode_bytes(&self.frame, raw_chunks).map(|(_, response)| response)
+    }
+
+    /// Decode an outbound frame
+    pub fn decode_request(&self) -> Result<Request, DecodeError> {
+        Request::decode(&self.frame).map(|(_, request)| request)
+    }
+}
+
+/// Iterator over the frames of a capture file written by [`WireCapture`]
+pub struct CaptureReader<R = BufReader<File>> {
+    reader: R,
+}
+
+impl CaptureReader {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        CaptureReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Read a capture from `reader`, failing if it does not start like a capture file
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a wire capture file",
+            ));
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    fn read_frame(&mut self) -> io::Result<Option<CapturedFrame>> {
+        let mut header = [0; RECORD_HEADER_SIZE];
+        match self.reader.read_exact(&mut header[..1]) {
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        self.reader.read_exact(&mut header[1..])?;
+
+        let direction = match header[0] {
+            0 => FrameDirection::Inbound,
+            1 => FrameDirection::Outbound,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown frame direction {}", other),
+                ))
+            }
+        };
+        let connection = u32::from_be_bytes(header[1..5].try_into().unwrap());
+        let timestamp = u64::from_be_bytes(header[5..13].try_into().unwrap());
+        let size = u32::from_be_bytes(header[13..17].try_into().unwrap());
+
+        let mut frame = vec![0; size as usize];
+        self.reader.read_exact(&mut frame)?;
+        Ok(Some(CapturedFrame {
+            direction,
+            connection,
+            timestamp: UNIX_EPOCH + Duration::from_micros(timestamp),
+            frame: frame.into(),
+        }))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = io::Result<CapturedFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rabbitmq_stream_protocol::{
+        codec::Encoder,
+        commands::{credit::CreditCommand, sasl_authenticate::SaslAuthenticateCommand},
+        Request,
+    };
+
+    use super::{CaptureReader, FrameDirection, WireCapture};
+
+    fn encode(request: Request) -> Vec<u8> {
+        let mut frame = Vec::new();
+        request.encode(&mut frame).unwrap();
+        frame
+    }
+
+    #[test]
+    fn wire_capture_test() {
+        let path = std::env::temp_dir().join(format!("wire-capture-{}", std::process::id()));
+        let capture = WireCapture::create(&path).unwrap();
+        let connection = capture.connection();
+
+        let credit = encode(CreditCommand::new(1, 10).into());
+        connection.record(FrameDirection::Outbound, &credit);
+        let authenticate = encode(
+            SaslAuthenticateCommand::new(1, "PLAIN".to_owned(), b"password".to_vec()).into(),
+        );
+        connection.record(FrameDirection::Outbound, &authenticate);
+        capture
+            .connection()
+            .record(FrameDirection::Inbound, &[0, 0, 0, 2, 0x80, 0x17]);
+
+        let frames: Vec<_> = CaptureReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(2, frames.len());
+        assert_eq!(FrameDirection::Outbound, frames[0].direction);
+        assert_eq!(1, frames[0].connection);
+        assert_eq!(&credit[..], &frames[0].frame[..]);
+        assert_eq!(encode(frames[0].decode_request().unwrap()), credit);
+        assert_eq!(FrameDirection::Inbound, frames[1].direction);
+        assert_eq!(2, frames[1].connection);
+
+        assert!(CaptureReader::new(&b"not a capture"[..]).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt, io,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{
+    error::ReplayError,
+    wire_capture::{
+        frame_key, CaptureReader, CapturedFrame, FrameDirection, SASL_AUTHENTICATE_KEY,
+    },
+};
+
+const HEARTBEAT_KEY: u16 = 23;
+
+/// Key of the answer to the authentication frame, the one frame recorded without its request
+const SASL_AUTHENTICATE_RESPONSE_KEY: u16 = SASL_AUTHENTICATE_KEY | 0x8000;
+
+/// Plays the broker side of connections recorded by a [`crate::types::WireCapture`], see
+/// [`crate::EnvironmentBuilder::wire_replay`]
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use rabbitmq_stream_client::{types::WireReplay, Environment};
+///
+/// let replay = WireReplay::open("tests/captures/issue-42.cap")?;
+/// let environment = Environment::builder()
+///     .wire_replay(replay.clone())
+///     .build()
+///     .await?;
+/// // drive the client as when the capture was recorded
+/// replay.verify()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The connections opened by the client get the recorded connections in order. Each one
+/// feeds the client the frames the broker sent, a frame held until the client wrote the
+/// frames recorded before it, and checks the frames the client writes are the recorded
+/// ones. The heartbeats are left out, the authentication frame holding the password was
+/// not recorded and is accepted as it is. The frames written by the background tasks, as
+/// the credits, can race with the ones of the application: let them go out before the
+/// next step in the recorded and in the replayed runs.
+#[derive(Clone)]
+pub struct WireReplay(Arc<Mutex<ReplayState>>);
+
+struct ReplayState {
+    /// Recorded connections not opened yet
+    recorded: VecDeque<Script>,
+    opened: Vec<Arc<Mutex<Script>>>,
+    connections: u32,
+}
+
+/// Frames of a recorded connection
+struct Script {
+    connection: u32,
+    frames: VecDeque<Expected>,
+    /// Start of the next frame written by the client
+    written: BytesMut,
+    mismatch: Option<ReplayError>,
+    reader: Option<Waker>,
+}
+
+enum Expected {
+    Inbound(Bytes),
+    Outbound(Bytes),
+    Authenticate,
+}
+
+impl WireReplay {
+    /// Replay the connections of a capture file
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let frames = CaptureReader::open(path)?.collect::<io::Result<Vec<_>>>()?;
+        Ok(WireReplay::new(frames))
+    }
+
+    /// Replay the connections of `frames`, in the order they were captured
+    pub fn new(frames: impl IntoIterator<Item = CapturedFrame>) -> Self {
+        let mut connections: BTreeMap<u32, VecDeque<Expected>> = BTreeMap::new();
+        for captured in frames {
+            let key = frame_key(&captured.frame).map(|key| key & 0x7fff);
+            if key == Some(HEARTBEAT_KEY) {
+                continue;
+            }
+            let script = connections.entry(captured.connection).or_default();
+            match captured.direction {
+                FrameDirection::Inbound => {
+                    if frame_key(&captured.frame) == Some(SASL_AUTHENTICATE_RESPONSE_KEY) {
+                        script.push_back(Expected::Authenticate);
+                    }
+                    script.push_back(Expected::Inbound(captured.frame));
+                }
+                FrameDirection::Outbound => script.push_back(Expected::Outbound(captured.frame)),
+            }
+        }
+        let recorded = connections
+            .into_iter()
+            .map(|(connection, frames)| Script {
+                connection,
+                frames,
+                written: BytesMut::new(),
+                mismatch: None,
+                reader: None,
+            })
+            .collect();
+        WireReplay(Arc::new(Mutex::new(ReplayState {
+            recorded,
+            opened: Vec::new(),
+            connections: 0,
+        })))
+    }
+
+    /// Check the client sent every recorded frame and nothing else, on every recorded
+    /// connection
+    ///
+    /// Returns the first difference, in the order the connections were opened.
+    pub fn verify(&self) -> Result<(), ReplayError> {
+        let state = self.0.lock().unwrap();
+        for script in &state.opened {
+            let script = script.lock().unwrap();
+            if let Some(mismatch) = &script.mismatch {
+                return Err(mismatch.clone());
+            }
+            let missing = script
+                .frames
+                .iter()
+                .filter(|frame| !matches!(frame, Expected::Inbound(_)))
+                .count();
+            if missing > 0 {
+                return Err(ReplayError::MissingFrames {
+                    connection: script.connection,
+                    missing,
+                });
+            }
+        }
+        if state.connections as usize > state.opened.len() {
+            return Err(ReplayError::ExtraConnection(state.opened.len() as u32 + 1));
+        }
+        if !state.recorded.is_empty() {
+            return Err(ReplayError::MissingConnections(state.recorded.len()));
+        }
+        Ok(())
+    }
+
+    /// Open the next recorded connection, refused when they are all open
+    pub(crate) fn connect(&self) -> io::Result<ReplayStream> {
+        let mut state = self.0.lock().unwrap();
+        state.connections += 1;
+        let script = match state.recorded.pop_front() {
+            Some(script) => Arc::new(Mutex::new(script)),
+            None => return Err(io::ErrorKind::ConnectionRefused.into()),
+        };
+        state.opened.push(script.clone());
+        Ok(ReplayStream(script))
+    }
+}
+
+impl fmt::Debug for WireReplay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0.lock().unwrap();
+        f.debug_struct("WireReplay")
+            .field("recorded", &state.recorded.len())
+            .field("opened", &state.opened.len())
+            .finish()
+    }
+}
+
+impl Script {
+    /// Match the complete frames written by the client against the recorded ones
+    fn check_written(&mut self) -> Result<(), ReplayError> {
+        while self.written.len() >= 4 {
+            let size = u32::from_be_bytes([
+                self.written[0],
+                self.written[1],
+                self.written[2],
+                self.written[3],
+            ]) as usize;
+            if self.written.len() < 4 + size {
+                break;
+            }
+            let actual = self.written.split_to(4 + size).freeze();
+            let key = frame_key(&actual);
+            if key == Some(HEARTBEAT_KEY) {
+                continue;
+            }
+            // the inbound frames before it can still be waiting to be read
+            let position = match self
+                .frames
+                .iter()
+                .position(|frame| !matches!(frame, Expected::Inbound(_)))
+            {
+                Some(position) => position,
+                None => {
+                    return Err(ReplayError::ExtraFrame {
+                        connection: self.connection,
+                        actual,
+                    })
+                }
+            };
+            match &self.frames[position] {
+                Expected::Outbound(expected) if *expected == actual => {}
+                Expected::Authenticate if key == Some(SASL_AUTHENTICATE_KEY) => {}
+                expected => {
+                    return Err(ReplayError::UnexpectedFrame {
+                        connection: self.connection,
+                        expected: match expected {
+                            Expected::Outbound(expected) => expected.clone(),
+                            _ => Bytes::new(),
+                        },
+                        actual,
+                    })
+                }
+            }
+            self.frames.remove(position);
+        }
+        Ok(())
+    }
+}
+
+fn replay_error(error: &ReplayError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+/// Transport of a connection played by a [`WireReplay`]
+pub(crate) struct ReplayStream(Arc<Mutex<Script>>);
+
+impl AsyncRead for ReplayStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut script = self.0.lock().unwrap();
+        if let Some(mismatch) = &script.mismatch {
+            return Poll::Ready(Err(replay_error(mismatch)));
+        }
+        match script.frames.front_mut() {
+            Some(Expected::Inbound(frame)) => {
+                let len = frame.len().min(buf.remaining());
+                buf.put_slice(&frame.split_to(len));
+                if frame.is_empty() {
+                    script.frames.pop_front();
+                }
+                Poll::Ready(Ok(()))
+            }
+            // waiting for the client to write the frames recorded before the next one
+            _ => {
+                script.reader = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl AsyncWrite for ReplayStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut script = self.0.lock().unwrap();
+        if let Some(mismatch) = &script.mismatch {
+            return Poll::Ready(Err(replay_error(mismatch)));
+        }
+        script.written.extend_from_slice(buf);
+        let result = script.check_written();
+        if let Some(reader) = script.reader.take() {
+            reader.wake();
+        }
+        match result {
+            Ok(()) => Poll::Ready(Ok(buf.len())),
+            Err(mismatch) => {
+                let error = replay_error(&mismatch);
+                script.mismatch = Some(mismatch);
+                Poll::Ready(Err(error))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use bytes::Bytes;
+    use rabbitmq_stream_protocol::{
+        codec::Encoder,
+        commands::{credit::CreditCommand, sasl_authenticate::SaslAuthenticateCommand},
+        Request,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::WireReplay;
+    use crate::{
+        error::ReplayError,
+        wire_capture::{CapturedFrame, FrameDirection},
+    };
+
+    fn encode(request: Request) -> Bytes {
+        let mut frame = Vec::new();
+        request.encode(&mut frame).unwrap();
+        frame.into()
+    }
+
+    fn captured(direction: FrameDirection, connection: u32, frame: &Bytes) -> CapturedFrame {
+        CapturedFrame {
+            direction,
+            connection,
+            timestamp: SystemTime::now(),
+            frame: frame.clone(),
+        }
+    }
+
+    #[tokio::test]
+    async fn wire_replay_test() {
+        let credit = encode(CreditCommand::new(1, 10).into());
+        let authenticate =
+            encode(SaslAuthenticateCommand::new(2, "PLAIN".to_owned(), b"secret".to_vec()).into());
+        let authenticated = Bytes::from_static(&[0, 0, 0, 10, 0x80, 0x13, 0, 1, 0, 0, 0, 2, 0, 1]);
+        let inbound = Bytes::from_static(&[0, 0, 0, 4, 0, 8, 0, 1]);
+        let replay = WireReplay::new(vec![
+            captured(FrameDirection::Outbound, 1, &credit),
+            captured(FrameDirection::Inbound, 1, &authenticated),
+            captured(FrameDirection::Inbound, 1, &inbound),
+            captured(FrameDirection::Outbound, 2, &credit),
+        ]);
+
+        let mut connection = replay.connect().unwrap();
+        // in two writes, the authentication frame was not recorded
+        connection.write_all(&credit[..3]).await.unwrap();
+        connection.write_all(&credit[3..]).await.unwrap();
+        connection.write_all(&authenticate).await.unwrap();
+        let mut read = vec![0; authenticated.len() + inbound.len()];
+        connection.read_exact(&mut read).await.unwrap();
+        assert_eq!([&authenticated[..], &inbound[..]].concat(), read);
+        assert!(replay.verify().is_err());
+
+        let mut connection = replay.connect().unwrap();
+        let other = encode(CreditCommand::new(1, 20).into());
+        assert!(connection.write_all(&other).await.is_err());
+        assert_eq!(
+            Err(ReplayError::UnexpectedFrame {
+                connection: 2,
+                expected: credit,
+                actual: other,
+            }),
+            replay.verify()
+        );
+        assert!(replay.connect().is_err());
+    }
+}
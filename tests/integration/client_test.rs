@@ -102,6 +102,40 @@ async fn client_create_subscribe_test() {
     assert_eq!(&ResponseCode::Ok, response.code());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn client_debug_state_test() {
+    let test = TestClient::create().await;
+
+    test.client
+        .subscribe(
+            1,
+            &test.stream,
+            OffsetSpecification::Next,
+            1,
+            HashMap::new(),
+        )
+        .await
+        .unwrap();
+    test.client.credit(1, 2).await.unwrap();
+    test.client
+        .declare_publisher(2, None, &test.stream)
+        .await
+        .unwrap();
+
+    let state = test.client.debug_state().await;
+    assert_eq!(1, state.subscriptions.len());
+    assert_eq!(test.stream, state.subscriptions[0].stream);
+    assert_eq!(3, state.subscriptions[0].credits);
+    assert_eq!(2, state.publishers[0].publisher_id);
+    assert!(state.pending_correlation_ids.is_empty());
+
+    test.client.unsubscribe(1).await.unwrap();
+    test.client.delete_publisher(2).await.unwrap();
+    let state = test.client.debug_state().await;
+    assert!(state.subscriptions.is_empty());
+    assert!(state.publishers.is_empty());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn client_store_and_query_offset_test() {
     let test = TestClient::create().await;
@@ -120,7 +154,8 @@ async fn client_store_and_query_offset_test() {
         .await
         .unwrap();
 
-    assert_eq!(offset, response);
+    assert_eq!(&ResponseCode::Ok, response.code());
+    assert_eq!(offset, response.offset());
 }
 
 /*
@@ -182,11 +217,8 @@ async fn client_publish() {
 
     let handler = move |msg: MessageResult| async move {
         if let Some(Ok(response)) = msg {
-            match response.kind() {
-                ResponseKind::Deliver(delivery) => {
-                    tx.send(delivery.clone()).await.unwrap();
-                }
-                _ => {}
+            if let ResponseKind::Deliver(delivery) = response.kind() {
+                tx.send(delivery.clone()).await.unwrap();
             }
         }
         Ok(())
@@ -227,6 +259,6 @@ async fn client_publish() {
     assert_eq!(1, delivery.messages.len());
     assert_eq!(
         Some(b"message".as_ref()),
-        delivery.messages.get(0).unwrap().data()
+        delivery.messages.first().unwrap().data()
     );
 }
@@ -39,7 +39,7 @@ impl TestEnvironment {
     pub async fn create() -> TestEnvironment {
         let stream: String = Faker.fake();
         let env = Environment::builder().build().await.unwrap();
-        let _ = env.stream_creator().create(&stream).await.unwrap();
+        env.stream_creator().create(&stream).await.unwrap();
 
         TestEnvironment { env, stream }
     }
@@ -53,3 +53,43 @@ impl Drop for TestEnvironment {
         });
     }
 }
+
+pub struct TestSuperStream {
+    pub env: Environment,
+    pub super_stream: String,
+    pub partitions: Vec<String>,
+}
+
+impl TestSuperStream {
+    pub async fn create(partitions: usize) -> TestSuperStream {
+        let super_stream: String = Faker.fake();
+        let env = Environment::builder().build().await.unwrap();
+        env.super_stream_creator()
+            .partitions(partitions)
+            .create(&super_stream)
+            .await
+            .unwrap();
+
+        let partitions = (0..partitions)
+            .map(|partition| format!("{}-{}", super_stream, partition))
+            .collect();
+        TestSuperStream {
+            env,
+            super_stream,
+            partitions,
+        }
+    }
+}
+
+impl Drop for TestSuperStream {
+    fn drop(&mut self) {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                self.env
+                    .delete_super_stream(&self.super_stream)
+                    .await
+                    .unwrap()
+            })
+        });
+    }
+}
@@ -1,11 +1,23 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use crate::common::TestEnvironment;
 use fake::{Fake, Faker};
 use futures::StreamExt;
 use rabbitmq_stream_client::{
-    error::{ConsumerCloseError, ProducerCloseError},
-    types::{Message, OffsetSpecification},
+    error::{
+        ConsumerCloseError, ConsumerCreateError, ConsumerDeliveryError, ConsumerQueryOffsetError,
+        ConsumerStoreOffsetError, OffsetStoreError, ProducerCloseError,
+    },
+    types::{
+        AutoOffsetTracking, BodyCodec, BodyCodecs, ByteCapacity, CodecError, ConsumerInterceptor,
+        CreditStrategy, InterceptorError, Message, OffsetSpecification, OffsetStore,
+        PoisonMessageHandling, PoisonReason, SlowConsumerReason, StreamEvent,
+    },
+    ConsumerRecoveryEvent,
 };
 
 #[tokio::test(flavor = "multi_thread")]
@@ -31,8 +43,11 @@ async fn consumer_test() {
         .unwrap();
 
     for n in 0..message_count {
-        let _ = producer
-            .send(Message::builder().body(format!("message{}", n)).build())
+        producer
+            .send(
+                Message::builder().body(format!("message{}", n)).build(),
+                |_| async {},
+            )
             .await
             .unwrap();
     }
@@ -68,15 +83,15 @@ async fn consumer_close_test() {
         .await
         .unwrap();
 
-    let _ = producer
-        .send(Message::builder().body("message").build())
+    producer
+        .send(Message::builder().body("message").build(), |_| async {})
         .await
         .unwrap();
 
     let handle = consumer.handle();
     let delivery = consumer.next().await;
 
-    assert_eq!(false, consumer.is_closed());
+    assert!(!consumer.is_closed());
     assert!(delivery.is_some());
 
     tokio::spawn(async move {
@@ -87,7 +102,7 @@ async fn consumer_close_test() {
     let delivery = consumer.next().await;
 
     assert!(delivery.is_none());
-    assert_eq!(true, consumer.is_closed());
+    assert!(consumer.is_closed());
 
     assert!(matches!(
         consumer.handle().close().await,
@@ -100,3 +115,1049 @@ async fn consumer_close_test() {
         Err(ProducerCloseError::AlreadyClosed),
     ));
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_delivery_offset_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::Offset(5))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for n in 5..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+        assert_eq!(1, delivery.subscription_id);
+        assert_eq!(
+            Some(format!("message{}", n).as_bytes()),
+            delivery.message.data()
+        );
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_message_handler_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let handle = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .message_handler(move |context, message| {
+            let sender = sender.clone();
+            async move {
+                sender
+                    .send((context.offset(), message.data().map(<[u8]>::to_vec)))
+                    .unwrap();
+            }
+        })
+        .start(&env.stream)
+        .await
+        .unwrap();
+
+    for n in 0..10 {
+        let (offset, data) = receiver.recv().await.unwrap();
+        assert_eq!(n, offset);
+        assert_eq!(Some(format!("message{}", n).into_bytes()), data);
+    }
+
+    handle.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_concurrent_message_handler_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let handle = env
+        .env
+        .consumer()
+        .name("concurrent-consumer")
+        .offset(OffsetSpecification::First)
+        .auto_offset_tracking(AutoOffsetTracking::default().message_count(1))
+        .message_handler(move |context, _| {
+            let sender = sender.clone();
+            async move {
+                // the first messages complete last
+                tokio::time::sleep(Duration::from_millis(100 - context.offset() * 10)).await;
+                sender.send(context.offset()).unwrap();
+            }
+        })
+        .concurrency(4)
+        .start(&env.stream)
+        .await
+        .unwrap();
+
+    let mut offsets = vec![];
+    for _ in 0..10 {
+        offsets.push(receiver.recv().await.unwrap());
+    }
+    assert_ne!((0..10).collect::<Vec<u64>>(), offsets);
+    offsets.sort();
+    assert_eq!((0..10).collect::<Vec<u64>>(), offsets);
+
+    // the offset is stored once every message before it completed
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(9, handle.query_offset().await.unwrap());
+
+    handle.close().await.unwrap();
+}
+
+struct DropOdd;
+
+impl ConsumerInterceptor for DropOdd {
+    fn on_delivery(
+        &self,
+        _stream: &str,
+        offset: u64,
+        message: Message,
+    ) -> Result<Message, InterceptorError> {
+        match offset % 2 {
+            0 => Ok(message),
+            _ => Err(format!("odd offset {}", offset).into()),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_interceptor_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..2)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .interceptor(DropOdd)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(0, delivery.offset);
+    assert!(matches!(
+        consumer.next().await.unwrap(),
+        Err(ConsumerDeliveryError::Interceptor { offset: 1, .. })
+    ));
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_poison_message_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..3)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let (poison_sender, mut poison_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let handle = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .poison_messages(
+            PoisonMessageHandling::callback(move |poison| {
+                let poison_sender = poison_sender.clone();
+                async move {
+                    poison_sender.send(poison).unwrap();
+                }
+            })
+            .max_attempts(2),
+        )
+        .try_message_handler(move |context, _| {
+            let sender = sender.clone();
+            async move {
+                sender.send(context.offset()).unwrap();
+                match context.offset() {
+                    1 => Err("cannot handle message1"),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .start(&env.stream)
+        .await
+        .unwrap();
+
+    // the failed message is handled twice, then given up on
+    let mut offsets = vec![];
+    for _ in 0..4 {
+        offsets.push(receiver.recv().await.unwrap());
+    }
+    assert_eq!(vec![0, 1, 1, 2], offsets);
+
+    let poison = poison_receiver.recv().await.unwrap();
+    assert_eq!(1, poison.offset());
+    assert_eq!(
+        Some(b"message1".as_ref()),
+        poison.message().and_then(Message::data)
+    );
+    assert!(matches!(
+        poison.reason(),
+        PoisonReason::Handler { attempts: 2, .. }
+    ));
+
+    handle.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_offset_specification_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    for offset in [
+        OffsetSpecification::First,
+        OffsetSpecification::Timestamp(0),
+    ] {
+        let mut consumer = env
+            .env
+            .consumer()
+            .offset(offset)
+            .build(&env.stream)
+            .await
+            .unwrap();
+
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(0, delivery.offset);
+
+        consumer.handle().close().await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_store_and_query_offset_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+
+    let consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        consumer.query_offset().await,
+        Err(ConsumerQueryOffsetError::OffsetNotFound { .. })
+    ));
+
+    consumer.store_offset(42).await.unwrap();
+    assert_eq!(42, consumer.query_offset().await.unwrap());
+
+    consumer.handle().close().await.unwrap();
+
+    let anonymous = env.env.consumer().build(&env.stream).await.unwrap();
+    assert!(matches!(
+        anonymous.store_offset(42).await,
+        Err(ConsumerStoreOffsetError::NameMissing)
+    ));
+    anonymous.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_start_from_stored_offset_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..3)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let result = env
+        .env
+        .consumer()
+        .start_from_stored_offset_or(OffsetSpecification::First)
+        .build(&env.stream)
+        .await;
+    assert!(matches!(result, Err(ConsumerCreateError::NameMissing)));
+
+    // nothing stored yet, the fallback applies
+    let mut consumer = env
+        .env
+        .consumer()
+        .name("stored-offset-consumer")
+        .start_from_stored_offset_or(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    assert_eq!(0, consumer.next().await.unwrap().unwrap().offset);
+    consumer.handle().store_offset(1).await.unwrap();
+    consumer.handle().close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name("stored-offset-consumer")
+        .start_from_stored_offset_or(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    assert_eq!(2, consumer.next().await.unwrap().unwrap().offset);
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_auto_offset_tracking_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .offset(OffsetSpecification::First)
+        .auto_offset_tracking(AutoOffsetTracking::default().message_count(5))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for _ in 0..7 {
+        consumer.next().await.unwrap().unwrap();
+    }
+
+    let handle = consumer.handle();
+    handle.close().await.unwrap();
+
+    let consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    assert_eq!(6, consumer.query_offset().await.unwrap());
+    consumer.handle().close().await.unwrap();
+
+    assert!(matches!(
+        env.env
+            .consumer()
+            .auto_offset_tracking(AutoOffsetTracking::default())
+            .build(&env.stream)
+            .await,
+        Err(ConsumerCreateError::NameMissing)
+    ));
+}
+
+#[derive(Clone, Default)]
+struct MemoryOffsetStore(Arc<Mutex<HashMap<(String, String), u64>>>);
+
+#[async_trait::async_trait]
+impl OffsetStore for MemoryOffsetStore {
+    async fn load(&self, reference: &str, stream: &str) -> Result<Option<u64>, OffsetStoreError> {
+        let offsets = self.0.lock().unwrap();
+        Ok(offsets
+            .get(&(reference.to_owned(), stream.to_owned()))
+            .copied())
+    }
+
+    async fn store(
+        &self,
+        reference: &str,
+        stream: &str,
+        offset: u64,
+    ) -> Result<(), OffsetStoreError> {
+        let mut offsets = self.0.lock().unwrap();
+        offsets.insert((reference.to_owned(), stream.to_owned()), offset);
+        Ok(())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_custom_offset_store_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+    let offset_store = MemoryOffsetStore::default();
+
+    let consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .offset_store(offset_store.clone())
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    consumer.store_offset(42).await.unwrap();
+    assert_eq!(42, consumer.query_offset().await.unwrap());
+    assert_eq!(
+        Some(42),
+        offset_store.load(&name, &env.stream).await.unwrap()
+    );
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_credit_strategy_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    for n in 0..10 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .initial_credits(1)
+        .credit_strategy(CreditStrategy::Refill {
+            threshold: 1,
+            credits: 2,
+        })
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for n in 0..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_credit_on_processed_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    for n in 0..10 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+    producer.close().await.unwrap();
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let handle = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .initial_credits(1)
+        .credit_strategy(CreditStrategy::OnProcessed)
+        .message_handler(move |context, _| {
+            let sender = sender.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                sender.send(context.offset()).unwrap();
+            }
+        })
+        .start(&env.stream)
+        .await
+        .unwrap();
+
+    for n in 0..10 {
+        assert_eq!(n, receiver.recv().await.unwrap());
+    }
+
+    handle.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_single_active_consumer_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+
+    let first = env
+        .env
+        .consumer()
+        .name(&name)
+        .enable_single_active_consumer(true)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let second = env
+        .env
+        .consumer()
+        .name(&name)
+        .enable_single_active_consumer(true)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(first.is_active());
+    assert!(!second.is_active());
+
+    first.handle().close().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(second.is_active());
+
+    second.handle().close().await.unwrap();
+
+    assert!(matches!(
+        env.env
+            .consumer()
+            .enable_single_active_consumer(true)
+            .build(&env.stream)
+            .await,
+        Err(ConsumerCreateError::NameMissing)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_update_listener_test() {
+    let env = TestEnvironment::create().await;
+    let name: String = Faker.fake();
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let offset_store = env
+        .env
+        .consumer()
+        .name(&name)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    offset_store.store_offset(4).await.unwrap();
+    offset_store.handle().close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name(&name)
+        .enable_single_active_consumer(true)
+        .consumer_update_listener(|context| async move {
+            match context.query_offset().await {
+                Ok(offset) => OffsetSpecification::Offset(offset + 1),
+                Err(_) => OffsetSpecification::First,
+            }
+        })
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(5, delivery.offset);
+    assert!(consumer.is_active());
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_post_filter_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .filter_values(vec!["even".to_owned()], |message| {
+            message
+                .data()
+                .is_some_and(|data| data[data.len() - 1] % 2 == 0)
+        })
+        .match_unfiltered(true)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for n in (0..10).step_by(2) {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_raw_chunks_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .raw_chunks()
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let mut bodies = Vec::new();
+    while bodies.len() < 10 {
+        let chunk = consumer.next().await.unwrap().unwrap();
+        assert_eq!(bodies.len() as u64, chunk.first_offset());
+        assert!(!chunk.data().is_empty());
+
+        let messages = chunk.messages().unwrap();
+        assert_eq!(chunk.num_records() as usize, messages.len());
+        let records: Vec<_> = chunk.records().map(Result::unwrap).collect();
+        assert_eq!(messages.len(), records.len());
+        for (record, message) in records.iter().zip(&messages) {
+            assert_eq!(message, &record.message().unwrap());
+        }
+        assert_eq!(chunk.first_offset(), records[0].offset());
+        bodies.extend(
+            messages
+                .iter()
+                .map(|message| message.data().unwrap().to_vec()),
+        );
+    }
+
+    let expected: Vec<Vec<u8>> = (0..10)
+        .map(|n| format!("message{}", n).into_bytes())
+        .collect();
+    assert_eq!(expected, bodies);
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_chunk_metadata_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for _ in 0..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        let chunk = delivery.chunk();
+        assert!(chunk.first_offset() <= delivery.offset);
+        assert!(delivery.offset <= chunk.last_offset());
+        assert!(chunk.num_records() >= 1);
+        assert!(chunk.timestamp() > 0);
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_lag_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    assert_eq!(10, consumer.lag().await.unwrap());
+
+    for _ in 0..4 {
+        consumer.next().await.unwrap().unwrap();
+    }
+    assert_eq!(6, consumer.lag().await.unwrap());
+
+    for _ in 0..6 {
+        consumer.next().await.unwrap().unwrap();
+    }
+    assert_eq!(0, consumer.handle().lag().await.unwrap());
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_pause_resume_test() {
+    let env = TestEnvironment::create().await;
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::Next)
+        .initial_credits(1)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    consumer.pause();
+    assert!(consumer.is_paused());
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    for n in 0..2 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    // the initial credit delivers the first chunk, the next one waits for the resume
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(0, delivery.offset);
+    let next = tokio::time::timeout(Duration::from_millis(500), consumer.next()).await;
+    assert!(next.is_err());
+
+    consumer.resume().await.unwrap();
+    assert!(!consumer.is_paused());
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(1, delivery.offset);
+
+    producer.close().await.unwrap();
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_close_gracefully_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .name("graceful-consumer")
+        .offset(OffsetSpecification::First)
+        .auto_offset_tracking(AutoOffsetTracking::default())
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    // wait for the messages to be received before closing
+    let first = consumer.next().await.unwrap().unwrap();
+    assert_eq!(0, first.offset);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let handle = consumer.handle();
+    let close = tokio::task::spawn(handle.close_gracefully(Duration::from_secs(5)));
+
+    let mut last_offset = first.offset;
+    while let Some(delivery) = consumer.next().await {
+        last_offset = delivery.unwrap().offset;
+    }
+    close.await.unwrap().unwrap();
+
+    assert_eq!(9, last_offset);
+    assert!(consumer.is_closed());
+    assert_eq!(9, consumer.query_offset().await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_recovery_test() {
+    let env = TestEnvironment::create().await;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .on_recovery(move |event| {
+            let _ = tx.send(event);
+        })
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    // deleting the stream cancels the subscription, the consumer subscribes again
+    // once the stream exists again
+    env.env.delete_stream(&env.stream).await.unwrap();
+    assert!(matches!(
+        rx.recv().await.unwrap(),
+        ConsumerRecoveryEvent::Started { .. }
+    ));
+    env.env.stream_creator().create(&env.stream).await.unwrap();
+
+    let recovered = tokio::time::timeout(Duration::from_secs(15), async {
+        loop {
+            if let ConsumerRecoveryEvent::Recovered { offset, .. } = rx.recv().await.unwrap() {
+                return offset;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(OffsetSpecification::First, recovered);
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_typed_test() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Order {
+        id: u32,
+    }
+
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(vec![
+            Message::builder().body(r#"{"id": 1}"#).build(),
+            Message::builder().body("not json").build(),
+        ])
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap()
+        .typed::<Order>();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(0, delivery.offset);
+    assert_eq!(Order { id: 1 }, delivery.message);
+
+    assert!(matches!(
+        consumer.next().await.unwrap(),
+        Err(ConsumerDeliveryError::Deserialize { offset: 1, .. })
+    ));
+
+    consumer.handle().close().await.unwrap();
+}
+
+struct Decimal;
+
+impl BodyCodec<u32> for Decimal {
+    fn encode(&self, value: &u32) -> Result<Vec<u8>, CodecError> {
+        Ok(value.to_string().into_bytes())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<u32, CodecError> {
+        Ok(std::str::from_utf8(data)?.parse()?)
+    }
+}
+
+struct BigEndian;
+
+impl BodyCodec<u32> for BigEndian {
+    fn encode(&self, value: &u32) -> Result<Vec<u8>, CodecError> {
+        Ok(value.to_be_bytes().to_vec())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<u32, CodecError> {
+        Ok(u32::from_be_bytes(std::convert::TryFrom::try_from(data)?))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_decoded_test() {
+    let env = TestEnvironment::create().await;
+    let codecs = BodyCodecs::new()
+        .register("text/plain", Decimal)
+        .register("application/octet-stream", BigEndian);
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(vec![
+            codecs.encode("text/plain", &1).unwrap().build(),
+            codecs
+                .encode("application/octet-stream", &2)
+                .unwrap()
+                .build(),
+            Message::builder().body("3").build(),
+        ])
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap()
+        .decoded(codecs);
+
+    assert_eq!(1, consumer.next().await.unwrap().unwrap().message);
+    assert_eq!(2, consumer.next().await.unwrap().unwrap().message);
+    assert!(matches!(
+        consumer.next().await.unwrap(),
+        Err(ConsumerDeliveryError::Decode { offset: 2, .. })
+    ));
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consumer_slow_consumer_pending_bytes_test() {
+    let env = TestEnvironment::create().await;
+    let mut events = env.env.events();
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(vec![Message::builder().body(vec![0; 100]).build(); 20])
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    // the messages are not consumed, they stay pending
+    let consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .slow_consumer_pending_bytes(ByteCapacity::B(1000))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        if let StreamEvent::SlowConsumer { stream, reason, .. } = event {
+            assert_eq!(env.stream, stream);
+            assert!(matches!(reason, SlowConsumerReason::PendingBytes { bytes } if bytes >= 1000));
+            break;
+        }
+    }
+
+    consumer.handle().close().await.unwrap();
+}
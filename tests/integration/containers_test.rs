@@ -0,0 +1,16 @@
+use rabbitmq_stream_client::{containers::RabbitMqStreamContainer, types::Message};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rabbitmq_stream_container_test() {
+    let rabbitmq = RabbitMqStreamContainer::start().await.unwrap();
+    let environment = rabbitmq.environment().await.unwrap();
+    environment.stream_creator().create("orders").await.unwrap();
+
+    let producer = environment.producer().build("orders").await.unwrap();
+    producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+    environment.delete_stream("orders").await.unwrap();
+}
@@ -1,6 +1,604 @@
-use crate::common::TestEnvironment;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use fake::{Fake, Faker};
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    error::{
+        ClientError, StreamCreateError, StreamDeleteError, StreamStatsError, SuperStreamQueryError,
+    },
+    types::{
+        ByteCapacity, LeaderLocator, Message, MetricsCollector, MetricsContext,
+        OffsetSpecification, ResponseCode, StreamEvent,
+    },
+    Environment,
+};
+
+use crate::common::{TestEnvironment, TestSuperStream};
 
 #[tokio::test(flavor = "multi_thread")]
 async fn environment_create_test() {
     let _ = TestEnvironment::create().await;
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_create_unknown_virtual_host_test() {
+    let virtual_host: String = Faker.fake();
+    let result = Environment::builder()
+        .virtual_host(&virtual_host)
+        .build()
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ClientError::Handshake {
+            step: "open",
+            code: ResponseCode::VirtualHostAccessFailure,
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_create_stream_with_retention_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+
+    env.stream_creator()
+        .max_length(ByteCapacity::GB(5))
+        .max_segment_size(ByteCapacity::MB(500))
+        .max_age(Duration::from_secs(3600))
+        .create(&stream)
+        .await
+        .unwrap();
+
+    env.delete_stream(&stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_create_stream_with_placement_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+
+    env.stream_creator()
+        .leader_locator(LeaderLocator::Balanced)
+        .initial_cluster_size(1)
+        .create(&stream)
+        .await
+        .unwrap();
+
+    let metadata = env.stream_metadata(vec![stream.clone()]).await.unwrap();
+    assert!(metadata[&stream].replicas.is_empty());
+    env.delete_stream(&stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_create_stream_with_filter_size_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+
+    let result = env.stream_creator().filter_size(8).create(&stream).await;
+    assert!(matches!(
+        result,
+        Err(StreamCreateError::InvalidArgument {
+            argument: "stream-filter-size-bytes",
+            ..
+        })
+    ));
+
+    env.stream_creator()
+        .filter_size(64)
+        .create(&stream)
+        .await
+        .unwrap();
+    env.delete_stream(&stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_delete_stream_error_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+
+    let response = env.delete_stream(&stream).await;
+
+    assert!(matches!(
+        response,
+        Err(StreamDeleteError::Delete {
+            status: ResponseCode::StreamDoesNotExist,
+            ..
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_stream_exists_test() {
+    let env = TestEnvironment::create().await;
+    let missing: String = Faker.fake();
+
+    assert!(env.env.stream_exists(&env.stream).await.unwrap());
+    assert!(!env.env.stream_exists(&missing).await.unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_stream_metadata_test() {
+    let env = TestEnvironment::create().await;
+
+    let metadata = env
+        .env
+        .stream_metadata(vec![env.stream.clone()])
+        .await
+        .unwrap();
+
+    let stream_metadata = metadata.get(&env.stream).unwrap();
+    assert_eq!(ResponseCode::Ok, stream_metadata.response_code);
+    assert_eq!(5552, stream_metadata.leader.port);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_stream_stats_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+    assert!(confirmation.confirmed());
+    producer.close().await.unwrap();
+
+    let stats = env.env.stream_stats(&env.stream).await.unwrap();
+
+    assert_eq!(Some(0), stats.first_offset());
+    assert_eq!(Some(0), stats.committed_chunk_id());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_stream_stats_error_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+
+    let response = env.stream_stats(&stream).await;
+
+    assert!(matches!(
+        response,
+        Err(StreamStatsError::Stats {
+            status: ResponseCode::StreamDoesNotExist,
+            ..
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_create_super_stream_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let super_stream: String = Faker.fake();
+
+    env.super_stream_creator()
+        .partitions(2)
+        .max_age(Duration::from_secs(3600))
+        .create(&super_stream)
+        .await
+        .unwrap();
+
+    for partition in 0..2 {
+        let partition = format!("{}-{}", super_stream, partition);
+        assert!(env.stream_exists(&partition).await.unwrap());
+    }
+
+    env.delete_super_stream(&super_stream).await.unwrap();
+
+    assert!(!env
+        .stream_exists(&format!("{}-0", super_stream))
+        .await
+        .unwrap());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_create_super_stream_with_binding_keys_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let super_stream: String = Faker.fake();
+
+    env.super_stream_creator()
+        .binding_keys(vec!["amer".to_owned(), "emea".to_owned()])
+        .create(&super_stream)
+        .await
+        .unwrap();
+
+    for key in &["amer", "emea"] {
+        let partition = format!("{}-{}", super_stream, key);
+        assert!(env.stream_exists(&partition).await.unwrap());
+    }
+
+    env.delete_super_stream(&super_stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_consumer_and_producer_defaults_test() {
+    let env = Environment::builder()
+        .producer_confirm_timeout(Duration::from_secs(5))
+        .consumer_offset(OffsetSpecification::First)
+        .consumer_initial_credits(5)
+        .build()
+        .await
+        .unwrap();
+    let stream: String = Faker.fake();
+    env.stream_creator().create(&stream).await.unwrap();
+
+    let producer = env.producer().build(&stream).await.unwrap();
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    assert!(confirmation.confirmed());
+
+    // offset inherited from the environment, messages published before subscribing are read
+    let mut consumer = env.consumer().build(&stream).await.unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"message".as_ref()), delivery.message.data());
+
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+    env.delete_stream(&stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_replay_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..3)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    let replay = env
+        .env
+        .replay(&env.stream)
+        .from(SystemTime::now() - Duration::from_secs(60))
+        .until(SystemTime::now())
+        .build()
+        .await
+        .unwrap();
+
+    // written after the replay started
+    producer
+        .send_with_confirm(Message::builder().body("message3").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let offsets: Vec<u64> = tokio::time::timeout(
+        Duration::from_secs(10),
+        replay.map(|delivery| delivery.unwrap().offset).collect(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(vec![0, 1, 2], offsets);
+
+    let replay = env
+        .env
+        .replay(&env.stream)
+        .from(SystemTime::now() + Duration::from_secs(60))
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(0, replay.count().await);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_query_super_stream_test() {
+    let env = TestSuperStream::create(3).await;
+
+    assert_eq!(
+        env.partitions,
+        env.env.query_partitions(&env.super_stream).await.unwrap()
+    );
+    assert_eq!(
+        vec![env.partitions[1].clone()],
+        env.env.query_route(&env.super_stream, "1").await.unwrap()
+    );
+    assert!(env
+        .env
+        .query_route(&env.super_stream, "unknown")
+        .await
+        .unwrap()
+        .is_empty());
+
+    let result = env.env.query_partitions("missing-super-stream").await;
+    assert!(matches!(
+        result,
+        Err(SuperStreamQueryError::Query {
+            status: ResponseCode::StreamDoesNotExist,
+            ..
+        })
+    ));
+}
+
+#[derive(Default)]
+struct CountingCollector {
+    published: AtomicU64,
+    confirmed: AtomicU64,
+    consumed: AtomicU64,
+    lag: AtomicU64,
+    written_bytes: AtomicU64,
+}
+
+impl MetricsCollector for CountingCollector {
+    fn publish(&self, context: &MetricsContext<'_>, messages: u64) {
+        assert!(context.stream.is_some());
+        self.published.fetch_add(messages, Ordering::Relaxed);
+    }
+
+    fn publish_confirm(&self, _context: &MetricsContext<'_>, messages: u64) {
+        self.confirmed.fetch_add(messages, Ordering::Relaxed);
+    }
+
+    fn consume(&self, _context: &MetricsContext<'_>, messages: u64) {
+        self.consumed.fetch_add(messages, Ordering::Relaxed);
+    }
+
+    fn lag(&self, _context: &MetricsContext<'_>, messages: u64) {
+        self.lag.store(messages, Ordering::Relaxed);
+    }
+
+    fn written_bytes(&self, context: &MetricsContext<'_>, bytes: u64) {
+        assert_eq!("/", context.vhost);
+        self.written_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_metrics_collector_test() {
+    let collector = Arc::new(CountingCollector::default());
+    let env = Environment::builder()
+        .metrics_collector(collector.clone())
+        .build()
+        .await
+        .unwrap();
+    let stream: String = Faker.fake();
+    env.stream_creator().create(&stream).await.unwrap();
+
+    let producer = env.producer().build(&stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(vec![Message::builder().body("message").build(); 3])
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&stream)
+        .await
+        .unwrap();
+    consumer.next().await.unwrap().unwrap();
+    assert_eq!(2, consumer.lag().await.unwrap());
+    assert_eq!(2, collector.lag.load(Ordering::Relaxed));
+    for _ in 0..2 {
+        consumer.next().await.unwrap().unwrap();
+    }
+    consumer.handle().close().await.unwrap();
+
+    assert_eq!(3, collector.published.load(Ordering::Relaxed));
+    assert_eq!(3, collector.confirmed.load(Ordering::Relaxed));
+    assert_eq!(3, collector.consumed.load(Ordering::Relaxed));
+    assert!(collector.written_bytes.load(Ordering::Relaxed) > 0);
+
+    env.delete_stream(&stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_stream_counters_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+    env.stream_creator().create(&stream).await.unwrap();
+    assert_eq!(None, env.stream_counters().stream(&stream));
+
+    let producer = env.producer().build(&stream).await.unwrap();
+    producer
+        .batch_send_with_confirm(vec![Message::builder().body("message").build(); 3])
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&stream)
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        consumer.next().await.unwrap().unwrap();
+    }
+    consumer.handle().close().await.unwrap();
+
+    let counters = env.stream_counters().stream(&stream).unwrap();
+    assert_eq!(3, counters.published);
+    assert_eq!(3, counters.confirmed);
+    assert_eq!(3, counters.consumed);
+    assert!(counters.chunks > 0);
+    assert!(counters.published_bytes > 0);
+    assert!(counters.delivered_bytes > 0);
+    assert!(env.stream_counters().snapshot().contains_key(&stream));
+
+    env.delete_stream(&stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_events_test() {
+    let env = Environment::builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+    env.stream_creator().create(&stream).await.unwrap();
+
+    let mut events = env.events();
+    let consumer = env.consumer().build(&stream).await.unwrap();
+    env.delete_stream(&stream).await.unwrap();
+
+    let mut deleted = false;
+    let mut metadata_update = false;
+    while !(deleted && metadata_update) {
+        match tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            StreamEvent::StreamDeleted {
+                stream: deleted_stream,
+            } => {
+                assert_eq!(stream, deleted_stream);
+                deleted = true;
+            }
+            StreamEvent::MetadataUpdate {
+                stream: updated_stream,
+                code,
+            } => {
+                assert_eq!(stream, updated_stream);
+                assert_eq!(ResponseCode::StreamNotAvailable, code);
+                metadata_update = true;
+            }
+            _ => {}
+        }
+    }
+
+    // the subscription is gone with the stream
+    let _ = consumer.handle().close().await;
+}
+
+#[cfg(feature = "management")]
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_management_test() {
+    let env = TestEnvironment::create().await;
+    let management = env.env.management().unwrap();
+
+    let streams = management.streams().await.unwrap();
+    assert!(streams.iter().any(|stream| stream.name == env.stream));
+
+    let stream = management.stream(&env.stream).await.unwrap().unwrap();
+    assert_eq!(env.stream, stream.name);
+    assert!(stream.leader.is_some());
+    assert!(!stream.members.is_empty());
+    assert!(management.stream("missing").await.unwrap().is_none());
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let connections = management.stream_connections().await.unwrap();
+    assert!(!connections.is_empty());
+    producer.close().await.unwrap();
+}
+
+#[cfg(feature = "management")]
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_management_stream_and_policy_test() {
+    use rabbitmq_stream_client::types::StreamPolicy;
+
+    let env = Environment::builder().build().await.unwrap();
+    let management = env.management().unwrap();
+    let stream: String = Faker.fake();
+    env.stream_creator()
+        .max_length(ByteCapacity::GB(1))
+        .create_with_management(&stream)
+        .await
+        .unwrap();
+    assert!(management.stream(&stream).await.unwrap().is_some());
+
+    let policy: String = Faker.fake();
+    management
+        .set_policy(
+            &policy,
+            &StreamPolicy::new(&format!("^{}$", stream)).max_age(Duration::from_secs(3600)),
+        )
+        .await
+        .unwrap();
+    let applied = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            let info = management.stream(&stream).await.unwrap().unwrap();
+            if info.policy.is_some() {
+                return info.policy;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(Some(policy.clone()), applied);
+
+    management.delete_policy(&policy).await.unwrap();
+    management.delete_stream(&stream).await.unwrap();
+    assert!(management.stream(&stream).await.unwrap().is_none());
+}
+
+#[cfg(feature = "management")]
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_management_discovery_test() {
+    let env = Environment::builder()
+        .management_discovery(true)
+        .build()
+        .await
+        .unwrap();
+    assert!(env.endpoints().iter().any(|(_, port)| *port == 5552));
+
+    let endpoints = env.management().unwrap().stream_endpoints().await.unwrap();
+    assert!(endpoints.iter().all(|endpoint| !endpoint.node.is_empty()));
+
+    let stream: String = Faker.fake();
+    env.stream_creator().create(&stream).await.unwrap();
+    env.delete_stream(&stream).await.unwrap();
+}
+
+/// Wait for the management statistics, refreshed every few seconds, to show an active
+/// consumer and `waiting` other ones in the group
+#[cfg(feature = "management")]
+async fn wait_for_group(
+    management: &rabbitmq_stream_client::ManagementClient,
+    stream: &str,
+    name: &str,
+    waiting: usize,
+) -> rabbitmq_stream_client::types::SingleActiveConsumerGroup {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            let group = management
+                .single_active_consumer_group(stream, name)
+                .await
+                .unwrap();
+            if group.active.is_some() && group.waiting.len() == waiting {
+                return group;
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    })
+    .await
+    .unwrap()
+}
+
+#[cfg(feature = "management")]
+#[tokio::test(flavor = "multi_thread")]
+async fn environment_management_single_active_consumer_group_test() {
+    let env = TestEnvironment::create().await;
+    let management = env.env.management().unwrap();
+    let name: String = Faker.fake();
+    let consumer = || {
+        env.env
+            .consumer()
+            .name(&name)
+            .enable_single_active_consumer(true)
+            .build(&env.stream)
+    };
+    let first = consumer().await.unwrap();
+    let second = consumer().await.unwrap();
+
+    let group = wait_for_group(&management, &env.stream, &name, 1).await;
+    assert_eq!(Some(name.clone()), group.active.unwrap().name);
+
+    first.handle().close().await.unwrap();
+    let group = wait_for_group(&management, &env.stream, &name, 0).await;
+    assert!(group.active.unwrap().active);
+    assert!(second.is_active());
+    second.handle().close().await.unwrap();
+}
@@ -1,5 +1,19 @@
 mod client_test;
 mod common;
 mod consumer_test;
+#[cfg(feature = "testcontainers")]
+mod containers_test;
 mod environment_test;
 mod producer_test;
+#[cfg(feature = "simulation")]
+mod simulation_test;
+mod super_stream_consumer_test;
+mod super_stream_producer_test;
+#[cfg(feature = "test-broker")]
+mod test_broker_test;
+#[cfg(feature = "test-util")]
+mod test_util_test;
+#[cfg(feature = "toxiproxy")]
+mod toxiproxy_test;
+#[cfg(feature = "test-broker")]
+mod wire_replay_test;
@@ -1,6 +1,22 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
 use fake::{Fake, Faker};
 use futures::StreamExt;
-use rabbitmq_stream_client::types::{Message, OffsetSpecification};
+use rabbitmq_stream_client::{
+    error::{ProducerPublishError, StreamPublishError},
+    types::{
+        ByteCapacity, Compression, InterceptorError, Message, MessageBatch, MessageId,
+        OffsetSpecification, OverflowStrategy, ProducerInterceptor, Properties, ResponseCode,
+        Value,
+    },
+    ProducerRecoveryEvent,
+};
 use tokio::sync::mpsc::channel;
 
 use crate::common::TestEnvironment;
@@ -19,8 +35,11 @@ async fn producer_send_no_name_ok() {
         .await
         .unwrap();
 
-    let _ = producer
-        .send(Message::builder().body(b"message".to_vec()).build())
+    producer
+        .send(
+            Message::builder().body(b"message".to_vec()).build(),
+            |_| async {},
+        )
         .await
         .unwrap();
 
@@ -53,24 +72,24 @@ async fn producer_send_name_with_deduplication_ok() {
         .await
         .unwrap();
 
-    let _ = producer
-        .send(Message::builder().body(b"message0".to_vec()).build())
+    producer
+        .send_with_confirm(Message::builder().body(b"message0".to_vec()).build())
         .await
         .unwrap();
 
     // this is not published
-    let _ = producer
-        .send(
+    producer
+        .send_with_confirm(
             Message::builder()
                 .body(b"message0".to_vec())
-                .publising_id(0)
+                .publishing_id(0)
                 .build(),
         )
         .await
         .unwrap();
 
-    let _ = producer
-        .send(Message::builder().body(b"message1".to_vec()).build())
+    producer
+        .send_with_confirm(Message::builder().body(b"message1".to_vec()).build())
         .await
         .unwrap();
 
@@ -100,22 +119,925 @@ async fn producer_send_with_callback() {
         .await
         .unwrap();
 
-    let _ = producer
-        .send_with_callback(
+    producer
+        .send(
+            Message::builder().body(b"message".to_vec()).build(),
+            move |confirm_result| async move {
+                let _ = tx.send(confirm_result).await;
+            },
+        )
+        .await
+        .unwrap();
+
+    let result = rx.recv().await.unwrap().unwrap();
+
+    assert_eq!(1, result.publishing_id());
+    assert!(result.confirmed());
+    assert_eq!(&ResponseCode::Ok, result.status());
+    assert_eq!(Some(b"message".as_ref()), result.message().data());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_batch_send() {
+    let env = TestEnvironment::create().await;
+    let message_count = 250;
+
+    let (tx, mut rx) = channel(message_count);
+    let producer = env
+        .env
+        .producer()
+        .batch_size(100)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let messages = (0..message_count)
+        .map(|i| Message::builder().body(format!("message{}", i)).build())
+        .collect();
+
+    producer
+        .batch_send(messages, move |confirm_result| {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(confirm_result).await;
+            }
+        })
+        .await
+        .unwrap();
+
+    let mut publishing_ids = Vec::with_capacity(message_count);
+    for _ in 0..message_count {
+        let confirmation = rx.recv().await.unwrap().unwrap();
+        assert!(confirmation.confirmed());
+        publishing_ids.push(confirmation.publishing_id());
+    }
+    publishing_ids.sort_unstable();
+    assert_eq!(
+        (0..message_count as u64).collect::<Vec<_>>(),
+        publishing_ids
+    );
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_send_with_confirm() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+
+    assert_eq!(0, confirmation.publishing_id());
+    assert!(confirmation.confirmed());
+
+    let confirmations = producer
+        .batch_send_with_confirm(
+            (0..3)
+                .map(|i| Message::builder().body(format!("message{}", i)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        vec![1, 2, 3],
+        confirmations
+            .iter()
+            .map(|confirmation| confirmation.publishing_id())
+            .collect::<Vec<_>>()
+    );
+    assert!(confirmations
+        .iter()
+        .all(|confirmation| confirmation.confirmed()));
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_flush_accumulated_messages() {
+    let env = TestEnvironment::create().await;
+
+    let (tx, mut rx) = channel(1);
+    let producer = env
+        .env
+        .producer()
+        .batch_size(1000)
+        .batch_publishing_delay(Duration::from_secs(3600))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    producer
+        .send(
+            Message::builder().body(b"message".to_vec()).build(),
+            move |confirm_result| async move {
+                let _ = tx.send(confirm_result).await;
+            },
+        )
+        .await
+        .unwrap();
+
+    // nothing is published before the delay elapses or the accumulator is flushed
+    assert!(tokio::time::timeout(Duration::from_millis(500), rx.recv())
+        .await
+        .is_err());
+
+    producer.flush().await.unwrap();
+
+    let confirmation = rx.recv().await.unwrap().unwrap();
+    assert!(confirmation.confirmed());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_publish_after_batch_publishing_delay() {
+    let env = TestEnvironment::create().await;
+
+    let (tx, mut rx) = channel(1);
+    let producer = env
+        .env
+        .producer()
+        .batch_size(1000)
+        .batch_publishing_delay(Duration::from_millis(50))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    producer
+        .send(
             Message::builder().body(b"message".to_vec()).build(),
-            move |confirm_result| {
-                let inner_tx = tx.clone();
-                async move {
-                    let _ = inner_tx.send(confirm_result).await;
-                }
+            move |confirm_result| async move {
+                let _ = tx.send(confirm_result).await;
             },
         )
         .await
         .unwrap();
 
-    let result = rx.recv().await.unwrap();
+    let confirmation = rx.recv().await.unwrap().unwrap();
+    assert!(confirmation.confirmed());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_name_continues_publishing_sequence() {
+    let env = TestEnvironment::create().await;
+    let reference: String = Faker.fake();
+
+    let producer = env
+        .env
+        .producer()
+        .name(&reference)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    producer
+        .batch_send_with_confirm(vec![
+            Message::builder().body(b"message0".to_vec()).build(),
+            Message::builder().body(b"message1".to_vec()).build(),
+        ])
+        .await
+        .unwrap();
+
+    // the ids of a new reference start at 1
+    assert_eq!(Some(2), producer.last_publishing_id().await.unwrap());
+    producer.close().await.unwrap();
+
+    let producer = env
+        .env
+        .producer()
+        .name(&reference)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body(b"message2".to_vec()).build())
+        .await
+        .unwrap();
+
+    assert_eq!(3, confirmation.publishing_id());
+    assert_eq!(Some(3), producer.last_publishing_id().await.unwrap());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_manual_publishing_id_test() {
+    let env = TestEnvironment::create().await;
+    let reference: String = Faker.fake();
+
+    let producer = env
+        .env
+        .producer()
+        .name(&reference)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body("message").publishing_id(10).build())
+        .await
+        .unwrap();
+    assert_eq!(10, confirmation.publishing_id());
+
+    // the ids assigned by the producer follow the ones set on the messages
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+    assert_eq!(11, confirmation.publishing_id());
+    assert_eq!(Some(11), producer.last_publishing_id().await.unwrap());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_latency_snapshot_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    assert_eq!(0, producer.latency_snapshot().count());
+
+    for _ in 0..10 {
+        producer
+            .send_with_confirm(Message::builder().body("message").build())
+            .await
+            .unwrap();
+    }
+
+    let snapshot = producer.latency_snapshot();
+    assert_eq!(10, snapshot.count());
+    assert!(snapshot.min() <= snapshot.p50());
+    assert!(snapshot.p99() <= snapshot.max());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_without_name_has_no_last_publishing_id() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+
+    assert_eq!(None, producer.last_publishing_id().await.unwrap());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_max_in_flight_fail_fast() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .max_in_flight(1)
+        .overflow_strategy(OverflowStrategy::FailFast)
+        .batch_publishing_delay(Duration::from_secs(3600))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    producer
+        .send(
+            Message::builder().body(b"message0".to_vec()).build(),
+            |_| async {},
+        )
+        .await
+        .unwrap();
+
+    let result = producer
+        .send(
+            Message::builder().body(b"message1".to_vec()).build(),
+            |_| async {},
+        )
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::QueueFull { .. })
+    ));
+
+    producer.flush().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_max_in_flight_drop_oldest() {
+    let env = TestEnvironment::create().await;
+
+    let (tx, mut rx) = channel(2);
+    let producer = env
+        .env
+        .producer()
+        .max_in_flight(1)
+        .overflow_strategy(OverflowStrategy::DropOldest)
+        .batch_publishing_delay(Duration::from_secs(3600))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for i in 0..2 {
+        let tx = tx.clone();
+        producer
+            .send(
+                Message::builder().body(format!("message{}", i)).build(),
+                move |confirm_result| async move {
+                    let _ = tx.send(confirm_result).await;
+                },
+            )
+            .await
+            .unwrap();
+    }
+
+    assert!(matches!(
+        rx.recv().await.unwrap(),
+        Err(ProducerPublishError::Dropped {
+            publishing_id: 0,
+            ..
+        })
+    ));
+
+    producer.flush().await.unwrap();
+    let confirmation = rx.recv().await.unwrap().unwrap();
+    assert_eq!(1, confirmation.publishing_id());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_sub_entry_batching_with_compression() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .sub_entry_size(10)
+        .compression(Compression::Gzip)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let confirmations = producer
+        .batch_send_with_confirm(
+            (0..25)
+                .map(|i| Message::builder().body(format!("message{}", i)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(25, confirmations.len());
+    assert!(confirmations
+        .iter()
+        .all(|confirmation| confirmation.confirmed()));
+
+    for i in 0..25 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(
+            Some(format!("message{}", i).as_bytes()),
+            delivery.message.data()
+        );
+    }
+
+    producer.close().await.unwrap();
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_publish_error_handler() {
+    let env = TestEnvironment::create().await;
+    let stream: String = Faker.fake();
+    env.env.stream_creator().create(&stream).await.unwrap();
+
+    let (tx, mut rx) = channel(1);
+    let producer = env
+        .env
+        .producer()
+        .on_publish_error(move |publishing_id, error| {
+            let _ = tx.try_send((publishing_id, error));
+        })
+        .build(&stream)
+        .await
+        .unwrap();
+
+    env.env.delete_stream(&stream).await.unwrap();
+
+    producer
+        .send(
+            Message::builder().body(b"message".to_vec()).build(),
+            |_| async {},
+        )
+        .await
+        .unwrap();
+    producer.flush().await.unwrap();
+
+    let (publishing_id, error) = rx.recv().await.unwrap();
+    assert_eq!(0, publishing_id);
+    assert!(matches!(
+        error,
+        StreamPublishError::PublisherDoesNotExist | StreamPublishError::StreamDoesNotExist
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_close_waits_for_confirms() {
+    let env = TestEnvironment::create().await;
+
+    let (tx, mut rx) = channel(100);
+    let producer = env
+        .env
+        .producer()
+        .batch_publishing_delay(Duration::from_secs(3600))
+        .close_timeout(Duration::from_secs(5))
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for i in 0..10 {
+        let tx = tx.clone();
+        producer
+            .send(
+                Message::builder().body(format!("message{}", i)).build(),
+                move |confirm_result| async move {
+                    let _ = tx.send(confirm_result).await;
+                },
+            )
+            .await
+            .unwrap();
+    }
+    drop(tx);
+
+    producer.clone().close().await.unwrap();
+
+    // every callback already ran when close returns
+    let mut confirmed = 0;
+    while let Ok(confirmation) = rx.try_recv() {
+        assert!(confirmation.unwrap().confirmed());
+        confirmed += 1;
+    }
+    assert_eq!(10, confirmed);
+
+    assert!(matches!(
+        producer
+            .send(Message::builder().body("late").build(), |_| async {})
+            .await,
+        Err(ProducerPublishError::Closed)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_as_sink() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .max_in_flight(5)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    futures::stream::iter(0..20)
+        .map(|i| Ok(Message::builder().body(format!("message{}", i)).build()))
+        .forward(producer.clone())
+        .await
+        .unwrap();
+
+    assert!(producer.is_closed());
+
+    for i in 0..20 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(
+            Some(format!("message{}", i).as_bytes()),
+            delivery.message.data()
+        );
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_filter_value_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .filter_value_extractor(|message| {
+            let data = message.data().unwrap();
+            match data[data.len() - 1] % 2 {
+                0 => "even".to_owned(),
+                _ => "odd".to_owned(),
+            }
+        })
+        .build(&env.stream)
+        .await
+        .unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .filter_values(vec!["odd".to_owned()], |message| {
+            message
+                .data()
+                .is_some_and(|data| data[data.len() - 1] % 2 == 1)
+        })
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    for n in (1..10).step_by(2) {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_send_message_sections_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let properties = Properties {
+        message_id: Some(MessageId::String("order-1".to_owned())),
+        content_type: Some("text/plain".to_owned()),
+        ..Default::default()
+    };
+    producer
+        .send_with_confirm(
+            Message::builder()
+                .body("message")
+                .properties(properties.clone())
+                .application_property("region", "emea")
+                .message_annotation("x-opt-source", "billing")
+                .build(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"message".as_ref()), delivery.message.data());
+    assert_eq!(Some(properties), delivery.message.properties());
+    assert_eq!(
+        Some(Value::String("emea".to_owned())),
+        delivery.message.application_property("region")
+    );
+    assert_eq!(
+        Some(Value::String("billing".to_owned())),
+        delivery.message.message_annotation("x-opt-source")
+    );
+
+    consumer.handle().close().await.unwrap();
+}
 
-    assert_eq!(0, result.unwrap());
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_stamp_times_test() {
+    let env = TestEnvironment::create().await;
 
+    let producer = env
+        .env
+        .producer()
+        .stamp_creation_time()
+        .stamp_publish_time()
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let creation_time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let before = SystemTime::now() - Duration::from_millis(1);
+    producer
+        .batch_send_with_confirm(vec![
+            Message::builder().body("message0").build(),
+            Message::builder()
+                .body("message1")
+                .creation_time(creation_time)
+                .build(),
+        ])
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert!(delivery.message.creation_time().unwrap() >= before);
+    assert!(matches!(
+        delivery.message.message_annotation("x-opt-publish-time"),
+        Some(Value::Timestamp(_))
+    ));
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(creation_time), delivery.message.creation_time());
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_generate_message_ids_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .generate_message_ids()
+        .build(&env.stream)
+        .await
+        .unwrap();
+    producer
+        .batch_send_with_confirm(vec![
+            Message::builder().body("message0").build(),
+            Message::builder()
+                .body("message1")
+                .message_id("order-1")
+                .build(),
+        ])
+        .await
+        .unwrap();
     producer.close().await.unwrap();
+
+    let counter = Arc::new(AtomicU64::new(100));
+    let producer = env
+        .env
+        .producer()
+        .message_id_generator(move || MessageId::Ulong(counter.fetch_add(1, Ordering::Relaxed)))
+        .build(&env.stream)
+        .await
+        .unwrap();
+    producer
+        .send_with_confirm(Message::builder().body("message2").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert!(matches!(
+        delivery.message.message_id(),
+        Some(MessageId::Uuid(_))
+    ));
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(
+        Some(MessageId::String("order-1".to_owned())),
+        delivery.message.message_id()
+    );
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(MessageId::Ulong(100)), delivery.message.message_id());
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_max_message_size_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .max_message_size(ByteCapacity::KB(1))
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let result = producer
+        .send_with_confirm(Message::builder().body(vec![0u8; 2000]).build())
+        .await;
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::MessageSizeLimit { size, limit: 1000 }) if size > 2000
+    ));
+
+    producer
+        .send_with_confirm(Message::builder().body(vec![0u8; 500]).build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_batch_send_message_batch_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let batch = MessageBatch::builder()
+        .content_type("text/plain")
+        .bodies((0..10).map(|i| format!("message{}", i)))
+        .build();
+    producer
+        .batch_send_with_confirm(batch.into())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    for i in 0..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(Some("text/plain"), delivery.message.content_type());
+        assert_eq!(
+            Some(format!("message{}", i).as_bytes()),
+            delivery.message.data()
+        );
+    }
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_message_too_large_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env.env.producer().build(&env.stream).await.unwrap();
+    let result = producer
+        .batch_send_with_confirm(vec![
+            Message::builder().body("small").build(),
+            Message::builder().body(vec![0u8; 2 * 1024 * 1024]).build(),
+        ])
+        .await;
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::MessageTooLarge { size, max: 1048576 }) if size > 2 * 1024 * 1024
+    ));
+
+    // nothing of the rejected batch is published
+    producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(&b"message"[..]), delivery.message.data());
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_recovery_test() {
+    let env = TestEnvironment::create().await;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let producer = env
+        .env
+        .producer()
+        .on_recovery(move |event| {
+            let _ = tx.send(event);
+        })
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    // the producer pauses while the stream is missing and declares itself again
+    // once the stream exists again
+    env.env.delete_stream(&env.stream).await.unwrap();
+    assert!(matches!(
+        rx.recv().await.unwrap(),
+        ProducerRecoveryEvent::Started { .. }
+    ));
+    env.env.stream_creator().create(&env.stream).await.unwrap();
+
+    let resent = tokio::time::timeout(Duration::from_secs(15), async {
+        loop {
+            if let ProducerRecoveryEvent::Recovered { resent, .. } = rx.recv().await.unwrap() {
+                return resent;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(0, resent);
+
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    assert!(confirmation.confirmed());
+
+    producer.close().await.unwrap();
+}
+
+struct Uppercase;
+
+impl ProducerInterceptor for Uppercase {
+    fn on_send(&self, _stream: &str, message: Message) -> Result<Message, InterceptorError> {
+        let body = message.data().unwrap_or_default().to_ascii_uppercase();
+        Ok(Message::builder().body(body).build())
+    }
+}
+
+struct RejectEmpty;
+
+impl ProducerInterceptor for RejectEmpty {
+    fn on_send(&self, _stream: &str, message: Message) -> Result<Message, InterceptorError> {
+        match message.data() {
+            Some(data) if !data.is_empty() => Ok(message),
+            _ => Err("empty message".into()),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn producer_interceptor_test() {
+    let env = TestEnvironment::create().await;
+
+    let producer = env
+        .env
+        .producer()
+        .interceptor(RejectEmpty)
+        .interceptor(Uppercase)
+        .build(&env.stream)
+        .await
+        .unwrap();
+
+    let result = producer
+        .send_with_confirm(Message::builder().body(b"".to_vec()).build())
+        .await;
+    assert!(matches!(
+        result,
+        Err(ProducerPublishError::Interceptor { .. })
+    ));
+
+    producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&env.stream)
+        .await
+        .unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"MESSAGE".as_ref()), delivery.message.data());
+
+    consumer.handle().close().await.unwrap();
 }
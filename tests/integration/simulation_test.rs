@@ -0,0 +1,179 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    simulation::Simulation,
+    types::{Message, OffsetSpecification, StreamDeletedPolicy},
+    ConsumerRecoveryEvent, ProducerRecoveryEvent,
+};
+
+/// Simulated times of the recovery attempts of a producer during an outage
+async fn outage_timeline(seed: u64) -> Vec<(Duration, bool)> {
+    let simulation = Simulation::new(seed);
+    let environment = simulation.environment().build().await.unwrap();
+    environment.stream_creator().create("orders").await.unwrap();
+
+    let timeline = Arc::new(Mutex::new(Vec::new()));
+    let attempts = timeline.clone();
+    let clock = simulation.clone();
+    let producer = environment
+        .producer()
+        .name("producer")
+        .on_recovery(move |event| match event {
+            ProducerRecoveryEvent::Failed { .. } => {
+                attempts.lock().unwrap().push((clock.elapsed(), false))
+            }
+            ProducerRecoveryEvent::Recovered { .. } => {
+                attempts.lock().unwrap().push((clock.elapsed(), true))
+            }
+            ProducerRecoveryEvent::Started { .. } | ProducerRecoveryEvent::GaveUp { .. } => {}
+        })
+        .build("orders")
+        .await
+        .unwrap();
+
+    simulation.outage(Duration::from_secs(30)).await;
+    simulation.advance(Duration::from_secs(10)).await;
+    producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let timeline = timeline.lock().unwrap().clone();
+    timeline
+}
+
+#[tokio::test(start_paused = true)]
+async fn simulation_outage_replay_test() {
+    let timeline = outage_timeline(7).await;
+
+    // 5 seconds plus up to 1 second between the attempts
+    assert!(timeline.len() >= 5);
+    assert_eq!(Some(&true), timeline.last().map(|(_, recovered)| recovered));
+    assert!(timeline
+        .windows(2)
+        .all(|pair| pair[1].0 - pair[0].0 >= Duration::from_secs(5)
+            && pair[1].0 - pair[0].0 <= Duration::from_secs(6)));
+
+    assert_eq!(timeline, outage_timeline(7).await);
+    assert_ne!(timeline, outage_timeline(8).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn simulation_reconnect_storm_test() {
+    let simulation = Simulation::new(1);
+    let environment = simulation.environment().build().await.unwrap();
+    environment.stream_creator().create("orders").await.unwrap();
+    let producer = environment
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    let attempts = simulation.broker().connection_attempts();
+
+    simulation
+        .reconnect_storm(10, Duration::from_millis(100))
+        .await;
+    producer
+        .send_with_confirm(Message::builder().body("message").build())
+        .await
+        .unwrap();
+
+    // every reconnection looks up the leader of the stream first
+    assert_eq!(attempts + 2 * 10, simulation.broker().connection_attempts());
+    assert_eq!(1, simulation.broker().messages("orders").unwrap().len());
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn simulation_single_active_consumer_handover_test() {
+    let simulation = Simulation::new(1);
+    let environment = simulation.environment().build().await.unwrap();
+    environment.stream_creator().create("orders").await.unwrap();
+    let producer = environment.producer().build("orders").await.unwrap();
+    for n in 0..2 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let consumer = |offset: OffsetSpecification| {
+        environment
+            .consumer()
+            .name("group")
+            .offset(offset)
+            .enable_single_active_consumer(true)
+            .consumer_update_listener(|context| async move {
+                match context.query_offset().await {
+                    Ok(offset) => OffsetSpecification::Offset(offset + 1),
+                    Err(_) => OffsetSpecification::First,
+                }
+            })
+            .build("orders")
+    };
+    let mut active = consumer(OffsetSpecification::First).await.unwrap();
+    let mut standby = consumer(OffsetSpecification::First).await.unwrap();
+
+    let first = active.next().await.unwrap().unwrap();
+    assert_eq!(0, first.offset);
+    active.handle().store_offset(0).await.unwrap();
+    simulation.advance(Duration::from_secs(1)).await;
+    active.handle().close().await.unwrap();
+
+    // the standby consumer takes over after the stored offset
+    let delivery = tokio::time::timeout(Duration::from_secs(10), standby.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(1, delivery.offset);
+    assert_eq!(Some(&b"message1"[..]), delivery.message.data());
+    standby.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn simulation_recreated_stream_test() {
+    let simulation = Simulation::new(1);
+    let environment = simulation.environment().build().await.unwrap();
+    environment.stream_creator().create("orders").await.unwrap();
+
+    let recovered = Arc::new(Mutex::new(false));
+    let recovery = recovered.clone();
+    // the consumer would end with the default policy once the stream is deleted
+    let mut consumer = environment
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .on_stream_deleted(StreamDeletedPolicy::recreate(environment.stream_creator()))
+        .on_recovery(move |event| {
+            if let ConsumerRecoveryEvent::Recovered { .. } = event {
+                *recovery.lock().unwrap() = true;
+            }
+        })
+        .build("orders")
+        .await
+        .unwrap();
+
+    simulation
+        .recreate_stream("orders", Duration::from_secs(20))
+        .await;
+    simulation.advance(Duration::from_secs(10)).await;
+    assert!(*recovered.lock().unwrap());
+
+    let producer = environment.producer().build("orders").await.unwrap();
+    producer
+        .send_with_confirm(Message::builder().body("after").build())
+        .await
+        .unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(0, delivery.offset);
+    assert_eq!(Some(&b"after"[..]), delivery.message.data());
+    consumer.handle().close().await.unwrap();
+    producer.close().await.unwrap();
+}
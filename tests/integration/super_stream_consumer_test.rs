@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+
+use fake::{Fake, Faker};
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    error::ConsumerStoreOffsetError,
+    types::{Message, OffsetSpecification},
+};
+
+use crate::common::TestSuperStream;
+
+fn routing_key(message: &Message) -> String {
+    String::from_utf8(message.data().unwrap().to_vec()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_consumer_consumes_all_partitions() {
+    let test = TestSuperStream::create(3).await;
+    let message_count = 30;
+
+    let producer = test
+        .env
+        .super_stream_producer(routing_key)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..message_count)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = test
+        .env
+        .super_stream_consumer()
+        .offset(OffsetSpecification::First)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+    assert_eq!(test.partitions, consumer.partitions());
+
+    let mut bodies = HashSet::new();
+    let mut per_partition: HashMap<String, usize> = HashMap::new();
+    for _ in 0..message_count {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert!(test.partitions.iter().any(|p| p == delivery.stream()));
+        *per_partition
+            .entry(delivery.stream().to_owned())
+            .or_default() += 1;
+        bodies.insert(routing_key(&delivery.message));
+    }
+
+    assert_eq!(message_count, bodies.len());
+    assert!(per_partition.len() > 1);
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_consumer_tracks_offsets_per_partition() {
+    let test = TestSuperStream::create(2).await;
+    let name: String = Faker.fake();
+
+    let consumer = test
+        .env
+        .super_stream_consumer()
+        .name(&name)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+
+    consumer.store_offset(&test.partitions[0], 3).await.unwrap();
+    consumer.store_offset(&test.partitions[1], 7).await.unwrap();
+
+    assert_eq!(3, consumer.query_offset(&test.partitions[0]).await.unwrap());
+    assert_eq!(7, consumer.query_offset(&test.partitions[1]).await.unwrap());
+    assert!(matches!(
+        consumer.store_offset("unknown", 1).await,
+        Err(ConsumerStoreOffsetError::PartitionNotFound(_))
+    ));
+
+    consumer.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_consumer_single_active_consumer_spreads_partitions() {
+    let test = TestSuperStream::create(3).await;
+    let name: String = Faker.fake();
+
+    let build = || {
+        test.env
+            .super_stream_consumer()
+            .name(&name)
+            .enable_single_active_consumer(true)
+            .build(&test.super_stream)
+    };
+
+    let first = build().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(test.partitions, first.active_partitions());
+
+    let second = build().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let first_active = first.active_partitions();
+    let second_active = second.active_partitions();
+    assert!(!first_active.is_empty());
+    assert!(!second_active.is_empty());
+    assert_eq!(
+        test.partitions.len(),
+        first_active.len() + second_active.len()
+    );
+    assert!(first_active.iter().all(|p| !second_active.contains(p)));
+
+    first.handle().close().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(test.partitions, second.active_partitions());
+
+    second.handle().close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_consumer_workers_process_partitions_in_order() {
+    let test = TestSuperStream::create(3).await;
+    let message_count = 30;
+
+    let producer = test
+        .env
+        .super_stream_producer(routing_key)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..message_count)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let consumer = test
+        .env
+        .super_stream_consumer()
+        .offset(OffsetSpecification::First)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+    let slow = test.partitions[0].clone();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let workers = consumer.process(3, move |delivery| {
+        let (tx, slow) = (tx.clone(), slow.clone());
+        async move {
+            let delivery = delivery.unwrap();
+            if delivery.stream() == slow {
+                // the other partitions are not stalled by this one
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            }
+            let _ = tx.send((delivery.stream().to_owned(), delivery.offset));
+        }
+    });
+    assert_eq!(3, workers.assignments().len());
+    assert!(workers
+        .assignments()
+        .iter()
+        .all(|partitions| partitions.len() == 1));
+
+    let mut offsets: HashMap<String, Vec<u64>> = HashMap::new();
+    let mut first_partitions = Vec::new();
+    for _ in 0..message_count {
+        let (partition, offset) = rx.recv().await.unwrap();
+        if first_partitions.len() < 2 {
+            first_partitions.push(partition.clone());
+        }
+        offsets.entry(partition).or_default().push(offset);
+    }
+    assert!(!first_partitions.contains(&test.partitions[0]));
+    for partition_offsets in offsets.values() {
+        assert!(partition_offsets.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    workers.stop().await.unwrap();
+}
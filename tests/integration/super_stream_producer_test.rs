@@ -0,0 +1,227 @@
+use std::time::Duration;
+
+use fake::{Fake, Faker};
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    error::ProducerPublishError,
+    types::{Message, OffsetSpecification, RoutingStrategy},
+    Environment,
+};
+
+use crate::common::TestSuperStream;
+
+fn routing_key(message: &Message) -> String {
+    String::from_utf8(message.data().unwrap().to_vec()).unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_producer_routes_by_hash() {
+    let test = TestSuperStream::create(3).await;
+
+    let producer = test
+        .env
+        .super_stream_producer(routing_key)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+
+    assert_eq!(test.partitions, producer.partitions().await.unwrap());
+
+    let messages: Vec<Message> = (0..30)
+        .map(|i| Message::builder().body(format!("key{}", i)).build())
+        .collect();
+    let mut expected_partitions: Vec<String> = Vec::new();
+    for message in &messages {
+        let partitions = producer.partitions_of(message).await.unwrap();
+        assert_eq!(1, partitions.len());
+        expected_partitions.push(partitions[0].clone());
+    }
+
+    let confirmations = producer.batch_send_with_confirm(messages).await.unwrap();
+    assert_eq!(30, confirmations.len());
+    for (i, confirmation) in confirmations.iter().enumerate() {
+        assert!(confirmation.confirmed());
+        assert_eq!(
+            Some(format!("key{}", i).as_bytes()),
+            confirmation.message().data()
+        );
+    }
+
+    producer.close().await.unwrap();
+
+    for partition in &test.partitions {
+        let expected = expected_partitions
+            .iter()
+            .filter(|expected| *expected == partition)
+            .count();
+        if expected == 0 {
+            continue;
+        }
+
+        let mut consumer = test
+            .env
+            .consumer()
+            .offset(OffsetSpecification::First)
+            .build(partition)
+            .await
+            .unwrap();
+        for _ in 0..expected {
+            let delivery = consumer.next().await.unwrap().unwrap();
+            let key = routing_key(&delivery.message);
+            let position: usize = key.trim_start_matches("key").parse().unwrap();
+            assert_eq!(partition, &expected_partitions[position]);
+        }
+        consumer.handle().close().await.unwrap();
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_producer_routes_by_binding_key() {
+    let env = Environment::builder().build().await.unwrap();
+    let super_stream: String = Faker.fake();
+    env.super_stream_creator()
+        .binding_keys(vec!["amer".to_owned(), "emea".to_owned()])
+        .create(&super_stream)
+        .await
+        .unwrap();
+
+    let producer = env
+        .super_stream_producer(routing_key)
+        .key_routing()
+        .build(&super_stream)
+        .await
+        .unwrap();
+
+    let emea = format!("{}-emea", super_stream);
+    assert_eq!(
+        vec![emea],
+        producer
+            .partitions_of(&Message::builder().body("emea").build())
+            .await
+            .unwrap()
+    );
+
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body("amer").build())
+        .await
+        .unwrap();
+    assert!(confirmation.confirmed());
+
+    assert!(matches!(
+        producer
+            .send_with_confirm(Message::builder().body("apac").build())
+            .await,
+        Err(ProducerPublishError::NoRoute { .. })
+    ));
+
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build(&format!("{}-amer", super_stream))
+        .await
+        .unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(b"amer".as_ref()), delivery.message.data());
+    consumer.handle().close().await.unwrap();
+
+    env.delete_super_stream(&super_stream).await.unwrap();
+}
+
+struct FirstPartition;
+
+impl RoutingStrategy for FirstPartition {
+    fn route(&self, _message: &Message, partitions: &[String]) -> Vec<String> {
+        partitions.iter().take(1).cloned().collect()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_producer_custom_routing_strategy() {
+    let test = TestSuperStream::create(2).await;
+
+    let producer = test
+        .env
+        .super_stream_producer(routing_key)
+        .routing_strategy(FirstPartition)
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+
+    let message = Message::builder().body("key").build();
+    assert_eq!(
+        vec![test.partitions[0].clone()],
+        producer.partitions_of(&message).await.unwrap()
+    );
+    assert!(producer
+        .send_with_confirm(message)
+        .await
+        .unwrap()
+        .confirmed());
+
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_producer_refreshes_partitions_on_metadata_update() {
+    let env = Environment::builder().build().await.unwrap();
+    let super_stream: String = Faker.fake();
+    env.super_stream_creator()
+        .partitions(3)
+        .create(&super_stream)
+        .await
+        .unwrap();
+
+    let producer = env
+        .super_stream_producer(routing_key)
+        .build(&super_stream)
+        .await
+        .unwrap();
+    assert_eq!(3, producer.partitions().await.unwrap().len());
+
+    // deleting a partition removes its binding and notifies its producer
+    env.delete_stream(&format!("{}-2", super_stream))
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert_eq!(
+        vec![format!("{}-0", super_stream), format!("{}-1", super_stream)],
+        producer.partitions().await.unwrap()
+    );
+    for i in 0..10 {
+        let confirmation = producer
+            .send_with_confirm(Message::builder().body(format!("key{}", i)).build())
+            .await
+            .unwrap();
+        assert!(confirmation.confirmed());
+    }
+
+    producer.close().await.unwrap();
+    let _ = env.delete_super_stream(&super_stream).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn super_stream_producer_refreshes_partitions_periodically() {
+    let test = TestSuperStream::create(2).await;
+
+    let producer = test
+        .env
+        .super_stream_producer(routing_key)
+        .partitions_refresh_interval(Duration::from_millis(100))
+        .build(&test.super_stream)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    // the partitions are queried again before the send, the producers are kept
+    assert!(producer
+        .send_with_confirm(Message::builder().body("key").build())
+        .await
+        .unwrap()
+        .confirmed());
+    assert_eq!(test.partitions, producer.partitions().await.unwrap());
+
+    producer.close().await.unwrap();
+}
@@ -0,0 +1,1576 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    error::{
+        ClientError, ConsumerCreateError, ConsumerDeliveryError, OffsetStoreError,
+        ProducerCreateError, ProducerPublishError, ReplicatorError, StreamCreateError,
+        StreamDeleteError, StreamStatsError,
+    },
+    test_broker::TestBroker,
+    types::{
+        BrokerCloseAction, BrokerClosePolicy, ByteCapacity, CircuitBreaker, DedupStatus, Fault,
+        FaultInjector, FaultRule, FileOffsetStore, FrameDirection, Message, NoRetry,
+        OffsetSpecification, OverflowStrategy, PoisonMessageHandling, RateLimiter, ResponseCode,
+        RetryPolicy, StreamDeletedPolicy, StreamEvent, Timeouts, Value,
+    },
+    ConsumerHandle, ConsumerRecoveryEvent, Environment, Producer, ProducerRecoveryEvent,
+};
+
+async fn environment(broker: &TestBroker) -> Environment {
+    Environment::builder()
+        .test_broker(broker.clone())
+        .build()
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_publish_consume_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    assert!(env.stream_exists("orders").await.unwrap());
+
+    let producer = env
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    for n in 0..10 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+    producer.close().await.unwrap();
+    assert_eq!(10, broker.messages("orders").unwrap().len());
+
+    let mut consumer = env
+        .consumer()
+        .name("consumer")
+        .offset(OffsetSpecification::Offset(5))
+        .build("orders")
+        .await
+        .unwrap();
+    // every publish was written in its own chunk, delivered in the order of the stream
+    for n in 5..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+        assert_eq!(
+            format!("message{}", n).as_bytes(),
+            delivery.message.data().unwrap()
+        );
+    }
+
+    let handle = consumer.handle();
+    handle.store_offset(9).await.unwrap();
+    assert_eq!(9, handle.query_offset().await.unwrap());
+    assert_eq!(Some(9), broker.stored_offset("orders", "consumer"));
+    handle.close().await.unwrap();
+
+    env.delete_stream("orders").await.unwrap();
+    assert!(broker.streams().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[allow(deprecated)]
+async fn test_broker_send_with_callback_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env.producer().build("orders").await.unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    for n in 0..5 {
+        let tx = tx.clone();
+        producer
+            .send_with_callback(
+                Message::builder().body(format!("message{}", n)).build(),
+                move |publishing_id| async move {
+                    // a slow callback does not reorder the ones queued after it
+                    if n == 0 {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    let _ = tx.send(publishing_id.unwrap());
+                },
+            )
+            .await
+            .unwrap();
+    }
+    producer.flush().await.unwrap();
+    for n in 0..5 {
+        assert_eq!(n, rx.recv().await.unwrap());
+    }
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_deduplication_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    producer
+        .send_with_confirm(Message::builder().publishing_id(1).body("first").build())
+        .await
+        .unwrap();
+    producer
+        .send_with_confirm(Message::builder().publishing_id(1).body("again").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let messages = broker.messages("orders").unwrap();
+    assert_eq!(1, messages.len());
+    assert_eq!(Some(&b"first"[..]), messages[0].data());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_resume_after_first_id_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    producer
+        .send_with_confirm(Message::builder().publishing_id(0).body("first").build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    // id 0 is stored, the next producer continues at 1
+    let producer = env
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    let status = producer
+        .send_with_confirm(Message::builder().body("second").build())
+        .await
+        .unwrap();
+    assert_eq!(1, status.publishing_id());
+    producer.close().await.unwrap();
+
+    assert_eq!(2, broker.messages("orders").unwrap().len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_drop_oldest_accumulated_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env
+        .producer()
+        .max_in_flight(1)
+        .overflow_strategy(OverflowStrategy::DropOldest)
+        .batch_publishing_delay(Duration::from_secs(3600))
+        .build("orders")
+        .await
+        .unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    for n in 0..2 {
+        let tx = tx.clone();
+        producer
+            .send(
+                Message::builder().body(format!("message{}", n)).build(),
+                move |status| async move {
+                    let _ = tx.send(status);
+                },
+            )
+            .await
+            .unwrap();
+    }
+    assert!(matches!(
+        rx.recv().await.unwrap(),
+        Err(ProducerPublishError::Dropped {
+            publishing_id: 0,
+            ..
+        })
+    ));
+    producer.flush().await.unwrap();
+    assert_eq!(1, rx.recv().await.unwrap().unwrap().publishing_id());
+    producer.close().await.unwrap();
+
+    // the dropped message was still accumulated, it is not published
+    let messages = broker.messages("orders").unwrap();
+    assert_eq!(1, messages.len());
+    assert_eq!(Some(&b"message1"[..]), messages[0].data());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_concurrent_deduplication_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    // the ids reach the broker in order, none of them is taken for a duplicate
+    let sends = (0..50).map(|n| {
+        let producer = producer.clone();
+        tokio::spawn(async move {
+            producer
+                .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+                .await
+                .unwrap()
+        })
+    });
+    for send in futures::future::join_all(sends).await {
+        assert!(send.unwrap().confirmed());
+    }
+    producer.close().await.unwrap();
+
+    assert_eq!(50, broker.messages("orders").unwrap().len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_offset_management_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    assert_eq!(None, env.query_offset("orders", "billing").await.unwrap());
+    env.store_offset("orders", "billing", 42).await.unwrap();
+    assert_eq!(
+        Some(42),
+        env.query_offset("orders", "billing").await.unwrap()
+    );
+    assert_eq!(Some(42), broker.stored_offset("orders", "billing"));
+
+    assert!(matches!(
+        env.query_offset("missing", "billing").await,
+        Err(OffsetStoreError::QueryOffset {
+            status: ResponseCode::StreamDoesNotExist,
+            ..
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_unsupported_command_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    // the broker closes the connection instead of answering
+    assert!(matches!(
+        env.stream_stats("orders").await,
+        Err(StreamStatsError::Client(ClientError::ClosedByBroker {
+            code: ResponseCode::UnknownFrame,
+            ..
+        }))
+    ));
+}
+
+async fn publish(producer: &Producer, messages: std::ops::Range<u32>) {
+    for n in messages {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+}
+
+async fn wait_for_messages(broker: &TestBroker, stream: &str, count: usize) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while broker.messages(stream).unwrap().len() < count {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_replicator_test() {
+    let source = TestBroker::new();
+    let target = TestBroker::new();
+    let source_env = environment(&source).await;
+    let target_env = environment(&target).await;
+    source_env.stream_creator().create("orders").await.unwrap();
+    target_env.stream_creator().create("orders").await.unwrap();
+    let producer = source_env.producer().build("orders").await.unwrap();
+
+    publish(&producer, 0..5).await;
+    let replicator = source_env
+        .replicator("orders")
+        .target(target_env.clone())
+        .start()
+        .await
+        .unwrap();
+    wait_for_messages(&target, "orders", 5).await;
+    publish(&producer, 5..8).await;
+    wait_for_messages(&target, "orders", 8).await;
+    replicator.stop().await.unwrap();
+
+    // started again, the replicator resumes after the messages stored by the target
+    publish(&producer, 8..10).await;
+    let replicator = source_env
+        .replicator("orders")
+        .target(target_env)
+        .start()
+        .await
+        .unwrap();
+    wait_for_messages(&target, "orders", 10).await;
+    let bodies: Vec<_> = target
+        .messages("orders")
+        .unwrap()
+        .iter()
+        .map(|message| message.data().unwrap().to_vec())
+        .collect();
+    let expected: Vec<_> = (0..10)
+        .map(|n| format!("message{}", n).into_bytes())
+        .collect();
+    assert_eq!(expected, bodies);
+    // the confirmations may come after the messages are stored
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while replicator.last_offset() != Some(9) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(2, replicator.replicated());
+    replicator.stop().await.unwrap();
+
+    assert!(matches!(
+        source_env.replicator("orders").start().await,
+        Err(ReplicatorError::SameStream(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_authentication_failure_test() {
+    let broker = TestBroker::new().credentials("user", "secret");
+    assert!(!format!("{:?}", broker).contains("secret"));
+    let result = Environment::builder().test_broker(broker).build().await;
+
+    assert!(matches!(
+        result,
+        Err(ClientError::Handshake {
+            step: "authentication",
+            code: ResponseCode::AuthenticationFailure,
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_fault_injection_recovery_test() {
+    let broker = TestBroker::new();
+    let faults = FaultInjector::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+
+    // the confirm is lost, the message is published again after the connection is
+    // recovered and deduplicated by the broker
+    faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Drop).key(3));
+    let (confirmed, confirmation) = tokio::sync::oneshot::channel();
+    producer
+        .send(
+            Message::builder().body("message").build(),
+            move |status| async move {
+                let _ = confirmed.send(status.map(|status| status.confirmed()));
+            },
+        )
+        .await
+        .unwrap();
+    while broker.messages("orders").unwrap().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    faults.sever();
+
+    let confirmed = tokio::time::timeout(Duration::from_secs(5), confirmation)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(confirmed.unwrap());
+    assert_eq!(1, broker.messages("orders").unwrap().len());
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_check_chunk_crc_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .check_chunk_crc(true)
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    for n in 0..3 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    for n in 0..3 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+    }
+
+    let mut chunks = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .raw_chunks()
+        .build("orders")
+        .await
+        .unwrap();
+    let chunk = chunks.next().await.unwrap().unwrap();
+    chunk.check_crc().unwrap();
+    assert_ne!(0, chunk.crc());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_uncompressed_sub_entries_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env
+        .producer()
+        .sub_entry_size(10)
+        .build("orders")
+        .await
+        .unwrap();
+    let confirmations = producer
+        .batch_send_with_confirm(
+            (0..25)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    assert!(confirmations
+        .iter()
+        .all(|confirmation| confirmation.confirmed()));
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    for n in 0..25 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(n, delivery.offset);
+        assert_eq!(
+            format!("message{}", n).as_bytes(),
+            delivery.message.data().unwrap()
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_chunk_entries_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+
+    let producer = env
+        .producer()
+        .sub_entry_size(3)
+        .compression(rabbitmq_stream_client::types::Compression::Gzip)
+        .build("orders")
+        .await
+        .unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..7)
+                .map(|n| Message::builder().body(format!("message{}", n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+    // a sub-entry producer packs even a single message
+    let producer = env.producer().build("orders").await.unwrap();
+    producer
+        .send_with_confirm(Message::builder().body("message7").build())
+        .await
+        .unwrap();
+
+    let mut chunks = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .raw_chunks()
+        .build("orders")
+        .await
+        .unwrap();
+    let mut entries = vec![];
+    while entries.len() < 4 {
+        let chunk = chunks.next().await.unwrap().unwrap();
+        for entry in chunk.entries() {
+            entries.push(entry.unwrap());
+        }
+    }
+
+    let offsets: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.offset(), entry.last_offset()))
+        .collect();
+    assert_eq!(vec![(0, 2), (3, 5), (6, 6), (7, 7)], offsets);
+    assert!(entries[..3].iter().all(|entry| entry.is_sub_entry()));
+    assert!(!entries[3].is_sub_entry());
+    assert_eq!(None, entries[3].compression());
+    let bodies: Vec<_> = entries[1]
+        .messages()
+        .unwrap()
+        .iter()
+        .map(|message| message.data().unwrap().to_vec())
+        .collect();
+    assert_eq!(
+        vec![
+            b"message3".to_vec(),
+            b"message4".to_vec(),
+            b"message5".to_vec()
+        ],
+        bodies
+    );
+    assert_eq!(
+        Some(&b"message7"[..]),
+        entries[3].messages().unwrap()[0].data()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_consumer_buffer_size_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .consumer_buffer_size(2)
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    for n in 0..5 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(2, consumer.debug_state().await.pending_deliveries);
+    for n in 0..5 {
+        assert_eq!(n, consumer.next().await.unwrap().unwrap().offset);
+    }
+
+    let consumer = env
+        .consumer()
+        .buffer_size(3)
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(3, consumer.debug_state().await.pending_deliveries);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_producer_rate_limiter_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    let limiter = RateLimiter::new().messages_per_second(20);
+    let producer = env
+        .producer()
+        .rate_limiter(limiter.clone())
+        .build("orders")
+        .await
+        .unwrap();
+
+    // the first 20 messages empty the bucket, the next 5 wait for a quarter of a second
+    let start = std::time::Instant::now();
+    for n in 0..25 {
+        producer
+            .send_with_confirm(Message::builder().body(format!("message{}", n)).build())
+            .await
+            .unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    limiter.set_messages_per_second(None);
+    let start = std::time::Instant::now();
+    let messages = (0..100)
+        .map(|n| Message::builder().body(format!("message{}", n)).build())
+        .collect();
+    producer.batch_send_with_confirm(messages).await.unwrap();
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_connection_sharing_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .producers_per_connection(2)
+        .consumers_per_connection(2)
+        .build()
+        .await
+        .unwrap();
+    for stream in ["a", "b", "c"] {
+        env.stream_creator().create(stream).await.unwrap();
+    }
+    let connections = broker.connection_attempts();
+
+    // the third producer does not fit on the connection of the first two, each one looks
+    // up the leader of its stream on a connection of its own first
+    let mut producers = Vec::new();
+    for stream in ["a", "b", "c"] {
+        producers.push(env.producer().build(stream).await.unwrap());
+    }
+    assert_eq!(connections + 3 + 2, broker.connection_attempts());
+    let first = producers[0].debug_state().await;
+    let second = producers[1].debug_state().await;
+    assert_eq!((1, 2), (first.publisher_id, second.publisher_id));
+    assert_eq!(2, second.connection.publishers.len());
+    assert_eq!(
+        1,
+        producers[2].debug_state().await.connection.publishers.len()
+    );
+    for (producer, stream) in producers.iter().zip(["a", "b", "c"]) {
+        producer
+            .send_with_confirm(Message::builder().body(stream).build())
+            .await
+            .unwrap();
+    }
+
+    // the deliveries reach the consumer of their subscription, the consumers look up their
+    // stream as well
+    let mut consumers = Vec::new();
+    for stream in ["a", "b", "c"] {
+        let consumer = env
+            .consumer()
+            .offset(OffsetSpecification::First)
+            .build(stream)
+            .await
+            .unwrap();
+        consumers.push(consumer);
+    }
+    assert_eq!(connections + 5 + 3 + 2, broker.connection_attempts());
+    for (consumer, stream) in consumers.iter_mut().zip(["a", "b", "c"]) {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(Some(stream.as_bytes()), delivery.message.data());
+    }
+
+    // a closed producer leaves its place to the next one
+    producers.remove(0).close().await.unwrap();
+    let producer = env.producer().build("a").await.unwrap();
+    assert_eq!(connections + 10 + 1, broker.connection_attempts());
+    assert_eq!(1, producer.debug_state().await.publisher_id);
+    producer
+        .send_with_confirm(Message::builder().body("a").build())
+        .await
+        .unwrap();
+    assert_eq!(2, broker.messages("a").unwrap().len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_shared_connection_recovery_test() {
+    let broker = TestBroker::new();
+    let faults = FaultInjector::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .producers_per_connection(2)
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("a").await.unwrap();
+    env.stream_creator().create("b").await.unwrap();
+    let first = env.producer().build("a").await.unwrap();
+    let second = env.producer().build("b").await.unwrap();
+    let connections = broker.connection_attempts();
+
+    // both producers move to a single new connection, keeping their publisher id, after
+    // looking up the leader
+    faults.sever();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while broker.connection_attempts() == connections
+            || first.debug_state().await.recovering
+            || second.debug_state().await.recovering
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    for (producer, stream) in [(&first, "a"), (&second, "b")] {
+        producer
+            .send_with_confirm(Message::builder().body(stream).build())
+            .await
+            .unwrap();
+    }
+    assert_eq!(connections + 2 + 1, broker.connection_attempts());
+    let state = second.debug_state().await;
+    assert_eq!(2, state.publisher_id);
+    assert_eq!(2, state.connection.publishers.len());
+    assert_eq!(1, broker.messages("b").unwrap().len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_consumer_max_buffered_bytes_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .consumer_max_buffered_bytes(ByteCapacity::B(2500))
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    for n in 0..5u8 {
+        producer
+            .send_with_confirm(Message::builder().body(vec![n; 1000]).build())
+            .await
+            .unwrap();
+    }
+
+    // the third message does not fit in the buffer
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let state = consumer.debug_state().await;
+    assert_eq!(2, state.pending_deliveries);
+    assert!(state.pending_bytes.unwrap() > 2000);
+    for n in 0..5u8 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(Some(&[n; 1000][..]), delivery.message.data());
+    }
+    assert_eq!(Some(0), consumer.debug_state().await.pending_bytes);
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_zstd_dictionary_test() {
+    use rabbitmq_stream_client::types::{Compression, ZstdDictionary};
+
+    let body = |n: u32| format!("{{\"order\":{},\"status\":\"shipped\"}}", n);
+    let samples: Vec<_> = (0..1000).map(|n| body(n).into_bytes()).collect();
+    let dictionary = ZstdDictionary::train(&samples, 1024).unwrap();
+
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env
+        .producer()
+        .sub_entry_size(5)
+        .zstd_dictionary(dictionary.clone())
+        .build("orders")
+        .await
+        .unwrap();
+    producer
+        .batch_send_with_confirm(
+            (0..10)
+                .map(|n| Message::builder().body(body(n)).build())
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .zstd_dictionary(dictionary)
+        .build("orders")
+        .await
+        .unwrap();
+    for n in 0..10 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(body(n).as_bytes(), delivery.message.data().unwrap());
+    }
+
+    let mut chunks = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .raw_chunks()
+        .build("orders")
+        .await
+        .unwrap();
+    let chunk = chunks.next().await.unwrap().unwrap();
+    let entry = chunk.entries().next().unwrap().unwrap();
+    assert_eq!(Some(Compression::Zstd), entry.compression());
+    assert_eq!(5, entry.messages().unwrap().len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_cycle_connections_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .producers_per_connection(1)
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producers = vec![
+        env.producer().build("orders").await.unwrap(),
+        env.producer().build("orders").await.unwrap(),
+    ];
+    let mut consumer = env.consumer().build("orders").await.unwrap();
+    let mut events = env.events();
+    let attempts = broker.connection_attempts();
+
+    // the two producer connections and the consumer one, closed one after the other, each
+    // one looks up its stream before reconnecting
+    assert_eq!(3, env.cycle_connections(Duration::from_millis(20)).await);
+    let mut recovered = 0;
+    while recovered < 3 {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        if matches!(
+            event,
+            StreamEvent::ProducerRecovery(ProducerRecoveryEvent::Recovered { .. })
+                | StreamEvent::ConsumerRecovery(ConsumerRecoveryEvent::Recovered { .. })
+        ) {
+            recovered += 1;
+        }
+    }
+    assert_eq!(attempts + 3 + 3, broker.connection_attempts());
+
+    for producer in &producers {
+        producer
+            .send_with_confirm(Message::builder().body("message").build())
+            .await
+            .unwrap();
+    }
+    for _ in 0..2 {
+        let delivery = consumer.next().await.unwrap().unwrap();
+        assert_eq!(Some(&b"message"[..]), delivery.message.data());
+    }
+    // nothing left to cycle once the producers and consumer are closed
+    for producer in producers {
+        producer.close().await.unwrap();
+    }
+    consumer.handle().close().await.unwrap();
+    assert_eq!(0, env.cycle_connections(Duration::ZERO).await);
+}
+
+/// Attempts with a fixed delay and a limit
+struct Attempts(u32);
+
+impl RetryPolicy for Attempts {
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.0)
+    }
+
+    fn delay(&self, _attempt: u32) -> Duration {
+        Duration::from_millis(10)
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_retry_policy_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .retry_policy(Attempts(3))
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+
+    broker.stop();
+    let attempts = broker.connection_attempts();
+    assert!(env.stream_exists("orders").await.is_err());
+    assert_eq!(attempts + 3, broker.connection_attempts());
+
+    // the errors answered by the broker are not attempted again
+    broker.start();
+    let attempts = broker.connection_attempts();
+    assert!(env.stream_creator().create("orders").await.is_err());
+    assert_eq!(attempts + 1, broker.connection_attempts());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_recovery_gave_up_test() {
+    let broker = TestBroker::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .recovery_policy(Attempts(2))
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    let mut events = env.events();
+
+    broker.stop();
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        if let StreamEvent::ProducerRecovery(ProducerRecoveryEvent::GaveUp { stream, .. }) = event {
+            assert_eq!("orders", stream);
+            break;
+        }
+    }
+    assert!(producer.is_closed());
+    assert!(matches!(
+        producer
+            .send_with_confirm(Message::builder().body("message").build())
+            .await,
+        Err(ProducerPublishError::Closed)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_circuit_breaker_test() {
+    let broker = TestBroker::new();
+    let breaker = CircuitBreaker::new()
+        .failure_threshold(2)
+        .cool_down(Duration::from_millis(200));
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .circuit_breaker(breaker.clone())
+        .build()
+        .await
+        .unwrap();
+
+    broker.stop();
+    assert!(env.stream_exists("orders").await.is_err());
+    assert!(env.stream_exists("orders").await.is_err());
+    assert_eq!(vec!["localhost:5552"], breaker.open_nodes());
+
+    // the node is skipped without connecting while the circuit is open
+    broker.start();
+    let attempts = broker.connection_attempts();
+    assert!(matches!(
+        env.stream_exists("orders").await,
+        Err(ClientError::CircuitOpen { node }) if node == "localhost:5552"
+    ));
+    assert_eq!(attempts, broker.connection_attempts());
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!env.stream_exists("orders").await.unwrap());
+    assert!(breaker.open_nodes().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_timeouts_test() {
+    let broker = TestBroker::new();
+    let faults = FaultInjector::new();
+    let timeouts = Timeouts::new()
+        .handshake(Duration::from_millis(200))
+        .rpc(Duration::from_millis(200));
+
+    // the response to open is lost
+    faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Drop).key(0x8015));
+    let result = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .timeouts(timeouts.rpc(Duration::from_secs(5)))
+        .build()
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::Timeout {
+            operation: "handshake",
+            ..
+        })
+    ));
+
+    // the response to the stream creation is lost
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .timeouts(timeouts)
+        .build()
+        .await
+        .unwrap();
+    faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Drop).key(0x800d));
+    let result = env.stream_creator().create("orders").await;
+    assert!(matches!(
+        result,
+        Err(StreamCreateError::Client(ClientError::Timeout {
+            operation: "request",
+            ..
+        }))
+    ));
+    env.stream_creator().create("other").await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_request_close_failed_test() {
+    let broker = TestBroker::new();
+    let faults = FaultInjector::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .timeouts(Timeouts::new().rpc(Duration::from_millis(200)))
+        .retry_policy(Attempts(3))
+        .build()
+        .await
+        .unwrap();
+
+    // the response to the close is lost, the stream is not created again
+    faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Drop).key(0x8016));
+    let attempts = broker.connection_attempts();
+    env.stream_creator().create("orders").await.unwrap();
+    assert_eq!(attempts + 1, broker.connection_attempts());
+    assert_eq!(vec!["orders"], broker.streams());
+}
+
+/// Consumer failing on `message1`, at least once with a single attempt per message, the
+/// offsets handled go to `handled`
+async fn at_least_once_consumer(
+    env: &Environment,
+    poison_messages: Option<PoisonMessageHandling>,
+    handled: tokio::sync::mpsc::UnboundedSender<u64>,
+) -> ConsumerHandle {
+    let mut builder = env
+        .consumer()
+        .name("billing")
+        .offset(OffsetSpecification::First);
+    if let Some(poison_messages) = poison_messages {
+        builder = builder.poison_messages(poison_messages);
+    }
+    builder
+        .try_message_handler(move |context, message| {
+            let handled = handled.clone();
+            async move {
+                match message.data() {
+                    Some(b"message1") => Err("failed"),
+                    _ => {
+                        let _ = handled.send(context.offset());
+                        Ok(())
+                    }
+                }
+            }
+        })
+        .at_least_once(NoRetry)
+        .start("orders")
+        .await
+        .unwrap()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_at_least_once_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    publish(&producer, 0..3).await;
+
+    // the failed message is not parked, the consumer closes storing the offset before it
+    let (handled, mut handled_offsets) = tokio::sync::mpsc::unbounded_channel();
+    let handle = at_least_once_consumer(&env, None, handled.clone()).await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !handle.is_closed().await || broker.stored_offset("orders", "billing").is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(Some(0), handled_offsets.recv().await);
+    assert_eq!(Some(0), broker.stored_offset("orders", "billing"));
+
+    // parked, the offset moves past it
+    let (parked, mut parked_offsets) = tokio::sync::mpsc::unbounded_channel();
+    let handle = at_least_once_consumer(
+        &env,
+        Some(PoisonMessageHandling::callback(move |poison| {
+            let _ = parked.send(poison.offset());
+            async {}
+        })),
+        handled,
+    )
+    .await;
+    assert_eq!(Some(1), parked_offsets.recv().await);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while handled_offsets.recv().await != Some(2) {}
+    })
+    .await
+    .unwrap();
+    handle
+        .close_gracefully(Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(Some(2), broker.stored_offset("orders", "billing"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_dedup_producer_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("lines").await.unwrap();
+    let line = |line: u64| {
+        Message::builder()
+            .body(format!("line{}", line))
+            .application_property("line", line)
+            .build()
+    };
+    let dedup_producer = || {
+        env.producer()
+            .name("import")
+            .deduplicate(|message| match message.application_property("line") {
+                Some(Value::Ulong(line)) => line,
+                _ => 0,
+            })
+            .build("lines")
+    };
+
+    let producer = dedup_producer().await.unwrap();
+    let statuses = producer
+        .batch_send_with_confirm((1..=3).map(line).collect())
+        .await
+        .unwrap();
+    assert!(statuses.iter().all(|status| !status.is_duplicate()));
+    producer.close().await.unwrap();
+
+    // the replayed range resumes after the last line published
+    let producer = dedup_producer().await.unwrap();
+    assert_eq!(Some(3), producer.last_sequence().await);
+    let statuses = producer
+        .batch_send_with_confirm((1..=5).map(line).collect())
+        .await
+        .unwrap();
+    let duplicates: Vec<bool> = statuses.iter().map(DedupStatus::is_duplicate).collect();
+    assert_eq!(vec![true, true, true, false, false], duplicates);
+    assert!(producer
+        .send_with_confirm(line(4))
+        .await
+        .unwrap()
+        .is_duplicate());
+    producer.close().await.unwrap();
+    assert_eq!(5, broker.messages("lines").unwrap().len());
+
+    assert!(matches!(
+        env.producer().deduplicate(|_| 1).build("lines").await,
+        Err(ProducerCreateError::NameMissing)
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_dedup_producer_failed_test() {
+    let broker = TestBroker::new();
+    let faults = FaultInjector::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("lines").await.unwrap();
+    let producer = env
+        .producer()
+        .name("import")
+        .confirm_timeout(Duration::from_millis(200))
+        .deduplicate(|message| match message.application_property("line") {
+            Some(Value::Ulong(line)) => line,
+            _ => 0,
+        })
+        .build("lines")
+        .await
+        .unwrap();
+    let line = || {
+        Message::builder()
+            .body("line1")
+            .application_property("line", 1u64)
+            .build()
+    };
+
+    // the publish frame is lost, the line is not skipped when it is replayed
+    faults.inject(FaultRule::new(FrameDirection::Outbound, Fault::Drop).key(2));
+    assert!(producer.send_with_confirm(line()).await.is_err());
+    assert_eq!(None, producer.last_sequence().await);
+    let status = producer.send_with_confirm(line()).await.unwrap();
+    assert!(matches!(status, DedupStatus::Published(status) if status.confirmed()));
+    assert_eq!(Some(1), producer.last_sequence().await);
+    assert_eq!(1, broker.messages("lines").unwrap().len());
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_file_offset_store_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    publish(&producer, 0..3).await;
+    let path = std::env::temp_dir().join(format!("file-offset-store-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let consumer = |offsets: FileOffsetStore| {
+        env.consumer()
+            .name("billing")
+            .offset_store(offsets)
+            .start_from_stored_offset_or(OffsetSpecification::First)
+            .build("orders")
+    };
+    let mut first = consumer(FileOffsetStore::open(&path).unwrap())
+        .await
+        .unwrap();
+    let delivery = first.next().await.unwrap().unwrap();
+    first.handle().store_offset(delivery.offset).await.unwrap();
+    first.handle().close().await.unwrap();
+
+    // a restart resumes after the offset of the file, the broker has none
+    let mut second = consumer(FileOffsetStore::open(&path).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(1, second.next().await.unwrap().unwrap().offset);
+    assert_eq!(None, broker.stored_offset("orders", "billing"));
+    second.handle().close().await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_stream_deleted_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    publish(&producer, 0..3).await;
+    producer.close().await.unwrap();
+
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        consumer.next().await.unwrap().unwrap();
+    }
+
+    // the deleted stream ends the deliveries instead of recovering
+    env.delete_stream("orders").await.unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+        .await
+        .unwrap();
+    assert!(matches!(
+        next,
+        Some(Err(ConsumerDeliveryError::StreamDeleted { stream })) if stream == "orders"
+    ));
+    assert!(consumer.next().await.is_none());
+    assert!(consumer.is_closed());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_stream_deleted_recreate_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("sessions").await.unwrap();
+    let producer = env.producer().build("sessions").await.unwrap();
+    publish(&producer, 0..3).await;
+    producer.close().await.unwrap();
+
+    let mut events = env.events();
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .on_stream_deleted(StreamDeletedPolicy::recreate(env.stream_creator()))
+        .build("sessions")
+        .await
+        .unwrap();
+    for _ in 0..3 {
+        consumer.next().await.unwrap().unwrap();
+    }
+
+    env.delete_stream("sessions").await.unwrap();
+    let mut deleted = false;
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match event {
+            StreamEvent::ConsumerRecovery(ConsumerRecoveryEvent::StreamDeleted { stream }) => {
+                assert_eq!("sessions", stream);
+                deleted = true;
+            }
+            StreamEvent::ConsumerRecovery(ConsumerRecoveryEvent::Recovered { offset, .. }) => {
+                assert!(deleted);
+                assert_eq!(OffsetSpecification::First, offset);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    // the messages of the new stream are consumed from its first offset
+    let producer = env.producer().build("sessions").await.unwrap();
+    publish(&producer, 3..5).await;
+    for expected in 0..2 {
+        let delivery = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, delivery.offset);
+    }
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_exact_resume_test() {
+    let broker = TestBroker::new();
+    let faults = FaultInjector::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    publish(&producer, 0..20).await;
+
+    // the handler waits for room in the buffer when the connection is lost
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .buffer_size(2)
+        .build("orders")
+        .await
+        .unwrap();
+    for expected in 0..5 {
+        assert_eq!(expected, consumer.next().await.unwrap().unwrap().offset);
+    }
+    faults.sever();
+
+    // neither replayed nor skipped after the recovery
+    for expected in 5..20 {
+        let delivery = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, delivery.offset);
+    }
+    publish(&producer, 20..22).await;
+    for expected in 20..22 {
+        let delivery = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, delivery.offset);
+    }
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_invalid_names_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+
+    let result = env.stream_creator().create("amq.orders").await;
+    assert!(matches!(
+        result,
+        Err(StreamCreateError::Client(ClientError::InvalidName {
+            kind: "stream",
+            ..
+        }))
+    ));
+    assert!(broker.messages("amq.orders").is_none());
+
+    let result = env.consumer().build(&"x".repeat(256)).await;
+    assert!(matches!(
+        result,
+        Err(ConsumerCreateError::Client(ClientError::InvalidName { .. }))
+    ));
+    let result = env.delete_super_stream("").await;
+    assert!(matches!(
+        result,
+        Err(StreamDeleteError::Client(ClientError::InvalidName {
+            kind: "super stream",
+            ..
+        }))
+    ));
+
+    let result = Environment::builder()
+        .test_broker(broker.clone())
+        .virtual_host("prod\0")
+        .build()
+        .await;
+    assert!(matches!(
+        result,
+        Err(ClientError::InvalidName {
+            kind: "virtual host",
+            ..
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_close_recover_test() {
+    let broker = TestBroker::new();
+    let env = environment(&broker).await;
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    let mut consumer = env
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    publish(&producer, 0..2).await;
+    let mut events = env.events();
+
+    // recovered on new connections by default
+    broker.close_connections(ResponseCode::InternalError, "node drained");
+    let mut recovered = 0;
+    while recovered < 2 {
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        match event {
+            StreamEvent::ProducerRecovery(ProducerRecoveryEvent::Recovered { .. })
+            | StreamEvent::ConsumerRecovery(ConsumerRecoveryEvent::Recovered { .. }) => {
+                recovered += 1
+            }
+            _ => {}
+        }
+    }
+    publish(&producer, 2..3).await;
+    for expected in 0..3 {
+        let delivery = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(expected, delivery.offset);
+    }
+    producer.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broker_close_fail_test() {
+    let broker = TestBroker::new();
+    let (closes, mut closed) = tokio::sync::mpsc::unbounded_channel();
+    let faults = FaultInjector::new();
+    let env = Environment::builder()
+        .test_broker(broker.clone())
+        .fault_injector(faults.clone())
+        .on_broker_close(BrokerClosePolicy::decide(move |close| {
+            let _ = closes.send(close.clone());
+            match close.code {
+                ResponseCode::AccessRefused => BrokerCloseAction::Fail,
+                _ => BrokerCloseAction::Recover,
+            }
+        }))
+        .build()
+        .await
+        .unwrap();
+    env.stream_creator().create("orders").await.unwrap();
+    let producer = env.producer().build("orders").await.unwrap();
+    let mut consumer = env.consumer().build("orders").await.unwrap();
+
+    // a message waiting for its confirm when the connection closes
+    faults.inject(FaultRule::new(FrameDirection::Inbound, Fault::Drop).key(3));
+    let (confirmed, confirmation) = tokio::sync::oneshot::channel();
+    producer
+        .send(
+            Message::builder().body("message").build(),
+            move |status| async move {
+                let _ = confirmed.send(status.map(|status| status.confirmed()));
+            },
+        )
+        .await
+        .unwrap();
+    wait_for_messages(&broker, "orders", 1).await;
+    consumer.next().await.unwrap().unwrap();
+
+    broker.close_connections(ResponseCode::AccessRefused, "permissions changed");
+    for _ in 0..2 {
+        let close = tokio::time::timeout(Duration::from_secs(5), closed.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ResponseCode::AccessRefused, close.code);
+        assert_eq!("permissions changed", close.reason);
+    }
+
+    // the deliveries end with the code and reason of the broker
+    let next = tokio::time::timeout(Duration::from_secs(5), consumer.next())
+        .await
+        .unwrap();
+    match next {
+        Some(Err(ConsumerDeliveryError::Client(error))) => {
+            assert_eq!(Some(&ResponseCode::AccessRefused), error.response_code());
+            assert!(error.to_string().contains("permissions changed"));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(consumer.next().await.is_none());
+
+    let status = tokio::time::timeout(Duration::from_secs(5), confirmation)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        status,
+        Err(ProducerPublishError::Client(ClientError::ClosedByBroker {
+            code: ResponseCode::AccessRefused,
+            ..
+        }))
+    ));
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !producer.is_closed() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(
+        producer
+            .send_with_confirm(Message::builder().body("message").build())
+            .await,
+        Err(ProducerPublishError::Closed)
+    ));
+}
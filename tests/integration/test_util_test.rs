@@ -0,0 +1,231 @@
+use std::time::SystemTime;
+
+use rabbitmq_stream_client::{
+    error::{ClientError, ConsumerDeliveryError, ProducerPublishError},
+    test_util::{
+        deliver_chunk, delivery, full_message, publish_confirm, publish_confirm_frame,
+        FakeConsumer, FakeProducer,
+    },
+    types::{CapturedFrame, FrameDirection, Message, ResponseCode, ResponseKind, Value},
+    MessageConsumer, MessageProducer,
+};
+
+/// Forward the messages with an uppercase body and store the offset of each forwarded one
+async fn forward(
+    consumer: &mut impl MessageConsumer,
+    producer: &impl MessageProducer,
+    count: usize,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut forwarded = 0;
+    while forwarded < count {
+        let delivery = match consumer.next_delivery().await {
+            Some(delivery) => delivery?,
+            None => break,
+        };
+        let body = delivery
+            .message
+            .data()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        let status = producer
+            .send_with_confirm(Message::builder().body(body).build())
+            .await?;
+        if !status.confirmed() {
+            return Err(format!("rejected with {:?}", status.status()).into());
+        }
+        consumer.store_offset(delivery.offset).await?;
+        forwarded += 1;
+    }
+    Ok(forwarded)
+}
+
+#[tokio::test]
+async fn fake_producer_and_consumer_test() {
+    let mut consumer = FakeConsumer::new("orders");
+    consumer.set_next_offset(10);
+    assert_eq!(10, consumer.push(Message::builder().body("a").build()));
+    assert_eq!(11, consumer.push(Message::builder().body("b").build()));
+    let producer = FakeProducer::new();
+
+    assert_eq!(2, forward(&mut consumer, &producer, 5).await.unwrap());
+    let messages = producer.messages();
+    assert_eq!(Some(&b"A"[..]), messages[0].data());
+    assert_eq!(Some(&b"B"[..]), messages[1].data());
+    assert_eq!(Some(&1), messages[1].publishing_id());
+    assert_eq!(Some(11), consumer.stored_offset());
+    assert_eq!(0, consumer.pending());
+}
+
+#[tokio::test]
+async fn fake_producer_failures_test() {
+    let producer = FakeProducer::new();
+    producer.reject(ResponseCode::PublisherDoesNotExist);
+    let status = producer
+        .send_with_confirm(Message::builder().body("a").build())
+        .await
+        .unwrap();
+    assert!(!status.confirmed());
+    assert_eq!(&ResponseCode::PublisherDoesNotExist, status.status());
+    assert!(producer.messages().is_empty());
+
+    producer.accept();
+    let statuses = producer
+        .batch_send_with_confirm(vec![
+            Message::builder().body("b").publishing_id(5).build(),
+            Message::builder().body("c").build(),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![5, 6],
+        statuses
+            .iter()
+            .map(|status| status.publishing_id())
+            .collect::<Vec<_>>()
+    );
+
+    producer.close();
+    assert!(matches!(
+        producer
+            .send_with_confirm(Message::builder().body("d").build())
+            .await,
+        Err(ProducerPublishError::Closed)
+    ));
+    assert_eq!(2, producer.messages().len());
+}
+
+#[tokio::test]
+async fn fake_consumer_error_test() {
+    let mut consumer = FakeConsumer::new("orders");
+    consumer.push(Message::builder().body("a").build());
+    consumer.push_error(ConsumerDeliveryError::Client(ClientError::AlreadyClosed));
+    let producer = FakeProducer::new();
+
+    assert!(forward(&mut consumer, &producer, 5).await.is_err());
+    assert_eq!(1, producer.messages().len());
+    assert_eq!(Some(0), consumer.stored_offset());
+}
+
+#[cfg(feature = "test-broker")]
+#[tokio::test(flavor = "multi_thread")]
+async fn real_producer_and_consumer_test() {
+    use rabbitmq_stream_client::{
+        test_broker::TestBroker, types::OffsetSpecification, Environment,
+    };
+
+    let environment = Environment::builder()
+        .test_broker(TestBroker::new())
+        .build()
+        .await
+        .unwrap();
+    environment.stream_creator().create("source").await.unwrap();
+    environment.stream_creator().create("target").await.unwrap();
+    let source = environment.producer().build("source").await.unwrap();
+    source
+        .send_with_confirm(Message::builder().body("a").build())
+        .await
+        .unwrap();
+
+    let mut consumer = environment
+        .consumer()
+        .name("forwarder")
+        .offset(OffsetSpecification::First)
+        .build("source")
+        .await
+        .unwrap();
+    let producer = environment.producer().build("target").await.unwrap();
+    assert_eq!(1, forward(&mut consumer, &producer, 1).await.unwrap());
+    assert_eq!(0, consumer.query_offset().await.unwrap());
+}
+
+#[test]
+fn deliver_chunk_test() {
+    let chunk = deliver_chunk(3, 100)
+        .timestamp(1_700_000_000_000)
+        .epoch(2)
+        .message(full_message())
+        .message(Message::builder().body("second").build());
+
+    let frame = CapturedFrame {
+        direction: FrameDirection::Inbound,
+        connection: 1,
+        timestamp: SystemTime::now(),
+        frame: chunk.frame(),
+    };
+    assert_eq!(chunk.response(), frame.decode_response(false).unwrap());
+
+    let deliveries = chunk.deliveries("orders");
+    assert_eq!(
+        vec![100, 101],
+        deliveries
+            .iter()
+            .map(|delivery| delivery.offset)
+            .collect::<Vec<_>>()
+    );
+    let second = &deliveries[1];
+    assert_eq!("orders", second.stream());
+    assert_eq!(3, second.subscription_id);
+    assert_eq!(100, second.chunk().first_offset());
+    assert_eq!(101, second.chunk().last_offset());
+    assert_eq!(2, second.chunk().epoch());
+    assert_eq!(Some(&b"second"[..]), second.message.data());
+
+    let single = delivery("orders", 7, Message::builder().body("a").build());
+    assert_eq!(7, single.chunk().first_offset());
+}
+
+#[test]
+fn publish_confirm_test() {
+    let frame = CapturedFrame {
+        direction: FrameDirection::Inbound,
+        connection: 1,
+        timestamp: SystemTime::now(),
+        frame: publish_confirm_frame(1, vec![4, 5]),
+    };
+    let response = frame.decode_response(false).unwrap();
+    assert_eq!(publish_confirm(1, vec![4, 5]), response);
+    match response.kind() {
+        ResponseKind::PublishConfirm(confirm) => assert_eq!(vec![4, 5], confirm.publishing_ids),
+        kind => panic!("unexpected response {:?}", kind),
+    }
+}
+
+#[test]
+fn full_message_test() {
+    let message = full_message();
+    let properties = message.properties().unwrap();
+    assert_eq!(Some(7), properties.group_sequence);
+    assert_eq!(
+        Some("reply-to-group-id"),
+        properties.reply_to_group_id.as_deref()
+    );
+    assert_eq!(18, message.application_properties().len());
+    assert_eq!(18, message.message_annotations().len());
+    assert_eq!(Some(Value::Char('λ')), message.application_property("char"));
+
+    // every section survives the encoding
+    let encoded = deliver_chunk(0, 0).message(message.clone()).command();
+    let frame = CapturedFrame {
+        direction: FrameDirection::Inbound,
+        connection: 1,
+        timestamp: SystemTime::now(),
+        frame: deliver_chunk(0, 0).message(message).frame(),
+    };
+    match frame.decode_response(false).unwrap().kind() {
+        ResponseKind::Deliver(deliver) => {
+            let decoded = &deliver.messages[0];
+            let expected = &encoded.messages[0];
+            assert_eq!(expected.properties(), decoded.properties());
+            assert_eq!(
+                expected.application_properties(),
+                decoded.application_properties()
+            );
+            assert_eq!(
+                expected.message_annotations(),
+                decoded.message_annotations()
+            );
+            assert_eq!(expected.data(), decoded.data());
+        }
+        kind => panic!("unexpected response {:?}", kind),
+    }
+}
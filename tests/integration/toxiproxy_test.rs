@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use fake::{Fake, Faker};
+use rabbitmq_stream_client::{
+    toxiproxy::{Proxy, Toxic, ToxicDirection, Toxiproxy, DEFAULT_URL},
+    types::Message,
+    Environment, ProducerRecoveryEvent,
+};
+use tokio::time::Instant;
+
+/// Proxy to the broker, the toxiproxy server running on the same host as the broker
+/// (`make toxiproxy-server`)
+async fn create_proxy(port: u16) -> (Proxy, Environment, String) {
+    let toxiproxy = Toxiproxy::new(DEFAULT_URL).unwrap();
+    let name: String = Faker.fake();
+    let proxy = toxiproxy
+        .create_proxy(&name, &format!("0.0.0.0:{}", port), "localhost:5552")
+        .await
+        .unwrap();
+    let environment = proxy.environment_builder().build().await.unwrap();
+    let stream: String = Faker.fake();
+    environment.stream_creator().create(&stream).await.unwrap();
+    (proxy, environment, stream)
+}
+
+async fn delete_stream(stream: &str) {
+    let environment = Environment::builder().build().await.unwrap();
+    environment.delete_stream(stream).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn toxiproxy_latency_test() {
+    let (proxy, environment, stream) = create_proxy(25552).await;
+    let producer = environment.producer().build(&stream).await.unwrap();
+
+    proxy
+        .add_toxic(
+            "latency",
+            ToxicDirection::Downstream,
+            Toxic::Latency {
+                latency: Duration::from_millis(300),
+                jitter: Duration::ZERO,
+            },
+        )
+        .await
+        .unwrap();
+    let started = Instant::now();
+    producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+
+    proxy.remove_toxic("latency").await.unwrap();
+    let started = Instant::now();
+    producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    assert!(started.elapsed() < Duration::from_millis(300));
+
+    producer.close().await.unwrap();
+    proxy.delete().await.unwrap();
+    delete_stream(&stream).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn toxiproxy_reset_peer_recovery_test() {
+    let (proxy, environment, stream) = create_proxy(25553).await;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let producer = environment
+        .producer()
+        .on_recovery(move |event| {
+            let _ = tx.send(event);
+        })
+        .build(&stream)
+        .await
+        .unwrap();
+
+    // the connections are reset as soon as data goes through them
+    proxy
+        .add_toxic(
+            "reset",
+            ToxicDirection::Upstream,
+            Toxic::ResetPeer {
+                timeout: Duration::ZERO,
+            },
+        )
+        .await
+        .unwrap();
+    let _ = producer
+        .send(
+            Message::builder().body(b"lost".to_vec()).build(),
+            |_| async {},
+        )
+        .await;
+    assert!(matches!(
+        tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .unwrap()
+            .unwrap(),
+        ProducerRecoveryEvent::Started { .. }
+    ));
+    proxy.remove_toxic("reset").await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while !matches!(
+            rx.recv().await.unwrap(),
+            ProducerRecoveryEvent::Recovered { .. }
+        ) {}
+    })
+    .await
+    .unwrap();
+    let confirmation = producer
+        .send_with_confirm(Message::builder().body(b"message".to_vec()).build())
+        .await
+        .unwrap();
+    assert!(confirmation.confirmed());
+
+    producer.close().await.unwrap();
+    proxy.delete().await.unwrap();
+    delete_stream(&stream).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn toxiproxy_outage_recovery_test() {
+    let (proxy, environment, stream) = create_proxy(25554).await;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let producer = environment
+        .producer()
+        .on_recovery(move |event| {
+            let _ = tx.send(event);
+        })
+        .build(&stream)
+        .await
+        .unwrap();
+
+    // the messages sent during the outage are published once the connection is recovered
+    proxy.disable().await.unwrap();
+    assert!(matches!(
+        tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .unwrap()
+            .unwrap(),
+        ProducerRecoveryEvent::Started { .. }
+    ));
+    let (confirmed, mut confirmations) = tokio::sync::mpsc::unbounded_channel();
+    producer
+        .send(
+            Message::builder().body(b"message".to_vec()).build(),
+            move |status| async move {
+                let _ = confirmed.send(status.map(|status| status.confirmed()));
+            },
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    proxy.enable().await.unwrap();
+
+    let confirmed = tokio::time::timeout(Duration::from_secs(30), confirmations.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(confirmed.unwrap());
+
+    producer.close().await.unwrap();
+    proxy.delete().await.unwrap();
+    delete_stream(&stream).await;
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use rabbitmq_stream_client::{
+    error::ReplayError,
+    test_broker::TestBroker,
+    types::{Message, OffsetSpecification, WireCapture, WireReplay},
+    Environment, EnvironmentBuilder,
+};
+
+async fn publish_and_consume(builder: EnvironmentBuilder, body: &'static str) {
+    let environment = builder.build().await.unwrap();
+    let producer = environment
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    producer
+        .send_with_confirm(Message::builder().body(body).build())
+        .await
+        .unwrap();
+    producer.close().await.unwrap();
+
+    let mut consumer = environment
+        .consumer()
+        .offset(OffsetSpecification::First)
+        .build("orders")
+        .await
+        .unwrap();
+    let delivery = consumer.next().await.unwrap().unwrap();
+    assert_eq!(Some(body.as_bytes()), delivery.message.data());
+    // the credit granted for the chunk goes before the unsubscription in every run
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    consumer.handle().close().await.unwrap();
+}
+
+async fn record(path: &std::path::Path) {
+    let broker = TestBroker::new();
+    broker.create_stream("orders");
+    let capture = WireCapture::create(path).unwrap();
+    let builder = Environment::builder()
+        .test_broker(broker)
+        .wire_capture(capture);
+    publish_and_consume(builder, "message").await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn wire_replay_test() {
+    let path = std::env::temp_dir().join(format!("wire-replay-{}", std::process::id()));
+    record(&path).await;
+
+    let replay = WireReplay::open(&path).unwrap();
+    publish_and_consume(
+        Environment::builder().wire_replay(replay.clone()),
+        "message",
+    )
+    .await;
+    assert_eq!(Ok(()), replay.verify());
+
+    // the same traffic with another body
+    let replay = WireReplay::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let environment = Environment::builder()
+        .wire_replay(replay.clone())
+        .build()
+        .await
+        .unwrap();
+    let producer = environment
+        .producer()
+        .name("producer")
+        .build("orders")
+        .await
+        .unwrap();
+    let sent = tokio::time::timeout(
+        Duration::from_secs(1),
+        producer.send_with_confirm(Message::builder().body("changed").build()),
+    )
+    .await;
+    assert!(!matches!(sent, Ok(Ok(_))));
+    // the producer connection follows the one looking up the leader of the stream
+    assert!(matches!(
+        replay.verify(),
+        Err(ReplayError::UnexpectedFrame { connection: 3, .. })
+    ));
+}